@@ -1,48 +1,336 @@
 //! Cleanup and uninstall functionality (Undeploytix)
 
-use crate::disk::detection::list_block_devices;
+mod uninstall;
+
+use crate::disk::detection::{get_device_info, list_block_devices_cached, probe_existing_data};
+use crate::disk::mount::mounts_under;
+use crate::install::DEFAULT_INSTALL_ROOT;
 use crate::utils::command::CommandRunner;
 use crate::utils::error::{DeploytixError, Result};
 use crate::utils::prompt::{prompt_confirm, prompt_select};
 use std::fs;
-use tracing::info;
+use std::path::Path;
+use std::process::Command;
+use tracing::{info, warn};
+
+/// Assumed sustained write speed of a hard disk, for overwrite estimates.
+const OVERWRITE_MIB_PER_SEC: u64 = 150;
+
+/// How a secure wipe makes the data on a disk unrecoverable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EraseMethod {
+    /// Discard every block (`blkdiscard`); the SSD controller drops the data
+    Discard,
+    /// Overwrite the whole disk once with random data (`shred`)
+    Overwrite,
+    /// Destroy the headers of these LUKS containers; without the key
+    /// material the encrypted data cannot be decrypted
+    LuksHeaders(Vec<String>),
+}
+
+impl EraseMethod {
+    /// Full-disk method suited to the media type.
+    pub fn for_media(is_rotational: bool) -> Self {
+        if is_rotational {
+            Self::Overwrite
+        } else {
+            Self::Discard
+        }
+    }
+
+    /// Rough duration in seconds for a disk of `size_bytes`.
+    pub fn estimate_secs(&self, size_bytes: u64) -> u64 {
+        match self {
+            Self::Overwrite => size_bytes / (OVERWRITE_MIB_PER_SEC * 1024 * 1024),
+            Self::Discard | Self::LuksHeaders(_) => 10,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Discard => "discard all blocks (blkdiscard)".to_string(),
+            Self::Overwrite => "overwrite once with random data (shred)".to_string(),
+            Self::LuksHeaders(paths) => {
+                format!("destroy the LUKS headers on {}", paths.join(", "))
+            }
+        }
+    }
+}
+
+/// Human-readable form of an estimated duration.
+fn format_duration(secs: u64) -> String {
+    match secs {
+        0..=59 => "under a minute".to_string(),
+        60..=3599 => format!("about {} min", secs.div_ceil(60)),
+        _ => format!("about {}h {:02}min", secs / 3600, (secs % 3600) / 60),
+    }
+}
+
+/// Block device nodes on `device`: the disk, its partitions and the dm
+/// mappings stacked on them, as listed by `lsblk`.
+fn device_nodes(device: &str) -> Vec<String> {
+    query(Command::new("lsblk").args(["-lnpo", "NAME", device]))
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Stdout of a read-only query; empty when the tool is missing or fails.
+/// Queries run even in dry-run so it can report what would be released.
+fn query(command: &mut Command) -> String {
+    command
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default()
+}
+
+/// Whether a PV or vdev belongs to this cleanup: a node of the target
+/// device, or, with no device (empty `nodes`), a LUKS mapping Deploytix
+/// opened.
+fn is_target_member(member: &str, nodes: &[String]) -> bool {
+    if nodes.is_empty() {
+        member.starts_with("/dev/mapper/Crypt-")
+    } else {
+        nodes.iter().any(|n| n == member)
+    }
+}
+
+/// Volume groups with a PV among the target members, from
+/// `pvs --noheadings -o pv_name,vg_name` output.
+fn target_volume_groups(pvs: &str, nodes: &[String]) -> Vec<String> {
+    let mut vgs: Vec<String> = Vec::new();
+    for line in pvs.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(pv), Some(vg)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        if is_target_member(pv, nodes) && !vgs.iter().any(|v| v == vg) {
+            vgs.push(vg.to_string());
+        }
+    }
+    vgs
+}
 
-/// Install root path
-const INSTALL_ROOT: &str = "/install";
+/// Device paths in `zpool status -P` output.  Without `-L` a vdev on a
+/// LUKS mapping keeps its `/dev/mapper` name, as `lsblk` lists it, rather
+/// than resolving to `/dev/dm-N`.
+fn zpool_vdevs(status: &str) -> Vec<String> {
+    status
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .filter(|t| t.starts_with("/dev/"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Imported ZFS pools with a vdev among the target members.
+fn target_zfs_pools(nodes: &[String]) -> Vec<String> {
+    let pools = query(Command::new("zpool").args(["list", "-H", "-o", "name"]));
+    pools
+        .lines()
+        .map(str::trim)
+        .filter(|pool| !pool.is_empty())
+        .filter(|pool| {
+            let status = query(Command::new("zpool").args(["status", "-P", pool]));
+            zpool_vdevs(&status)
+                .iter()
+                .any(|v| is_target_member(v, nodes))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Kernel names of the devices in `dmsetup deps -o blkdevname` output,
+/// e.g. `1 dependencies  : (sda2)`.
+fn dmsetup_deps(output: &str) -> Vec<String> {
+    output
+        .split_once(':')
+        .map(|(_, deps)| deps)
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|d| d.trim_matches(|c| c == '(' || c == ')').to_string())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+/// The devices at the bottom of the stack beneath the kernel device
+/// `name`, following `/sys/block/<name>/slaves` (a LUKS mapping over a
+/// dm-integrity mapping over a partition); `name` itself when nothing is
+/// beneath it.
+fn backing_devices(name: &str) -> Vec<String> {
+    let slaves: Vec<String> = fs::read_dir(format!("/sys/block/{}/slaves", name))
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    if slaves.is_empty() {
+        return vec![name.to_string()];
+    }
+    slaves.iter().flat_map(|s| backing_devices(s)).collect()
+}
+
+/// Whether the dm mapping `name` sits on one of the target `nodes`.
+fn mapping_on_target(name: &str, nodes: &[String]) -> bool {
+    if nodes.iter().any(|n| *n == format!("/dev/mapper/{}", name)) {
+        return true;
+    }
+    let deps = query(Command::new("dmsetup").args(["deps", "-o", "blkdevname", name]));
+    dmsetup_deps(&deps)
+        .iter()
+        .flat_map(|d| backing_devices(d))
+        .any(|d| nodes.iter().any(|n| *n == format!("/dev/{}", d)))
+}
+
+/// What a cleanup releases: everything Deploytix set up, or only what
+/// traces back to one device.
+struct Target {
+    /// Nodes of the device (see [`device_nodes`]); empty for everything
+    nodes: Vec<String>,
+    /// ZFS pools with a vdev on the device
+    pools: Vec<String>,
+}
+
+impl Target {
+    fn is_everything(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Whether a mount or swap source (a device path or ZFS dataset) is on
+    /// the target.
+    fn holds(&self, source: &str) -> bool {
+        if self.is_everything() {
+            return true;
+        }
+        let resolve =
+            |path: &str| fs::canonicalize(path).unwrap_or_else(|_| Path::new(path).into());
+        self.nodes
+            .iter()
+            .any(|n| n == source || resolve(n) == resolve(source))
+            || self.pools.iter().any(|p| {
+                source
+                    .strip_prefix(p.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+    }
+}
+
+/// Mount points to release from [`mounts_under`] output: those whose
+/// source is on the target, and whatever is mounted beneath them (such as
+/// a chroot's `/proc`).  Deepest first.
+fn target_mount_points(mounts: Vec<(String, String)>, target: &Target) -> Vec<String> {
+    let tops: Vec<String> = mounts
+        .iter()
+        .filter(|(source, _)| target.holds(source))
+        .map(|(_, mp)| mp.clone())
+        .collect();
+    mounts
+        .into_iter()
+        .map(|(_, mp)| mp)
+        .filter(|mp| tops.iter().any(|top| Path::new(mp).starts_with(top)))
+        .collect()
+}
 
 /// Cleanup utility
 pub struct Cleaner {
     cmd: CommandRunner,
+    secure: bool,
+    install_root: String,
 }
 
 impl Cleaner {
     pub fn new(dry_run: bool) -> Self {
         Self {
-            cmd: CommandRunner::new(dry_run),
+            // shred reports its progress on stderr; show it as it runs
+            cmd: CommandRunner::new(dry_run)
+                .with_output_callback(Box::new(|line| println!("  {}", line))),
+            secure: false,
+            install_root: DEFAULT_INSTALL_ROOT.to_string(),
         }
     }
 
+    /// Unmount what is mounted under `root` instead of
+    /// [`DEFAULT_INSTALL_ROOT`].
+    pub fn with_install_root(mut self, root: &str) -> Self {
+        self.install_root = root.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Make the data unrecoverable before writing the blank partition
+    /// table when wiping (see [`EraseMethod`]).
+    pub fn with_secure_erase(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
     /// Perform cleanup operations
+    ///
+    /// With a `device`, only the mounts, swap, pools, volume groups and
+    /// LUKS mappings on that device are released; without one, everything
+    /// under the install root and every Deploytix mapping, after
+    /// confirmation.  Safe to re-run: every step skips resources that are
+    /// already released.
     pub fn cleanup(&self, device: Option<&str>, wipe: bool) -> Result<()> {
+        self.release(device, wipe, false)
+    }
+
+    /// [`Cleaner::cleanup`], without asking again before wiping when the
+    /// caller has already had the wipe `confirmed`.
+    fn release(&self, device: Option<&str>, wipe: bool, confirmed: bool) -> Result<()> {
         info!(
-            "Starting cleanup (unmount, close LUKS{})",
+            "Starting cleanup (unmount, deactivate LVM/ZFS, close LUKS{})",
             if wipe { ", wipe" } else { "" }
         );
 
-        // Unmount all filesystems
-        self.unmount_all()?;
+        let device = match device {
+            Some(d) => Some(d.to_string()),
+            None if wipe => Some(self.prompt_for_device()?),
+            None => {
+                self.confirm_release_all()?;
+                None
+            }
+        };
+        let nodes = match device.as_deref() {
+            Some(d) => {
+                let nodes = device_nodes(d);
+                if nodes.is_empty() {
+                    return Err(DeploytixError::DeviceNotFound(d.to_string()));
+                }
+                nodes
+            }
+            None => Vec::new(),
+        };
+        let pools = target_zfs_pools(&nodes);
+        let target = Target { nodes, pools };
+
+        // Unmount the target's filesystems
+        self.unmount_all(&target)?;
+
+        // Release pools and VGs before the LUKS containers beneath them
+        self.export_zfs_pools(&target.pools);
+        self.deactivate_volume_groups(&target.nodes);
 
-        // Close any LUKS containers
-        self.close_encrypted_volumes()?;
+        // Close the target's LUKS containers
+        self.close_encrypted_volumes(&target.nodes)?;
 
         // Wipe if requested
-        if wipe {
-            let device = if let Some(d) = device {
-                d.to_string()
+        if let Some(device) = device.filter(|_| wipe) {
+            let method = if self.secure {
+                Some(self.choose_erase_method(&device)?)
             } else {
-                self.prompt_for_device()?
+                None
             };
-
+            if !confirmed {
+                self.confirm_wipe(&device, method.as_ref())?;
+            }
+            if let Some(method) = method {
+                self.secure_erase(&device, &method)?;
+            }
             self.wipe_device(&device)?;
         }
 
@@ -50,45 +338,40 @@ impl Cleaner {
         Ok(())
     }
 
-    /// Unmount all filesystems under install root
-    fn unmount_all(&self) -> Result<()> {
-        info!("Unmounting all filesystems under {}", INSTALL_ROOT);
+    /// Unmount the target's filesystems under the install root
+    fn unmount_all(&self, target: &Target) -> Result<()> {
+        info!("Unmounting filesystems under {}", self.install_root);
+
+        let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+        let mount_points = target_mount_points(mounts_under(&mounts, &self.install_root), target);
 
         // Disable swap devices that were set up for the installation
         // (avoid disabling all host swap with -a)
-        let mounts = fs::read_to_string("/proc/swaps").unwrap_or_default();
-        for line in mounts.lines().skip(1) {
+        let swaps = fs::read_to_string("/proc/swaps").unwrap_or_default();
+        for line in swaps.lines().skip(1) {
             if let Some(device) = line.split_whitespace().next() {
-                if device.starts_with(INSTALL_ROOT) || device.contains("/dev/mapper/Crypt-") {
+                let ours = if target.is_everything() {
+                    Path::new(device).starts_with(&self.install_root)
+                        || device.contains("/dev/mapper/Crypt-")
+                } else {
+                    target.holds(device)
+                        || mount_points
+                            .iter()
+                            .any(|mp| Path::new(device).starts_with(mp))
+                };
+                if ours {
                     let _ = self.cmd.run("swapoff", &[device]);
                 }
             }
         }
 
-        // Get mount points under install root
-        let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
-        let mut mount_points: Vec<&str> = mounts
-            .lines()
-            .filter_map(|line| {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 && parts[1].starts_with(INSTALL_ROOT) {
-                    Some(parts[1])
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        // Sort by depth (deepest first)
-        mount_points.sort_by_key(|b| std::cmp::Reverse(b.matches('/').count()));
-
         // Unmount each
-        for mp in mount_points {
+        for mp in &mount_points {
             info!("Unmounting {}", mp);
             if let Err(e) = self.cmd.run("umount", &[mp]) {
-                tracing::warn!("Failed to unmount {}: {} (trying lazy unmount)", mp, e);
+                warn!("Failed to unmount {}: {} (trying lazy unmount)", mp, e);
                 if let Err(e2) = self.cmd.run("umount", &["-l", mp]) {
-                    tracing::warn!("Lazy unmount of {} also failed: {}", mp, e2);
+                    warn!("Lazy unmount of {} also failed: {}", mp, e2);
                 }
             }
         }
@@ -96,19 +379,41 @@ impl Cleaner {
         Ok(())
     }
 
-    /// Close any open LUKS encrypted volumes
+    /// Export the target's ZFS pools (see [`target_zfs_pools`]).
+    fn export_zfs_pools(&self, pools: &[String]) {
+        for pool in pools {
+            info!("Exporting ZFS pool {}", pool);
+            if let Err(e) = self.cmd.run("zpool", &["export", pool]) {
+                warn!("Failed to export ZFS pool {}: {}", pool, e);
+            }
+        }
+    }
+
+    /// Deactivate volume groups with a PV on the target device or on a
+    /// Deploytix LUKS mapping.
+    fn deactivate_volume_groups(&self, nodes: &[String]) {
+        let pvs = query(Command::new("pvs").args(["--noheadings", "-o", "pv_name,vg_name"]));
+        for vg in target_volume_groups(&pvs, nodes) {
+            info!("Deactivating volume group {}", vg);
+            if let Err(e) = self.cmd.run("vgchange", &["-an", &vg]) {
+                warn!("Failed to deactivate volume group {}: {}", vg, e);
+            }
+        }
+    }
+
+    /// Close open LUKS encrypted volumes
     ///
     /// Dynamically enumerates `/dev/mapper/Crypt-*` and
     /// `/dev/mapper/temporary-cryptsetup-*` entries so that both
     /// canonical names (e.g. `Crypt-Root`) and disambiguated names
     /// (e.g. `Crypt-Root-1`) are closed, as well as any temporary
     /// dm mappings left behind by interrupted `cryptsetup luksFormat`
-    /// operations.
-    fn close_encrypted_volumes(&self) -> Result<()> {
-        info!("Closing any open LUKS encrypted volumes");
+    /// operations.  With target `nodes`, only mappings on them are closed.
+    fn close_encrypted_volumes(&self, nodes: &[String]) -> Result<()> {
+        info!("Closing open LUKS encrypted volumes");
 
         // Kill orphaned cryptsetup processes first (they hold dm mappings open)
-        self.kill_orphaned_cryptsetup();
+        self.kill_orphaned_cryptsetup(nodes);
 
         let mapper_dir = std::path::Path::new("/dev/mapper");
         if let Ok(entries) = fs::read_dir(mapper_dir) {
@@ -123,6 +428,7 @@ impl Cleaner {
                         None
                     }
                 })
+                .filter(|name| nodes.is_empty() || mapping_on_target(name, nodes))
                 .collect();
             names.sort();
             names.reverse();
@@ -130,7 +436,7 @@ impl Cleaner {
             for name in names {
                 info!("Closing {}", name);
                 if let Err(e) = self.cmd.run("cryptsetup", &["close", &name]) {
-                    tracing::warn!("Failed to close LUKS volume {}: {}", name, e);
+                    warn!("Failed to close LUKS volume {}: {}", name, e);
                 }
             }
         }
@@ -140,9 +446,8 @@ impl Cleaner {
 
     /// Kill orphaned `cryptsetup` processes (PPID == 1) that may be holding
     /// dm mappings open (e.g. integrity wipe from an interrupted luksFormat).
-    fn kill_orphaned_cryptsetup(&self) {
-        use tracing::warn;
-
+    /// With target `nodes`, only those working on one of them.
+    fn kill_orphaned_cryptsetup(&self, nodes: &[String]) {
         let Ok(proc_entries) = fs::read_dir("/proc") else {
             return;
         };
@@ -161,6 +466,13 @@ impl Cleaner {
             if !cmdline.starts_with("cryptsetup\0") && !cmdline.starts_with("cryptsetup ") {
                 continue;
             }
+            if !nodes.is_empty()
+                && !cmdline
+                    .split('\0')
+                    .any(|arg| nodes.iter().any(|n| n == arg))
+            {
+                continue;
+            }
 
             // Check if orphaned (PPID == 1)
             let stat_path = format!("/proc/{}/stat", pid);
@@ -191,9 +503,26 @@ impl Cleaner {
         }
     }
 
+    /// Ask before releasing everything when no device was given; other
+    /// installs mounted under the root would be torn down too.
+    fn confirm_release_all(&self) -> Result<()> {
+        if self.cmd.is_dry_run() {
+            return Ok(());
+        }
+        println!(
+            "\n⚠️  No device given: every mount under {} and every Deploytix LUKS mapping, \
+             volume group and ZFS pool will be released, whichever disk it is on.\n",
+            self.install_root
+        );
+        if !prompt_confirm("Release everything?", false)? {
+            return Err(DeploytixError::UserCancelled);
+        }
+        Ok(())
+    }
+
     /// Prompt user for device to wipe
     fn prompt_for_device(&self) -> Result<String> {
-        let devices = list_block_devices(true)?;
+        let devices = list_block_devices_cached(true)?;
 
         if devices.is_empty() {
             return Err(DeploytixError::ConfigError(
@@ -217,19 +546,106 @@ impl Cleaner {
         Ok(devices[idx].path.clone())
     }
 
-    /// Wipe partition table from device
-    fn wipe_device(&self, device: &str) -> Result<()> {
-        // Confirm
+    /// Pick how to securely erase `device`: by media type, or, when it
+    /// holds LUKS containers, optionally just their headers.
+    fn choose_erase_method(&self, device: &str) -> Result<EraseMethod> {
+        let info = get_device_info(device)?;
+        let full = EraseMethod::for_media(info.is_rotational);
+
+        let nodes = probe_existing_data(device)?;
+        let luks: Vec<String> = nodes
+            .iter()
+            .filter(|n| n.fstype.as_deref() == Some("crypto_LUKS"))
+            .map(|n| n.path.clone())
+            .collect();
+        if luks.is_empty() {
+            return Ok(full);
+        }
+
+        // Filesystems outside the containers stay readable after a header wipe
+        let plain = nodes
+            .iter()
+            .filter(|n| n.path != device && !n.path.starts_with("/dev/mapper/"))
+            .filter(|n| n.fstype.as_deref().is_some_and(|t| t != "crypto_LUKS"))
+            .count();
+        let items = [
+            format!(
+                "Destroy LUKS headers only ({}; {} other partition(s) stay readable)",
+                format_duration(EraseMethod::LuksHeaders(Vec::new()).estimate_secs(0)),
+                plain
+            ),
+            format!(
+                "Erase the whole disk: {} ({})",
+                full.describe(),
+                format_duration(full.estimate_secs(info.size_bytes))
+            ),
+        ];
+        let idx = prompt_select(
+            "The disk holds LUKS containers. How should it be erased?",
+            &items,
+            0,
+        )?;
+        Ok(if idx == 0 {
+            EraseMethod::LuksHeaders(luks)
+        } else {
+            full
+        })
+    }
+
+    /// Show what is about to happen to `device` and ask for confirmation.
+    fn confirm_wipe(&self, device: &str, method: Option<&EraseMethod>) -> Result<()> {
         let warning = format!(
             "This will WIPE the partition table on {}. This cannot be undone!",
             device
         );
-        println!("\n⚠️  WARNING: {}\n", warning);
+        println!("\n⚠️  WARNING: {}", warning);
+        if let Some(method) = method {
+            let size = get_device_info(device).map(|d| d.size_bytes).unwrap_or(0);
+            println!(
+                "   Secure erase: {} first; estimated time {}.",
+                method.describe(),
+                format_duration(method.estimate_secs(size))
+            );
+        }
+        println!();
 
         if !prompt_confirm("Are you sure you want to continue?", false)? {
             return Err(DeploytixError::UserCancelled);
         }
+        Ok(())
+    }
+
+    /// Make the data on `device` unrecoverable.
+    fn secure_erase(&self, device: &str, method: &EraseMethod) -> Result<()> {
+        info!("Secure erase of {}: {}", device, method.describe());
 
+        if self.cmd.is_dry_run() {
+            println!("  [dry-run] Would {} on {}", method.describe(), device);
+            return Ok(());
+        }
+
+        match method {
+            EraseMethod::Discard => {
+                self.cmd.run("blkdiscard", &["-f", device])?;
+            }
+            EraseMethod::Overwrite => {
+                self.cmd.run_captured("shred", &["-v", "-n", "1", device])?;
+            }
+            EraseMethod::LuksHeaders(paths) => {
+                for path in paths {
+                    // erase drops every keyslot; wipefs clears the header magic
+                    self.cmd.run("cryptsetup", &["erase", "-q", path])?;
+                    self.cmd.run("wipefs", &["-a", path])?;
+                }
+            }
+        }
+
+        info!("Secure erase of {} complete", device);
+        Ok(())
+    }
+
+    /// Wipe partition table from device
+    fn wipe_device(&self, device: &str) -> Result<()> {
         info!(
             "Wiping partition table and filesystem signatures on {}",
             device
@@ -246,12 +662,12 @@ impl Cleaner {
         // Create blank GPT
         // Using sfdisk to write empty GPT
         let script = "label: gpt\n";
-        let script_path = "/tmp/deploytix_wipe";
-        fs::write(script_path, script)?;
+        let script_path = crate::resources::runtime_path("wipe_script");
+        fs::write(&script_path, script)?;
 
         let result = std::process::Command::new("sfdisk")
             .arg(device)
-            .stdin(fs::File::open(script_path)?)
+            .stdin(fs::File::open(&script_path)?)
             .output();
 
         let _ = fs::remove_file(script_path);
@@ -277,3 +693,99 @@ impl Cleaner {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volume_groups_on_target_are_found() {
+        let pvs = "  /dev/sda3             vg0\n  /dev/mapper/Crypt-LVM vg1\n  /dev/sdb1             data\n  /dev/sda4             vg0\n  /dev/sdc1\n";
+        let nodes = vec![
+            "/dev/sda".to_string(),
+            "/dev/sda3".to_string(),
+            "/dev/sda4".to_string(),
+        ];
+        assert_eq!(target_volume_groups(pvs, &nodes), vec!["vg0"]);
+        assert_eq!(target_volume_groups(pvs, &[]), vec!["vg1"]);
+    }
+
+    #[test]
+    fn zpool_status_vdevs() {
+        let status = "  pool: rpool\n state: ONLINE\nconfig:\n\n\tNAME         STATE     READ WRITE CKSUM\n\trpool        ONLINE       0     0     0\n\t  /dev/sda3  ONLINE       0     0     0\n\nerrors: No known data errors\n";
+        assert_eq!(zpool_vdevs(status), vec!["/dev/sda3"]);
+    }
+
+    #[test]
+    fn mapper_backed_vdev_is_a_target_member() {
+        let status = "  pool: rpool\n state: ONLINE\nconfig:\n\n\tNAME                      STATE     READ WRITE CKSUM\n\trpool                     ONLINE       0     0     0\n\t  /dev/mapper/Crypt-ROOT  ONLINE       0     0     0\n\nerrors: No known data errors\n";
+        let vdevs = zpool_vdevs(status);
+        assert_eq!(vdevs, vec!["/dev/mapper/Crypt-ROOT"]);
+        let nodes = vec![
+            "/dev/sda".to_string(),
+            "/dev/sda3".to_string(),
+            "/dev/mapper/Crypt-ROOT".to_string(),
+        ];
+        assert!(is_target_member(&vdevs[0], &nodes));
+        assert!(is_target_member(&vdevs[0], &[]));
+    }
+
+    #[test]
+    fn dmsetup_deps_are_parsed() {
+        assert_eq!(dmsetup_deps(" 1 dependencies\t: (sda2)\n"), vec!["sda2"]);
+        assert_eq!(
+            dmsetup_deps(" 2 dependencies  : (dm-3) (sdb1)\n"),
+            vec!["dm-3", "sdb1"]
+        );
+        assert!(dmsetup_deps("").is_empty());
+    }
+
+    #[test]
+    fn only_mounts_on_the_device_are_released() {
+        let mounts = "\
+/dev/sdb2 /install ext4 rw 0 0
+/dev/sda2 /install/other ext4 rw 0 0
+proc /install/other/proc proc rw 0 0
+/dev/sdb1 /install/boot vfat rw 0 0
+rpool/ROOT/default /install/zfs zfs rw 0 0
+proc /install/proc proc rw 0 0
+";
+        let target = Target {
+            nodes: vec!["/dev/sda".to_string(), "/dev/sda2".to_string()],
+            pools: vec!["rpool".to_string()],
+        };
+        let released = target_mount_points(mounts_under(mounts, "/install"), &target);
+        assert_eq!(
+            released,
+            vec!["/install/other/proc", "/install/other", "/install/zfs"]
+        );
+
+        let everything = Target {
+            nodes: Vec::new(),
+            pools: Vec::new(),
+        };
+        assert_eq!(
+            target_mount_points(mounts_under(mounts, "/install"), &everything).len(),
+            6
+        );
+    }
+
+    #[test]
+    fn erase_method_follows_media() {
+        assert_eq!(EraseMethod::for_media(true), EraseMethod::Overwrite);
+        assert_eq!(EraseMethod::for_media(false), EraseMethod::Discard);
+    }
+
+    #[test]
+    fn overwrite_estimate_scales_with_size() {
+        let tib = 1024 * 1024 * 1024 * 1024;
+        let secs = EraseMethod::Overwrite.estimate_secs(tib);
+        assert_eq!(secs, 1024 * 1024 / OVERWRITE_MIB_PER_SEC);
+        assert_eq!(format_duration(secs), "about 1h 56min");
+        assert_eq!(
+            format_duration(EraseMethod::Discard.estimate_secs(tib)),
+            "under a minute"
+        );
+        assert_eq!(format_duration(90), "about 2 min");
+    }
+}
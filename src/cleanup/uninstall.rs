@@ -0,0 +1,181 @@
+//! Uninstall (Undeploytix)
+//!
+//! Reverses what a deploy created on a disk: the NVRAM boot entries that
+//! point at its ESP, then the mounts, volume groups and LUKS containers on
+//! it, and optionally the partition table.
+
+use super::{query, Cleaner};
+use crate::configure::bootloader::{
+    efivars_usable, BOOT_ENTRY_LABEL, MIRROR_ENTRY_LABEL, SECUREBOOT_ENTRY_LABEL,
+};
+use crate::utils::error::{DeploytixError, Result};
+use crate::utils::prompt::prompt_confirm;
+use std::process::Command;
+use tracing::{info, warn};
+
+/// An NVRAM boot entry from `efibootmgr -v`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BootEntry {
+    /// Boot number in hex, as `efibootmgr -b` takes it
+    number: String,
+    label: String,
+    /// GPT partition GUID from the entry's `HD(...)` device path, lowercase
+    partuuid: Option<String>,
+}
+
+/// Boot entries in `efibootmgr -v` output, whose lines look like
+/// `Boot0003* Artix Linux\tHD(1,GPT,<guid>,0x800,0x100000)/File(...)`
+/// (no `*` for inactive entries).
+fn parse_boot_entries(output: &str) -> Vec<BootEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("Boot")?;
+            let number = rest.get(..4)?;
+            if !number.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let rest = rest[4..]
+                .strip_prefix('*')
+                .unwrap_or(&rest[4..])
+                .trim_start();
+            let (label, path) = match rest.find('\t').or_else(|| rest.find("HD(")) {
+                Some(i) => rest.split_at(i),
+                None => (rest, ""),
+            };
+            let partuuid = path
+                .split_once("HD(")
+                .and_then(|(_, hd)| hd.split(')').next())
+                .and_then(|hd| {
+                    let fields: Vec<&str> = hd.split(',').collect();
+                    (fields.len() >= 3 && fields[1] == "GPT").then(|| fields[2].to_lowercase())
+                });
+            Some(BootEntry {
+                number: number.to_string(),
+                label: label.trim().to_string(),
+                partuuid,
+            })
+        })
+        .collect()
+}
+
+/// The entries Deploytix created (by label) whose ESP is one of the
+/// partitions `partuuids`.
+fn deploytix_entries(entries: Vec<BootEntry>, partuuids: &[String]) -> Vec<BootEntry> {
+    let labels = [BOOT_ENTRY_LABEL, SECUREBOOT_ENTRY_LABEL, MIRROR_ENTRY_LABEL];
+    entries
+        .into_iter()
+        .filter(|e| labels.contains(&e.label.as_str()))
+        .filter(|e| {
+            e.partuuid
+                .as_ref()
+                .is_some_and(|uuid| partuuids.iter().any(|p| p == uuid))
+        })
+        .collect()
+}
+
+impl Cleaner {
+    /// Remove the Deploytix install on `device`: delete its boot entries,
+    /// release what is mounted and opened on it (see [`Cleaner::cleanup`])
+    /// and wipe it when `wipe` is set.
+    pub fn uninstall(&self, device: &str, wipe: bool) -> Result<()> {
+        info!("Uninstalling Deploytix from {}", device);
+
+        let entries = self.boot_entries_on(device);
+        if entries.is_empty() {
+            info!("No Deploytix boot entries point at {}", device);
+        } else {
+            println!("\nBoot entries to remove:");
+            for entry in &entries {
+                println!("  Boot{}  {}", entry.number, entry.label);
+            }
+        }
+        if wipe {
+            println!(
+                "\n⚠️  WARNING: This will WIPE the partition table on {}. This cannot be undone!",
+                device
+            );
+        }
+        println!();
+
+        // The one confirmation covers the wipe too; cleanup does not ask again
+        if !self.cmd.is_dry_run()
+            && !prompt_confirm(&format!("Uninstall Deploytix from {}?", device), false)?
+        {
+            return Err(DeploytixError::UserCancelled);
+        }
+
+        for entry in &entries {
+            info!("Removing boot entry Boot{} ({})", entry.number, entry.label);
+            if let Err(e) = self.cmd.run("efibootmgr", &["-b", &entry.number, "-B"]) {
+                warn!("Failed to remove boot entry Boot{}: {}", entry.number, e);
+            }
+        }
+
+        self.release(Some(device), wipe, true)
+    }
+
+    /// Deploytix boot entries whose ESP is a partition of `device`; none
+    /// when this host has no usable EFI variables.
+    fn boot_entries_on(&self, device: &str) -> Vec<BootEntry> {
+        if !efivars_usable() {
+            info!("EFI variables are not available; leaving NVRAM boot entries alone");
+            return Vec::new();
+        }
+        let partuuids: Vec<String> =
+            query(Command::new("lsblk").args(["-lnpo", "PARTUUID", device]))
+                .lines()
+                .map(|l| l.trim().to_lowercase())
+                .filter(|l| !l.is_empty())
+                .collect();
+        let entries = parse_boot_entries(&query(Command::new("efibootmgr").arg("-v")));
+        deploytix_entries(entries, &partuuids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EFIBOOTMGR: &str = "\
+BootCurrent: 0001
+Timeout: 1 seconds
+BootOrder: 0003,0001,0000,0004
+Boot0000* Windows Boot Manager\tHD(1,GPT,0d5c1f2e-aaaa-4bbb-8ccc-000000000001,0x800,0x32000)/File(\\EFI\\Microsoft\\Boot\\bootmgfw.efi)
+Boot0001* UEFI: Built-in EFI Shell\tVenMedia(5023b95c-db26-429b-a648-bd47664c8012)
+Boot0003* Artix Linux\tHD(1,GPT,5E2B3C4D-1111-4222-8333-444455556666,0x800,0x100000)/File(\\EFI\\BOOT\\BOOTX64.EFI)
+Boot0004  Artix Linux\tHD(1,GPT,9f9f9f9f-1111-4222-8333-444455556666,0x800,0x100000)/File(\\EFI\\BOOT\\BOOTX64.EFI)
+Boot0005* Artix-SB\tHD(1,GPT,5e2b3c4d-1111-4222-8333-444455556666,0x800,0x100000)/File(\\EFI\\BOOT\\BOOTX64.EFI)
+";
+
+    #[test]
+    fn boot_entries_are_parsed() {
+        let entries = parse_boot_entries(EFIBOOTMGR);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| e.number.as_str())
+                .collect::<Vec<_>>(),
+            vec!["0000", "0001", "0003", "0004", "0005"]
+        );
+        assert_eq!(entries[0].label, "Windows Boot Manager");
+        assert_eq!(entries[1].partuuid, None);
+        assert_eq!(
+            entries[2].partuuid.as_deref(),
+            Some("5e2b3c4d-1111-4222-8333-444455556666")
+        );
+    }
+
+    #[test]
+    fn only_deploytix_entries_on_the_disk_are_selected() {
+        let partuuids = vec![
+            "0d5c1f2e-aaaa-4bbb-8ccc-000000000001".to_string(),
+            "5e2b3c4d-1111-4222-8333-444455556666".to_string(),
+        ];
+        let found = deploytix_entries(parse_boot_entries(EFIBOOTMGR), &partuuids);
+        assert_eq!(
+            found.iter().map(|e| e.number.as_str()).collect::<Vec<_>>(),
+            vec!["0003", "0005"]
+        );
+    }
+}
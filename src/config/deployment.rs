@@ -1,9 +1,11 @@
 //! Deployment configuration structure
 
-use crate::disk::detection::list_block_devices;
+use crate::disk::detection::{get_device_info, list_block_devices_cached, smart_health};
+use crate::disk::layouts::minimal_layout_mib;
 use crate::utils::error::{DeploytixError, Result};
 use crate::utils::prompt::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::os::unix::fs::FileTypeExt;
 use std::path::Path;
 
@@ -18,6 +20,15 @@ pub struct DeploymentConfig {
     /// Optional package collections (AUR helper, Wine, Gaming, GPU drivers)
     #[serde(default)]
     pub packages: PackagesConfig,
+    /// mkfs tuning for the data filesystem
+    #[serde(default, skip_serializing_if = "FilesystemOptions::is_empty")]
+    pub filesystem_options: FilesystemOptions,
+    /// ZFS data pool settings (used when `disk.filesystem = "zfs"`)
+    #[serde(default)]
+    pub zfs: ZfsConfig,
+    /// sudo policy for the wheel group
+    #[serde(default)]
+    pub sudo: SudoConfig,
 }
 
 /// One user-defined data partition.
@@ -38,6 +49,40 @@ pub struct CustomPartitionEntry {
     /// Per-partition encryption override. Inherits `disk.encryption` when None.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub encryption: Option<bool>,
+
+    /// Per-partition filesystem override. Inherits `disk.filesystem` when None.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filesystem: Option<Filesystem>,
+
+    /// fstab mount options for this partition (e.g. "nodev,nosuid,noexec"),
+    /// replacing the filesystem defaults.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_options: Option<String>,
+}
+
+/// One LVM thin volume, overriding the volumes derived from the data
+/// partitions when `use_lvm_thin` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThinVolumeSpec {
+    /// Logical volume name (e.g. "root", "home")
+    pub name: String,
+    /// Virtual size in lvcreate syntax (e.g. "50G", "512M")
+    pub virtual_size: String,
+    /// Mount point (e.g. "/", "/home")
+    pub mount_point: String,
+}
+
+/// One btrfs subvolume, replacing the default `@`, `@home`, `@usr`, `@var`,
+/// `@log` scheme of a btrfs layout with a single data partition.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubvolumeSpec {
+    /// Subvolume name (e.g. "@", "@pkg")
+    pub name: String,
+    /// Mount point (e.g. "/", "/var/cache/pacman/pkg")
+    pub mount_point: String,
+    /// Mount options, replacing the btrfs defaults for this subvolume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_options: Option<String>,
 }
 
 impl CustomPartitionEntry {
@@ -61,12 +106,31 @@ impl CustomPartitionEntry {
     pub fn is_encrypted(&self, global_encryption: bool) -> bool {
         self.encryption.unwrap_or(global_encryption)
     }
+
+    /// Filesystem for this partition, falling back to `disk.filesystem`.
+    pub fn effective_filesystem<'a>(&'a self, global: &'a Filesystem) -> &'a Filesystem {
+        self.filesystem.as_ref().unwrap_or(global)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiskConfig {
     /// Target device path (e.g., /dev/sda)
     pub device: String,
+    /// Second disk holding a RAID1 mirror of `device`.  It gets the same
+    /// partition table; btrfs data and /boot span both disks and LVM thin
+    /// pools are mirrored.  Its contents are erased too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_device: Option<String>,
+    /// Keep the partitions already on `device` and create the new ones in
+    /// its largest free region instead of writing a fresh GPT.
+    #[serde(default)]
+    pub preserve_partitions: bool,
+    /// Existing EFI System Partition on `device` (e.g. /dev/nvme0n1p1) to
+    /// share with another system; implies `preserve_partitions`.  GRUB is
+    /// added to the ESP as `EFI/Artix` without formatting it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub existing_esp: Option<String>,
     /// Filesystem type for data partitions
     #[serde(default)]
     pub filesystem: Filesystem,
@@ -92,15 +156,60 @@ pub struct DiskConfig {
     /// Path to keyfile (None = password prompt)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub keyfile_path: Option<String>,
+    /// Directory of the generated unlock keyfiles in the installed system
+    #[serde(default = "default_keyfile_dir")]
+    pub keyfile_dir: String,
+    /// Size of each generated keyfile (512-8192 bytes, default 512)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyfile_size_bytes: Option<usize>,
     /// Enable dm-integrity for per-sector integrity protection alongside encryption
     /// Uses HMAC-SHA256 to detect silent data corruption on encrypted volumes.
     /// Only supported with LUKS2; boot partition (LUKS1) is excluded from integrity.
     /// Not compatible with TRIM/discard.
     #[serde(default)]
     pub integrity: bool,
+    /// The user chose dm-integrity over TRIM/discard on a flash (SSD/NVMe)
+    /// target.  Silences the integrity-on-SSD warning.
+    #[serde(default)]
+    pub integrity_trim_acknowledged: bool,
+    /// Skip the initial dm-integrity wipe (`--integrity-no-wipe`).  The
+    /// format no longer writes the whole device, but until a sector is
+    /// first written, reading it fails its integrity check with an I/O
+    /// error (`dmesg` noise, and tools that scan the raw volume fail).
+    #[serde(default)]
+    pub integrity_no_wipe: bool,
+    /// Minutes before a stuck `cryptsetup luksFormat` is killed.  Unset
+    /// uses 10 minutes, or with integrity a limit scaled to the partition
+    /// size; 0 waits indefinitely.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub luks_format_timeout_mins: Option<u64>,
+    /// Run the mkfs/mkswap of independent partitions concurrently
+    #[serde(default)]
+    pub parallel_format: bool,
+    /// Cap on concurrent format jobs with `parallel_format` (default: CPU count)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format_jobs: Option<usize>,
     /// Enable keyfile-based automatic unlocking (default: true when encryption enabled)
     #[serde(default = "default_true")]
     pub keyfile_enabled: bool,
+    /// Enroll a FIDO2 security key into the LUKS2 data containers and
+    /// unlock with it at boot (the passphrase stays as a fallback)
+    #[serde(default)]
+    pub fido2_unlock: bool,
+    /// Mount an `@snapshots` subvolume at `/.snapshots`, configure snapper
+    /// and take a baseline snapshot after the install (btrfs only)
+    #[serde(default)]
+    pub btrfs_snapshots: bool,
+    /// Independent recovery passphrase added to every LUKS container:
+    /// `true` prompts for it at install time, a string supplies it.
+    /// Never written back out in plaintext.
+    #[serde(
+        default,
+        skip_serializing_if = "RecoveryPassphrase::is_disabled",
+        serialize_with = "serialize_recovery_passphrase",
+        deserialize_with = "deserialize_recovery_passphrase"
+    )]
+    pub recovery_passphrase: RecoveryPassphrase,
     /// Use btrfs subvolumes within partitions.
     /// Automatically set to true whenever `filesystem == Btrfs`; no manual
     /// opt-in is required.  Kept as a serialisable field for backwards
@@ -121,6 +230,23 @@ pub struct DiskConfig {
     /// Thin pool size as percentage of VG (default: 95%)
     #[serde(default = "default_thin_pool_percent")]
     pub lvm_thin_pool_percent: u8,
+    /// Pool fullness (percent, 50–100) at which dmeventd autoextends the
+    /// thin pool.  Unset uses 80; 100 disables autoextend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thin_autoextend_threshold: Option<u8>,
+    /// Thin volumes to create in the pool.  Unset derives one volume per
+    /// data partition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lvm_thin_volumes: Option<Vec<ThinVolumeSpec>>,
+    /// Where the EFI System Partition is mounted on the installed system.
+    /// Both bootloaders accept either: GRUB is always given an explicit
+    /// `--efi-directory` and rEFInd is installed by device.
+    #[serde(default)]
+    pub esp_mountpoint: EspMount,
+    /// Boot firmware of the target machine.  Unset follows the running
+    /// system (UEFI when `/sys/firmware/efi` exists, BIOS otherwise).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<Firmware>,
 
     // Swap configuration
     /// Swap configuration type
@@ -132,37 +258,267 @@ pub struct DiskConfig {
     /// ZRAM compression algorithm (default: "zstd")
     #[serde(default = "default_zram_algorithm")]
     pub zram_algorithm: String,
+    /// Number of ZRAM swap devices sharing the swap size (default: 1).
+    /// More devices spread compression across CPUs.
+    #[serde(default = "default_zram_devices")]
+    pub zram_devices: u8,
+    /// `vm.swappiness` (0–200) written to a sysctl drop-in.  Unset keeps
+    /// the kernel default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vm_swappiness: Option<u8>,
+    /// Size in MiB of a zram-backed `/tmp` (0 = disabled)
+    #[serde(default)]
+    pub zram_tmp_mib: u64,
+    /// Size in MiB of a zram-backed `/var/log` (0 = disabled).  Logs are
+    /// synced back to disk periodically and on shutdown.
+    #[serde(default)]
+    pub zram_log_mib: u64,
+    /// Minutes between syncs of the zram `/var/log` to disk (default: 30)
+    #[serde(default = "default_zram_log_sync_minutes")]
+    pub zram_log_sync_minutes: u32,
+
+    // Btrfs mount options
+    /// Btrfs compression (`zstd`, `zstd:3`, `zlib:6`, `lzo`, `none`).
+    /// Unset keeps the default `zstd`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub btrfs_compression: Option<String>,
+    /// Base btrfs mount options, replacing the default `defaults,noatime`.
+    /// Compression is controlled by `btrfs_compression`, not here.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub btrfs_mount_options: Option<String>,
+    /// Add `ssd,discard=async` to btrfs mounts.  Unset follows the target
+    /// disk: enabled for SSDs without dm-integrity, disabled for rotational
+    /// disks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub btrfs_ssd: Option<bool>,
+    /// Add `discard` to ext4, xfs and f2fs fstab entries.  Unset follows
+    /// the target disk: enabled for SSDs without dm-integrity, disabled
+    /// for rotational disks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discard: Option<bool>,
+    /// Create btrfs with mixed data/metadata block groups.  Unset enables
+    /// it automatically for volumes below 16 GiB.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub btrfs_mixed: Option<bool>,
+    /// Subvolumes of a single-partition btrfs layout.  Unset uses `@` (/),
+    /// `@home`, `@usr`, `@var` and `@log`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub btrfs_subvolumes: Option<Vec<SubvolumeSpec>>,
+
+    /// f2fs compression for data volumes (`zstd`, `zstd:6`, `lz4`, `lzo`,
+    /// `lzo-rle`, `none`).  Set, it creates f2fs with the `compression`
+    /// feature and mounts with `compress_algorithm=<algo>` and
+    /// `compress_extension=*`.  Only valid with `filesystem = "f2fs"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub f2fs_compression: Option<String>,
+
+    /// Filesystem label overrides keyed by partition name (`EFI`, `BOOT`,
+    /// `SWAP`, `ROOT`, `HOME`, ...).  Unset partitions are labelled with
+    /// their name.  GPT partition names are not affected.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
 
     /// User-defined data partitions (e.g. ROOT, HOME, USR, VAR).
-    /// EFI + Boot are always auto-prepended; Swap is prepended when
+    /// EFI (or BIOS boot) + Boot are always auto-prepended; Swap is prepended when
     /// `swap_type == Partition`.
     #[serde(default = "default_partitions")]
     pub partitions: Vec<CustomPartitionEntry>,
 }
 
+impl DiskConfig {
+    /// Effective boot firmware, auto-detecting when `firmware` is unset.
+    pub fn firmware(&self) -> Firmware {
+        self.firmware.unwrap_or_else(Firmware::detect)
+    }
+
+    /// Whether the partitions already on the disk are kept (the install
+    /// goes into free space) rather than replaced by a fresh GPT.
+    pub fn preserves_partitions(&self) -> bool {
+        self.preserve_partitions || self.existing_esp.is_some()
+    }
+
+    /// Whether encryption is done with LUKS containers.  ZFS data pools
+    /// use native encryption instead.
+    pub fn uses_luks(&self) -> bool {
+        self.encryption && self.filesystem != Filesystem::Zfs
+    }
+
+    /// Every filesystem formatted on the disk: the data filesystem, the
+    /// /boot filesystem and the per-partition overrides, without repeats.
+    pub fn filesystems_in_use(&self) -> Vec<Filesystem> {
+        let mut filesystems = vec![self.filesystem.clone()];
+        let overrides = self.partitions.iter().filter_map(|p| p.filesystem.as_ref());
+        for fs in std::iter::once(&self.boot_filesystem).chain(overrides) {
+            if !filesystems.contains(fs) {
+                filesystems.push(fs.clone());
+            }
+        }
+        filesystems
+    }
+
+    /// Whether the snapper baseline setup applies: btrfs subvolumes
+    /// outside LVM thin volumes.
+    pub fn uses_btrfs_snapshots(&self) -> bool {
+        self.btrfs_snapshots && self.filesystem == Filesystem::Btrfs && !self.use_lvm_thin
+    }
+
+    /// Number of partitions formatted at once: 1 unless `parallel_format`
+    /// is set, then `format_jobs` or the CPU count.
+    pub fn format_parallelism(&self) -> usize {
+        if !self.parallel_format {
+            return 1;
+        }
+        self.format_jobs.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+    }
+
+    /// Size of each generated keyfile in bytes.
+    pub fn keyfile_size(&self) -> usize {
+        self.keyfile_size_bytes
+            .unwrap_or(crate::configure::keyfiles::DEFAULT_KEYFILE_SIZE)
+    }
+
+    /// Whether the ZFS data pool is created with native encryption.
+    pub fn uses_zfs_encryption(&self) -> bool {
+        self.encryption && self.filesystem == Filesystem::Zfs
+    }
+
+    /// Filesystem label for a partition, honouring `labels` overrides
+    /// (matched case-insensitively against the partition name).
+    pub fn fs_label<'a>(&'a self, name: &'a str) -> &'a str {
+        self.labels
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+            .unwrap_or(name)
+    }
+
+    /// Check label overrides: every key names a partition, overridden
+    /// labels are shell-safe and fit the owning filesystem's length limit,
+    /// and the resulting labels are unique.
+    pub fn validate_labels(&self) -> std::result::Result<(), String> {
+        use crate::disk::formatting::{fs_label_max, FAT32_LABEL_MAX, SWAP_LABEL_MAX};
+
+        // (partition name, max label length)
+        let mut parts: Vec<(String, usize)> = vec![
+            ("EFI".to_string(), FAT32_LABEL_MAX),
+            ("BOOT".to_string(), fs_label_max(&self.boot_filesystem)),
+        ];
+        if self.swap_type == SwapType::Partition {
+            parts.push(("SWAP".to_string(), SWAP_LABEL_MAX));
+        }
+        for p in &self.partitions {
+            parts.push((
+                p.effective_label(),
+                fs_label_max(p.effective_filesystem(&self.filesystem)),
+            ));
+        }
+
+        for key in self.labels.keys() {
+            if !parts.iter().any(|(name, _)| name.eq_ignore_ascii_case(key)) {
+                return Err(format!("labels: no partition named '{}'", key));
+            }
+        }
+
+        let mut seen: Vec<&str> = Vec::new();
+        for (name, max) in &parts {
+            let label = self.fs_label(name);
+            if label != name.as_str()
+                && (label.is_empty()
+                    || !label
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            {
+                return Err(format!(
+                    "Invalid label '{}' for {}: use letters, digits, '-', '_' or '.'",
+                    label, name
+                ));
+            }
+            if label != name.as_str() && label.len() > *max {
+                return Err(format!(
+                    "Label '{}' for {} exceeds {} characters",
+                    label, name, max
+                ));
+            }
+            if seen.iter().any(|l| l.eq_ignore_ascii_case(label)) {
+                return Err(format!("Duplicate filesystem label '{}'", label));
+            }
+            seen.push(label);
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemConfig {
     /// Init system
     #[serde(default)]
     pub init: InitSystem,
+    /// Kernel package (zen, lts, stable, hardened)
+    #[serde(default)]
+    pub kernel: KernelChoice,
     /// Bootloader
     #[serde(default)]
     pub bootloader: Bootloader,
-    /// Timezone (e.g., "America/New_York")
+    /// GRUB menu timeout in seconds (default 5).  0 boots immediately with
+    /// the menu hidden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grub_timeout: Option<u32>,
+    /// GRUB default entry: an index, a menu entry title or `saved`
+    /// (default 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grub_default: Option<String>,
+    /// `GRUB_DISTRIBUTOR`, the name of the GRUB menu entries (default
+    /// "Artix")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grub_distributor: Option<String>,
+    /// Install os-prober and let grub-mkconfig add menu entries for other
+    /// operating systems (Windows, other Linux installs)
+    #[serde(default)]
+    pub grub_os_prober: bool,
+    /// Timezone (e.g., "America/New_York"), or "auto" to detect it at
+    /// install time via GeoIP
     #[serde(default = "default_timezone")]
     pub timezone: String,
-    /// Locale (e.g., "en_US.UTF-8")
-    #[serde(default = "default_locale")]
-    pub locale: String,
+    /// Locales to generate (e.g., ["en_US.UTF-8", "de_DE.UTF-8"]); the
+    /// first one is `LANG`.  A single string is accepted too.
+    #[serde(
+        default = "default_locales",
+        alias = "locale",
+        deserialize_with = "deserialize_locales"
+    )]
+    pub locales: Vec<String>,
+    /// `LC_*` overrides for locale.conf (e.g., LC_TIME = "de_DE.UTF-8")
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub locale_overrides: HashMap<String, String>,
     /// Keyboard layout
     #[serde(default = "default_keymap")]
     pub keymap: String,
+    /// Console font (e.g. "ter-132n" for HiDPI TTYs).  Installs
+    /// terminus-font; unset keeps the kernel's built-in font.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub console_font: Option<String>,
     /// Hostname
     #[serde(default = "default_hostname")]
     pub hostname: String,
     /// Enable hibernation support
     #[serde(default)]
     pub hibernation: bool,
+    /// Mount `/tmp` as tmpfs
+    #[serde(default)]
+    pub tmp_tmpfs: bool,
+    /// tmpfs `/tmp` size (e.g. "4G", "25%").  Unset uses the kernel
+    /// default of half of RAM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_tmpfs_size: Option<String>,
+    /// Harden the generated fstab: nodev/nosuid/noexec where safe and,
+    /// without a desktop, a hidepid /proc (see
+    /// `install::fstab::HARDENED_MOUNT_OPTIONS`)
+    #[serde(default)]
+    pub hardened: bool,
 
     // SecureBoot options
     /// Enable SecureBoot signing
@@ -174,6 +530,93 @@ pub struct SystemConfig {
     /// Path to existing keys directory (for ManualKeys method)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub secureboot_keys_path: Option<String>,
+
+    /// Kernel modules to blacklist via /etc/modprobe.d.  GPU/KMS modules
+    /// are additionally blocked with `modprobe.blacklist=` on the kernel
+    /// command line.  `nouveau` is added automatically for NVIDIA drivers
+    /// unless it is listed here or in `load_modules`.
+    #[serde(default)]
+    pub blacklist_modules: Vec<String>,
+
+    /// Kernel modules to load at boot via /etc/modules-load.d.  VFIO
+    /// modules are also added to the initramfs, ahead of any GPU driver.
+    #[serde(default)]
+    pub load_modules: Vec<String>,
+
+    /// Extra kernel command-line parameters (e.g. `mitigations=off`),
+    /// appended after the parameters Deploytix manages.  Parameters that
+    /// would override managed ones such as `root=` are ignored.
+    #[serde(default)]
+    pub kernel_params: Vec<String>,
+
+    /// Public keys written to `/root/.ssh/authorized_keys` for first-boot
+    /// remote access.  Non-empty installs and enables sshd with key-only
+    /// root login (`PermitRootLogin prohibit-password`).
+    #[serde(default)]
+    pub root_authorized_keys: Vec<String>,
+
+    /// Further services to enable (e.g. "cronie", "chronyd"), after the
+    /// extra packages are installed.  Their packages are not installed
+    /// automatically; list them in `packages.extra_packages`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_services: Vec<String>,
+
+    /// Install and enable sshd with a hardened configuration (no root
+    /// login except with `root_authorized_keys`)
+    #[serde(default)]
+    pub ssh: bool,
+
+    /// sshd `PasswordAuthentication`.  Unset allows passwords only when no
+    /// `root_authorized_keys` are configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_password_authentication: Option<bool>,
+
+    /// Early microcode loading: separate initrd image, bundled into the
+    /// initramfs, or not installed.  The vendor follows the running CPU.
+    /// Also accepts `true` (separate initrd) / `false` (e.g. for VMs).
+    #[serde(default, deserialize_with = "deserialize_microcode")]
+    pub microcode: MicrocodeLoading,
+
+    /// NTP client keeping the clock in sync from first boot
+    #[serde(default)]
+    pub time_sync: TimeSync,
+
+    /// Firewall with a default-deny-incoming / allow-outgoing profile
+    #[serde(default)]
+    pub firewall: Firewall,
+
+    /// Whether the firewall allows inbound SSH.  Unset opens port 22
+    /// exactly when sshd is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firewall_open_ssh: Option<bool>,
+
+    /// Firmware packages to install instead of the full `linux-firmware`
+    /// (e.g. `linux-firmware-intel`).  Empty installs everything.
+    #[serde(default)]
+    pub firmware_packages: Vec<String>,
+
+    /// Initramfs compression (`COMPRESSION` in mkinitcpio.conf): gzip,
+    /// lz4, xz, zstd, lzop or bzip2 (default zstd).  lz4 unpacks fastest,
+    /// xz gives the smallest images.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initramfs_compression: Option<String>,
+
+    /// Extra kernel modules for the initramfs `MODULES` array, e.g. the
+    /// driver of a RAID HBA the root filesystem sits behind
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub initramfs_modules: Vec<String>,
+
+    /// Extra programs for the initramfs `BINARIES` array, by name or
+    /// absolute path
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub initramfs_binaries: Vec<String>,
+}
+
+impl SystemConfig {
+    /// Default locale (`LANG`): the first configured locale.
+    pub fn lang(&self) -> &str {
+        self.locales.first().map(String::as_str).unwrap_or("C")
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -188,6 +631,37 @@ pub struct UserConfig {
     /// Create as sudoer (wheel group)
     #[serde(default = "default_true")]
     pub sudoer: bool,
+    /// Login shell (e.g. "/usr/bin/zsh"); bash when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+}
+
+/// Common login shells and the packages providing them
+pub const LOGIN_SHELLS: &[(&str, &str)] = &[
+    ("/bin/bash", "bash"),
+    ("/usr/bin/zsh", "zsh"),
+    ("/usr/bin/fish", "fish"),
+    ("/usr/bin/nu", "nushell"),
+    ("/usr/bin/dash", "dash"),
+    ("/usr/bin/tcsh", "tcsh"),
+    ("/usr/bin/mksh", "mksh"),
+];
+
+impl UserConfig {
+    /// Login shell path, defaulting to bash
+    pub fn shell_path(&self) -> &str {
+        self.shell.as_deref().unwrap_or("/bin/bash")
+    }
+
+    /// Package providing the login shell, if it is one of [`LOGIN_SHELLS`].
+    /// Matched by file name, so `/bin/zsh` and `/usr/bin/zsh` both map to zsh.
+    pub fn shell_package(&self) -> Option<&'static str> {
+        let name = self.shell_path().rsplit('/').next()?;
+        LOGIN_SHELLS
+            .iter()
+            .find(|(path, _)| path.rsplit('/').next() == Some(name))
+            .map(|(_, package)| *package)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -208,6 +682,10 @@ pub struct NetworkConfig {
     /// WPA-PSK passphrase for `wifi_ssid`. Omit for an open network.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub wifi_password: Option<String>,
+    /// How many times package downloads (basestrap, pacman, yay) are
+    /// retried after a failure during installation.  0 disables retries.
+    #[serde(default = "default_download_retries")]
+    pub download_retries: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -219,6 +697,159 @@ pub struct DesktopConfig {
     /// `environment = "none"`)
     #[serde(default)]
     pub display_manager: DisplayManager,
+    /// Package set size for the desktop environment (default: full).
+    /// Ignored when `environment = "none"`.
+    #[serde(default)]
+    pub profile: DesktopProfile,
+}
+
+/// ZFS data pool compression
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ZfsCompression {
+    Lz4,
+    #[default]
+    Zstd,
+}
+
+impl std::fmt::Display for ZfsCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lz4 => write!(f, "lz4"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Access-time updates on ZFS datasets
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ZfsAtime {
+    /// Never update access times
+    #[default]
+    Off,
+    /// Update access times on every read
+    On,
+    /// Update access times only when older than the modification time or
+    /// a day old, as `relatime` on other filesystems
+    Relatime,
+}
+
+impl ZfsAtime {
+    /// Dataset properties (`zpool create -O`) setting this behaviour
+    pub fn properties(self) -> &'static [&'static str] {
+        match self {
+            Self::Off => &["atime=off"],
+            Self::On => &["atime=on", "relatime=off"],
+            Self::Relatime => &["atime=on", "relatime=on"],
+        }
+    }
+}
+
+/// sudoers policy for the wheel group, written as a drop-in under
+/// `/etc/sudoers.d/` when `user.sudoer` is set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SudoConfig {
+    /// Run commands without a password prompt.  Unset skips the prompt
+    /// exactly when session switching is installed, which needs it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nopasswd: Option<bool>,
+    /// Minutes a password stays cached (`timestamp_timeout`); 0 asks every
+    /// time.  Unset keeps sudo's default of 5.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_minutes: Option<u32>,
+    /// Commands wheel may run, as absolute paths with optional arguments
+    /// (e.g. "/usr/bin/sv restart greetd").  Empty allows every command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+}
+
+/// ZFS data pool properties and dataset layout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZfsConfig {
+    /// Name of the data pool
+    #[serde(default = "default_zfs_pool_name")]
+    pub pool_name: String,
+    /// Pool ashift (9-16); derived from the disk's physical sector size
+    /// when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ashift: Option<u8>,
+    /// Compression applied to every dataset
+    #[serde(default)]
+    pub compression: ZfsCompression,
+    /// Access-time updates on every dataset of the data and boot pools
+    #[serde(default)]
+    pub atime: ZfsAtime,
+    /// Dataset (relative to the pool) → mount point.  Empty uses the
+    /// standard ROOT/home/var/var/log layout.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub datasets: HashMap<String, String>,
+}
+
+impl Default for ZfsConfig {
+    fn default() -> Self {
+        Self {
+            pool_name: default_zfs_pool_name(),
+            ashift: None,
+            compression: ZfsCompression::default(),
+            atime: ZfsAtime::default(),
+            datasets: HashMap::new(),
+        }
+    }
+}
+
+fn default_zfs_pool_name() -> String {
+    crate::disk::formatting::ZFS_RPOOL_NAME.to_string()
+}
+
+/// mkfs tuning, keyed by filesystem.  Applies to data volumes only; /boot
+/// and the ESP keep the defaults.  Unset values keep the mkfs defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FilesystemOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ext4: Option<Ext4Options>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xfs: Option<XfsOptions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub f2fs: Option<F2fsOptions>,
+}
+
+impl FilesystemOptions {
+    /// Whether no filesystem is tuned.
+    pub fn is_empty(&self) -> bool {
+        self.ext4.is_none() && self.xfs.is_none() && self.f2fs.is_none()
+    }
+}
+
+/// `mkfs.ext4` tuning
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ext4Options {
+    /// Percent of blocks reserved for root (`-m`, 0-50; mkfs default 5)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reserved_percent: Option<u8>,
+    /// Bytes per inode (`-i`, 1024-67108864)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inode_ratio: Option<u32>,
+    /// Force the `64bit` feature on or off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature_64bit: Option<bool>,
+}
+
+/// `mkfs.xfs` tuning
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct XfsOptions {
+    /// Filesystem block size in bytes (`-b size=`, power of two, 512 up
+    /// to the page size, usually 4096)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_size: Option<u32>,
+}
+
+/// `mkfs.f2fs` tuning
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct F2fsOptions {
+    /// Extra arguments passed to mkfs.f2fs as-is (e.g. ["-O", "inode_checksum"])
+    #[serde(default)]
+    pub extra_options: Vec<String>,
 }
 
 /// Optional package collections
@@ -278,6 +909,14 @@ pub struct PackagesConfig {
     /// non-interactively at the end of phase 5.
     #[serde(default)]
     pub extra_packages: ExtraPackagesConfig,
+    /// pacman `ParallelDownloads` for basestrap.  Unset picks a value from
+    /// the CPU and mirror count (at most 5); 1 downloads serially.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_downloads: Option<u32>,
+    /// Host directory bind-mounted over the target's pacman cache, so
+    /// downloaded packages are kept and reused across installs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pacman_cache_dir: Option<String>,
 }
 
 /// User-supplied extras to install in phase 5.95 after the configured
@@ -445,89 +1084,398 @@ impl InitSystem {
 pub enum Bootloader {
     #[default]
     Grub,
+    /// rEFInd boot manager (UEFI only, auto-detects kernels)
+    Refind,
 }
 
 impl std::fmt::Display for Bootloader {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Grub => write!(f, "GRUB"),
+            Self::Refind => write!(f, "rEFInd"),
         }
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+impl Bootloader {
+    /// Whether the bootloader can boot the target's firmware.
+    pub fn supports_firmware(&self, firmware: &Firmware) -> bool {
+        match (self, firmware) {
+            (Self::Grub, Firmware::Uefi | Firmware::Bios) => true,
+            (Self::Refind, Firmware::Uefi) => true,
+            (Self::Refind, Firmware::Bios) => false,
+        }
+    }
+
+    /// Whether the bootloader can read a LUKS-encrypted `/boot`.
+    ///
+    /// Only GRUB has cryptodisk support; rEFInd needs the kernel and
+    /// initramfs on a plain filesystem.
+    pub fn supports_boot_encryption(&self) -> bool {
+        matches!(self, Self::Grub)
+    }
+}
+
+/// Boot firmware of the target machine
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
-pub enum NetworkBackend {
-    /// iwd paired with an AUR GUI frontend (iwgtk / iwdgui / iwqt).
-    /// The specific frontend is selected via `NetworkConfig::iwd_frontend`.
+pub enum Firmware {
+    /// UEFI: GPT with an EFI System Partition
     #[default]
-    Iwd,
-    /// NetworkManager with iwd as the wifi backend.
-    NetworkManager,
-    /// NetworkManager with wpa_supplicant as the wifi backend.
-    #[serde(rename = "networkmanager-wpa")]
-    NetworkManagerWpa,
+    Uefi,
+    /// Legacy BIOS: GPT with a BIOS boot partition for GRUB's core.img
+    Bios,
 }
 
-impl std::fmt::Display for NetworkBackend {
+impl Firmware {
+    /// Firmware the running system was booted with.
+    pub fn detect() -> Self {
+        if std::path::Path::new("/sys/firmware/efi").exists() {
+            Self::Uefi
+        } else {
+            Self::Bios
+        }
+    }
+}
+
+impl std::fmt::Display for Firmware {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Iwd => write!(f, "iwd + GUI frontend (AUR)"),
-            Self::NetworkManager => write!(f, "NetworkManager + iwd"),
-            Self::NetworkManagerWpa => write!(f, "NetworkManager + wpa_supplicant"),
+            Self::Uefi => write!(f, "UEFI"),
+            Self::Bios => write!(f, "BIOS"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+/// Kernel package installed on the target
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
-pub enum IwdFrontend {
+pub enum KernelChoice {
+    /// `linux-zen`: desktop/gaming tuned
     #[default]
-    Iwgtk,
-    Iwdgui,
-    Iwqt,
+    Zen,
+    /// `linux-lts`: long-term support
+    Lts,
+    /// `linux`: mainline stable
+    Stable,
+    /// `linux-hardened`: security-focused patchset
+    Hardened,
 }
 
-impl IwdFrontend {
-    /// AUR package name for this frontend.
-    pub fn aur_package(&self) -> &'static str {
+impl KernelChoice {
+    /// Kernel package name, which is also the suffix of its `/boot` images
+    /// and its mkinitcpio preset.
+    pub fn package(&self) -> &'static str {
         match self {
-            Self::Iwgtk => "iwgtk",
-            Self::Iwdgui => "iwdgui",
-            Self::Iwqt => "iwqt",
+            Self::Zen => "linux-zen",
+            Self::Lts => "linux-lts",
+            Self::Stable => "linux",
+            Self::Hardened => "linux-hardened",
         }
     }
 
-    /// Desktop entry name (used to autostart the tray applet).
-    pub fn desktop_entry(&self) -> &'static str {
-        match self {
-            Self::Iwgtk => "iwgtk",
-            Self::Iwdgui => "iwdgui",
-            Self::Iwqt => "iwqt",
-        }
+    /// Matching headers package (for DKMS modules).
+    pub fn headers_package(&self) -> String {
+        format!("{}-headers", self.package())
+    }
+
+    /// Kernel image installed by the package.
+    pub fn vmlinuz(&self) -> String {
+        format!("/boot/vmlinuz-{}", self.package())
+    }
+
+    /// Default initramfs built from the package's mkinitcpio preset.
+    pub fn initramfs(&self) -> String {
+        format!("/boot/initramfs-{}.img", self.package())
+    }
+
+    /// mkinitcpio preset shipped by the package.
+    pub fn preset_path(&self) -> String {
+        format!("/etc/mkinitcpio.d/{}.preset", self.package())
     }
 }
 
-impl std::fmt::Display for IwdFrontend {
+impl std::fmt::Display for KernelChoice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Iwgtk => write!(f, "iwgtk (GTK)"),
-            Self::Iwdgui => write!(f, "iwdgui (GTK)"),
-            Self::Iwqt => write!(f, "iwqt (Qt)"),
-        }
+        write!(f, "{}", self.package())
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+/// How CPU microcode is loaded early in boot
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
-pub enum DesktopEnvironment {
+pub enum MicrocodeLoading {
+    /// Separate `/boot/<vendor>-ucode.img` loaded by the bootloader ahead
+    /// of the initramfs
     #[default]
+    Initrd,
+    /// Bundled into the initramfs by the mkinitcpio `microcode` hook
+    Bundled,
+    /// Do not install microcode
     None,
-    Kde,
+}
+
+impl std::fmt::Display for MicrocodeLoading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Initrd => write!(f, "separate early initrd"),
+            Self::Bundled => write!(f, "bundled in initramfs"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+/// NTP client for time synchronization
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeSync {
+    /// No NTP client
+    None,
+    #[default]
+    Chrony,
+    /// ntpd from the reference NTP implementation
+    Ntpd,
+    /// OpenBSD's ntpd
+    OpenNtpd,
+}
+
+impl TimeSync {
+    /// Service to enable, if any
+    pub fn service(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Chrony => Some("chronyd"),
+            Self::Ntpd => Some("ntpd"),
+            Self::OpenNtpd => Some("openntpd"),
+        }
+    }
+}
+
+impl std::fmt::Display for TimeSync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Chrony => write!(f, "chrony"),
+            Self::Ntpd => write!(f, "ntpd"),
+            Self::OpenNtpd => write!(f, "OpenNTPD"),
+        }
+    }
+}
+
+/// Firewall frontend
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Firewall {
+    /// No firewall
+    #[default]
+    None,
+    Ufw,
+    Nftables,
+}
+
+impl Firewall {
+    /// Service to enable, if any
+    pub fn service(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Ufw => Some("ufw"),
+            Self::Nftables => Some("nftables"),
+        }
+    }
+}
+
+impl std::fmt::Display for Firewall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Ufw => write!(f, "ufw"),
+            Self::Nftables => write!(f, "nftables"),
+        }
+    }
+}
+
+/// Recovery passphrase for the LUKS containers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum RecoveryPassphrase {
+    #[default]
+    Disabled,
+    /// Ask for the passphrase when the install starts
+    Prompt,
+    Value(String),
+}
+
+impl RecoveryPassphrase {
+    pub fn is_disabled(&self) -> bool {
+        *self == Self::Disabled
+    }
+}
+
+/// Write a recovery passphrase as `true`, so saving a config never leaks
+/// it; reloading the config prompts again.
+fn serialize_recovery_passphrase<S>(
+    value: &RecoveryPassphrase,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_bool(!value.is_disabled())
+}
+
+/// Read `disk.recovery_passphrase` as an on/off switch or the passphrase.
+fn deserialize_recovery_passphrase<'de, D>(
+    deserializer: D,
+) -> std::result::Result<RecoveryPassphrase, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Enabled(bool),
+        Value(String),
+    }
+
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::Enabled(true) => RecoveryPassphrase::Prompt,
+        Repr::Enabled(false) => RecoveryPassphrase::Disabled,
+        Repr::Value(value) => RecoveryPassphrase::Value(value),
+    })
+}
+
+/// Read `system.microcode` as either a [`MicrocodeLoading`] mode or a
+/// plain on/off switch.
+fn deserialize_microcode<'de, D>(deserializer: D) -> std::result::Result<MicrocodeLoading, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Enabled(bool),
+        Mode(MicrocodeLoading),
+    }
+
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::Enabled(true) => MicrocodeLoading::Initrd,
+        Repr::Enabled(false) => MicrocodeLoading::None,
+        Repr::Mode(mode) => mode,
+    })
+}
+
+/// Mount point convention for the EFI System Partition
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum EspMount {
+    /// Traditional `/boot/efi`, nested inside `/boot`
+    #[default]
+    #[serde(rename = "/boot/efi")]
+    BootEfi,
+    /// `/efi` at the filesystem root, independent of `/boot`
+    #[serde(rename = "/efi")]
+    Efi,
+}
+
+impl EspMount {
+    /// Absolute mount point on the installed system.
+    pub fn path(&self) -> &'static str {
+        match self {
+            Self::BootEfi => "/boot/efi",
+            Self::Efi => "/efi",
+        }
+    }
+}
+
+impl std::fmt::Display for EspMount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkBackend {
+    /// iwd paired with an AUR GUI frontend (iwgtk / iwdgui / iwqt).
+    /// The specific frontend is selected via `NetworkConfig::iwd_frontend`.
+    #[default]
+    Iwd,
+    /// NetworkManager with iwd as the wifi backend.
+    NetworkManager,
+    /// NetworkManager with wpa_supplicant as the wifi backend.
+    #[serde(rename = "networkmanager-wpa")]
+    NetworkManagerWpa,
+}
+
+impl std::fmt::Display for NetworkBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Iwd => write!(f, "iwd + GUI frontend (AUR)"),
+            Self::NetworkManager => write!(f, "NetworkManager + iwd"),
+            Self::NetworkManagerWpa => write!(f, "NetworkManager + wpa_supplicant"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IwdFrontend {
+    #[default]
+    Iwgtk,
+    Iwdgui,
+    Iwqt,
+}
+
+impl IwdFrontend {
+    /// AUR package name for this frontend.
+    pub fn aur_package(&self) -> &'static str {
+        match self {
+            Self::Iwgtk => "iwgtk",
+            Self::Iwdgui => "iwdgui",
+            Self::Iwqt => "iwqt",
+        }
+    }
+
+    /// Desktop entry name (used to autostart the tray applet).
+    pub fn desktop_entry(&self) -> &'static str {
+        match self {
+            Self::Iwgtk => "iwgtk",
+            Self::Iwdgui => "iwdgui",
+            Self::Iwqt => "iwqt",
+        }
+    }
+}
+
+impl std::fmt::Display for IwdFrontend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Iwgtk => write!(f, "iwgtk (GTK)"),
+            Self::Iwdgui => write!(f, "iwdgui (GTK)"),
+            Self::Iwqt => write!(f, "iwqt (Qt)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DesktopEnvironment {
+    #[default]
+    None,
+    Kde,
     Gnome,
     Xfce,
 }
 
+impl DesktopEnvironment {
+    /// The display manager the desktop is built and tested with upstream
+    pub fn recommended_display_manager(&self) -> Option<DisplayManager> {
+        match self {
+            Self::None => None,
+            Self::Kde => Some(DisplayManager::Sddm),
+            Self::Gnome => Some(DisplayManager::Gdm),
+            Self::Xfce => Some(DisplayManager::Lightdm),
+        }
+    }
+}
+
 impl std::fmt::Display for DesktopEnvironment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -539,6 +1487,33 @@ impl std::fmt::Display for DesktopEnvironment {
     }
 }
 
+/// Package set installed for the selected desktop environment.
+///
+/// `Full` is the historical deploytix package list; `Minimal` installs only
+/// the shell, a terminal, a file manager and the portal/audio glue needed
+/// for a usable session — intended for small or constrained disks.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DesktopProfile {
+    Minimal,
+    #[default]
+    Full,
+}
+
+impl DesktopProfile {
+    /// Every profile, in the order the wizard and GUI offer them.
+    pub const ALL: [DesktopProfile; 2] = [Self::Full, Self::Minimal];
+}
+
+impl std::fmt::Display for DesktopProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Minimal => write!(f, "Minimal (core desktop only)"),
+            Self::Full => write!(f, "Full (desktop + applications)"),
+        }
+    }
+}
+
 /// Display manager selection for desktop installs.
 ///
 /// `Greetd` is the deploytix default and keeps the original behavior:
@@ -592,8 +1567,26 @@ fn default_timezone() -> String {
     "UTC".to_string()
 }
 
-fn default_locale() -> String {
-    "en_US.UTF-8".to_string()
+fn default_locales() -> Vec<String> {
+    vec!["en_US.UTF-8".to_string()]
+}
+
+/// Read `system.locales` as either a list or a single locale.
+fn deserialize_locales<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::One(locale) => vec![locale],
+        Repr::Many(locales) => locales,
+    })
 }
 
 fn default_keymap() -> String {
@@ -612,6 +1605,10 @@ pub fn default_luks_boot_mapper_name() -> String {
     "Crypt-Boot".to_string()
 }
 
+pub fn default_keyfile_dir() -> String {
+    crate::configure::keyfiles::KEYFILE_DIR.to_string()
+}
+
 fn default_vg_name() -> String {
     "vg0".to_string()
 }
@@ -628,6 +1625,18 @@ fn default_zram_algorithm() -> String {
     "zstd".to_string()
 }
 
+fn default_zram_devices() -> u8 {
+    crate::configure::swap::DEFAULT_ZRAM_DEVICES
+}
+
+fn default_zram_log_sync_minutes() -> u32 {
+    30
+}
+
+fn default_download_retries() -> u32 {
+    crate::utils::command::DEFAULT_NETWORK_RETRIES
+}
+
 pub fn default_groups() -> Vec<String> {
     vec![
         "wheel".to_string(),
@@ -652,24 +1661,32 @@ pub fn default_partitions() -> Vec<CustomPartitionEntry> {
             label: None,
             size_mib: 20480, // 20 GiB
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         },
         CustomPartitionEntry {
             mount_point: "/usr".to_string(),
             label: None,
             size_mib: 30720, // 30 GiB
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         },
         CustomPartitionEntry {
             mount_point: "/var".to_string(),
             label: None,
             size_mib: 10240, // 10 GiB
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         },
         CustomPartitionEntry {
             mount_point: "/home".to_string(),
             label: None,
             size_mib: 0, // Remainder
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         },
     ]
 }
@@ -719,7 +1736,7 @@ impl DeploymentConfig {
         let device = if let Some(d) = device {
             d
         } else {
-            let devices = list_block_devices(false)?;
+            let devices = list_block_devices_cached(false)?;
             if devices.is_empty() {
                 return Err(DeploytixError::ConfigError(
                     "No suitable disks found".to_string(),
@@ -729,16 +1746,50 @@ impl DeploymentConfig {
                 .iter()
                 .map(|d| {
                     format!(
-                        "{} - {} {}",
+                        "{} - {} {} [{}]",
                         d.path,
                         d.size_human(),
-                        d.model.as_deref().unwrap_or("")
+                        d.model.as_deref().unwrap_or(""),
+                        d.media_kind()
                     )
                 })
                 .collect();
             let idx = prompt_select("Select target disk", &items, 0)?;
             devices[idx].path.clone()
         };
+        let device_info = get_device_info(&device).ok();
+        let rotational = device_info.as_ref().is_none_or(|d| d.is_rotational);
+
+        // Refuse a disk no layout fits on before asking anything else
+        if let Some(ref info) = device_info {
+            let required_mib = minimal_layout_mib(Firmware::detect());
+            if info.size_mib() < required_mib {
+                return Err(DeploytixError::DiskTooSmall {
+                    size_mib: info.size_mib(),
+                    required_mib,
+                });
+            }
+        }
+
+        // SMART pre-flight (skipped when smartmontools is not installed)
+        if let Some(report) = smart_health(&device) {
+            println!("  SMART health of {}: {}", device, report.summary());
+            if !report.passed {
+                use colored::Colorize;
+                println!(
+                    "\n{}\n",
+                    format!(
+                        "⚠  {} reports a FAILED SMART health check. The drive may be dying.",
+                        device
+                    )
+                    .red()
+                    .bold()
+                );
+                if !prompt_confirm("Install onto this disk anyway?", false)? {
+                    return Err(DeploytixError::UserCancelled);
+                }
+            }
+        }
 
         // Partition definition
         println!("\n📦 Partition Configuration");
@@ -804,6 +1855,8 @@ impl DeploymentConfig {
                 label,
                 size_mib,
                 encryption: None, // Inherit from global setting
+                filesystem: None,
+                mount_options: None,
             });
 
             if !prompt_confirm("Add another partition?", true)? {
@@ -832,6 +1885,8 @@ impl DeploymentConfig {
                     label: None,
                     size_mib: root_size,
                     encryption: None,
+                    filesystem: None,
+                    mount_options: None,
                 },
             );
         }
@@ -858,7 +1913,7 @@ impl DeploymentConfig {
         let use_subvolumes = filesystem == Filesystem::Btrfs;
 
         // Integrity (dm-integrity alongside LUKS2 encryption)
-        let integrity = if encryption {
+        let mut integrity = if encryption {
             prompt_confirm(
                 "Enable dm-integrity (per-sector HMAC-SHA256 integrity protection)?",
                 false,
@@ -867,6 +1922,24 @@ impl DeploymentConfig {
             false
         };
 
+        // dm-integrity cannot pass TRIM through, so on flash the user has
+        // to pick one or the other.
+        let mut integrity_trim_acknowledged = false;
+        if integrity && !rotational {
+            println!("\n⚠  {} is an SSD.", device);
+            println!("  dm-integrity does not support TRIM/discard: the SSD will not be told");
+            println!("  which blocks are free, which slows writes and wears the flash faster");
+            println!("  over time.  Without integrity, discard is enabled on the LUKS volumes");
+            println!("  but silent data corruption is no longer detected.\n");
+            let choices = [
+                "Keep dm-integrity (no TRIM)",
+                "Enable TRIM/discard (no integrity)",
+            ];
+            let choice = prompt_select("Integrity or TRIM", &choices, 0)?;
+            integrity = choice == 0;
+            integrity_trim_acknowledged = integrity;
+        }
+
         // Boot encryption (LUKS1 on separate /boot partition)
         // When integrity is enabled, boot uses LUKS1 without integrity (LUKS1 doesn't support it)
         let boot_encryption = if encryption {
@@ -876,7 +1949,7 @@ impl DeploymentConfig {
         };
 
         let encryption_password = if encryption {
-            Some(prompt_password("Encryption password", true)?)
+            Some(prompt_new_password("Encryption password")?)
         } else {
             None
         };
@@ -891,11 +1964,21 @@ impl DeploymentConfig {
         let init_idx = prompt_select("Init system", &init_systems, 0)?;
         let init = init_systems[init_idx].clone();
 
-        // Bootloader (GRUB is the only supported bootloader on Artix)
-        let bootloader = Bootloader::Grub;
+        // Bootloader (rEFInd is UEFI-only and cannot read an encrypted /boot)
+        let bootloader = if Firmware::detect() == Firmware::Uefi && !boot_encryption {
+            let bootloaders = [Bootloader::Grub, Bootloader::Refind];
+            let idx = prompt_select("Bootloader", &bootloaders, 0)?;
+            bootloaders[idx].clone()
+        } else {
+            Bootloader::Grub
+        };
 
         // Locale settings
-        let timezone = prompt_input("Timezone", Some("UTC"))?;
+        let mut timezone = prompt_input("Timezone (\"auto\" to detect)", Some("UTC"))?;
+        if timezone == crate::configure::locale::AUTO_TIMEZONE {
+            timezone = crate::configure::locale::detect_timezone(false);
+            println!("  Detected timezone: {}", timezone);
+        }
         let locale = prompt_input("Locale", Some("en_US.UTF-8"))?;
         let keymap = prompt_input("Keyboard layout", Some("us"))?;
         let hostname = prompt_input("Hostname", Some("artix"))?;
@@ -903,7 +1986,7 @@ impl DeploymentConfig {
         // User
         println!("\n👤 User Configuration\n");
         let username = prompt_input("Username", None)?;
-        let password = prompt_password("User password", true)?;
+        let password = prompt_new_password("User password")?;
         // Network
         let backends = [
             NetworkBackend::Iwd,
@@ -964,6 +2047,15 @@ impl DeploymentConfig {
             DisplayManager::None
         };
 
+        // Desktop package profile (only meaningful with a desktop environment)
+        let profile = if environment != DesktopEnvironment::None {
+            let profiles = DesktopProfile::ALL;
+            let p_idx = prompt_select("Desktop package set", &profiles, 0)?;
+            profiles[p_idx]
+        } else {
+            DesktopProfile::default()
+        };
+
         // Swap type selection
         let swap_types = [SwapType::Partition, SwapType::FileZram, SwapType::ZramOnly];
         let swap_idx = prompt_select("Swap configuration", &swap_types, 0)?;
@@ -972,8 +2064,10 @@ impl DeploymentConfig {
         // LVM thin provisioning (available on all layouts)
         let use_lvm_thin = prompt_confirm("Enable LVM thin provisioning?", false)?;
 
-        // SecureBoot option
-        let secureboot = prompt_confirm("Enable SecureBoot signing?", false)?;
+        // SecureBoot option (UEFI only)
+        let secureboot = Firmware::detect() == Firmware::Uefi
+            && bootloader == Bootloader::Grub
+            && prompt_confirm("Enable SecureBoot signing?", false)?;
         let secureboot_method = if secureboot {
             let methods = [
                 SecureBootMethod::Sbctl,
@@ -1102,6 +2196,9 @@ impl DeploymentConfig {
         Ok(DeploymentConfig {
             disk: DiskConfig {
                 device,
+                mirror_device: None,
+                preserve_partitions: false,
+                existing_esp: None,
                 filesystem,
                 boot_filesystem,
                 encryption,
@@ -1110,45 +2207,100 @@ impl DeploymentConfig {
                 boot_encryption,
                 luks_boot_mapper_name: default_luks_boot_mapper_name(),
                 keyfile_path: None,
+                keyfile_dir: default_keyfile_dir(),
+                keyfile_size_bytes: None,
                 integrity,
+                integrity_trim_acknowledged,
+                integrity_no_wipe: false,
+                luks_format_timeout_mins: None,
+                parallel_format: false,
+                format_jobs: None,
                 keyfile_enabled: encryption, // Enable keyfiles when encryption is enabled
+                fido2_unlock: false,
+                btrfs_snapshots: false,
+                recovery_passphrase: RecoveryPassphrase::Disabled,
                 use_subvolumes,
                 use_lvm_thin,
                 lvm_vg_name: default_vg_name(),
                 lvm_thin_pool_name: default_thin_pool_name(),
                 lvm_thin_pool_percent: default_thin_pool_percent(),
+                thin_autoextend_threshold: None,
+                lvm_thin_volumes: None,
+                esp_mountpoint: EspMount::default(),
+                firmware: None,
                 swap_type,
                 swap_file_size_mib: 0, // Auto-calculate
                 zram_algorithm: default_zram_algorithm(),
+                zram_devices: default_zram_devices(),
+                vm_swappiness: None,
+                zram_tmp_mib: 0,
+                zram_log_mib: 0,
+                zram_log_sync_minutes: default_zram_log_sync_minutes(),
+                btrfs_compression: None,
+                btrfs_mount_options: None,
+                btrfs_ssd: None,
+                discard: None,
+                btrfs_mixed: None,
+                btrfs_subvolumes: None,
+                f2fs_compression: None,
+                labels: HashMap::new(),
                 partitions,
             },
             system: SystemConfig {
                 init,
                 bootloader,
+                kernel: KernelChoice::default(),
+                grub_timeout: None,
+                grub_default: None,
+                grub_distributor: None,
+                grub_os_prober: false,
                 timezone,
-                locale,
+                locales: vec![locale],
+                locale_overrides: HashMap::new(),
                 keymap,
+                console_font: None,
                 hostname,
                 hibernation: false,
+                tmp_tmpfs: false,
+                tmp_tmpfs_size: None,
+                hardened: false,
                 secureboot,
                 secureboot_method,
                 secureboot_keys_path: None,
+                blacklist_modules: Vec::new(),
+                load_modules: Vec::new(),
+                kernel_params: Vec::new(),
+                root_authorized_keys: Vec::new(),
+                extra_services: Vec::new(),
+                ssh: false,
+                ssh_password_authentication: None,
+                microcode: MicrocodeLoading::default(),
+                time_sync: TimeSync::default(),
+                firewall: Firewall::default(),
+                firewall_open_ssh: None,
+                firmware_packages: Vec::new(),
+                initramfs_compression: None,
+                initramfs_modules: Vec::new(),
+                initramfs_binaries: Vec::new(),
             },
             user: UserConfig {
                 name: username,
                 password,
                 groups: default_groups(),
                 sudoer: true,
+                shell: None,
             },
             network: NetworkConfig {
                 backend,
                 iwd_frontend,
                 wifi_ssid,
                 wifi_password,
+                download_retries: default_download_retries(),
             },
             desktop: DesktopConfig {
                 environment,
                 display_manager,
+                profile,
             },
             packages: PackagesConfig {
                 install_yay,
@@ -1163,7 +2315,12 @@ impl DeploymentConfig {
                 install_evdevhook2,
                 gpu_drivers,
                 extra_packages: ExtraPackagesConfig::default(),
+                parallel_downloads: None,
+                pacman_cache_dir: None,
             },
+            filesystem_options: FilesystemOptions::default(),
+            zfs: ZfsConfig::default(),
+            sudo: SudoConfig::default(),
         })
     }
 
@@ -1172,6 +2329,9 @@ impl DeploymentConfig {
         DeploymentConfig {
             disk: DiskConfig {
                 device: "/dev/sda".to_string(),
+                mirror_device: None,
+                preserve_partitions: false,
+                existing_esp: None,
                 filesystem: Filesystem::Btrfs,
                 boot_filesystem: Filesystem::Btrfs,
                 encryption: false,
@@ -1180,48 +2340,196 @@ impl DeploymentConfig {
                 boot_encryption: false,
                 luks_boot_mapper_name: default_luks_boot_mapper_name(),
                 keyfile_path: None,
+                keyfile_dir: default_keyfile_dir(),
+                keyfile_size_bytes: None,
                 integrity: false,
+                integrity_trim_acknowledged: false,
+                integrity_no_wipe: false,
+                luks_format_timeout_mins: None,
+                parallel_format: false,
+                format_jobs: None,
                 keyfile_enabled: false,
+                fido2_unlock: false,
+                btrfs_snapshots: false,
+                recovery_passphrase: RecoveryPassphrase::Disabled,
                 use_subvolumes: false,
                 use_lvm_thin: false,
                 lvm_vg_name: default_vg_name(),
                 lvm_thin_pool_name: default_thin_pool_name(),
                 lvm_thin_pool_percent: default_thin_pool_percent(),
+                thin_autoextend_threshold: None,
+                lvm_thin_volumes: None,
+                esp_mountpoint: EspMount::default(),
+                firmware: None,
                 swap_type: SwapType::Partition,
                 swap_file_size_mib: 0,
                 zram_algorithm: default_zram_algorithm(),
+                zram_devices: default_zram_devices(),
+                vm_swappiness: None,
+                zram_tmp_mib: 0,
+                zram_log_mib: 0,
+                zram_log_sync_minutes: default_zram_log_sync_minutes(),
+                btrfs_compression: None,
+                btrfs_mount_options: None,
+                btrfs_ssd: None,
+                discard: None,
+                btrfs_mixed: None,
+                btrfs_subvolumes: None,
+                f2fs_compression: None,
+                labels: HashMap::new(),
                 partitions: default_partitions(),
             },
             system: SystemConfig {
                 init: InitSystem::Runit,
                 bootloader: Bootloader::Grub,
+                kernel: KernelChoice::default(),
+                grub_timeout: None,
+                grub_default: None,
+                grub_distributor: None,
+                grub_os_prober: false,
                 timezone: "America/New_York".to_string(),
-                locale: "en_US.UTF-8".to_string(),
+                locales: default_locales(),
+                locale_overrides: HashMap::new(),
                 keymap: "us".to_string(),
+                console_font: None,
                 hostname: "artix".to_string(),
                 hibernation: false,
+                tmp_tmpfs: false,
+                tmp_tmpfs_size: None,
+                hardened: false,
                 secureboot: false,
                 secureboot_method: SecureBootMethod::Sbctl,
                 secureboot_keys_path: None,
+                blacklist_modules: Vec::new(),
+                load_modules: Vec::new(),
+                kernel_params: Vec::new(),
+                root_authorized_keys: Vec::new(),
+                extra_services: Vec::new(),
+                ssh: false,
+                ssh_password_authentication: None,
+                microcode: MicrocodeLoading::default(),
+                time_sync: TimeSync::default(),
+                firewall: Firewall::default(),
+                firewall_open_ssh: None,
+                firmware_packages: Vec::new(),
+                initramfs_compression: None,
+                initramfs_modules: Vec::new(),
+                initramfs_binaries: Vec::new(),
             },
             user: UserConfig {
                 name: "user".to_string(),
                 password: "changeme".to_string(),
                 groups: default_groups(),
                 sudoer: true,
+                shell: None,
             },
             network: NetworkConfig {
                 backend: NetworkBackend::Iwd,
                 iwd_frontend: IwdFrontend::default(),
                 wifi_ssid: None,
                 wifi_password: None,
+                download_retries: default_download_retries(),
             },
             desktop: DesktopConfig {
                 environment: DesktopEnvironment::Kde,
                 display_manager: DisplayManager::default(),
+                profile: DesktopProfile::default(),
             },
             packages: PackagesConfig::default(),
+            filesystem_options: FilesystemOptions::default(),
+            zfs: ZfsConfig::default(),
+            sudo: SudoConfig::default(),
+        }
+    }
+
+    /// Warn about dm-integrity on a flash target without an explicit choice.
+    ///
+    /// Integrity disables TRIM/discard on the LUKS volumes; on an SSD that
+    /// is a real cost, so it should be a deliberate decision.
+    pub fn integrity_trim_lint(&self, rotational: bool) -> Option<String> {
+        if self.disk.encryption
+            && self.disk.integrity
+            && !rotational
+            && !self.disk.integrity_trim_acknowledged
+        {
+            Some(format!(
+                "dm-integrity disables TRIM/discard on SSD {}; set integrity_trim_acknowledged = true to keep it, or disable integrity to enable discard",
+                self.disk.device
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Warn about a login shell whose package is not installed.
+    ///
+    /// Shells outside [`LOGIN_SHELLS`] are only installed when listed in
+    /// the extra packages; without one, the user cannot log in.
+    pub fn shell_package_lint(&self) -> Option<String> {
+        let shell = self.user.shell.as_deref()?;
+        if self.user.shell_package().is_some() {
+            return None;
+        }
+        let name = shell.rsplit('/').next().unwrap_or(shell);
+        let extra = &self.packages.extra_packages;
+        if extra.pacman.iter().chain(&extra.aur).any(|p| p == name) {
+            return None;
+        }
+        Some(format!(
+            "login shell {} is not a known shell and no extra package named '{}' is installed; add its package to packages.extra_packages",
+            shell, name
+        ))
+    }
+
+    /// Passwords and passphrases set in the config, to keep out of logs and
+    /// the command transcript.
+    pub fn secrets(&self) -> Vec<String> {
+        let recovery = match &self.disk.recovery_passphrase {
+            RecoveryPassphrase::Value(passphrase) => Some(passphrase.clone()),
+            _ => None,
+        };
+        std::iter::once(Some(self.user.password.clone()))
+            .chain([
+                self.disk.encryption_password.clone(),
+                recovery,
+                self.network.wifi_password.clone(),
+            ])
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    /// Warning when sshd is enabled behind a firewall that keeps port 22
+    /// closed, which locks out remote logins.
+    pub fn firewall_ssh_lint(&self) -> Option<String> {
+        if self.system.firewall == Firewall::None
+            || !crate::configure::ssh::sshd_enabled(self)
+            || crate::configure::firewall::opens_ssh(self)
+        {
+            return None;
+        }
+        Some(format!(
+            "sshd is enabled but the {} firewall does not open port 22; remote logins will be refused",
+            self.system.firewall
+        ))
+    }
+
+    /// Warning when a login-screen display manager other than the one the
+    /// desktop is made for is selected.  greetd (auto-login) and the TTY
+    /// login work with every desktop and are not flagged.
+    pub fn display_manager_lint(&self) -> Option<String> {
+        let dm = self.desktop.display_manager;
+        let recommended = self.desktop.environment.recommended_display_manager()?;
+        if matches!(dm, DisplayManager::Greetd | DisplayManager::None) || dm == recommended {
+            return None;
         }
+        Some(format!(
+            "{} is usually paired with {}, but display_manager is {}; if the login screen misbehaves, set display_manager = \"{}\"",
+            self.desktop.environment,
+            recommended.service_name().unwrap_or_default(),
+            dm.service_name().unwrap_or_default(),
+            recommended.service_name().unwrap_or_default()
+        ))
     }
 
     /// Validate the configuration
@@ -1237,6 +2545,27 @@ impl DeploymentConfig {
             return Err(DeploytixError::NotBlockDevice(self.disk.device.clone()));
         }
 
+        if let Some(ref mirror) = self.disk.mirror_device {
+            if !Path::new(mirror).exists() {
+                return Err(DeploytixError::DeviceNotFound(mirror.clone()));
+            }
+            if !std::fs::metadata(mirror)?.file_type().is_block_device() {
+                return Err(DeploytixError::NotBlockDevice(mirror.clone()));
+            }
+            if std::fs::canonicalize(mirror)? == std::fs::canonicalize(&self.disk.device)? {
+                return Err(DeploytixError::ValidationError(format!(
+                    "mirror_device {} is the same disk as device {}",
+                    mirror, self.disk.device
+                )));
+            }
+        }
+
+        self.validate_settings()
+    }
+
+    /// Validate everything except the target device itself, so a config can
+    /// be checked before (or without) the disk being present.
+    pub fn validate_settings(&self) -> Result<()> {
         // Validate username
         if self.user.name.is_empty() {
             return Err(DeploytixError::ValidationError(
@@ -1279,6 +2608,29 @@ impl DeploymentConfig {
             ));
         }
 
+        // ZFS encrypts the pool natively; LUKS-only features cannot be layered
+        // underneath it.
+        if self.disk.uses_zfs_encryption() {
+            if self.disk.boot_encryption || self.disk.integrity {
+                return Err(DeploytixError::ValidationError(
+                    "ZFS uses native encryption; boot_encryption and integrity (LUKS) cannot be combined with it"
+                        .to_string(),
+                ));
+            }
+            // zfs rejects passphrases shorter than 8 characters
+            if self
+                .disk
+                .encryption_password
+                .as_ref()
+                .is_some_and(|p| p.len() < 8)
+            {
+                return Err(DeploytixError::ValidationError(
+                    "ZFS native encryption requires a passphrase of at least 8 characters"
+                        .to_string(),
+                ));
+            }
+        }
+
         // boot_encryption is LUKS1 only - boot_filesystem must not be ZFS
         // (ZFS on /boot with LUKS1 is unsupported)
         if self.disk.boot_encryption && self.disk.boot_filesystem == Filesystem::Zfs {
@@ -1287,6 +2639,10 @@ impl DeploymentConfig {
             ));
         }
 
+        crate::disk::mirror::validate_mirror(&self.disk)
+            .map_err(DeploytixError::ValidationError)?;
+        crate::disk::dualboot::validate_alongside(self).map_err(DeploytixError::ValidationError)?;
+
         // Integrity requires encryption
         if self.disk.integrity && !self.disk.encryption {
             return Err(DeploytixError::ValidationError(
@@ -1294,42 +2650,468 @@ impl DeploymentConfig {
             ));
         }
 
-        // Boot encryption requires encryption to be enabled
-        if self.disk.boot_encryption && !self.disk.encryption {
+        // FIDO2 tokens live in LUKS2 headers opened by crypttab-unlock, which
+        // only the multi-volume layout uses (LVM is opened by `encrypt`)
+        if self.disk.fido2_unlock && (!self.disk.uses_luks() || self.disk.use_lvm_thin) {
             return Err(DeploytixError::ValidationError(
-                "Boot encryption requires disk encryption to be enabled".to_string(),
+                "fido2_unlock requires LUKS encryption without LVM thin provisioning".to_string(),
             ));
         }
 
-        // lvm_thin_pool_percent must be 1–100 (passed as N%VG to lvcreate)
-        if self.disk.lvm_thin_pool_percent == 0 || self.disk.lvm_thin_pool_percent > 100 {
-            return Err(DeploytixError::ValidationError(format!(
-                "lvm_thin_pool_percent must be between 1 and 100, got {}",
-                self.disk.lvm_thin_pool_percent
-            )));
-        }
-
-        // Swap file requires btrfs or ext4 filesystem
-        if self.disk.swap_type == SwapType::FileZram
-            && self.disk.filesystem != Filesystem::Btrfs
-            && self.disk.filesystem != Filesystem::Ext4
-        {
-            return Err(DeploytixError::ValidationError(
-                "Swap file requires btrfs or ext4 filesystem".to_string(),
-            ));
-        }
+        crate::configure::keyfiles::validate_keyfile_settings(&self.disk)?;
 
-        // SecureBoot with ManualKeys requires keys path
-        if self.system.secureboot
-            && self.system.secureboot_method == SecureBootMethod::ManualKeys
-            && self.system.secureboot_keys_path.is_none()
-        {
+        if self.disk.format_jobs == Some(0) {
             return Err(DeploytixError::ValidationError(
-                "SecureBoot with ManualKeys method requires secureboot_keys_path".to_string(),
+                "format_jobs must be at least 1".to_string(),
             ));
         }
 
-        // Partition list validation
+        match &self.disk.recovery_passphrase {
+            RecoveryPassphrase::Disabled => {}
+            _ if !self.disk.uses_luks() => {
+                return Err(DeploytixError::ValidationError(
+                    "recovery_passphrase requires LUKS encryption".to_string(),
+                ));
+            }
+            RecoveryPassphrase::Value(value)
+                if value.is_empty()
+                    || self.disk.encryption_password.as_deref() == Some(value.as_str()) =>
+            {
+                return Err(DeploytixError::ValidationError(
+                    "recovery_passphrase must be non-empty and differ from encryption_password"
+                        .to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        // Boot encryption requires encryption to be enabled
+        if self.disk.boot_encryption && !self.disk.encryption {
+            return Err(DeploytixError::ValidationError(
+                "Boot encryption requires disk encryption to be enabled".to_string(),
+            ));
+        }
+
+        if let Some(t) = self.disk.thin_autoextend_threshold {
+            if !(50..=100).contains(&t) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "thin_autoextend_threshold must be between 50 and 100, got {}",
+                    t
+                )));
+            }
+        }
+
+        let firmware = self.disk.firmware();
+        if !self.system.bootloader.supports_firmware(&firmware) {
+            return Err(DeploytixError::ValidationError(format!(
+                "{} does not support {} firmware",
+                self.system.bootloader, firmware
+            )));
+        }
+        if firmware == Firmware::Bios {
+            if self.system.secureboot {
+                return Err(DeploytixError::ValidationError(
+                    "SecureBoot requires UEFI firmware".to_string(),
+                ));
+            }
+            if self.disk.esp_mountpoint != EspMount::default() {
+                return Err(DeploytixError::ValidationError(
+                    "esp_mountpoint does not apply to BIOS installs (no EFI partition)".to_string(),
+                ));
+            }
+        }
+
+        if self.disk.boot_encryption && !self.system.bootloader.supports_boot_encryption() {
+            return Err(DeploytixError::ValidationError(format!(
+                "{} cannot boot from an encrypted /boot (disable boot_encryption or use GRUB)",
+                self.system.bootloader
+            )));
+        }
+        if self.system.secureboot && self.system.bootloader != Bootloader::Grub {
+            return Err(DeploytixError::ValidationError(format!(
+                "SecureBoot signing is only supported with GRUB, not {}",
+                self.system.bootloader
+            )));
+        }
+
+        // lvm_thin_pool_percent must be 1–100 (passed as N%VG to lvcreate)
+        if self.disk.lvm_thin_pool_percent == 0 || self.disk.lvm_thin_pool_percent > 100 {
+            return Err(DeploytixError::ValidationError(format!(
+                "lvm_thin_pool_percent must be between 1 and 100, got {}",
+                self.disk.lvm_thin_pool_percent
+            )));
+        }
+
+        if let Some(ref volumes) = self.disk.lvm_thin_volumes {
+            crate::disk::lvm::validate_thin_volumes(volumes)
+                .map_err(DeploytixError::ValidationError)?;
+        }
+
+        // Btrfs compression / mount options end up verbatim in fstab and the
+        // mountcrypt hook, so reject anything btrfs would refuse at boot.
+        if let Some(ref c) = self.disk.btrfs_compression {
+            if !crate::disk::layouts::is_valid_btrfs_compression(c) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid btrfs_compression '{}' (expected zstd[:1-15], zlib[:1-9], lzo or none)",
+                    c
+                )));
+            }
+        }
+        if let Some(ref c) = self.disk.f2fs_compression {
+            if self.disk.filesystem != Filesystem::F2fs {
+                return Err(DeploytixError::ValidationError(
+                    "f2fs_compression requires filesystem = \"f2fs\"".to_string(),
+                ));
+            }
+            if !crate::disk::layouts::is_valid_f2fs_compression(c) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid f2fs_compression '{}' (expected zstd[:1-22], lz4[:3-16], lzo, lzo-rle or none)",
+                    c
+                )));
+            }
+        }
+        if let Some(ref opts) = self.disk.btrfs_mount_options {
+            if opts.is_empty() || opts.chars().any(char::is_whitespace) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid btrfs_mount_options '{}': must be a comma-separated list without spaces",
+                    opts
+                )));
+            }
+            if opts
+                .split(',')
+                .any(|o| o.starts_with("subvol") || o.starts_with("compress"))
+            {
+                return Err(DeploytixError::ValidationError(
+                    "btrfs_mount_options must not set subvol/compress (use btrfs_compression)"
+                        .to_string(),
+                ));
+            }
+        }
+
+        self.disk
+            .validate_labels()
+            .map_err(DeploytixError::ValidationError)?;
+
+        if let Some(ref subvolumes) = self.disk.btrfs_subvolumes {
+            if self.disk.filesystem != Filesystem::Btrfs || self.disk.use_lvm_thin {
+                return Err(DeploytixError::ValidationError(
+                    "btrfs_subvolumes requires filesystem = \"btrfs\" without LVM thin".to_string(),
+                ));
+            }
+            if self.disk.partitions.iter().any(|p| p.mount_point != "/") {
+                return Err(DeploytixError::ValidationError(
+                    "btrfs_subvolumes needs a single data partition mounted at / \
+                     (separate partitions get one subvolume each)"
+                        .to_string(),
+                ));
+            }
+            crate::disk::layouts::validate_subvolumes(subvolumes)
+                .map_err(DeploytixError::ValidationError)?;
+            if self.disk.uses_btrfs_snapshots()
+                && subvolumes
+                    .iter()
+                    .any(|s| s.mount_point == crate::disk::layouts::SNAPSHOTS_MOUNT_POINT)
+            {
+                return Err(DeploytixError::ValidationError(format!(
+                    "btrfs_snapshots adds the subvolume at {} itself; remove it from btrfs_subvolumes",
+                    crate::disk::layouts::SNAPSHOTS_MOUNT_POINT
+                )));
+            }
+        }
+
+        if self.disk.integrity_no_wipe && !(self.disk.encryption && self.disk.integrity) {
+            return Err(DeploytixError::ValidationError(
+                "integrity_no_wipe requires encryption and integrity to be enabled".to_string(),
+            ));
+        }
+
+        if self.disk.btrfs_mixed == Some(true) && self.disk.filesystem != Filesystem::Btrfs {
+            return Err(DeploytixError::ValidationError(
+                "btrfs_mixed only applies when filesystem = btrfs".to_string(),
+            ));
+        }
+
+        // Swap file requires btrfs or ext4 filesystem
+        if self.disk.swap_type == SwapType::FileZram
+            && self.disk.filesystem != Filesystem::Btrfs
+            && self.disk.filesystem != Filesystem::Ext4
+        {
+            return Err(DeploytixError::ValidationError(
+                "Swap file requires btrfs or ext4 filesystem".to_string(),
+            ));
+        }
+
+        if self.disk.zram_devices == 0 {
+            return Err(DeploytixError::ValidationError(
+                "zram_devices must be at least 1".to_string(),
+            ));
+        }
+        if let Some(s) = self.disk.vm_swappiness {
+            if s > 200 {
+                return Err(DeploytixError::ValidationError(format!(
+                    "vm_swappiness must be between 0 and 200, got {}",
+                    s
+                )));
+            }
+        }
+
+        // zram-backed /tmp and /var/log.  The devices are hot-added at boot
+        // so they never claim zram0, which is reserved for ZRAM swap.
+        for (mib, mount_point) in [
+            (self.disk.zram_tmp_mib, "/tmp"),
+            (self.disk.zram_log_mib, "/var/log"),
+        ] {
+            if mib == 0 {
+                continue;
+            }
+            if mib < crate::configure::zram_mounts::MIN_ZRAM_MOUNT_MIB {
+                return Err(DeploytixError::ValidationError(format!(
+                    "zram {} must be at least {} MiB",
+                    mount_point,
+                    crate::configure::zram_mounts::MIN_ZRAM_MOUNT_MIB
+                )));
+            }
+            if self
+                .disk
+                .partitions
+                .iter()
+                .any(|p| p.mount_point == mount_point)
+            {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Cannot put {} on zram: it is already a partition",
+                    mount_point
+                )));
+            }
+        }
+        let timezone = &self.system.timezone;
+        if timezone != crate::configure::locale::AUTO_TIMEZONE
+            && !crate::configure::locale::is_valid_timezone(timezone)
+        {
+            return Err(DeploytixError::ValidationError(format!(
+                "Unknown timezone '{}' (see /usr/share/zoneinfo, or use \"auto\")",
+                timezone
+            )));
+        }
+        if self.system.locales.is_empty() {
+            return Err(DeploytixError::ValidationError(
+                "At least one locale is required".to_string(),
+            ));
+        }
+        for locale in &self.system.locales {
+            if !crate::configure::locale::is_supported_locale(locale) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Unknown locale '{}' (see /usr/share/i18n/SUPPORTED)",
+                    locale
+                )));
+            }
+        }
+        for (category, locale) in &self.system.locale_overrides {
+            if !crate::configure::locale::LOCALE_CATEGORIES.contains(&category.as_str()) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Unknown locale category '{}' in locale_overrides",
+                    category
+                )));
+            }
+            if !crate::configure::locale::is_supported_locale(locale) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Unknown locale '{}' for {}",
+                    locale, category
+                )));
+            }
+        }
+        crate::disk::formatting::validate_filesystem_options(&self.filesystem_options)
+            .map_err(DeploytixError::ValidationError)?;
+        crate::disk::formatting::validate_zfs_config(&self.zfs)
+            .map_err(DeploytixError::ValidationError)?;
+        if let Some(ref font) = self.system.console_font {
+            if !crate::configure::locale::is_known_console_font(font) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Unknown console font '{}' (see /usr/share/kbd/consolefonts)",
+                    font
+                )));
+            }
+        }
+        if self.system.tmp_tmpfs {
+            if self.disk.zram_tmp_mib > 0 {
+                return Err(DeploytixError::ValidationError(
+                    "tmp_tmpfs and zram_tmp_mib both mount /tmp; choose one".to_string(),
+                ));
+            }
+            if self.disk.partitions.iter().any(|p| p.mount_point == "/tmp") {
+                return Err(DeploytixError::ValidationError(
+                    "Cannot mount /tmp as tmpfs: it is already a partition".to_string(),
+                ));
+            }
+        }
+        // Checked even with tmp_tmpfs off so a typo surfaces before it is enabled
+        if let Some(ref size) = self.system.tmp_tmpfs_size {
+            if !crate::install::is_valid_tmpfs_size(size) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid tmp_tmpfs_size '{}' (expected e.g. 4G, 512M or 25%)",
+                    size
+                )));
+            }
+        }
+        if self.disk.zram_log_mib > 0 && self.disk.zram_log_sync_minutes == 0 {
+            return Err(DeploytixError::ValidationError(
+                "zram_log_sync_minutes must be greater than 0".to_string(),
+            ));
+        }
+
+        // SecureBoot with ManualKeys requires keys path
+        if self.system.secureboot
+            && self.system.secureboot_method == SecureBootMethod::ManualKeys
+            && self.system.secureboot_keys_path.is_none()
+        {
+            return Err(DeploytixError::ValidationError(
+                "SecureBoot with ManualKeys method requires secureboot_keys_path".to_string(),
+            ));
+        }
+
+        // Blacklisted module names end up in modprobe.d and on the kernel
+        // command line, so restrict them to the kernel's module charset.
+        for m in &self.system.blacklist_modules {
+            if !crate::configure::modules::is_valid_module_name(m) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid kernel module name '{}' in blacklist_modules",
+                    m
+                )));
+            }
+        }
+        for m in &self.system.load_modules {
+            if !crate::configure::modules::is_valid_module_name(m) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid kernel module name '{}' in load_modules",
+                    m
+                )));
+            }
+            let normalized = crate::configure::modules::normalize(m);
+            if self
+                .system
+                .blacklist_modules
+                .iter()
+                .any(|b| crate::configure::modules::normalize(b) == normalized)
+            {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Kernel module '{}' is both blacklisted and in load_modules",
+                    m
+                )));
+            }
+        }
+
+        for m in &self.system.initramfs_modules {
+            if !crate::configure::modules::is_valid_module_name(m) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid kernel module name '{}' in initramfs_modules",
+                    m
+                )));
+            }
+        }
+        for b in &self.system.initramfs_binaries {
+            if !crate::configure::mkinitcpio::is_valid_binary(b) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid program '{}' in initramfs_binaries (expected a name or absolute path)",
+                    b
+                )));
+            }
+        }
+
+        // Kernel parameters are written quoted into /etc/default/grub and
+        // refind_linux.conf, one whitespace-separated token each.
+        for p in &self.system.kernel_params {
+            if p.is_empty() || p.contains(|c: char| c.is_whitespace() || "\"'\\$`".contains(c)) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid kernel parameter '{}' in kernel_params",
+                    p
+                )));
+            }
+        }
+
+        // grub_default and grub_distributor are written quoted into
+        // /etc/default/grub
+        for (key, value) in [
+            ("grub_default", &self.system.grub_default),
+            ("grub_distributor", &self.system.grub_distributor),
+        ] {
+            if let Some(value) = value {
+                if value.is_empty() || value.contains(['"', '\\', '\n', '$', '`']) {
+                    return Err(DeploytixError::ValidationError(format!(
+                        "Invalid {} '{}'",
+                        key, value
+                    )));
+                }
+            }
+        }
+        if self.system.grub_os_prober && self.system.bootloader != Bootloader::Grub {
+            return Err(DeploytixError::ValidationError(format!(
+                "grub_os_prober only applies to GRUB; {} finds other systems on its own",
+                self.system.bootloader
+            )));
+        }
+
+        for pkg in &self.system.firmware_packages {
+            if !crate::configure::microcode::is_firmware_package(pkg) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "'{}' in firmware_packages is not a linux-firmware package",
+                    pkg
+                )));
+            }
+        }
+
+        if let Some(ref algo) = self.system.initramfs_compression {
+            if !crate::configure::mkinitcpio::INITRAMFS_COMPRESSIONS.contains(&algo.as_str()) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Unsupported initramfs_compression '{}' (expected one of: {})",
+                    algo,
+                    crate::configure::mkinitcpio::INITRAMFS_COMPRESSIONS.join(", ")
+                )));
+            }
+        }
+
+        for (i, key) in self.system.root_authorized_keys.iter().enumerate() {
+            if let Err(reason) = crate::configure::ssh::validate_authorized_key(key) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid SSH key #{} in root_authorized_keys: {}",
+                    i + 1,
+                    reason
+                )));
+            }
+        }
+
+        for service in &self.system.extra_services {
+            if service.is_empty()
+                || !service
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "._@-".contains(c))
+            {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid service name '{}' in extra_services",
+                    service
+                )));
+            }
+            // Artix has no systemd; its units would never be enabled
+            if crate::configure::services::is_systemd_unit(service) {
+                return Err(DeploytixError::ValidationError(format!(
+                    "'{}' in extra_services is a systemd unit, which {} cannot enable; \
+                     list the {} service name instead (e.g. 'cronie', not 'cronie.service')",
+                    service, self.system.init, self.system.init
+                )));
+            }
+        }
+
+        // Without keys, disabling passwords leaves no way to log in over SSH
+        if self.system.ssh
+            && self.system.ssh_password_authentication == Some(false)
+            && self.system.root_authorized_keys.is_empty()
+        {
+            return Err(DeploytixError::ValidationError(
+                "ssh_password_authentication = false needs root_authorized_keys, \
+                 otherwise nobody can log in over SSH"
+                    .to_string(),
+            ));
+        }
+
+        // Partition list validation
         let partitions = &self.disk.partitions;
 
         if partitions.is_empty() {
@@ -1358,7 +3140,10 @@ impl DeploymentConfig {
 
         // Reserved mount points
         for p in partitions {
-            if p.mount_point == "/boot" || p.mount_point == "/boot/efi" {
+            if p.mount_point == "/boot"
+                || p.mount_point == "/boot/efi"
+                || p.mount_point == self.disk.esp_mountpoint.path()
+            {
                 return Err(DeploytixError::ValidationError(format!(
                     "Mount point '{}' is reserved for system partitions",
                     p.mount_point
@@ -1395,6 +3180,57 @@ impl DeploymentConfig {
             }
         }
 
+        // Per-partition filesystem overrides apply to plain data partitions
+        for p in partitions {
+            let Some(ref fs) = p.filesystem else {
+                continue;
+            };
+            if *fs == self.disk.filesystem {
+                continue;
+            }
+            let reason = if *fs == Filesystem::Zfs || self.disk.filesystem == Filesystem::Zfs {
+                Some("ZFS pools cannot be mixed with other filesystems")
+            } else if self.disk.use_lvm_thin {
+                Some("LVM thin volumes all use disk.filesystem")
+            } else if self.disk.uses_luks() {
+                Some("LUKS containers all use disk.filesystem")
+            } else if p.mount_point == "/" && self.disk.filesystem == Filesystem::Btrfs {
+                Some("the root partition carries the btrfs subvolumes")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Partition '{}' cannot override the filesystem to {}: {}",
+                    p.mount_point, fs, reason
+                )));
+            }
+        }
+
+        // Per-partition mount options go into fstab verbatim
+        for p in partitions {
+            if let Some(ref opts) = p.mount_options {
+                if self.disk.filesystem == Filesystem::Zfs {
+                    return Err(DeploytixError::ValidationError(format!(
+                        "mount_options on partition '{}' are not supported with ZFS (use dataset properties)",
+                        p.mount_point
+                    )));
+                }
+                if !crate::install::is_valid_mount_options(opts) {
+                    return Err(DeploytixError::ValidationError(format!(
+                        "Invalid mount_options '{}' for partition '{}': must be a comma-separated list without spaces",
+                        opts, p.mount_point
+                    )));
+                }
+                if opts.split(',').any(|o| o.starts_with("subvol")) {
+                    return Err(DeploytixError::ValidationError(format!(
+                        "mount_options for partition '{}' must not set subvol (subvolumes are managed by the layout)",
+                        p.mount_point
+                    )));
+                }
+            }
+        }
+
         // Session switching requires gaming + a desktop environment
         if self.packages.install_session_switching {
             if !self.packages.install_gaming {
@@ -1428,6 +3264,41 @@ impl DeploymentConfig {
                         .to_string(),
                 ));
             }
+            // The switch scripts run `sudo setsid sh -c ...` non-interactively
+            if self.sudo.nopasswd == Some(false) || !self.sudo.commands.is_empty() {
+                return Err(DeploytixError::ValidationError(
+                    "Session switching requires unrestricted passwordless sudo \
+                     (sudo.nopasswd unset or true, no sudo.commands)"
+                        .to_string(),
+                ));
+            }
+        }
+
+        if let Some(ref shell) = self.user.shell {
+            let plausible = shell.starts_with('/')
+                && !shell.ends_with('/')
+                && !shell.contains("..")
+                && shell
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "/._+-".contains(c));
+            if !plausible {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid login shell '{}': must be an absolute path such as /usr/bin/zsh",
+                    shell
+                )));
+            }
+        }
+
+        for command in &self.sudo.commands {
+            if !command.starts_with('/')
+                || command.contains(',')
+                || command.contains(char::is_control)
+            {
+                return Err(DeploytixError::ValidationError(format!(
+                    "Invalid sudo command '{}': must be an absolute path, optionally with arguments, and contain no commas",
+                    command
+                )));
+            }
         }
 
         // Wi-Fi pre-seeding sanity checks
@@ -1494,6 +3365,12 @@ impl DeploymentConfig {
             }
         }
 
+        if let Some(ref dir) = self.packages.pacman_cache_dir {
+            crate::install::check_package_cache_dir(dir).map_err(|reason| {
+                DeploytixError::ValidationError(format!("pacman_cache_dir {}: {}", dir, reason))
+            })?;
+        }
+
         // AUR extras require yay
         if !self.packages.extra_packages.aur.is_empty() && !self.packages.install_yay {
             return Err(DeploytixError::ValidationError(
@@ -1523,6 +3400,62 @@ impl DeploymentConfig {
 mod tests {
     use super::*;
 
+    // ── RecoveryPassphrase ───────────────────────────────────────────────────
+
+    #[test]
+    fn recovery_passphrase_is_never_serialized_in_plaintext() {
+        let mut config = DeploymentConfig::sample();
+        let parse = |toml: &str| -> RecoveryPassphrase {
+            let mut c = toml::to_string(&DeploymentConfig::sample()).unwrap();
+            c = c.replace(
+                "[disk]\n",
+                &format!("[disk]\nrecovery_passphrase = {}\n", toml),
+            );
+            toml::from_str::<DeploymentConfig>(&c)
+                .unwrap()
+                .disk
+                .recovery_passphrase
+        };
+        assert_eq!(parse("true"), RecoveryPassphrase::Prompt);
+        assert_eq!(parse("false"), RecoveryPassphrase::Disabled);
+        assert_eq!(
+            parse("\"spare key\""),
+            RecoveryPassphrase::Value("spare key".into())
+        );
+
+        config.disk.recovery_passphrase = RecoveryPassphrase::Value("spare key".into());
+        let saved = toml::to_string(&config).unwrap();
+        assert!(!saved.contains("spare key"));
+        assert!(saved.contains("recovery_passphrase = true"));
+    }
+
+    #[test]
+    fn desktop_profile_defaults_to_full_and_parses_lowercase() {
+        let saved = toml::to_string(&DeploymentConfig::sample()).unwrap();
+        let parse = |profile: Option<&str>| -> DesktopProfile {
+            let mut c = saved.replace("profile = \"full\"\n", "");
+            if let Some(p) = profile {
+                c = c.replace("[desktop]\n", &format!("[desktop]\nprofile = \"{}\"\n", p));
+            }
+            toml::from_str::<DeploymentConfig>(&c)
+                .unwrap()
+                .desktop
+                .profile
+        };
+        assert_eq!(parse(None), DesktopProfile::Full);
+        assert_eq!(parse(Some("minimal")), DesktopProfile::Minimal);
+        assert_eq!(parse(Some("full")), DesktopProfile::Full);
+
+        let labels: Vec<String> = DesktopProfile::ALL.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            labels,
+            [
+                "Full (desktop + applications)",
+                "Minimal (core desktop only)"
+            ]
+        );
+    }
+
     // ── CustomPartitionEntry::effective_label ────────────────────────────────
 
     #[test]
@@ -1532,6 +3465,8 @@ mod tests {
             size_mib: 0,
             label: Some("MYDATA".into()),
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         };
         assert_eq!(p.effective_label(), "MYDATA");
     }
@@ -1543,6 +3478,8 @@ mod tests {
             size_mib: 0,
             label: None,
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         };
         assert_eq!(p.effective_label(), "ROOT");
     }
@@ -1561,6 +3498,8 @@ mod tests {
                 size_mib: 0,
                 label: None,
                 encryption: None,
+                filesystem: None,
+                mount_options: None,
             };
             assert_eq!(
                 p.effective_label(),
@@ -1580,6 +3519,8 @@ mod tests {
             size_mib: 0,
             label: None,
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         };
         assert!(p.is_encrypted(true), "should inherit global=true");
         assert!(!p.is_encrypted(false), "should inherit global=false");
@@ -1592,6 +3533,8 @@ mod tests {
             size_mib: 0,
             label: None,
             encryption: Some(true),
+            filesystem: None,
+            mount_options: None,
         };
         assert!(
             force_on.is_encrypted(false),
@@ -1603,6 +3546,8 @@ mod tests {
             size_mib: 0,
             label: None,
             encryption: Some(false),
+            filesystem: None,
+            mount_options: None,
         };
         assert!(
             !force_off.is_encrypted(true),
@@ -1610,6 +3555,289 @@ mod tests {
         );
     }
 
+    // ── Login shell ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn login_shell_packages_match_by_file_name() {
+        let mut user = DeploymentConfig::sample().user;
+        assert_eq!(user.shell_path(), "/bin/bash");
+        assert_eq!(user.shell_package(), Some("bash"));
+
+        user.shell = Some("/bin/zsh".into());
+        assert_eq!(user.shell_package(), Some("zsh"));
+        user.shell = Some("/usr/bin/nu".into());
+        assert_eq!(user.shell_package(), Some("nushell"));
+        user.shell = Some("/usr/bin/xonsh".into());
+        assert_eq!(user.shell_package(), None);
+    }
+
+    #[test]
+    fn unknown_shell_without_package_is_linted() {
+        let mut cfg = DeploymentConfig::sample();
+        assert!(cfg.shell_package_lint().is_none());
+
+        cfg.user.shell = Some("/usr/bin/xonsh".into());
+        assert!(cfg.shell_package_lint().unwrap().contains("xonsh"));
+
+        cfg.packages.extra_packages.pacman.push("xonsh".into());
+        assert!(cfg.shell_package_lint().is_none());
+    }
+
+    #[test]
+    fn firewall_closing_ssh_is_linted() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.ssh = true;
+        cfg.system.firewall = Firewall::Nftables;
+        assert!(cfg.firewall_ssh_lint().is_none());
+
+        cfg.system.firewall_open_ssh = Some(false);
+        assert!(cfg.firewall_ssh_lint().unwrap().contains("port 22"));
+    }
+
+    // ── DiskConfig labels ────────────────────────────────────────────────────
+
+    /// The disk settings the GUI produces for `filesystem`: subvolumes only
+    /// on btrfs, partition swap.
+    fn gui_style_config(filesystem: Filesystem, encryption: bool) -> DeploymentConfig {
+        let mut cfg = DeploymentConfig::sample();
+        // sample()'s iwd frontend (iwgtk) comes from the AUR
+        cfg.packages.install_yay = true;
+        cfg.disk.boot_filesystem = boot_filesystem_for(&filesystem);
+        cfg.disk.use_subvolumes = filesystem == Filesystem::Btrfs;
+        cfg.disk.filesystem = filesystem;
+        cfg.disk.swap_type = SwapType::Partition;
+        cfg.disk.encryption = encryption;
+        cfg.disk.encryption_password = encryption.then(|| "Correct-horse-9".to_string());
+        cfg
+    }
+
+    #[test]
+    fn every_filesystem_choice_passes_validation() {
+        for filesystem in [
+            Filesystem::Btrfs,
+            Filesystem::Ext4,
+            Filesystem::Xfs,
+            Filesystem::F2fs,
+            Filesystem::Zfs,
+        ] {
+            for encryption in [false, true] {
+                let cfg = gui_style_config(filesystem.clone(), encryption);
+                assert!(
+                    cfg.validate_settings().is_ok(),
+                    "{} (encryption {}): {:?}",
+                    filesystem,
+                    encryption,
+                    cfg.validate_settings()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn btrfs_only_features_are_rejected_on_xfs_and_f2fs() {
+        for filesystem in [Filesystem::Xfs, Filesystem::F2fs] {
+            let mut cfg = gui_style_config(filesystem.clone(), false);
+            cfg.disk.use_subvolumes = true;
+            assert!(
+                cfg.validate_settings().is_err(),
+                "{} subvolumes",
+                filesystem
+            );
+
+            let mut cfg = gui_style_config(filesystem.clone(), false);
+            cfg.disk.swap_type = SwapType::FileZram;
+            assert!(cfg.validate_settings().is_err(), "{} swap file", filesystem);
+        }
+    }
+
+    #[test]
+    fn label_overrides_apply_case_insensitively() {
+        let mut disk = DeploymentConfig::sample().disk;
+        disk.labels.insert("boot".into(), "BOOT2".into());
+        assert_eq!(disk.fs_label("BOOT"), "BOOT2");
+        assert_eq!(disk.fs_label("EFI"), "EFI");
+        assert!(disk.validate_labels().is_ok());
+    }
+
+    #[test]
+    fn label_overrides_are_validated() {
+        let mut disk = DeploymentConfig::sample().disk;
+        disk.labels.insert("EFI".into(), "ARTIX_EFI_LONG".into());
+        assert!(disk.validate_labels().unwrap_err().contains("11"));
+
+        disk.labels.clear();
+        disk.labels.insert("HOME".into(), "ROOT".into());
+        assert!(disk.validate_labels().unwrap_err().contains("Duplicate"));
+
+        disk.labels.clear();
+        disk.labels.insert("NOPE".into(), "X".into());
+        assert!(disk.validate_labels().is_err());
+
+        // The limit follows a partition's own filesystem
+        disk.labels.clear();
+        disk.filesystem = Filesystem::Btrfs;
+        disk.labels.insert("HOME".into(), "ARTIX_HOME_XL".into());
+        assert!(disk.validate_labels().is_ok());
+        let home = disk
+            .partitions
+            .iter_mut()
+            .find(|p| p.mount_point == "/home")
+            .unwrap();
+        home.filesystem = Some(Filesystem::Xfs);
+        assert!(disk.validate_labels().unwrap_err().contains("12"));
+    }
+
+    // ── integrity_trim_lint ──────────────────────────────────────────────────
+
+    #[test]
+    fn integrity_on_ssd_warns_until_acknowledged() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.disk.encryption = true;
+        cfg.disk.integrity = true;
+        assert!(cfg.integrity_trim_lint(false).is_some());
+        assert!(cfg.integrity_trim_lint(true).is_none(), "HDDs have no TRIM");
+
+        cfg.disk.integrity_trim_acknowledged = true;
+        assert!(cfg.integrity_trim_lint(false).is_none());
+    }
+
+    #[test]
+    fn integrity_no_wipe_needs_integrity() {
+        let no_wipe_error = |cfg: &DeploymentConfig| {
+            cfg.validate_settings()
+                .is_err_and(|e| e.to_string().contains("integrity_no_wipe"))
+        };
+        let mut cfg = DeploymentConfig::sample();
+        cfg.disk.integrity_no_wipe = true;
+        assert!(no_wipe_error(&cfg));
+
+        cfg.disk.encryption = true;
+        cfg.disk.encryption_password = Some("pw".to_string());
+        cfg.disk.integrity = true;
+        assert!(!no_wipe_error(&cfg));
+    }
+
+    #[test]
+    fn initramfs_compression_must_be_supported() {
+        let compression_error = |cfg: &DeploymentConfig| {
+            cfg.validate_settings()
+                .is_err_and(|e| e.to_string().contains("initramfs_compression"))
+        };
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.initramfs_compression = Some("brotli".to_string());
+        assert!(compression_error(&cfg));
+
+        cfg.system.initramfs_compression = Some("xz".to_string());
+        assert!(!compression_error(&cfg));
+    }
+
+    #[test]
+    fn initramfs_additions_must_be_plain_names() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.initramfs_modules = vec!["mpt3sas".to_string(), "bad name".to_string()];
+        assert!(cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("initramfs_modules")));
+
+        cfg.system.initramfs_modules.pop();
+        cfg.system.initramfs_binaries = vec!["mdadm".to_string(), "$(reboot)".to_string()];
+        assert!(cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("initramfs_binaries")));
+
+        cfg.system.initramfs_binaries = vec!["/usr/bin/nvme".to_string()];
+        assert!(!cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("initramfs_")));
+    }
+
+    #[test]
+    fn blacklisted_modules_cannot_be_loaded_under_either_spelling() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.blacklist_modules = vec!["nvidia-drm".to_string()];
+        cfg.system.load_modules = vec!["nvidia_drm".to_string()];
+        assert!(cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("both blacklisted")));
+    }
+
+    #[test]
+    fn loading_nouveau_with_nvidia_drivers_is_not_a_conflict() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.packages.gpu_drivers = vec![GpuDriverVendor::Nvidia];
+        cfg.system.load_modules = vec!["nouveau".to_string()];
+        assert!(!cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("both blacklisted")));
+        assert!(crate::configure::modules::effective_blacklist(&cfg).is_empty());
+    }
+
+    #[test]
+    fn tmpfs_size_is_validated() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.tmp_tmpfs_size = Some("4GB".to_string());
+        assert!(cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("tmp_tmpfs_size")));
+
+        cfg.system.tmp_tmpfs = true;
+        assert!(cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("tmp_tmpfs_size")));
+
+        cfg.system.tmp_tmpfs_size = Some("25%".to_string());
+        assert!(!cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("tmp_tmpfs_size")));
+    }
+
+    #[test]
+    fn session_switching_accepts_an_unset_sudo_policy() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.packages.install_gaming = true;
+        cfg.packages.install_session_switching = true;
+        cfg.desktop.display_manager = DisplayManager::Greetd;
+        cfg.network.backend = NetworkBackend::NetworkManager;
+        assert!(!cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("passwordless sudo")));
+
+        cfg.sudo.nopasswd = Some(false);
+        assert!(cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("passwordless sudo")));
+    }
+
+    #[test]
+    fn systemd_units_are_rejected_in_extra_services() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.extra_services = vec!["cronie.service".to_string()];
+        let err = cfg.validate_settings().unwrap_err().to_string();
+        assert!(err.contains("systemd unit"), "{}", err);
+
+        cfg.system.extra_services = vec!["cronie".to_string()];
+        assert!(!cfg
+            .validate_settings()
+            .is_err_and(|e| e.to_string().contains("systemd unit")));
+    }
+
+    #[test]
+    fn mismatched_login_screen_is_flagged() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.desktop.environment = DesktopEnvironment::Gnome;
+        cfg.desktop.display_manager = DisplayManager::Greetd;
+        assert!(cfg.display_manager_lint().is_none());
+        cfg.desktop.display_manager = DisplayManager::Gdm;
+        assert!(cfg.display_manager_lint().is_none());
+
+        cfg.desktop.display_manager = DisplayManager::Sddm;
+        let lint = cfg.display_manager_lint().unwrap();
+        assert!(lint.contains("GNOME") && lint.contains("display_manager = \"gdm\""));
+
+        cfg.desktop.environment = DesktopEnvironment::None;
+        assert!(cfg.display_manager_lint().is_none());
+    }
+
     // ── InitSystem methods ───────────────────────────────────────────────────
 
     #[test]
@@ -1,13 +1,15 @@
 //! Bootloader installation and configuration
 
-use crate::config::{Bootloader, DeploymentConfig, SecureBootMethod};
+use crate::config::{Bootloader, DeploymentConfig, Firmware, SecureBootMethod, SwapType};
 use crate::configure::encryption::get_luks_uuid;
+use crate::configure::microcode;
+use crate::configure::swap;
 use crate::disk::detection::partition_path;
 use crate::disk::formatting::get_partition_uuid;
 use crate::disk::layouts::ComputedLayout;
 use crate::disk::lvm;
 use crate::utils::command::CommandRunner;
-use crate::utils::error::Result;
+use crate::utils::error::{DeploytixError, Result};
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use tracing::{info, warn};
@@ -32,6 +34,7 @@ pub fn install_bootloader(
 ) -> Result<()> {
     match config.system.bootloader {
         Bootloader::Grub => install_grub(cmd, config, device, layout, install_root),
+        Bootloader::Refind => install_refind(cmd, config, device, layout, install_root),
     }
 }
 
@@ -45,14 +48,18 @@ pub fn install_bootloader_with_layout(
 ) -> Result<()> {
     match config.system.bootloader {
         Bootloader::Grub => install_grub_with_layout(cmd, config, device, layout, install_root),
+        Bootloader::Refind => install_refind(cmd, config, device, layout, install_root),
     }
 }
 
-/// Get the swap partition UUID from the layout, if a swap partition exists.
-fn get_swap_uuid_from_layout(
+/// Hibernation resume parameters: the swap partition from the layout, or
+/// the filesystem holding the swap file plus the file's offset in it.
+fn resume_params(
     cmd: &CommandRunner,
+    config: &DeploymentConfig,
     device: &str,
     layout: &ComputedLayout,
+    install_root: &str,
 ) -> Result<Option<String>> {
     if let Some(swap_part) = layout.partitions.iter().find(|p| p.is_swap) {
         let swap_device = partition_path(device, swap_part.number);
@@ -61,7 +68,9 @@ fn get_swap_uuid_from_layout(
         } else {
             get_partition_uuid(&swap_device)?
         };
-        Ok(Some(uuid))
+        Ok(Some(format!("resume=UUID={}", uuid)))
+    } else if config.system.hibernation && config.disk.swap_type == SwapType::FileZram {
+        swap::swap_file_resume_params(cmd, config, install_root).map(Some)
     } else {
         Ok(None)
     }
@@ -75,10 +84,14 @@ fn install_grub(
     layout: &ComputedLayout,
     install_root: &str,
 ) -> Result<()> {
-    info!("Installing GRUB bootloader to {} (x86_64-efi)", device);
+    info!(
+        "Installing GRUB bootloader to {} ({})",
+        device,
+        config.disk.firmware()
+    );
 
     // If encryption or LVM thin is active, should use install_grub_with_layout
-    if config.disk.encryption || config.disk.use_lvm_thin {
+    if config.disk.uses_luks() || config.disk.use_lvm_thin {
         return Err(crate::utils::error::DeploytixError::ConfigError(
             "Encrypted or LVM thin systems require install_bootloader_with_layout".to_string(),
         ));
@@ -108,7 +121,7 @@ fn install_grub(
 
     // Configure GRUB defaults
     let uses_subvolumes = config.disk.use_subvolumes;
-    let swap_uuid = get_swap_uuid_from_layout(cmd, device, layout)?;
+    let resume = resume_params(cmd, config, device, layout, install_root)?;
     configure_grub_defaults(
         cmd,
         config,
@@ -117,10 +130,10 @@ fn install_grub(
         uses_subvolumes,
         false,
         install_root,
-        swap_uuid.as_deref(),
+        resume.as_deref(),
     )?;
 
-    run_grub_install(cmd, device, install_root)?;
+    run_grub_install(cmd, config, device, layout, install_root)?;
 
     info!("GRUB installation complete");
     Ok(())
@@ -135,15 +148,16 @@ fn install_grub_with_layout(
     install_root: &str,
 ) -> Result<()> {
     info!(
-        "Installing GRUB bootloader to {} (x86_64-efi, encrypted)",
-        device
+        "Installing GRUB bootloader to {} ({}, encrypted)",
+        device,
+        config.disk.firmware()
     );
 
     // Find LUKS partition from layout
     let luks_part = layout.partitions.iter().find(|p| p.is_luks);
 
-    // Get swap UUID for hibernation resume parameter
-    let swap_uuid = get_swap_uuid_from_layout(cmd, device, layout)?;
+    // Hibernation resume parameters (swap partition or swap file)
+    let resume = resume_params(cmd, config, device, layout, install_root)?;
 
     if config.disk.use_lvm_thin && config.disk.encryption {
         // LVM thin + encryption: encrypt hook needs cryptdevice= parameter,
@@ -159,13 +173,7 @@ fn install_grub_with_layout(
         } else {
             get_luks_uuid(&luks_device)?
         };
-        configure_grub_defaults_lvm_thin(
-            cmd,
-            config,
-            &luks_uuid,
-            install_root,
-            swap_uuid.as_deref(),
-        )?;
+        configure_grub_defaults_lvm_thin(cmd, config, &luks_uuid, install_root, resume.as_deref())?;
     } else if config.disk.use_lvm_thin {
         // LVM thin without encryption: root is on an LVM LV
         let vg_name = &config.disk.lvm_vg_name;
@@ -183,7 +191,7 @@ fn install_grub_with_layout(
             layout.uses_subvolumes(),
             false,
             install_root,
-            swap_uuid.as_deref(),
+            resume.as_deref(),
         )?;
     } else if let Some(luks) = luks_part {
         // Multi-LUKS: configure with mapper name for root
@@ -201,7 +209,7 @@ fn install_grub_with_layout(
             layout.uses_subvolumes(),
             config.disk.boot_encryption,
             install_root,
-            swap_uuid.as_deref(),
+            resume.as_deref(),
         )?;
     } else {
         // No LUKS, no LVM thin — should not reach here from install_bootloader_with_layout
@@ -210,40 +218,141 @@ fn install_grub_with_layout(
 
     // Use SecureBoot-aware install if SecureBoot is enabled
     if config.system.secureboot {
-        run_grub_install_with_secureboot(cmd, config, device, install_root)?;
+        run_grub_install_with_secureboot(cmd, config, device, layout, install_root)?;
     } else {
-        run_grub_install(cmd, device, install_root)?;
+        run_grub_install(cmd, config, device, layout, install_root)?;
     }
 
     info!("GRUB installation complete");
     Ok(())
 }
 
-/// Run grub-install, grub-mkconfig, and create EFI boot entry
-fn run_grub_install(cmd: &CommandRunner, device: &str, install_root: &str) -> Result<()> {
+/// Removable-path EFI loader, relative to the ESP, written by
+/// `grub-install --removable` and the SecureBoot `grub-mkstandalone` image.
+pub const REMOVABLE_EFI_LOADER: &str = "EFI/BOOT/BOOTX64.EFI";
+
+/// NVRAM boot entry labels Deploytix creates: the regular GRUB entry, the
+/// SecureBoot standalone image and the mirror disk's copy.
+pub const BOOT_ENTRY_LABEL: &str = "Artix Linux";
+pub const SECUREBOOT_ENTRY_LABEL: &str = "Artix-SB";
+pub const MIRROR_ENTRY_LABEL: &str = "Artix Linux (mirror)";
+
+/// EFI loader GRUB is installed as, relative to the ESP.  An ESP shared
+/// with another system (`disk.existing_esp`) gets GRUB in its own
+/// `EFI/Artix` directory so the other system's fallback loader survives.
+pub fn efi_loader(config: &DeploymentConfig) -> &'static str {
+    if config.disk.existing_esp.is_some() {
+        "EFI/Artix/grubx64.efi"
+    } else {
+        REMOVABLE_EFI_LOADER
+    }
+}
+
+/// Partition number of the ESP in `layout`, which is not the first
+/// partition when installing next to an existing system.
+fn esp_partition_number(layout: &ComputedLayout) -> u32 {
+    layout
+        .partitions
+        .iter()
+        .find(|p| p.is_efi)
+        .map_or(1, |p| p.number)
+}
+
+/// grub-install invocation for the target firmware.
+///
+/// UEFI installs to the removable path on the ESP, passed explicitly as
+/// `--efi-directory` so both `/boot/efi` and `/efi` work; on a shared ESP
+/// it installs to `EFI/Artix` and the boot entry is created separately.
+/// BIOS embeds core.img in the BIOS boot partition and writes boot code to
+/// the MBR.
+fn grub_install_command(config: &DeploymentConfig, device: &str) -> String {
+    match config.disk.firmware() {
+        Firmware::Uefi if config.disk.existing_esp.is_some() => format!(
+            "grub-install --target=x86_64-efi --boot-directory=/boot --efi-directory={} --bootloader-id=Artix --no-nvram {}",
+            config.disk.esp_mountpoint.path(),
+            device
+        ),
+        Firmware::Uefi => format!(
+            "grub-install --target=x86_64-efi --boot-directory=/boot --efi-directory={} --removable {}",
+            config.disk.esp_mountpoint.path(),
+            device
+        ),
+        Firmware::Bios => format!(
+            "grub-install --target=i386-pc --boot-directory=/boot {}",
+            device
+        ),
+    }
+}
+
+/// Run grub-install, grub-mkconfig, and (on UEFI) create an EFI boot entry
+fn run_grub_install(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    device: &str,
+    layout: &ComputedLayout,
+    install_root: &str,
+) -> Result<()> {
+    let grub_install_cmd = grub_install_command(config, device);
+    let uefi = config.disk.firmware() == Firmware::Uefi;
+    let esp_part = esp_partition_number(layout);
+    let loader = format!("/{}", efi_loader(config));
+
     if cmd.is_dry_run() {
-        println!("  [dry-run] grub-install --target=x86_64-efi --boot-directory=/boot --efi-directory=/boot/efi --removable {}", device);
-        println!("  [dry-run] grub-mkconfig -o /boot/grub/grub.cfg");
-        println!("  [dry-run] efibootmgr --create --disk {} --part 1 --loader /EFI/BOOT/BOOTX64.EFI --label 'Artix Linux'", device);
+        cmd.log_dry_run(&grub_install_cmd);
+        cmd.log_dry_run("grub-mkconfig -o /boot/grub/grub.cfg");
+        if uefi {
+            cmd.log_dry_run(&format!(
+                "efibootmgr --create --disk {} --part {} --loader {} --label '{}'",
+                device, esp_part, loader, BOOT_ENTRY_LABEL
+            ));
+        }
         return Ok(());
     }
 
     // Install GRUB
-    let grub_install_cmd = format!(
-        "grub-install --target=x86_64-efi --boot-directory=/boot --efi-directory=/boot/efi --removable {}",
-        device
-    );
     cmd.run_in_chroot(install_root, &grub_install_cmd)?;
 
     // Generate GRUB config
     cmd.run_in_chroot(install_root, "grub-mkconfig -o /boot/grub/grub.cfg")?;
 
+    let grub_cfg = check_generated_grub_cfg(config, install_root);
+    if config.system.grub_os_prober {
+        let found = os_prober_entries(&grub_cfg);
+        if found.is_empty() {
+            warn!(
+                "os-prober found no other operating systems; run grub-mkconfig again after booting"
+            );
+        }
+        for entry in found {
+            info!("GRUB menu entry for another system: {}", entry);
+        }
+    }
+
     // Create EFI boot entry using efibootmgr (required for bootable system)
-    create_efi_boot_entry(cmd, device, 1, "Artix Linux")?;
+    if uefi {
+        create_efi_boot_entry(cmd, device, esp_part, &loader, BOOT_ENTRY_LABEL)?;
+    }
 
     Ok(())
 }
 
+/// Read the grub.cfg that grub-mkconfig just wrote and check it for the
+/// installed microcode.
+///
+/// grub-mkconfig loads /boot/*-ucode.img as early initrds on its own, so a
+/// missing image means it did not find the microcode that was installed.
+fn check_generated_grub_cfg(config: &DeploymentConfig, install_root: &str) -> String {
+    let grub_cfg =
+        fs::read_to_string(format!("{}/boot/grub/grub.cfg", install_root)).unwrap_or_default();
+    for image in microcode::missing_from_grub_cfg(config, &grub_cfg) {
+        warn!(
+            "grub.cfg does not load /boot/{}; CPU microcode will not be applied early",
+            image
+        );
+    }
+    grub_cfg
+}
+
 /// Run grub-install with SecureBoot signing
 ///
 /// For sbctl method with encryption, uses grub-mkstandalone to create a self-contained
@@ -252,19 +361,27 @@ pub fn run_grub_install_with_secureboot(
     cmd: &CommandRunner,
     config: &DeploymentConfig,
     device: &str,
+    layout: &ComputedLayout,
     install_root: &str,
 ) -> Result<()> {
     // For sbctl method with encryption, use standalone GRUB to avoid verification errors
     let use_standalone = config.system.secureboot
         && config.system.secureboot_method == SecureBootMethod::Sbctl
-        && config.disk.encryption;
+        && config.disk.uses_luks();
 
     if use_standalone {
         info!("Using standalone GRUB for SecureBoot with encryption");
-        run_grub_mkstandalone(cmd, device, install_root)?;
+        run_grub_mkstandalone(
+            cmd,
+            config,
+            device,
+            esp_partition_number(layout),
+            config.disk.esp_mountpoint.path(),
+            install_root,
+        )?;
     } else {
         // Standard GRUB install for non-encrypted or shim-based SecureBoot
-        run_grub_install(cmd, device, install_root)?;
+        run_grub_install(cmd, config, device, layout, install_root)?;
     }
 
     // Sign the EFI binaries if SecureBoot is enabled
@@ -283,13 +400,23 @@ pub fn run_grub_install_with_secureboot(
 /// - Has grub.cfg embedded in a memdisk
 /// - Uses --disable-shim-lock for sbctl-based signing
 /// - Avoids "verification requested but nobody cares" errors
-fn run_grub_mkstandalone(cmd: &CommandRunner, device: &str, install_root: &str) -> Result<()> {
+fn run_grub_mkstandalone(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    device: &str,
+    esp_part: u32,
+    esp: &str,
+    install_root: &str,
+) -> Result<()> {
     info!("Creating standalone GRUB EFI binary");
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] grub-mkconfig -o /boot/grub/grub.cfg");
-        println!("  [dry-run] grub-mkstandalone --format=x86_64-efi --output=/boot/efi/EFI/BOOT/BOOTX64.EFI --disable-shim-lock --modules=\"...\" boot/grub/grub.cfg=/boot/grub/grub.cfg");
-        println!("  [dry-run] efibootmgr --create --disk {} --part 1 --loader /EFI/BOOT/BOOTX64.EFI --label 'Artix-SB'", device);
+        cmd.log_dry_run("grub-mkconfig -o /boot/grub/grub.cfg");
+        cmd.log_dry_run(&format!("grub-mkstandalone --format=x86_64-efi --output={}/EFI/BOOT/BOOTX64.EFI --disable-shim-lock --modules=\"...\" boot/grub/grub.cfg=/boot/grub/grub.cfg", esp));
+        cmd.log_dry_run(&format!(
+            "efibootmgr --create --disk {} --part {} --loader /EFI/BOOT/BOOTX64.EFI --label '{}'",
+            device, esp_part, SECUREBOOT_ENTRY_LABEL
+        ));
         return Ok(());
     }
 
@@ -300,38 +427,87 @@ fn run_grub_mkstandalone(cmd: &CommandRunner, device: &str, install_root: &str)
 
     // Generate GRUB config
     cmd.run_in_chroot(install_root, "grub-mkconfig -o /boot/grub/grub.cfg")?;
+    check_generated_grub_cfg(config, install_root);
 
     // Ensure EFI directory exists
-    let efi_boot_dir = format!("{}/boot/efi/EFI/BOOT", install_root);
+    let efi_boot_dir = format!("{}{}/EFI/BOOT", install_root, esp);
     fs::create_dir_all(&efi_boot_dir)?;
 
     // Create standalone GRUB with embedded config and modules
     let grub_mkstandalone_cmd = format!(
         "grub-mkstandalone \
             --format=x86_64-efi \
-            --output=/boot/efi/EFI/BOOT/BOOTX64.EFI \
+            --output={}/EFI/BOOT/BOOTX64.EFI \
             --disable-shim-lock \
             --modules=\"{}\" \
             \"boot/grub/grub.cfg=/boot/grub/grub.cfg\"",
-        GRUB_STANDALONE_MODULES
+        esp, GRUB_STANDALONE_MODULES
     );
     cmd.run_in_chroot(install_root, &grub_mkstandalone_cmd)?;
 
     // Create EFI boot entry with SecureBoot label
-    create_efi_boot_entry(cmd, device, 1, "Artix-SB")?;
+    create_efi_boot_entry(
+        cmd,
+        device,
+        esp_part,
+        &format!("/{}", REMOVABLE_EFI_LOADER),
+        SECUREBOOT_ENTRY_LABEL,
+    )?;
 
     info!("Standalone GRUB created successfully");
     Ok(())
 }
 
+/// Whether this host exposes writable EFI variables, which NVRAM boot
+/// entries need.  A live system booted in BIOS/CSM mode has none, and
+/// efivarfs may be mounted read-only.
+pub fn efivars_usable() -> bool {
+    let readable = fs::read_dir("/sys/firmware/efi/efivars")
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false);
+    readable && efivarfs_writable(&fs::read_to_string("/proc/mounts").unwrap_or_default())
+}
+
+/// Whether the `/proc/mounts` contents `mounts` show efivarfs mounted
+/// read-write.
+fn efivarfs_writable(mounts: &str) -> bool {
+    mounts.lines().any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        fields.len() >= 4 && fields[2] == "efivarfs" && fields[3].split(',').any(|opt| opt == "rw")
+    })
+}
+
+/// Tell the user that the NVRAM entry for `loader` could not be created
+/// and how to add it once booted in UEFI mode.
+fn warn_missing_boot_entry(device: &str, efi_partition: u32, loader: &str, label: &str, why: &str) {
+    warn!("==================================================================");
+    warn!("No UEFI boot entry was created for '{}': {}", label, why);
+    warn!(
+        "The target still boots through the removable fallback path {}; \
+         to add the entry, boot any UEFI live system and run:",
+        loader
+    );
+    warn!(
+        "  efibootmgr --create --disk {} --part {} --loader {} --label '{}'",
+        device, efi_partition, loader, label
+    );
+    warn!("==================================================================");
+}
+
 /// Create EFI boot entry using efibootmgr
 ///
 /// This is REQUIRED after grub-install to register the boot entry in UEFI firmware.
 /// Without this, the system will not present a bootable option after reboot.
+///
+/// A loader at the removable fallback path (`/EFI/BOOT/BOOTX64.EFI`) boots
+/// without an entry, so for it a host without usable EFI variables (live
+/// system booted in BIOS/CSM mode, common on VPSes) or a failing
+/// efibootmgr only produces a warning.  Any other loader needs the entry.
 pub fn create_efi_boot_entry(
     cmd: &CommandRunner,
     device: &str,
     efi_partition: u32,
+    loader: &str,
     label: &str,
 ) -> Result<()> {
     info!(
@@ -340,33 +516,29 @@ pub fn create_efi_boot_entry(
     );
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] efibootmgr --create --disk {} --part {} --loader /EFI/BOOT/BOOTX64.EFI --label '{}'",
-            device, efi_partition, label);
+        cmd.log_dry_run(&format!(
+            "efibootmgr --create --disk {} --part {} --loader {} --label '{}'",
+            device, efi_partition, loader, label
+        ));
         return Ok(());
     }
 
-    // NVRAM entries require EFI variables, which are absent when the
-    // installing host is BIOS-booted (common on VPSes) or the chroot has no
-    // efivarfs. The loader sits at the removable fallback path
-    // /EFI/BOOT/BOOTX64.EFI, which UEFI firmware boots without an NVRAM
-    // entry, so the target stays bootable — skip registration instead of
-    // failing the install.
-    let efivars = std::path::Path::new("/sys/firmware/efi/efivars");
-    let efivars_usable = fs::read_dir(efivars)
-        .map(|mut entries| entries.next().is_some())
-        .unwrap_or(false);
-    if !efivars_usable {
-        warn!(
-            "EFI variables unavailable on this host; skipping efibootmgr registration for '{}' \
-             (the removable-path loader /EFI/BOOT/BOOTX64.EFI boots without an NVRAM entry)",
-            label
-        );
+    let has_fallback = loader.trim_start_matches('/') == REMOVABLE_EFI_LOADER;
+    if !efivars_usable() {
+        let why = "EFI variables are not available or not writable on this host \
+                   (was the live system booted in BIOS/CSM mode?)";
+        if !has_fallback {
+            return Err(DeploytixError::CommandFailed {
+                command: format!("efibootmgr --create --loader {}", loader),
+                stderr: why.to_string(),
+            });
+        }
+        warn_missing_boot_entry(device, efi_partition, loader, label, why);
         return Ok(());
     }
 
     // Create boot entry pointing to GRUB's EFI binary
-    // --removable flag in grub-install places it at /EFI/BOOT/BOOTX64.EFI
-    cmd.run(
+    let created = cmd.run(
         "efibootmgr",
         &[
             "--create",
@@ -375,16 +547,69 @@ pub fn create_efi_boot_entry(
             "--part",
             &efi_partition.to_string(),
             "--loader",
-            "/EFI/BOOT/BOOTX64.EFI",
+            loader,
             "--label",
             label,
         ],
-    )?;
+    );
+    if let Err(e) = created {
+        if !has_fallback {
+            return Err(e);
+        }
+        warn_missing_boot_entry(device, efi_partition, loader, label, &e.to_string());
+        return Ok(());
+    }
 
     info!("EFI boot entry '{}' created successfully", label);
     Ok(())
 }
 
+/// Make the mirror disk bootable on its own.  On UEFI the finished ESP
+/// (signed binaries included) is copied onto the mirror's EFI partition
+/// and registered as a second boot entry; on BIOS GRUB is also embedded
+/// in the mirror's BIOS boot partition and MBR.  /boot itself is a btrfs
+/// RAID1 across both disks, so either copy finds the kernels.
+pub fn install_mirror_bootloader(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    layout: &ComputedLayout,
+    mirror: &str,
+    install_root: &str,
+) -> Result<()> {
+    info!("Installing the bootloader on mirror disk {}", mirror);
+
+    if config.disk.firmware() == Firmware::Bios {
+        cmd.run_in_chroot(install_root, &grub_install_command(config, mirror))?;
+        return Ok(());
+    }
+
+    let efi_part = layout.partitions.iter().find(|p| p.is_efi).ok_or_else(|| {
+        DeploytixError::ConfigError("No EFI partition found in layout".to_string())
+    })?;
+    let mirror_esp = partition_path(mirror, efi_part.number);
+    let esp_dir = format!("{}{}", install_root, config.disk.esp_mountpoint.path());
+    let mount_dir = crate::resources::runtime_path("mirror-esp");
+    let mount_dir = mount_dir.to_string_lossy();
+    if !cmd.is_dry_run() {
+        fs::create_dir_all(mount_dir.as_ref())?;
+    }
+
+    cmd.run("mount", &[&mirror_esp, &mount_dir])?;
+    let copied = cmd.run("cp", &["-a", &format!("{}/.", esp_dir), &mount_dir]);
+    if let Err(e) = cmd.run("umount", &[&mount_dir]) {
+        warn!("Failed to unmount {}: {}", mount_dir, e);
+    }
+    copied?;
+
+    create_efi_boot_entry(
+        cmd,
+        mirror,
+        efi_part.number,
+        &format!("/{}", REMOVABLE_EFI_LOADER),
+        MIRROR_ENTRY_LABEL,
+    )
+}
+
 /// Create a pacman hook that reinstalls GRUB after kernel or GRUB package updates.
 ///
 /// This is essential for systems with encrypted boot, standalone GRUB
@@ -426,7 +651,7 @@ pub fn create_grub_reinstall_hook(
     // Standalone GRUB is used when SecureBoot (sbctl) + encryption are both active
     let use_standalone = config.system.secureboot
         && config.system.secureboot_method == SecureBootMethod::Sbctl
-        && config.disk.encryption;
+        && config.disk.uses_luks();
 
     create_grub_reinstall_script(config, device, use_standalone, install_root)?;
 
@@ -526,7 +751,7 @@ grub-mkconfig -o /boot/grub/grub.cfg
 echo "Rebuilding standalone GRUB EFI binary..."
 grub-mkstandalone \
     --format=x86_64-efi \
-    --output=/boot/efi/EFI/BOOT/BOOTX64.EFI \
+    --output={esp}/EFI/BOOT/BOOTX64.EFI \
     --disable-shim-lock \
     --modules="$MODULES" \
     "boot/grub/grub.cfg=/boot/grub/grub.cfg"
@@ -534,6 +759,7 @@ grub-mkstandalone \
 echo "GRUB reinstallation complete"
 "#,
             modules = GRUB_STANDALONE_MODULES,
+            esp = config.disk.esp_mountpoint.path(),
             secureboot = secureboot_block,
         )
     } else {
@@ -549,12 +775,12 @@ set -e
 echo "Regenerating GRUB config..."
 grub-mkconfig -o /boot/grub/grub.cfg
 
-echo "Reinstalling GRUB to EFI..."
-grub-install --target=x86_64-efi --boot-directory=/boot --efi-directory=/boot/efi --removable {device}
+echo "Reinstalling GRUB..."
+{grub_install}
 {secureboot}
 echo "GRUB reinstallation complete"
 "#,
-            device = device,
+            grub_install = grub_install_command(config, device),
             secureboot = secureboot_block,
         )
     };
@@ -582,7 +808,7 @@ fn configure_grub_defaults(
     uses_subvolumes: bool,
     boot_encryption: bool,
     install_root: &str,
-    swap_uuid: Option<&str>,
+    resume: Option<&str>,
 ) -> Result<()> {
     let grub_default_path = format!("{}/etc/default/grub", install_root);
 
@@ -595,58 +821,45 @@ fn configure_grub_defaults(
         if uses_subvolumes {
             println!("    rootflags=subvol=@");
         }
-        return Ok(());
-    }
-
-    // Build kernel cmdline
-    let mut cmdline_parts = vec!["quiet".to_string()];
-
-    if let Some(mapper) = mapper_name {
-        // Encrypted system uses custom hooks (crypttab-unlock + mountcrypt)
-        // NOT the standard encrypt hook, so we don't use cryptdevice= parameter.
-        // The mountcrypt hook's mount_handler handles all mounting.
-        // Set root= to the mapper device so mkinitcpio knows what to pass to mount_handler.
-        cmdline_parts.push(format!("root=/dev/mapper/{}", mapper));
-        if uses_subvolumes {
-            cmdline_parts.push("rootflags=subvol=@".to_string());
+        if let Some(param) = crate::configure::modules::blacklist_cmdline_param(config) {
+            println!("    {}", param);
         }
-        cmdline_parts.push("rw".to_string());
-    } else if config.disk.filesystem == crate::config::Filesystem::Zfs {
-        // ZFS root: the zfs hook reads the root dataset from the kernel cmdline
-        cmdline_parts.push(format!(
-            "root=ZFS={}",
-            crate::disk::formatting::ZFS_ROOT_DATASET
-        ));
-        cmdline_parts.push("rw".to_string());
-    } else {
-        // Non-encrypted system
-        cmdline_parts.push(format!("root=UUID={}", root_or_luks_uuid));
-        // Only add rootflags=subvol=@ if layout uses btrfs subvolumes
-        if uses_subvolumes {
-            cmdline_parts.push("rootflags=subvol=@".to_string());
+        for line in grub_menu_settings(config)
+            .lines()
+            .chain(grub_os_settings(config).lines())
+        {
+            println!("    {}", line);
         }
-        cmdline_parts.push("rw".to_string());
-    }
-
-    // Add resume for hibernation
-    if config.system.hibernation {
-        if let Some(uuid) = swap_uuid {
-            cmdline_parts.push(format!("resume=UUID={}", uuid));
+        if !config.system.kernel_params.is_empty() {
+            println!("    {}", config.system.kernel_params.join(" "));
+        }
+        print_grub_microcode_dry_run(config);
+        if crate::disk::mirror::mirrors_btrfs_root(&config.disk) {
+            println!(
+                "  [dry-run] Would write {} (rootflags=degraded entries)",
+                GRUB_DEGRADED_SCRIPT
+            );
         }
+        return Ok(());
     }
 
-    let cmdline = cmdline_parts.join(" ");
+    let cmdline = kernel_cmdline(
+        config,
+        root_or_luks_uuid,
+        mapper_name,
+        uses_subvolumes,
+        resume,
+    );
 
     // Build GRUB config content
     let mut content = format!(
         r#"# GRUB boot loader configuration
 # Generated by Deploytix
 
-GRUB_DEFAULT=0
-GRUB_TIMEOUT=5
-GRUB_DISTRIBUTOR="Artix"
-GRUB_CMDLINE_LINUX_DEFAULT="{}"
+{}{}GRUB_CMDLINE_LINUX_DEFAULT="{}"
 "#,
+        grub_menu_settings(config),
+        grub_os_settings(config),
         cmdline
     );
 
@@ -655,14 +868,99 @@ GRUB_CMDLINE_LINUX_DEFAULT="{}"
     if boot_encryption {
         content.push_str("GRUB_ENABLE_CRYPTODISK=y\n");
     }
+    content.push_str(&grub_microcode_settings(config));
 
     fs::create_dir_all(format!("{}/etc/default", install_root))?;
     fs::write(&grub_default_path, content)?;
 
+    if crate::disk::mirror::mirrors_btrfs_root(&config.disk) {
+        let script_path = format!("{}{}", install_root, GRUB_DEGRADED_SCRIPT);
+        fs::write(&script_path, grub_degraded_script(&cmdline))?;
+        fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755))?;
+    }
+
     info!("GRUB defaults written to /etc/default/grub");
     Ok(())
 }
 
+/// Default GRUB menu timeout in seconds.
+const DEFAULT_GRUB_TIMEOUT: u32 = 5;
+
+/// `GRUB_DEFAULT`/`GRUB_TIMEOUT` lines for `/etc/default/grub`.
+///
+/// A timeout of 0 also hides the menu; holding Shift or Esc still shows it.
+fn grub_menu_settings(config: &DeploymentConfig) -> String {
+    let default = match &config.system.grub_default {
+        Some(entry) => format!("\"{}\"", entry),
+        None => "0".to_string(),
+    };
+    let timeout = config.system.grub_timeout.unwrap_or(DEFAULT_GRUB_TIMEOUT);
+
+    let mut lines = format!("GRUB_DEFAULT={}\nGRUB_TIMEOUT={}\n", default, timeout);
+    if timeout == 0 {
+        lines.push_str("GRUB_TIMEOUT_STYLE=hidden\n");
+    }
+    lines
+}
+
+/// Default `GRUB_DISTRIBUTOR`.
+pub const DEFAULT_GRUB_DISTRIBUTOR: &str = "Artix";
+
+/// `GRUB_DISTRIBUTOR` and os-prober lines for `/etc/default/grub`.
+///
+/// os-prober has been off by default since GRUB 2.06; turning it on makes
+/// grub-mkconfig mount the other partitions read-only and add menu
+/// entries for the systems it finds.
+fn grub_os_settings(config: &DeploymentConfig) -> String {
+    let distributor = config
+        .system
+        .grub_distributor
+        .as_deref()
+        .unwrap_or(DEFAULT_GRUB_DISTRIBUTOR);
+    let mut lines = format!("GRUB_DISTRIBUTOR=\"{}\"\n", distributor);
+    if config.system.grub_os_prober {
+        lines.push_str("GRUB_DISABLE_OS_PROBER=false\n");
+    }
+    lines
+}
+
+/// Menu entries grub-mkconfig added for other operating systems, i.e.
+/// those in the `30_os-prober` section of grub.cfg.
+fn os_prober_entries(grub_cfg: &str) -> Vec<String> {
+    grub_cfg
+        .lines()
+        .skip_while(|line| !line.starts_with("### BEGIN /etc/grub.d/30_os-prober"))
+        .take_while(|line| !line.starts_with("### END /etc/grub.d/30_os-prober"))
+        .filter_map(|line| {
+            let title = line.trim_start().strip_prefix("menuentry ")?;
+            let quote = title.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+            title[1..].split(quote).next().map(str::to_string)
+        })
+        .collect()
+}
+
+/// `/etc/default/grub` lines for early microcode loading.
+///
+/// `grub-mkconfig` already loads any `/boot/*-ucode.img` as an early
+/// initrd; when the microcode is bundled into the initramfs instead, that
+/// scan is switched off so it is not loaded twice.
+fn grub_microcode_settings(config: &DeploymentConfig) -> String {
+    if microcode::uses_microcode_hook(config) {
+        "GRUB_EARLY_INITRD_LINUX_STOCK=\"\"\n".to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn print_grub_microcode_dry_run(config: &DeploymentConfig) {
+    for image in microcode::early_initrd_images(config) {
+        println!("    early initrd: /boot/{}", image);
+    }
+    if microcode::uses_microcode_hook(config) {
+        println!("    GRUB_EARLY_INITRD_LINUX_STOCK=\"\" (microcode bundled in initramfs)");
+    }
+}
+
 /// Configure GRUB defaults for LvmThin encrypted layout
 ///
 /// LvmThin requires:
@@ -674,7 +972,7 @@ fn configure_grub_defaults_lvm_thin(
     config: &DeploymentConfig,
     luks_uuid: &str,
     install_root: &str,
-    swap_uuid: Option<&str>,
+    resume: Option<&str>,
 ) -> Result<()> {
     let grub_default_path = format!("{}/etc/default/grub", install_root);
     let vg_name = &config.disk.lvm_vg_name;
@@ -689,56 +987,495 @@ fn configure_grub_defaults_lvm_thin(
         if config.disk.boot_encryption {
             println!("    GRUB_ENABLE_CRYPTODISK=y");
         }
+        if let Some(param) = crate::configure::modules::blacklist_cmdline_param(config) {
+            println!("    {}", param);
+        }
+        for line in grub_menu_settings(config)
+            .lines()
+            .chain(grub_os_settings(config).lines())
+        {
+            println!("    {}", line);
+        }
+        if !config.system.kernel_params.is_empty() {
+            println!("    {}", config.system.kernel_params.join(" "));
+        }
+        print_grub_microcode_dry_run(config);
         return Ok(());
     }
 
-    // Build kernel cmdline
+    let cmdline = kernel_cmdline_lvm_thin(config, luks_uuid, resume);
+
+    let mut content = format!(
+        r#"# GRUB boot loader configuration
+# Generated by Deploytix - LVM Thin Provisioning layout
+
+{}{}GRUB_CMDLINE_LINUX_DEFAULT="{}"
+"#,
+        grub_menu_settings(config),
+        grub_os_settings(config),
+        cmdline
+    );
+
+    // Enable cryptodisk only when boot partition is encrypted (LUKS1)
+    // GRUB needs this to decrypt /boot at early boot stage
+    if config.disk.boot_encryption {
+        content.push_str("GRUB_ENABLE_CRYPTODISK=y\n");
+    }
+    content.push_str(&grub_microcode_settings(config));
+
+    fs::create_dir_all(format!("{}/etc/default", install_root))?;
+    fs::write(&grub_default_path, content)?;
+
+    info!("GRUB defaults (LvmThin) written to /etc/default/grub");
+    Ok(())
+}
+
+/// Kernel command line shared by every bootloader.
+///
+/// `mapper_name` is set for multi-volume encryption, where root is the
+/// mapper device opened by the crypttab-unlock/mountcrypt hooks; otherwise
+/// root is `root_uuid` (or the ZFS root dataset).
+fn kernel_cmdline(
+    config: &DeploymentConfig,
+    root_uuid: &str,
+    mapper_name: Option<&str>,
+    uses_subvolumes: bool,
+    resume: Option<&str>,
+) -> String {
+    let mut cmdline_parts = vec!["quiet".to_string()];
+
+    if let Some(mapper) = mapper_name {
+        // Encrypted system uses custom hooks (crypttab-unlock + mountcrypt)
+        // NOT the standard encrypt hook, so we don't use cryptdevice= parameter.
+        // The mountcrypt hook's mount_handler handles all mounting.
+        // Set root= to the mapper device so mkinitcpio knows what to pass to mount_handler.
+        cmdline_parts.push(format!("root=/dev/mapper/{}", mapper));
+        if uses_subvolumes {
+            cmdline_parts.push("rootflags=subvol=@".to_string());
+        }
+        cmdline_parts.push("rw".to_string());
+    } else if config.disk.filesystem == crate::config::Filesystem::Zfs {
+        // ZFS root: the zfs hook reads the root dataset from the kernel cmdline
+        cmdline_parts.push(format!(
+            "root=ZFS={}",
+            crate::disk::formatting::zfs_root_dataset(&config.zfs)
+        ));
+        cmdline_parts.push("rw".to_string());
+    } else {
+        // Non-encrypted system
+        cmdline_parts.push(format!("root=UUID={}", root_uuid));
+        // Only add subvol=@ if layout uses btrfs subvolumes
+        if uses_subvolumes {
+            cmdline_parts.push("rootflags=subvol=@".to_string());
+        }
+        cmdline_parts.push("rw".to_string());
+    }
+
+    push_common_params(config, &mut cmdline_parts, resume);
+    cmdline_parts.join(" ")
+}
+
+/// `cmdline` with `degraded` added to its rootflags, for the fallback entry
+/// that boots a btrfs RAID1 root with one disk of the mirror missing.  The
+/// default entries leave it out so a lost disk stops the boot instead of
+/// new writes silently landing as single copies.
+fn degraded_cmdline(cmdline: &str) -> String {
+    let mut parts: Vec<String> = cmdline.split(' ').map(str::to_string).collect();
+    match parts.iter_mut().find(|p| p.starts_with("rootflags=")) {
+        Some(flags) => flags.push_str(",degraded"),
+        None => {
+            let at = parts.iter().position(|p| p == "rw").unwrap_or(parts.len());
+            parts.insert(at, "rootflags=degraded".to_string());
+        }
+    }
+    parts.join(" ")
+}
+
+/// `/etc/grub.d` script adding the degraded-mirror entries: GRUB's own
+/// `10_linux` run again with the [`degraded_cmdline`].
+const GRUB_DEGRADED_SCRIPT: &str = "/etc/grub.d/11_degraded_mirror";
+
+fn grub_degraded_script(cmdline: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+# Generated by Deploytix: boot entries for the btrfs RAID1 root with one
+# disk of the mirror missing.
+export GRUB_CMDLINE_LINUX_DEFAULT="{}"
+export GRUB_DISTRIBUTOR="${{GRUB_DISTRIBUTOR:-Artix}} (degraded mirror)"
+export GRUB_DISABLE_RECOVERY=true
+export GRUB_DISABLE_SUBMENU=y
+exec /etc/grub.d/10_linux
+"#,
+        degraded_cmdline(cmdline)
+    )
+}
+
+/// Kernel command line for the encrypted LVM thin layout, where the
+/// standard encrypt hook opens the single `Crypt-LVM` container.
+fn kernel_cmdline_lvm_thin(
+    config: &DeploymentConfig,
+    luks_uuid: &str,
+    resume: Option<&str>,
+) -> String {
     let mut cmdline_parts = vec!["quiet".to_string()];
 
     // The encrypt hook reads cryptdevice= to know which LUKS device to decrypt
     cmdline_parts.push(format!("cryptdevice=UUID={}:Crypt-LVM", luks_uuid));
-    cmdline_parts.push(format!("root={}", root_lv));
+    cmdline_parts.push(format!(
+        "root={}",
+        lvm::lv_path(&config.disk.lvm_vg_name, "root")
+    ));
 
     // When boot_encryption is enabled, a keyfile for Crypt-LVM is embedded in
-    // the initramfs (at /etc/cryptsetup-keys.d/cryptlvm.key).  Tell the encrypt
+    // the initramfs (at <keyfile_dir>/cryptlvm.key).  Tell the encrypt
     // hook to use it so the LUKS container is opened automatically without
     // prompting for a second password at early boot.
     if config.disk.boot_encryption {
-        cmdline_parts.push("cryptkey=rootfs:/etc/cryptsetup-keys.d/cryptlvm.key".to_string());
+        cmdline_parts.push(format!(
+            "cryptkey=rootfs:{}",
+            crate::configure::keyfiles::keyfile_path(&config.disk.keyfile_dir, "Lvm")
+        ));
     }
 
     cmdline_parts.push("rw".to_string());
 
+    push_common_params(config, &mut cmdline_parts, resume);
+    cmdline_parts.join(" ")
+}
+
+/// Hibernation resume and early-boot module blacklist parameters.
+fn push_common_params(
+    config: &DeploymentConfig,
+    cmdline_parts: &mut Vec<String>,
+    resume: Option<&str>,
+) {
     // Add resume for hibernation
     if config.system.hibernation {
-        if let Some(uuid) = swap_uuid {
-            cmdline_parts.push(format!("resume=UUID={}", uuid));
+        if let Some(resume) = resume {
+            cmdline_parts.push(resume.to_string());
         }
     }
 
-    let cmdline = cmdline_parts.join(" ");
+    // Early-boot module blacklist (KMS drivers loaded from the initramfs)
+    if let Some(param) = crate::configure::modules::blacklist_cmdline_param(config) {
+        cmdline_parts.push(param);
+    }
 
-    let mut content = format!(
-        r#"# GRUB boot loader configuration
-# Generated by Deploytix - LVM Thin Provisioning layout
+    push_user_params(&config.system.kernel_params, cmdline_parts);
+}
+
+/// Append user `kernel_params` after the managed parameters.
+///
+/// Parameters already present are skipped.  A parameter that would
+/// override a managed key (`root=`, `cryptdevice=`, ...) is dropped with a
+/// warning so the encryption and root handling stays intact.
+fn push_user_params(user_params: &[String], cmdline_parts: &mut Vec<String>) {
+    let key = |p: &str| p.split_once('=').map_or(p, |(k, _)| k).to_string();
+    let managed: Vec<String> = cmdline_parts.iter().map(|p| key(p)).collect();
+
+    for param in user_params {
+        if cmdline_parts.contains(param) {
+            continue;
+        }
+        let k = key(param);
+        if param.contains('=') && managed.contains(&k) {
+            warn!(
+                "Ignoring kernel parameter '{}': {}= is managed by Deploytix",
+                param, k
+            );
+            continue;
+        }
+        cmdline_parts.push(param.clone());
+    }
+}
 
-GRUB_DEFAULT=0
-GRUB_TIMEOUT=5
-GRUB_DISTRIBUTOR="Artix"
-GRUB_CMDLINE_LINUX_DEFAULT="{}"
+/// Work out the kernel command line for `layout`, mirroring the root
+/// selection GRUB makes in `install_grub` / `install_grub_with_layout`.
+fn resolve_kernel_cmdline(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    device: &str,
+    layout: &ComputedLayout,
+    install_root: &str,
+) -> Result<String> {
+    let placeholder = || "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX".to_string();
+    let resume = resume_params(cmd, config, device, layout, install_root)?;
+    let luks_part = layout.partitions.iter().find(|p| p.is_luks);
+
+    if config.disk.use_lvm_thin && config.disk.encryption {
+        let luks = luks_part.ok_or_else(|| {
+            DeploytixError::ConfigError(
+                "LVM thin + encryption: no LUKS partition found in layout".to_string(),
+            )
+        })?;
+        let luks_uuid = if cmd.is_dry_run() {
+            placeholder()
+        } else {
+            get_luks_uuid(&partition_path(device, luks.number))?
+        };
+        return Ok(kernel_cmdline_lvm_thin(
+            config,
+            &luks_uuid,
+            resume.as_deref(),
+        ));
+    }
+
+    if config.disk.uses_luks() {
+        let luks = luks_part.ok_or_else(|| {
+            DeploytixError::ConfigError("No LUKS partition found in layout".to_string())
+        })?;
+        let luks_uuid = if cmd.is_dry_run() {
+            placeholder()
+        } else {
+            get_luks_uuid(&partition_path(device, luks.number))?
+        };
+        return Ok(kernel_cmdline(
+            config,
+            &luks_uuid,
+            Some(&config.disk.luks_mapper_name),
+            layout.uses_subvolumes(),
+            resume.as_deref(),
+        ));
+    }
+
+    let root_device = if config.disk.use_lvm_thin {
+        lvm::lv_path(&config.disk.lvm_vg_name, "root")
+    } else {
+        let root_part = layout
+            .partitions
+            .iter()
+            .find(|p| p.mount_point.as_deref() == Some("/"))
+            .or_else(|| layout.partitions.iter().find(|p| p.name == "ROOT"))
+            .ok_or_else(|| {
+                DeploytixError::ConfigError("No root partition found in layout".to_string())
+            })?;
+        partition_path(device, root_part.number)
+    };
+    let root_uuid = if cmd.is_dry_run() {
+        placeholder()
+    } else {
+        get_partition_uuid(&root_device)?
+    };
+    Ok(kernel_cmdline(
+        config,
+        &root_uuid,
+        None,
+        layout.uses_subvolumes(),
+        resume.as_deref(),
+    ))
+}
+
+/// Generate a minimal `refind.conf`.
+///
+/// rEFInd scans for kernels itself; the extra version strings let it pair
+/// `vmlinuz-linux-zen` with `initramfs-linux-zen.img`.
+pub fn generate_refind_conf() -> String {
+    r#"# rEFInd boot manager configuration
+# Generated by Deploytix
+
+timeout 5
+use_nvram false
+scanfor internal,external,optical,manual
+extra_kernel_version_strings linux-zen,linux-hardened,linux-lts,linux
+"#
+    .to_string()
+}
+
+/// Generate `/boot/refind_linux.conf`, which supplies the kernel
+/// parameters for every kernel rEFInd finds in `/boot`.  With `degraded`
+/// an extra option boots a btrfs RAID1 root with one disk missing.
+pub fn generate_refind_linux_conf(cmdline: &str, degraded: bool) -> String {
+    let mut conf = format!(
+        r#""Boot with standard options"  "{cmdline}"
+"Boot to single-user mode"    "{cmdline} single"
+"Boot with minimal options"   "{minimal}"
 "#,
-        cmdline
+        cmdline = cmdline,
+        minimal = cmdline.replace("quiet ", ""),
     );
+    if degraded {
+        conf.push_str(&format!(
+            "\"Boot with a mirror disk missing\" \"{}\"\n",
+            degraded_cmdline(cmdline)
+        ));
+    }
+    conf
+}
 
-    // Enable cryptodisk only when boot partition is encrypted (LUKS1)
-    // GRUB needs this to decrypt /boot at early boot stage
-    if config.disk.boot_encryption {
-        content.push_str("GRUB_ENABLE_CRYPTODISK=y\n");
+/// Install rEFInd to the ESP's removable path and configure it.
+///
+/// `refind-install --usedefault` writes `EFI/BOOT/bootx64.efi` plus the
+/// filesystem driver for `/boot`, mirroring GRUB's `--removable` install.
+/// Kernel parameters, including those for encrypted roots, go into
+/// `/boot/refind_linux.conf`.
+pub fn install_refind(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    device: &str,
+    layout: &ComputedLayout,
+    install_root: &str,
+) -> Result<()> {
+    info!("Installing rEFInd boot manager to {}", device);
+
+    let efi_part = layout.partitions.iter().find(|p| p.is_efi).ok_or_else(|| {
+        DeploytixError::ConfigError("rEFInd requires an EFI System Partition".to_string())
+    })?;
+    let efi_device = partition_path(device, efi_part.number);
+    // rEFInd only adds the initramfs it detects itself, so microcode
+    // images must be listed as initrd= options ahead of it.
+    let mut options = microcode::refind_initrd_params(&microcode::early_initrd_images(config));
+    options.push(resolve_kernel_cmdline(
+        cmd,
+        config,
+        device,
+        layout,
+        install_root,
+    )?);
+    let cmdline = options.join(" ");
+    let refind_dir = format!("{}/EFI/BOOT", config.disk.esp_mountpoint.path());
+
+    if cmd.is_dry_run() {
+        cmd.log_dry_run(&format!("refind-install --usedefault {}", efi_device));
+        println!("  [dry-run] Would write {}/refind.conf", refind_dir);
+        println!("  [dry-run] Would write /boot/refind_linux.conf:");
+        println!("    {}", cmdline);
+        return Ok(());
     }
 
-    fs::create_dir_all(format!("{}/etc/default", install_root))?;
-    fs::write(&grub_default_path, content)?;
+    cmd.run_in_chroot(
+        install_root,
+        &format!("refind-install --usedefault {}", efi_device),
+    )?;
 
-    info!("GRUB defaults (LvmThin) written to /etc/default/grub");
+    let conf_dir = format!("{}{}", install_root, refind_dir);
+    fs::create_dir_all(&conf_dir)?;
+    fs::write(format!("{}/refind.conf", conf_dir), generate_refind_conf())?;
+
+    fs::write(
+        format!("{}/boot/refind_linux.conf", install_root),
+        generate_refind_linux_conf(
+            &cmdline,
+            crate::disk::mirror::mirrors_btrfs_root(&config.disk),
+        ),
+    )?;
+
+    info!("rEFInd installation complete");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refind_linux_conf_has_all_entries() {
+        let conf = generate_refind_linux_conf("quiet root=UUID=abcd rw", false);
+        assert!(conf.contains("\"Boot with standard options\"  \"quiet root=UUID=abcd rw\""));
+        assert!(conf.contains("\"quiet root=UUID=abcd rw single\""));
+        assert!(conf.contains("\"Boot with minimal options\"   \"root=UUID=abcd rw\""));
+        assert!(!conf.contains("degraded"));
+
+        let conf = generate_refind_linux_conf("quiet root=UUID=abcd rw", true);
+        assert!(conf.contains(
+            "\"Boot with a mirror disk missing\" \"quiet root=UUID=abcd rootflags=degraded rw\""
+        ));
+    }
+
+    #[test]
+    fn user_kernel_params_follow_managed_ones() {
+        let mut config = DeploymentConfig::sample();
+        config.system.kernel_params = vec![
+            "mitigations=off".to_string(),
+            "i915.enable_psr=0".to_string(),
+            "quiet".to_string(),
+            "root=/dev/sdb2".to_string(),
+        ];
+        let cmdline = kernel_cmdline(&config, "abcd", None, false, None);
+        assert_eq!(
+            cmdline,
+            "quiet root=UUID=abcd rw mitigations=off i915.enable_psr=0"
+        );
+    }
+
+    #[test]
+    fn mirrored_btrfs_root_mounts_degraded_only_from_the_fallback_entry() {
+        let mut config = DeploymentConfig::sample();
+        config.disk.filesystem = crate::config::Filesystem::Btrfs;
+        config.disk.mirror_device = Some("/dev/sdb".to_string());
+        let cmdline = kernel_cmdline(&config, "abcd", None, true, None);
+        assert_eq!(cmdline, "quiet root=UUID=abcd rootflags=subvol=@ rw");
+        assert_eq!(
+            degraded_cmdline(&cmdline),
+            "quiet root=UUID=abcd rootflags=subvol=@,degraded rw"
+        );
+        assert_eq!(
+            degraded_cmdline("quiet root=UUID=abcd rw"),
+            "quiet root=UUID=abcd rootflags=degraded rw"
+        );
+
+        let script = grub_degraded_script(&cmdline);
+        assert!(script.contains(
+            "GRUB_CMDLINE_LINUX_DEFAULT=\"quiet root=UUID=abcd rootflags=subvol=@,degraded rw\""
+        ));
+        assert!(script.ends_with("exec /etc/grub.d/10_linux\n"));
+    }
+
+    #[test]
+    fn grub_menu_defaults() {
+        let settings = grub_menu_settings(&DeploymentConfig::sample());
+        assert_eq!(settings, "GRUB_DEFAULT=0\nGRUB_TIMEOUT=5\n");
+    }
+
+    #[test]
+    fn grub_distributor_and_os_prober_follow_config() {
+        let mut config = DeploymentConfig::sample();
+        assert_eq!(grub_os_settings(&config), "GRUB_DISTRIBUTOR=\"Artix\"\n");
+
+        config.system.grub_distributor = Some("Workstation".to_string());
+        config.system.grub_os_prober = true;
+        assert_eq!(
+            grub_os_settings(&config),
+            "GRUB_DISTRIBUTOR=\"Workstation\"\nGRUB_DISABLE_OS_PROBER=false\n"
+        );
+    }
+
+    #[test]
+    fn os_prober_entries_are_read_from_grub_cfg() {
+        let grub_cfg = r#"### BEGIN /etc/grub.d/10_linux ###
+menuentry 'Artix Linux' --class artix {
+}
+### END /etc/grub.d/10_linux ###
+### BEGIN /etc/grub.d/30_os-prober ###
+menuentry 'Windows Boot Manager (on /dev/nvme0n1p1)' --class windows --class os $menuentry_id_option 'osprober-efi-1234' {
+}
+### END /etc/grub.d/30_os-prober ###
+"#;
+        assert_eq!(
+            os_prober_entries(grub_cfg),
+            vec!["Windows Boot Manager (on /dev/nvme0n1p1)".to_string()]
+        );
+        assert!(os_prober_entries("### BEGIN /etc/grub.d/10_linux ###\n").is_empty());
+    }
+
+    #[test]
+    fn efivarfs_must_be_mounted_read_write() {
+        let rw =
+            "efivarfs /sys/firmware/efi/efivars efivarfs rw,nosuid,nodev,noexec,relatime 0 0\n";
+        let ro =
+            "efivarfs /sys/firmware/efi/efivars efivarfs ro,nosuid,nodev,noexec,relatime 0 0\n";
+        assert!(efivarfs_writable(rw));
+        assert!(!efivarfs_writable(ro));
+        assert!(!efivarfs_writable("proc /proc proc rw 0 0\n"));
+    }
+
+    #[test]
+    fn grub_zero_timeout_hides_menu() {
+        let mut config = DeploymentConfig::sample();
+        config.system.grub_timeout = Some(0);
+        config.system.grub_default = Some("saved".to_string());
+        let settings = grub_menu_settings(&config);
+        assert!(settings.contains("GRUB_DEFAULT=\"saved\"\n"));
+        assert!(settings.contains("GRUB_TIMEOUT=0\n"));
+        assert!(settings.contains("GRUB_TIMEOUT_STYLE=hidden\n"));
+    }
+}
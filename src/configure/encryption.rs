@@ -2,11 +2,51 @@
 
 use crate::config::DeploymentConfig;
 use crate::disk::detection::partition_path;
-use crate::utils::command::CommandRunner;
+use crate::disk::formatting::block_device_size;
+use crate::utils::command::{wait_with_timeout, wait_with_timeout_observed, CommandRunner};
 use crate::utils::error::{DeploytixError, Result};
 use std::io::Write;
+use std::os::unix::process::CommandExt;
 use std::process::{Command, Stdio};
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Default limit for `cryptsetup luksFormat` without integrity: the KDF
+/// benchmark and header write take seconds, so anything this long is hung.
+const LUKS_FORMAT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Slowest wipe rate (MiB/s) assumed when sizing the `--integrity`
+/// timeout; the initial integrity wipe writes the whole device.
+const INTEGRITY_WIPE_MIN_MIB_PER_SEC: u64 = 20;
+
+/// Default limit for an `--integrity` luksFormat of a device of
+/// `size_bytes`: the plain format allowance plus a full wipe at the
+/// slowest expected rate.
+fn integrity_format_timeout(size_bytes: u64) -> Duration {
+    let wipe_secs = size_bytes / (1024 * 1024) / INTEGRITY_WIPE_MIN_MIB_PER_SEC;
+    LUKS_FORMAT_TIMEOUT + Duration::from_secs(wipe_secs)
+}
+
+/// Seconds between the progress lines cryptsetup prints during the
+/// integrity wipe (`--progress-frequency`).
+const WIPE_PROGRESS_SECS: &str = "5";
+
+/// Fraction of the integrity wipe done, read from a cryptsetup progress
+/// line such as `Progress:  42.5%, ETA 01:02, 1024 MiB written, speed ...`.
+fn wipe_progress(line: &str) -> Option<f32> {
+    let percent = line.trim().strip_prefix("Progress:")?.split('%').next()?;
+    let percent: f32 = percent.trim().parse().ok()?;
+    (0.0..=100.0).contains(&percent).then_some(percent / 100.0)
+}
+
+/// luksFormat arguments shown for the integrity settings in dry-run output.
+fn integrity_flags(integrity: bool, no_wipe: bool) -> &'static str {
+    match (integrity, no_wipe) {
+        (false, _) => "",
+        (true, false) => " --integrity hmac-sha256",
+        (true, true) => " --integrity hmac-sha256 --integrity-no-wipe",
+    }
+}
 
 /// Convert string to title case (e.g., "ROOT" -> "Root", "USR" -> "Usr")
 fn to_title_case(s: &str) -> String {
@@ -85,21 +125,15 @@ pub fn setup_encryption(
     );
 
     let integrity = config.disk.integrity;
+    let no_wipe = config.disk.integrity_no_wipe;
 
     if cmd.is_dry_run() {
-        let integrity_flag = if integrity {
-            " --integrity hmac-sha256"
-        } else {
-            ""
-        };
-        println!(
-            "  [dry-run] cryptsetup luksFormat --type luks2{} {}",
-            integrity_flag, luks_device
-        );
-        println!(
-            "  [dry-run] cryptsetup open {} {}",
-            luks_device, mapper_name
-        );
+        cmd.log_dry_run(&format!(
+            "cryptsetup luksFormat --type luks2{} {}",
+            integrity_flags(integrity, no_wipe),
+            luks_device
+        ));
+        cmd.log_dry_run(&format!("cryptsetup open {} {}", luks_device, mapper_name));
         return Ok(LuksContainer {
             device: luks_device,
             mapper_name,
@@ -110,13 +144,25 @@ pub fn setup_encryption(
 
     // Format LUKS container (with or without integrity)
     if integrity {
-        luks_format_integrity(&luks_device, password)?;
+        luks_format_integrity(
+            cmd,
+            &luks_device,
+            password,
+            no_wipe,
+            config.disk.luks_format_timeout_mins,
+            &|_| {},
+        )?;
     } else {
-        luks_format(&luks_device, password)?;
+        luks_format(
+            cmd,
+            &luks_device,
+            password,
+            config.disk.luks_format_timeout_mins,
+        )?;
     }
 
     // Open LUKS container
-    luks_open(&luks_device, &mapper_name, password)?;
+    luks_open(cmd, &luks_device, &mapper_name, password)?;
 
     info!(
         "LUKS encryption setup complete: {} -> {}",
@@ -131,18 +177,69 @@ pub fn setup_encryption(
     })
 }
 
+/// luksFormat timeout: `timeout_mins` (`disk.luks_format_timeout_mins`)
+/// when set, where 0 waits indefinitely, else the built-in `default`.
+fn format_timeout(timeout_mins: Option<u64>, default: Option<Duration>) -> Option<Duration> {
+    match timeout_mins {
+        Some(0) => None,
+        Some(mins) => Some(Duration::from_secs(mins.saturating_mul(60))),
+        None => default,
+    }
+}
+
 /// Format a device as LUKS2
-fn luks_format(device: &str, password: &str) -> Result<()> {
-    luks_format_inner(device, password, false)
+fn luks_format(
+    cmd: &CommandRunner,
+    device: &str,
+    password: &str,
+    timeout_mins: Option<u64>,
+) -> Result<()> {
+    let timeout = format_timeout(timeout_mins, Some(LUKS_FORMAT_TIMEOUT));
+    luks_format_inner(cmd, device, password, false, false, timeout, &|_| {})
 }
 
 /// Format a device as LUKS2 with dm-integrity (HMAC-SHA256 per-sector integrity)
-fn luks_format_integrity(device: &str, password: &str) -> Result<()> {
-    luks_format_inner(device, password, true)
+///
+/// With `no_wipe` the initial wipe of the integrity tags is skipped
+/// (`--integrity-no-wipe`): the format takes seconds instead of a full
+/// device write, but every sector fails its integrity check until it is
+/// first written.  Otherwise `on_wipe` is given the fraction of the wipe
+/// done as cryptsetup reports it.
+fn luks_format_integrity(
+    cmd: &CommandRunner,
+    device: &str,
+    password: &str,
+    no_wipe: bool,
+    timeout_mins: Option<u64>,
+    on_wipe: &dyn Fn(f32),
+) -> Result<()> {
+    // Without a size the wipe time is unknown; wait for it unless overridden
+    let default = if no_wipe {
+        Some(LUKS_FORMAT_TIMEOUT)
+    } else {
+        block_device_size(device).map(integrity_format_timeout)
+    };
+    luks_format_inner(
+        cmd,
+        device,
+        password,
+        true,
+        no_wipe,
+        format_timeout(timeout_mins, default),
+        on_wipe,
+    )
 }
 
 /// Internal LUKS2 format implementation
-fn luks_format_inner(device: &str, password: &str, integrity: bool) -> Result<()> {
+fn luks_format_inner(
+    cmd: &CommandRunner,
+    device: &str,
+    password: &str,
+    integrity: bool,
+    no_wipe: bool,
+    timeout: Option<Duration>,
+    on_wipe: &dyn Fn(f32),
+) -> Result<()> {
     if integrity {
         info!(
             "Formatting {} as LUKS2 container with dm-integrity (aes-xts-plain64, argon2id, hmac-sha256)",
@@ -177,15 +274,29 @@ fn luks_format_inner(device: &str, password: &str, integrity: bool) -> Result<()
         // Use 4096 sector size for optimal performance with integrity
         args.push("--sector-size");
         args.push("4096");
+        if no_wipe {
+            warn!(
+                "Skipping the integrity wipe of {}: unwritten sectors read as I/O errors",
+                device
+            );
+            args.push("--integrity-no-wipe");
+        } else {
+            args.push("--progress-frequency");
+            args.push(WIPE_PROGRESS_SECS);
+        }
     }
 
     args.push(device);
+    cmd.transcribe_stdin("cryptsetup", &args);
 
-    // Use stdin to pass password securely (fixes command injection vulnerability)
+    // Use stdin to pass password securely (fixes command injection vulnerability).
+    // Own process group so a timeout can stop everything it spawned.
     let mut child = Command::new("cryptsetup")
         .args(&args)
         .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        .process_group(0)
         .spawn()
         .map_err(|e| DeploytixError::CommandFailed {
             command: "cryptsetup luksFormat".to_string(),
@@ -198,7 +309,12 @@ fn luks_format_inner(device: &str, password: &str, integrity: bool) -> Result<()
     }
     drop(child.stdin.take()); // Close stdin to signal EOF
 
-    let output = child.wait_with_output()?;
+    let output = wait_with_timeout_observed(child, "cryptsetup luksFormat", timeout, &|line| {
+        cmd.log_output(line);
+        if let Some(fraction) = wipe_progress(line) {
+            on_wipe(fraction);
+        }
+    })?;
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(DeploytixError::CommandFailed {
@@ -218,15 +334,16 @@ pub fn open_luks(
     password: &str,
 ) -> Result<()> {
     if cmd.is_dry_run() {
-        println!("  [dry-run] cryptsetup open {} {}", device, mapper_name);
+        cmd.log_dry_run(&format!("cryptsetup open {} {}", device, mapper_name));
         return Ok(());
     }
-    luks_open(device, mapper_name, password)
+    luks_open(cmd, device, mapper_name, password)
 }
 
 /// Open a LUKS container (internal)
-fn luks_open(device: &str, mapper_name: &str, password: &str) -> Result<()> {
+fn luks_open(cmd: &CommandRunner, device: &str, mapper_name: &str, password: &str) -> Result<()> {
     info!("Opening LUKS container {} as {}", device, mapper_name);
+    cmd.transcribe_stdin("cryptsetup", &["open", device, mapper_name]);
 
     let mut child = Command::new("cryptsetup")
         .args(["open", device, mapper_name])
@@ -291,14 +408,11 @@ pub fn setup_boot_encryption(
     );
 
     if cmd.is_dry_run() {
-        println!(
-            "  [dry-run] cryptsetup luksFormat --type luks1 {}",
+        cmd.log_dry_run(&format!(
+            "cryptsetup luksFormat --type luks1 {}",
             boot_device
-        );
-        println!(
-            "  [dry-run] cryptsetup open {} {}",
-            boot_device, mapper_name
-        );
+        ));
+        cmd.log_dry_run(&format!("cryptsetup open {} {}", boot_device, mapper_name));
         return Ok(LuksContainer {
             device: boot_device,
             mapper_name,
@@ -308,10 +422,15 @@ pub fn setup_boot_encryption(
     }
 
     // Format as LUKS1
-    luks_format_v1(&boot_device, password)?;
+    luks_format_v1(
+        cmd,
+        &boot_device,
+        password,
+        config.disk.luks_format_timeout_mins,
+    )?;
 
     // Open LUKS container
-    luks_open(&boot_device, &mapper_name, password)?;
+    luks_open(cmd, &boot_device, &mapper_name, password)?;
 
     info!(
         "LUKS1 boot encryption setup complete: {} -> {}",
@@ -330,28 +449,36 @@ pub fn setup_boot_encryption(
 ///
 /// Uses pbkdf2 instead of argon2id because GRUB's cryptodisk module only
 /// supports pbkdf2 for LUKS1 containers.
-fn luks_format_v1(device: &str, password: &str) -> Result<()> {
+fn luks_format_v1(
+    cmd: &CommandRunner,
+    device: &str,
+    password: &str,
+    timeout_mins: Option<u64>,
+) -> Result<()> {
     info!(
         "Formatting {} as LUKS1 container (aes-xts-plain64, pbkdf2)",
         device
     );
 
+    let args = [
+        "luksFormat",
+        "--type",
+        "luks1",
+        "--cipher",
+        "aes-xts-plain64",
+        "--key-size",
+        "512",
+        "--hash",
+        "sha512",
+        "--batch-mode",
+        device,
+    ];
+    cmd.transcribe_stdin("cryptsetup", &args);
     let mut child = Command::new("cryptsetup")
-        .args([
-            "luksFormat",
-            "--type",
-            "luks1",
-            "--cipher",
-            "aes-xts-plain64",
-            "--key-size",
-            "512",
-            "--hash",
-            "sha512",
-            "--batch-mode",
-            device,
-        ])
+        .args(args)
         .stdin(Stdio::piped())
         .stderr(Stdio::piped())
+        .process_group(0)
         .spawn()
         .map_err(|e| DeploytixError::CommandFailed {
             command: "cryptsetup luksFormat (LUKS1)".to_string(),
@@ -364,7 +491,8 @@ fn luks_format_v1(device: &str, password: &str) -> Result<()> {
     }
     drop(child.stdin.take());
 
-    let output = child.wait_with_output()?;
+    let timeout = format_timeout(timeout_mins, Some(LUKS_FORMAT_TIMEOUT));
+    let output = wait_with_timeout(child, "cryptsetup luksFormat (LUKS1)", timeout)?;
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(DeploytixError::CommandFailed {
@@ -381,7 +509,7 @@ pub fn close_luks(cmd: &CommandRunner, mapper_name: &str) -> Result<()> {
     info!("Closing LUKS container {}", mapper_name);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] cryptsetup close {}", mapper_name);
+        cmd.log_dry_run(&format!("cryptsetup close {}", mapper_name));
         return Ok(());
     }
 
@@ -389,6 +517,18 @@ pub fn close_luks(cmd: &CommandRunner, mapper_name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Close `containers` in reverse order on an error path: runs even after
+/// an interrupt, and a container that fails to close is logged without
+/// stopping the rest.
+pub fn force_close_luks(cmd: &CommandRunner, containers: &[LuksContainer]) {
+    for container in containers.iter().rev() {
+        info!("Closing LUKS container {}", container.mapper_name);
+        if let Err(e) = cmd.force_run("cryptsetup", &["close", &container.mapper_name]) {
+            warn!("Failed to close {}: {}", container.mapper_name, e);
+        }
+    }
+}
+
 /// Get UUID of LUKS container
 pub fn get_luks_uuid(device: &str) -> Result<String> {
     let output = Command::new("cryptsetup")
@@ -419,23 +559,51 @@ pub fn setup_single_luks(
     password: &str,
     canonical_mapper: &str,
     volume_name: &str,
+    timeout_mins: Option<u64>,
 ) -> Result<LuksContainer> {
-    setup_single_luks_inner(cmd, device, password, canonical_mapper, volume_name, false)
+    setup_single_luks_inner(
+        cmd,
+        device,
+        password,
+        canonical_mapper,
+        volume_name,
+        false,
+        false,
+        timeout_mins,
+        &|_| {},
+    )
 }
 
 /// Setup LUKS2 encryption with dm-integrity for a single partition
 ///
-/// Same as `setup_single_luks` but adds per-sector HMAC-SHA256 integrity protection.
+/// Same as `setup_single_luks` but adds per-sector HMAC-SHA256 integrity
+/// protection; `no_wipe` skips the initial tag wipe (see
+/// `disk.integrity_no_wipe`).  `on_wipe` follows the wipe's progress.
+#[allow(clippy::too_many_arguments)]
 pub fn setup_single_luks_with_integrity(
     cmd: &CommandRunner,
     device: &str,
     password: &str,
     canonical_mapper: &str,
     volume_name: &str,
+    no_wipe: bool,
+    timeout_mins: Option<u64>,
+    on_wipe: &dyn Fn(f32),
 ) -> Result<LuksContainer> {
-    setup_single_luks_inner(cmd, device, password, canonical_mapper, volume_name, true)
+    setup_single_luks_inner(
+        cmd,
+        device,
+        password,
+        canonical_mapper,
+        volume_name,
+        true,
+        no_wipe,
+        timeout_mins,
+        on_wipe,
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 fn setup_single_luks_inner(
     cmd: &CommandRunner,
     device: &str,
@@ -443,6 +611,10 @@ fn setup_single_luks_inner(
     canonical_mapper: &str,
     volume_name: &str,
     integrity: bool,
+    // With integrity: whether to skip the tag wipe
+    no_wipe: bool,
+    timeout_mins: Option<u64>,
+    on_wipe: &dyn Fn(f32),
 ) -> Result<LuksContainer> {
     let mapper_name = resolve_mapper_name(canonical_mapper);
     let mapped_path = format!("/dev/mapper/{}", mapper_name);
@@ -460,16 +632,12 @@ fn setup_single_luks_inner(
     }
 
     if cmd.is_dry_run() {
-        let integrity_flag = if integrity {
-            " --integrity hmac-sha256"
-        } else {
-            ""
-        };
-        println!(
-            "  [dry-run] cryptsetup luksFormat --type luks2{} {}",
-            integrity_flag, device
-        );
-        println!("  [dry-run] cryptsetup open {} {}", device, mapper_name);
+        cmd.log_dry_run(&format!(
+            "cryptsetup luksFormat --type luks2{} {}",
+            integrity_flags(integrity, no_wipe),
+            device
+        ));
+        cmd.log_dry_run(&format!("cryptsetup open {} {}", device, mapper_name));
         return Ok(LuksContainer {
             device: device.to_string(),
             mapper_name: mapper_name.clone(),
@@ -480,13 +648,13 @@ fn setup_single_luks_inner(
 
     // Format LUKS container (with or without integrity)
     if integrity {
-        luks_format_integrity(device, password)?;
+        luks_format_integrity(cmd, device, password, no_wipe, timeout_mins, on_wipe)?;
     } else {
-        luks_format(device, password)?;
+        luks_format(cmd, device, password, timeout_mins)?;
     }
 
     // Open LUKS container
-    luks_open(device, &mapper_name, password)?;
+    luks_open(cmd, device, &mapper_name, password)?;
 
     info!(
         "LUKS2 encryption setup complete: {} -> {}",
@@ -505,11 +673,13 @@ fn setup_single_luks_inner(
 ///
 /// Creates and opens LUKS containers for ROOT, USR, VAR, and HOME partitions.
 /// Each container gets a unique mapper name (e.g., Crypt-Root, Crypt-Usr, etc.).
+/// With integrity, `on_wipe` follows the wipes of all containers together.
 pub fn setup_multi_volume_encryption(
     cmd: &CommandRunner,
     config: &DeploymentConfig,
     device: &str,
     luks_partitions: &[(u32, &str)], // (partition_number, name)
+    on_wipe: &dyn Fn(f32),
 ) -> Result<Vec<LuksContainer>> {
     if !config.disk.encryption {
         return Err(DeploytixError::ConfigError(
@@ -522,9 +692,10 @@ pub fn setup_multi_volume_encryption(
     })?;
 
     let integrity = config.disk.integrity;
+    let no_wipe = config.disk.integrity_no_wipe;
     let mut containers = Vec::new();
 
-    for (part_num, name) in luks_partitions {
+    for (index, (part_num, name)) in luks_partitions.iter().enumerate() {
         let luks_device = partition_path(device, *part_num);
         // Convert partition name to title case (e.g., "ROOT" -> "Root")
         let volume_name = to_title_case(name);
@@ -545,29 +716,35 @@ pub fn setup_multi_volume_encryption(
         }
 
         if cmd.is_dry_run() {
-            let integrity_flag = if integrity {
-                " --integrity hmac-sha256"
-            } else {
-                ""
-            };
-            println!(
-                "  [dry-run] cryptsetup luksFormat --type luks2{} {}",
-                integrity_flag, luks_device
-            );
-            println!(
-                "  [dry-run] cryptsetup open {} {}",
-                luks_device, mapper_name
-            );
+            cmd.log_dry_run(&format!(
+                "cryptsetup luksFormat --type luks2{} {}",
+                integrity_flags(integrity, no_wipe),
+                luks_device
+            ));
+            cmd.log_dry_run(&format!("cryptsetup open {} {}", luks_device, mapper_name));
         } else {
             // Format LUKS container (with or without integrity)
             if integrity {
-                luks_format_integrity(&luks_device, password)?;
+                let count = luks_partitions.len() as f32;
+                luks_format_integrity(
+                    cmd,
+                    &luks_device,
+                    password,
+                    no_wipe,
+                    config.disk.luks_format_timeout_mins,
+                    &|fraction| on_wipe((index as f32 + fraction) / count),
+                )?;
             } else {
-                luks_format(&luks_device, password)?;
+                luks_format(
+                    cmd,
+                    &luks_device,
+                    password,
+                    config.disk.luks_format_timeout_mins,
+                )?;
             }
 
             // Open LUKS container
-            luks_open(&luks_device, &mapper_name, password)?;
+            luks_open(cmd, &luks_device, &mapper_name, password)?;
         }
 
         info!(
@@ -590,6 +767,55 @@ pub fn setup_multi_volume_encryption(
     Ok(containers)
 }
 
+/// Open already formatted LUKS containers, as set up by
+/// [`setup_multi_volume_encryption`], under the same mapper names.  If one
+/// fails to open, those opened before it are closed again.
+pub fn reopen_multi_volume(
+    cmd: &CommandRunner,
+    device: &str,
+    luks_partitions: &[(u32, &str)], // (partition_number, name)
+    password: &str,
+) -> Result<Vec<LuksContainer>> {
+    let mut containers = Vec::new();
+    for (part_num, name) in luks_partitions {
+        let volume_name = to_title_case(name);
+        let opened = reopen_luks(
+            cmd,
+            &partition_path(device, *part_num),
+            &format!("Crypt-{}", volume_name),
+            &volume_name,
+            password,
+        );
+        match opened {
+            Ok(container) => containers.push(container),
+            Err(e) => {
+                force_close_luks(cmd, &containers);
+                return Err(e);
+            }
+        }
+    }
+    Ok(containers)
+}
+
+/// Open an already formatted LUKS container under `canonical_mapper`, or a
+/// disambiguated name if that one is taken.
+pub fn reopen_luks(
+    cmd: &CommandRunner,
+    device: &str,
+    canonical_mapper: &str,
+    volume_name: &str,
+    password: &str,
+) -> Result<LuksContainer> {
+    let mapper_name = resolve_mapper_name(canonical_mapper);
+    open_luks(cmd, device, &mapper_name, password)?;
+    Ok(LuksContainer {
+        device: device.to_string(),
+        mapped_path: format!("/dev/mapper/{}", mapper_name),
+        mapper_name,
+        volume_name: volume_name.to_string(),
+    })
+}
+
 /// Close multiple LUKS containers
 pub fn close_multi_luks(cmd: &CommandRunner, containers: &[LuksContainer]) -> Result<()> {
     info!("Closing {} LUKS containers", containers.len());
@@ -608,6 +834,63 @@ mod tests {
 
     // ── to_title_case ────────────────────────────────────────────────────────
 
+    #[test]
+    fn integrity_timeout_scales_with_device_size() {
+        assert_eq!(integrity_format_timeout(0), LUKS_FORMAT_TIMEOUT);
+        // 1 TiB at 20 MiB/s
+        let tib = 1024 * 1024 * 1024 * 1024;
+        assert_eq!(
+            integrity_format_timeout(tib),
+            LUKS_FORMAT_TIMEOUT + Duration::from_secs(1024 * 1024 / 20)
+        );
+    }
+
+    #[test]
+    fn force_close_runs_in_reverse_after_interrupt() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let cmd = CommandRunner::new(true).with_recorder(tx);
+        let containers: Vec<LuksContainer> = ["Crypt-Root", "Crypt-Home"]
+            .iter()
+            .map(|name| LuksContainer {
+                device: "/dev/sda2".to_string(),
+                mapper_name: name.to_string(),
+                mapped_path: format!("/dev/mapper/{}", name),
+                volume_name: name.trim_start_matches("Crypt-").to_string(),
+            })
+            .collect();
+        force_close_luks(&cmd, &containers);
+        let commands: Vec<String> = rx.try_iter().map(|r| r.command).collect();
+        assert_eq!(
+            commands,
+            ["cryptsetup close Crypt-Home", "cryptsetup close Crypt-Root"]
+        );
+    }
+
+    #[test]
+    fn configured_timeout_replaces_default() {
+        let default = Some(LUKS_FORMAT_TIMEOUT);
+        assert_eq!(format_timeout(None, default), default);
+        assert_eq!(
+            format_timeout(Some(5), None),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(format_timeout(Some(0), default), None);
+    }
+
+    #[test]
+    fn wipe_progress_reads_cryptsetup_percentage() {
+        assert_eq!(
+            wipe_progress("Progress:  42.5%, ETA 01:02,  1024 MiB written, speed  98.1 MiB/s"),
+            Some(0.425)
+        );
+        assert_eq!(wipe_progress("Progress: 100.0%, ETA 00:00"), Some(1.0));
+        assert_eq!(
+            wipe_progress("Wiping device to initialize integrity checksum."),
+            None
+        );
+        assert_eq!(wipe_progress("Progress: n/a"), None);
+    }
+
     #[test]
     fn to_title_case_capitalizes_first_letter_lowercase_rest() {
         assert_eq!(to_title_case("root"), "Root");
@@ -0,0 +1,203 @@
+//! FIDO2 security key unlock for LUKS2 containers
+//!
+//! With `disk.fido2_unlock` a FIDO2 token (YubiKey, SoloKey, ...) is
+//! enrolled into every LUKS2 data container with `systemd-cryptenroll`.
+//! crypttab marks those entries with `fido2-device=auto`, and the
+//! crypttab-unlock hook tries the token before asking for the passphrase,
+//! which stays enrolled as a fallback.  The LUKS1 /boot container cannot
+//! hold tokens and keeps its keyfile.
+
+use crate::configure::encryption::LuksContainer;
+use crate::utils::command::{command_exists, CommandRunner};
+use crate::utils::error::{DeploytixError, Result};
+use std::path::Path;
+use std::process::Command;
+use tracing::info;
+
+/// Package providing libfido2 and `fido2-token` in the installed system.
+pub const FIDO2_PACKAGE: &str = "libfido2";
+
+/// crypttab option marking a token-unlockable container.
+pub const CRYPTTAB_OPTION: &str = "fido2-device=auto";
+
+/// cryptsetup's FIDO2 token plugin.  The crypttab-unlock hook copies it
+/// into the initramfs; without it the key cannot unlock anything.
+pub const TOKEN_PLUGIN: &str = "/usr/lib/cryptsetup/libcryptsetup-token-systemd-fido2.so";
+
+/// Security keys listed by `fido2-token -L` (one `/dev/hidrawN: ...` line
+/// per key), as their device paths.
+fn parse_token_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .map(|(dev, _)| dev.trim())
+        .filter(|dev| dev.starts_with("/dev/"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// FIDO2 security keys plugged into the host.
+pub fn detected_keys() -> Vec<String> {
+    Command::new("fido2-token")
+        .arg("-L")
+        .output()
+        .map(|o| parse_token_list(&String::from_utf8_lossy(&o.stdout)))
+        .unwrap_or_default()
+}
+
+/// Check that a key can be enrolled before anything on the disk is
+/// touched: `systemd-cryptenroll` and `fido2-token` must be installed and
+/// a key plugged in.
+pub fn check_enrollment_possible() -> Result<()> {
+    for (program, package) in [
+        ("systemd-cryptenroll", "systemd"),
+        ("fido2-token", FIDO2_PACKAGE),
+    ] {
+        if !command_exists(program) {
+            return Err(DeploytixError::ConfigError(format!(
+                "fido2_unlock needs {} (package {}) on the installing host",
+                program, package
+            )));
+        }
+    }
+
+    let keys = detected_keys();
+    if keys.is_empty() {
+        return Err(DeploytixError::ConfigError(
+            "fido2_unlock is set but no FIDO2 security key was detected. Plug the key in \
+             (check `fido2-token -L`) or set fido2_unlock = false; nothing has been written \
+             to the disk."
+                .to_string(),
+        ));
+    }
+    info!("FIDO2 security key detected: {}", keys.join(", "));
+    Ok(())
+}
+
+/// Package providing `TOKEN_PLUGIN` in the first line of `pacman -Fq`
+/// output (`core/systemd`), without its repository.
+fn parse_file_owner(output: &str) -> Option<String> {
+    let line = output.lines().map(str::trim).find(|l| !l.is_empty())?;
+    Some(line.rsplit('/').next().unwrap_or(line).to_string())
+}
+
+/// Find the package that provides the token plugin in the sync databases,
+/// before anything on the disk is touched.  With no provider the enrolled
+/// key could never unlock the installed system.
+pub fn token_plugin_package() -> Result<String> {
+    let output = Command::new("pacman")
+        .args(["-Fq", TOKEN_PLUGIN.trim_start_matches('/')])
+        .output()
+        .map_err(|e| DeploytixError::CommandFailed {
+            command: "pacman -Fq".to_string(),
+            stderr: e.to_string(),
+        })?;
+    parse_file_owner(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+        DeploytixError::ConfigError(format!(
+            "fido2_unlock needs {} in the initramfs, but no package in the sync databases \
+             provides it. Refresh the file lists with `pacman -Fy` or set fido2_unlock = false; \
+             nothing has been written to the disk.",
+            TOKEN_PLUGIN
+        ))
+    })
+}
+
+/// Check that the installed system has cryptsetup's FIDO2 token plugin, so
+/// the initramfs can unlock with the enrolled key instead of silently
+/// falling back to the passphrase.
+pub fn check_token_plugin(cmd: &CommandRunner, install_root: &str) -> Result<()> {
+    if cmd.is_dry_run() {
+        cmd.log_dry_run(&format!("check {}{} exists", install_root, TOKEN_PLUGIN));
+        return Ok(());
+    }
+    if Path::new(&format!("{}{}", install_root, TOKEN_PLUGIN)).exists() {
+        return Ok(());
+    }
+    let file = TOKEN_PLUGIN.rsplit('/').next().unwrap_or(TOKEN_PLUGIN);
+    Err(DeploytixError::BootCheckFailed(format!(
+        "fido2_unlock is set but {} is missing from the installed system; install the \
+         package that provides it (find it with `pacman -F {}`) or set fido2_unlock = false",
+        TOKEN_PLUGIN, file
+    )))
+}
+
+/// Enroll the plugged-in key into each LUKS2 container.  The key has to be
+/// touched once per container.
+pub fn enroll_containers(
+    cmd: &CommandRunner,
+    containers: &[LuksContainer],
+    password: &str,
+) -> Result<()> {
+    for container in containers {
+        if cmd.is_dry_run() {
+            cmd.log_dry_run(&format!(
+                "systemd-cryptenroll --fido2-device=auto {}",
+                container.device
+            ));
+            continue;
+        }
+
+        println!(
+            "Touch your security key to enroll it for {}...",
+            container.volume_name
+        );
+        // systemd-cryptenroll reads the existing passphrase from $PASSWORD
+        let output = Command::new("systemd-cryptenroll")
+            .args(["--fido2-device=auto", &container.device])
+            .env("PASSWORD", password)
+            .output()
+            .map_err(|e| DeploytixError::CommandFailed {
+                command: "systemd-cryptenroll".to_string(),
+                stderr: e.to_string(),
+            })?;
+        if !output.status.success() {
+            return Err(DeploytixError::CommandFailed {
+                command: format!(
+                    "systemd-cryptenroll --fido2-device=auto {}",
+                    container.device
+                ),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
+        }
+        info!("FIDO2 key enrolled for {}", container.volume_name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_list_yields_device_paths() {
+        let output = "/dev/hidraw3: vendor=0x1050, product=0x0407 (Yubico YubiKey OTP+FIDO+CCID)\n";
+        assert_eq!(parse_token_list(output), vec!["/dev/hidraw3"]);
+        assert!(parse_token_list("").is_empty());
+    }
+
+    #[test]
+    fn file_owner_drops_the_repository() {
+        assert_eq!(
+            parse_file_owner("core/systemd\n").as_deref(),
+            Some("systemd")
+        );
+        assert!(parse_file_owner("").is_none());
+    }
+
+    #[test]
+    fn missing_token_plugin_fails_the_install() {
+        let cmd = CommandRunner::new(false);
+        let root = std::env::temp_dir().join(format!("deploytix-fido2-{}", std::process::id()));
+        let root_str = root.to_str().unwrap();
+        let err = check_token_plugin(&cmd, root_str).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("libcryptsetup-token-systemd-fido2.so"));
+
+        let plugin = format!("{}{}", root_str, TOKEN_PLUGIN);
+        std::fs::create_dir_all(Path::new(&plugin).parent().unwrap()).unwrap();
+        std::fs::write(&plugin, b"").unwrap();
+        assert!(check_token_plugin(&cmd, root_str).is_ok());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
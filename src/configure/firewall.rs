@@ -0,0 +1,133 @@
+//! Firewall configuration
+//!
+//! Sets up a default-deny-incoming / allow-outgoing profile with either
+//! nftables or ufw.  The package and service come with `configure::services`;
+//! SSH is opened when sshd is enabled unless `firewall_open_ssh` says
+//! otherwise.
+
+use crate::config::{DeploymentConfig, Firewall};
+use crate::utils::command::CommandRunner;
+use crate::utils::error::Result;
+use std::fs;
+use tracing::info;
+
+/// Whether the firewall opens port 22: as configured, else when sshd is
+/// enabled (by Deploytix or through `extra_services`).
+pub fn opens_ssh(config: &DeploymentConfig) -> bool {
+    config.system.firewall_open_ssh.unwrap_or_else(|| {
+        crate::configure::ssh::sshd_enabled(config)
+            || config.system.extra_services.iter().any(|s| s == "sshd")
+    })
+}
+
+/// `/etc/nftables.conf` dropping unsolicited inbound traffic.
+pub fn generate_nftables_conf(open_ssh: bool) -> String {
+    let ssh = if open_ssh {
+        "        tcp dport 22 accept\n"
+    } else {
+        ""
+    };
+    format!(
+        "#!/usr/bin/nft -f\n\
+         # Generated by Deploytix: deny incoming, allow outgoing\n\
+         \n\
+         flush ruleset\n\
+         \n\
+         table inet filter {{\n\
+         \x20   chain input {{\n\
+         \x20       type filter hook input priority filter; policy drop;\n\
+         \x20       ct state invalid drop\n\
+         \x20       ct state {{ established, related }} accept\n\
+         \x20       iif lo accept\n\
+         \x20       meta l4proto {{ icmp, ipv6-icmp }} accept\n\
+         {}\
+         \x20   }}\n\
+         \n\
+         \x20   chain forward {{\n\
+         \x20       type filter hook forward priority filter; policy drop;\n\
+         \x20   }}\n\
+         \n\
+         \x20   chain output {{\n\
+         \x20       type filter hook output priority filter; policy accept;\n\
+         \x20   }}\n\
+         }}\n",
+        ssh
+    )
+}
+
+/// ufw commands run in the chroot.  `ufw enable` would load the rules
+/// into the live kernel's netfilter, which the chroot shares with the
+/// host, so the profile is switched on through `ufw.conf` instead.
+fn ufw_commands(open_ssh: bool) -> Vec<&'static str> {
+    let mut commands = vec!["ufw default deny incoming", "ufw default allow outgoing"];
+    if open_ssh {
+        commands.push("ufw allow 22/tcp");
+    }
+    commands.push("sed -i 's/^ENABLED=.*/ENABLED=yes/' /etc/ufw/ufw.conf");
+    commands
+}
+
+/// Write the firewall ruleset.  No-op for `firewall = "none"`.
+pub fn configure_firewall(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let open_ssh = opens_ssh(config);
+    match config.system.firewall {
+        Firewall::None => return Ok(()),
+        Firewall::Nftables => {
+            info!("Writing default-deny nftables ruleset");
+            let content = generate_nftables_conf(open_ssh);
+            if cmd.is_dry_run() {
+                println!("  [dry-run] Would write /etc/nftables.conf:");
+                for line in content.lines() {
+                    println!("    {}", line);
+                }
+            } else {
+                fs::write(format!("{}/etc/nftables.conf", install_root), content)?;
+            }
+        }
+        Firewall::Ufw => {
+            info!("Configuring default-deny ufw profile");
+            for command in ufw_commands(open_ssh) {
+                cmd.run_in_chroot(install_root, command)?;
+            }
+        }
+    }
+    if open_ssh {
+        info!("Firewall allows inbound SSH on port 22");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nftables_opens_ssh_only_when_asked() {
+        let closed = generate_nftables_conf(false);
+        assert!(closed.contains("policy drop;"));
+        assert!(!closed.contains("dport 22"));
+        assert!(generate_nftables_conf(true).contains("tcp dport 22 accept"));
+    }
+
+    #[test]
+    fn ufw_profile_is_enabled_without_touching_the_host() {
+        let commands = ufw_commands(true);
+        assert!(commands.contains(&"ufw allow 22/tcp"));
+        assert!(!commands.contains(&"ufw enable"));
+        assert!(!ufw_commands(false).contains(&"ufw allow 22/tcp"));
+    }
+
+    #[test]
+    fn ssh_is_opened_when_sshd_is_enabled() {
+        let mut cfg = DeploymentConfig::sample();
+        assert!(!opens_ssh(&cfg));
+        cfg.system.ssh = true;
+        assert!(opens_ssh(&cfg));
+        cfg.system.firewall_open_ssh = Some(false);
+        assert!(!opens_ssh(&cfg));
+    }
+}
@@ -74,7 +74,7 @@ fn generate_hooks(
     layout: &ComputedLayout,
 ) -> Result<Vec<GeneratedHook>> {
     let uses_lvm_thin = config.disk.use_lvm_thin;
-    let uses_multi_luks = config.disk.encryption && !uses_lvm_thin;
+    let uses_multi_luks = config.disk.uses_luks() && !uses_lvm_thin;
 
     let mut hooks = Vec::new();
 
@@ -176,9 +176,21 @@ unlock_device() {
         *discard*) cmd="$cmd --allow-discards" ;;
     esac
 
+    # FIDO2 token first; if that fails cryptsetup asks for the passphrase
+    case "$options" in
+        *fido2-device=*)
+            echo "[crypttab-unlock] Touch your security key to unlock $mapper_name ..."
+            if $cmd --token-only; then
+                cmd=""
+            else
+                echo "[crypttab-unlock] Security key unlock failed for $mapper_name, falling back to passphrase"
+            fi
+            ;;
+    esac
+
     # Run the cryptsetup command
-    echo "[crypttab-unlock] Running: $cmd"
-    if ! $cmd; then
+    [ -n "$cmd" ] && echo "[crypttab-unlock] Running: $cmd"
+    if [ -n "$cmd" ] && ! $cmd; then
         echo "[crypttab-unlock] ERROR: cryptsetup failed for $mapper_name"
         return 1
     fi
@@ -339,6 +351,13 @@ build() {
     # cryptsetup loads the legacy provider which is required for whirlpool
     add_binary '/usr/lib/ossl-modules/legacy.so'
 
+    # FIDO2 unlock: cryptsetup's token plugin pulls in libfido2.  With
+    # fido2_unlock the installer has already checked that it exists.
+    if [[ -e /usr/lib/cryptsetup/libcryptsetup-token-systemd-fido2.so ]]; then
+        add_binary '/usr/lib/cryptsetup/libcryptsetup-token-systemd-fido2.so'
+        add_module 'usbhid'
+    fi
+
     # Include /etc/crypttab so the hook can read it at boot
     add_file '/etc/crypttab'
 
@@ -371,9 +390,9 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
 
     // Boot mount options: include subvol=@boot when boot filesystem is btrfs
     let boot_extra_opts = if config.disk.boot_filesystem == Filesystem::Btrfs {
-        " \"subvol=@boot,noatime,compress=zstd\""
+        format!(" \"subvol=@boot,{}\"", layout.btrfs_mount_options)
     } else {
-        ""
+        String::new()
     };
 
     // Generate /boot mount section depending on boot encryption
@@ -388,7 +407,7 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
         format!(
             r#"    # Mount unencrypted /boot partition
     boot_partition=""
-    for dev in $(blkid -t LABEL=BOOT -o device 2>/dev/null); do
+    for dev in $(blkid -t LABEL={boot_label} -o device 2>/dev/null); do
         boot_partition="$dev"
         break
     done
@@ -396,8 +415,9 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
     if [ -n "$boot_partition" ] && [ -b "$boot_partition" ]; then
         mount_volume "$boot_partition" "$new_root/boot" "boot"{boot_opts} || true
     else
-        echo "[mountcrypt] Warning: BOOT partition not found" >&2
+        echo "[mountcrypt] Warning: {boot_label} partition not found" >&2
     fi"#,
+            boot_label = config.disk.fs_label("BOOT"),
             boot_opts = boot_extra_opts,
         )
     };
@@ -406,6 +426,12 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
     // Root is mounted first (fatal on failure). Other volumes are best-effort.
     let mut volume_mounts = String::new();
     let use_subvolumes = layout.uses_subvolumes();
+    // f2fs compression options for plain (non-subvolume) data volumes
+    let data_opts = layout
+        .f2fs_mount_options
+        .as_ref()
+        .map(|o| format!(" \"{}\"", o))
+        .unwrap_or_default();
 
     // Root must always be first
     let has_root = luks_data_parts
@@ -414,7 +440,7 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
     if has_root {
         if use_subvolumes {
             // Mount root with @ subvolume
-            let root_svols = multi_volume_subvolumes("Root");
+            let root_svols = multi_volume_subvolumes("Root", &layout.btrfs_mount_options);
             volume_mounts.push_str(&format!(
                 r#"    # Mount root first (required) — subvol={sv_name}
     echo "[mountcrypt] === Mounting root (subvol={sv_name}) ==="
@@ -427,15 +453,16 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
                 sv_opts = root_svols[0].mount_options,
             ));
         } else {
-            volume_mounts.push_str(
+            volume_mounts.push_str(&format!(
                 r#"    # Mount root first (required)
     echo "[mountcrypt] === Mounting root ==="
-    if ! mount_volume "/dev/mapper/Crypt-Root" "$new_root" "root"; then
+    if ! mount_volume "/dev/mapper/Crypt-Root" "$new_root" "root"{data_opts}; then
         echo "[mountcrypt] FATAL: Cannot mount root filesystem" >&2
         return 1
     fi
 "#,
-            );
+                data_opts = data_opts,
+            ));
         }
     }
 
@@ -459,7 +486,7 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
         let mapper = format!("Crypt-{}", title);
 
         if use_subvolumes {
-            let svols = multi_volume_subvolumes(&title);
+            let svols = multi_volume_subvolumes(&title, &layout.btrfs_mount_options);
             for sv in &svols {
                 // /usr failure is a hard error; everything else is a warning
                 let severity = if sv.mount_point == "/usr" {
@@ -502,11 +529,12 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
                 r#"
     # Mount {mp}
     echo "[mountcrypt] === Mounting {mp} ==="
-    if ! mount_volume "/dev/mapper/{mapper}" "$new_root{mp}" "{name}"; then
+    if ! mount_volume "/dev/mapper/{mapper}" "$new_root{mp}" "{name}"{data_opts}; then
         echo "[mountcrypt] {severity}: Failed to mount {mp}" >&2
 {fail_action}
     fi
 "#,
+                data_opts = data_opts,
                 mp = mp,
                 mapper = mapper,
                 name = part.name.to_lowercase(),
@@ -533,6 +561,50 @@ fn generate_mountcrypt_hook(config: &DeploymentConfig, layout: &ComputedLayout)
         .collect();
     let volume_comment = volume_list.join("\n");
 
+    // Mount the ESP on UEFI installs; BIOS layouts have no EFI partition.
+    let efi_mount_section = if layout.partitions.iter().any(|p| p.is_efi) {
+        format!(
+            r#"    # Mount EFI partition (after /boot, which may contain the ESP mount point)
+    echo "[mountcrypt] === Mounting EFI ==="
+    mkdir -p "$new_root{esp}"
+
+    efi_partition=""
+
+    # Primary: use udev-provided partlabel symlink (most reliable in initramfs)
+    if [ -b "/dev/disk/by-partlabel/EFI" ]; then
+        efi_partition="/dev/disk/by-partlabel/EFI"
+    fi
+
+    # Fallback: blkid search by PARTLABEL
+    if [ -z "$efi_partition" ]; then
+        for dev in $(blkid -t TYPE=vfat -o device 2>/dev/null); do
+            if blkid "$dev" | grep -qi 'PARTLABEL="EFI"'; then
+                efi_partition="$dev"
+                break
+            fi
+        done
+    fi
+
+    # Last resort: first vfat partition
+    if [ -z "$efi_partition" ]; then
+        efi_partition=$(blkid -t TYPE=vfat -o device 2>/dev/null | head -n1)
+    fi
+
+    if [ -n "$efi_partition" ] && [ -b "$efi_partition" ]; then
+        mount_volume "$efi_partition" "$new_root{esp}" "efi" || {{
+            echo "[mountcrypt] WARNING: Failed to mount EFI partition" >&2
+        }}
+    else
+        echo "[mountcrypt] WARNING: EFI partition not found, skipping" >&2
+    fi
+
+"#,
+            esp = layout.esp_mount_point()
+        )
+    } else {
+        String::new()
+    };
+
     let hook_content = format!(
         r#"#!/usr/bin/ash
 # mountcrypt: Mount multi-volume encrypted system
@@ -637,47 +709,14 @@ mountcrypt_handler() {{
     echo "[mountcrypt] === Mounting /boot ==="
 {boot_mount}
 
-    # Mount EFI partition (must come after /boot since it mounts to /boot/efi)
-    echo "[mountcrypt] === Mounting EFI ==="
-    mkdir -p "$new_root/boot/efi"
-
-    efi_partition=""
-
-    # Primary: use udev-provided partlabel symlink (most reliable in initramfs)
-    if [ -b "/dev/disk/by-partlabel/EFI" ]; then
-        efi_partition="/dev/disk/by-partlabel/EFI"
-    fi
-
-    # Fallback: blkid search by PARTLABEL
-    if [ -z "$efi_partition" ]; then
-        for dev in $(blkid -t TYPE=vfat -o device 2>/dev/null); do
-            if blkid "$dev" | grep -qi 'PARTLABEL="EFI"'; then
-                efi_partition="$dev"
-                break
-            fi
-        done
-    fi
-
-    # Last resort: first vfat partition
-    if [ -z "$efi_partition" ]; then
-        efi_partition=$(blkid -t TYPE=vfat -o device 2>/dev/null | head -n1)
-    fi
-
-    if [ -n "$efi_partition" ] && [ -b "$efi_partition" ]; then
-        mount_volume "$efi_partition" "$new_root/boot/efi" "efi" || {{
-            echo "[mountcrypt] WARNING: Failed to mount EFI partition" >&2
-        }}
-    else
-        echo "[mountcrypt] WARNING: EFI partition not found, skipping" >&2
-    fi
-
-    echo "[mountcrypt] Mount sequence complete"
+{efi_mount}    echo "[mountcrypt] Mount sequence complete"
     return $ret
 }}
 "#,
         volume_comment = volume_comment,
         volume_mounts = volume_mounts,
-        boot_mount = boot_mount_section
+        boot_mount = boot_mount_section,
+        efi_mount = efi_mount_section,
     );
 
     let help_volumes: Vec<String> = luks_data_parts
@@ -738,6 +777,11 @@ mod tests {
             partitions: vec![],
             total_mib: 0,
             subvolumes: None,
+            btrfs_mount_options: crate::disk::layouts::default_btrfs_mount_options(),
+            f2fs_mount_options: None,
+            discard: false,
+            filesystem_overrides: Default::default(),
+            mount_option_overrides: Default::default(),
             planned_thin_volumes: None,
         }
     }
@@ -848,6 +892,11 @@ mod tests {
             ],
             total_mib: 100000,
             subvolumes: None,
+            btrfs_mount_options: crate::disk::layouts::default_btrfs_mount_options(),
+            f2fs_mount_options: None,
+            discard: false,
+            filesystem_overrides: Default::default(),
+            mount_option_overrides: Default::default(),
             planned_thin_volumes: None,
         }
     }
@@ -916,6 +965,11 @@ mod tests {
             ],
             total_mib: 100000,
             subvolumes: None,
+            btrfs_mount_options: crate::disk::layouts::default_btrfs_mount_options(),
+            f2fs_mount_options: None,
+            discard: false,
+            filesystem_overrides: Default::default(),
+            mount_option_overrides: Default::default(),
             planned_thin_volumes: None,
         }
     }
@@ -963,6 +1017,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn crypttab_unlock_hook_tries_fido2_token_first() {
+        let hook = generate_crypttab_unlock_hook();
+        assert!(hook.hook_content.contains("*fido2-device=*"));
+        assert!(hook.hook_content.contains("--token-only"));
+        assert!(hook
+            .install_content
+            .contains("libcryptsetup-token-systemd-fido2.so"));
+    }
+
     #[test]
     fn mountcrypt_hook_mounts_all_encrypted_partitions() {
         let cfg = config_encrypted(true);
@@ -1006,6 +1070,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn mountcrypt_hook_uses_boot_label_override() {
+        let mut cfg = config_encrypted(true);
+        cfg.disk.boot_encryption = false;
+        cfg.disk.labels.insert("BOOT".into(), "BOOT_B".into());
+        let hook = generate_mountcrypt_hook(&cfg, &standard_encrypted_layout());
+        assert!(hook.hook_content.contains("LABEL=BOOT_B "));
+    }
+
+    #[test]
+    fn mountcrypt_hook_mounts_esp_at_efi() {
+        let cfg = config_encrypted(true);
+        let mut layout = standard_encrypted_layout();
+        for p in layout.partitions.iter_mut().filter(|p| p.is_efi) {
+            p.mount_point = Some("/efi".into());
+        }
+        let hook = generate_mountcrypt_hook(&cfg, &layout);
+        assert!(hook.hook_content.contains(r#"mkdir -p "$new_root/efi""#));
+        assert!(hook.hook_content.contains(r#""$new_root/efi" "efi""#));
+        assert!(!hook.hook_content.contains("/boot/efi"));
+    }
+
+    #[test]
+    fn mountcrypt_hook_skips_esp_without_efi_partition() {
+        let cfg = config_encrypted(true);
+        let mut layout = standard_encrypted_layout();
+        layout.partitions.retain(|p| !p.is_efi);
+        let hook = generate_mountcrypt_hook(&cfg, &layout);
+        assert!(!hook.hook_content.contains("Mounting EFI"));
+        assert!(hook.hook_content.contains("Mount sequence complete"));
+    }
+
     #[test]
     fn mountcrypt_hook_unencrypted_boot() {
         let mut cfg = config_encrypted(true);
@@ -1,5 +1,6 @@
 //! LUKS keyfile generation and management
 
+use crate::config::DiskConfig;
 use crate::configure::encryption::LuksContainer;
 use crate::utils::command::CommandRunner;
 use crate::utils::error::{DeploytixError, Result};
@@ -9,27 +10,73 @@ use std::os::unix::fs::PermissionsExt;
 use std::process::{Command, Stdio};
 use tracing::info;
 
-/// Keyfile directory path (inside installed system)
+/// Default keyfile directory path (inside installed system)
 pub const KEYFILE_DIR: &str = "/etc/cryptsetup-keys.d";
 
-/// Keyfile size in bytes (512 bytes = 4096 bits)
-const KEYFILE_SIZE: usize = 512;
+/// Keyslot the recovery passphrase is enrolled into.
+pub const RECOVERY_KEYSLOT: u8 = 1;
 
-/// Generate a keyfile path for a given volume name
-pub fn keyfile_path(volume_name: &str) -> String {
-    format!("{}/crypt{}.key", KEYFILE_DIR, volume_name.to_lowercase())
+/// Default keyfile size in bytes (512 bytes = 4096 bits)
+pub const DEFAULT_KEYFILE_SIZE: usize = 512;
+
+/// Accepted range for `keyfile_size_bytes`; cryptsetup reads at most
+/// 8 KiB from a keyfile by default.
+const KEYFILE_SIZE_RANGE: std::ops::RangeInclusive<usize> = 512..=8192;
+
+/// Generate a keyfile path for a given volume name under `dir`
+pub fn keyfile_path(dir: &str, volume_name: &str) -> String {
+    format!(
+        "{}/crypt{}.key",
+        dir.trim_end_matches('/'),
+        volume_name.to_lowercase()
+    )
+}
+
+/// Check `keyfile_dir` and `keyfile_size_bytes`.  The directory ends up in
+/// crypttab and the mkinitcpio FILES array, so it must be an absolute path
+/// without whitespace or quotes.
+pub fn validate_keyfile_settings(disk: &DiskConfig) -> Result<()> {
+    if let Some(size) = disk.keyfile_size_bytes {
+        if !KEYFILE_SIZE_RANGE.contains(&size) {
+            return Err(DeploytixError::ValidationError(format!(
+                "keyfile_size_bytes must be between {} and {} (got {})",
+                KEYFILE_SIZE_RANGE.start(),
+                KEYFILE_SIZE_RANGE.end(),
+                size
+            )));
+        }
+    }
+
+    let dir = &disk.keyfile_dir;
+    if !dir.starts_with('/')
+        || dir.trim_end_matches('/').is_empty()
+        || dir
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\'')
+    {
+        return Err(DeploytixError::ValidationError(format!(
+            "keyfile_dir must be an absolute directory path without spaces or quotes: '{}'",
+            dir
+        )));
+    }
+    if dir == "/boot" || dir.starts_with("/boot/") {
+        return Err(DeploytixError::ValidationError(
+            "keyfile_dir must not be under /boot".to_string(),
+        ));
+    }
+    Ok(())
 }
 
-/// Generate a secure random keyfile
-pub fn generate_keyfile(cmd: &CommandRunner, path: &str) -> Result<()> {
+/// Generate a secure random keyfile of `size` bytes
+pub fn generate_keyfile(cmd: &CommandRunner, path: &str, size: usize) -> Result<()> {
     info!("Generating keyfile: {}", path);
 
     if cmd.is_dry_run() {
-        println!(
-            "  [dry-run] dd if=/dev/random of={} bs={} count=1 iflag=fullblock",
-            path, KEYFILE_SIZE
-        );
-        println!("  [dry-run] chmod 000 {}", path);
+        cmd.log_dry_run(&format!(
+            "dd if=/dev/random of={} bs={} count=1 iflag=fullblock",
+            path, size
+        ));
+        cmd.log_dry_run(&format!("chmod 400 {}", path));
         return Ok(());
     }
 
@@ -44,16 +91,16 @@ pub fn generate_keyfile(cmd: &CommandRunner, path: &str) -> Result<()> {
         &[
             "if=/dev/random",
             &format!("of={}", path),
-            &format!("bs={}", KEYFILE_SIZE),
+            &format!("bs={}", size),
             "count=1",
             "iflag=fullblock",
         ],
     )?;
 
-    // Set restrictive permissions (mode 000 - only root can read via capabilities)
-    fs::set_permissions(path, fs::Permissions::from_mode(0o000))?;
+    // Read-only for root
+    fs::set_permissions(path, fs::Permissions::from_mode(0o400))?;
 
-    info!("Keyfile generated: {} (mode 000)", path);
+    info!("Keyfile generated: {} (mode 400)", path);
     Ok(())
 }
 
@@ -67,11 +114,12 @@ pub fn add_keyfile_to_luks(
     info!("Adding keyfile {} to LUKS device {}", keyfile, device);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] cryptsetup luksAddKey {} {}", device, keyfile);
+        cmd.log_dry_run(&format!("cryptsetup luksAddKey {} {}", device, keyfile));
         return Ok(());
     }
 
     // Use stdin to pass password securely
+    cmd.transcribe_stdin("cryptsetup", &["luksAddKey", device, keyfile]);
     let mut child = Command::new("cryptsetup")
         .args(["luksAddKey", device, keyfile])
         .stdin(Stdio::piped())
@@ -99,6 +147,108 @@ pub fn add_keyfile_to_luks(
     Ok(())
 }
 
+/// Whether `cryptsetup luksDump` output shows keyslot `slot` in use, for
+/// both LUKS1 (`Key Slot 1: ENABLED`) and LUKS2 (`  1: luks2` under
+/// `Keyslots:`).
+fn keyslot_in_use(dump: &str, slot: u8) -> bool {
+    let mut in_keyslots = false;
+    for line in dump.lines() {
+        if line.trim_end() == format!("Key Slot {}: ENABLED", slot) {
+            return true;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            in_keyslots = line.trim_end() == "Keyslots:";
+        } else if in_keyslots && line.trim_start().starts_with(&format!("{}: ", slot)) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Add `new_passphrase` to keyslot [`RECOVERY_KEYSLOT`] of a LUKS
+/// container, unlocking with `password`, then check it by test-opening the
+/// container with it (no mapping is created).  Fails if the slot is taken.
+pub fn add_passphrase_to_luks(
+    cmd: &CommandRunner,
+    device: &str,
+    password: &str,
+    new_passphrase: &str,
+) -> Result<()> {
+    info!("Adding recovery passphrase to LUKS device {}", device);
+
+    let slot = RECOVERY_KEYSLOT.to_string();
+    let add_args = ["luksAddKey", "--key-slot", &slot, device];
+    if cmd.is_dry_run() {
+        cmd.log_dry_run(&format!("cryptsetup {}", add_args.join(" ")));
+        cmd.log_dry_run(&format!("cryptsetup open --test-passphrase {}", device));
+        return Ok(());
+    }
+
+    let dump = cmd
+        .run("cryptsetup", &["luksDump", device])?
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default();
+    if keyslot_in_use(&dump, RECOVERY_KEYSLOT) {
+        return Err(DeploytixError::ConfigError(format!(
+            "Keyslot {} of {} is already in use; the recovery passphrase needs it free",
+            RECOVERY_KEYSLOT, device
+        )));
+    }
+
+    // Both passphrases go through stdin, one per line: existing, then new
+    let stdin_input = format!("{}\n{}\n", password, new_passphrase);
+    cmd.transcribe_stdin("cryptsetup", &add_args);
+    run_cryptsetup_with_stdin(&add_args, &stdin_input).map_err(|_| {
+        DeploytixError::CommandFailed {
+            command: "cryptsetup luksAddKey".to_string(),
+            stderr: format!(
+                "Failed to add recovery passphrase to keyslot {} of {}",
+                RECOVERY_KEYSLOT, device
+            ),
+        }
+    })?;
+
+    cmd.transcribe_stdin("cryptsetup", &["open", "--test-passphrase", device]);
+    run_cryptsetup_with_stdin(
+        &["open", "--test-passphrase", device],
+        &format!("{}\n", new_passphrase),
+    )
+    .map_err(|_| DeploytixError::CommandFailed {
+        command: "cryptsetup open --test-passphrase".to_string(),
+        stderr: format!(
+            "Recovery passphrase was added to {} but does not unlock it",
+            device
+        ),
+    })?;
+
+    info!("Recovery passphrase verified on {}", device);
+    Ok(())
+}
+
+/// Run cryptsetup with `input` on stdin; an error means it exited non-zero.
+fn run_cryptsetup_with_stdin(args: &[&str], input: &str) -> Result<()> {
+    let mut child = Command::new("cryptsetup")
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| DeploytixError::CommandFailed {
+            command: format!("cryptsetup {}", args[0]),
+            stderr: e.to_string(),
+        })?;
+    if let Some(ref mut stdin) = child.stdin {
+        stdin.write_all(input.as_bytes())?;
+    }
+    drop(child.stdin.take());
+
+    if !child.wait()?.success() {
+        return Err(DeploytixError::CommandFailed {
+            command: format!("cryptsetup {}", args[0]),
+            stderr: String::new(),
+        });
+    }
+    Ok(())
+}
+
 /// Volume keyfile information
 #[derive(Debug, Clone)]
 pub struct VolumeKeyfile {
@@ -113,12 +263,13 @@ pub struct VolumeKeyfile {
 
 /// Setup keyfiles for all encrypted volumes
 ///
-/// This creates keyfiles in the installed system's /etc/cryptsetup-keys.d/
+/// This creates keyfiles in the installed system's `disk.keyfile_dir`
 /// and adds them to each LUKS container for automatic unlocking.
 pub fn setup_keyfiles_for_volumes(
     cmd: &CommandRunner,
     containers: &[LuksContainer],
     password: &str,
+    disk: &DiskConfig,
     install_root: &str,
 ) -> Result<Vec<VolumeKeyfile>> {
     info!(
@@ -129,7 +280,7 @@ pub fn setup_keyfiles_for_volumes(
     let mut keyfiles = Vec::new();
 
     // Create keyfile directory in installed system
-    let keyfile_dir = format!("{}{}", install_root, KEYFILE_DIR);
+    let keyfile_dir = format!("{}{}", install_root, disk.keyfile_dir);
     if !cmd.is_dry_run() {
         fs::create_dir_all(&keyfile_dir)?;
         // Set directory permissions to 700
@@ -140,11 +291,11 @@ pub fn setup_keyfiles_for_volumes(
         let volume_name = container.volume_name.clone();
 
         // Generate keyfile path (inside installed system)
-        let keyfile_rel = keyfile_path(&volume_name);
+        let keyfile_rel = keyfile_path(&disk.keyfile_dir, &volume_name);
         let keyfile_full = format!("{}{}", install_root, keyfile_rel);
 
         // Generate the keyfile
-        generate_keyfile(cmd, &keyfile_full)?;
+        generate_keyfile(cmd, &keyfile_full, disk.keyfile_size())?;
 
         // Add keyfile to LUKS container
         add_keyfile_to_luks(cmd, &container.device, password, &keyfile_full)?;
@@ -172,7 +323,49 @@ mod tests {
 
     #[test]
     fn test_keyfile_path() {
-        assert_eq!(keyfile_path("Root"), "/etc/cryptsetup-keys.d/cryptroot.key");
-        assert_eq!(keyfile_path("Usr"), "/etc/cryptsetup-keys.d/cryptusr.key");
+        assert_eq!(
+            keyfile_path(KEYFILE_DIR, "Root"),
+            "/etc/cryptsetup-keys.d/cryptroot.key"
+        );
+        assert_eq!(keyfile_path("/etc/keys/", "Usr"), "/etc/keys/cryptusr.key");
+    }
+
+    #[test]
+    fn keyslot_use_is_read_from_luks1_and_luks2_dumps() {
+        let luks2 = "LUKS header information\n\
+                     Version:       \t2\n\
+                     Keyslots:\n  \
+                       0: luks2\n\
+                     \tKey:        512 bits\n\
+                     Tokens:\n\
+                     Digests:\n  \
+                       0: pbkdf2\n  \
+                       1: pbkdf2\n";
+        assert!(keyslot_in_use(luks2, 0));
+        assert!(!keyslot_in_use(luks2, 1));
+        assert!(keyslot_in_use(
+            &luks2.replace("Tokens:", "  1: luks2\nTokens:"),
+            1
+        ));
+
+        let luks1 = "Key Slot 0: ENABLED\nKey Slot 1: DISABLED\nKey Slot 2: ENABLED\n";
+        assert!(!keyslot_in_use(luks1, 1));
+        assert!(keyslot_in_use(luks1, 2));
+    }
+
+    #[test]
+    fn keyfile_settings_are_validated() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        assert!(validate_keyfile_settings(&disk).is_ok());
+
+        disk.keyfile_size_bytes = Some(256);
+        assert!(validate_keyfile_settings(&disk).is_err());
+        disk.keyfile_size_bytes = Some(8192);
+        assert!(validate_keyfile_settings(&disk).is_ok());
+
+        for dir in ["etc/keys", "/", "/etc/my keys", "/boot/keys"] {
+            disk.keyfile_dir = dir.to_string();
+            assert!(validate_keyfile_settings(&disk).is_err(), "{}", dir);
+        }
     }
 }
@@ -1,12 +1,54 @@
 //! Locale and timezone configuration
 
-use crate::config::DeploymentConfig;
-use crate::utils::command::CommandRunner;
+use crate::config::{DeploymentConfig, SystemConfig};
+use crate::utils::command::{run_command_with_timeout, CommandRunner};
 use crate::utils::error::Result;
 use std::fs;
-use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
-use tracing::info;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// `system.timezone` value that asks for detection at install time.
+pub const AUTO_TIMEZONE: &str = "auto";
+
+/// Time zone database.
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// tzdata's list of the canonical zones, one per line after the country
+/// code and coordinates.
+const ZONE_TAB: &str = "/usr/share/zoneinfo/zone.tab";
+
+/// GeoIP endpoint answering with the caller's zone name as plain text.
+const GEOIP_TIMEZONE_URL: &str = "https://ipapi.co/timezone";
+
+/// How long to wait for the GeoIP lookup before falling back.
+const GEOIP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Console fonts shipped by kbd and terminus-font.
+const CONSOLEFONTS_DIR: &str = "/usr/share/kbd/consolefonts";
+
+/// Package installed when a console font is configured.
+pub const CONSOLE_FONT_PACKAGE: &str = "terminus-font";
+
+/// glibc's list of the locales `locale-gen` can build.
+const SUPPORTED_LOCALES: &str = "/usr/share/i18n/SUPPORTED";
+
+/// Categories that may be overridden in locale.conf.
+pub const LOCALE_CATEGORIES: &[&str] = &[
+    "LC_ADDRESS",
+    "LC_COLLATE",
+    "LC_CTYPE",
+    "LC_IDENTIFICATION",
+    "LC_MEASUREMENT",
+    "LC_MESSAGES",
+    "LC_MONETARY",
+    "LC_NAME",
+    "LC_NUMERIC",
+    "LC_PAPER",
+    "LC_TELEPHONE",
+    "LC_TIME",
+];
 
 /// Configure locale, timezone, and keymap
 pub fn configure_locale(
@@ -19,11 +61,16 @@ pub fn configure_locale(
     // Set timezone
     set_timezone(cmd, &config.system.timezone, install_root)?;
 
-    // Configure locale
-    set_locale(cmd, &config.system.locale, install_root)?;
+    // Configure locales
+    set_locale(cmd, &config.system, install_root)?;
 
-    // Set keymap
-    set_keymap(cmd, &config.system.keymap, install_root)?;
+    // Set keymap and console font
+    set_vconsole(
+        cmd,
+        &config.system.keymap,
+        config.system.console_font.as_deref(),
+        install_root,
+    )?;
 
     // Set hostname
     set_hostname(cmd, &config.system.hostname, install_root)?;
@@ -39,7 +86,7 @@ fn set_timezone(cmd: &CommandRunner, timezone: &str, install_root: &str) -> Resu
     let localtime_path = format!("{}/etc/localtime", install_root);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] ln -sf {} {}", zoneinfo_path, localtime_path);
+        cmd.log_dry_run(&format!("ln -sf {} {}", zoneinfo_path, localtime_path));
         return Ok(());
     }
 
@@ -55,36 +102,188 @@ fn set_timezone(cmd: &CommandRunner, timezone: &str, install_root: &str) -> Resu
     Ok(())
 }
 
-/// Configure locale
-fn set_locale(cmd: &CommandRunner, locale: &str, install_root: &str) -> Result<()> {
-    info!("Setting locale to {}", locale);
+/// Whether `zone` names a time zone under `/usr/share/zoneinfo` on the
+/// host.  Any well-formed name passes when the database is unavailable.
+pub fn is_valid_timezone(zone: &str) -> bool {
+    let well_formed = !zone.is_empty()
+        && !zone.starts_with('/')
+        && zone
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..");
+    if !well_formed {
+        return false;
+    }
+    if !Path::new(ZONEINFO_DIR).is_dir() {
+        return true;
+    }
+    Path::new(ZONEINFO_DIR).join(zone).is_file()
+}
+
+/// Whether `zone` is listed in zone.tab `content` (UTC always is).
+fn zone_tab_lists(content: &str, zone: &str) -> bool {
+    zone == "UTC"
+        || content
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .any(|line| line.split('\t').nth(2) == Some(zone))
+}
+
+/// Whether a detected `zone` is a canonical zone per the host's zone.tab;
+/// falls back to [`is_valid_timezone`] when the table is unavailable.
+fn is_listed_timezone(zone: &str) -> bool {
+    match fs::read_to_string(ZONE_TAB) {
+        Ok(content) => is_valid_timezone(zone) && zone_tab_lists(&content, zone),
+        Err(_) => is_valid_timezone(zone),
+    }
+}
+
+/// Detect the time zone for `timezone = "auto"`: from GeoIP when the
+/// network is up, else from the live environment's `/etc/localtime`, else
+/// UTC.  A dry run skips the GeoIP lookup.
+pub fn detect_timezone(dry_run: bool) -> String {
+    if dry_run {
+        info!("[dry-run] Skipping the GeoIP timezone lookup");
+    } else if let Some(zone) = geoip_timezone() {
+        info!("Detected timezone {} via GeoIP", zone);
+        return zone;
+    }
+    let local = fs::read_link("/etc/localtime").ok().and_then(|target| {
+        let target = target.to_string_lossy().to_string();
+        let (_, zone) = target.split_once("zoneinfo/")?;
+        Some(zone.to_string())
+    });
+    match local.filter(|zone| is_listed_timezone(zone)) {
+        Some(zone) => {
+            info!("Using the live environment's timezone {}", zone);
+            zone
+        }
+        None => {
+            warn!("Could not detect the timezone; using UTC");
+            "UTC".to_string()
+        }
+    }
+}
+
+fn geoip_timezone() -> Option<String> {
+    let output = run_command_with_timeout(
+        "curl",
+        &["-fsS", "--max-time", "8", GEOIP_TIMEZONE_URL],
+        GEOIP_TIMEOUT,
+    )
+    .map_err(|e| warn!("GeoIP timezone lookup failed: {}", e))
+    .ok()?;
+    let zone = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !is_listed_timezone(&zone) {
+        warn!("GeoIP answered with an unknown timezone '{}'", zone);
+        return None;
+    }
+    Some(zone)
+}
+
+/// Whether `locale` is one glibc can generate, per the host's SUPPORTED
+/// list.  Anything passes when the list is unavailable.
+pub fn is_supported_locale(locale: &str) -> bool {
+    match fs::read_to_string(SUPPORTED_LOCALES) {
+        Ok(supported) => supported_entry(locale, &supported).is_some(),
+        Err(_) => !locale.is_empty() && !locale.chars().any(char::is_whitespace),
+    }
+}
+
+/// The SUPPORTED line ("<locale> <charset>") for `locale`.
+fn supported_entry<'a>(locale: &str, supported: &'a str) -> Option<&'a str> {
+    supported
+        .lines()
+        .find(|l| l.split_whitespace().next() == Some(locale))
+}
+
+/// Every locale to generate: the configured ones, then any used only by
+/// an override.
+pub fn locales_to_generate(system: &SystemConfig) -> Vec<String> {
+    let mut locales = system.locales.clone();
+    let mut overrides: Vec<&String> = system.locale_overrides.values().collect();
+    overrides.sort();
+    for locale in overrides {
+        if !locales.contains(locale) {
+            locales.push(locale.clone());
+        }
+    }
+    locales
+}
+
+/// Uncomment the locale.gen line for each entry ("<locale> <charset>"),
+/// appending entries the file does not list.
+pub fn enable_locale_gen_entries(content: &str, entries: &[String]) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    for entry in entries {
+        let matches = |line: &str| {
+            line.trim_start_matches('#')
+                .split_whitespace()
+                .eq(entry.split_whitespace())
+        };
+        match lines.iter_mut().find(|l| matches(l)) {
+            Some(line) => *line = entry.clone(),
+            None => lines.push(entry.clone()),
+        }
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Render `/etc/locale.conf`: `LANG` and the `LC_*` overrides.
+pub fn locale_conf(system: &SystemConfig) -> String {
+    let mut content = format!("LANG={}\n", system.lang());
+    let mut overrides: Vec<_> = system.locale_overrides.iter().collect();
+    overrides.sort();
+    for (category, locale) in overrides {
+        content.push_str(&format!("{}={}\n", category, locale));
+    }
+    content
+}
+
+/// Configure locales
+fn set_locale(cmd: &CommandRunner, system: &SystemConfig, install_root: &str) -> Result<()> {
+    let locales = locales_to_generate(system);
+    info!(
+        "Setting locale to {} (generating {})",
+        system.lang(),
+        locales.join(", ")
+    );
 
     let locale_gen_path = format!("{}/etc/locale.gen", install_root);
     let locale_conf_path = format!("{}/etc/locale.conf", install_root);
 
     if cmd.is_dry_run() {
         println!(
-            "  [dry-run] Would configure locale {} in {}",
-            locale, install_root
+            "  [dry-run] Would enable {} in {}",
+            locales.join(", "),
+            locale_gen_path
         );
+        println!("  [dry-run] Would write {}:", locale_conf_path);
+        for line in locale_conf(system).lines() {
+            println!("    {}", line);
+        }
         return Ok(());
     }
 
-    // Enable locale in locale.gen
-    let locale_entry = format!("{} UTF-8", locale);
+    // Enable each locale in locale.gen, with the charset glibc lists for it
+    let supported =
+        fs::read_to_string(format!("{}{}", install_root, SUPPORTED_LOCALES)).unwrap_or_default();
+    let entries: Vec<String> = locales
+        .iter()
+        .map(|locale| match supported_entry(locale, &supported) {
+            Some(entry) => entry.to_string(),
+            None => format!("{} UTF-8", locale),
+        })
+        .collect();
     let locale_gen_content = fs::read_to_string(&locale_gen_path).unwrap_or_default();
-
-    if !locale_gen_content.contains(&locale_entry) {
-        let mut file = fs::OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&locale_gen_path)?;
-        writeln!(file, "{}", locale_entry)?;
-    }
+    fs::write(
+        &locale_gen_path,
+        enable_locale_gen_entries(&locale_gen_content, &entries),
+    )?;
 
     // Create locale.conf
-    let locale_conf_content = format!("LANG={}\n", locale);
-    fs::write(&locale_conf_path, locale_conf_content)?;
+    fs::write(&locale_conf_path, locale_conf(system))?;
 
     // Generate locales
     cmd.run_in_chroot(install_root, "locale-gen")?;
@@ -92,39 +291,85 @@ fn set_locale(cmd: &CommandRunner, locale: &str, install_root: &str) -> Result<(
     Ok(())
 }
 
-/// Set keyboard layout
-fn set_keymap(cmd: &CommandRunner, keymap: &str, install_root: &str) -> Result<()> {
-    info!("Setting keymap to {}", keymap);
+/// Whether `font` names a console font: a Terminus font (`ter-*`, from
+/// terminus-font) or one under `/usr/share/kbd/consolefonts` on the host.
+/// Any plausible name passes when the host has no fonts to check against.
+pub fn is_known_console_font(font: &str) -> bool {
+    if font.is_empty() || font.contains('/') || font.chars().any(char::is_whitespace) {
+        return false;
+    }
+    font.starts_with("ter-") || font_in_dir(Path::new(CONSOLEFONTS_DIR), font).unwrap_or(true)
+}
+
+/// Whether `dir` holds `font` (with or without `.psf`/`.psfu`/`.gz`
+/// suffixes); `None` if the directory cannot be read.
+fn font_in_dir(dir: &Path, font: &str) -> Option<bool> {
+    let entries = fs::read_dir(dir).ok()?;
+    Some(entries.filter_map(|e| e.ok()).any(|e| {
+        let name = e.file_name().to_string_lossy().to_string();
+        name.strip_prefix(font)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    }))
+}
+
+/// Render `/etc/vconsole.conf`.  The mkinitcpio `keymap` and `consolefont`
+/// hooks read it, so both apply from early boot.
+pub fn vconsole_conf(keymap: &str, font: Option<&str>) -> String {
+    let mut content = format!("KEYMAP={}\n", keymap);
+    if let Some(font) = font {
+        content.push_str(&format!("FONT={}\n", font));
+    }
+    content
+}
+
+/// Set keyboard layout and console font
+fn set_vconsole(
+    cmd: &CommandRunner,
+    keymap: &str,
+    font: Option<&str>,
+    install_root: &str,
+) -> Result<()> {
+    info!(
+        "Setting keymap to {} (console font: {})",
+        keymap,
+        font.unwrap_or("default")
+    );
 
     let vconsole_path = format!("{}/etc/vconsole.conf", install_root);
 
     if cmd.is_dry_run() {
-        println!(
-            "  [dry-run] Would set keymap {} in {}",
-            keymap, vconsole_path
-        );
+        println!("  [dry-run] Would write {}:", vconsole_path);
+        for line in vconsole_conf(keymap, font).lines() {
+            println!("    {}", line);
+        }
         return Ok(());
     }
 
-    let content = format!("KEYMAP={}\n", keymap);
-    fs::write(&vconsole_path, content)?;
+    fs::write(&vconsole_path, vconsole_conf(keymap, font))?;
 
     Ok(())
 }
 
-/// Create a dinit service that loads the console keymap from
-/// `/etc/vconsole.conf` at boot.
+/// Create a dinit service that loads the console keymap (and font, if
+/// set) at boot.
 ///
 /// Unlike runit/openrc/s6, dinit does not ship a built-in service
 /// for keymap loading, so we provide one.
-pub fn create_dinit_keymap_service(install_root: &str, keymap: &str) -> Result<()> {
+pub fn create_dinit_keymap_service(
+    install_root: &str,
+    keymap: &str,
+    font: Option<&str>,
+) -> Result<()> {
     info!("Creating dinit keymap service for '{}'", keymap);
 
     // Script that loads the keymap
     let script_dir = format!("{}/usr/local/bin", install_root);
     fs::create_dir_all(&script_dir)?;
 
-    let script = format!("#!/bin/sh\nloadkeys {}\n", keymap);
+    let mut script = format!("#!/bin/sh\nloadkeys {}\n", keymap);
+    if let Some(font) = font {
+        script.push_str(&format!("setfont {}\n", font));
+    }
     let script_path = format!("{}/loadkeys-boot", script_dir);
     fs::write(&script_path, script)?;
     let mut perms = fs::metadata(&script_path)?.permissions();
@@ -172,3 +417,90 @@ fn set_hostname(cmd: &CommandRunner, hostname: &str, install_root: &str) -> Resu
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timezone_names_must_be_relative_zone_paths() {
+        assert!(is_valid_timezone("UTC"));
+        assert!(is_valid_timezone("Europe/Berlin"));
+        assert!(!is_valid_timezone(""));
+        assert!(!is_valid_timezone("/etc/passwd"));
+        assert!(!is_valid_timezone("../../etc/passwd"));
+        assert!(!is_valid_timezone("Europe//Berlin"));
+    }
+
+    #[test]
+    fn detected_zones_must_be_listed_in_zone_tab() {
+        let tab = "# comment\tx\tEurope/Oslo\n\
+                   DE\t+5230+01322\tEurope/Berlin\tmost of Germany\n\
+                   NZ\t-3652+17446\tPacific/Auckland\n";
+        assert!(zone_tab_lists(tab, "Europe/Berlin"));
+        assert!(zone_tab_lists(tab, "Pacific/Auckland"));
+        assert!(zone_tab_lists(tab, "UTC"));
+        assert!(!zone_tab_lists(tab, "Europe/Oslo"));
+        assert!(!zone_tab_lists(tab, "posixrules"));
+        assert!(!zone_tab_lists(tab, "DE"));
+    }
+
+    #[test]
+    fn locale_gen_entries_are_uncommented_or_appended() {
+        let gen = "# comment\n#de_DE.UTF-8 UTF-8  \n#en_US.UTF-8 UTF-8\n";
+        let out = enable_locale_gen_entries(
+            gen,
+            &[
+                "en_US.UTF-8 UTF-8".to_string(),
+                "fr_FR ISO-8859-1".to_string(),
+            ],
+        );
+        assert_eq!(
+            out,
+            "# comment\n#de_DE.UTF-8 UTF-8  \nen_US.UTF-8 UTF-8\nfr_FR ISO-8859-1\n"
+        );
+        assert_eq!(
+            supported_entry("fr_FR", "fr_FR.UTF-8 UTF-8\nfr_FR ISO-8859-1\n"),
+            Some("fr_FR ISO-8859-1")
+        );
+    }
+
+    #[test]
+    fn overrides_extend_generated_locales() {
+        let toml = "locale = \"en_US.UTF-8\"\n[locale_overrides]\nLC_TIME = \"de_DE.UTF-8\"\nLC_PAPER = \"en_US.UTF-8\"\n";
+        let system: SystemConfig = toml::from_str(toml).unwrap();
+        assert_eq!(system.locales, vec!["en_US.UTF-8"]);
+        assert_eq!(
+            locales_to_generate(&system),
+            vec!["en_US.UTF-8", "de_DE.UTF-8"]
+        );
+        assert_eq!(
+            locale_conf(&system),
+            "LANG=en_US.UTF-8\nLC_PAPER=en_US.UTF-8\nLC_TIME=de_DE.UTF-8\n"
+        );
+    }
+
+    #[test]
+    fn vconsole_carries_font_when_set() {
+        assert_eq!(vconsole_conf("de", None), "KEYMAP=de\n");
+        assert_eq!(
+            vconsole_conf("us", Some("ter-132n")),
+            "KEYMAP=us\nFONT=ter-132n\n"
+        );
+    }
+
+    #[test]
+    fn console_fonts_are_looked_up_by_stem() {
+        let dir = std::env::temp_dir().join(format!("deploytix-fonts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("lat2-16.psfu.gz"), "").unwrap();
+        let found = |font: &str| font_in_dir(&dir, font);
+        assert_eq!(found("lat2-16"), Some(true));
+        assert_eq!(found("lat2"), Some(false));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(is_known_console_font("ter-132n"));
+        assert!(!is_known_console_font("../etc/passwd"));
+        assert!(!is_known_console_font(""));
+    }
+}
@@ -0,0 +1,216 @@
+//! CPU microcode and firmware package selection
+//!
+//! Microcode is either loaded from a separate early initrd
+//! (`/boot/<vendor>-ucode.img`, placed ahead of the initramfs by the
+//! bootloader) or bundled into the initramfs by the mkinitcpio `microcode`
+//! hook.  GRUB's `grub-mkconfig` picks up the separate images on its own;
+//! rEFInd has to be told about them with `initrd=` options.
+
+use crate::config::{DeploymentConfig, MicrocodeLoading};
+use std::fs;
+
+/// CPU vendor, for choosing the microcode package
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuVendor {
+    Intel,
+    Amd,
+}
+
+impl CpuVendor {
+    /// Vendor of the running CPU, from `/proc/cpuinfo`.
+    pub fn detect() -> Option<Self> {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+        Self::from_cpuinfo(&cpuinfo)
+    }
+
+    fn from_cpuinfo(cpuinfo: &str) -> Option<Self> {
+        let vendor = cpuinfo
+            .lines()
+            .find(|l| l.starts_with("vendor_id"))?
+            .split(':')
+            .nth(1)?
+            .trim();
+        match vendor {
+            "GenuineIntel" => Some(Self::Intel),
+            "AuthenticAMD" => Some(Self::Amd),
+            _ => None,
+        }
+    }
+
+    /// Package shipping this vendor's microcode.
+    pub fn package(&self) -> &'static str {
+        match self {
+            Self::Intel => "intel-ucode",
+            Self::Amd => "amd-ucode",
+        }
+    }
+
+    /// Early initrd image installed by [`Self::package`] into `/boot`.
+    pub fn image(&self) -> &'static str {
+        match self {
+            Self::Intel => "intel-ucode.img",
+            Self::Amd => "amd-ucode.img",
+        }
+    }
+}
+
+/// Vendors to install microcode for: the running CPU's, or both when it
+/// cannot be identified (the kernel ignores microcode for other vendors).
+fn vendors(config: &DeploymentConfig) -> Vec<CpuVendor> {
+    if config.system.microcode == MicrocodeLoading::None {
+        return Vec::new();
+    }
+    match CpuVendor::detect() {
+        Some(vendor) => vec![vendor],
+        None => vec![CpuVendor::Intel, CpuVendor::Amd],
+    }
+}
+
+/// Microcode packages to install.
+pub fn microcode_packages(config: &DeploymentConfig) -> Vec<String> {
+    vendors(config)
+        .iter()
+        .map(|v| v.package().to_string())
+        .collect()
+}
+
+/// Microcode images the bootloader must load as early initrds.  Empty
+/// unless microcode is loaded from a separate initrd.
+pub fn early_initrd_images(config: &DeploymentConfig) -> Vec<&'static str> {
+    if config.system.microcode != MicrocodeLoading::Initrd {
+        return Vec::new();
+    }
+    vendors(config).iter().map(|v| v.image()).collect()
+}
+
+/// Whether the mkinitcpio `microcode` hook should bundle microcode into
+/// the initramfs.
+pub fn uses_microcode_hook(config: &DeploymentConfig) -> bool {
+    config.system.microcode == MicrocodeLoading::Bundled
+}
+
+/// rEFInd `initrd=` options for the early microcode images.  Paths are
+/// relative to the `/boot` partition rEFInd loads the kernel from.
+pub fn refind_initrd_params(images: &[&str]) -> Vec<String> {
+    images
+        .iter()
+        .map(|img| format!("initrd=\\{}", img))
+        .collect()
+}
+
+/// Early microcode images that a generated `grub.cfg` fails to load.
+///
+/// `grub-mkconfig` only adds images it finds in `/boot`, so a missing one
+/// usually means the microcode package did not install.
+pub fn missing_from_grub_cfg(config: &DeploymentConfig, grub_cfg: &str) -> Vec<&'static str> {
+    early_initrd_images(config)
+        .into_iter()
+        .filter(|img| !grub_cfg.contains(&format!("/{}", img)))
+        .collect()
+}
+
+/// Whether `name` is `linux-firmware` or one of its split subpackages.
+pub fn is_firmware_package(name: &str) -> bool {
+    name == "linux-firmware"
+        || name.strip_prefix("linux-firmware-").is_some_and(|rest| {
+            !rest.is_empty()
+                && rest
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Firmware packages to install: the configured subset, or the full
+/// `linux-firmware` when none are selected.
+pub fn firmware_packages(config: &DeploymentConfig) -> Vec<String> {
+    if config.system.firmware_packages.is_empty() {
+        vec!["linux-firmware".to_string()]
+    } else {
+        config.system.firmware_packages.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vendor_from_cpuinfo() {
+        let intel = "processor\t: 0\nvendor_id\t: GenuineIntel\ncpu family\t: 6\n";
+        let amd = "processor\t: 0\nvendor_id\t: AuthenticAMD\n";
+        assert_eq!(CpuVendor::from_cpuinfo(intel), Some(CpuVendor::Intel));
+        assert_eq!(CpuVendor::from_cpuinfo(amd), Some(CpuVendor::Amd));
+        assert_eq!(CpuVendor::from_cpuinfo("processor\t: 0\n"), None);
+    }
+
+    #[test]
+    fn vendor_selects_microcode_package() {
+        assert_eq!(CpuVendor::Intel.package(), "intel-ucode");
+        assert_eq!(CpuVendor::Amd.package(), "amd-ucode");
+        let amd = CpuVendor::from_cpuinfo("vendor_id\t: AuthenticAMD\n").unwrap();
+        assert_eq!(amd.image(), "amd-ucode.img");
+    }
+
+    #[test]
+    fn microcode_accepts_bool_switch() {
+        let parse = |v: &str| {
+            let mut config = toml::to_string(&DeploymentConfig::sample()).unwrap();
+            config = config.replace("microcode = \"initrd\"", &format!("microcode = {}", v));
+            toml::from_str::<DeploymentConfig>(&config)
+                .unwrap()
+                .system
+                .microcode
+        };
+        assert_eq!(parse("false"), MicrocodeLoading::None);
+        assert_eq!(parse("true"), MicrocodeLoading::Initrd);
+        assert_eq!(parse("\"bundled\""), MicrocodeLoading::Bundled);
+    }
+
+    #[test]
+    fn grub_cfg_missing_microcode_is_reported() {
+        let config = DeploymentConfig::sample();
+        let images = early_initrd_images(&config);
+        let loaded: Vec<String> = images.iter().map(|i| format!("/{}", i)).collect();
+        let grub_cfg = format!("initrd {} /initramfs-linux-zen.img", loaded.join(" "));
+        assert!(missing_from_grub_cfg(&config, &grub_cfg).is_empty());
+        assert_eq!(
+            missing_from_grub_cfg(&config, "initrd /initramfs-linux-zen.img"),
+            images
+        );
+    }
+
+    #[test]
+    fn early_initrd_only_for_separate_image() {
+        let mut config = DeploymentConfig::sample();
+        assert!(!early_initrd_images(&config).is_empty());
+        assert!(!uses_microcode_hook(&config));
+
+        config.system.microcode = MicrocodeLoading::Bundled;
+        assert!(early_initrd_images(&config).is_empty());
+        assert!(uses_microcode_hook(&config));
+        assert!(!microcode_packages(&config).is_empty());
+
+        config.system.microcode = MicrocodeLoading::None;
+        assert!(microcode_packages(&config).is_empty());
+    }
+
+    #[test]
+    fn refind_params_reference_boot_root() {
+        assert_eq!(
+            refind_initrd_params(&["intel-ucode.img"]),
+            vec!["initrd=\\intel-ucode.img".to_string()]
+        );
+    }
+
+    #[test]
+    fn firmware_subset_replaces_full_blob() {
+        let mut config = DeploymentConfig::sample();
+        assert_eq!(firmware_packages(&config), vec!["linux-firmware"]);
+        config.system.firmware_packages = vec!["linux-firmware-intel".to_string()];
+        assert_eq!(firmware_packages(&config), vec!["linux-firmware-intel"]);
+
+        assert!(is_firmware_package("linux-firmware-amdgpu"));
+        assert!(!is_firmware_package("linux-firmware-"));
+        assert!(!is_firmware_package("intel-ucode"));
+    }
+}
@@ -1,39 +1,27 @@
 //! mkinitcpio configuration and hook construction
 
-use crate::config::{DeploymentConfig, Filesystem};
+use crate::config::{DeploymentConfig, Filesystem, KernelChoice};
+use crate::configure::keyfiles::keyfile_path;
 use crate::utils::command::CommandRunner;
-use crate::utils::error::Result;
+use crate::utils::error::{DeploytixError, Result};
 use std::fs;
 use tracing::info;
 
 /// Construct MODULES array based on configuration
 pub fn construct_modules(config: &DeploymentConfig) -> Vec<String> {
-    let mut modules = Vec::new();
-
-    // Data filesystem modules
-    match config.disk.filesystem {
-        Filesystem::Btrfs => modules.push("btrfs".to_string()),
-        Filesystem::Ext4 => modules.push("ext4".to_string()),
-        Filesystem::Xfs => modules.push("xfs".to_string()),
-        Filesystem::Zfs => {} // ZFS is loaded via the zfs hook, not as a static module
-        Filesystem::F2fs => modules.push("f2fs".to_string()),
-    }
-
-    // Boot filesystem modules (if different from data filesystem)
-    match config.disk.boot_filesystem {
-        Filesystem::Btrfs if config.disk.filesystem != Filesystem::Btrfs => {
-            modules.push("btrfs".to_string());
-        }
-        Filesystem::Ext4 if config.disk.filesystem != Filesystem::Ext4 => {
-            modules.push("ext4".to_string());
+    // VFIO passthrough modules go first so vfio-pci claims its devices
+    // before any GPU driver is loaded.
+    let mut modules = crate::configure::modules::early_load_modules(config);
+
+    // Filesystem modules for the data, boot and per-partition filesystems
+    for filesystem in config.disk.filesystems_in_use() {
+        match filesystem {
+            Filesystem::Btrfs => modules.push("btrfs".to_string()),
+            Filesystem::Ext4 => modules.push("ext4".to_string()),
+            Filesystem::Xfs => modules.push("xfs".to_string()),
+            Filesystem::Zfs => {} // ZFS is loaded via the zfs hook, not as a static module
+            Filesystem::F2fs => modules.push("f2fs".to_string()),
         }
-        Filesystem::Xfs if config.disk.filesystem != Filesystem::Xfs => {
-            modules.push("xfs".to_string());
-        }
-        Filesystem::F2fs if config.disk.filesystem != Filesystem::F2fs => {
-            modules.push("f2fs".to_string());
-        }
-        _ => {} // same as data filesystem or ZFS (hook-based)
     }
 
     // Always include vfat and dependencies for EFI partition mounting in initramfs
@@ -45,18 +33,34 @@ pub fn construct_modules(config: &DeploymentConfig) -> Vec<String> {
     ]);
 
     // Encryption modules
-    if config.disk.encryption {
+    if config.disk.uses_luks() {
         modules.extend(["dm_crypt".to_string(), "dm_mod".to_string()]);
 
         // dm-integrity module for per-sector integrity protection
         if config.disk.integrity {
             modules.push("dm_integrity".to_string());
         }
+
+        // USB HID driver for the FIDO2 security key
+        if config.disk.fido2_unlock {
+            modules.push("usbhid".to_string());
+        }
     }
 
     // LVM thin provisioning modules (feature-driven)
     if config.disk.use_lvm_thin {
         modules.extend(["dm_thin_pool".to_string()]);
+        // The mirrored pool is a RAID1 LV
+        if config.disk.mirror_device.is_some() {
+            modules.extend(["dm_raid".to_string(), "raid1".to_string()]);
+        }
+    }
+
+    // Modules the user asked for, e.g. for an unusual storage controller
+    for m in &config.system.initramfs_modules {
+        if !modules.contains(m) {
+            modules.push(m.clone());
+        }
     }
 
     modules
@@ -71,17 +75,24 @@ pub fn construct_modules(config: &DeploymentConfig) -> Vec<String> {
 /// - `btrfs` → `btrfs` hook
 pub fn construct_hooks(config: &DeploymentConfig) -> Vec<String> {
     let uses_lvm_thin = config.disk.use_lvm_thin;
-    let uses_encryption = config.disk.encryption;
+    // ZFS native encryption is unlocked by the zfs hook itself
+    let uses_encryption = config.disk.uses_luks();
     let uses_multi_luks = uses_encryption && !uses_lvm_thin;
 
     let mut hooks = vec![
         "base".to_string(),
         "udev".to_string(),
         "autodetect".to_string(),
-        "modconf".to_string(),
-        "block".to_string(),
     ];
 
+    // Bundle CPU microcode into the initramfs (must follow autodetect so
+    // only the running CPU's microcode is included)
+    if crate::configure::microcode::uses_microcode_hook(config) {
+        hooks.push("microcode".to_string());
+    }
+
+    hooks.extend(["modconf".to_string(), "block".to_string()]);
+
     // Keyboard/console hooks
     hooks.extend([
         "keyboard".to_string(),
@@ -169,9 +180,26 @@ pub fn construct_hooks(config: &DeploymentConfig) -> Vec<String> {
     hooks
 }
 
+/// Whether `name` can go into the BINARIES array: a program name or an
+/// absolute path, with no characters the shell-sourced config would
+/// interpret.
+pub fn is_valid_binary(name: &str) -> bool {
+    !name.is_empty()
+        && (!name.contains('/') || name.starts_with('/'))
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._+-/".contains(c))
+}
+
 /// Construct BINARIES array
-pub fn construct_binaries(_config: &DeploymentConfig) -> Vec<String> {
-    vec!["lsblk".to_string()]
+pub fn construct_binaries(config: &DeploymentConfig) -> Vec<String> {
+    let mut binaries = vec!["lsblk".to_string()];
+    for b in &config.system.initramfs_binaries {
+        if !binaries.contains(b) {
+            binaries.push(b.clone());
+        }
+    }
+    binaries
 }
 
 /// Construct FILES array.
@@ -180,29 +208,34 @@ pub fn construct_binaries(_config: &DeploymentConfig) -> Vec<String> {
 /// regardless of the chosen layout.
 pub fn construct_files(config: &DeploymentConfig) -> Vec<String> {
     let uses_lvm_thin = config.disk.use_lvm_thin;
-    let uses_multi_luks = config.disk.encryption && !uses_lvm_thin;
+    let uses_multi_luks = config.disk.uses_luks() && !uses_lvm_thin;
 
+    let keyfile = |volume: &str| keyfile_path(&config.disk.keyfile_dir, volume);
     let mut files = Vec::new();
 
     // Multi-LUKS: include crypttab and per-volume keyfiles
     if uses_multi_luks {
         files.push("/etc/crypttab".to_string());
 
-        files.push("/etc/cryptsetup-keys.d/cryptroot.key".to_string());
-        files.push("/etc/cryptsetup-keys.d/cryptusr.key".to_string());
-        files.push("/etc/cryptsetup-keys.d/cryptvar.key".to_string());
-        files.push("/etc/cryptsetup-keys.d/crypthome.key".to_string());
+        // FIDO2 replaces the data keyfiles; leaving them in the initramfs
+        // would make the security key pointless
+        if !config.disk.fido2_unlock {
+            files.push(keyfile("Root"));
+            files.push(keyfile("Usr"));
+            files.push(keyfile("Var"));
+            files.push(keyfile("Home"));
+        }
 
         if config.disk.boot_encryption {
-            files.push("/etc/cryptsetup-keys.d/cryptboot.key".to_string());
+            files.push(keyfile("Boot"));
         }
     }
 
     // LVM thin with encryption + boot encryption: include LVM and boot keyfiles
     if uses_lvm_thin && config.disk.encryption && config.disk.boot_encryption {
-        files.push("/etc/cryptsetup-keys.d/cryptlvm.key".to_string());
+        files.push(keyfile("Lvm"));
         files.push("/etc/crypttab".to_string());
-        files.push("/etc/cryptsetup-keys.d/cryptboot.key".to_string());
+        files.push(keyfile("Boot"));
     }
 
     files
@@ -229,12 +262,40 @@ pub fn construct_files_with_keyfiles(
     files
 }
 
+/// Compression algorithms mkinitcpio supports for the initramfs
+pub const INITRAMFS_COMPRESSIONS: &[&str] = &["gzip", "lz4", "xz", "zstd", "lzop", "bzip2"];
+
+/// Initramfs compression used when none is configured
+const DEFAULT_COMPRESSION: &str = "zstd";
+
+/// The configured initramfs compression algorithm
+pub fn compression(config: &DeploymentConfig) -> &str {
+    config
+        .system
+        .initramfs_compression
+        .as_deref()
+        .unwrap_or(DEFAULT_COMPRESSION)
+}
+
+/// Package providing the compressor for `algo`, when it is not already
+/// part of the base system
+pub fn compression_package(algo: &str) -> Option<&'static str> {
+    match algo {
+        "lz4" => Some("lz4"),
+        "lzop" => Some("lzop"),
+        _ => None,
+    }
+}
+
 /// Generate mkinitcpio.conf content
 pub fn generate_mkinitcpio_conf(config: &DeploymentConfig) -> String {
     let modules = construct_modules(config);
     let binaries = construct_binaries(config);
     let files = construct_files(config);
     let hooks = construct_hooks(config);
+    let compression = compression(config);
+    // Multi-threaded compression; the other compressors take no such option
+    let compression_options = if compression == "zstd" { "-T0" } else { "" };
 
     format!(
         r#"# mkinitcpio.conf - Generated by Deploytix
@@ -246,13 +307,15 @@ FILES=({})
 HOOKS=({})
 
 # Compression
-COMPRESSION="zstd"
-COMPRESSION_OPTIONS=(-T0)
+COMPRESSION="{}"
+COMPRESSION_OPTIONS=({})
 "#,
         modules.join(" "),
         binaries.join(" "),
         files.join(" "),
-        hooks.join(" ")
+        hooks.join(" "),
+        compression,
+        compression_options
     )
 }
 
@@ -292,6 +355,69 @@ pub fn configure_mkinitcpio(
     Ok(())
 }
 
+/// Check a mkinitcpio preset builds `kernel`'s default initramfs from its
+/// kernel image.  Returns the problem found, if any.
+pub fn check_kernel_preset(preset: &str, kernel: &KernelChoice) -> Option<String> {
+    let value = |key: &str| {
+        preset
+            .lines()
+            .map(str::trim)
+            .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+            .map(|v| v.trim_matches(|c| c == '"' || c == '\''))
+    };
+
+    let checks = [
+        ("ALL_kver", kernel.vmlinuz()),
+        ("default_image", kernel.initramfs()),
+    ];
+    for (key, expected) in checks {
+        match value(key) {
+            Some(v) if v == expected => {}
+            Some(v) => return Some(format!("{} is {}, expected {}", key, v, expected)),
+            None => return Some(format!("{} is not set", key)),
+        }
+    }
+    None
+}
+
+/// Verify the chosen kernel's preset exists and targets its images, so
+/// `mkinitcpio -P` regenerates the initramfs the bootloader loads.
+pub fn verify_kernel_preset(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let kernel = &config.system.kernel;
+    let preset_path = kernel.preset_path();
+
+    if cmd.is_dry_run() {
+        println!(
+            "  [dry-run] Would verify {} builds {}",
+            preset_path,
+            kernel.initramfs()
+        );
+        return Ok(());
+    }
+
+    let preset = fs::read_to_string(format!("{}{}", install_root, preset_path)).map_err(|e| {
+        DeploytixError::BootCheckFailed(format!(
+            "{} missing ({}); is {} installed?",
+            preset_path,
+            e,
+            kernel.package()
+        ))
+    })?;
+    if let Some(problem) = check_kernel_preset(&preset, kernel) {
+        return Err(DeploytixError::BootCheckFailed(format!(
+            "{}: {}",
+            preset_path, problem
+        )));
+    }
+
+    info!("mkinitcpio preset {} matches {}", preset_path, kernel);
+    Ok(())
+}
+
 /// Regenerate initramfs
 #[allow(dead_code)]
 pub fn regenerate_initramfs(cmd: &CommandRunner, install_root: &str) -> Result<()> {
@@ -316,6 +442,54 @@ mod tests {
         cfg
     }
 
+    #[test]
+    fn filesystem_overrides_add_their_modules() {
+        let mut cfg = config_encrypted(false);
+        cfg.disk.filesystem = Filesystem::Ext4;
+        cfg.disk.boot_filesystem = Filesystem::Ext4;
+        assert!(!construct_modules(&cfg).contains(&"xfs".to_string()));
+
+        let home = cfg
+            .disk
+            .partitions
+            .iter_mut()
+            .find(|p| p.mount_point == "/home");
+        home.unwrap().filesystem = Some(Filesystem::Xfs);
+        let modules = construct_modules(&cfg);
+        assert!(modules.contains(&"xfs".to_string()));
+        assert_eq!(modules.iter().filter(|m| *m == "ext4").count(), 1);
+    }
+
+    #[test]
+    fn configured_modules_and_binaries_are_appended_once() {
+        let mut cfg = config_encrypted(true);
+        cfg.system.initramfs_modules = vec!["mpt3sas".to_string(), "dm_crypt".to_string()];
+        cfg.system.initramfs_binaries = vec!["lsblk".to_string(), "/usr/bin/nvme".to_string()];
+
+        let modules = construct_modules(&cfg);
+        assert_eq!(modules.last().map(String::as_str), Some("mpt3sas"));
+        assert_eq!(modules.iter().filter(|m| *m == "dm_crypt").count(), 1);
+        assert_eq!(construct_binaries(&cfg), vec!["lsblk", "/usr/bin/nvme"]);
+        assert!(generate_mkinitcpio_conf(&cfg).contains("BINARIES=(lsblk /usr/bin/nvme)"));
+
+        assert!(is_valid_binary("mdadm"));
+        assert!(!is_valid_binary("bin/mdadm"));
+        assert!(!is_valid_binary("a b"));
+    }
+
+    #[test]
+    fn compression_defaults_to_zstd() {
+        let mut cfg = config_encrypted(false);
+        let conf = generate_mkinitcpio_conf(&cfg);
+        assert!(conf.contains("COMPRESSION=\"zstd\"\nCOMPRESSION_OPTIONS=(-T0)\n"));
+
+        cfg.system.initramfs_compression = Some("lz4".to_string());
+        let conf = generate_mkinitcpio_conf(&cfg);
+        assert!(conf.contains("COMPRESSION=\"lz4\"\nCOMPRESSION_OPTIONS=()\n"));
+        assert_eq!(compression_package(compression(&cfg)), Some("lz4"));
+        assert_eq!(compression_package("xz"), None);
+    }
+
     #[test]
     fn encrypted_uses_custom_hooks() {
         let cfg = config_encrypted(true);
@@ -327,6 +501,30 @@ mod tests {
         assert!(!hooks.contains(&"filesystems".to_string()));
     }
 
+    #[test]
+    fn zfs_native_encryption_skips_luks_hooks() {
+        let mut cfg = config_encrypted(true);
+        cfg.disk.filesystem = crate::config::Filesystem::Zfs;
+        let hooks = construct_hooks(&cfg);
+        assert!(hooks.contains(&"zfs".to_string()));
+        assert!(!hooks.contains(&"crypttab-unlock".to_string()));
+        assert!(!hooks.contains(&"lvm2".to_string()));
+        assert!(construct_files(&cfg).is_empty());
+        assert!(!construct_modules(&cfg).contains(&"dm_crypt".to_string()));
+    }
+
+    #[test]
+    fn mirrored_lvm_thin_loads_raid_modules() {
+        let mut cfg = config_encrypted(false);
+        cfg.disk.use_lvm_thin = true;
+        assert!(!construct_modules(&cfg).contains(&"dm_raid".to_string()));
+
+        cfg.disk.mirror_device = Some("/dev/sdb".to_string());
+        let modules = construct_modules(&cfg);
+        assert!(modules.contains(&"dm_raid".to_string()));
+        assert!(modules.contains(&"raid1".to_string()));
+    }
+
     #[test]
     fn unencrypted_no_usr_has_filesystems_hook() {
         let mut cfg = config_encrypted(false);
@@ -383,6 +581,18 @@ mod tests {
         assert!(files.contains(&"/etc/cryptsetup-keys.d/cryptboot.key".to_string()));
     }
 
+    #[test]
+    fn fido2_drops_data_keyfiles_from_initramfs() {
+        let mut cfg = config_encrypted(true);
+        cfg.disk.fido2_unlock = true;
+        cfg.disk.boot_encryption = true;
+        let files = construct_files(&cfg);
+        assert!(files.contains(&"/etc/crypttab".to_string()));
+        assert!(!files.contains(&"/etc/cryptsetup-keys.d/cryptroot.key".to_string()));
+        assert!(files.contains(&"/etc/cryptsetup-keys.d/cryptboot.key".to_string()));
+        assert!(construct_modules(&cfg).contains(&"usbhid".to_string()));
+    }
+
     #[test]
     fn unencrypted_no_files() {
         let cfg = config_encrypted(false);
@@ -511,6 +721,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn vfio_modules_lead_the_modules_array() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.load_modules = vec!["it87".to_string(), "vfio-pci".to_string()];
+        let modules = construct_modules(&cfg);
+        assert_eq!(modules.first().map(String::as_str), Some("vfio-pci"));
+        assert!(!modules.contains(&"it87".to_string()));
+    }
+
+    #[test]
+    fn kernel_preset_must_match_choice() {
+        let preset = "ALL_kver=\"/boot/vmlinuz-linux-lts\"\n\
+                      PRESETS=('default' 'fallback')\n\
+                      default_image=\"/boot/initramfs-linux-lts.img\"\n\
+                      fallback_image=\"/boot/initramfs-linux-lts-fallback.img\"\n";
+        assert_eq!(check_kernel_preset(preset, &KernelChoice::Lts), None);
+        assert!(check_kernel_preset(preset, &KernelChoice::Zen)
+            .unwrap()
+            .contains("ALL_kver"));
+        assert!(check_kernel_preset("PRESETS=('default')\n", &KernelChoice::Lts).is_some());
+    }
+
+    #[test]
+    fn bundled_microcode_adds_hook_after_autodetect() {
+        let mut config = config_encrypted(false);
+        assert!(!construct_hooks(&config).contains(&"microcode".to_string()));
+
+        config.system.microcode = crate::config::MicrocodeLoading::Bundled;
+        let hooks = construct_hooks(&config);
+        let autodetect = hooks.iter().position(|h| h == "autodetect").unwrap();
+        assert_eq!(hooks[autodetect + 1], "microcode");
+    }
+
     #[test]
     fn usr_hook_added_when_usr_partition_present() {
         let mut cfg = config_encrypted(false);
@@ -519,6 +762,8 @@ mod tests {
             label: None,
             size_mib: 20480,
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         });
         let hooks = construct_hooks(&cfg);
         assert!(
@@ -3,16 +3,24 @@
 pub mod bootloader;
 pub mod display_manager;
 pub mod encryption;
+pub mod fido2;
+pub mod firewall;
 pub mod gamescope_update;
 pub mod greetd;
 pub mod hooks;
 pub mod keyfiles;
 pub mod locale;
+pub mod microcode;
 pub mod mkinitcpio;
+pub mod modules;
 pub mod network;
 pub mod packages;
 pub mod secureboot;
 pub mod services;
 pub mod session_switching;
+pub mod snapper;
+pub mod ssh;
 pub mod swap;
+pub mod time_sync;
 pub mod users;
+pub mod zram_mounts;
@@ -0,0 +1,358 @@
+//! Kernel module blacklist and boot-time module loading configuration
+
+use crate::config::{DeploymentConfig, GpuDriverVendor};
+use crate::utils::command::CommandRunner;
+use crate::utils::error::Result;
+use std::fs;
+use tracing::{info, warn};
+
+/// Path (relative to the install root) of the generated blacklist file.
+const BLACKLIST_CONF: &str = "/etc/modprobe.d/deploytix-blacklist.conf";
+
+/// Path (relative to the install root) of the generated modules-load.d file.
+const MODULES_LOAD_CONF: &str = "/etc/modules-load.d/deploytix.conf";
+
+/// GPU drivers that would claim a passthrough device if they bind before
+/// `vfio-pci` does.
+const GPU_DRIVER_MODULES: &[&str] = &["nvidia", "nouveau", "amdgpu", "radeon", "i915", "xe"];
+
+/// Modules that can be pulled in from the initramfs (KMS / framebuffer
+/// drivers) before `/etc/modprobe.d` on the root filesystem is readable.
+/// Blacklisting these only in modprobe.d is not enough — they must also be
+/// blocked with `modprobe.blacklist=` on the kernel command line.
+const EARLY_BOOT_MODULES: &[&str] = &[
+    "nouveau", "nvidiafb", "radeon", "amdgpu", "i915", "xe", "simplefb",
+];
+
+/// Check that `name` is a plausible kernel module name.
+///
+/// Module names consist of ASCII letters, digits, `_` and `-` (modprobe
+/// treats `-` and `_` as equivalent).
+pub fn is_valid_module_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Normalise a module name the way modprobe does (`-` and `_` are equivalent).
+pub fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Whether `name` is one of the VFIO passthrough modules (`vfio`, `vfio-pci`,
+/// `vfio_iommu_type1`, ...).
+pub fn is_vfio_module(name: &str) -> bool {
+    let n = normalize(name);
+    n == "vfio" || n.starts_with("vfio_")
+}
+
+/// Whether `name` is a GPU driver that competes with `vfio-pci`.
+fn is_gpu_driver_module(name: &str) -> bool {
+    GPU_DRIVER_MODULES.contains(&normalize(name).as_str())
+}
+
+/// Modules to load at boot, deduplicated, with VFIO modules moved ahead of
+/// everything else so `vfio-pci` binds passthrough devices before any GPU
+/// driver can.
+pub fn ordered_load_modules(config: &DeploymentConfig) -> Vec<String> {
+    let mut vfio: Vec<String> = Vec::new();
+    let mut rest: Vec<String> = Vec::new();
+    for m in &config.system.load_modules {
+        let seen = vfio
+            .iter()
+            .chain(rest.iter())
+            .any(|x| normalize(x) == normalize(m));
+        if seen {
+            continue;
+        }
+        if is_vfio_module(m) {
+            vfio.push(m.clone());
+        } else {
+            rest.push(m.clone());
+        }
+    }
+    vfio.extend(rest);
+    vfio
+}
+
+/// VFIO modules that must be loaded from the initramfs (mkinitcpio MODULES).
+pub fn early_load_modules(config: &DeploymentConfig) -> Vec<String> {
+    ordered_load_modules(config)
+        .into_iter()
+        .filter(|m| is_vfio_module(m))
+        .collect()
+}
+
+/// Ordering problems in `load_modules` worth telling the user about.
+///
+/// VFIO must load before the GPU driver it is meant to take a device away
+/// from.  Deploytix fixes the order itself (VFIO goes first in both the
+/// initramfs and modules-load.d), but a GPU driver listed ahead of VFIO
+/// usually means the user expected a different order.
+pub fn load_order_warnings(config: &DeploymentConfig) -> Vec<String> {
+    let modules = &config.system.load_modules;
+    let Some(first_vfio) = modules.iter().position(|m| is_vfio_module(m)) else {
+        return Vec::new();
+    };
+
+    let mut warnings: Vec<String> = modules[..first_vfio]
+        .iter()
+        .filter(|m| is_gpu_driver_module(m))
+        .map(|m| {
+            format!(
+                "GPU driver '{}' is listed before VFIO in load_modules; \
+                 VFIO modules will be loaded first so vfio-pci can claim \
+                 passthrough devices",
+                m
+            )
+        })
+        .collect();
+
+    if !config.packages.gpu_drivers.is_empty() {
+        warnings.push(
+            "VFIO modules are loaded from the initramfs ahead of the GPU driver; \
+             bind passthrough devices with vfio-pci.ids= on the kernel command line"
+                .to_string(),
+        );
+    }
+
+    warnings
+}
+
+/// Effective blacklist: the configured modules plus `nouveau` when the
+/// proprietary NVIDIA driver is selected and the user has not configured
+/// nouveau themselves.  Deduplicated, order preserved.
+pub fn effective_blacklist(config: &DeploymentConfig) -> Vec<String> {
+    let mut modules: Vec<String> = Vec::new();
+    for m in &config.system.blacklist_modules {
+        if !modules.iter().any(|x| normalize(x) == normalize(m)) {
+            modules.push(m.clone());
+        }
+    }
+
+    if config
+        .packages
+        .gpu_drivers
+        .contains(&GpuDriverVendor::Nvidia)
+        && !modules
+            .iter()
+            .chain(&config.system.load_modules)
+            .any(|m| normalize(m) == "nouveau")
+    {
+        modules.push("nouveau".to_string());
+    }
+
+    modules
+}
+
+/// Blacklisted modules that must also be blocked on the kernel command line.
+pub fn early_boot_blacklist(config: &DeploymentConfig) -> Vec<String> {
+    effective_blacklist(config)
+        .into_iter()
+        .filter(|m| EARLY_BOOT_MODULES.contains(&normalize(m).as_str()))
+        .collect()
+}
+
+/// Kernel command-line parameter for early-boot blacklisting
+/// (`modprobe.blacklist=a,b`), or `None` when nothing needs it.
+pub fn blacklist_cmdline_param(config: &DeploymentConfig) -> Option<String> {
+    let early = early_boot_blacklist(config);
+    if early.is_empty() {
+        None
+    } else {
+        Some(format!("modprobe.blacklist={}", early.join(",")))
+    }
+}
+
+/// Render the modprobe.d blacklist file content.
+pub fn generate_blacklist_conf(modules: &[String]) -> String {
+    let mut content = String::from("# Kernel module blacklist - Generated by Deploytix\n");
+    for m in modules {
+        content.push_str(&format!("blacklist {}\n", m));
+    }
+    content
+}
+
+/// Write `/etc/modprobe.d/deploytix-blacklist.conf` on the target system.
+/// No-op when the effective blacklist is empty.
+pub fn configure_blacklist(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let modules = effective_blacklist(config);
+    if modules.is_empty() {
+        return Ok(());
+    }
+
+    info!("Blacklisting kernel modules: [{}]", modules.join(", "));
+    let content = generate_blacklist_conf(&modules);
+    let conf_path = format!("{}{}", install_root, BLACKLIST_CONF);
+
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would write {}:", BLACKLIST_CONF);
+        for line in content.lines() {
+            println!("    {}", line);
+        }
+        return Ok(());
+    }
+
+    fs::create_dir_all(format!("{}/etc/modprobe.d", install_root))?;
+    fs::write(&conf_path, content)?;
+
+    info!("Module blacklist written to {}", conf_path);
+    Ok(())
+}
+
+/// Render the modules-load.d file content.
+pub fn generate_modules_load_conf(modules: &[String]) -> String {
+    let mut content = String::from("# Modules loaded at boot - Generated by Deploytix\n");
+    for m in modules {
+        content.push_str(m);
+        content.push('\n');
+    }
+    content
+}
+
+/// Write `/etc/modules-load.d/deploytix.conf` on the target system.
+/// No-op when `load_modules` is empty.
+pub fn configure_modules_load(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let modules = ordered_load_modules(config);
+    if modules.is_empty() {
+        return Ok(());
+    }
+
+    for w in load_order_warnings(config) {
+        warn!("{}", w);
+    }
+
+    info!("Loading kernel modules at boot: [{}]", modules.join(", "));
+    let content = generate_modules_load_conf(&modules);
+    let conf_path = format!("{}{}", install_root, MODULES_LOAD_CONF);
+
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would write {}:", MODULES_LOAD_CONF);
+        for line in content.lines() {
+            println!("    {}", line);
+        }
+        let early = early_load_modules(config);
+        if !early.is_empty() {
+            println!(
+                "  [dry-run] Would add to mkinitcpio MODULES (before GPU drivers): {}",
+                early.join(" ")
+            );
+        }
+        return Ok(());
+    }
+
+    fs::create_dir_all(format!("{}/etc/modules-load.d", install_root))?;
+    fs::write(&conf_path, content)?;
+
+    info!("Boot module list written to {}", conf_path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_name_charset() {
+        assert!(is_valid_module_name("pcspkr"));
+        assert!(is_valid_module_name("snd_hda_intel"));
+        assert!(is_valid_module_name("vfio-pci"));
+        assert!(!is_valid_module_name(""));
+        assert!(!is_valid_module_name("foo bar"));
+        assert!(!is_valid_module_name("foo,bar"));
+        assert!(!is_valid_module_name("../evil"));
+    }
+
+    #[test]
+    fn nvidia_driver_adds_nouveau() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.packages.gpu_drivers = vec![GpuDriverVendor::Nvidia];
+        assert_eq!(effective_blacklist(&cfg), vec!["nouveau".to_string()]);
+
+        cfg.system.blacklist_modules = vec!["nouveau".to_string(), "pcspkr".to_string()];
+        assert_eq!(
+            effective_blacklist(&cfg),
+            vec!["nouveau".to_string(), "pcspkr".to_string()],
+            "nouveau must not be duplicated"
+        );
+
+        cfg.system.blacklist_modules = vec!["pcspkr".to_string()];
+        cfg.system.load_modules = vec!["nouveau".to_string()];
+        assert_eq!(
+            effective_blacklist(&cfg),
+            vec!["pcspkr".to_string()],
+            "an explicitly loaded nouveau must not be blacklisted"
+        );
+    }
+
+    #[test]
+    fn blacklist_dedup_treats_dash_and_underscore_alike() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.blacklist_modules = vec!["nvidia-drm".to_string(), "nvidia_drm".to_string()];
+        assert_eq!(effective_blacklist(&cfg), vec!["nvidia-drm".to_string()]);
+    }
+
+    #[test]
+    fn only_early_boot_modules_reach_cmdline() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.blacklist_modules = vec!["pcspkr".to_string()];
+        assert_eq!(blacklist_cmdline_param(&cfg), None);
+
+        cfg.packages.gpu_drivers = vec![GpuDriverVendor::Nvidia];
+        assert_eq!(
+            blacklist_cmdline_param(&cfg).as_deref(),
+            Some("modprobe.blacklist=nouveau")
+        );
+    }
+
+    #[test]
+    fn vfio_modules_are_ordered_first() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.load_modules = vec![
+            "it87".to_string(),
+            "amdgpu".to_string(),
+            "vfio-pci".to_string(),
+            "vfio_iommu_type1".to_string(),
+            "vfio_pci".to_string(),
+        ];
+        assert_eq!(
+            ordered_load_modules(&cfg),
+            vec!["vfio-pci", "vfio_iommu_type1", "it87", "amdgpu"]
+        );
+        assert_eq!(
+            early_load_modules(&cfg),
+            vec!["vfio-pci", "vfio_iommu_type1"]
+        );
+        assert!(load_order_warnings(&cfg)
+            .iter()
+            .any(|w| w.contains("'amdgpu'")));
+    }
+
+    #[test]
+    fn no_vfio_means_no_early_modules() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.system.load_modules = vec!["it87".to_string()];
+        assert!(early_load_modules(&cfg).is_empty());
+        assert!(load_order_warnings(&cfg).is_empty());
+        assert_eq!(
+            generate_modules_load_conf(&ordered_load_modules(&cfg)),
+            "# Modules loaded at boot - Generated by Deploytix\nit87\n"
+        );
+    }
+
+    #[test]
+    fn blacklist_conf_has_one_line_per_module() {
+        let conf = generate_blacklist_conf(&["pcspkr".to_string(), "nouveau".to_string()]);
+        assert!(conf.contains("blacklist pcspkr\n"));
+        assert!(conf.contains("blacklist nouveau\n"));
+    }
+}
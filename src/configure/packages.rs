@@ -14,7 +14,7 @@
 //! - evdevhook2 (Cemuhook UDP motion server) via AUR + udev rule + service file
 
 use crate::config::{DeploymentConfig, GpuDriverVendor};
-use crate::utils::command::CommandRunner;
+use crate::utils::command::{CommandRunner, NETWORK_RETRY_BACKOFF};
 use crate::utils::error::{DeploytixError, Result};
 use crate::utils::interactive::PacmanInvocation;
 use std::fs;
@@ -73,7 +73,13 @@ pub(crate) fn yay_install_chroot_reviewed(
         extras,
         inv.packages.join(" ")
     );
-    cmd.run_in_chroot(install_root, &cmd_str).map(|_| ())
+    cmd.run_in_chroot_with_retry(
+        install_root,
+        &cmd_str,
+        cmd.network_retries(),
+        NETWORK_RETRY_BACKOFF,
+    )
+    .map(|_| ())
 }
 
 // ======================== Signature-error recovery ========================
@@ -148,6 +154,9 @@ fn inject_config_flag(pacman_cmd: &str) -> String {
 /// genuinely serves a mis-signed package), falls back to a final retry
 /// with `SigLevel = Optional TrustAll`.
 ///
+/// Failed downloads (network errors only) are first retried
+/// `cmd.network_retries()` times.
+///
 /// Recovery sequence on signature failure:
 ///  1. Clear the pacman package cache so the corrupt / invalid download
 ///     is not reused on the retry.
@@ -167,7 +176,8 @@ pub(crate) fn pacman_install_chroot(
     install_root: &str,
     pacman_cmd: &str,
 ) -> Result<()> {
-    match cmd.run_in_chroot(install_root, pacman_cmd) {
+    let retries = cmd.network_retries();
+    match cmd.run_in_chroot_with_retry(install_root, pacman_cmd, retries, NETWORK_RETRY_BACKOFF) {
         Ok(_) => return Ok(()),
         Err(DeploytixError::CommandFailed { ref stderr, .. }) if is_signature_error(stderr) => {
             warn!(
@@ -195,7 +205,7 @@ pub(crate) fn pacman_install_chroot(
     let _ = cmd.run_in_chroot(install_root, "pacman-key --populate archlinux");
 
     // 5. Retry with refreshed keyring.
-    match cmd.run_in_chroot(install_root, pacman_cmd) {
+    match cmd.run_in_chroot_with_retry(install_root, pacman_cmd, retries, NETWORK_RETRY_BACKOFF) {
         Ok(_) => return Ok(()),
         Err(DeploytixError::CommandFailed { ref stderr, .. }) if is_signature_error(stderr) => {
             warn!(
@@ -1237,7 +1247,7 @@ fn write_hhd_service(config: &DeploymentConfig, install_root: &str, username: &s
 /// assumes that layout).  Because the AUR-shipped `decky-loader-helper`
 /// hardcodes its destination to `~/.local/var/opt/decky-loader`, we
 /// bypass it and copy `PluginLoader` into place ourselves.
-/// ```
+/// ```text
 /// /usr/lib/decky-loader/PluginLoader               (AUR package file)
 /// /home/{user}/homebrew/
 ///   services/
@@ -56,8 +56,8 @@ fn setup_sbctl(cmd: &CommandRunner, install_root: &str) -> Result<()> {
     info!("Setting up SecureBoot with sbctl");
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] sbctl create-keys");
-        println!("  [dry-run] sbctl enroll-keys --microsoft");
+        cmd.log_dry_run("sbctl create-keys");
+        cmd.log_dry_run("sbctl enroll-keys --microsoft");
         return Ok(());
     }
 
@@ -293,10 +293,10 @@ pub fn sign_boot_files(
     }
 
     // Sign the standalone GRUB EFI binary
-    let bootloader_path = "/boot/efi/EFI/BOOT/BOOTX64.EFI";
+    let bootloader_path = format!("{}/EFI/BOOT/BOOTX64.EFI", config.disk.esp_mountpoint.path());
     let full_bootloader_path = format!("{}{}", install_root, bootloader_path);
     if std::path::Path::new(&full_bootloader_path).exists() {
-        sign_efi_binary(cmd, config, bootloader_path, install_root)?;
+        sign_efi_binary(cmd, config, &bootloader_path, install_root)?;
     }
 
     // Sign all kernels found in /boot
@@ -339,26 +339,30 @@ fn create_signing_hook(
     let hooks_dir = format!("{}/etc/pacman.d/hooks", install_root);
     fs::create_dir_all(&hooks_dir)?;
 
+    let esp = config.disk.esp_mountpoint.path();
     let hook_content = match config.system.secureboot_method {
         SecureBootMethod::Sbctl => {
-            r#"[Trigger]
+            format!(
+                r#"[Trigger]
 Operation = Install
 Operation = Upgrade
 Type = Path
 Target = usr/lib/modules/*/vmlinuz
 Target = boot/vmlinuz-*
-Target = boot/efi/EFI/BOOT/BOOTX64.EFI
+Target = {esp}/EFI/BOOT/BOOTX64.EFI
 
 [Action]
 Description = Signing EFI binaries for SecureBoot...
 When = PostTransaction
 Exec = /usr/bin/sbctl sign-all
 Depends = sbctl
-"#
+"#,
+                esp = esp.trim_start_matches('/')
+            )
         }
         SecureBootMethod::ManualKeys | SecureBootMethod::Shim => {
             // For manual signing, create a script
-            create_manual_signing_script(install_root)?;
+            create_manual_signing_script(esp, install_root)?;
             r#"[Trigger]
 Operation = Install
 Operation = Upgrade
@@ -371,6 +375,7 @@ Description = Signing kernel for SecureBoot...
 When = PostTransaction
 Exec = /usr/local/bin/sign-kernel
 "#
+            .to_string()
         }
     };
 
@@ -382,11 +387,12 @@ Exec = /usr/local/bin/sign-kernel
 }
 
 /// Create manual signing script for non-sbctl methods
-fn create_manual_signing_script(install_root: &str) -> Result<()> {
+fn create_manual_signing_script(esp: &str, install_root: &str) -> Result<()> {
     let script_dir = format!("{}/usr/local/bin", install_root);
     fs::create_dir_all(&script_dir)?;
 
-    let script = r#"#!/bin/bash
+    let script = format!(
+        r#"#!/bin/bash
 # Sign kernel for SecureBoot
 
 KEY="/etc/secureboot/keys/db.key"
@@ -412,15 +418,17 @@ for kernel in /boot/vmlinuz-*; do
 done
 
 # Sign GRUB if present
-if [ -f /boot/efi/EFI/BOOT/BOOTX64.EFI ]; then
+if [ -f {loader} ]; then
     echo "Signing GRUB..."
     sbsign --key "$KEY" --cert "$CERT" \
-        --output /boot/efi/EFI/BOOT/BOOTX64.EFI \
-        /boot/efi/EFI/BOOT/BOOTX64.EFI
+        --output {loader} \
+        {loader}
 fi
 
 echo "SecureBoot signing complete"
-"#;
+"#,
+        loader = format!("{}/EFI/BOOT/BOOTX64.EFI", esp)
+    );
 
     let script_path = format!("{}/sign-kernel", script_dir);
     fs::write(&script_path, script)?;
@@ -1,6 +1,8 @@
 //! Service management for different init systems
 
-use crate::config::{DeploymentConfig, DesktopEnvironment, InitSystem, NetworkBackend};
+use crate::config::{
+    DeploymentConfig, DesktopEnvironment, DisplayManager, InitSystem, NetworkBackend,
+};
 use crate::utils::command::CommandRunner;
 use crate::utils::error::Result;
 use std::fs;
@@ -25,27 +27,92 @@ pub fn enable_services(
     install_service_packages(cmd, config, install_root, &services)?;
 
     for service in services {
-        // The init-specific elogind service package is blacklisted in
-        // build_service_packages() because it conflicts with seatd-<init>,
-        // so no elogind service file lands on disk — skip the enable to
-        // avoid a misleading "service not found" warning.  The base elogind
-        // package is still installed for pam_elogind.
-        if service == "elogind" {
-            continue;
-        }
         enable_service(cmd, &config.system.init, &service, install_root)?;
     }
 
     Ok(())
 }
 
+/// Unit type suffixes of systemd unit names
+const SYSTEMD_UNIT_SUFFIXES: &[&str] = &[".service", ".socket", ".timer", ".target", ".path"];
+
+/// Whether `service` names a systemd unit (`cronie.service`,
+/// `systemd-resolved`) rather than an init service
+pub fn is_systemd_unit(service: &str) -> bool {
+    service.starts_with("systemd-")
+        || SYSTEMD_UNIT_SUFFIXES
+            .iter()
+            .any(|suffix| service.ends_with(suffix))
+}
+
+/// Enable `system.extra_services`.  Runs after the extra packages are
+/// installed, since that is where their packages usually come from; a
+/// service whose files are missing is skipped with a warning.
+pub fn enable_extra_services(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let init = &config.system.init;
+    for service in &config.system.extra_services {
+        if !cmd.is_dry_run() && !service_installed(init, service, install_root) {
+            warn!(
+                "Extra service {} is not installed (no {}/{}); add its package, usually {} and {}-{}, to packages.extra_packages",
+                service,
+                init.service_dir(),
+                service_file_name(init, service),
+                service,
+                service,
+                init
+            );
+            continue;
+        }
+        enable_service(cmd, init, service, install_root)?;
+    }
+    Ok(())
+}
+
+/// Name of the service's entry under [`InitSystem::service_dir`].
+fn service_file_name(init: &InitSystem, service: &str) -> String {
+    match init {
+        InitSystem::S6 => map_s6_service_name(service),
+        _ => service.to_string(),
+    }
+}
+
+/// Seat manager the desktop session runs on.  The login-screen display
+/// managers (SDDM, GDM, LightDM) track sessions through logind and need
+/// elogind; greetd and the TTY login only need seatd.  The two init service
+/// packages conflict, so exactly one is installed.  `None` without a
+/// desktop.
+pub fn seat_manager(config: &DeploymentConfig) -> Option<&'static str> {
+    if config.desktop.environment == DesktopEnvironment::None {
+        return None;
+    }
+    Some(match config.desktop.display_manager {
+        DisplayManager::Sddm | DisplayManager::Gdm | DisplayManager::Lightdm => "elogind",
+        DisplayManager::Greetd | DisplayManager::None => "seatd",
+    })
+}
+
+/// Whether the installed system ships `service` for `init`.
+pub fn service_installed(init: &InitSystem, service: &str, install_root: &str) -> bool {
+    Path::new(&format!(
+        "{}{}/{}",
+        install_root,
+        init.service_dir(),
+        service_file_name(init, service)
+    ))
+    .exists()
+}
+
 /// Build list of services to enable based on configuration
 fn build_service_list(config: &DeploymentConfig) -> Vec<String> {
     let mut services = Vec::new();
 
-    // Seat management (only needed for desktop environments with Wayland support)
-    if config.desktop.environment != DesktopEnvironment::None {
-        services.push("seatd".to_string());
+    // Seat management for the desktop session
+    if let Some(seat) = seat_manager(config) {
+        services.push(seat.to_string());
     }
 
     // Network backend
@@ -72,13 +139,25 @@ fn build_service_list(config: &DeploymentConfig) -> Vec<String> {
         }
     }
 
-    // elogind — must be running before greetd so PAM pam_elogind can
-    // create the seat session that grants gamescope DRM/input ACLs.
-    // elogind-s6 exists in Artix repos alongside all other init variants.
-    if config.packages.install_session_switching
-        && config.desktop.environment != DesktopEnvironment::None
-    {
-        services.push("elogind".to_string());
+    // dmeventd monitors the LVM thin pool and autoextends it before it fills
+    if config.disk.use_lvm_thin {
+        services.push("dmeventd".to_string());
+    }
+
+    // NTP client so the clock is right from first boot
+    if let Some(service) = config.system.time_sync.service() {
+        services.push(service.to_string());
+    }
+
+    // Firewall; its ruleset is written by configure::firewall
+    if let Some(service) = config.system.firewall.service() {
+        services.push(service.to_string());
+    }
+
+    // sshd for headless installs and first-boot root access with the
+    // configured authorized keys
+    if crate::configure::ssh::sshd_enabled(config) {
+        services.push("sshd".to_string());
     }
 
     services
@@ -89,6 +168,11 @@ fn service_base_package(service: &str) -> &str {
     match service {
         // Service name uses CamelCase but the package is lowercase
         "NetworkManager" => "networkmanager",
+        // dmeventd ships with lvm2 (and lvm2-<init>)
+        "dmeventd" => "lvm2",
+        "sshd" => "openssh",
+        "chronyd" => "chrony",
+        "ntpd" => "ntp",
         other => other,
     }
 }
@@ -113,14 +197,6 @@ fn build_service_packages(services: &[String], init: &InitSystem) -> Vec<String>
         if *init == InitSystem::S6 && base == "greetd" {
             continue;
         }
-        // elogind-<init> conflicts with seatd-<init>: the two service packages
-        // both ship a `org.freedesktop.login1`-style seat manager unit and
-        // pacman refuses to install both.  pam_elogind from the base elogind
-        // package is enough for greetd's PAM stack, so the init-specific
-        // elogind service package is blacklisted from installation.
-        if base == "elogind" {
-            continue;
-        }
         let init_pkg = format!("{}-{}", base, init);
         packages.push(init_pkg);
     }
@@ -188,10 +264,11 @@ pub(crate) fn enable_service(
 
 /// Enable a runit service by creating symlink from runsvdir/default to sv/
 fn enable_runit_service(service: &str, install_root: &str) -> Result<()> {
+    let sv_dir = InitSystem::Runit.service_dir();
     // Path to check if service exists (within install_root)
-    let service_dir_check = format!("{}/etc/runit/sv/{}", install_root, service);
+    let service_dir_check = format!("{}{}/{}", install_root, sv_dir, service);
     // Symlink target - path relative to installed system root (not install_root)
-    let service_dir_target = format!("/etc/runit/sv/{}", service);
+    let service_dir_target = format!("{}/{}", sv_dir, service);
     // Directory where symlinks are created
     let enabled_dir = format!("{}/etc/runit/runsvdir/default", install_root);
     let link_path = format!("{}/{}", enabled_dir, service);
@@ -219,7 +296,12 @@ fn enable_runit_service(service: &str, install_root: &str) -> Result<()> {
 
 /// Enable an OpenRC service
 fn enable_openrc_service(cmd: &CommandRunner, service: &str, install_root: &str) -> Result<()> {
-    let service_path = format!("{}/etc/init.d/{}", install_root, service);
+    let service_path = format!(
+        "{}{}/{}",
+        install_root,
+        InitSystem::OpenRC.service_dir(),
+        service
+    );
 
     if !Path::new(&service_path).exists() {
         warn!(
@@ -250,7 +332,12 @@ fn map_s6_service_name(service: &str) -> String {
 /// corresponding package was not installed and we skip with a warning.
 fn enable_s6_service(service: &str, install_root: &str) -> Result<()> {
     let s6_service_name = map_s6_service_name(service);
-    let service_dir = format!("{}/etc/s6/sv/{}", install_root, s6_service_name);
+    let service_dir = format!(
+        "{}{}/{}",
+        install_root,
+        InitSystem::S6.service_dir(),
+        s6_service_name
+    );
     let enabled_dir = format!("{}/etc/s6/adminsv/default/contents.d", install_root);
     let link_path = format!("{}/{}", enabled_dir, s6_service_name);
 
@@ -274,10 +361,11 @@ fn enable_s6_service(service: &str, install_root: &str) -> Result<()> {
 
 /// Enable a dinit service
 fn enable_dinit_service(service: &str, install_root: &str) -> Result<()> {
-    let service_file_check = format!("{}/etc/dinit.d/{}", install_root, service);
+    let init = InitSystem::Dinit;
+    let service_file_check = format!("{}{}/{}", install_root, init.service_dir(), service);
     // Symlink target - path relative to installed system root
-    let service_file_target = format!("/etc/dinit.d/{}", service);
-    let enabled_dir = format!("{}/etc/dinit.d/boot.d", install_root);
+    let service_file_target = format!("{}/{}", init.service_dir(), service);
+    let enabled_dir = format!("{}{}", install_root, init.enabled_dir());
     let link_path = format!("{}/{}", enabled_dir, service);
 
     if !Path::new(&service_file_check).exists() {
@@ -301,7 +389,7 @@ fn enable_dinit_service(service: &str, install_root: &str) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::DisplayManager;
+    use crate::config::{DisplayManager, Firewall, TimeSync};
 
     fn config() -> DeploymentConfig {
         // sample(): KDE desktop, greetd display manager (default), runit,
@@ -325,6 +413,23 @@ mod tests {
         assert!(!services.contains(&"greetd".to_string()));
     }
 
+    #[test]
+    fn login_screens_run_on_elogind_instead_of_seatd() {
+        let mut cfg = config();
+        cfg.desktop.display_manager = DisplayManager::Gdm;
+        let services = build_service_list(&cfg);
+        assert!(services.contains(&"elogind".to_string()));
+        assert!(!services.contains(&"seatd".to_string()));
+        let packages = build_service_packages(&services, &cfg.system.init);
+        assert!(packages.contains(&"elogind-runit".to_string()));
+        assert!(!packages.contains(&"seatd-runit".to_string()));
+
+        cfg.desktop.display_manager = DisplayManager::None;
+        assert_eq!(seat_manager(&cfg), Some("seatd"));
+        cfg.desktop.environment = DesktopEnvironment::None;
+        assert_eq!(seat_manager(&cfg), None);
+    }
+
     #[test]
     fn display_manager_none_enables_no_dm_service() {
         let mut cfg = config();
@@ -335,6 +440,85 @@ mod tests {
         }
     }
 
+    #[test]
+    fn lvm_thin_enables_dmeventd_from_lvm2() {
+        let mut cfg = config();
+        cfg.disk.use_lvm_thin = true;
+        let services = build_service_list(&cfg);
+        assert!(services.contains(&"dmeventd".to_string()));
+        let packages = build_service_packages(&services, &cfg.system.init);
+        assert!(packages.contains(&"lvm2-runit".to_string()));
+    }
+
+    #[test]
+    fn root_authorized_keys_enable_sshd_from_openssh() {
+        let mut cfg = config();
+        assert!(!build_service_list(&cfg).contains(&"sshd".to_string()));
+        cfg.system.root_authorized_keys = vec!["ssh-ed25519 AAAA admin@host".to_string()];
+        let services = build_service_list(&cfg);
+        assert!(services.contains(&"sshd".to_string()));
+        let packages = build_service_packages(&services, &cfg.system.init);
+        assert!(packages.contains(&"openssh".to_string()));
+        assert!(packages.contains(&"openssh-runit".to_string()));
+    }
+
+    #[test]
+    fn firewall_enables_its_service() {
+        let mut cfg = config();
+        assert!(!build_service_list(&cfg).contains(&"nftables".to_string()));
+        cfg.system.firewall = Firewall::Nftables;
+        let services = build_service_list(&cfg);
+        assert!(services.contains(&"nftables".to_string()));
+        let packages = build_service_packages(&services, &cfg.system.init);
+        assert!(packages.contains(&"nftables-runit".to_string()));
+    }
+
+    #[test]
+    fn time_sync_enables_its_ntp_service() {
+        let mut cfg = config();
+        let services = build_service_list(&cfg);
+        assert!(services.contains(&"chronyd".to_string()));
+        let packages = build_service_packages(&services, &cfg.system.init);
+        assert!(packages.contains(&"chrony".to_string()));
+        assert!(packages.contains(&"chrony-runit".to_string()));
+
+        cfg.system.time_sync = TimeSync::None;
+        assert!(!build_service_list(&cfg).contains(&"chronyd".to_string()));
+    }
+
+    #[test]
+    fn ssh_flag_enables_sshd_without_keys() {
+        let mut cfg = config();
+        cfg.system.ssh = true;
+        let services = build_service_list(&cfg);
+        assert!(services.contains(&"sshd".to_string()));
+        let packages = build_service_packages(&services, &cfg.system.init);
+        assert!(packages.contains(&"openssh".to_string()));
+    }
+
+    #[test]
+    fn systemd_units_are_recognised() {
+        assert!(is_systemd_unit("cronie.service"));
+        assert!(is_systemd_unit("fstrim.timer"));
+        assert!(is_systemd_unit("systemd-resolved"));
+        assert!(!is_systemd_unit("cronie"));
+        assert!(!is_systemd_unit("NetworkManager"));
+    }
+
+    #[test]
+    fn extra_services_are_looked_up_per_init() {
+        let root = std::env::temp_dir().join(format!("deploytix-svc-{}", std::process::id()));
+        fs::create_dir_all(root.join("etc/runit/sv/cronie")).unwrap();
+        fs::create_dir_all(root.join("etc/s6/sv/cronie-srv")).unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        assert!(service_installed(&InitSystem::Runit, "cronie", &root_str));
+        assert!(service_installed(&InitSystem::S6, "cronie", &root_str));
+        assert!(!service_installed(&InitSystem::Dinit, "cronie", &root_str));
+        assert!(!service_installed(&InitSystem::Runit, "chronyd", &root_str));
+        let _ = fs::remove_dir_all(&root);
+    }
+
     #[test]
     fn headless_config_enables_no_dm_service() {
         let mut cfg = config();
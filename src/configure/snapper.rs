@@ -0,0 +1,191 @@
+//! Snapper configuration and the post-install baseline snapshot
+//!
+//! With `disk.btrfs_snapshots` the root filesystem gets an `@snapshots`
+//! subvolume mounted at `/.snapshots` (see
+//! [`crate::disk::layouts::snapshots_subvolume`]).  The snapper `root`
+//! config is written directly instead of through `snapper create-config`,
+//! which insists on creating `/.snapshots` as a nested subvolume itself.
+//! Once everything is installed a read-only baseline snapshot is taken;
+//! with GRUB, `grub-btrfs` lists it (and later snapshots) in the boot menu.
+//!
+//! `@log` and `@pkg` are separate nocow subvolumes (see
+//! [`crate::disk::layouts::snapshot_excluded_subvolumes`]), so snapshots of
+//! `@` never contain them and a rollback keeps logs and the package cache.
+
+use crate::config::{Bootloader, DeploymentConfig};
+use crate::disk::layouts::{snapshot_excluded_subvolumes, SNAPSHOTS_MOUNT_POINT};
+use crate::utils::command::CommandRunner;
+use crate::utils::error::Result;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use tracing::info;
+
+/// Snapper config covering `/`.
+const CONFIG_NAME: &str = "root";
+
+/// Description of the snapshot taken at the end of the install.
+const BASELINE_DESCRIPTION: &str = "Deploytix post-install baseline";
+
+/// Packages installed with snapshots enabled.
+pub fn packages(config: &DeploymentConfig) -> Vec<String> {
+    if !config.disk.uses_btrfs_snapshots() {
+        return Vec::new();
+    }
+    let mut packages = vec!["snapper".to_string()];
+    if config.system.bootloader == Bootloader::Grub {
+        packages.push("grub-btrfs".to_string());
+    }
+    packages
+}
+
+/// Contents of `/etc/snapper/configs/root`.  Timeline snapshots need the
+/// systemd timers snapper ships, so only number cleanup is enabled.
+fn generate_config() -> String {
+    r#"# snapper config for / (generated by Deploytix)
+SUBVOLUME="/"
+FSTYPE="btrfs"
+QGROUP=""
+SPACE_LIMIT="0.5"
+FREE_LIMIT="0.2"
+ALLOW_USERS=""
+ALLOW_GROUPS=""
+SYNC_ACL="no"
+BACKGROUND_COMPARISON="yes"
+NUMBER_CLEANUP="yes"
+NUMBER_MIN_AGE="1800"
+NUMBER_LIMIT="50"
+NUMBER_LIMIT_IMPORTANT="10"
+TIMELINE_CREATE="no"
+TIMELINE_CLEANUP="yes"
+EMPTY_PRE_POST_CLEANUP="yes"
+"#
+    .to_string()
+}
+
+/// Line appended to `/etc/default/grub-btrfs/config` so grub-btrfs never
+/// offers `@` itself or the snapshot-excluded subvolumes as boot entries.
+fn grub_btrfs_ignore_setting() -> String {
+    let mut paths = vec!["\"@\"".to_string()];
+    paths.extend(
+        snapshot_excluded_subvolumes("")
+            .iter()
+            .map(|s| format!("\"{}\"", s.name)),
+    );
+    format!("GRUB_BTRFS_IGNORE_SPECIFIC_PATH=({})\n", paths.join(" "))
+}
+
+/// Write the snapper `root` config and register it.
+pub fn configure_snapper(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    if !config.disk.uses_btrfs_snapshots() {
+        return Ok(());
+    }
+    info!("Configuring snapper for / with {}", SNAPSHOTS_MOUNT_POINT);
+
+    let config_path = format!("{}/etc/snapper/configs/{}", install_root, CONFIG_NAME);
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would write {}", config_path);
+        println!(
+            "  [dry-run] Would set SNAPPER_CONFIGS=\"{}\" in /etc/conf.d/snapper",
+            CONFIG_NAME
+        );
+        if config.system.bootloader == Bootloader::Grub {
+            print!(
+                "  [dry-run] Would add to /etc/default/grub-btrfs/config: {}",
+                grub_btrfs_ignore_setting()
+            );
+        }
+        return Ok(());
+    }
+
+    fs::create_dir_all(format!("{}/etc/snapper/configs", install_root))?;
+    fs::write(&config_path, generate_config())?;
+
+    fs::create_dir_all(format!("{}/etc/conf.d", install_root))?;
+    fs::write(
+        format!("{}/etc/conf.d/snapper", install_root),
+        format!("SNAPPER_CONFIGS=\"{}\"\n", CONFIG_NAME),
+    )?;
+
+    if config.system.bootloader == Bootloader::Grub {
+        let grub_btrfs_dir = format!("{}/etc/default/grub-btrfs", install_root);
+        fs::create_dir_all(&grub_btrfs_dir)?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(format!("{}/config", grub_btrfs_dir))?;
+        file.write_all(
+            format!(
+                "\n# Added by Deploytix: logs and the package cache are not snapshots\n{}",
+                grub_btrfs_ignore_setting()
+            )
+            .as_bytes(),
+        )?;
+    }
+
+    // Snapshots may hold files unreadable to users; keep them private
+    fs::set_permissions(
+        format!("{}{}", install_root, SNAPSHOTS_MOUNT_POINT),
+        fs::Permissions::from_mode(0o750),
+    )?;
+
+    info!("Snapper config written to {}", config_path);
+    Ok(())
+}
+
+/// Take the read-only baseline snapshot and, with GRUB, regenerate
+/// grub.cfg so grub-btrfs adds it to the boot menu.
+pub fn create_baseline_snapshot(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    if !config.disk.uses_btrfs_snapshots() {
+        return Ok(());
+    }
+    info!("Taking baseline snapshot of /");
+
+    // No D-Bus in the chroot; --no-dbus works on the config directly
+    cmd.run_in_chroot(
+        install_root,
+        &format!(
+            "snapper --no-dbus -c {} create --description '{}' --cleanup-algorithm number --userdata important=yes",
+            CONFIG_NAME, BASELINE_DESCRIPTION
+        ),
+    )?;
+
+    if config.system.bootloader == Bootloader::Grub {
+        cmd.run_in_chroot(install_root, "grub-mkconfig -o /boot/grub/grub.cfg")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Filesystem;
+
+    #[test]
+    fn packages_follow_filesystem_and_bootloader() {
+        let mut config = DeploymentConfig::sample();
+        config.disk.filesystem = Filesystem::Btrfs;
+        config.disk.btrfs_snapshots = true;
+        config.system.bootloader = Bootloader::Grub;
+        assert_eq!(packages(&config), vec!["snapper", "grub-btrfs"]);
+
+        config.disk.filesystem = Filesystem::Ext4;
+        assert!(packages(&config).is_empty());
+    }
+
+    #[test]
+    fn grub_btrfs_ignores_excluded_subvolumes() {
+        assert_eq!(
+            grub_btrfs_ignore_setting(),
+            "GRUB_BTRFS_IGNORE_SPECIFIC_PATH=(\"@\" \"@log\" \"@pkg\")\n"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! SSH server and root SSH access for headless provisioning
+//!
+//! Keys from `system.root_authorized_keys` are written to
+//! `/root/.ssh/authorized_keys` so the machine can be reached over SSH on
+//! first boot, before any user account is set up.  sshd is told to accept
+//! key-only root logins via a drop-in under `/etc/ssh/sshd_config.d`.
+//!
+//! `system.ssh` enables sshd on its own, with a hardening drop-in that
+//! forbids root login (unless root keys are configured) and allows
+//! passwords only when no keys are.
+
+use crate::config::DeploymentConfig;
+use crate::utils::command::CommandRunner;
+use crate::utils::error::Result;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use tracing::info;
+
+/// Path (relative to the install root) of root's authorized_keys file.
+const ROOT_AUTHORIZED_KEYS: &str = "/root/.ssh/authorized_keys";
+
+/// Main sshd configuration file.
+const SSHD_CONFIG: &str = "/etc/ssh/sshd_config";
+
+/// Drop-in permitting key-only root login.
+const SSHD_ROOT_DROPIN: &str = "/etc/ssh/sshd_config.d/20-deploytix-root.conf";
+
+/// Drop-in hardening the SSH server.  Sorts after the root drop-in, whose
+/// `PermitRootLogin` must win (sshd keeps the first value it reads).
+const SSHD_HARDENING_DROPIN: &str = "/etc/ssh/sshd_config.d/30-deploytix-sshd.conf";
+
+/// Include line the drop-in relies on (present in current openssh packages).
+const SSHD_INCLUDE: &str = "Include /etc/ssh/sshd_config.d/*.conf";
+
+/// Public key algorithms accepted in authorized_keys.
+const KEY_TYPES: &[&str] = &[
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+
+/// Whether root SSH access was requested.
+pub fn is_enabled(config: &DeploymentConfig) -> bool {
+    !config.system.root_authorized_keys.is_empty()
+}
+
+/// Whether sshd is installed and enabled: for `system.ssh` or root keys.
+pub fn sshd_enabled(config: &DeploymentConfig) -> bool {
+    config.system.ssh || is_enabled(config)
+}
+
+/// sshd `PasswordAuthentication`: as configured, else only without keys.
+pub fn password_authentication(config: &DeploymentConfig) -> bool {
+    config
+        .system
+        .ssh_password_authentication
+        .unwrap_or(config.system.root_authorized_keys.is_empty())
+}
+
+/// Check that `line` is a single authorized_keys entry:
+/// `[options] <type> <base64 blob> [comment]`.
+///
+/// The blob must decode and start with the same key type it is labelled
+/// with, which catches truncated or mangled copy-pastes.
+pub fn validate_authorized_key(line: &str) -> std::result::Result<(), String> {
+    if line.contains('\n') || line.contains('\r') {
+        return Err("key must be a single line".to_string());
+    }
+
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let Some(pos) = tokens.iter().position(|t| KEY_TYPES.contains(t)) else {
+        return Err("no supported key type found".to_string());
+    };
+    // Options, if any, come before the type as a single token
+    if pos > 1 {
+        return Err("unexpected text before the key type".to_string());
+    }
+
+    let key_type = tokens[pos];
+    let blob = tokens
+        .get(pos + 1)
+        .ok_or_else(|| "missing key data".to_string())?;
+    let decoded = decode_base64(blob).ok_or_else(|| "key data is not valid base64".to_string())?;
+
+    if decoded.len() < 4 {
+        return Err("key data is truncated".to_string());
+    }
+    let name_len = u32::from_be_bytes([decoded[0], decoded[1], decoded[2], decoded[3]]) as usize;
+    let embedded = decoded.get(4..4 + name_len).unwrap_or_default();
+    if embedded != key_type.as_bytes() {
+        return Err(format!("key data does not match type {}", key_type));
+    }
+
+    Ok(())
+}
+
+/// Decode standard (padded) base64.
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let bytes = input.as_bytes();
+    if bytes.is_empty() || !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 3);
+    let chunks = bytes.chunks(4);
+    let last = chunks.len() - 1;
+    for (i, chunk) in chunks.enumerate() {
+        let pad = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if pad > 2 || (pad > 0 && i != last) {
+            return None;
+        }
+        let mut acc = 0u32;
+        for &c in &chunk[..4 - pad] {
+            acc = (acc << 6) | value(c)?;
+        }
+        acc <<= 6 * pad as u32;
+        out.extend_from_slice(&acc.to_be_bytes()[1..4 - pad]);
+    }
+    Some(out)
+}
+
+/// Render root's authorized_keys file.
+pub fn generate_authorized_keys(keys: &[String]) -> String {
+    let mut content = String::new();
+    for key in keys {
+        content.push_str(key.trim());
+        content.push('\n');
+    }
+    content
+}
+
+/// Render the sshd drop-in permitting key-only root login.
+pub fn generate_sshd_root_dropin() -> String {
+    "# Root SSH access - Generated by Deploytix\n\
+     # Root may log in with the keys in /root/.ssh/authorized_keys;\n\
+     # password logins for root stay disabled.\n\
+     PermitRootLogin prohibit-password\n"
+        .to_string()
+}
+
+/// Render the sshd hardening drop-in for `system.ssh`.
+pub fn generate_sshd_hardening_dropin(config: &DeploymentConfig) -> String {
+    let mut content = String::from("# SSH server - Generated by Deploytix\n");
+    if !is_enabled(config) {
+        content.push_str("PermitRootLogin no\n");
+    }
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    content.push_str(&format!(
+        "PasswordAuthentication {}\n\
+         KbdInteractiveAuthentication no\n\
+         PermitEmptyPasswords no\n\
+         X11Forwarding no\n\
+         MaxAuthTries 3\n",
+        yes_no(password_authentication(config))
+    ));
+    content
+}
+
+/// Prepend the drop-in Include to sshd_config unless it is there; older
+/// sshd_config files predate it.
+fn ensure_sshd_include(install_root: &str) -> Result<()> {
+    let sshd_config_path = format!("{}{}", install_root, SSHD_CONFIG);
+    let sshd_config = fs::read_to_string(&sshd_config_path).unwrap_or_default();
+    if !sshd_config.lines().any(|l| l.trim() == SSHD_INCLUDE) {
+        // Include must precede other directives to take effect
+        fs::write(
+            &sshd_config_path,
+            format!("{}\n{}", SSHD_INCLUDE, sshd_config),
+        )?;
+    }
+    fs::create_dir_all(format!("{}/etc/ssh/sshd_config.d", install_root))?;
+    Ok(())
+}
+
+/// Write the sshd hardening drop-in.  No-op unless `system.ssh` is set.
+pub fn configure_sshd(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    if !config.system.ssh {
+        return Ok(());
+    }
+
+    let content = generate_sshd_hardening_dropin(config);
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would write {}:", SSHD_HARDENING_DROPIN);
+        for line in content.lines() {
+            println!("    {}", line);
+        }
+        return Ok(());
+    }
+
+    ensure_sshd_include(install_root)?;
+    fs::write(
+        format!("{}{}", install_root, SSHD_HARDENING_DROPIN),
+        content,
+    )?;
+    info!("SSH server hardened via {}", SSHD_HARDENING_DROPIN);
+    Ok(())
+}
+
+/// Write root's authorized_keys (0600, in a 0700 `.ssh`) and the sshd
+/// drop-in.  No-op when no keys are configured.
+pub fn configure_root_ssh(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    if !is_enabled(config) {
+        return Ok(());
+    }
+
+    let keys = &config.system.root_authorized_keys;
+    info!(
+        "Deploying {} authorized SSH key(s) for root (key-only root login)",
+        keys.len()
+    );
+
+    if cmd.is_dry_run() {
+        println!(
+            "  [dry-run] Would write {} ({} key(s), mode 0600, /root/.ssh mode 0700)",
+            ROOT_AUTHORIZED_KEYS,
+            keys.len()
+        );
+        println!("  [dry-run] Would write {}:", SSHD_ROOT_DROPIN);
+        for line in generate_sshd_root_dropin().lines() {
+            println!("    {}", line);
+        }
+        return Ok(());
+    }
+
+    let ssh_dir = format!("{}/root/.ssh", install_root);
+    fs::create_dir_all(&ssh_dir)?;
+    fs::set_permissions(&ssh_dir, fs::Permissions::from_mode(0o700))?;
+
+    let keys_path = format!("{}{}", install_root, ROOT_AUTHORIZED_KEYS);
+    fs::write(&keys_path, generate_authorized_keys(keys))?;
+    fs::set_permissions(&keys_path, fs::Permissions::from_mode(0o600))?;
+    info!("Root authorized_keys written to {}", keys_path);
+
+    ensure_sshd_include(install_root)?;
+    fs::write(
+        format!("{}{}", install_root, SSHD_ROOT_DROPIN),
+        generate_sshd_root_dropin(),
+    )?;
+    info!("Key-only root login permitted via {}", SSHD_ROOT_DROPIN);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIGx8x4V5a0m7Lr5y5h0Kp3p3S3G6h8Q1m5B3d0k2b9Zq admin@host";
+
+    #[test]
+    fn accepts_well_formed_keys() {
+        assert!(validate_authorized_key(ED25519).is_ok());
+        assert!(validate_authorized_key(&format!("no-pty {}", ED25519)).is_ok());
+    }
+
+    #[test]
+    fn rejects_mangled_keys() {
+        assert!(validate_authorized_key("").is_err());
+        assert!(validate_authorized_key("ssh-ed25519").is_err());
+        assert!(validate_authorized_key("ssh-ed25519 not*base64").is_err());
+        // Blob labelled as RSA but containing an ed25519 key
+        let mislabelled = ED25519.replacen("ssh-ed25519", "ssh-rsa", 1);
+        assert!(validate_authorized_key(&mislabelled).is_err());
+    }
+
+    #[test]
+    fn base64_round_trips_padding() {
+        assert_eq!(decode_base64("Zm9v").unwrap(), b"foo");
+        assert_eq!(decode_base64("Zm8=").unwrap(), b"fo");
+        assert_eq!(decode_base64("Zg==").unwrap(), b"f");
+        assert!(decode_base64("Zg=a").is_none());
+    }
+
+    #[test]
+    fn dropin_permits_key_only_root() {
+        assert!(generate_sshd_root_dropin().contains("PermitRootLogin prohibit-password"));
+    }
+
+    #[test]
+    fn hardening_ties_passwords_to_keys() {
+        let mut config = DeploymentConfig::sample();
+        config.system.ssh = true;
+        let dropin = generate_sshd_hardening_dropin(&config);
+        assert!(dropin.contains("PermitRootLogin no\n"));
+        assert!(dropin.contains("PasswordAuthentication yes\n"));
+
+        // Root keys: the root drop-in decides root login, passwords go off
+        config.system.root_authorized_keys = vec![ED25519.to_string()];
+        let dropin = generate_sshd_hardening_dropin(&config);
+        assert!(!dropin.contains("PermitRootLogin"));
+        assert!(dropin.contains("PasswordAuthentication no\n"));
+
+        config.system.ssh_password_authentication = Some(true);
+        assert!(generate_sshd_hardening_dropin(&config).contains("PasswordAuthentication yes\n"));
+    }
+}
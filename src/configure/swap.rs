@@ -4,46 +4,125 @@
 //! - ZRAM: Compressed RAM-based swap with higher priority
 //! - Swap file: File-based swap on btrfs or ext4
 
-use crate::config::{DeploymentConfig, InitSystem, SwapType};
+use crate::config::{DeploymentConfig, Filesystem, InitSystem, SwapType};
 use crate::disk::detection::get_ram_mib;
 use crate::utils::command::CommandRunner;
 use crate::utils::error::{DeploytixError, Result};
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 use tracing::info;
 
 /// Default swap file path
 pub const SWAP_FILE_PATH: &str = "/swap/swapfile";
 
-/// Fixed ZRAM size: 4 GiB in bytes.
+/// Total ZRAM swap size: 4 GiB in bytes, split evenly across the devices.
 const ZRAM_SIZE_BYTES: u64 = 4 * 1024 * 1024 * 1024;
 
+/// Helper script path on the installed system.
+const ZRAM_SCRIPT_PATH: &str = "/usr/local/bin/zram-swap";
+
+/// sysctl drop-in holding `vm.swappiness`.  Sorts after `99-gaming.conf`
+/// so an explicit setting wins over the gaming tweaks.
+const SWAPPINESS_CONF: &str = "/etc/sysctl.d/99-swappiness.conf";
+
+/// Default number of ZRAM swap devices.
+pub const DEFAULT_ZRAM_DEVICES: u8 = 1;
+
+/// Generate the `zram-swap` helper script.
+///
+/// `start` sets up `zram0` plus `devices - 1` hot-added devices as swap,
+/// recording their ids so `stop` tears down exactly those.  Hot-adding the
+/// extra devices keeps them clear of the zram mounts, which allocate theirs
+/// the same way.
+pub fn generate_zram_script(algorithm: &str, devices: u8) -> String {
+    let devices = devices.max(1);
+    format!(
+        r#"#!/bin/sh
+# ZRAM swap (generated by Deploytix)
+
+ALGORITHM={algorithm}
+DEVICES={devices}
+SIZE={size}
+STATE=/run/zram-swap
+
+swap_on() {{
+    echo "$ALGORITHM" > /sys/block/zram$1/comp_algorithm
+    echo "$SIZE" > /sys/block/zram$1/disksize
+    mkswap /dev/zram$1
+    swapon -p 100 /dev/zram$1
+    echo "$1" >> "$STATE"
+}}
+
+start() {{
+    modprobe zram
+    : > "$STATE"
+    swap_on 0
+    i=1
+    while [ "$i" -lt "$DEVICES" ]; do
+        swap_on "$(cat /sys/class/zram-control/hot_add)"
+        i=$((i + 1))
+    done
+}}
+
+stop() {{
+    [ -e "$STATE" ] || return 0
+    while read -r id; do
+        swapoff /dev/zram$id 2>/dev/null
+        echo 1 > /sys/block/zram$id/reset 2>/dev/null
+        [ "$id" = 0 ] || echo "$id" > /sys/class/zram-control/hot_remove
+    done < "$STATE"
+    rm -f "$STATE"
+}}
+
+case "$1" in
+    start|stop) "$1" ;;
+    *) echo "usage: $0 start|stop" >&2; exit 1 ;;
+esac
+"#,
+        algorithm = algorithm,
+        devices = devices,
+        size = ZRAM_SIZE_BYTES / u64::from(devices),
+    )
+}
+
 /// Setup ZRAM swap device
 ///
-/// Creates a init service that configures ZRAM at boot with a fixed 4 GiB device.
-/// ZRAM provides compressed in-memory swap with configurable compression algorithm.
+/// Installs the `zram-swap` helper and an init service that runs it at boot.
+/// ZRAM provides compressed in-memory swap with configurable compression
+/// algorithm; the fixed 4 GiB is split across `zram_devices` devices.
 pub fn setup_zram(
     cmd: &CommandRunner,
     config: &DeploymentConfig,
     install_root: &str,
 ) -> Result<()> {
     let algorithm = &config.disk.zram_algorithm;
+    let devices = config.disk.zram_devices.max(1);
 
-    info!("Setting up ZRAM: 4 GiB fixed, compression: {}", algorithm);
+    info!(
+        "Setting up ZRAM: 4 GiB fixed across {} device(s), compression: {}",
+        devices, algorithm
+    );
 
     if cmd.is_dry_run() {
         println!(
-            "  [dry-run] Would create ZRAM service: 4 GiB, {} compression",
-            algorithm
+            "  [dry-run] Would create ZRAM service: 4 GiB across {} device(s), {} compression",
+            devices, algorithm
         );
         return Ok(());
     }
 
+    fs::create_dir_all(format!("{}/usr/local/bin", install_root))?;
+    write_executable(
+        &format!("{}{}", install_root, ZRAM_SCRIPT_PATH),
+        &generate_zram_script(algorithm, devices),
+    )?;
+
     match config.system.init {
-        InitSystem::Runit => setup_zram_runit(install_root, algorithm)?,
-        InitSystem::OpenRC => setup_zram_openrc(install_root, algorithm)?,
-        InitSystem::S6 => setup_zram_s6(install_root, algorithm)?,
-        InitSystem::Dinit => setup_zram_dinit(install_root, algorithm)?,
+        InitSystem::Runit => setup_zram_runit(install_root)?,
+        InitSystem::OpenRC => setup_zram_openrc(install_root)?,
+        InitSystem::S6 => setup_zram_s6(install_root)?,
+        InitSystem::Dinit => setup_zram_dinit(install_root)?,
     }
 
     info!("ZRAM service configured successfully");
@@ -51,52 +130,22 @@ pub fn setup_zram(
 }
 
 /// Create ZRAM runit service
-fn setup_zram_runit(install_root: &str, algorithm: &str) -> Result<()> {
+fn setup_zram_runit(install_root: &str) -> Result<()> {
     let sv_dir = format!("{}/etc/runit/sv/zram", install_root);
     fs::create_dir_all(&sv_dir)?;
 
-    // Create run script
-    let run_script = format!(
-        r#"#!/bin/sh
-exec 2>&1
-
-# Load zram module
-modprobe zram num_devices=1
-
-# Configure zram0 with fixed 4 GiB size
-echo {algorithm} > /sys/block/zram0/comp_algorithm
-echo {size} > /sys/block/zram0/disksize
-
-# Setup swap
-mkswap /dev/zram0
-swapon -p 100 /dev/zram0
-
-# Keep service running
-exec pause
-"#,
-        algorithm = algorithm,
-        size = ZRAM_SIZE_BYTES
-    );
-
-    let run_path = format!("{}/run", sv_dir);
-    fs::write(&run_path, run_script)?;
-
-    // Make executable
-    let mut perms = fs::metadata(&run_path)?.permissions();
-    perms.set_mode(0o755);
-    fs::set_permissions(&run_path, perms)?;
-
-    // Create finish script for cleanup
-    let finish_script = r#"#!/bin/sh
-swapoff /dev/zram0 2>/dev/null
-echo 1 > /sys/block/zram0/reset 2>/dev/null
-"#;
-
-    let finish_path = format!("{}/finish", sv_dir);
-    fs::write(&finish_path, finish_script)?;
-    let mut perms = fs::metadata(&finish_path)?.permissions();
-    perms.set_mode(0o755);
-    fs::set_permissions(&finish_path, perms)?;
+    // Set up the devices, then keep the service up so finish runs on stop
+    write_executable(
+        &format!("{}/run", sv_dir),
+        &format!(
+            "#!/bin/sh\nexec 2>&1\n{} start\nexec pause\n",
+            ZRAM_SCRIPT_PATH
+        ),
+    )?;
+    write_executable(
+        &format!("{}/finish", sv_dir),
+        &format!("#!/bin/sh\n{} stop\n", ZRAM_SCRIPT_PATH),
+    )?;
 
     // Enable the service by creating a symlink in the default runsvdir
     let link_dir = format!("{}/etc/runit/runsvdir/default", install_root);
@@ -108,7 +157,7 @@ echo 1 > /sys/block/zram0/reset 2>/dev/null
 }
 
 /// Create ZRAM OpenRC service
-fn setup_zram_openrc(install_root: &str, algorithm: &str) -> Result<()> {
+fn setup_zram_openrc(install_root: &str) -> Result<()> {
     let init_dir = format!("{}/etc/init.d", install_root);
     fs::create_dir_all(&init_dir)?;
 
@@ -124,32 +173,21 @@ depend() {{
 
 start() {{
     ebegin "Starting ZRAM swap"
-    
-    modprobe zram num_devices=1
-    echo {algorithm} > /sys/block/zram0/comp_algorithm
-    echo {size} > /sys/block/zram0/disksize
-    mkswap /dev/zram0
-    swapon -p 100 /dev/zram0
-    
+    {script} start
     eend $?
 }}
 
 stop() {{
     ebegin "Stopping ZRAM swap"
-    swapoff /dev/zram0 2>/dev/null
-    echo 1 > /sys/block/zram0/reset 2>/dev/null
+    {script} stop
     eend $?
 }}
 "#,
-        algorithm = algorithm,
-        size = ZRAM_SIZE_BYTES
+        script = ZRAM_SCRIPT_PATH
     );
 
     let script_path = format!("{}/zram", init_dir);
-    fs::write(&script_path, init_script)?;
-    let mut perms = fs::metadata(&script_path)?.permissions();
-    perms.set_mode(0o755);
-    fs::set_permissions(&script_path, perms)?;
+    write_executable(&script_path, &init_script)?;
 
     // Enable the service in the default runlevel
     let runlevel_dir = format!("{}/etc/runlevels/default", install_root);
@@ -162,12 +200,9 @@ stop() {{
 
 /// Create ZRAM s6 service
 ///
-/// Follows the structure used by the `zram-s6` AUR package
-/// (upstream: github.com/Senderman/s6-services).  The service is an s6-rc
-/// oneshot, so the startup script lives in `up` (not `run`, which is for
-/// longruns).  Configuration is stored in `/etc/s6/config/zram.conf` and
-/// read at boot via `envfile`.
-fn setup_zram_s6(install_root: &str, algorithm: &str) -> Result<()> {
+/// An s6-rc oneshot: `up` and `down` are execline command lines that call
+/// the helper script.
+fn setup_zram_s6(install_root: &str) -> Result<()> {
     let sv_dir = format!("{}/etc/s6/sv/zram", install_root);
     fs::create_dir_all(&sv_dir)?;
 
@@ -177,48 +212,16 @@ fn setup_zram_s6(install_root: &str, algorithm: &str) -> Result<()> {
     fs::write(format!("{}/mount-devfs", deps_dir), "")?;
     fs::write(format!("{}/mount-sysfs", deps_dir), "")?;
 
-    // /etc/s6/config/zram.conf — configuration read by the up script at boot
-    let config_dir = format!("{}/etc/s6/config", install_root);
-    fs::create_dir_all(&config_dir)?;
-
-    let config_content = format!(
-        "COMP_ALGORITHM={}\nZRAM_SIZE={}\n",
-        algorithm, ZRAM_SIZE_BYTES
-    );
-    fs::write(format!("{}/zram.conf", config_dir), config_content)?;
-
-    // up — execlineb oneshot startup script (mirrors the AUR package)
-    let up_script = r#"#!/usr/bin/execlineb -P
-fdmove -c 2 1
-envfile /etc/s6/config/zram.conf
-importas comp_algorithm COMP_ALGORITHM
-importas zram_size ZRAM_SIZE
-
-foreground { modprobe zram }
-foreground { redirfd -w 1 /sys/block/zram0/comp_algorithm echo $comp_algorithm }
-foreground { redirfd -w 1 /sys/block/zram0/disksize echo $zram_size }
-foreground { mkswap --label zram0 /dev/zram0 }
-swapon --priority 100 /dev/zram0
-"#;
-
-    let up_path = format!("{}/up", sv_dir);
-    fs::write(&up_path, up_script)?;
-    let mut perms = fs::metadata(&up_path)?.permissions();
-    perms.set_mode(0o755);
-    fs::set_permissions(&up_path, perms)?;
-
-    // down — teardown script run when the service is stopped
-    let down_script = r#"#!/usr/bin/execlineb -P
-fdmove -c 2 1
-foreground { swapoff /dev/zram0 }
-redirfd -w 1 /sys/block/zram0/reset echo 1
-"#;
-
-    let down_path = format!("{}/down", sv_dir);
-    fs::write(&down_path, down_script)?;
-    let mut perms = fs::metadata(&down_path)?.permissions();
-    perms.set_mode(0o755);
-    fs::set_permissions(&down_path, perms)?;
+    for action in ["up", "down"] {
+        let verb = if action == "up" { "start" } else { "stop" };
+        write_executable(
+            &format!("{}/{}", sv_dir, action),
+            &format!(
+                "#!/usr/bin/execlineb -P\nfdmove -c 2 1\n{} {}\n",
+                ZRAM_SCRIPT_PATH, verb
+            ),
+        )?;
+    }
 
     // type — declares this as an s6-rc oneshot (runs once, not supervised)
     fs::write(format!("{}/type", sv_dir), "oneshot\n")?;
@@ -233,39 +236,17 @@ redirfd -w 1 /sys/block/zram0/reset echo 1
 }
 
 /// Create ZRAM dinit service
-fn setup_zram_dinit(install_root: &str, algorithm: &str) -> Result<()> {
+fn setup_zram_dinit(install_root: &str) -> Result<()> {
     let dinit_dir = format!("{}/etc/dinit.d", install_root);
     fs::create_dir_all(&dinit_dir)?;
 
-    // Create setup script
-    let script_dir = format!("{}/usr/local/bin", install_root);
-    fs::create_dir_all(&script_dir)?;
-
-    let setup_script = format!(
-        r#"#!/bin/sh
-# Fixed 4 GiB ZRAM swap device
-modprobe zram num_devices=1
-echo {algorithm} > /sys/block/zram0/comp_algorithm
-echo {size} > /sys/block/zram0/disksize
-mkswap /dev/zram0
-swapon -p 100 /dev/zram0
-"#,
-        algorithm = algorithm,
-        size = ZRAM_SIZE_BYTES
-    );
-
-    let script_path = format!("{}/zram-setup", script_dir);
-    fs::write(&script_path, setup_script)?;
-    let mut perms = fs::metadata(&script_path)?.permissions();
-    perms.set_mode(0o755);
-    fs::set_permissions(&script_path, perms)?;
-
     // Create dinit service file.
     // Note: no dependency declared — modprobe and /sys are available
     // early in the boot sequence and do not require a mount service.
-    let service_content = r#"type = scripted
-command = /usr/local/bin/zram-setup
-"#;
+    let service_content = format!(
+        "type = scripted\ncommand = {script} start\nstop-command = {script} stop\n",
+        script = ZRAM_SCRIPT_PATH
+    );
 
     let service_path = format!("{}/zram", dinit_dir);
     fs::write(&service_path, service_content)?;
@@ -279,46 +260,101 @@ command = /usr/local/bin/zram-setup
     Ok(())
 }
 
-/// Create a swap file
-///
-/// For btrfs: Uses `btrfs filesystem mkswapfile` (kernel 6.1+) or fallback method.
-/// For ext4: Uses fallocate + mkswap.
-pub fn create_swap_file(
+/// Write the `vm.swappiness` sysctl drop-in.  No-op when unset.
+pub fn configure_swappiness(
     cmd: &CommandRunner,
     config: &DeploymentConfig,
     install_root: &str,
 ) -> Result<()> {
-    let size_mib = if config.disk.swap_file_size_mib > 0 {
-        config.disk.swap_file_size_mib
-    } else {
-        // Auto-calculate: 2x RAM, capped at 16 GiB
-        let ram_mib = get_ram_mib();
-        std::cmp::min(ram_mib * 2, 16384)
+    let Some(swappiness) = config.disk.vm_swappiness else {
+        return Ok(());
     };
-
-    let swap_dir = format!("{}/swap", install_root);
-    let swap_file = format!("{}/swapfile", swap_dir);
-
-    info!("Creating {} MiB swap file at {}", size_mib, swap_file);
+    info!("Setting vm.swappiness = {}", swappiness);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] mkdir -p {}", swap_dir);
         println!(
-            "  [dry-run] Create {} MiB swap file at {}",
-            size_mib, swap_file
+            "  [dry-run] Would write {} (vm.swappiness = {})",
+            SWAPPINESS_CONF, swappiness
         );
         return Ok(());
     }
 
-    // Create swap directory
-    fs::create_dir_all(&swap_dir)?;
+    fs::create_dir_all(format!("{}/etc/sysctl.d", install_root))?;
+    fs::write(
+        format!("{}{}", install_root, SWAPPINESS_CONF),
+        format!(
+            "# Swap tuning - Generated by Deploytix\nvm.swappiness = {}\n",
+            swappiness
+        ),
+    )?;
+    Ok(())
+}
+
+fn write_executable(path: &str, content: &str) -> Result<()> {
+    fs::write(path, content)?;
+    let mut perms = fs::metadata(path)?.permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(path, perms)?;
+    Ok(())
+}
+
+/// Swap file size: `swap_file_size_mib`, or twice the RAM capped at 16 GiB.
+fn swap_file_size_mib(config: &DeploymentConfig) -> Result<u64> {
+    if config.disk.swap_file_size_mib > 0 {
+        return Ok(config.disk.swap_file_size_mib);
+    }
+    let ram_mib = get_ram_mib()?;
+    Ok(std::cmp::min(ram_mib.saturating_mul(2), 16384))
+}
+
+/// Create a swap file at [`SWAP_FILE_PATH`]
+///
+/// For btrfs: `/swap` becomes a nested subvolume, so snapshots of `@` skip
+/// it, and the file is created NOCOW (and therefore uncompressed) with
+/// `btrfs filesystem mkswapfile` (btrfs-progs 6.1+) or `chattr +C`.
+/// For ext4: fallocate + mkswap.
+pub fn create_swap_file(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let size_mib = swap_file_size_mib(config)?;
+    let is_btrfs = config.disk.filesystem == Filesystem::Btrfs;
+
+    let swap_file = format!("{}{}", install_root, SWAP_FILE_PATH);
+    let swap_dir = Path::new(&swap_file)
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| install_root.to_string());
+
+    info!("Creating {} MiB swap file at {}", size_mib, swap_file);
 
-    // Check if btrfs
-    let is_btrfs = check_is_btrfs(&swap_dir);
+    if cmd.is_dry_run() {
+        if is_btrfs {
+            cmd.log_dry_run(&format!("btrfs subvolume create {}", swap_dir));
+            cmd.log_dry_run(&format!(
+                "btrfs filesystem mkswapfile --size {}m {}",
+                size_mib, swap_file
+            ));
+        } else {
+            cmd.log_dry_run(&format!("mkdir -p {}", swap_dir));
+            cmd.log_dry_run(&format!("fallocate -l {}M {}", size_mib, swap_file));
+            cmd.log_dry_run(&format!("mkswap {}", swap_file));
+        }
+        return Ok(());
+    }
 
     if is_btrfs {
-        create_btrfs_swap_file(cmd, &swap_file, size_mib)?;
+        if !Path::new(&swap_dir).exists() {
+            cmd.run("btrfs", &["subvolume", "create", &swap_dir])
+                .map_err(|e| DeploytixError::CommandFailed {
+                    command: "btrfs subvolume create".to_string(),
+                    stderr: e.to_string(),
+                })?;
+        }
+        create_btrfs_swap_file(cmd, &swap_dir, &swap_file, size_mib)?;
     } else {
+        fs::create_dir_all(&swap_dir)?;
         create_regular_swap_file(cmd, &swap_file, size_mib)?;
     }
 
@@ -327,7 +363,7 @@ pub fn create_swap_file(
     perms.set_mode(0o600);
     fs::set_permissions(&swap_file, perms)?;
 
-    // Format as swap
+    // Format as swap (mkswapfile already did, but the fallback did not)
     cmd.run("mkswap", &[&swap_file])
         .map_err(|e| DeploytixError::CommandFailed {
             command: "mkswap".to_string(),
@@ -338,29 +374,20 @@ pub fn create_swap_file(
     Ok(())
 }
 
-/// Check if a path is on a btrfs filesystem
-fn check_is_btrfs(path: &str) -> bool {
-    use std::process::Command;
-
-    let output = Command::new("stat").args(["-f", "-c", "%T", path]).output();
-
-    match output {
-        Ok(out) => {
-            let fs_type = String::from_utf8_lossy(&out.stdout).trim().to_string();
-            fs_type == "btrfs"
-        }
-        Err(_) => false,
-    }
-}
-
 /// Create swap file on btrfs
 ///
-/// Uses `btrfs filesystem mkswapfile` if available (kernel 6.1+),
-/// otherwise falls back to chattr + truncate method.
-fn create_btrfs_swap_file(cmd: &CommandRunner, path: &str, size_mib: u64) -> Result<()> {
+/// Uses `btrfs filesystem mkswapfile` if available (btrfs-progs 6.1+),
+/// otherwise falls back to chattr + fallocate.  Either way the file is
+/// NOCOW: swap activation fails on a copy-on-write or compressed file.
+fn create_btrfs_swap_file(
+    cmd: &CommandRunner,
+    swap_dir: &str,
+    path: &str,
+    size_mib: u64,
+) -> Result<()> {
     info!("Creating btrfs swap file at {}", path);
 
-    // Try the modern mkswapfile command first (kernel 6.1+)
+    // Try the modern mkswapfile command first
     let result = cmd.run(
         "btrfs",
         &[
@@ -379,15 +406,18 @@ fn create_btrfs_swap_file(cmd: &CommandRunner, path: &str, size_mib: u64) -> Res
     // Fallback: manual creation
     info!("Falling back to manual btrfs swap file creation");
 
-    // Create empty file
+    // Disable COW on the directory too, so a recreated file inherits it.
+    // The flag only takes effect on an empty file, hence before fallocate.
+    let chattr = |target: &str| {
+        cmd.run("chattr", &["+C", target])
+            .map_err(|e| DeploytixError::CommandFailed {
+                command: "chattr +C".to_string(),
+                stderr: e.to_string(),
+            })
+    };
+    chattr(swap_dir)?;
     std::fs::File::create(path)?;
-
-    // Disable COW
-    cmd.run("chattr", &["+C", path])
-        .map_err(|e| DeploytixError::CommandFailed {
-            command: "chattr +C".to_string(),
-            stderr: e.to_string(),
-        })?;
+    chattr(path)?;
 
     // Allocate space
     cmd.run("fallocate", &["-l", &format!("{}M", size_mib), path])
@@ -412,30 +442,33 @@ fn create_regular_swap_file(cmd: &CommandRunner, path: &str, size_mib: u64) -> R
     Ok(())
 }
 
-/// Get swap file physical offset for hibernation resume
+/// Get swap file offset for hibernation resume, in pages: the value for
+/// the `resume_offset=` kernel parameter.
 ///
-/// Required for hibernation with swap file on btrfs.
-/// Returns the physical offset that should be used in `resume_offset=` kernel parameter.
-pub fn get_swap_file_offset(swap_file: &str) -> Result<u64> {
+/// On btrfs the physical offsets filefrag reports are btrfs logical
+/// addresses, so only `btrfs inspect-internal map-swapfile` is usable.
+pub fn get_swap_file_offset(swap_file: &str, btrfs: bool) -> Result<u64> {
     use std::process::Command;
 
     info!("Getting swap file offset for {}", swap_file);
 
-    // Try btrfs-specific command first
-    let output = Command::new("btrfs")
-        .args(["inspect-internal", "map-swapfile", "-r", swap_file])
-        .output();
-
-    if let Ok(out) = output {
-        if out.status.success() {
-            let offset_str = String::from_utf8_lossy(&out.stdout).trim().to_string();
-            if let Ok(offset) = offset_str.parse::<u64>() {
-                return Ok(offset);
-            }
-        }
+    if btrfs {
+        let output = Command::new("btrfs")
+            .args(["inspect-internal", "map-swapfile", "-r", swap_file])
+            .output()
+            .map_err(|e| DeploytixError::CommandFailed {
+                command: "btrfs inspect-internal map-swapfile".to_string(),
+                stderr: e.to_string(),
+            })?;
+        let offset_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return offset_str
+            .parse::<u64>()
+            .map_err(|_| DeploytixError::CommandFailed {
+                command: "btrfs inspect-internal map-swapfile".to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
     }
 
-    // Fallback: use filefrag
     let output = Command::new("filefrag")
         .args(["-v", swap_file])
         .output()
@@ -451,39 +484,61 @@ pub fn get_swap_file_offset(swap_file: &str) -> Result<u64> {
         });
     }
 
-    // Parse filefrag output to get physical offset
-    // Format: "   0:        0..    8191:     123456..    131647: ..."
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    for line in stdout.lines() {
-        if line.contains("0:") && line.contains("..") {
-            // Find the physical start offset
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            for (i, part) in parts.iter().enumerate() {
-                if part.contains("..") && i > 0 {
-                    // Previous part should be the logical extent, next should be physical
-                    if let Some(phys_part) = parts.get(i + 1) {
-                        let phys_str = phys_part.split("..").next().unwrap_or("0");
-                        if let Ok(offset) = phys_str.parse::<u64>() {
-                            return Ok(offset);
-                        }
-                    }
-                }
-            }
+    parse_filefrag_offset(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+        DeploytixError::CommandFailed {
+            command: "get_swap_file_offset".to_string(),
+            stderr: "Could not determine swap file offset".to_string(),
         }
-    }
+    })
+}
 
-    Err(DeploytixError::CommandFailed {
-        command: "get_swap_file_offset".to_string(),
-        stderr: "Could not determine swap file offset".to_string(),
+/// Physical start of the first extent in `filefrag -v` output.  Extent
+/// lines look like `   0:        0..  32767:      34816..     67583:  32768:`
+/// (the numbers may run into the `..` when they fill their column).  ext4
+/// blocks are page-sized, so the block number is the resume offset.
+fn parse_filefrag_offset(output: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()?.trim() != "0" {
+            return None;
+        }
+        let _logical = fields.next()?;
+        fields.next()?.split("..").next()?.trim().parse().ok()
     })
 }
 
+/// `resume=` and `resume_offset=` kernel parameters for the swap file: the
+/// UUID of the filesystem holding it and its offset in that filesystem.
+pub fn swap_file_resume_params(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<String> {
+    if cmd.is_dry_run() {
+        return Ok(
+            "resume=UUID=XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX resume_offset=<offset>".to_string(),
+        );
+    }
+
+    let swap_file = format!("{}{}", install_root, SWAP_FILE_PATH);
+    let uuid = cmd.run_captured("findmnt", &["-no", "UUID", "-T", &swap_file])?;
+    let offset = get_swap_file_offset(&swap_file, config.disk.filesystem == Filesystem::Btrfs)?;
+    info!("Swap file resume offset: {}", offset);
+    Ok(format!(
+        "resume=UUID={} resume_offset={}",
+        uuid.trim(),
+        offset
+    ))
+}
+
 /// Configure swap based on SwapType
 pub fn configure_swap(
     cmd: &CommandRunner,
     config: &DeploymentConfig,
     install_root: &str,
 ) -> Result<()> {
+    configure_swappiness(cmd, config, install_root)?;
+
     match config.disk.swap_type {
         SwapType::Partition => {
             // Swap partition is handled by layout and fstab
@@ -494,26 +549,6 @@ pub fn configure_swap(
             // Setup both ZRAM and swap file
             setup_zram(cmd, config, install_root)?;
             create_swap_file(cmd, config, install_root)?;
-
-            // If hibernation is enabled, get the swap file offset for resume
-            if config.system.hibernation {
-                let swap_file = format!("{}{}", install_root, SWAP_FILE_PATH);
-                match get_swap_file_offset(&swap_file) {
-                    Ok(offset) => {
-                        info!("Swap file offset for hibernation: {}", offset);
-                        info!(
-                            "Add 'resume_offset={}' to kernel parameters for hibernation",
-                            offset
-                        );
-                    }
-                    Err(e) => {
-                        info!(
-                            "Could not determine swap file offset: {} (hibernation may not work)",
-                            e
-                        );
-                    }
-                }
-            }
             Ok(())
         }
         SwapType::ZramOnly => {
@@ -533,6 +568,37 @@ pub fn swap_file_fstab_entry() -> String {
 mod tests {
     use super::*;
 
+    // ── zram swap script ─────────────────────────────────────────────────────
+
+    #[test]
+    fn zram_script_splits_size_across_devices() {
+        let one = generate_zram_script("zstd", 1);
+        assert!(one.contains("DEVICES=1\n"));
+        assert!(one.contains(&format!("SIZE={}\n", ZRAM_SIZE_BYTES)));
+
+        let four = generate_zram_script("lz4", 4);
+        assert!(four.contains("ALGORITHM=lz4\n"));
+        assert!(four.contains("DEVICES=4\n"));
+        assert!(four.contains(&format!("SIZE={}\n", ZRAM_SIZE_BYTES / 4)));
+        assert!(four.contains("/sys/class/zram-control/hot_add"));
+    }
+
+    // ── swap file resume offset ──────────────────────────────────────────────
+
+    #[test]
+    fn filefrag_offset_is_first_physical_extent() {
+        let output = "Filesystem type is: ef53\n\
+File size of /swap/swapfile is 4294967296 (1048576 blocks of 4096 bytes)\n \
+ext:     logical_offset:        physical_offset: length:   expected: flags:\n   \
+0:        0..   32767:      34816..     67583:  32768:            \n   \
+1:    32768..   65535:      69632..    102399:  32768:      67584:\n";
+        assert_eq!(parse_filefrag_offset(output), Some(34816));
+
+        let packed = "   0:        0..32767:  123456789..123490000:  32768:\n";
+        assert_eq!(parse_filefrag_offset(packed), Some(123456789));
+        assert_eq!(parse_filefrag_offset("no extents\n"), None);
+    }
+
     // ── swap_file_fstab_entry ────────────────────────────────────────────────
 
     #[test]
@@ -0,0 +1,90 @@
+//! NTP client configuration
+//!
+//! The client's package and service are handled by `configure::services`;
+//! this writes a minimal configuration pointing at the NTP pool so the
+//! clock is stepped into place at first boot instead of drifting.
+
+use crate::config::{DeploymentConfig, TimeSync};
+use crate::utils::command::CommandRunner;
+use crate::utils::error::Result;
+use std::fs;
+use tracing::info;
+
+/// NTP pool the generated configurations use.
+const NTP_POOL: &str = "pool.ntp.org";
+
+/// Config file path and contents for `time_sync`, or None without a client.
+pub fn time_sync_config(time_sync: TimeSync) -> Option<(&'static str, String)> {
+    match time_sync {
+        TimeSync::None => None,
+        TimeSync::Chrony => Some((
+            "/etc/chrony.conf",
+            format!(
+                "# Generated by Deploytix\n\
+                 pool {} iburst\n\
+                 driftfile /var/lib/chrony/drift\n\
+                 # Step the clock if it is off by more than a second at boot\n\
+                 makestep 1.0 3\n\
+                 rtcsync\n",
+                NTP_POOL
+            ),
+        )),
+        TimeSync::Ntpd => Some((
+            "/etc/ntp.conf",
+            format!(
+                "# Generated by Deploytix\n\
+                 pool {} iburst\n\
+                 driftfile /var/lib/ntp/ntp.drift\n\
+                 restrict default kod limited nomodify nopeer noquery notrap\n\
+                 restrict 127.0.0.1\n\
+                 restrict ::1\n",
+                NTP_POOL
+            ),
+        )),
+        TimeSync::OpenNtpd => Some((
+            "/etc/ntpd.conf",
+            format!("# Generated by Deploytix\nservers {}\n", NTP_POOL),
+        )),
+    }
+}
+
+/// Write the NTP client configuration.  No-op for `time_sync = "none"`.
+pub fn configure_time_sync(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let Some((path, content)) = time_sync_config(config.system.time_sync) else {
+        return Ok(());
+    };
+    info!(
+        "Configuring {} time synchronization",
+        config.system.time_sync
+    );
+
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would write {}:", path);
+        for line in content.lines() {
+            println!("    {}", line);
+        }
+        return Ok(());
+    }
+
+    fs::write(format!("{}{}", install_root, path), content)?;
+    info!("NTP configuration written to {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_client_points_at_the_pool() {
+        for time_sync in [TimeSync::Chrony, TimeSync::Ntpd, TimeSync::OpenNtpd] {
+            let (_, content) = time_sync_config(time_sync).unwrap();
+            assert!(content.contains(NTP_POOL), "{}", time_sync);
+        }
+        assert!(time_sync_config(TimeSync::None).is_none());
+    }
+}
@@ -1,12 +1,31 @@
 //! User creation and management
 
-use crate::config::DeploymentConfig;
+use crate::config::{DeploymentConfig, SudoConfig};
 use crate::utils::command::CommandRunner;
-use crate::utils::error::Result;
+use crate::utils::error::{DeploytixError, Result};
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use tracing::info;
 
+/// Groups a desktop user needs: `seat` for seatd's socket and `video` for
+/// the GPU device nodes
+pub const SEAT_GROUPS: &[&str] = &["seat", "video"];
+
+/// The user's groups: the configured ones plus, with a desktop,
+/// [`SEAT_GROUPS`], so a trimmed `user.groups` cannot leave the graphical
+/// session without access to its seat.
+pub fn user_groups(config: &DeploymentConfig) -> Vec<String> {
+    let mut groups = config.user.groups.clone();
+    if crate::configure::services::seat_manager(config).is_some() {
+        for group in SEAT_GROUPS {
+            if !groups.iter().any(|g| g == group) {
+                groups.push(group.to_string());
+            }
+        }
+    }
+    groups
+}
+
 /// Create user account
 pub fn create_user(
     cmd: &CommandRunner,
@@ -15,7 +34,7 @@ pub fn create_user(
 ) -> Result<()> {
     let username = &config.user.name;
     let password = &config.user.password;
-    let groups = &config.user.groups;
+    let groups = &user_groups(config);
 
     info!(
         "Creating user '{}' with groups [{}]",
@@ -25,8 +44,10 @@ pub fn create_user(
 
     if cmd.is_dry_run() {
         println!(
-            "  [dry-run] Would create user {} with groups {:?}",
-            username, groups,
+            "  [dry-run] Would create user {} with groups {:?} and shell {}",
+            username,
+            groups,
+            config.user.shell_path(),
         );
         return Ok(());
     }
@@ -34,7 +55,12 @@ pub fn create_user(
     // Build groups string
     let groups_str = groups.join(",");
 
-    let useradd_cmd = format!("useradd -m -G {} -s /bin/bash {}", groups_str, username);
+    let useradd_cmd = format!(
+        "useradd -m -G {} -s {} {}",
+        groups_str,
+        config.user.shell_path(),
+        username
+    );
     cmd.run_in_chroot(install_root, &useradd_cmd)?;
 
     // Set password using chpasswd, passing credentials via a temp file to
@@ -51,7 +77,7 @@ pub fn create_user(
 
     // Configure sudoers if user should be sudoer
     if config.user.sudoer {
-        configure_sudoers(cmd, install_root)?;
+        configure_sudoers(cmd, &config.sudo, sudo_nopasswd(config), install_root)?;
     }
 
     // Raise nofile ulimit so gamescope-session-plus can set ulimit -n 524288
@@ -109,37 +135,65 @@ fn configure_bashrc_path(install_root: &str, username: &str) -> Result<()> {
     Ok(())
 }
 
-/// Configure sudoers for wheel group
-fn configure_sudoers(cmd: &CommandRunner, install_root: &str) -> Result<()> {
-    info!("Configuring sudoers for wheel group");
+/// sudoers drop-in written for the wheel group
+const SUDOERS_DROPIN: &str = "/etc/sudoers.d/10-deploytix-wheel";
 
-    let sudoers_path = format!("{}/etc/sudoers", install_root);
+/// Whether wheel runs sudo without a password: as configured, else only
+/// when the session switch scripts need it.
+pub fn sudo_nopasswd(config: &DeploymentConfig) -> bool {
+    config
+        .sudo
+        .nopasswd
+        .unwrap_or(config.packages.install_session_switching)
+}
+
+/// Contents of the wheel sudoers drop-in for `sudo`.
+fn sudoers_dropin(sudo: &SudoConfig, nopasswd: bool) -> String {
+    let mut content = String::from("# Generated by Deploytix\n");
+    if let Some(minutes) = sudo.timeout_minutes {
+        content.push_str(&format!("Defaults:%wheel timestamp_timeout={}\n", minutes));
+    }
+    let tag = if nopasswd { "NOPASSWD: " } else { "" };
+    let commands = if sudo.commands.is_empty() {
+        "ALL".to_string()
+    } else {
+        sudo.commands.join(", ")
+    };
+    content.push_str(&format!("%wheel ALL=(ALL:ALL) {}{}\n", tag, commands));
+    content
+}
+
+/// Write the wheel sudoers drop-in and check it with `visudo -c`,
+/// removing it again if sudo would reject it.
+fn configure_sudoers(
+    cmd: &CommandRunner,
+    sudo: &SudoConfig,
+    nopasswd: bool,
+    install_root: &str,
+) -> Result<()> {
+    info!("Configuring sudoers for wheel group");
+    let content = sudoers_dropin(sudo, nopasswd);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] Would enable wheel group in sudoers");
+        println!("  [dry-run] Would write {}:", SUDOERS_DROPIN);
+        for line in content.lines() {
+            println!("    {}", line);
+        }
         return Ok(());
     }
 
-    // Read current sudoers
-    let content = fs::read_to_string(&sudoers_path).unwrap_or_default();
-
-    // Enable wheel group NOPASSWD (matching original script behavior)
-    // Uncomment: # %wheel ALL=(ALL:ALL) NOPASSWD: ALL
-    let new_content = content
-        .lines()
-        .map(|line| {
-            if line.contains("# %wheel ALL=(ALL:ALL) NOPASSWD: ALL") {
-                "%wheel ALL=(ALL:ALL) NOPASSWD: ALL"
-            } else if line.contains("# %wheel ALL=(ALL) NOPASSWD: ALL") {
-                "%wheel ALL=(ALL) NOPASSWD: ALL"
-            } else {
-                line
-            }
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    fs::write(&sudoers_path, new_content + "\n")?;
+    let path = format!("{}{}", install_root, SUDOERS_DROPIN);
+    fs::create_dir_all(format!("{}/etc/sudoers.d", install_root))?;
+    fs::write(&path, content)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o440))?;
+
+    if let Err(e) = cmd.run_in_chroot(install_root, &format!("visudo -c -f {}", SUDOERS_DROPIN)) {
+        let _ = fs::remove_file(&path);
+        return Err(DeploytixError::ConfigError(format!(
+            "sudoers drop-in rejected by visudo: {}",
+            e
+        )));
+    }
 
     Ok(())
 }
@@ -181,3 +235,57 @@ pub fn lock_root_account(cmd: &CommandRunner, install_root: &str) -> Result<()>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_users_get_seat_groups() {
+        let mut config = DeploymentConfig::sample();
+        config.user.groups = vec!["wheel".to_string(), "video".to_string()];
+        assert_eq!(user_groups(&config), vec!["wheel", "video", "seat"]);
+
+        config.desktop.environment = crate::config::DesktopEnvironment::None;
+        assert_eq!(user_groups(&config), vec!["wheel", "video"]);
+    }
+
+    #[test]
+    fn default_policy_follows_session_switching() {
+        let mut config = DeploymentConfig::sample();
+        config.packages.install_session_switching = false;
+        assert!(!sudo_nopasswd(&config));
+        assert_eq!(
+            sudoers_dropin(&config.sudo, false),
+            "# Generated by Deploytix\n%wheel ALL=(ALL:ALL) ALL\n"
+        );
+
+        config.packages.install_session_switching = true;
+        assert!(sudo_nopasswd(&config));
+        assert_eq!(
+            sudoers_dropin(&config.sudo, true),
+            "# Generated by Deploytix\n%wheel ALL=(ALL:ALL) NOPASSWD: ALL\n"
+        );
+
+        config.sudo.nopasswd = Some(false);
+        assert!(!sudo_nopasswd(&config));
+    }
+
+    #[test]
+    fn restricted_policy_lists_commands_and_timeout() {
+        let sudo = SudoConfig {
+            nopasswd: Some(false),
+            timeout_minutes: Some(0),
+            commands: vec![
+                "/usr/bin/sv restart greetd".to_string(),
+                "/usr/bin/poweroff".to_string(),
+            ],
+        };
+        assert_eq!(
+            sudoers_dropin(&sudo, false),
+            "# Generated by Deploytix\n\
+             Defaults:%wheel timestamp_timeout=0\n\
+             %wheel ALL=(ALL:ALL) /usr/bin/sv restart greetd, /usr/bin/poweroff\n"
+        );
+    }
+}
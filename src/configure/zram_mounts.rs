@@ -0,0 +1,294 @@
+//! zram-backed `/tmp` and `/var/log`
+//!
+//! Installs a `zram-mounts` helper and an init service that mounts
+//! compressed RAM disks over `/tmp` and/or `/var/log` at boot.  `/var/log`
+//! follows the log2ram approach: the on-disk directory is bind-mounted to
+//! `/var/hdd.log`, its contents are copied into the zram device, and changes
+//! are synced back periodically and when the service stops.
+//!
+//! Devices are allocated through `/sys/class/zram-control/hot_add`, so they
+//! never claim `zram0`, which the swap setup in [`crate::configure::swap`]
+//! configures directly.
+
+use crate::config::{DeploymentConfig, InitSystem};
+use crate::configure::services::enable_service;
+use crate::utils::command::CommandRunner;
+use crate::utils::error::Result;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use tracing::info;
+
+/// Service name shared by all init systems.
+const SERVICE_NAME: &str = "zram-mounts";
+
+/// Helper script path on the installed system.
+const SCRIPT_PATH: &str = "/usr/local/bin/zram-mounts";
+
+/// Smallest zram mount that mkfs.ext4 handles comfortably.
+pub const MIN_ZRAM_MOUNT_MIB: u64 = 16;
+
+/// Whether the configuration asks for any zram mount.
+pub fn is_enabled(config: &DeploymentConfig) -> bool {
+    config.disk.zram_tmp_mib > 0 || config.disk.zram_log_mib > 0
+}
+
+/// Generate the `zram-mounts` helper script.
+///
+/// `start`/`stop` mount and tear down the devices, `sync` copies `/var/log`
+/// back to disk, and `run` (used by the supervised services) starts, syncs
+/// every `zram_log_sync_minutes`, and stops cleanly on SIGTERM.
+pub fn generate_script(config: &DeploymentConfig) -> String {
+    let size = |mib: u64| {
+        if mib > 0 {
+            format!("{}M", mib)
+        } else {
+            String::new()
+        }
+    };
+
+    format!(
+        r#"#!/bin/sh
+# zram-backed /tmp and /var/log (generated by Deploytix)
+# zram0 is reserved for ZRAM swap; these devices are hot-added.
+
+ALGORITHM={algorithm}
+TMP_SIZE={tmp_size}
+LOG_SIZE={log_size}
+SYNC_INTERVAL={sync_secs}
+LOG_DISK=/var/hdd.log
+STATE=/run/zram-mounts
+
+zram_add() {{
+    modprobe zram
+    id=$(cat /sys/class/zram-control/hot_add) || return 1
+    echo "$ALGORITHM" > /sys/block/zram$id/comp_algorithm
+    echo "$1" > /sys/block/zram$id/disksize
+    mkfs.ext4 -q -O ^has_journal -m 0 /dev/zram$id || return 1
+    echo "$id"
+}}
+
+zram_remove() {{
+    echo 1 > /sys/block/zram$1/reset
+    echo "$1" > /sys/class/zram-control/hot_remove
+}}
+
+start() {{
+    mkdir -p "$STATE"
+    if [ -n "$TMP_SIZE" ] && [ ! -e "$STATE/tmp" ]; then
+        id=$(zram_add "$TMP_SIZE") || exit 1
+        mount -o nosuid,nodev /dev/zram$id /tmp
+        chmod 1777 /tmp
+        echo "$id" > "$STATE/tmp"
+    fi
+    if [ -n "$LOG_SIZE" ] && [ ! -e "$STATE/log" ]; then
+        id=$(zram_add "$LOG_SIZE") || exit 1
+        mkdir -p "$LOG_DISK"
+        mount --bind /var/log "$LOG_DISK"
+        mount --make-private "$LOG_DISK"
+        mount -o nosuid,nodev,noexec /dev/zram$id /var/log
+        cp -a "$LOG_DISK"/. /var/log/
+        echo "$id" > "$STATE/log"
+    fi
+}}
+
+sync_log() {{
+    [ -e "$STATE/log" ] && cp -au /var/log/. "$LOG_DISK"/
+}}
+
+stop() {{
+    if [ -e "$STATE/log" ]; then
+        sync_log
+        umount -l /var/log
+        umount "$LOG_DISK"
+        zram_remove "$(cat "$STATE/log")"
+        rm -f "$STATE/log"
+    fi
+    if [ -e "$STATE/tmp" ]; then
+        umount -l /tmp
+        zram_remove "$(cat "$STATE/tmp")"
+        rm -f "$STATE/tmp"
+    fi
+}}
+
+run() {{
+    start
+    trap 'stop; exit 0' TERM INT
+    while :; do
+        sleep "$SYNC_INTERVAL" &
+        wait $!
+        sync_log
+    done
+}}
+
+case "$1" in
+    start|stop|run) "$1" ;;
+    sync) sync_log ;;
+    *) echo "usage: $0 start|stop|sync|run" >&2; exit 1 ;;
+esac
+"#,
+        algorithm = config.disk.zram_algorithm,
+        tmp_size = size(config.disk.zram_tmp_mib),
+        log_size = size(config.disk.zram_log_mib),
+        sync_secs = u64::from(config.disk.zram_log_sync_minutes) * 60,
+    )
+}
+
+/// Configure zram-backed `/tmp` and `/var/log` and enable the service.
+pub fn configure_zram_mounts(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    if !is_enabled(config) {
+        return Ok(());
+    }
+
+    let disk = &config.disk;
+    info!(
+        "Configuring zram mounts: /tmp {} MiB, /var/log {} MiB, {} compression",
+        disk.zram_tmp_mib, disk.zram_log_mib, disk.zram_algorithm
+    );
+
+    if cmd.is_dry_run() {
+        if disk.zram_tmp_mib > 0 {
+            println!(
+                "  [dry-run] Would mount a {} MiB zram device ({}) on /tmp",
+                disk.zram_tmp_mib, disk.zram_algorithm
+            );
+        }
+        if disk.zram_log_mib > 0 {
+            println!(
+                "  [dry-run] Would mount a {} MiB zram device ({}) on /var/log, syncing to disk every {} min",
+                disk.zram_log_mib, disk.zram_algorithm, disk.zram_log_sync_minutes
+            );
+        }
+        println!("  [dry-run] Would write {}", SCRIPT_PATH);
+        return enable_service(cmd, &config.system.init, SERVICE_NAME, install_root);
+    }
+
+    let script_path = format!("{}{}", install_root, SCRIPT_PATH);
+    fs::create_dir_all(format!("{}/usr/local/bin", install_root))?;
+    write_executable(&script_path, &generate_script(config))?;
+
+    match config.system.init {
+        InitSystem::Runit => write_runit_service(install_root)?,
+        InitSystem::OpenRC => write_openrc_service(install_root)?,
+        InitSystem::S6 => write_s6_service(install_root)?,
+        InitSystem::Dinit => write_dinit_service(install_root)?,
+    }
+
+    enable_service(cmd, &config.system.init, SERVICE_NAME, install_root)?;
+
+    info!("zram mounts configured");
+    Ok(())
+}
+
+/// runit: supervised `run`; runsv sends TERM on shutdown, which syncs.
+fn write_runit_service(install_root: &str) -> Result<()> {
+    let sv_dir = format!("{}/etc/runit/sv/{}", install_root, SERVICE_NAME);
+    fs::create_dir_all(&sv_dir)?;
+    write_executable(
+        &format!("{}/run", sv_dir),
+        &format!("#!/bin/sh\nexec 2>&1\nexec {} run\n", SCRIPT_PATH),
+    )
+}
+
+/// OpenRC: backgrounded `run`, mounted before the system logger starts.
+fn write_openrc_service(install_root: &str) -> Result<()> {
+    let init_dir = format!("{}/etc/init.d", install_root);
+    fs::create_dir_all(&init_dir)?;
+
+    let script = format!(
+        r#"#!/sbin/openrc-run
+
+description="zram-backed /tmp and /var/log"
+command="{script}"
+command_args="run"
+command_background="yes"
+pidfile="/run/${{RC_SVCNAME}}.pid"
+
+depend() {{
+    need localmount
+    before logger
+}}
+"#,
+        script = SCRIPT_PATH
+    );
+
+    write_executable(&format!("{}/{}", init_dir, SERVICE_NAME), &script)
+}
+
+/// s6: longrun in the `-srv` directory the service enabler expects.
+fn write_s6_service(install_root: &str) -> Result<()> {
+    let sv_dir = format!("{}/etc/s6/sv/{}-srv", install_root, SERVICE_NAME);
+    let deps_dir = format!("{}/dependencies.d", sv_dir);
+    fs::create_dir_all(&deps_dir)?;
+    fs::write(format!("{}/mount-filesystems", deps_dir), "")?;
+
+    fs::write(format!("{}/type", sv_dir), "longrun\n")?;
+    write_executable(
+        &format!("{}/run", sv_dir),
+        &format!(
+            "#!/usr/bin/execlineb -P\nfdmove -c 2 1\n{} run\n",
+            SCRIPT_PATH
+        ),
+    )
+}
+
+/// dinit: supervised process started once local filesystems are up.
+fn write_dinit_service(install_root: &str) -> Result<()> {
+    let dinit_dir = format!("{}/etc/dinit.d", install_root);
+    fs::create_dir_all(&dinit_dir)?;
+
+    let service = format!(
+        "type = process\ncommand = {} run\ndepends-on = local.target\n",
+        SCRIPT_PATH
+    );
+    fs::write(format!("{}/{}", dinit_dir, SERVICE_NAME), service)?;
+    Ok(())
+}
+
+fn write_executable(path: &str, content: &str) -> Result<()> {
+    fs::write(path, content)?;
+    let mut perms = fs::metadata(path)?.permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(path, perms)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(tmp: u64, log: u64) -> DeploymentConfig {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.disk.zram_tmp_mib = tmp;
+        cfg.disk.zram_log_mib = log;
+        cfg
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        assert!(!is_enabled(&DeploymentConfig::sample()));
+        assert!(is_enabled(&config(0, 256)));
+    }
+
+    #[test]
+    fn script_sizes_and_sync_interval() {
+        let mut cfg = config(0, 256);
+        cfg.disk.zram_log_sync_minutes = 15;
+        let script = generate_script(&cfg);
+        assert!(script.contains("TMP_SIZE=\n"));
+        assert!(script.contains("LOG_SIZE=256M\n"));
+        assert!(script.contains("SYNC_INTERVAL=900\n"));
+        assert!(script.contains("ALGORITHM=zstd\n"));
+    }
+
+    #[test]
+    fn script_never_claims_swap_device() {
+        let script = generate_script(&config(512, 256));
+        assert!(script.contains("/sys/class/zram-control/hot_add"));
+        assert!(!script.contains("/dev/zram0"));
+        assert!(!script.contains("/sys/block/zram0"));
+    }
+}
@@ -1,6 +1,6 @@
 //! GNOME desktop environment installer
 
-use crate::config::DeploymentConfig;
+use crate::config::{DeploymentConfig, DesktopProfile};
 use crate::utils::command::CommandRunner;
 use crate::utils::error::Result;
 use std::fs;
@@ -9,20 +9,40 @@ use tracing::info;
 /// GNOME packages (display manager handled centrally via desktop.display_manager)
 const GNOME_PACKAGES: &[&str] = &["gnome", "gnome-extra"];
 
+/// Minimal GNOME: the shell, settings, a terminal, and a file manager
+const GNOME_MINIMAL_PACKAGES: &[&str] = &[
+    "gnome-shell",
+    "gnome-control-center",
+    "gnome-console",
+    "nautilus",
+    "gnome-keyring",
+    "xdg-desktop-portal-gnome",
+];
+
+/// Package list for the selected desktop profile.
+pub fn packages_for(profile: DesktopProfile) -> &'static [&'static str] {
+    match profile {
+        DesktopProfile::Minimal => GNOME_MINIMAL_PACKAGES,
+        DesktopProfile::Full => GNOME_PACKAGES,
+    }
+}
+
 /// Install GNOME desktop environment
 pub fn install(cmd: &CommandRunner, config: &DeploymentConfig, install_root: &str) -> Result<()> {
-    info!("Installing GNOME desktop environment");
+    let profile = config.desktop.profile;
+    info!(
+        "Installing GNOME desktop environment ({:?} profile)",
+        profile
+    );
+    let packages = packages_for(profile);
 
     if cmd.is_dry_run() {
-        println!(
-            "  [dry-run] Would install GNOME packages: {:?}",
-            GNOME_PACKAGES
-        );
+        println!("  [dry-run] Would install GNOME packages: {:?}", packages);
         return Ok(());
     }
 
     // Install packages
-    let pkg_list = GNOME_PACKAGES.join(" ");
+    let pkg_list = packages.join(" ");
     let install_cmd = format!("pacman -S --noconfirm {}", pkg_list);
     crate::configure::packages::pacman_install_chroot(cmd, install_root, &install_cmd)?;
 
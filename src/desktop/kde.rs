@@ -1,6 +1,6 @@
 //! KDE Plasma desktop environment installer
 
-use crate::config::{DeploymentConfig, InitSystem};
+use crate::config::{DeploymentConfig, DesktopProfile, InitSystem};
 use crate::utils::command::CommandRunner;
 use crate::utils::error::Result;
 use std::fs;
@@ -32,26 +32,53 @@ const KDE_PACKAGES: &[&str] = &[
     "kate",
 ];
 
+/// Minimal KDE Plasma: the shell, a terminal, a file manager, and the
+/// audio/display/portal glue needed for a usable session.
+const KDE_MINIMAL_PACKAGES: &[&str] = &[
+    "plasma-desktop",
+    "konsole",
+    "dolphin",
+    "plasma-pa",
+    "kscreen",
+    "xdg-desktop-portal-kde",
+];
+
+/// Package list for the selected desktop profile.
+pub fn packages_for(profile: DesktopProfile) -> &'static [&'static str] {
+    match profile {
+        DesktopProfile::Minimal => KDE_MINIMAL_PACKAGES,
+        DesktopProfile::Full => KDE_PACKAGES,
+    }
+}
+
 /// s6-specific service packages for KDE
 const KDE_S6_PACKAGES: &[&str] = &["bluez-s6", "power-profiles-daemon-s6"];
 
 /// Install KDE Plasma desktop environment
 pub fn install(cmd: &CommandRunner, config: &DeploymentConfig, install_root: &str) -> Result<()> {
-    info!("Installing KDE Plasma desktop environment");
+    let profile = config.desktop.profile;
+    info!(
+        "Installing KDE Plasma desktop environment ({:?} profile)",
+        profile
+    );
 
     // Display manager packages and configuration are handled centrally
     // (configure::services / configure::display_manager) based on
     // desktop.display_manager.
-    let mut packages: Vec<&str> = KDE_PACKAGES.to_vec();
+    let mut packages: Vec<&str> = packages_for(profile).to_vec();
+
+    // bluez and power-profiles-daemon are only part of the full profile,
+    // so their init service packages are too.
+    let with_services = profile == DesktopProfile::Full;
 
     // Add s6-specific service packages
-    if config.system.init == InitSystem::S6 {
+    if with_services && config.system.init == InitSystem::S6 {
         packages.extend(KDE_S6_PACKAGES);
     }
 
     if cmd.is_dry_run() {
         println!("  [dry-run] Would install KDE packages: {:?}", packages);
-        if config.system.init == InitSystem::S6 {
+        if with_services && config.system.init == InitSystem::S6 {
             println!(
                 "  [dry-run] Would install s6 service packages: {:?}",
                 KDE_S6_PACKAGES
@@ -63,7 +90,7 @@ pub fn install(cmd: &CommandRunner, config: &DeploymentConfig, install_root: &st
     // Build the full package list (including init-specific service
     // pkgs) up front so we can preflight resolution before pacman runs.
     let mut all_pkgs: Vec<String> = packages.iter().map(|s| (*s).to_string()).collect();
-    if config.system.init != InitSystem::S6 {
+    if with_services && config.system.init != InitSystem::S6 {
         all_pkgs.push(format!("bluez-{}", config.system.init));
         all_pkgs.push(format!("power-profiles-daemon-{}", config.system.init));
     }
@@ -1,6 +1,6 @@
 //! XFCE desktop environment installer
 
-use crate::config::DeploymentConfig;
+use crate::config::{DeploymentConfig, DesktopProfile};
 use crate::utils::command::CommandRunner;
 use crate::utils::error::Result;
 use std::fs;
@@ -9,20 +9,41 @@ use tracing::info;
 /// XFCE packages (display manager handled centrally via desktop.display_manager)
 const XFCE_PACKAGES: &[&str] = &["xfce4", "xfce4-goodies"];
 
+/// Minimal XFCE: session, window manager, panel, desktop, terminal, Thunar
+const XFCE_MINIMAL_PACKAGES: &[&str] = &[
+    "xfce4-session",
+    "xfwm4",
+    "xfce4-panel",
+    "xfdesktop",
+    "xfce4-settings",
+    "xfce4-terminal",
+    "thunar",
+];
+
+/// Package list for the selected desktop profile.
+pub fn packages_for(profile: DesktopProfile) -> &'static [&'static str] {
+    match profile {
+        DesktopProfile::Minimal => XFCE_MINIMAL_PACKAGES,
+        DesktopProfile::Full => XFCE_PACKAGES,
+    }
+}
+
 /// Install XFCE desktop environment
 pub fn install(cmd: &CommandRunner, config: &DeploymentConfig, install_root: &str) -> Result<()> {
-    info!("Installing XFCE desktop environment");
+    let profile = config.desktop.profile;
+    info!(
+        "Installing XFCE desktop environment ({:?} profile)",
+        profile
+    );
+    let packages = packages_for(profile);
 
     if cmd.is_dry_run() {
-        println!(
-            "  [dry-run] Would install XFCE packages: {:?}",
-            XFCE_PACKAGES
-        );
+        println!("  [dry-run] Would install XFCE packages: {:?}", packages);
         return Ok(());
     }
 
     // Install packages
-    let pkg_list = XFCE_PACKAGES.join(" ");
+    let pkg_list = packages.join(" ");
     let install_cmd = format!("pacman -S --noconfirm {}", pkg_list);
     crate::configure::packages::pacman_install_chroot(cmd, install_root, &install_cmd)?;
 
@@ -0,0 +1,464 @@
+//! Encryption and filesystem throughput benchmark
+//!
+//! Measures what integrity and cipher choices cost before committing to
+//! them: `cryptsetup benchmark` for the candidate ciphers (in memory), then
+//! a direct-I/O sequential write on each filesystem, plain and under LUKS2
+//! with and without dm-integrity, on a scratch device or loopback file.
+
+use crate::config::{Filesystem, FilesystemOptions};
+use crate::configure::keyfiles::generate_keyfile;
+use crate::disk::detection::{is_running_system_disk, probe_existing_data};
+use crate::disk::formatting::format_partition;
+use crate::disk::image::{attach_loop, detach_loop};
+use crate::resources::runtime_path;
+use crate::utils::command::CommandRunner;
+use crate::utils::deps::optional_binary_available;
+use crate::utils::error::{DeploytixError, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Cipher / key size pairs compared by `cryptsetup benchmark`.
+const CANDIDATE_CIPHERS: &[(&str, u32)] = &[
+    ("aes-xts-plain64", 256),
+    ("aes-xts-plain64", 512),
+    ("serpent-xts-plain64", 512),
+    ("twofish-xts-plain64", 512),
+    ("xchacha20,aes-adiantum-plain64", 256),
+];
+
+/// Filesystems written to directly on the scratch device.
+const PLAIN_FILESYSTEMS: &[Filesystem] = &[
+    Filesystem::Ext4,
+    Filesystem::Xfs,
+    Filesystem::Btrfs,
+    Filesystem::F2fs,
+];
+
+/// Mapper name of the LUKS container opened on the scratch device.
+const BENCH_MAPPER: &str = "deploytix-bench";
+
+/// Benchmark settings
+#[derive(Debug, Clone)]
+pub struct BenchmarkOptions {
+    /// Scratch block device, or a file path used through a loop device
+    pub target: String,
+    /// Size of the loopback file created when `target` does not exist
+    pub file_size_mib: u64,
+    /// Data written per filesystem run
+    pub write_mib: u64,
+    /// Use a target that holds existing data
+    pub force: bool,
+}
+
+/// How the data filesystem is layered on the scratch device
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layer {
+    Plain,
+    Luks,
+    LuksIntegrity,
+}
+
+impl std::fmt::Display for Layer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plain => write!(f, "plain"),
+            Self::Luks => write!(f, "LUKS2"),
+            Self::LuksIntegrity => write!(f, "LUKS2 + integrity"),
+        }
+    }
+}
+
+/// One `cryptsetup benchmark` result
+#[derive(Debug, Clone, PartialEq)]
+pub struct CipherResult {
+    pub cipher: String,
+    pub key_bits: u32,
+    pub encrypt_mib_s: f64,
+    pub decrypt_mib_s: f64,
+}
+
+/// Parse the cipher lines of `cryptsetup benchmark` output, e.g.
+/// `aes-xts   512b  2345.6 MiB/s  2401.2 MiB/s`.
+pub fn parse_cryptsetup_benchmark(output: &str) -> Vec<CipherResult> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // cipher, key size, encrypt, unit, decrypt, unit
+            if fields.len() != 6 || fields[3] != "MiB/s" || fields[5] != "MiB/s" {
+                return None;
+            }
+            Some(CipherResult {
+                cipher: fields[0].to_string(),
+                key_bits: fields[1].strip_suffix('b')?.parse().ok()?,
+                encrypt_mib_s: fields[2].parse().ok()?,
+                decrypt_mib_s: fields[4].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Throughput of writing `mib` MiB in `elapsed`.
+fn throughput_mib_s(mib: u64, elapsed: Duration) -> f64 {
+    mib as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+/// Run `cryptsetup benchmark` for each candidate cipher.
+fn benchmark_ciphers(cmd: &CommandRunner) -> Vec<CipherResult> {
+    let mut results = Vec::new();
+    for (cipher, key_bits) in CANDIDATE_CIPHERS {
+        let key_size = key_bits.to_string();
+        match cmd.run(
+            "cryptsetup",
+            &["benchmark", "--cipher", cipher, "--key-size", &key_size],
+        ) {
+            Ok(Some(output)) => {
+                results.extend(parse_cryptsetup_benchmark(&String::from_utf8_lossy(
+                    &output.stdout,
+                )));
+            }
+            Ok(None) => {}
+            Err(e) => warn!("cryptsetup benchmark {} failed: {}", cipher, e),
+        }
+    }
+    results
+}
+
+/// Reasons `device` must not be used as scratch space.  Mounted nodes and
+/// the running system's disk are refused even with `force`.
+fn check_scratch_device(device: &str, force: bool) -> Result<()> {
+    if let Some(disk) = is_running_system_disk(device) {
+        return Err(DeploytixError::RunningSystemDisk(disk));
+    }
+    let existing = probe_existing_data(device)?;
+    if let Some(node) = existing.iter().find(|n| n.is_mounted()) {
+        return Err(DeploytixError::DeviceMounted(node.path.clone()));
+    }
+    if !existing.is_empty() && !force {
+        let nodes: Vec<String> = existing
+            .iter()
+            .map(|n| {
+                format!(
+                    "{} ({})",
+                    n.path,
+                    n.fstype.as_deref().unwrap_or("partition")
+                )
+            })
+            .collect();
+        return Err(DeploytixError::ValidationError(format!(
+            "{} holds existing data: {}; pass --force to overwrite it",
+            device,
+            nodes.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Scratch device for the run; detaches the loop device it attached and
+/// removes the file it created.
+struct Scratch {
+    device: String,
+    loop_file: Option<String>,
+    created_file: bool,
+}
+
+impl Scratch {
+    fn open(cmd: &CommandRunner, opts: &BenchmarkOptions) -> Result<Self> {
+        let path = Path::new(&opts.target);
+        if path.starts_with("/dev") {
+            check_scratch_device(&opts.target, opts.force)?;
+            return Ok(Self {
+                device: opts.target.clone(),
+                loop_file: None,
+                created_file: false,
+            });
+        }
+
+        let created_file = !path.exists();
+        if !created_file && fs::metadata(path)?.len() > 0 && !opts.force {
+            return Err(DeploytixError::ValidationError(format!(
+                "{} already exists; pass --force to overwrite it or name a new file",
+                opts.target
+            )));
+        }
+        if created_file {
+            let file = fs::File::create(path)?;
+            file.set_len(opts.file_size_mib * 1024 * 1024)?;
+        }
+        let output = attach_loop(cmd, &opts.target, false)?;
+        info!("Benchmarking on {} backed by {}", output, opts.target);
+        Ok(Self {
+            device: output,
+            loop_file: Some(opts.target.clone()),
+            created_file,
+        })
+    }
+
+    fn close(self, cmd: &CommandRunner) {
+        if let Some(ref file) = self.loop_file {
+            detach_loop(cmd, &self.device);
+            if self.created_file {
+                let _ = fs::remove_file(file);
+            }
+        }
+    }
+}
+
+/// Format `device` as `layer` + `filesystem`, write `write_mib` MiB with
+/// direct I/O and return the throughput.
+fn benchmark_filesystem(
+    cmd: &CommandRunner,
+    device: &str,
+    filesystem: &Filesystem,
+    layer: Layer,
+    write_mib: u64,
+) -> Result<f64> {
+    let _ = cmd.run("wipefs", &["-a", device]);
+    let key_path = runtime_path("benchmark.key");
+    let key_file = key_path.to_string_lossy();
+
+    // Remove the key on every return path, including the `?` ones below
+    struct KeyGuard(PathBuf);
+    impl Drop for KeyGuard {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    let mut _key_guard = None;
+    let data_device = if layer == Layer::Plain {
+        device.to_string()
+    } else {
+        _key_guard = Some(KeyGuard(key_path.clone()));
+        generate_keyfile(cmd, &key_file, 64)?;
+        let mut args = vec![
+            "luksFormat",
+            "--batch-mode",
+            "--type",
+            "luks2",
+            "--key-file",
+            &key_file,
+            // Unlock speed is irrelevant to throughput
+            "--pbkdf",
+            "pbkdf2",
+            "--pbkdf-force-iterations",
+            "1000",
+        ];
+        if layer == Layer::LuksIntegrity {
+            args.extend([
+                "--integrity",
+                "hmac-sha256",
+                "--integrity-no-wipe",
+                "--sector-size",
+                "4096",
+            ]);
+        }
+        args.push(device);
+        cmd.run("cryptsetup", &args)?;
+        cmd.run(
+            "cryptsetup",
+            &["open", "--key-file", &key_file, device, BENCH_MAPPER],
+        )?;
+        format!("/dev/mapper/{}", BENCH_MAPPER)
+    };
+
+    let mount_point = runtime_path("benchmark");
+    let mount_point = mount_point.to_string_lossy().into_owned();
+    let result = (|| {
+        format_partition(
+            cmd,
+            &data_device,
+            filesystem,
+            None,
+            false,
+            None,
+            false,
+            &FilesystemOptions::default(),
+        )?;
+        fs::create_dir_all(&mount_point)?;
+        cmd.run("mount", &[&data_device, &mount_point])?;
+        let of = format!("of={}/deploytix-bench", mount_point);
+        let count = format!("count={}", write_mib);
+        let started = Instant::now();
+        let written = cmd.run(
+            "dd",
+            &[
+                "if=/dev/zero",
+                &of,
+                "bs=1M",
+                &count,
+                "oflag=direct",
+                "conv=fsync",
+                "status=none",
+            ],
+        );
+        let elapsed = started.elapsed();
+        let _ = cmd.run("umount", &[&mount_point]);
+        written?;
+        Ok(throughput_mib_s(write_mib, elapsed))
+    })();
+
+    if layer != Layer::Plain {
+        let _ = cmd.run("cryptsetup", &["close", BENCH_MAPPER]);
+    }
+    result
+}
+
+/// Outcome of one filesystem run
+struct FilesystemRow {
+    filesystem: Filesystem,
+    layer: Layer,
+    result: std::result::Result<f64, String>,
+}
+
+/// Comparison table of the cipher and filesystem results.
+fn format_report(ciphers: &[CipherResult], rows: &[FilesystemRow], write_mib: u64) -> String {
+    let mut out = String::new();
+    out.push_str("Ciphers (cryptsetup benchmark, in memory)\n");
+    out.push_str(&format!(
+        "  {:<34} {:>5} {:>14} {:>14}\n",
+        "cipher", "key", "encrypt", "decrypt"
+    ));
+    for c in ciphers {
+        out.push_str(&format!(
+            "  {:<34} {:>4}b {:>8.1} MiB/s {:>8.1} MiB/s\n",
+            c.cipher, c.key_bits, c.encrypt_mib_s, c.decrypt_mib_s
+        ));
+    }
+    if ciphers.is_empty() {
+        out.push_str("  (no results)\n");
+    }
+
+    out.push_str(&format!(
+        "\nSequential write ({} MiB, direct I/O)\n",
+        write_mib
+    ));
+    let baseline = rows
+        .iter()
+        .find(|r| r.layer == Layer::Plain && r.filesystem == Filesystem::Ext4)
+        .and_then(|r| r.result.as_ref().ok().copied());
+    for row in rows {
+        let name = format!("{} ({})", row.filesystem, row.layer);
+        match (&row.result, baseline) {
+            (Ok(speed), Some(base)) if base > 0.0 => out.push_str(&format!(
+                "  {:<28} {:>8.1} MiB/s  {:>4.0}% of plain ext4\n",
+                name,
+                speed,
+                speed / base * 100.0
+            )),
+            (Ok(speed), _) => out.push_str(&format!("  {:<28} {:>8.1} MiB/s\n", name, speed)),
+            (Err(reason), _) => out.push_str(&format!("  {:<28} {}\n", name, reason)),
+        }
+    }
+    out
+}
+
+/// Run the benchmark and print the comparison table.
+pub fn run_benchmark(cmd: &CommandRunner, opts: &BenchmarkOptions) -> Result<()> {
+    if opts.write_mib == 0 {
+        return Err(DeploytixError::ValidationError(
+            "write size must be at least 1 MiB".to_string(),
+        ));
+    }
+    if opts.file_size_mib == 0 {
+        return Err(DeploytixError::ValidationError(
+            "loopback file size must be at least 1 MiB".to_string(),
+        ));
+    }
+    let ciphers = benchmark_ciphers(cmd);
+
+    let scratch = Scratch::open(cmd, opts)?;
+    let cases = PLAIN_FILESYSTEMS
+        .iter()
+        .map(|fs| (fs.clone(), Layer::Plain))
+        .chain([
+            (Filesystem::Ext4, Layer::Luks),
+            (Filesystem::Ext4, Layer::LuksIntegrity),
+        ]);
+    let mut rows = Vec::new();
+    for (filesystem, layer) in cases {
+        let mkfs = format!("mkfs.{}", filesystem);
+        let result = if !optional_binary_available(&mkfs) {
+            Err(format!("skipped ({} not installed)", mkfs))
+        } else {
+            info!("Benchmarking {} ({})", filesystem, layer);
+            benchmark_filesystem(cmd, &scratch.device, &filesystem, layer, opts.write_mib)
+                .map_err(|e| format!("failed: {}", e))
+        };
+        rows.push(FilesystemRow {
+            filesystem,
+            layer,
+            result,
+        });
+    }
+    let _ = cmd.run("wipefs", &["-a", &scratch.device]);
+    scratch.close(cmd);
+
+    print!("{}", format_report(&ciphers, &rows, opts.write_mib));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_file_size_is_rejected() {
+        let opts = BenchmarkOptions {
+            target: "/tmp/deploytix-bench.img".to_string(),
+            file_size_mib: 0,
+            write_mib: 64,
+            force: false,
+        };
+        assert!(matches!(
+            run_benchmark(&CommandRunner::new(true), &opts),
+            Err(DeploytixError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn cryptsetup_cipher_lines_are_parsed() {
+        let output = "\
+# Tests are approximate using memory only (no storage IO).
+PBKDF2-sha1      1234567 iterations per second for 256-bit key
+#     Algorithm |       Key |      Encryption |      Decryption
+        aes-xts        512b      2345.6 MiB/s      2401.2 MiB/s
+";
+        assert_eq!(
+            parse_cryptsetup_benchmark(output),
+            vec![CipherResult {
+                cipher: "aes-xts".to_string(),
+                key_bits: 512,
+                encrypt_mib_s: 2345.6,
+                decrypt_mib_s: 2401.2,
+            }]
+        );
+    }
+
+    #[test]
+    fn report_compares_against_plain_ext4() {
+        let rows = vec![
+            FilesystemRow {
+                filesystem: Filesystem::Ext4,
+                layer: Layer::Plain,
+                result: Ok(400.0),
+            },
+            FilesystemRow {
+                filesystem: Filesystem::Ext4,
+                layer: Layer::LuksIntegrity,
+                result: Ok(100.0),
+            },
+            FilesystemRow {
+                filesystem: Filesystem::F2fs,
+                layer: Layer::Plain,
+                result: Err("skipped (mkfs.f2fs not installed)".to_string()),
+            },
+        ];
+        let report = format_report(&[], &rows, 256);
+        assert!(report.contains("ext4 (LUKS2 + integrity)"));
+        assert!(report.contains("25% of plain ext4"));
+        assert!(report.contains("skipped (mkfs.f2fs not installed)"));
+    }
+}
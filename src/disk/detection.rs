@@ -1,8 +1,12 @@
 //! Disk detection and enumeration
 
-use crate::utils::error::Result;
+use crate::utils::error::{DeploytixError, Result};
 use std::fs;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 /// Information about a block device
 #[derive(Debug, Clone)]
@@ -22,9 +26,36 @@ pub struct BlockDevice {
     pub removable: bool,
     /// Whether device is read-only
     pub read_only: bool,
+    /// Whether device is rotational (HDD) rather than flash (SSD/NVMe/MMC)
+    pub is_rotational: bool,
+    /// Logical sector size in bytes (512, or 4096 on 4Kn disks)
+    pub logical_sector_size: u64,
+    /// Physical sector size in bytes
+    pub physical_sector_size: u64,
 }
 
 impl BlockDevice {
+    /// A flash disk of `size_mib` MiB with 512-byte sectors, for planning
+    /// without the device present.
+    pub fn simulated(path: &str, size_mib: u64) -> Self {
+        let name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self {
+            path: path.to_string(),
+            device_type: determine_device_type(&name),
+            name,
+            size_bytes: size_mib * 1024 * 1024,
+            model: Some("simulated".to_string()),
+            removable: false,
+            read_only: false,
+            is_rotational: false,
+            logical_sector_size: 512,
+            physical_sector_size: 512,
+        }
+    }
+
     /// Get human-readable size
     pub fn size_human(&self) -> String {
         const KIB: u64 = 1024;
@@ -49,6 +80,15 @@ impl BlockDevice {
     pub fn size_mib(&self) -> u64 {
         self.size_bytes / (1024 * 1024)
     }
+
+    /// Storage media kind for display ("HDD" or "SSD")
+    pub fn media_kind(&self) -> &'static str {
+        if self.is_rotational {
+            "HDD"
+        } else {
+            "SSD"
+        }
+    }
 }
 
 /// Read a sysfs attribute, returning None if not available
@@ -62,6 +102,25 @@ fn read_sysfs_u64(device: &str, attr: &str) -> Option<u64> {
     read_sysfs_attr(device, attr).and_then(|s| s.parse().ok())
 }
 
+/// Read `queue/rotational` for a device.
+///
+/// Defaults to rotational when the attribute is unavailable, so SSD-only
+/// tuning (discard, `ssd` mount option) is never applied to an unknown disk.
+fn is_rotational(device: &str) -> bool {
+    read_sysfs_u64(device, "queue/rotational").unwrap_or(1) != 0
+}
+
+/// Read the logical and physical sector sizes of a device.
+///
+/// `/sys/block/<dev>/size` is always in 512-byte units, but sfdisk scripts
+/// count in logical sectors, which are 4096 bytes on 4Kn disks.  Falls
+/// back to 512 when the attributes are unavailable.
+fn sector_sizes(device: &str) -> (u64, u64) {
+    let logical = read_sysfs_u64(device, "queue/logical_block_size").unwrap_or(512);
+    let physical = read_sysfs_u64(device, "queue/physical_block_size").unwrap_or(logical);
+    (logical, physical.max(logical))
+}
+
 /// Determine device type from sysfs
 fn determine_device_type(device: &str) -> String {
     // Check if NVMe
@@ -118,24 +177,75 @@ fn is_physical_disk(name: &str) -> bool {
 /// Matches both the whole-disk device (e.g. `/dev/sda`) and any partition
 /// derived from it (e.g. `/dev/sda1`, `/dev/nvme0n1p2`).
 fn is_device_mounted(device: &str) -> bool {
-    let prefix = partition_prefix(device);
     let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
     mounts.lines().any(|line| {
         if let Some(dev) = line.split_whitespace().next() {
-            dev == device || dev.starts_with(&prefix)
+            dev == device || is_partition_of(dev, device)
         } else {
             false
         }
     })
 }
 
+/// Whether `path` names a partition of `device` (`/dev/sda2` of `/dev/sda`,
+/// but not `/dev/sdaa1`).
+pub fn is_partition_of(path: &str, device: &str) -> bool {
+    path.strip_prefix(&partition_prefix(device))
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
 /// List available block devices
 ///
 /// If `all` is false, filters to only show suitable installation targets:
 /// physical whole-disks (SCSI/SATA/USB, NVMe, MMC/SD, virtio, Xen, IDE).
 /// Device-mapper devices (dm-crypt, LVM), ZRAM, loop, software RAID, optical
 /// drives, and other virtual block devices are excluded.
+///
+/// Always scans; the result also refreshes the cache behind
+/// [`list_block_devices_cached`].
 pub fn list_block_devices(all: bool) -> Result<Vec<BlockDevice>> {
+    let scans = DEVICE_SCANS.fetch_add(1, Ordering::Relaxed) + 1;
+    debug!("Scanning block devices (scan #{} this run)", scans);
+
+    let devices = scan_block_devices(all)?;
+    DEVICE_CACHE.lock().unwrap()[usize::from(all)] = Some((Instant::now(), devices.clone()));
+    Ok(devices)
+}
+
+/// How long [`list_block_devices_cached`] reuses a scan.
+const DEVICE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// A scan and when it was made.
+type CachedScan = Option<(Instant, Vec<BlockDevice>)>;
+
+/// Last scan per `all` flag (index 0: targets only, 1: all devices).
+static DEVICE_CACHE: Mutex<[CachedScan; 2]> = Mutex::new([None, None]);
+
+/// Number of block device scans this run, for `--verbose` logging.
+static DEVICE_SCANS: AtomicUsize = AtomicUsize::new(0);
+
+/// [`list_block_devices`], reusing a scan made in the last few seconds so
+/// that the wizard, validation and cleanup steps of one operation share it.
+pub fn list_block_devices_cached(all: bool) -> Result<Vec<BlockDevice>> {
+    let cached = fresh_scan(
+        &DEVICE_CACHE.lock().unwrap()[usize::from(all)],
+        Instant::now(),
+    );
+    match cached {
+        Some(devices) => Ok(devices),
+        None => list_block_devices(all),
+    }
+}
+
+/// The devices in `scan` when it was made less than [`DEVICE_CACHE_TTL`]
+/// before `now`.
+fn fresh_scan(scan: &CachedScan, now: Instant) -> Option<Vec<BlockDevice>> {
+    scan.as_ref()
+        .filter(|(at, _)| now.saturating_duration_since(*at) < DEVICE_CACHE_TTL)
+        .map(|(_, devices)| devices.clone())
+}
+
+fn scan_block_devices(all: bool) -> Result<Vec<BlockDevice>> {
     let mut devices = Vec::new();
 
     for entry in fs::read_dir("/sys/block")? {
@@ -175,6 +285,8 @@ pub fn list_block_devices(all: bool) -> Result<Vec<BlockDevice>> {
 
         let removable = read_sysfs_u64(&name, "removable").unwrap_or(0) == 1;
         let read_only = read_sysfs_u64(&name, "ro").unwrap_or(0) == 1;
+        let is_rotational = is_rotational(&name);
+        let (logical_sector_size, physical_sector_size) = sector_sizes(&name);
 
         // Skip read-only devices unless showing all
         if !all && read_only {
@@ -200,6 +312,9 @@ pub fn list_block_devices(all: bool) -> Result<Vec<BlockDevice>> {
             device_type,
             removable,
             read_only,
+            is_rotational,
+            logical_sector_size,
+            physical_sector_size,
         });
     }
 
@@ -228,8 +343,12 @@ pub fn get_device_info(device_path: &str) -> Result<BlockDevice> {
 
     let removable = read_sysfs_u64(&name, "removable").unwrap_or(0) == 1;
     let read_only = read_sysfs_u64(&name, "ro").unwrap_or(0) == 1;
-    let model =
-        read_sysfs_attr(&name, "device/model").or_else(|| read_sysfs_attr(&name, "device/name"));
+    let is_rotational = is_rotational(&name);
+    let (logical_sector_size, physical_sector_size) = sector_sizes(&name);
+    // Loop devices have no model; name the image they are backed by
+    let model = read_sysfs_attr(&name, "device/model")
+        .or_else(|| read_sysfs_attr(&name, "device/name"))
+        .or_else(|| read_sysfs_attr(&name, "loop/backing_file"));
 
     Ok(BlockDevice {
         path: device_path.to_string(),
@@ -239,9 +358,285 @@ pub fn get_device_info(device_path: &str) -> Result<BlockDevice> {
         device_type,
         removable,
         read_only,
+        is_rotational,
+        logical_sector_size,
+        physical_sector_size,
     })
 }
 
+/// A partition, LUKS container, or other node found on a disk before it is
+/// wiped, as reported by `lsblk`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExistingData {
+    /// Device path (e.g., /dev/sda2, /dev/mapper/cryptroot)
+    pub path: String,
+    /// Filesystem or signature type (ext4, btrfs, crypto_LUKS, swap, ...)
+    pub fstype: Option<String>,
+    /// Filesystem label
+    pub label: Option<String>,
+    /// Current mountpoint (`[SWAP]` for active swap)
+    pub mountpoint: Option<String>,
+}
+
+impl ExistingData {
+    /// Whether this node is in use by the running system.
+    pub fn is_mounted(&self) -> bool {
+        self.mountpoint.is_some()
+    }
+
+    /// Whether this node is the running system's root filesystem.
+    pub fn is_root(&self) -> bool {
+        self.mountpoint.as_deref() == Some("/")
+    }
+}
+
+/// Parse `lsblk -P -p -o NAME,FSTYPE,LABEL,MOUNTPOINT` output.
+///
+/// Each line is a set of `KEY="value"` pairs.  The whole-disk line is kept
+/// only when it carries a signature itself (e.g. a partitionless LUKS disk)
+/// or is mounted.
+pub fn parse_lsblk_pairs(output: &str, device: &str) -> Vec<ExistingData> {
+    let mut entries = Vec::new();
+
+    for line in output.lines() {
+        let mut fields = std::collections::HashMap::new();
+        let mut rest = line.trim();
+        while let Some(eq) = rest.find("=\"") {
+            let key = rest[..eq].trim();
+            let after = &rest[eq + 2..];
+            let Some(end) = after.find('"') else { break };
+            fields.insert(key, &after[..end]);
+            rest = &after[end + 1..];
+        }
+
+        let non_empty = |key: &str| {
+            fields
+                .get(key)
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+        };
+        let Some(path) = non_empty("NAME") else {
+            continue;
+        };
+        let entry = ExistingData {
+            path,
+            fstype: non_empty("FSTYPE"),
+            label: non_empty("LABEL"),
+            mountpoint: non_empty("MOUNTPOINT"),
+        };
+
+        if entry.path == device && entry.fstype.is_none() && !entry.is_mounted() {
+            continue;
+        }
+        entries.push(entry);
+    }
+
+    entries
+}
+
+/// Probe a disk for existing filesystems, LUKS containers, and mounts.
+///
+/// Read-only: runs `lsblk` on the device and all of its children (including
+/// opened dm-crypt/LVM nodes), so it is safe to call in dry-run mode.
+pub fn probe_existing_data(device: &str) -> Result<Vec<ExistingData>> {
+    let output = std::process::Command::new("lsblk")
+        .args(["-P", "-p", "-o", "NAME,FSTYPE,LABEL,MOUNTPOINT", device])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(crate::utils::error::DeploytixError::CommandFailed {
+            command: format!("lsblk {}", device),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+
+    Ok(parse_lsblk_pairs(
+        &String::from_utf8_lossy(&output.stdout),
+        device,
+    ))
+}
+
+/// SMART health summary for a disk, from `smartctl -H -A`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmartReport {
+    /// Overall self-assessment passed (`PASSED` / `OK`)
+    pub passed: bool,
+    /// Raw value of attribute 5 (`Reallocated_Sector_Ct`), when reported
+    pub reallocated_sectors: Option<u64>,
+}
+
+impl SmartReport {
+    /// One-line summary, e.g. `PASSED, 0 reallocated sectors`.
+    pub fn summary(&self) -> String {
+        let status = if self.passed { "PASSED" } else { "FAILED" };
+        match self.reallocated_sectors {
+            Some(n) => format!("{}, {} reallocated sectors", status, n),
+            None => status.to_string(),
+        }
+    }
+}
+
+/// Parse `smartctl -H -A` output.
+///
+/// ATA and NVMe drives report `SMART overall-health self-assessment test
+/// result: PASSED`; SCSI drives report `SMART Health Status: OK`.  Returns
+/// `None` when no verdict is present (e.g. SMART unsupported).
+pub fn parse_smartctl(output: &str) -> Option<SmartReport> {
+    let mut passed = None;
+    let mut reallocated_sectors = None;
+
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(verdict) = line
+            .strip_prefix("SMART overall-health self-assessment test result:")
+            .or_else(|| line.strip_prefix("SMART Health Status:"))
+        {
+            let verdict = verdict.trim();
+            passed = Some(verdict == "PASSED" || verdict == "OK");
+        }
+
+        // ID# ATTRIBUTE_NAME FLAG VALUE WORST THRESH TYPE UPDATED WHEN_FAILED RAW_VALUE
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() >= 10 && fields[0] == "5" && fields[1] == "Reallocated_Sector_Ct" {
+            reallocated_sectors = fields[9].parse().ok();
+        }
+    }
+
+    passed.map(|passed| SmartReport {
+        passed,
+        reallocated_sectors,
+    })
+}
+
+/// Query SMART health for a disk.
+///
+/// Optional pre-flight check: returns `None` (after an info log) when
+/// `smartctl` is not installed or the drive reports no SMART verdict, so it
+/// never blocks an install.
+pub fn smart_health(device: &str) -> Option<SmartReport> {
+    if !crate::utils::deps::optional_binary_available("smartctl") {
+        return None;
+    }
+
+    // smartctl's exit status is a bitmask that is non-zero for failing
+    // drives too, so parse the output regardless of it.
+    let output = std::process::Command::new("smartctl")
+        .args(["-H", "-A", device])
+        .output()
+        .ok()?;
+    let report = parse_smartctl(&String::from_utf8_lossy(&output.stdout));
+    if report.is_none() {
+        tracing::info!("No SMART health data for {}, skipping", device);
+    }
+    report
+}
+
+/// Mountpoints whose backing device is the live ISO's boot medium.
+const LIVE_MEDIA_MOUNTS: &[&str] = &[
+    "/run/artix/bootmnt",
+    "/run/archiso/bootmnt",
+    "/run/initramfs/live",
+];
+
+/// Source devices of `/` and of the live medium, taken from `/proc/mounts`.
+///
+/// Only `/dev/*` sources are returned: on a live ISO `/` is an overlay, so
+/// the medium is found through its bootmnt mountpoint instead.
+pub fn protected_mount_sources(mounts: &str) -> Vec<String> {
+    let mut sources = Vec::new();
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(source), Some(target)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        if !source.starts_with("/dev/") {
+            continue;
+        }
+        if (target == "/" || LIVE_MEDIA_MOUNTS.contains(&target))
+            && !sources.iter().any(|s| s == source)
+        {
+            sources.push(source.to_string());
+        }
+    }
+    sources
+}
+
+/// Resolve a block device to the whole disk(s) backing it.
+///
+/// Follows `/dev/mapper` and other symlinks to the kernel name, then walks
+/// `slaves/` for dm-crypt/LVM/md stacks and the sysfs parent for partitions.
+pub(crate) fn backing_disks(device: &str) -> Vec<String> {
+    let name = match fs::canonicalize(device) {
+        Ok(p) => p
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        Err(_) => return Vec::new(),
+    };
+    backing_disks_by_name(&name, 0)
+}
+
+fn backing_disks_by_name(name: &str, depth: u8) -> Vec<String> {
+    let sys = Path::new("/sys/class/block").join(name);
+    if depth > 8 || !sys.exists() {
+        return Vec::new();
+    }
+
+    let slaves: Vec<String> = fs::read_dir(sys.join("slaves"))
+        .map(|rd| {
+            rd.flatten()
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    if !slaves.is_empty() {
+        let mut disks: Vec<String> = Vec::new();
+        for slave in slaves {
+            for disk in backing_disks_by_name(&slave, depth + 1) {
+                if !disks.contains(&disk) {
+                    disks.push(disk);
+                }
+            }
+        }
+        return disks;
+    }
+
+    if sys.join("partition").exists() {
+        // /sys/class/block/sda2 -> .../block/sda/sda2
+        if let Some(parent) = fs::canonicalize(&sys)
+            .ok()
+            .and_then(|p| p.parent().and_then(|p| p.file_name()).map(|n| n.to_owned()))
+        {
+            return vec![format!("/dev/{}", parent.to_string_lossy())];
+        }
+    }
+
+    vec![format!("/dev/{}", name)]
+}
+
+/// Whole disks backing the running system's `/` and the live ISO medium.
+pub fn running_system_disks() -> Vec<String> {
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let mut disks: Vec<String> = Vec::new();
+    for source in protected_mount_sources(&mounts) {
+        for disk in backing_disks(&source) {
+            if !disks.contains(&disk) {
+                disks.push(disk);
+            }
+        }
+    }
+    disks
+}
+
+/// Whether `device` is (or resolves to) a disk backing the running system.
+/// Returns the matching disk.
+pub fn is_running_system_disk(device: &str) -> Option<String> {
+    let target = backing_disks(device);
+    running_system_disks()
+        .into_iter()
+        .find(|disk| disk == device || target.contains(disk))
+}
+
 /// Get the partition naming prefix for a device.
 ///
 /// Mirrors the kernel's partition-naming rule (see `disk_name()` in
@@ -256,7 +651,15 @@ pub fn get_device_info(device_path: &str) -> Result<BlockDevice> {
 ///   /dev/loop0    -> /dev/loop0p     (loop0p1, ...)
 ///   /dev/nbd0     -> /dev/nbd0p      (nbd0p1, ...)
 ///   /dev/md0      -> /dev/md0p       (md0p1, ...)
+///
+/// Device-mapper names follow the same rule, matching kpartx's default
+/// delimiter (`/dev/mapper/mpatha` -> `mpatha1`, `/dev/dm-0` -> `dm-0p1`).
+/// Persistent udev links under `/dev/disk/by-*` use udev's `-partN` suffix
+/// instead (`/dev/disk/by-id/ata-X` -> `ata-X-part1`).
 pub fn partition_prefix(device: &str) -> String {
+    if device.starts_with("/dev/disk/by-") {
+        return format!("{}-part", device);
+    }
     match device.chars().last() {
         Some(c) if c.is_ascii_digit() => format!("{}p", device),
         _ => device.to_string(),
@@ -268,27 +671,138 @@ pub fn partition_path(device: &str, partition_num: u32) -> String {
     format!("{}{}", partition_prefix(device), partition_num)
 }
 
-/// Get the amount of RAM in MiB
-pub fn get_ram_mib() -> u64 {
-    let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
-    for line in meminfo.lines() {
-        if line.starts_with("MemTotal:") {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                if let Ok(kb) = parts[1].parse::<u64>() {
-                    return kb / 1024;
-                }
-            }
-        }
+/// Smallest RAM figure accepted as a real reading; anything lower means
+/// `/proc/meminfo` was misread.
+pub const MIN_PLAUSIBLE_RAM_MIB: u64 = 256;
+
+/// Largest RAM figure accepted as a real reading (64 TiB).
+pub const MAX_PLAUSIBLE_RAM_MIB: u64 = 64 * 1024 * 1024;
+
+/// RAM figure set by `--simulate-ram`, replacing `/proc/meminfo`.
+static SIMULATED_RAM_MIB: OnceLock<u64> = OnceLock::new();
+
+/// Make [`get_ram_mib`] report `ram_mib` for the rest of the run, so
+/// RAM-dependent sizing (swap) can be reproduced for another machine.
+pub fn simulate_ram_mib(ram_mib: u64) -> Result<()> {
+    if !(MIN_PLAUSIBLE_RAM_MIB..=MAX_PLAUSIBLE_RAM_MIB).contains(&ram_mib) {
+        return Err(DeploytixError::ConfigError(format!(
+            "--simulate-ram {} MiB is outside the plausible range {}-{} MiB",
+            ram_mib, MIN_PLAUSIBLE_RAM_MIB, MAX_PLAUSIBLE_RAM_MIB
+        )));
+    }
+    if SIMULATED_RAM_MIB.set(ram_mib).is_ok() {
+        warn!("Simulating {} MiB RAM; host RAM is ignored", ram_mib);
+    }
+    Ok(())
+}
+
+/// Parse `MemTotal` from `/proc/meminfo` content, in MiB.
+fn parse_meminfo_total_mib(meminfo: &str) -> Option<u64> {
+    parse_meminfo_mib(meminfo, "MemTotal")
+}
+
+/// Parse a `/proc/meminfo` field, in MiB.
+fn parse_meminfo_mib(meminfo: &str, field: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find(|l| l.split_once(':').map(|(k, _)| k) == Some(field))?;
+    let mut fields = line[field.len() + 1..].split_whitespace();
+    let value: u64 = fields.next()?.parse().ok()?;
+    // The kernel always reports kB; refuse anything else rather than guess
+    match fields.next() {
+        Some("kB") | None => Some(value / 1024),
+        Some(_) => None,
+    }
+}
+
+/// Check a RAM reading against [`MIN_PLAUSIBLE_RAM_MIB`] and
+/// [`MAX_PLAUSIBLE_RAM_MIB`].
+fn validate_ram_mib(ram_mib: u64) -> Result<u64> {
+    if !(MIN_PLAUSIBLE_RAM_MIB..=MAX_PLAUSIBLE_RAM_MIB).contains(&ram_mib) {
+        return Err(DeploytixError::RamUndetectable(format!(
+            "/proc/meminfo reports {} MiB, outside the plausible range {}-{} MiB",
+            ram_mib, MIN_PLAUSIBLE_RAM_MIB, MAX_PLAUSIBLE_RAM_MIB
+        )));
+    }
+    Ok(ram_mib)
+}
+
+/// Get the amount of RAM in MiB from `/proc/meminfo` (or the
+/// `--simulate-ram` override).
+///
+/// Fails when the file is unreadable, has no parsable `MemTotal`, or
+/// reports an implausible size, so swap is never sized from a bad figure.
+pub fn get_ram_mib() -> Result<u64> {
+    if let Some(&ram_mib) = SIMULATED_RAM_MIB.get() {
+        info!("Using simulated RAM: {} MiB", ram_mib);
+        return Ok(ram_mib);
     }
-    // Fallback: 8GB
-    8192
+
+    let meminfo = fs::read_to_string("/proc/meminfo")
+        .map_err(|e| DeploytixError::RamUndetectable(format!("/proc/meminfo: {}", e)))?;
+    let ram_mib = parse_meminfo_total_mib(&meminfo).ok_or_else(|| {
+        DeploytixError::RamUndetectable("no MemTotal line in /proc/meminfo".to_string())
+    })?;
+    let ram_mib = validate_ram_mib(ram_mib)?;
+    info!("Detected {} MiB RAM", ram_mib);
+    Ok(ram_mib)
+}
+
+/// RAM available to new allocations (`MemAvailable`), or the
+/// `--simulate-ram` figure.  `None` when it cannot be read.
+pub fn available_ram_mib() -> Option<u64> {
+    if let Some(&ram_mib) = SIMULATED_RAM_MIB.get() {
+        return Some(ram_mib);
+    }
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    parse_meminfo_mib(&meminfo, "MemAvailable")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // ── list_block_devices_cached ────────────────────────────────────────────
+
+    #[test]
+    fn cached_listing_reuses_a_recent_scan() {
+        let at = Instant::now();
+        let scan: CachedScan = Some((at, Vec::new()));
+        assert!(fresh_scan(&scan, at).is_some());
+        assert!(fresh_scan(&scan, at + DEVICE_CACHE_TTL / 2).is_some());
+        assert!(fresh_scan(&scan, at + DEVICE_CACHE_TTL).is_none());
+        assert!(fresh_scan(&None, at).is_none());
+    }
+
+    // ── get_ram_mib ──────────────────────────────────────────────────────────
+
+    const SAMPLE_MEMINFO: &str = "MemTotal:       16323584 kB\n\
+                                  MemFree:         9017264 kB\n\
+                                  MemAvailable:   12735116 kB\n\
+                                  Buffers:          215528 kB\n";
+
+    #[test]
+    fn meminfo_total_parses_to_mib() {
+        assert_eq!(parse_meminfo_total_mib(SAMPLE_MEMINFO), Some(15941));
+        assert_eq!(validate_ram_mib(15941).unwrap(), 15941);
+    }
+
+    #[test]
+    fn meminfo_without_total_is_rejected() {
+        assert_eq!(parse_meminfo_total_mib("MemFree: 1024 kB\n"), None);
+        assert_eq!(parse_meminfo_total_mib("MemTotal: lots kB\n"), None);
+        assert_eq!(parse_meminfo_total_mib("MemTotal: 1024 MB\n"), None);
+    }
+
+    #[test]
+    fn implausible_ram_is_rejected() {
+        assert!(validate_ram_mib(0).is_err());
+        assert!(validate_ram_mib(MIN_PLAUSIBLE_RAM_MIB - 1).is_err());
+        assert!(validate_ram_mib(MAX_PLAUSIBLE_RAM_MIB + 1).is_err());
+        // Rejected before the override is recorded
+        assert!(simulate_ram_mib(0).is_err());
+    }
+
     // ── partition_prefix ─────────────────────────────────────────────────────
 
     #[test]
@@ -318,6 +832,77 @@ mod tests {
         assert_eq!(partition_prefix("/dev/vda"), "/dev/vda");
     }
 
+    // ── parse_lsblk_pairs ────────────────────────────────────────────────────
+
+    const LSBLK_OUTPUT: &str = r#"NAME="/dev/sda" FSTYPE="" LABEL="" MOUNTPOINT=""
+NAME="/dev/sda1" FSTYPE="vfat" LABEL="EFI" MOUNTPOINT="/boot/efi"
+NAME="/dev/sda2" FSTYPE="crypto_LUKS" LABEL="" MOUNTPOINT=""
+NAME="/dev/mapper/Crypt-Root" FSTYPE="btrfs" LABEL="Artix Root" MOUNTPOINT="/"
+"#;
+
+    #[test]
+    fn lsblk_pairs_list_partitions_and_containers() {
+        let entries = parse_lsblk_pairs(LSBLK_OUTPUT, "/dev/sda");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].fstype.as_deref(), Some("crypto_LUKS"));
+        assert!(!entries[1].is_mounted());
+        assert_eq!(entries[2].label.as_deref(), Some("Artix Root"));
+        assert!(entries[2].is_root());
+    }
+
+    #[test]
+    fn lsblk_pairs_blank_disk_has_no_entries() {
+        let output = "NAME=\"/dev/sdb\" FSTYPE=\"\" LABEL=\"\" MOUNTPOINT=\"\"\n";
+        assert!(parse_lsblk_pairs(output, "/dev/sdb").is_empty());
+    }
+
+    // ── parse_smartctl ───────────────────────────────────────────────────────
+
+    #[test]
+    fn smartctl_ata_verdict_and_reallocated_sectors() {
+        let output = "\
+=== START OF READ SMART DATA SECTION ===
+SMART overall-health self-assessment test result: FAILED!
+ID# ATTRIBUTE_NAME          FLAG     VALUE WORST THRESH TYPE      UPDATED  WHEN_FAILED RAW_VALUE
+  5 Reallocated_Sector_Ct   0x0033   001   001   036    Pre-fail  Always   FAILING_NOW 4088
+  9 Power_On_Hours          0x0032   068   068   000    Old_age   Always       -       28417
+";
+        let report = parse_smartctl(output).unwrap();
+        assert!(!report.passed);
+        assert_eq!(report.reallocated_sectors, Some(4088));
+        assert_eq!(report.summary(), "FAILED, 4088 reallocated sectors");
+    }
+
+    #[test]
+    fn smartctl_scsi_and_missing_verdicts() {
+        let report = parse_smartctl("SMART Health Status: OK\n").unwrap();
+        assert!(report.passed);
+        assert_eq!(report.reallocated_sectors, None);
+        assert!(parse_smartctl("SMART support is: Unavailable\n").is_none());
+    }
+
+    // ── protected_mount_sources ──────────────────────────────────────────────
+
+    #[test]
+    fn protected_sources_include_root_and_live_media() {
+        let mounts = "\
+/dev/mapper/Crypt-Root / btrfs rw,subvol=/@ 0 0
+/dev/nvme0n1p1 /boot/efi vfat rw 0 0
+/dev/sdb1 /run/artix/bootmnt iso9660 ro 0 0
+proc /proc proc rw 0 0
+";
+        assert_eq!(
+            protected_mount_sources(mounts),
+            vec!["/dev/mapper/Crypt-Root", "/dev/sdb1"]
+        );
+    }
+
+    #[test]
+    fn protected_sources_skip_overlay_root() {
+        let mounts = "overlay / overlay rw 0 0\n/dev/sdc1 /home ext4 rw 0 0\n";
+        assert!(protected_mount_sources(mounts).is_empty());
+    }
+
     // ── partition_path ───────────────────────────────────────────────────────
 
     #[test]
@@ -367,6 +952,46 @@ mod tests {
         assert_eq!(partition_path("/dev/nbd1", 10), "/dev/nbd1p10");
     }
 
+    #[test]
+    fn partition_path_follows_the_digit_rule_for_every_disk_type() {
+        let cases = [
+            ("/dev/sda", "/dev/sda2"),
+            ("/dev/vdb", "/dev/vdb2"),
+            ("/dev/nvme1n1", "/dev/nvme1n1p2"),
+            ("/dev/mmcblk1", "/dev/mmcblk1p2"),
+            ("/dev/loop7", "/dev/loop7p2"),
+            ("/dev/dm-0", "/dev/dm-0p2"),
+            ("/dev/mapper/mpatha", "/dev/mapper/mpatha2"),
+            ("/dev/mapper/vol0", "/dev/mapper/vol0p2"),
+        ];
+        for (device, expected) in cases {
+            assert_eq!(partition_path(device, 2), expected, "{}", device);
+        }
+    }
+
+    #[test]
+    fn partition_path_udev_links_use_part_suffix() {
+        assert_eq!(
+            partition_path("/dev/disk/by-id/nvme-Samsung_SSD_980_S123", 1),
+            "/dev/disk/by-id/nvme-Samsung_SSD_980_S123-part1"
+        );
+        assert_eq!(
+            partition_path("/dev/disk/by-path/pci-0000:00:1f.2-ata-1", 3),
+            "/dev/disk/by-path/pci-0000:00:1f.2-ata-1-part3"
+        );
+    }
+
+    #[test]
+    fn is_partition_of_requires_a_partition_number() {
+        assert!(is_partition_of("/dev/sda1", "/dev/sda"));
+        assert!(is_partition_of("/dev/nvme0n1p12", "/dev/nvme0n1"));
+        assert!(is_partition_of("/dev/mmcblk0p1", "/dev/mmcblk0"));
+        assert!(!is_partition_of("/dev/sdaa1", "/dev/sda"));
+        assert!(!is_partition_of("/dev/sda", "/dev/sda"));
+        assert!(!is_partition_of("/dev/nvme0n10", "/dev/nvme0n1"));
+        assert!(!is_partition_of("/dev/mmcblk0boot0", "/dev/mmcblk0"));
+    }
+
     // ── is_physical_disk ──────────────────────────────────────────────────────
 
     #[test]
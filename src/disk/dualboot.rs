@@ -0,0 +1,625 @@
+//! Installing next to an existing system
+//!
+//! With `disk.preserve_partitions` (or `disk.existing_esp`) set, the disk
+//! is not wiped: the partition table is read with `sfdisk --json`, the new
+//! partitions are placed in the largest free region and appended with
+//! `sfdisk --append`.  With `disk.existing_esp`, that EFI System Partition
+//! is mounted as-is instead of a new one being created and formatted.
+
+use crate::config::{Bootloader, DeploymentConfig, Firmware};
+use crate::disk::detection::{is_partition_of, partition_path, partition_prefix};
+use crate::disk::layouts::{ComputedLayout, PartitionDef, EFI_MIB};
+use crate::disk::partitioning::{partition_line, run_sfdisk};
+use crate::utils::command::CommandRunner;
+use crate::utils::error::{DeploytixError, Result};
+use serde::Deserialize;
+use tracing::info;
+
+/// GPT type GUID of an EFI System Partition.
+pub const ESP_TYPE_GUID: &str = "C12A7328-F81F-11D2-BA4B-00A0C93EC93B";
+
+/// Largest partition number a GPT holds with the default entry array.
+const GPT_MAX_PARTITIONS: u32 = 128;
+
+/// Check that a config keeping the existing partitions is something the
+/// pipeline can build: a single disk and, when an ESP is reused, a
+/// partition of the target disk booted by GRUB through UEFI.
+pub fn validate_alongside(config: &DeploymentConfig) -> std::result::Result<(), String> {
+    if !config.disk.preserves_partitions() {
+        return Ok(());
+    }
+    if config.disk.mirror_device.is_some() {
+        return Err(
+            "mirror_device erases both disks; it cannot keep existing partitions".to_string(),
+        );
+    }
+    let Some(ref esp) = config.disk.existing_esp else {
+        return Ok(());
+    };
+    if !is_partition_of(esp, &config.disk.device) {
+        return Err(format!(
+            "existing_esp {} is not a partition of {}",
+            esp, config.disk.device
+        ));
+    }
+    if config.disk.firmware() != Firmware::Uefi {
+        return Err("existing_esp requires UEFI firmware".to_string());
+    }
+    if config.system.bootloader != Bootloader::Grub {
+        return Err(format!(
+            "existing_esp is only supported with GRUB, not {}",
+            config.system.bootloader
+        ));
+    }
+    if config.system.secureboot {
+        return Err("existing_esp cannot be combined with SecureBoot signing".to_string());
+    }
+    Ok(())
+}
+
+/// Partition table as reported by `sfdisk --json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartitionTable {
+    /// Table type (`gpt`, `dos`)
+    pub label: String,
+    /// First usable sector
+    #[serde(default)]
+    pub firstlba: u64,
+    /// Last usable sector
+    pub lastlba: u64,
+    /// Logical sector size (older sfdisk omits it)
+    #[serde(default = "default_sector_size")]
+    pub sectorsize: u64,
+    /// Existing partitions
+    #[serde(default)]
+    pub partitions: Vec<ExistingPartition>,
+}
+
+fn default_sector_size() -> u64 {
+    512
+}
+
+/// One partition of a [`PartitionTable`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ExistingPartition {
+    /// Device node (e.g. /dev/nvme0n1p1)
+    pub node: String,
+    /// First sector
+    pub start: u64,
+    /// Size in sectors
+    pub size: u64,
+    /// Type GUID (GPT) or type code (MBR)
+    #[serde(rename = "type")]
+    pub part_type: String,
+}
+
+#[derive(Deserialize)]
+struct SfdiskDump {
+    partitiontable: PartitionTable,
+}
+
+/// Parse the output of `sfdisk --json`.
+pub fn parse_partition_table(json: &str) -> Result<PartitionTable> {
+    serde_json::from_str::<SfdiskDump>(json)
+        .map(|dump| dump.partitiontable)
+        .map_err(|e| DeploytixError::PartitionError(format!("Cannot parse sfdisk output: {}", e)))
+}
+
+/// Read the partition table of `device`.
+pub fn read_partition_table(device: &str) -> Result<PartitionTable> {
+    let output = std::process::Command::new("sfdisk")
+        .args(["--json", device])
+        .output()
+        .map_err(|e| DeploytixError::CommandFailed {
+            command: format!("sfdisk --json {}", device),
+            stderr: e.to_string(),
+        })?;
+    if !output.status.success() {
+        return Err(DeploytixError::PartitionError(format!(
+            "Cannot read the partition table of {}: {}",
+            device,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_partition_table(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Unallocated sectors `start..=end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeRegion {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl FreeRegion {
+    /// Size of the region in sectors.
+    pub fn sectors(&self) -> u64 {
+        self.end + 1 - self.start
+    }
+}
+
+/// Largest run of unallocated sectors, starting on a 1 MiB boundary.
+pub fn largest_free_region(table: &PartitionTable) -> Option<FreeRegion> {
+    let align = (1024 * 1024 / table.sectorsize).max(1);
+    let align_up = |sector: u64| sector.div_ceil(align) * align;
+
+    let mut partitions: Vec<&ExistingPartition> = table.partitions.iter().collect();
+    partitions.sort_by_key(|p| p.start);
+
+    let mut regions = Vec::new();
+    let mut cursor = align_up(table.firstlba);
+    for part in partitions {
+        if part.start > cursor {
+            regions.push(FreeRegion {
+                start: cursor,
+                end: part.start - 1,
+            });
+        }
+        cursor = cursor.max(align_up(part.start + part.size));
+    }
+    if cursor <= table.lastlba {
+        regions.push(FreeRegion {
+            start: cursor,
+            end: table.lastlba,
+        });
+    }
+    regions.into_iter().max_by_key(FreeRegion::sectors)
+}
+
+/// Partition number of `partition` on `device` (`/dev/nvme0n1p3` -> 3).
+pub fn partition_number(device: &str, partition: &str) -> Option<u32> {
+    if !is_partition_of(partition, device) {
+        return None;
+    }
+    partition
+        .strip_prefix(&partition_prefix(device))?
+        .parse()
+        .ok()
+}
+
+/// Where the new partitions go when installing next to an existing system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlongsidePlan {
+    /// Number of the reused EFI System Partition, if any
+    pub esp_number: Option<u32>,
+    /// Free region the new partitions are created in
+    pub region: FreeRegion,
+    /// Number of the first new partition
+    pub first_number: u32,
+    /// Logical sector size of the disk
+    pub sector_size: u64,
+    /// Partitions that are kept as they are
+    pub kept: Vec<ExistingPartition>,
+}
+
+impl AlongsidePlan {
+    /// Size of the free region in MiB.
+    pub fn region_mib(&self) -> u64 {
+        self.region.sectors() * self.sector_size / (1024 * 1024)
+    }
+
+    /// Disk size (MiB) to compute the layout for: the free region, plus
+    /// the reused ESP the layout still lists.
+    pub fn layout_mib(&self) -> u64 {
+        match self.esp_number {
+            Some(_) => self.region_mib() + EFI_MIB,
+            None => self.region_mib(),
+        }
+    }
+
+    /// Whether `part` of an adopted layout is created by the install (as
+    /// opposed to being the reused ESP).
+    fn creates(&self, part: &PartitionDef) -> bool {
+        !(part.is_efi && self.esp_number.is_some())
+    }
+}
+
+/// Pick the free region of the GPT on `device` for the new partitions,
+/// checking that `esp`, when given, is an EFI System Partition on it.
+pub fn plan_alongside(
+    device: &str,
+    esp: Option<&str>,
+    table: &PartitionTable,
+) -> Result<AlongsidePlan> {
+    if table.label != "gpt" {
+        return Err(DeploytixError::ValidationError(format!(
+            "{} has a {} partition table; keeping existing partitions needs GPT",
+            device, table.label
+        )));
+    }
+    let esp_number = esp.map(|esp| check_esp(device, esp, table)).transpose()?;
+
+    let region = largest_free_region(table)
+        .ok_or_else(|| DeploytixError::PartitionError(format!("{} has no free space", device)))?;
+    let first_number = table
+        .partitions
+        .iter()
+        .filter_map(|p| partition_number(device, &p.node))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    Ok(AlongsidePlan {
+        esp_number,
+        region,
+        first_number,
+        sector_size: table.sectorsize,
+        kept: table.partitions.clone(),
+    })
+}
+
+/// Number of `esp` on `device`, if the table lists it as an ESP.
+fn check_esp(device: &str, esp: &str, table: &PartitionTable) -> Result<u32> {
+    let esp_number = partition_number(device, esp).ok_or_else(|| {
+        DeploytixError::ValidationError(format!("{} is not a partition of {}", esp, device))
+    })?;
+    let existing = table
+        .partitions
+        .iter()
+        .find(|p| partition_number(device, &p.node) == Some(esp_number))
+        .ok_or_else(|| {
+            DeploytixError::ValidationError(format!("{} does not exist on {}", esp, device))
+        })?;
+    if !existing.part_type.eq_ignore_ascii_case(ESP_TYPE_GUID) {
+        return Err(DeploytixError::ValidationError(format!(
+            "{} is not an EFI System Partition (type {})",
+            esp, existing.part_type
+        )));
+    }
+    Ok(esp_number)
+}
+
+/// Partition table a dry run plans against when the disk is simulated:
+/// only the ESP (if one is reused), at the start of an otherwise empty
+/// disk of `disk_mib`.
+pub fn simulated_table(esp: Option<&str>, disk_mib: u64) -> PartitionTable {
+    let mib = 1024 * 1024 / 512;
+    PartitionTable {
+        label: "gpt".to_string(),
+        firstlba: 2048,
+        lastlba: disk_mib * mib - 34,
+        sectorsize: 512,
+        partitions: esp
+            .map(|esp| ExistingPartition {
+                node: esp.to_string(),
+                start: 2048,
+                size: EFI_MIB * mib,
+                part_type: ESP_TYPE_GUID.to_string(),
+            })
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Renumber `layout` around the existing partitions: the EFI entry takes
+/// the number of the reused ESP, every other partition a new number from
+/// `plan.first_number` on.
+pub fn adopt_layout(layout: &mut ComputedLayout, plan: &AlongsidePlan) -> Result<()> {
+    let mut next = plan.first_number;
+    for part in &mut layout.partitions {
+        match plan.esp_number {
+            Some(esp_number) if part.is_efi => part.number = esp_number,
+            _ => {
+                part.number = next;
+                next += 1;
+            }
+        }
+    }
+    if next - 1 > GPT_MAX_PARTITIONS {
+        return Err(DeploytixError::PartitionError(format!(
+            "the disk would need partition number {}, but GPT holds {}",
+            next - 1,
+            GPT_MAX_PARTITIONS
+        )));
+    }
+    Ok(())
+}
+
+/// Start and size (in sectors) of every partition an adopted `layout`
+/// creates inside `plan.region`.
+fn placements<'a>(
+    device: &str,
+    layout: &'a ComputedLayout,
+    plan: &AlongsidePlan,
+) -> Result<Vec<(&'a PartitionDef, u64, u64)>> {
+    let align = (1024 * 1024 / plan.sector_size).max(1);
+    let mut placed = Vec::new();
+    let mut cursor = plan.region.start;
+    for part in layout.partitions.iter().filter(|p| plan.creates(p)) {
+        let size = if part.size_mib == 0 {
+            (plan.region.end + 1).saturating_sub(cursor)
+        } else {
+            part.size_mib * 1024 * 1024 / plan.sector_size
+        };
+        if size == 0 || cursor + size - 1 > plan.region.end {
+            return Err(DeploytixError::PartitionError(format!(
+                "{} does not fit in the {} MiB of free space on {}",
+                part.name,
+                plan.region_mib(),
+                device
+            )));
+        }
+        placed.push((part, cursor, size));
+        cursor = (cursor + size).div_ceil(align) * align;
+    }
+    Ok(placed)
+}
+
+/// sfdisk `--append` script creating the new partitions of an adopted
+/// `layout` inside `plan.region`.
+pub fn alongside_sfdisk_script(
+    device: &str,
+    layout: &ComputedLayout,
+    plan: &AlongsidePlan,
+) -> Result<String> {
+    let mut script = String::new();
+    for (part, start, size) in placements(device, layout, plan)? {
+        script.push_str(&partition_line(device, part, start, size));
+        script.push('\n');
+    }
+    Ok(script)
+}
+
+/// Human-readable name of a GPT partition type.
+fn type_name(guid: &str) -> &str {
+    match guid.to_ascii_uppercase().as_str() {
+        ESP_TYPE_GUID => "EFI System",
+        "E3C9E316-0B5C-4DB8-817D-F92DF00215AE" => "Microsoft reserved",
+        "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7" => "Microsoft basic data",
+        "DE94BBA4-06D1-4D40-A16A-BFD50179D6AC" => "Windows recovery",
+        "0FC63DAF-8483-4772-8E79-3D69D8477DE4" => "Linux filesystem",
+        "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F" => "Linux swap",
+        "E6D6D379-F507-44C2-A23C-238F2A3DF928" => "Linux LVM",
+        "21686148-6449-6E6F-744E-656564454649" => "BIOS boot",
+        "48465300-0000-11AA-AA11-00306543ECAC" => "Apple HFS+",
+        "7C3457EF-0000-11AA-AA11-00306543ECAC" => "Apple APFS",
+        _ => guid,
+    }
+}
+
+/// Preview of what an install next to an existing system does to
+/// `device`: the partitions it keeps and the ones it creates, with their
+/// final sizes.  Fails like partitioning would if the layout does not fit.
+pub fn alongside_preview(
+    device: &str,
+    layout: &ComputedLayout,
+    plan: &AlongsidePlan,
+) -> Result<String> {
+    let mib = |sectors: u64| sectors * plan.sector_size / (1024 * 1024);
+    let mut preview = format!("Partitions kept on {}:\n", device);
+    if plan.kept.is_empty() {
+        preview.push_str("  (none)\n");
+    }
+    for part in &plan.kept {
+        let reused =
+            plan.esp_number.is_some() && partition_number(device, &part.node) == plan.esp_number;
+        preview.push_str(&format!(
+            "  {:<20} {:>10} MiB  {}{}\n",
+            part.node,
+            mib(part.size),
+            type_name(&part.part_type),
+            if reused { " (reused as ESP)" } else { "" }
+        ));
+    }
+    preview.push_str(&format!(
+        "Partitions created in {} MiB of free space:\n",
+        plan.region_mib()
+    ));
+    for (part, _, size) in placements(device, layout, plan)? {
+        preview.push_str(&format!(
+            "  {:<20} {:>10} MiB  {} {}\n",
+            partition_path(device, part.number),
+            mib(size),
+            part.name,
+            part.mount_point.as_deref().unwrap_or("")
+        ));
+    }
+    Ok(preview)
+}
+
+/// Create the new partitions of an adopted `layout` in the free space of
+/// `device`, leaving every existing partition untouched.
+pub fn apply_partitions_alongside(
+    cmd: &CommandRunner,
+    device: &str,
+    layout: &ComputedLayout,
+    plan: &AlongsidePlan,
+) -> Result<()> {
+    info!(
+        "Adding {} partitions in {} MiB of free space on {}",
+        layout.partitions.iter().filter(|p| plan.creates(p)).count(),
+        plan.region_mib(),
+        device
+    );
+    let script = alongside_sfdisk_script(device, layout, plan)?;
+    run_sfdisk(cmd, device, &["--append"], &script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Firmware;
+    use crate::disk::layouts::compute_layout_from_entries;
+
+    /// A Windows disk: ESP, MSR, C: and a recovery partition, with 100 GiB
+    /// free between C: and recovery.
+    const WINDOWS_DISK: &str = r#"{
+       "partitiontable": {
+          "label": "gpt",
+          "device": "/dev/nvme0n1",
+          "unit": "sectors",
+          "firstlba": 34,
+          "lastlba": 1000215182,
+          "sectorsize": 512,
+          "partitions": [
+             {"node": "/dev/nvme0n1p1", "start": 2048, "size": 204800, "type": "C12A7328-F81F-11D2-BA4B-00A0C93EC93B"},
+             {"node": "/dev/nvme0n1p2", "start": 206848, "size": 32768, "type": "E3C9E316-0B5C-4DB8-817D-F92DF00215AE"},
+             {"node": "/dev/nvme0n1p3", "start": 239616, "size": 788000000, "type": "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7"},
+             {"node": "/dev/nvme0n1p4", "start": 998166528, "size": 2048000, "type": "DE94BBA4-06D1-4D40-A16A-BFD50179D6AC"}
+          ]
+       }
+    }"#;
+
+    #[test]
+    fn existing_esp_needs_uefi_grub_and_a_partition_of_the_disk() {
+        let mut config = DeploymentConfig::sample();
+        config.disk.device = "/dev/nvme0n1".to_string();
+        config.disk.existing_esp = Some("/dev/nvme0n1p1".to_string());
+        config.disk.firmware = Some(Firmware::Uefi);
+        config.system.bootloader = Bootloader::Grub;
+        config.system.secureboot = false;
+        assert!(validate_alongside(&config).is_ok());
+
+        let mut other_disk = config.clone();
+        other_disk.disk.existing_esp = Some("/dev/sda1".to_string());
+        assert!(validate_alongside(&other_disk)
+            .unwrap_err()
+            .contains("not a partition"));
+
+        let mut bios = config.clone();
+        bios.disk.firmware = Some(Firmware::Bios);
+        assert!(validate_alongside(&bios).is_err());
+
+        let mut refind = config.clone();
+        refind.system.bootloader = Bootloader::Refind;
+        assert!(validate_alongside(&refind).is_err());
+    }
+
+    #[test]
+    fn preserving_partitions_rules_out_a_mirror() {
+        let mut config = DeploymentConfig::sample();
+        config.disk.preserve_partitions = true;
+        config.disk.existing_esp = None;
+        assert!(validate_alongside(&config).is_ok());
+
+        config.disk.mirror_device = Some("/dev/sdb".to_string());
+        assert!(validate_alongside(&config).is_err());
+    }
+
+    #[test]
+    fn free_region_is_found_between_partitions() {
+        let table = parse_partition_table(WINDOWS_DISK).unwrap();
+        let region = largest_free_region(&table).unwrap();
+        // C: ends at sector 788239615; the gap starts on the next MiB
+        assert_eq!(region.start, 788240384);
+        assert_eq!(region.end, 998166527);
+    }
+
+    #[test]
+    fn plan_reuses_the_esp_and_appends_after_the_last_number() {
+        let table = parse_partition_table(WINDOWS_DISK).unwrap();
+        let plan = plan_alongside("/dev/nvme0n1", Some("/dev/nvme0n1p1"), &table).unwrap();
+        assert_eq!(plan.esp_number, Some(1));
+        assert_eq!(plan.first_number, 5);
+        assert_eq!(plan.region_mib(), 102503);
+    }
+
+    #[test]
+    fn non_esp_partitions_are_refused() {
+        let table = parse_partition_table(WINDOWS_DISK).unwrap();
+        let err = plan_alongside("/dev/nvme0n1", Some("/dev/nvme0n1p3"), &table).unwrap_err();
+        assert!(err.to_string().contains("not an EFI System Partition"));
+        assert!(plan_alongside("/dev/nvme0n1", Some("/dev/sda1"), &table).is_err());
+        assert!(plan_alongside("/dev/nvme0n1", Some("/dev/nvme0n1p9"), &table).is_err());
+    }
+
+    #[test]
+    fn new_partitions_stay_inside_the_free_region() {
+        let table = parse_partition_table(WINDOWS_DISK).unwrap();
+        let plan = plan_alongside("/dev/nvme0n1", Some("/dev/nvme0n1p1"), &table).unwrap();
+        let mut layout = compute_layout_from_entries(
+            plan.layout_mib(),
+            Firmware::Uefi,
+            false,
+            false,
+            &crate::config::default_partitions(),
+        )
+        .unwrap();
+        adopt_layout(&mut layout, &plan).unwrap();
+        assert_eq!(layout.partitions[0].number, 1);
+        assert!(layout.partitions[1..].iter().all(|p| p.number >= 5));
+
+        let script = alongside_sfdisk_script("/dev/nvme0n1", &layout, &plan).unwrap();
+        assert!(!script.contains("/dev/nvme0n1p1 "));
+        assert!(script.starts_with("/dev/nvme0n1p5 : start=788240384,"));
+        let last_end = script
+            .lines()
+            .map(|line| {
+                let field = |key: &str| -> u64 {
+                    line.split(", ")
+                        .find_map(|f| f.split_once(&format!("{}=", key)).map(|(_, v)| v))
+                        .unwrap()
+                        .parse()
+                        .unwrap()
+                };
+                field("start") + field("size") - 1
+            })
+            .max()
+            .unwrap();
+        assert_eq!(last_end, plan.region.end);
+    }
+
+    #[test]
+    fn too_little_free_space_is_reported() {
+        let mut table = parse_partition_table(WINDOWS_DISK).unwrap();
+        table.partitions[2].size = 998166528 - 239616 - 2048 * 1024;
+        let plan = plan_alongside("/dev/nvme0n1", Some("/dev/nvme0n1p1"), &table).unwrap();
+        let mut layout = compute_layout_from_entries(
+            200_000,
+            Firmware::Uefi,
+            false,
+            false,
+            &crate::config::default_partitions(),
+        )
+        .unwrap();
+        adopt_layout(&mut layout, &plan).unwrap();
+        let err = alongside_sfdisk_script("/dev/nvme0n1", &layout, &plan).unwrap_err();
+        assert!(err.to_string().contains("does not fit"));
+    }
+
+    #[test]
+    fn free_space_install_creates_its_own_esp() {
+        let table = parse_partition_table(WINDOWS_DISK).unwrap();
+        let plan = plan_alongside("/dev/nvme0n1", None, &table).unwrap();
+        assert_eq!(plan.esp_number, None);
+        assert_eq!(plan.layout_mib(), plan.region_mib());
+
+        let mut layout = compute_layout_from_entries(
+            plan.layout_mib(),
+            Firmware::Uefi,
+            false,
+            false,
+            &crate::config::default_partitions(),
+        )
+        .unwrap();
+        adopt_layout(&mut layout, &plan).unwrap();
+        assert!(layout.partitions[0].is_efi);
+        assert_eq!(layout.partitions[0].number, 5);
+
+        let script = alongside_sfdisk_script("/dev/nvme0n1", &layout, &plan).unwrap();
+        assert!(script.starts_with("/dev/nvme0n1p5 : start=788240384,"));
+        assert!(script.lines().next().unwrap().contains(ESP_TYPE_GUID));
+    }
+
+    #[test]
+    fn preview_lists_kept_and_created_partitions() {
+        let table = parse_partition_table(WINDOWS_DISK).unwrap();
+        let plan = plan_alongside("/dev/nvme0n1", Some("/dev/nvme0n1p1"), &table).unwrap();
+        let mut layout = compute_layout_from_entries(
+            plan.layout_mib(),
+            Firmware::Uefi,
+            false,
+            false,
+            &crate::config::default_partitions(),
+        )
+        .unwrap();
+        adopt_layout(&mut layout, &plan).unwrap();
+
+        let preview = alongside_preview("/dev/nvme0n1", &layout, &plan).unwrap();
+        let (kept, created) = preview.split_once("Partitions created").unwrap();
+        assert!(kept.contains("EFI System (reused as ESP)"));
+        assert!(kept.contains("Microsoft basic data"));
+        assert!(kept.contains("/dev/nvme0n1p4"));
+        assert!(!created.contains("/dev/nvme0n1p1 "));
+        assert!(created.contains("/dev/nvme0n1p5"));
+    }
+}
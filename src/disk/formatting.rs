@@ -1,19 +1,168 @@
 //! Filesystem formatting
 
-use crate::config::Filesystem;
+use crate::config::{DiskConfig, Filesystem, FilesystemOptions, ZfsAtime, ZfsConfig};
 use crate::disk::detection::partition_path;
 use crate::disk::layouts::{ComputedLayout, SubvolumeDef};
+use crate::disk::mount::ensure_mounted;
 use crate::utils::command::CommandRunner;
 use crate::utils::error::{DeploytixError, Result};
 use std::fs;
-use tracing::info;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::{debug, info, warn};
 
-/// Format a partition with the specified filesystem
+/// mkfs flags that skip the initial whole-device discard on rotational
+/// disks, where TRIM is unsupported and the attempt only slows formatting.
+fn mkfs_discard_args(filesystem: &Filesystem, rotational: bool) -> &'static [&'static str] {
+    if !rotational {
+        return &[];
+    }
+    match filesystem {
+        Filesystem::Ext4 => &["-E", "nodiscard"],
+        Filesystem::Btrfs => &["--nodiscard"],
+        Filesystem::Xfs => &["-K"],
+        Filesystem::F2fs => &["-t", "0"],
+        Filesystem::Zfs => &[],
+    }
+}
+
+/// The host's memory page size in bytes.
+fn page_size() -> u32 {
+    // SAFETY: sysconf only reads a system constant
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    u32::try_from(size).unwrap_or(4096)
+}
+
+/// XFS block sizes mkfs accepts are powers of two from 512 up to 65536,
+/// but the kernel cannot mount one larger than the page size.
+fn check_xfs_block_size(size: u32, page_size: u32) -> std::result::Result<(), String> {
+    let max = page_size.min(65536);
+    if !(512..=max).contains(&size) || !size.is_power_of_two() {
+        return Err(format!(
+            "filesystem_options.xfs.block_size must be a power of two in 512-{} \
+             (the page size), got {}",
+            max, size
+        ));
+    }
+    Ok(())
+}
+
+/// Check `[filesystem_options]` values against the ranges mkfs accepts.
+pub fn validate_filesystem_options(opts: &FilesystemOptions) -> std::result::Result<(), String> {
+    if let Some(ref ext4) = opts.ext4 {
+        if let Some(pct) = ext4.reserved_percent {
+            if pct > 50 {
+                return Err(format!(
+                    "filesystem_options.ext4.reserved_percent must be 0-50, got {}",
+                    pct
+                ));
+            }
+        }
+        if let Some(ratio) = ext4.inode_ratio {
+            if !(1024..=67_108_864).contains(&ratio) {
+                return Err(format!(
+                    "filesystem_options.ext4.inode_ratio must be 1024-67108864, got {}",
+                    ratio
+                ));
+            }
+        }
+    }
+    if let Some(size) = opts.xfs.as_ref().and_then(|x| x.block_size) {
+        check_xfs_block_size(size, page_size())?;
+    }
+    if let Some(ref f2fs) = opts.f2fs {
+        if let Some(arg) = f2fs
+            .extra_options
+            .iter()
+            .find(|a| a.is_empty() || a.contains(char::is_whitespace))
+        {
+            return Err(format!(
+                "filesystem_options.f2fs.extra_options: '{}' must be a single non-empty argument",
+                arg
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// mkfs flags from `[filesystem_options]` for `filesystem`.  Empty when the
+/// filesystem has no tuning configured.
+fn mkfs_tuning_args(filesystem: &Filesystem, opts: &FilesystemOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    match filesystem {
+        Filesystem::Ext4 => {
+            if let Some(ref ext4) = opts.ext4 {
+                if let Some(pct) = ext4.reserved_percent {
+                    args.extend(["-m".to_string(), pct.to_string()]);
+                }
+                if let Some(ratio) = ext4.inode_ratio {
+                    args.extend(["-i".to_string(), ratio.to_string()]);
+                }
+                match ext4.feature_64bit {
+                    Some(true) => args.extend(["-O".to_string(), "64bit".to_string()]),
+                    Some(false) => args.extend(["-O".to_string(), "^64bit".to_string()]),
+                    None => {}
+                }
+            }
+        }
+        Filesystem::Xfs => {
+            if let Some(size) = opts.xfs.as_ref().and_then(|x| x.block_size) {
+                args.extend(["-b".to_string(), format!("size={}", size)]);
+            }
+        }
+        Filesystem::F2fs => {
+            if let Some(ref f2fs) = opts.f2fs {
+                args.extend(f2fs.extra_options.iter().cloned());
+            }
+        }
+        Filesystem::Btrfs | Filesystem::Zfs => {}
+    }
+    args
+}
+
+/// Below this size btrfs is created with `--mixed` data/metadata block
+/// groups, so small devices (USB sticks) don't strand space in separately
+/// allocated metadata chunks.
+pub const BTRFS_MIXED_THRESHOLD_MIB: u64 = 16 * 1024;
+
+/// Whether to create btrfs with `--mixed`: the explicit override wins,
+/// otherwise devices below [`BTRFS_MIXED_THRESHOLD_MIB`].  Unknown sizes
+/// keep the normal layout.
+fn use_btrfs_mixed(mixed: Option<bool>, size_bytes: Option<u64>) -> bool {
+    mixed.unwrap_or_else(|| {
+        size_bytes.is_some_and(|b| b / (1024 * 1024) < BTRFS_MIXED_THRESHOLD_MIB)
+    })
+}
+
+/// Size of a block device in bytes, via `blockdev --getsize64`.
+pub(crate) fn block_device_size(device: &str) -> Option<u64> {
+    let output = std::process::Command::new("blockdev")
+        .args(["--getsize64", device])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Format a partition with the specified filesystem.
+///
+/// `rotational` reflects the target disk; HDDs skip the mkfs discard pass.
+/// `btrfs_mixed` forces (or suppresses) btrfs `--mixed`; unset picks it
+/// from the device size.  `f2fs_compression` enables the f2fs features
+/// needed for transparent compression.  `tuning` adds the configured
+/// `[filesystem_options]` flags.
+#[allow(clippy::too_many_arguments)]
 pub fn format_partition(
     cmd: &CommandRunner,
     partition: &str,
     filesystem: &Filesystem,
     label: Option<&str>,
+    rotational: bool,
+    btrfs_mixed: Option<bool>,
+    f2fs_compression: bool,
+    tuning: &FilesystemOptions,
 ) -> Result<()> {
     info!("Formatting {} as {}", partition, filesystem);
 
@@ -30,11 +179,15 @@ pub fn format_partition(
         (Filesystem::F2fs, Some(l)) => vec!["-l", l],
         _ => vec![],
     };
+    let discard_args = mkfs_discard_args(filesystem, rotational);
+    let tuning_args = mkfs_tuning_args(filesystem, tuning);
 
     let result = match filesystem {
         Filesystem::Ext4 => {
             let mut args = vec!["-F"];
             args.extend(&label_args);
+            args.extend(discard_args);
+            args.extend(tuning_args.iter().map(|s| s.as_str()));
             args.push(partition);
             cmd.run("mkfs.ext4", &args)
         }
@@ -42,6 +195,11 @@ pub fn format_partition(
             let compat_args = btrfs_runtime_compat_args();
             let mut args = vec!["-f"];
             args.extend(&label_args);
+            args.extend(discard_args);
+            if use_btrfs_mixed(btrfs_mixed, block_device_size(partition)) {
+                info!("Using mixed data/metadata block groups for {}", partition);
+                args.push("--mixed");
+            }
             args.extend(compat_args.iter().map(|s| s.as_str()));
             args.push(partition);
             cmd.run("mkfs.btrfs", &args)
@@ -49,12 +207,19 @@ pub fn format_partition(
         Filesystem::Xfs => {
             let mut args = vec!["-f"];
             args.extend(&label_args);
+            args.extend(discard_args);
+            args.extend(tuning_args.iter().map(|s| s.as_str()));
             args.push(partition);
             cmd.run("mkfs.xfs", &args)
         }
         Filesystem::F2fs => {
             let mut args = vec!["-f"];
             args.extend(&label_args);
+            args.extend(discard_args);
+            if f2fs_compression {
+                args.extend(["-O", "extra_attr,compression"]);
+            }
+            args.extend(tuning_args.iter().map(|s| s.as_str()));
             args.push(partition);
             cmd.run("mkfs.f2fs", &args)
         }
@@ -87,6 +252,44 @@ pub fn format_partition(
     })
 }
 
+/// Create one btrfs filesystem across `devices` with the `raid1` profile
+/// for data and metadata, so every block is stored on two of them.
+pub fn format_btrfs_raid1(
+    cmd: &CommandRunner,
+    devices: &[&str],
+    label: &str,
+    rotational: bool,
+    btrfs_mixed: Option<bool>,
+) -> Result<()> {
+    info!(
+        "Formatting {} as btrfs RAID1 ({})",
+        devices.join(" + "),
+        label
+    );
+
+    for device in devices {
+        let _ = cmd.run("wipefs", &["-a", device]);
+    }
+    let compat_args = btrfs_runtime_compat_args();
+    let mut args = vec!["-f", "-L", label, "-d", "raid1", "-m", "raid1"];
+    args.extend(mkfs_discard_args(&Filesystem::Btrfs, rotational));
+    let smallest = devices.iter().filter_map(|d| block_device_size(d)).min();
+    if use_btrfs_mixed(btrfs_mixed, smallest) {
+        info!("Using mixed data/metadata block groups for {}", label);
+        args.push("--mixed");
+    }
+    args.extend(compat_args.iter().map(|s| s.as_str()));
+    args.extend(devices);
+
+    cmd.run("mkfs.btrfs", &args).map(|_| ()).map_err(|e| {
+        DeploytixError::FilesystemError(format!(
+            "Failed to format {} as btrfs RAID1: {}",
+            devices.join(" + "),
+            e
+        ))
+    })
+}
+
 /// Extra `mkfs.btrfs -O` arguments that keep the new filesystem mountable by
 /// the running kernel.  btrfs-progs >= 6.7 enables `block-group-tree` by
 /// default, but kernels older than 6.1 cannot mount such a filesystem
@@ -138,12 +341,30 @@ fn kernel_at_least(major: u32, minor: u32) -> bool {
     (maj, min) >= (major, minor)
 }
 
+/// Maximum filesystem label length for FAT32 (EFI partition).
+pub const FAT32_LABEL_MAX: usize = 11;
+
+/// Maximum swap label length (`mkswap -L`).
+pub const SWAP_LABEL_MAX: usize = 16;
+
+/// Maximum filesystem label length accepted by each mkfs tool.
+/// ZFS has no label; its pools are named instead.
+pub fn fs_label_max(filesystem: &Filesystem) -> usize {
+    match filesystem {
+        Filesystem::Ext4 => 16,
+        Filesystem::Btrfs => 255,
+        Filesystem::Xfs => 12,
+        Filesystem::F2fs => 512,
+        Filesystem::Zfs => 255,
+    }
+}
+
 /// Format the EFI partition as FAT32
-pub fn format_efi(cmd: &CommandRunner, partition: &str) -> Result<()> {
-    info!("Formatting {} as FAT32 (EFI)", partition);
+pub fn format_efi(cmd: &CommandRunner, partition: &str, label: &str) -> Result<()> {
+    info!("Formatting {} as FAT32 ({})", partition, label);
 
     let _ = cmd.run("wipefs", &["-a", partition]);
-    cmd.run("mkfs.vfat", &["-F32", "-n", "EFI", partition])
+    cmd.run("mkfs.vfat", &["-F32", "-n", label, partition])
         .map(|_| ())
         .map_err(|e| {
             DeploytixError::FilesystemError(format!("Failed to format EFI partition: {}", e))
@@ -163,14 +384,30 @@ pub fn format_boot_partition(
     cmd: &CommandRunner,
     partition: &str,
     boot_filesystem: &Filesystem,
+    label: &str,
+    rotational: bool,
+    zfs_atime: ZfsAtime,
 ) -> Result<()> {
-    info!("Formatting {} as {} (BOOT)", partition, boot_filesystem);
+    info!(
+        "Formatting {} as {} ({})",
+        partition, boot_filesystem, label
+    );
     if *boot_filesystem == Filesystem::Zfs {
-        return create_zfs_boot_pool(cmd, partition);
+        return create_zfs_boot_pool(cmd, partition, zfs_atime);
     }
-    format_partition(cmd, partition, boot_filesystem, Some("BOOT")).map_err(|e| {
-        DeploytixError::FilesystemError(format!("Failed to format BOOT partition: {}", e))
-    })
+    // /boot keeps the standard btrfs layout regardless of its size, and
+    // is never compressed: GRUB's f2fs driver cannot read compressed files.
+    format_partition(
+        cmd,
+        partition,
+        boot_filesystem,
+        Some(label),
+        rotational,
+        Some(false),
+        false,
+        &FilesystemOptions::default(),
+    )
+    .map_err(|e| DeploytixError::FilesystemError(format!("Failed to format BOOT partition: {}", e)))
 }
 
 /// Format a swap partition
@@ -191,8 +428,10 @@ pub fn format_swap(cmd: &CommandRunner, partition: &str, label: Option<&str>) ->
 
 /// Format all partitions according to the layout.
 ///
-/// `filesystem` applies to data partitions; `boot_filesystem` applies to the
-/// /boot partition (`is_boot_fs`).  The EFI partition is always FAT32.
+/// `disk.filesystem` applies to data partitions; `disk.boot_filesystem`
+/// applies to the /boot partition (`is_boot_fs`).  The EFI partition is
+/// always FAT32.  Filesystem labels honour `disk.labels` overrides, and
+/// data partitions get the `tuning` mkfs flags.
 ///
 /// `is_bios_boot` is purely a GPT attribute (LegacyBIOSBootable, the
 /// "Bootable" flag in fdisk/sfdisk expert mode) and has NO effect on
@@ -200,13 +439,22 @@ pub fn format_swap(cmd: &CommandRunner, partition: &str, label: Option<&str>) ->
 /// `is_boot_fs`; its filesystem is determined by `boot_filesystem`.
 /// A standalone `is_bios_boot` partition (without `is_boot_fs`) is raw
 /// storage for GRUB core.img and is never formatted.
+///
+/// With `disk.mirror_device`, the mirror's EFI partition is formatted too
+/// and the /boot and data partitions become btrfs RAID1 filesystems across
+/// both disks.  Swap stays on `device`.  An `existing_esp` is left as it
+/// is.
 pub fn format_all_partitions(
     cmd: &CommandRunner,
     device: &str,
     layout: &ComputedLayout,
-    filesystem: &Filesystem,
-    boot_filesystem: &Filesystem,
+    disk: &DiskConfig,
+    rotational: bool,
+    tuning: &FilesystemOptions,
+    zfs_atime: ZfsAtime,
 ) -> Result<()> {
+    let filesystem = &disk.filesystem;
+    let boot_filesystem = &disk.boot_filesystem;
     info!(
         "Formatting {} partitions on {} (data fs: {}, boot fs: {})",
         layout.partitions.len(),
@@ -215,11 +463,27 @@ pub fn format_all_partitions(
         boot_filesystem,
     );
 
+    // One job per partition; they touch distinct devices, so any of them
+    // may run concurrently.  LUKS containers are formatted (and opened, in
+    // order) by the encryption module instead.
+    let mut jobs: Vec<FormatJob> = Vec::new();
+    let mirror = disk.mirror_device.as_deref();
     for part in &layout.partitions {
         let part_path = partition_path(device, part.number);
+        let mirror_path = mirror.map(|m| partition_path(m, part.number));
+        let label = disk.fs_label(&part.name);
 
-        if part.is_efi {
-            format_efi(cmd, &part_path)?;
+        if part.is_efi && disk.existing_esp.is_some() {
+            info!("Keeping the existing EFI System Partition {}", part_path);
+        } else if part.is_efi {
+            jobs.push(FormatJob::new(part_path, move |path| {
+                format_efi(cmd, path, label)
+            }));
+            if let Some(mirror_path) = mirror_path {
+                jobs.push(FormatJob::new(mirror_path, move |path| {
+                    format_efi(cmd, path, label)
+                }));
+            }
         } else if part.is_bios_boot && !part.is_boot_fs {
             // Standalone BIOS Boot partition: raw area for GRUB core.img.
             // Only the GPT LegacyBIOSBootable attribute is set (by sfdisk);
@@ -229,22 +493,65 @@ pub fn format_all_partitions(
                 part_path
             );
         } else if part.is_swap {
-            format_swap(cmd, &part_path, Some(&part.name))?;
+            jobs.push(FormatJob::new(part_path, move |path| {
+                format_swap(cmd, path, Some(label))
+            }));
         } else if part.is_luks {
             // LUKS partitions are handled separately by the encryption module
             info!(
                 "Skipping {} (LUKS partition, formatted by encryption module)",
                 part_path
             );
+        } else if let Some(mirror_path) = mirror_path {
+            // /boot and data partitions: one btrfs RAID1 across both disks
+            // (validation only allows btrfs here)
+            let mixed = if part.is_boot_fs {
+                Some(false)
+            } else {
+                disk.btrfs_mixed
+            };
+            jobs.push(FormatJob::new(part_path, move |path| {
+                format_btrfs_raid1(cmd, &[path, &mirror_path], label, rotational, mixed)
+            }));
         } else if part.is_boot_fs {
             // /boot filesystem: kernel, initramfs, and GRUB config live here.
             // Formatted with the chosen boot filesystem (not the data filesystem).
-            format_boot_partition(cmd, &part_path, boot_filesystem)?;
+            jobs.push(FormatJob::new(part_path, move |path| {
+                format_boot_partition(cmd, path, boot_filesystem, label, rotational, zfs_atime)
+            }));
+        } else if *filesystem == Filesystem::Zfs {
+            // The data pool is created on it together with its datasets
+            info!(
+                "Skipping {} (ZFS data pool, created by create_zfs_pool)",
+                part_path
+            );
         } else {
-            format_partition(cmd, &part_path, filesystem, Some(&part.name))?;
+            let fs = layout.partition_filesystem(&part.name, filesystem);
+            let compression =
+                *fs == Filesystem::F2fs && crate::disk::layouts::f2fs_compression_enabled(disk);
+            jobs.push(FormatJob::new(part_path, move |path| {
+                format_partition(
+                    cmd,
+                    path,
+                    fs,
+                    Some(label),
+                    rotational,
+                    disk.btrfs_mixed,
+                    compression,
+                    tuning,
+                )
+            }));
         }
     }
 
+    // Dry runs only print, and interleaved output helps nobody
+    let parallelism = if cmd.is_dry_run() {
+        1
+    } else {
+        disk.format_parallelism()
+    };
+    run_format_jobs(jobs, parallelism)?;
+
     // Flush all pending writes and let udev update device metadata before
     // the caller tries to mount.  On virtual block devices (NBD, loop) the
     // kernel page-cache may not have been flushed to the backing store yet.
@@ -255,6 +562,76 @@ pub fn format_all_partitions(
     Ok(())
 }
 
+/// Formats the partition at the given path.
+type FormatFn<'a> = Box<dyn FnOnce(&str) -> Result<()> + Send + 'a>;
+
+/// Formatting of one partition, run by [`run_format_jobs`].
+struct FormatJob<'a> {
+    path: String,
+    run: FormatFn<'a>,
+}
+
+impl<'a> FormatJob<'a> {
+    fn new(path: String, run: impl FnOnce(&str) -> Result<()> + Send + 'a) -> Self {
+        Self {
+            path,
+            run: Box::new(run),
+        }
+    }
+
+    /// Run the job, logging how long it took (visible with `--verbose`).
+    fn execute(self) -> Result<()> {
+        let start = Instant::now();
+        let result = (self.run)(&self.path);
+        debug!("Formatted {} in {:.1?}", self.path, start.elapsed());
+        result
+    }
+}
+
+/// Run `jobs` on up to `parallelism` threads.  Every job runs even when
+/// another fails; the first error (in partition order) is returned after
+/// all of them have finished.  With a single worker the jobs instead run
+/// in order on the calling thread, stopping at the first error.
+fn run_format_jobs(jobs: Vec<FormatJob>, parallelism: usize) -> Result<()> {
+    let workers = parallelism.clamp(1, jobs.len().max(1));
+    if workers == 1 {
+        return jobs.into_iter().try_for_each(FormatJob::execute);
+    }
+
+    info!(
+        "Formatting {} partitions with {} parallel jobs",
+        jobs.len(),
+        workers
+    );
+    let queue: Mutex<Vec<(usize, FormatJob)>> =
+        Mutex::new(jobs.into_iter().enumerate().rev().collect());
+    let results: Mutex<Vec<(usize, String, Result<()>)>> = Mutex::new(Vec::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let Some((index, job)) = queue.lock().unwrap().pop() else {
+                    break;
+                };
+                let path = job.path.clone();
+                let result = job.execute();
+                results.lock().unwrap().push((index, path, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _, _)| *index);
+    let mut first_error = None;
+    for (_, path, result) in results {
+        if let Err(e) = result {
+            warn!("Formatting {} failed: {}", path, e);
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
 /// Get the UUID of a formatted partition
 pub fn get_partition_uuid(partition: &str) -> Result<String> {
     let output = std::process::Command::new("blkid")
@@ -301,51 +678,223 @@ pub fn get_all_uuids(
 pub const ZFS_RPOOL_NAME: &str = "rpool";
 /// Default ZFS boot pool name.
 pub const ZFS_BPOOL_NAME: &str = "bpool";
-/// Default root dataset under the data pool.
-pub const ZFS_ROOT_DATASET: &str = "rpool/ROOT";
 /// Default boot dataset under the boot pool.
 pub const ZFS_BOOT_DATASET: &str = "bpool/BOOT";
 
-/// Create a ZFS data pool (`rpool`) on a device with production-ready defaults.
+/// ashift used when neither the config nor the disk asks for more.
+/// Many drives report 512-byte physical sectors while writing 4 KiB
+/// internally, so anything below 12 costs performance.
+const ZFS_MIN_AUTO_ASHIFT: u8 = 12;
+
+/// ashift for a disk with `physical_sector_size`-byte sectors, never
+/// below [`ZFS_MIN_AUTO_ASHIFT`] nor above ZFS's maximum of 16.
+pub fn ashift_for_sector_size(physical_sector_size: u64) -> u8 {
+    (physical_sector_size.max(1).ilog2() as u8).clamp(ZFS_MIN_AUTO_ASHIFT, 16)
+}
+
+/// Whether `name` is a valid ZFS pool or dataset name component.
+fn is_valid_zfs_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+/// Check the `[zfs]` section: pool name, ashift range and the dataset map.
+pub fn validate_zfs_config(zfs: &ZfsConfig) -> std::result::Result<(), String> {
+    if !is_valid_zfs_name(&zfs.pool_name) || zfs.pool_name == ZFS_BPOOL_NAME {
+        return Err(format!("zfs.pool_name '{}' is not usable", zfs.pool_name));
+    }
+    if let Some(ashift) = zfs.ashift {
+        if !(9..=16).contains(&ashift) {
+            return Err(format!("zfs.ashift must be 9-16, got {}", ashift));
+        }
+    }
+    if zfs.datasets.is_empty() {
+        return Ok(());
+    }
+
+    let mut mount_points: Vec<&str> = Vec::new();
+    for (dataset, mount_point) in &zfs.datasets {
+        if !dataset.split('/').all(is_valid_zfs_name) {
+            return Err(format!("zfs.datasets: invalid dataset name '{}'", dataset));
+        }
+        if !mount_point.starts_with('/') {
+            return Err(format!(
+                "zfs.datasets: mount point '{}' for {} must be absolute",
+                mount_point, dataset
+            ));
+        }
+        if mount_point.starts_with("/boot") {
+            return Err(format!(
+                "zfs.datasets: {} is mounted separately and cannot be a dataset",
+                mount_point
+            ));
+        }
+        if mount_points.contains(&mount_point.as_str()) {
+            return Err(format!(
+                "zfs.datasets: mount point {} is used twice",
+                mount_point
+            ));
+        }
+        mount_points.push(mount_point);
+    }
+    if !mount_points.contains(&"/") {
+        return Err("zfs.datasets: no dataset is mounted at /".to_string());
+    }
+    Ok(())
+}
+
+/// Datasets to create as `(full name, mount point)`, ordered by mount
+/// point so parents are mounted before their children.
+pub fn zfs_datasets(zfs: &ZfsConfig) -> Vec<(String, String)> {
+    let mut datasets: Vec<(String, String)> = if zfs.datasets.is_empty() {
+        ZFS_DATASETS
+            .iter()
+            .map(|(ds, mp)| (ds.to_string(), mp.to_string()))
+            .collect()
+    } else {
+        zfs.datasets
+            .iter()
+            .map(|(ds, mp)| (ds.clone(), mp.clone()))
+            .collect()
+    };
+    datasets.sort_by(|a, b| a.1.cmp(&b.1));
+    datasets
+        .into_iter()
+        .map(|(ds, mp)| (format!("{}/{}", zfs.pool_name, ds), mp))
+        .collect()
+}
+
+/// Full name of the dataset mounted at `/`.
+pub fn zfs_root_dataset(zfs: &ZfsConfig) -> String {
+    zfs_datasets(zfs)
+        .into_iter()
+        .find(|(_, mp)| mp == "/")
+        .map(|(ds, _)| ds)
+        .unwrap_or_else(|| format!("{}/ROOT", zfs.pool_name))
+}
+
+/// Pool properties enabling native encryption; the passphrase is read
+/// from stdin at creation and prompted for by the zfs hook at boot.
+const ZFS_ENCRYPTION_ARGS: &[&str] = &[
+    "-O",
+    "encryption=aes-256-gcm",
+    "-O",
+    "keyformat=passphrase",
+    "-O",
+    "keylocation=prompt",
+];
+
+/// Run a zpool/zfs command that reads a passphrase from stdin.
+fn run_with_passphrase(
+    cmd: &CommandRunner,
+    program: &str,
+    args: &[&str],
+    passphrase: &str,
+) -> Result<()> {
+    cmd.transcribe_stdin(program, args);
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let command = format!("{} {}", program, args.first().unwrap_or(&""));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| DeploytixError::CommandFailed {
+            command: command.clone(),
+            stderr: e.to_string(),
+        })?;
+    if let Some(ref mut stdin) = child.stdin {
+        writeln!(stdin, "{}", passphrase)?;
+    }
+    drop(child.stdin.take());
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(DeploytixError::CommandFailed {
+            command,
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Create the ZFS data pool on a device.
 ///
 /// The pool is created with `mountpoint=none` so that all mounts are handled
 /// via legacy mountpoints in fstab. Datasets are created separately by
-/// [`create_zfs_datasets`].
-pub fn create_zfs_pool(cmd: &CommandRunner, device: &str) -> Result<()> {
-    info!("Creating ZFS data pool ({}) on {}", ZFS_RPOOL_NAME, device);
+/// [`create_zfs_datasets`].  ashift defaults to 12 when the config leaves
+/// it unset and the disk was not probed.  With a `passphrase` the pool
+/// uses native encryption, which every dataset inherits.
+pub fn create_zfs_pool(
+    cmd: &CommandRunner,
+    device: &str,
+    zfs: &ZfsConfig,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let ashift = format!("ashift={}", zfs.ashift.unwrap_or(ZFS_MIN_AUTO_ASHIFT));
+    let compression = format!("compression={}", zfs.compression);
+    info!(
+        "Creating ZFS data pool ({}, {}, {}{}) on {}",
+        zfs.pool_name,
+        ashift,
+        compression,
+        if passphrase.is_some() {
+            ", encrypted"
+        } else {
+            ""
+        },
+        device
+    );
+
+    let mut args = vec!["create", "-f", "-o", &ashift, "-O", "mountpoint=none"];
+    for property in zfs.atime.properties() {
+        args.extend(["-O", property]);
+    }
+    args.extend([
+        "-O",
+        &compression,
+        "-O",
+        "xattr=sa",
+        "-O",
+        "acltype=posixacl",
+    ]);
+    if passphrase.is_some() {
+        args.extend(ZFS_ENCRYPTION_ARGS);
+    }
+    args.extend([zfs.pool_name.as_str(), device]);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] zpool create -f -o ashift=12 -O mountpoint=none -O atime=off -O compression=zstd -O xattr=sa -O acltype=posixacl {} {}",
-            ZFS_RPOOL_NAME, device);
+        cmd.log_dry_run(&format!("zpool {}", args.join(" ")));
         return Ok(());
     }
 
-    cmd.run(
-        "zpool",
-        &[
-            "create",
-            "-f",
-            "-o",
-            "ashift=12",
-            "-O",
-            "mountpoint=none",
-            "-O",
-            "atime=off",
-            "-O",
-            "compression=zstd",
-            "-O",
-            "xattr=sa",
-            "-O",
-            "acltype=posixacl",
-            ZFS_RPOOL_NAME,
-            device,
-        ],
-    )
-    .map(|_| ())
-    .map_err(|e| {
+    let result = match passphrase {
+        Some(passphrase) => run_with_passphrase(cmd, "zpool", &args, passphrase),
+        None => cmd.run("zpool", &args).map(|_| ()),
+    };
+    result.map_err(|e| {
         DeploytixError::FilesystemError(format!(
             "Failed to create ZFS pool {}: {}",
-            ZFS_RPOOL_NAME, e
+            zfs.pool_name, e
+        ))
+    })
+}
+
+/// Load the encryption key of an imported, natively encrypted pool.
+pub fn load_zfs_key(cmd: &CommandRunner, pool: &str, passphrase: &str) -> Result<()> {
+    if cmd.is_dry_run() {
+        cmd.log_dry_run(&format!("zfs load-key {}", pool));
+        return Ok(());
+    }
+    run_with_passphrase(cmd, "zfs", &["load-key", pool], passphrase).map_err(|e| {
+        DeploytixError::FilesystemError(format!(
+            "Failed to load the encryption key of {}: {}",
+            pool, e
         ))
     })
 }
@@ -355,60 +904,63 @@ pub fn create_zfs_pool(cmd: &CommandRunner, device: &str) -> Result<()> {
 /// GRUB's ZFS driver supports only a subset of pool features. The boot pool
 /// restricts features to those GRUB can read, ensuring the kernel and
 /// initramfs are always accessible at early boot.
-fn create_zfs_boot_pool(cmd: &CommandRunner, device: &str) -> Result<()> {
+fn create_zfs_boot_pool(cmd: &CommandRunner, device: &str, atime: ZfsAtime) -> Result<()> {
     info!("Creating ZFS boot pool ({}) on {}", ZFS_BPOOL_NAME, device);
 
+    let atime_args: Vec<&str> = atime
+        .properties()
+        .iter()
+        .flat_map(|property| ["-O", property])
+        .collect();
     if cmd.is_dry_run() {
-        println!("  [dry-run] zpool create -f -d -o ashift=12 -o feature@... -O mountpoint=none -O compression=lz4 {} {}",
-            ZFS_BPOOL_NAME, device);
+        cmd.log_dry_run(&format!(
+            "zpool create -f -d -o ashift=12 -o feature@... -O mountpoint=none -O compression=lz4 {} {} {}",
+            atime_args.join(" "),
+            ZFS_BPOOL_NAME,
+            device
+        ));
         return Ok(());
     }
 
     // GRUB-compatible feature flags — enable only the features GRUB's ZFS
     // driver can handle. Everything else stays disabled so the boot pool
     // remains readable by the bootloader.
-    cmd.run(
-        "zpool",
-        &[
-            "create",
-            "-f",
-            "-d",
-            "-o",
-            "ashift=12",
-            "-o",
-            "feature@async_destroy=enabled",
-            "-o",
-            "feature@bookmarks=enabled",
-            "-o",
-            "feature@embedded_data=enabled",
-            "-o",
-            "feature@empty_bpobj=enabled",
-            "-o",
-            "feature@enabled_txg=enabled",
-            "-o",
-            "feature@extensible_dataset=enabled",
-            "-o",
-            "feature@filesystem_limits=enabled",
-            "-o",
-            "feature@hole_birth=enabled",
-            "-o",
-            "feature@large_blocks=enabled",
-            "-o",
-            "feature@lz4_compress=enabled",
-            "-o",
-            "feature@spacemap_histogram=enabled",
-            "-O",
-            "mountpoint=none",
-            "-O",
-            "compression=lz4",
-            "-O",
-            "atime=off",
-            ZFS_BPOOL_NAME,
-            device,
-        ],
-    )
-    .map(|_| ())
-    .map_err(|e| {
+    let mut args = vec![
+        "create",
+        "-f",
+        "-d",
+        "-o",
+        "ashift=12",
+        "-o",
+        "feature@async_destroy=enabled",
+        "-o",
+        "feature@bookmarks=enabled",
+        "-o",
+        "feature@embedded_data=enabled",
+        "-o",
+        "feature@empty_bpobj=enabled",
+        "-o",
+        "feature@enabled_txg=enabled",
+        "-o",
+        "feature@extensible_dataset=enabled",
+        "-o",
+        "feature@filesystem_limits=enabled",
+        "-o",
+        "feature@hole_birth=enabled",
+        "-o",
+        "feature@large_blocks=enabled",
+        "-o",
+        "feature@lz4_compress=enabled",
+        "-o",
+        "feature@spacemap_histogram=enabled",
+        "-O",
+        "mountpoint=none",
+        "-O",
+        "compression=lz4",
+    ];
+    args.extend(atime_args);
+    args.extend([ZFS_BPOOL_NAME, device]);
+    cmd.run("zpool", &args).map(|_| ()).map_err(|e| {
         DeploytixError::FilesystemError(format!("Failed to create ZFS boot pool: {}", e))
     })?;
 
@@ -423,32 +975,32 @@ fn create_zfs_boot_pool(cmd: &CommandRunner, device: &str) -> Result<()> {
     })
 }
 
-/// Standard ZFS dataset layout for a system installation.
+/// Standard ZFS dataset layout (relative to the data pool), used when
+/// `zfs.datasets` is empty.
 ///
 /// All datasets use `mountpoint=legacy` so they can be managed via fstab.
 pub const ZFS_DATASETS: &[(&str, &str)] = &[
-    ("rpool/ROOT", "/"),
-    ("rpool/home", "/home"),
-    ("rpool/var", "/var"),
-    ("rpool/var/log", "/var/log"),
+    ("ROOT", "/"),
+    ("home", "/home"),
+    ("var", "/var"),
+    ("var/log", "/var/log"),
 ];
 
-/// Create the standard ZFS dataset hierarchy under an existing data pool.
-pub fn create_zfs_datasets(cmd: &CommandRunner) -> Result<()> {
-    info!("Creating ZFS datasets under {}", ZFS_RPOOL_NAME);
+/// Create the configured ZFS dataset hierarchy under an existing data pool.
+/// Intermediate datasets are created as needed (`zfs create -p`).
+pub fn create_zfs_datasets(cmd: &CommandRunner, zfs: &ZfsConfig) -> Result<()> {
+    info!("Creating ZFS datasets under {}", zfs.pool_name);
+    let datasets = zfs_datasets(zfs);
 
     if cmd.is_dry_run() {
-        for (ds, mp) in ZFS_DATASETS {
-            println!(
-                "  [dry-run] zfs create -o mountpoint=legacy {} (→ {})",
-                ds, mp
-            );
+        for (ds, _) in &datasets {
+            cmd.log_dry_run(&format!("zfs create -p -o mountpoint=legacy {}", ds));
         }
         return Ok(());
     }
 
-    for (ds, mp) in ZFS_DATASETS {
-        cmd.run("zfs", &["create", "-o", "mountpoint=legacy", ds])
+    for (ds, mp) in &datasets {
+        cmd.run("zfs", &["create", "-p", "-o", "mountpoint=legacy", ds])
             .map(|_| ())
             .map_err(|e| {
                 DeploytixError::FilesystemError(format!(
@@ -463,30 +1015,20 @@ pub fn create_zfs_datasets(cmd: &CommandRunner) -> Result<()> {
 }
 
 /// Mount all ZFS datasets under the given install root (shallowest first).
-pub fn mount_zfs_datasets(cmd: &CommandRunner, install_root: &str) -> Result<()> {
+pub fn mount_zfs_datasets(cmd: &CommandRunner, zfs: &ZfsConfig, install_root: &str) -> Result<()> {
     info!("Mounting ZFS datasets to {}", install_root);
 
-    // ZFS_DATASETS is already ordered shallowest-first
-    for (ds, mp) in ZFS_DATASETS {
-        let target = if *mp == "/" {
+    for (ds, mp) in zfs_datasets(zfs) {
+        let target = if mp == "/" {
             install_root.to_string()
         } else {
             format!("{}{}", install_root, mp)
         };
 
+        ensure_mounted(cmd, &ds, &target, Some("zfs"), None).map_err(|e| {
+            DeploytixError::FilesystemError(format!("Failed to mount ZFS dataset {}: {}", ds, e))
+        })?;
         if !cmd.is_dry_run() {
-            fs::create_dir_all(&target)?;
-        }
-
-        if cmd.is_dry_run() {
-            println!("  [dry-run] mount -t zfs {} {}", ds, target);
-        } else {
-            cmd.run("mount", &["-t", "zfs", ds, &target]).map_err(|e| {
-                DeploytixError::FilesystemError(format!(
-                    "Failed to mount ZFS dataset {}: {}",
-                    ds, e
-                ))
-            })?;
             info!("Mounted {} to {}", ds, target);
         }
     }
@@ -502,28 +1044,23 @@ pub fn mount_zfs_boot(cmd: &CommandRunner, install_root: &str) -> Result<()> {
         ZFS_BOOT_DATASET, target
     );
 
-    if !cmd.is_dry_run() {
-        fs::create_dir_all(&target)?;
-    }
-
-    if cmd.is_dry_run() {
-        println!("  [dry-run] mount -t zfs {} {}", ZFS_BOOT_DATASET, target);
-        return Ok(());
-    }
-
-    cmd.run("mount", &["-t", "zfs", ZFS_BOOT_DATASET, &target])
-        .map(|_| ())
-        .map_err(|e| {
-            DeploytixError::FilesystemError(format!("Failed to mount ZFS boot dataset: {}", e))
-        })
+    ensure_mounted(cmd, ZFS_BOOT_DATASET, &target, Some("zfs"), None).map_err(|e| {
+        DeploytixError::FilesystemError(format!("Failed to mount ZFS boot dataset: {}", e))
+    })
 }
 
-/// Export (unmount) ZFS pools during cleanup.
-pub fn export_zfs_pools(cmd: &CommandRunner) -> Result<()> {
+/// Export (unmount) the pools `disk` uses during cleanup: the boot pool
+/// when /boot is ZFS, and the `pool_name` data pool when the data
+/// filesystem is.
+pub fn export_zfs_pools(cmd: &CommandRunner, disk: &DiskConfig, pool_name: &str) -> Result<()> {
     info!("Exporting ZFS pools");
     // Export boot pool first, then data pool
-    let _ = cmd.run("zpool", &["export", ZFS_BPOOL_NAME]);
-    let _ = cmd.run("zpool", &["export", ZFS_RPOOL_NAME]);
+    if disk.boot_filesystem == Filesystem::Zfs {
+        let _ = cmd.run("zpool", &["export", ZFS_BPOOL_NAME]);
+    }
+    if disk.filesystem == Filesystem::Zfs {
+        let _ = cmd.run("zpool", &["export", pool_name]);
+    }
     Ok(())
 }
 
@@ -545,14 +1082,14 @@ pub fn create_btrfs_subvolumes(
     );
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] mount -t btrfs {} {}", device, fs_mount);
+        cmd.log_dry_run(&format!("mount -t btrfs {} {}", device, fs_mount));
         for sv in subvolumes {
-            println!(
-                "  [dry-run] btrfs subvolume create {}/{}",
-                fs_mount, sv.name
-            );
+            cmd.log_dry_run(&format!("btrfs subvolume create {}/{}", fs_mount, sv.name));
+            if sv.nocow {
+                cmd.log_dry_run(&format!("chattr +C {}/{}", fs_mount, sv.name));
+            }
         }
-        println!("  [dry-run] umount {}", fs_mount);
+        cmd.log_dry_run(&format!("umount {}", fs_mount));
         return Ok(());
     }
 
@@ -576,6 +1113,16 @@ pub fn create_btrfs_subvolumes(
                 ))
             })?;
         info!("Created subvolume: {}", sv.name);
+
+        // New files in a nocow subvolume inherit +C from its directory
+        if sv.nocow {
+            cmd.run("chattr", &["+C", &subvol_path]).map_err(|e| {
+                DeploytixError::FilesystemError(format!(
+                    "Failed to disable copy-on-write on {}: {}",
+                    sv.name, e
+                ))
+            })?;
+        }
     }
 
     // Unmount from filesystem mountpoint
@@ -596,10 +1143,10 @@ pub fn mount_btrfs_subvolumes(
 
     if cmd.is_dry_run() {
         for sv in subvolumes {
-            println!(
-                "  [dry-run] mount -t btrfs -o subvol={},{} {} {}{}",
+            cmd.log_dry_run(&format!(
+                "mount -t btrfs -o subvol={},{} {} {}{}",
                 sv.name, sv.mount_options, device, install_root, sv.mount_point
-            );
+            ));
         }
         return Ok(());
     }
@@ -615,18 +1162,195 @@ pub fn mount_btrfs_subvolumes(
 
     for sv in &sorted_subvolumes {
         let target = format!("{}{}", install_root, sv.mount_point);
-        fs::create_dir_all(&target)?;
-
         let options = format!("subvol={},{}", sv.name, sv.mount_options);
         // Pass -t btrfs explicitly: same reason as create_btrfs_subvolumes —
         // bypasses stale blkid/udev cache that may report crypto_LUKS.
-        cmd.run("mount", &["-t", "btrfs", "-o", &options, device, &target])
-            .map_err(|e| {
-                DeploytixError::FilesystemError(format!("Failed to mount {}: {}", sv.name, e))
-            })?;
+        ensure_mounted(cmd, device, &target, Some("btrfs"), Some(&options)).map_err(|e| {
+            DeploytixError::FilesystemError(format!("Failed to mount {}: {}", sv.name, e))
+        })?;
         info!("Mounted {} to {}", sv.name, target);
     }
 
     info!("All subvolumes mounted successfully");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_jobs_all_run_and_first_error_wins() {
+        let ran = Mutex::new(Vec::new());
+        let jobs: Vec<FormatJob> = (1..=4)
+            .map(|n| {
+                let ran = &ran;
+                FormatJob::new(format!("/dev/sdx{}", n), move |path| {
+                    ran.lock().unwrap().push(path.to_string());
+                    if n % 2 == 0 {
+                        Err(DeploytixError::FilesystemError(path.to_string()))
+                    } else {
+                        Ok(())
+                    }
+                })
+            })
+            .collect();
+
+        let err = run_format_jobs(jobs, 3).unwrap_err();
+        assert!(err.to_string().contains("/dev/sdx2"));
+        assert_eq!(ran.into_inner().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn discard_is_skipped_only_on_rotational_disks() {
+        for fs in [
+            Filesystem::Ext4,
+            Filesystem::Btrfs,
+            Filesystem::Xfs,
+            Filesystem::F2fs,
+        ] {
+            assert!(mkfs_discard_args(&fs, false).is_empty());
+            assert!(!mkfs_discard_args(&fs, true).is_empty(), "{:?}", fs);
+        }
+        assert!(mkfs_discard_args(&Filesystem::Zfs, true).is_empty());
+    }
+
+    #[test]
+    fn btrfs_mixed_follows_size_unless_overridden() {
+        let gib = 1024 * 1024 * 1024;
+        assert!(use_btrfs_mixed(None, Some(8 * gib)));
+        assert!(!use_btrfs_mixed(None, Some(64 * gib)));
+        assert!(!use_btrfs_mixed(None, None));
+        assert!(!use_btrfs_mixed(Some(false), Some(8 * gib)));
+        assert!(use_btrfs_mixed(Some(true), Some(64 * gib)));
+    }
+
+    #[test]
+    fn tuning_args_follow_filesystem() {
+        use crate::config::{Ext4Options, XfsOptions};
+        let opts = FilesystemOptions {
+            ext4: Some(Ext4Options {
+                reserved_percent: Some(1),
+                inode_ratio: Some(65536),
+                feature_64bit: Some(false),
+            }),
+            xfs: Some(XfsOptions {
+                block_size: Some(4096),
+            }),
+            f2fs: None,
+        };
+        assert_eq!(
+            mkfs_tuning_args(&Filesystem::Ext4, &opts),
+            vec!["-m", "1", "-i", "65536", "-O", "^64bit"]
+        );
+        assert_eq!(
+            mkfs_tuning_args(&Filesystem::Xfs, &opts),
+            vec!["-b", "size=4096"]
+        );
+        assert!(mkfs_tuning_args(&Filesystem::F2fs, &opts).is_empty());
+        assert!(mkfs_tuning_args(&Filesystem::Ext4, &FilesystemOptions::default()).is_empty());
+        assert!(validate_filesystem_options(&opts).is_ok());
+    }
+
+    #[test]
+    fn tuning_ranges_are_validated() {
+        let parse = |toml: &str| toml::from_str::<FilesystemOptions>(toml).unwrap();
+        assert!(validate_filesystem_options(&parse("[ext4]\nreserved_percent = 60")).is_err());
+        assert!(validate_filesystem_options(&parse("[ext4]\ninode_ratio = 512")).is_err());
+        assert!(validate_filesystem_options(&parse("[xfs]\nblock_size = 3000")).is_err());
+        assert!(check_xfs_block_size(4096, 4096).is_ok());
+        assert!(check_xfs_block_size(512, 4096).is_ok());
+        assert!(check_xfs_block_size(8192, 4096).is_err());
+        assert!(check_xfs_block_size(65536, 65536).is_ok());
+        assert!(check_xfs_block_size(256, 4096).is_err());
+        assert!(validate_filesystem_options(&parse("[f2fs]\nextra_options = [\"-O a\"]")).is_err());
+        assert!(
+            validate_filesystem_options(&parse("[f2fs]\nextra_options = [\"-O\", \"a\"]")).is_ok()
+        );
+    }
+    #[test]
+    fn ashift_follows_physical_sector_size() {
+        assert_eq!(ashift_for_sector_size(512), 12);
+        assert_eq!(ashift_for_sector_size(4096), 12);
+        assert_eq!(ashift_for_sector_size(8192), 13);
+        assert_eq!(ashift_for_sector_size(1 << 20), 16);
+    }
+
+    #[test]
+    fn zfs_datasets_are_prefixed_and_ordered() {
+        let mut zfs = ZfsConfig::default();
+        assert_eq!(zfs_root_dataset(&zfs), "rpool/ROOT");
+        assert!(validate_zfs_config(&zfs).is_ok());
+
+        zfs.pool_name = "tank".to_string();
+        zfs.datasets = [("data/home", "/home"), ("root", "/"), ("data", "/srv")]
+            .iter()
+            .map(|(d, m)| (d.to_string(), m.to_string()))
+            .collect();
+        assert_eq!(
+            zfs_datasets(&zfs),
+            vec![
+                ("tank/root".to_string(), "/".to_string()),
+                ("tank/data/home".to_string(), "/home".to_string()),
+                ("tank/data".to_string(), "/srv".to_string()),
+            ]
+        );
+        assert_eq!(zfs_root_dataset(&zfs), "tank/root");
+        assert!(validate_zfs_config(&zfs).is_ok());
+    }
+
+    #[test]
+    fn zfs_pools_use_configured_atime() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let cmd = CommandRunner::new(true).with_recorder(tx);
+        let zfs = ZfsConfig {
+            atime: ZfsAtime::Relatime,
+            ..ZfsConfig::default()
+        };
+        create_zfs_pool(&cmd, "/dev/sda2", &zfs, None).unwrap();
+        format_boot_partition(
+            &cmd,
+            "/dev/sda1",
+            &Filesystem::Zfs,
+            "BOOT",
+            false,
+            zfs.atime,
+        )
+        .unwrap();
+
+        let commands: Vec<String> = rx.try_iter().map(|r| r.command).collect();
+        assert_eq!(commands.len(), 2);
+        for command in &commands {
+            assert!(
+                command.contains("-O atime=on -O relatime=on"),
+                "{}",
+                command
+            );
+            assert!(!command.contains("atime=off"), "{}", command);
+        }
+        assert_eq!(ZfsAtime::default().properties(), ["atime=off"]);
+    }
+
+    #[test]
+    fn zfs_config_is_validated() {
+        let mut zfs = ZfsConfig {
+            ashift: Some(17),
+            ..ZfsConfig::default()
+        };
+        assert!(validate_zfs_config(&zfs).is_err());
+        zfs.ashift = Some(9);
+        assert!(validate_zfs_config(&zfs).is_ok());
+
+        zfs.datasets.insert("home".to_string(), "/home".to_string());
+        assert!(validate_zfs_config(&zfs).is_err(), "no dataset at /");
+        zfs.datasets.insert("ROOT".to_string(), "/".to_string());
+        assert!(validate_zfs_config(&zfs).is_ok());
+        zfs.datasets
+            .insert("bad name".to_string(), "/srv".to_string());
+        assert!(validate_zfs_config(&zfs).is_err());
+
+        zfs.datasets.clear();
+        zfs.pool_name = "bpool".to_string();
+        assert!(validate_zfs_config(&zfs).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Disk image targets
+//!
+//! Installs into a regular file instead of a physical disk by attaching it
+//! to a loop device with partition scanning, so `partition_path` yields
+//! `/dev/loopNp1`, ... and the rest of the pipeline runs unchanged.
+
+use crate::utils::command::CommandRunner;
+use crate::utils::error::{DeploytixError, Result};
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// A disk image attached to a loop device for the duration of an install.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskImage {
+    /// Path of the image file
+    pub path: String,
+    /// Loop device the image is attached to (e.g., /dev/loop0)
+    pub loop_device: String,
+}
+
+/// Kernel name of a loop device path (`/dev/loop3` -> `loop3`), or `None`
+/// when `device` is not a loop device.
+pub fn loop_name(device: &str) -> Option<&str> {
+    let name = device.strip_prefix("/dev/")?;
+    let number = name.strip_prefix("loop")?;
+    (!number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())).then_some(name)
+}
+
+/// Whether `device` is a loop device.
+pub fn is_loop_device(device: &str) -> bool {
+    loop_name(device).is_some()
+}
+
+/// File backing an attached loop device.
+pub fn loop_backing_file(device: &str) -> Option<String> {
+    let name = loop_name(device)?;
+    fs::read_to_string(format!("/sys/block/{}/loop/backing_file", name))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// Refuse a loop device attached without partition scanning: the kernel
+/// would never create the `pN` nodes for the partitions we write.
+pub fn check_loop_partscan(device: &str) -> Result<()> {
+    let Some(name) = loop_name(device) else {
+        return Ok(());
+    };
+    let partscan = fs::read_to_string(format!("/sys/block/{}/loop/partscan", name))
+        .map(|s| s.trim() == "1")
+        .unwrap_or(true);
+    if partscan {
+        Ok(())
+    } else {
+        Err(DeploytixError::ValidationError(format!(
+            "{} was attached without partition scanning; detach it and re-attach with \
+             `losetup --find --show --partscan <file>`, or pass --image instead",
+            device
+        )))
+    }
+}
+
+/// Attach `file` to the first free loop device and return its path.
+/// `partscan` makes the kernel create nodes for the partitions on it.
+pub fn attach_loop(cmd: &CommandRunner, file: &str, partscan: bool) -> Result<String> {
+    let mut args = vec!["--find", "--show"];
+    if partscan {
+        args.push("--partscan");
+    }
+    args.push(file);
+    let device = cmd
+        .run("losetup", &args)?
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default();
+    if device.is_empty() {
+        return Err(DeploytixError::CommandFailed {
+            command: format!("losetup {}", args.join(" ")),
+            stderr: "no loop device reported".to_string(),
+        });
+    }
+    Ok(device)
+}
+
+/// Detach a loop device, logging rather than failing when it is busy.
+pub fn detach_loop(cmd: &CommandRunner, device: &str) {
+    if let Err(e) = cmd.force_run("losetup", &["--detach", device]) {
+        warn!("Failed to detach {}: {}", device, e);
+    }
+}
+
+/// Largest `--image-size` accepted, in GiB (ext4's 16 TiB file size limit).
+const MAX_IMAGE_GIB: u64 = 16 * 1024;
+
+/// Image size in bytes for `--image-size` `gib`; zero and sizes above
+/// [`MAX_IMAGE_GIB`] are rejected.
+fn image_size_bytes(gib: u64) -> Result<u64> {
+    if !(1..=MAX_IMAGE_GIB).contains(&gib) {
+        return Err(DeploytixError::ValidationError(format!(
+            "--image-size {} GiB is out of range (1-{} GiB)",
+            gib, MAX_IMAGE_GIB
+        )));
+    }
+    Ok(gib * 1024 * 1024 * 1024)
+}
+
+/// Open the image at `path` for installation, creating it with `size_gib`
+/// GiB when it does not exist (the file is sparse, so only written blocks
+/// take space).  An existing image is reused as-is; `size_gib` then only
+/// grows it.
+pub fn open_image(cmd: &CommandRunner, path: &str, size_gib: Option<u64>) -> Result<DiskImage> {
+    let file_path = Path::new(path);
+    if file_path.starts_with("/dev") {
+        return Err(DeploytixError::ValidationError(format!(
+            "{} is a device node; pass it with --device instead of --image",
+            path
+        )));
+    }
+
+    let size_bytes = size_gib.map(image_size_bytes).transpose()?;
+    if file_path.exists() {
+        let len = fs::metadata(file_path)?.len();
+        if let Some(size) = size_bytes.filter(|&size| size > len) {
+            info!("Growing image {} to {} GiB", path, size_gib.unwrap_or(0));
+            fs::OpenOptions::new()
+                .write(true)
+                .open(file_path)?
+                .set_len(size)?;
+        }
+    } else {
+        let size = size_bytes.ok_or_else(|| {
+            DeploytixError::ValidationError(format!(
+                "image {} does not exist; pass --image-size to create it",
+                path
+            ))
+        })?;
+        if let Some(parent) = file_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        info!("Creating {} GiB image {}", size_gib.unwrap_or(0), path);
+        fs::File::create(file_path)?.set_len(size)?;
+    }
+
+    let loop_device = attach_loop(cmd, path, true)?;
+    info!("Attached image {} to {}", path, loop_device);
+    Ok(DiskImage {
+        path: path.to_string(),
+        loop_device,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_name_accepts_only_numbered_loop_devices() {
+        assert_eq!(loop_name("/dev/loop0"), Some("loop0"));
+        assert_eq!(loop_name("/dev/loop12"), Some("loop12"));
+        assert_eq!(loop_name("/dev/loop0p1"), None);
+        assert_eq!(loop_name("/dev/loop"), None);
+        assert_eq!(loop_name("/dev/sda"), None);
+        assert_eq!(loop_name("loop0"), None);
+    }
+
+    #[test]
+    fn device_nodes_are_not_accepted_as_images() {
+        let cmd = CommandRunner::new(true);
+        assert!(open_image(&cmd, "/dev/sda", Some(8)).is_err());
+    }
+
+    #[test]
+    fn image_size_is_range_checked() {
+        assert_eq!(image_size_bytes(8).unwrap(), 8 << 30);
+        assert!(image_size_bytes(0).is_err());
+        assert!(image_size_bytes(MAX_IMAGE_GIB + 1).is_err());
+        assert!(image_size_bytes(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn missing_image_needs_a_size() {
+        let cmd = CommandRunner::new(true);
+        let path = std::env::temp_dir().join("deploytix-missing-image-test.img");
+        let _ = fs::remove_file(&path);
+        let err = open_image(&cmd, path.to_str().unwrap(), None).unwrap_err();
+        assert!(err.to_string().contains("--image-size"));
+        assert!(!path.exists());
+    }
+}
@@ -5,9 +5,13 @@
 //! Layouts define the *partition table* only. Storage features (encryption,
 //! LVM thin, subvolumes) are applied as layers by the installer pipeline.
 
-use crate::config::{CustomPartitionEntry, DiskConfig, Filesystem, SwapType};
+use crate::config::{
+    CustomPartitionEntry, DiskConfig, EspMount, Filesystem, Firmware, SubvolumeSpec, SwapType,
+};
 use crate::disk::detection::get_ram_mib;
 use crate::utils::error::{DeploytixError, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
 
 /// GPT partition type GUIDs
 #[allow(dead_code)]
@@ -23,7 +27,7 @@ pub mod partition_types {
 }
 
 /// Btrfs subvolume definition
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SubvolumeDef {
     /// Subvolume name (e.g., "@", "@home")
     pub name: String,
@@ -31,41 +35,288 @@ pub struct SubvolumeDef {
     pub mount_point: String,
     /// Mount options
     pub mount_options: String,
+    /// Created without copy-on-write (`chattr +C`)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub nocow: bool,
+}
+
+/// Default btrfs mount options when `btrfs_mount_options` is unset
+pub const DEFAULT_BTRFS_BASE_OPTIONS: &str = "defaults,noatime";
+
+/// Default btrfs compression when `btrfs_compression` is unset
+pub const DEFAULT_BTRFS_COMPRESSION: &str = "zstd";
+
+/// Check a btrfs compression setting against what the kernel accepts:
+/// `zstd[:1-15]`, `zlib[:1-9]`, `lzo`, or `none`/`no` to disable.
+pub fn is_valid_btrfs_compression(value: &str) -> bool {
+    let (algo, level) = match value.split_once(':') {
+        Some((a, l)) => (a, Some(l)),
+        None => (value, None),
+    };
+    let max_level = match algo {
+        "zstd" => 15,
+        "zlib" => 9,
+        "lzo" | "none" | "no" => return level.is_none(),
+        _ => return false,
+    };
+    match level {
+        None => true,
+        Some(l) => l.parse::<u8>().is_ok_and(|n| (1..=max_level).contains(&n)),
+    }
+}
+
+/// Build the btrfs mount option string from the disk configuration.
+///
+/// Defaults to `defaults,noatime,compress=zstd` when nothing is set.
+pub fn btrfs_mount_options(disk_config: &DiskConfig) -> String {
+    let mut opts = disk_config
+        .btrfs_mount_options
+        .clone()
+        .unwrap_or_else(|| DEFAULT_BTRFS_BASE_OPTIONS.to_string());
+
+    let compression = disk_config
+        .btrfs_compression
+        .as_deref()
+        .unwrap_or(DEFAULT_BTRFS_COMPRESSION);
+    if compression != "none" && compression != "no" {
+        opts.push_str(&format!(",compress={}", compression));
+    }
+
+    if disk_config.btrfs_ssd == Some(true) {
+        opts.push_str(",ssd,discard=async");
+    }
+
+    opts
+}
+
+/// Check an f2fs compression setting: `zstd[:1-22]`, `lz4[:3-16]`
+/// (LZ4HC levels), `lzo`, `lzo-rle`, or `none` to disable.
+pub fn is_valid_f2fs_compression(value: &str) -> bool {
+    let (algo, level) = match value.split_once(':') {
+        Some((a, l)) => (a, Some(l)),
+        None => (value, None),
+    };
+    let levels = match algo {
+        "zstd" => 1..=22,
+        "lz4" => 3..=16,
+        "lzo" | "lzo-rle" | "none" => return level.is_none(),
+        _ => return false,
+    };
+    match level {
+        None => true,
+        Some(l) => l.parse::<u8>().is_ok_and(|n| levels.contains(&n)),
+    }
+}
+
+/// Whether f2fs data volumes are created with compression support.
+pub fn f2fs_compression_enabled(disk_config: &DiskConfig) -> bool {
+    disk_config.filesystem == Filesystem::F2fs
+        && disk_config
+            .f2fs_compression
+            .as_deref()
+            .is_some_and(|c| c != "none")
+}
+
+/// f2fs mount options with compression enabled for every file
+/// (`compress_extension=*`), or None when compression is off.
+pub fn f2fs_mount_options(disk_config: &DiskConfig) -> Option<String> {
+    if !f2fs_compression_enabled(disk_config) {
+        return None;
+    }
+    Some(format!(
+        "defaults,noatime,compress_algorithm={},compress_extension=*",
+        disk_config.f2fs_compression.as_deref()?
+    ))
+}
+
+/// Default btrfs mount options (`defaults,noatime,compress=zstd`)
+pub fn default_btrfs_mount_options() -> String {
+    format!(
+        "{},compress={}",
+        DEFAULT_BTRFS_BASE_OPTIONS, DEFAULT_BTRFS_COMPRESSION
+    )
 }
 
 /// Create standard btrfs subvolume definitions
 /// Following the common convention: @=root, @home, @usr, @var, @log
-pub fn standard_subvolumes() -> Vec<SubvolumeDef> {
-    let default_opts = "defaults,noatime,compress=zstd".to_string();
+pub fn standard_subvolumes(mount_options: &str) -> Vec<SubvolumeDef> {
+    let default_opts = mount_options.to_string();
     vec![
         SubvolumeDef {
             name: "@".to_string(),
             mount_point: "/".to_string(),
             mount_options: default_opts.clone(),
+            nocow: false,
         },
         SubvolumeDef {
             name: "@home".to_string(),
             mount_point: "/home".to_string(),
             mount_options: default_opts.clone(),
+            nocow: false,
         },
         SubvolumeDef {
             name: "@usr".to_string(),
             mount_point: "/usr".to_string(),
             mount_options: default_opts.clone(),
+            nocow: false,
         },
         SubvolumeDef {
             name: "@var".to_string(),
             mount_point: "/var".to_string(),
             mount_options: default_opts.clone(),
+            nocow: false,
         },
         SubvolumeDef {
             name: "@log".to_string(),
             mount_point: "/var/log".to_string(),
             mount_options: default_opts,
+            nocow: false,
         },
     ]
 }
 
+/// Subvolumes from `disk.btrfs_subvolumes`, parents before the subvolumes
+/// mounted inside them.  Those without their own options get
+/// `mount_options`.
+pub fn configured_subvolumes(specs: &[SubvolumeSpec], mount_options: &str) -> Vec<SubvolumeDef> {
+    let mut subvolumes: Vec<SubvolumeDef> = specs
+        .iter()
+        .map(|s| SubvolumeDef {
+            name: s.name.clone(),
+            mount_point: s.mount_point.clone(),
+            mount_options: s
+                .mount_options
+                .clone()
+                .unwrap_or_else(|| mount_options.to_string()),
+            nocow: false,
+        })
+        .collect();
+    subvolumes.sort_by_key(|s| (s.mount_point != "/", s.mount_point.matches('/').count()));
+    subvolumes
+}
+
+/// Check configured subvolumes: valid and distinct names, absolute and
+/// distinct mount points, and exactly one root subvolume named `@`
+/// (the kernel command line and the mountcrypt hook mount `subvol=@`).
+/// `/boot` has its own partition.
+pub fn validate_subvolumes(specs: &[SubvolumeSpec]) -> std::result::Result<(), String> {
+    let mut names = std::collections::HashSet::new();
+    let mut mounts = std::collections::HashSet::new();
+    for sv in specs {
+        let valid_name = !sv.name.is_empty()
+            && sv.name != "@boot"
+            && !sv
+                .name
+                .chars()
+                .any(|c| c == '/' || c == ',' || c.is_whitespace());
+        if !valid_name {
+            return Err(format!("Invalid btrfs subvolume name '{}'", sv.name));
+        }
+        let mount_point = sv.mount_point.as_str();
+        if !mount_point.starts_with('/')
+            || (mount_point != "/" && mount_point.ends_with('/'))
+            || mount_point.chars().any(char::is_whitespace)
+        {
+            return Err(format!(
+                "Subvolume '{}' mount point must be an absolute path, got '{}'",
+                sv.name, sv.mount_point
+            ));
+        }
+        if mount_point == "/boot" || mount_point.starts_with("/boot/") {
+            return Err(format!(
+                "Subvolume '{}' cannot be mounted at {} (/boot is a separate partition)",
+                sv.name, sv.mount_point
+            ));
+        }
+        if let Some(ref opts) = sv.mount_options {
+            if opts.is_empty()
+                || opts.chars().any(char::is_whitespace)
+                || opts.split(',').any(|o| o.starts_with("subvol"))
+            {
+                return Err(format!(
+                    "Invalid mount_options '{}' for subvolume '{}': must be a comma-separated list \
+                     without spaces or subvol=",
+                    opts, sv.name
+                ));
+            }
+        }
+        if !names.insert(sv.name.as_str()) {
+            return Err(format!("Duplicate btrfs subvolume name '{}'", sv.name));
+        }
+        if !mounts.insert(mount_point) {
+            return Err(format!(
+                "Duplicate btrfs subvolume mount point '{}'",
+                mount_point
+            ));
+        }
+    }
+    match specs.iter().find(|s| s.mount_point == "/") {
+        None => Err("btrfs_subvolumes must include a subvolume mounted at /".to_string()),
+        Some(root) if root.name != "@" => Err(format!(
+            "The subvolume mounted at / must be named '@', got '{}'",
+            root.name
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Subvolume holding snapper's snapshots of `/`
+pub const SNAPSHOTS_SUBVOLUME: &str = "@snapshots";
+
+/// Where [`SNAPSHOTS_SUBVOLUME`] is mounted
+pub const SNAPSHOTS_MOUNT_POINT: &str = "/.snapshots";
+
+/// `@snapshots` (→ /.snapshots), a sibling of `@` so that rolling back
+/// `@` leaves the snapshots in place.
+pub fn snapshots_subvolume(mount_options: &str) -> SubvolumeDef {
+    SubvolumeDef {
+        name: SNAPSHOTS_SUBVOLUME.to_string(),
+        mount_point: SNAPSHOTS_MOUNT_POINT.to_string(),
+        mount_options: mount_options.to_string(),
+        nocow: false,
+    }
+}
+
+/// Subvolume holding pacman's package cache
+pub const PKG_CACHE_SUBVOLUME: &str = "@pkg";
+
+/// Where [`PKG_CACHE_SUBVOLUME`] is mounted
+pub const PKG_CACHE_MOUNT_POINT: &str = "/var/cache/pacman/pkg";
+
+/// Subvolumes kept out of snapshots of `@` when snapshots are enabled, so
+/// that a rollback neither reverts the package cache nor loses logs:
+/// `@log` (→ /var/log) and `@pkg` (→ /var/cache/pacman/pkg), both without
+/// copy-on-write.
+pub fn snapshot_excluded_subvolumes(mount_options: &str) -> Vec<SubvolumeDef> {
+    [
+        ("@log", "/var/log"),
+        (PKG_CACHE_SUBVOLUME, PKG_CACHE_MOUNT_POINT),
+    ]
+    .into_iter()
+    .map(|(name, mount_point)| SubvolumeDef {
+        name: name.to_string(),
+        mount_point: mount_point.to_string(),
+        mount_options: mount_options.to_string(),
+        nocow: true,
+    })
+    .collect()
+}
+
+/// Add the [`snapshot_excluded_subvolumes`] to the subvolumes of the
+/// filesystem holding /var; one already mounted at the same place is
+/// switched to nocow instead.
+pub fn exclude_from_snapshots(subvolumes: &mut Vec<SubvolumeDef>, mount_options: &str) {
+    for excluded in snapshot_excluded_subvolumes(mount_options) {
+        match subvolumes
+            .iter_mut()
+            .find(|s| s.mount_point == excluded.mount_point)
+        {
+            Some(existing) => existing.nocow = true,
+            None => subvolumes.push(excluded),
+        }
+    }
+}
+
 /// Create btrfs subvolume definitions for a multi-volume encrypted container.
 ///
 /// Unlike `standard_subvolumes()` (which places all subvolumes on a single
@@ -75,24 +326,27 @@ pub fn standard_subvolumes() -> Vec<SubvolumeDef> {
 /// - Usr:  @usr (→ /usr)
 /// - Var:  @var (→ /var), @log (→ /var/log)
 /// - Home: @home (→ /home)
-pub fn multi_volume_subvolumes(volume_name: &str) -> Vec<SubvolumeDef> {
-    let default_opts = "defaults,noatime,compress=zstd".to_string();
+pub fn multi_volume_subvolumes(volume_name: &str, mount_options: &str) -> Vec<SubvolumeDef> {
+    let default_opts = mount_options.to_string();
     match volume_name {
         "Root" => vec![SubvolumeDef {
             name: "@".to_string(),
             mount_point: "/".to_string(),
             mount_options: default_opts,
+            nocow: false,
         }],
         "Var" => vec![
             SubvolumeDef {
                 name: "@var".to_string(),
                 mount_point: "/var".to_string(),
                 mount_options: default_opts.clone(),
+                nocow: false,
             },
             SubvolumeDef {
                 name: "@log".to_string(),
                 mount_point: "/var/log".to_string(),
                 mount_options: default_opts,
+                nocow: false,
             },
         ],
         other => {
@@ -101,13 +355,14 @@ pub fn multi_volume_subvolumes(volume_name: &str) -> Vec<SubvolumeDef> {
                 name: format!("@{}", name_lower),
                 mount_point: format!("/{}", name_lower),
                 mount_options: default_opts,
+                nocow: false,
             }]
         }
     }
 }
 
 /// A single partition definition
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PartitionDef {
     /// Partition number (1-based)
     pub number: u32,
@@ -139,7 +394,7 @@ pub struct PartitionDef {
 }
 
 /// Planned thin volume definition (saved when LVM thin collapses partitions)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[allow(dead_code)]
 pub struct PlannedThinVolume {
     /// Volume name (e.g., "root", "home")
@@ -151,12 +406,24 @@ pub struct PlannedThinVolume {
 }
 
 /// Computed partition layout for a specific disk
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ComputedLayout {
     pub partitions: Vec<PartitionDef>,
     pub total_mib: u64,
     /// Btrfs subvolumes (None for layouts without subvolumes)
     pub subvolumes: Option<Vec<SubvolumeDef>>,
+    /// Mount options for every btrfs mount (subvolumes, @boot, fstab)
+    pub btrfs_mount_options: String,
+    /// Mount options for f2fs data volumes when compression is enabled
+    /// (None keeps the plain defaults)
+    pub f2fs_mount_options: Option<String>,
+    /// Add `discard` to ext4, xfs and f2fs fstab entries (SSD targets)
+    pub discard: bool,
+    /// Data partitions formatted with something other than
+    /// `disk.filesystem`, keyed by partition name
+    pub filesystem_overrides: BTreeMap<String, Filesystem>,
+    /// fstab mount options set on the partition entries, keyed by mount point
+    pub mount_option_overrides: BTreeMap<String, String>,
     /// When LVM thin is applied, the data partitions are collapsed into a
     /// single LVM PV partition and the original volumes are stored here
     /// as planned thin volumes.
@@ -170,16 +437,84 @@ impl ComputedLayout {
         self.subvolumes.is_some() && !self.subvolumes.as_ref().unwrap().is_empty()
     }
 
+    /// Whether the layout has an `@snapshots` subvolume
+    pub fn has_snapshots(&self) -> bool {
+        self.subvolumes
+            .iter()
+            .flatten()
+            .any(|s| s.name == SNAPSHOTS_SUBVOLUME)
+    }
+
+    /// Subvolumes of a multi-volume encrypted container: those of
+    /// [`multi_volume_subvolumes`], plus `@snapshots` and any
+    /// snapshot-excluded subvolumes the layout puts on Root, or `@pkg` on
+    /// Var when the layout has snapshots.
+    pub fn container_subvolumes(&self, volume_name: &str) -> Vec<SubvolumeDef> {
+        let mut svols = multi_volume_subvolumes(volume_name, &self.btrfs_mount_options);
+        match volume_name {
+            "Root" => svols.extend(
+                self.subvolumes
+                    .iter()
+                    .flatten()
+                    .filter(|s| s.name == SNAPSHOTS_SUBVOLUME || s.nocow)
+                    .cloned(),
+            ),
+            "Var" if self.has_snapshots() => {
+                exclude_from_snapshots(&mut svols, &self.btrfs_mount_options)
+            }
+            _ => {}
+        }
+        svols
+    }
+
+    /// Filesystem of the data partition `name`: its override, or `default`.
+    pub fn partition_filesystem<'a>(
+        &'a self,
+        name: &str,
+        default: &'a Filesystem,
+    ) -> &'a Filesystem {
+        self.filesystem_overrides.get(name).unwrap_or(default)
+    }
+
+    /// Mount options for the data volume `name`: the f2fs compression
+    /// options, unless the partition is overridden to another filesystem.
+    pub fn data_mount_options(&self, name: &str) -> Option<&str> {
+        match self.filesystem_overrides.get(name) {
+            Some(fs) if *fs != Filesystem::F2fs => None,
+            _ => self.f2fs_mount_options.as_deref(),
+        }
+    }
+
+    /// fstab options for `mount_point`: those set on its partition entry,
+    /// or `defaults`.
+    pub fn fstab_mount_options(&self, mount_point: &str, defaults: &str) -> String {
+        self.mount_option_overrides
+            .get(mount_point)
+            .map_or_else(|| defaults.to_string(), String::clone)
+    }
+
     /// Check if this layout has LVM thin provisioning
     #[allow(dead_code)]
     pub fn uses_lvm_thin(&self) -> bool {
         self.planned_thin_volumes.is_some()
     }
+
+    /// Mount point of the EFI System Partition (`/boot/efi` or `/efi`)
+    pub fn esp_mount_point(&self) -> &str {
+        self.partitions
+            .iter()
+            .find(|p| p.is_efi)
+            .and_then(|p| p.mount_point.as_deref())
+            .unwrap_or_else(|| EspMount::default().path())
+    }
 }
 
 /// System partition sizes
 pub const EFI_MIB: u64 = 512;
+pub const BIOS_BOOT_MIB: u64 = 1;
 pub const BOOT_MIB: u64 = 2048;
+/// Smallest size accepted for the remainder partition.
+pub const MIN_REMAINDER_MIB: u64 = 1024;
 
 /// Swap limits
 const SWAP_MIN_MIB: u64 = 4096; // 4 GiB
@@ -231,20 +566,32 @@ pub fn mount_point_to_subvol_name(mount_point: &str) -> String {
     }
 }
 
+/// Smallest disk any layout fits on: loader, Boot and a minimal remainder
+/// partition, without swap.
+pub fn minimal_layout_mib(firmware: Firmware) -> u64 {
+    let loader_mib = match firmware {
+        Firmware::Uefi => EFI_MIB,
+        Firmware::Bios => BIOS_BOOT_MIB,
+    };
+    loader_mib + BOOT_MIB + MIN_REMAINDER_MIB
+}
+
 /// Compute partition layout from user-defined entries.
 ///
-/// Always prepends EFI + Boot. Swap is prepended only when
-/// `use_swap_partition` is true. User entries follow as data partitions.
-/// Exactly one entry may have `size_mib = 0` (remainder of disk).
+/// Always prepends a loader partition + Boot: the EFI System Partition on
+/// UEFI, or a raw BIOS boot partition for GRUB's core.img on BIOS.  Swap is
+/// prepended only when `use_swap_partition` is true. User entries follow as
+/// data partitions.  Exactly one entry may have `size_mib = 0` (remainder of
+/// disk).
 pub fn compute_layout_from_entries(
     disk_mib: u64,
+    firmware: Firmware,
     encryption: bool,
     use_swap_partition: bool,
     entries: &[CustomPartitionEntry],
 ) -> Result<ComputedLayout> {
-    let ram_mib = get_ram_mib();
     let swap_mib = if use_swap_partition {
-        calculate_swap_mib(ram_mib)
+        calculate_swap_mib(get_ram_mib()?)
     } else {
         0
     };
@@ -258,13 +605,23 @@ pub fn compute_layout_from_entries(
     }
 
     // Calculate reserved space for system partitions
-    let reserved_mib = EFI_MIB + BOOT_MIB + swap_mib;
+    let loader_mib = match firmware {
+        Firmware::Uefi => EFI_MIB,
+        Firmware::Bios => BIOS_BOOT_MIB,
+    };
+    let reserved_mib = loader_mib + BOOT_MIB + swap_mib;
 
     // Calculate total fixed size from user entries
     let fixed_total: u64 = entries.iter().map(|e| e.size_mib).sum();
 
     // Validate disk has enough space
-    let min_required = reserved_mib + fixed_total + if remainder_count == 0 { 0 } else { 1024 }; // 1GiB minimum for remainder
+    let min_required = reserved_mib
+        + fixed_total
+        + if remainder_count == 0 {
+            0
+        } else {
+            MIN_REMAINDER_MIB
+        };
     if disk_mib < min_required {
         return Err(DeploytixError::DiskTooSmall {
             size_mib: disk_mib,
@@ -273,8 +630,8 @@ pub fn compute_layout_from_entries(
     }
 
     // Build system partitions
-    let mut partitions = vec![
-        PartitionDef {
+    let loader = match firmware {
+        Firmware::Uefi => PartitionDef {
             number: 1,
             name: "EFI".to_string(),
             size_mib: EFI_MIB,
@@ -288,6 +645,25 @@ pub fn compute_layout_from_entries(
             attributes: None,
             subvolume_name: None,
         },
+        // Raw, unformatted area where grub-install --target=i386-pc embeds
+        // core.img on GPT disks.
+        Firmware::Bios => PartitionDef {
+            number: 1,
+            name: "BIOS".to_string(),
+            size_mib: BIOS_BOOT_MIB,
+            type_guid: partition_types::BIOS_BOOT.to_string(),
+            mount_point: None,
+            is_swap: false,
+            is_efi: false,
+            is_luks: false,
+            is_bios_boot: true,
+            is_boot_fs: false,
+            attributes: None,
+            subvolume_name: None,
+        },
+    };
+    let mut partitions = vec![
+        loader,
         PartitionDef {
             number: 2,
             name: "BOOT".to_string(),
@@ -368,6 +744,11 @@ pub fn compute_layout_from_entries(
         partitions,
         total_mib: disk_mib,
         subvolumes: None,
+        btrfs_mount_options: default_btrfs_mount_options(),
+        f2fs_mount_options: None,
+        discard: false,
+        filesystem_overrides: BTreeMap::new(),
+        mount_option_overrides: BTreeMap::new(),
         planned_thin_volumes: None,
     })
 }
@@ -379,7 +760,8 @@ pub fn compute_layout_from_entries(
 /// works regardless of whether encryption is enabled.
 ///
 /// When `use_lvm_thin` is true, `apply_lvm_thin_to_layout` is called
-/// automatically to collapse data partitions into a single LVM PV.
+/// automatically to collapse data partitions into a single LVM PV; ZFS
+/// collapses them into one pool partition (`apply_zfs_pool_to_layout`).
 pub fn compute_layout_from_config(
     disk_config: &DiskConfig,
     disk_mib: u64,
@@ -388,7 +770,8 @@ pub fn compute_layout_from_config(
 
     let mut layout = compute_layout_from_entries(
         disk_mib,
-        disk_config.encryption,
+        disk_config.firmware(),
+        disk_config.uses_luks(),
         use_swap_partition,
         &disk_config.partitions,
     )?;
@@ -396,10 +779,38 @@ pub fn compute_layout_from_config(
     // Apply encryption flags to data partitions.
     // When LVM thin is active, encryption is applied to the single LVM PV
     // partition by apply_lvm_thin_to_layout, not to individual data partitions.
-    if disk_config.encryption && !disk_config.use_lvm_thin {
+    if disk_config.uses_luks() && !disk_config.use_lvm_thin {
         apply_encryption_flags(&mut layout);
     }
 
+    let esp = disk_config.esp_mountpoint.path();
+    for part in layout.partitions.iter_mut().filter(|p| p.is_efi) {
+        part.mount_point = Some(esp.to_string());
+    }
+
+    // Btrfs mount options (compression, noatime, ssd) shared by every btrfs
+    // mount: subvolumes, @boot, fstab and the mountcrypt hook.
+    layout.btrfs_mount_options = btrfs_mount_options(disk_config);
+    layout.f2fs_mount_options = f2fs_mount_options(disk_config);
+    layout.discard = disk_config.discard == Some(true);
+
+    // Partitions whose filesystem differs from the global one
+    layout.filesystem_overrides = disk_config
+        .partitions
+        .iter()
+        .filter_map(|e| match e.filesystem {
+            Some(ref fs) if *fs != disk_config.filesystem => {
+                Some((e.effective_label(), fs.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    layout.mount_option_overrides = disk_config
+        .partitions
+        .iter()
+        .filter_map(|e| Some((e.mount_point.clone(), e.mount_options.clone()?)))
+        .collect();
+
     // Apply btrfs subvolumes unconditionally when the filesystem is btrfs.
     //
     // Each data partition gets its own named subvolume (e.g. "@" for root,
@@ -424,20 +835,27 @@ pub fn compute_layout_from_config(
 
         if non_root_data_mounts.is_empty() {
             // Single-partition layout: all subvolumes live on ROOT.
-            layout.subvolumes = Some(standard_subvolumes());
+            layout.subvolumes = Some(match disk_config.btrfs_subvolumes {
+                Some(ref specs) => configured_subvolumes(specs, &layout.btrfs_mount_options),
+                None => standard_subvolumes(&layout.btrfs_mount_options),
+            });
         } else {
             // Multi-partition layout: ROOT gets only "@"; every other data
             // partition gets its own "@<name>" subvolume.
             layout.subvolumes = Some(vec![SubvolumeDef {
                 name: "@".to_string(),
                 mount_point: "/".to_string(),
-                mount_options: "defaults,noatime,compress=zstd".to_string(),
+                mount_options: layout.btrfs_mount_options.clone(),
+                nocow: false,
             }]);
-            let default_opts = "defaults,noatime,compress=zstd".to_string();
+            let default_opts = layout.btrfs_mount_options.clone();
             for part in &mut layout.partitions {
                 if part.is_efi || part.is_boot_fs || part.is_swap || part.is_bios_boot {
                     continue;
                 }
+                if layout.filesystem_overrides.contains_key(&part.name) {
+                    continue;
+                }
                 if let Some(ref mp) = part.mount_point.clone() {
                     if mp != "/" {
                         part.subvolume_name = Some(mount_point_to_subvol_name(mp));
@@ -449,6 +867,16 @@ pub fn compute_layout_from_config(
             }
         }
 
+        if disk_config.uses_btrfs_snapshots() {
+            if let Some(ref mut subvolumes) = layout.subvolumes {
+                // A separate /var partition carries @log and @pkg itself
+                if !non_root_data_mounts.contains("/var") {
+                    exclude_from_snapshots(subvolumes, &layout.btrfs_mount_options);
+                }
+                subvolumes.push(snapshots_subvolume(&layout.btrfs_mount_options));
+            }
+        }
+
         // Clear the ROOT partition's mount_point so it is mounted via its
         // subvolume rather than as a raw filesystem.
         for part in &mut layout.partitions {
@@ -458,9 +886,28 @@ pub fn compute_layout_from_config(
         }
     }
 
+    // ZFS: one pool carries every data mount point as a dataset
+    if disk_config.filesystem == Filesystem::Zfs {
+        layout = apply_zfs_pool_to_layout(layout);
+    }
+
     // Apply LVM thin: collapse data partitions into a single LVM PV
     if disk_config.use_lvm_thin {
         layout = apply_lvm_thin_to_layout(layout, disk_config.encryption)?;
+
+        // Explicit thin volumes replace the ones derived from the partitions
+        if let Some(ref specs) = disk_config.lvm_thin_volumes {
+            layout.planned_thin_volumes = Some(
+                specs
+                    .iter()
+                    .map(|s| PlannedThinVolume {
+                        name: s.name.clone(),
+                        virtual_size: s.virtual_size.clone(),
+                        mount_point: s.mount_point.clone(),
+                    })
+                    .collect(),
+            );
+        }
     }
 
     Ok(layout)
@@ -475,6 +922,31 @@ fn apply_encryption_flags(layout: &mut ComputedLayout) {
     }
 }
 
+/// Transform a layout by collapsing data partitions into the root partition,
+/// which then takes the rest of the disk and holds the ZFS data pool.
+///
+/// The pool's datasets (see `formatting::zfs_datasets`) replace the other
+/// data partitions, which would otherwise be left unused.
+pub fn apply_zfs_pool_to_layout(layout: ComputedLayout) -> ComputedLayout {
+    let (mut partitions, data): (Vec<PartitionDef>, Vec<PartitionDef>) = layout
+        .partitions
+        .into_iter()
+        .partition(|p| p.is_efi || p.is_boot_fs || p.is_swap || p.is_bios_boot);
+    let next_part_num = partitions.iter().map(|p| p.number).max().unwrap_or(0) + 1;
+    if let Some(mut root) = data
+        .into_iter()
+        .find(|p| p.mount_point.as_deref() == Some("/"))
+    {
+        root.number = next_part_num;
+        root.size_mib = 0; // Remainder
+        partitions.push(root);
+    }
+    ComputedLayout {
+        partitions,
+        ..layout
+    }
+}
+
 /// Transform a layout by collapsing data partitions into a single LVM PV partition.
 ///
 /// System partitions (EFI, Boot, Swap) are preserved. Data partitions are
@@ -545,14 +1017,140 @@ pub fn apply_lvm_thin_to_layout(
         partitions: system_parts,
         total_mib: layout.total_mib,
         subvolumes: layout.subvolumes,
+        btrfs_mount_options: layout.btrfs_mount_options,
+        f2fs_mount_options: layout.f2fs_mount_options,
+        discard: layout.discard,
+        filesystem_overrides: layout.filesystem_overrides,
+        mount_option_overrides: layout.mount_option_overrides,
         planned_thin_volumes: Some(planned_volumes),
     })
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn esp_mount_point_follows_config() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.firmware = Some(Firmware::Uefi);
+        let layout = compute_layout_from_config(&disk, 256 * 1024).unwrap();
+        assert_eq!(layout.esp_mount_point(), "/boot/efi");
+
+        disk.esp_mountpoint = EspMount::Efi;
+        let layout = compute_layout_from_config(&disk, 256 * 1024).unwrap();
+        assert_eq!(layout.esp_mount_point(), "/efi");
+    }
+
+    #[test]
+    fn configured_thin_volumes_replace_derived_ones() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.use_lvm_thin = true;
+        let derived = compute_layout_from_config(&disk, 256 * 1024).unwrap();
+        assert_eq!(derived.planned_thin_volumes.unwrap().len(), 4);
+
+        disk.lvm_thin_volumes = Some(vec![crate::config::ThinVolumeSpec {
+            name: "root".to_string(),
+            virtual_size: "80G".to_string(),
+            mount_point: "/".to_string(),
+        }]);
+        let layout = compute_layout_from_config(&disk, 256 * 1024).unwrap();
+        let planned = layout.planned_thin_volumes.unwrap();
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].virtual_size, "80G");
+    }
+
+    #[test]
+    fn zfs_data_partitions_collapse_into_one_pool() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.filesystem = Filesystem::Zfs;
+        disk.encryption = false;
+        let layout = compute_layout_from_config(&disk, 256 * 1024).unwrap();
+        let data: Vec<&PartitionDef> = layout
+            .partitions
+            .iter()
+            .filter(|p| !(p.is_efi || p.is_boot_fs || p.is_swap || p.is_bios_boot))
+            .collect();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].mount_point.as_deref(), Some("/"));
+        assert_eq!(data[0].size_mib, 0);
+        assert_eq!(
+            data[0].number,
+            layout.partitions.iter().map(|p| p.number).max().unwrap()
+        );
+    }
+
+    fn subvol(name: &str, mount_point: &str) -> SubvolumeSpec {
+        SubvolumeSpec {
+            name: name.to_string(),
+            mount_point: mount_point.to_string(),
+            mount_options: None,
+        }
+    }
+
+    #[test]
+    fn configured_subvolumes_replace_the_standard_scheme() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.filesystem = Filesystem::Btrfs;
+        disk.partitions = vec![CustomPartitionEntry {
+            mount_point: "/".to_string(),
+            label: None,
+            size_mib: 0,
+            encryption: None,
+            filesystem: None,
+            mount_options: None,
+        }];
+        let standard = compute_layout_from_config(&disk, 256 * 1024).unwrap();
+        assert_eq!(standard.subvolumes.unwrap().len(), 5);
+
+        disk.btrfs_subvolumes = Some(vec![
+            SubvolumeSpec {
+                mount_options: Some("noatime,nodatacow".to_string()),
+                ..subvol("@pkg", "/var/cache/pacman/pkg")
+            },
+            subvol("@home", "/home"),
+            subvol("@", "/"),
+        ]);
+        let layout = compute_layout_from_config(&disk, 256 * 1024).unwrap();
+        let subvolumes = layout.subvolumes.unwrap();
+        assert_eq!(
+            subvolumes
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["@", "@home", "@pkg"]
+        );
+        assert_eq!(subvolumes[1].mount_options, layout.btrfs_mount_options);
+        assert_eq!(subvolumes[2].mount_options, "noatime,nodatacow");
+    }
+
+    #[test]
+    fn configured_subvolumes_need_one_root_named_at() {
+        assert!(validate_subvolumes(&[subvol("@", "/"), subvol("@home", "/home")]).is_ok());
+        assert!(validate_subvolumes(&[subvol("@home", "/home")]).is_err());
+        assert!(validate_subvolumes(&[subvol("@root", "/")]).is_err());
+        assert!(validate_subvolumes(&[subvol("@", "/"), subvol("@2", "/")]).is_err());
+        assert!(validate_subvolumes(&[subvol("@", "/"), subvol("@", "/home")]).is_err());
+        assert!(validate_subvolumes(&[subvol("@", "/"), subvol("@b", "/boot")]).is_err());
+        assert!(validate_subvolumes(&[subvol("@", "/"), subvol("@x", "home")]).is_err());
+    }
+
+    #[test]
+    fn bios_layout_uses_bios_boot_partition() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.firmware = Some(Firmware::Bios);
+        let layout = compute_layout_from_config(&disk, 256 * 1024).unwrap();
+
+        assert!(!layout.partitions.iter().any(|p| p.is_efi));
+        let bios = &layout.partitions[0];
+        assert_eq!(bios.type_guid, partition_types::BIOS_BOOT);
+        assert_eq!(bios.size_mib, BIOS_BOOT_MIB);
+        assert!(bios.is_bios_boot && !bios.is_boot_fs);
+        assert!(bios.mount_point.is_none());
+        assert!(layout.partitions[1].is_boot_fs);
+    }
+
     // ── Pure math helpers ────────────────────────────────────────────────────
 
     #[test]
@@ -628,6 +1226,11 @@ mod tests {
             total_mib: 100_000,
             partitions,
             subvolumes: None,
+            btrfs_mount_options: default_btrfs_mount_options(),
+            f2fs_mount_options: None,
+            discard: false,
+            filesystem_overrides: BTreeMap::new(),
+            mount_option_overrides: BTreeMap::new(),
             planned_thin_volumes: None,
         }
     }
@@ -657,7 +1260,7 @@ mod tests {
 
     #[test]
     fn standard_subvolumes_includes_root_and_home() {
-        let svols = standard_subvolumes();
+        let svols = standard_subvolumes(&default_btrfs_mount_options());
         let mounts: Vec<&str> = svols.iter().map(|s| s.mount_point.as_str()).collect();
         assert!(mounts.contains(&"/"), "must include root subvolume");
         assert!(mounts.contains(&"/home"), "must include /home subvolume");
@@ -667,7 +1270,7 @@ mod tests {
 
     #[test]
     fn standard_subvolumes_each_have_non_empty_fields() {
-        for sv in standard_subvolumes() {
+        for sv in standard_subvolumes(&default_btrfs_mount_options()) {
             assert!(!sv.name.is_empty(), "subvolume name must not be empty");
             assert!(
                 sv.mount_point.starts_with('/'),
@@ -679,6 +1282,179 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn btrfs_compression_values() {
+        for ok in ["zstd", "zstd:1", "zstd:15", "zlib", "zlib:9", "lzo", "none"] {
+            assert!(is_valid_btrfs_compression(ok), "{} should be accepted", ok);
+        }
+        for bad in ["", "zstd:0", "zstd:16", "zlib:10", "lzo:1", "lz4", "zstd:x"] {
+            assert!(
+                !is_valid_btrfs_compression(bad),
+                "{} should be rejected",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn f2fs_compression_values() {
+        for ok in ["zstd", "zstd:6", "lz4", "lz4:9", "lzo", "lzo-rle", "none"] {
+            assert!(is_valid_f2fs_compression(ok), "{} should be accepted", ok);
+        }
+        for bad in ["zstd:23", "lz4:2", "lzo:1", "zlib", ""] {
+            assert!(
+                !is_valid_f2fs_compression(bad),
+                "{} should be rejected",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn f2fs_compression_mount_options_only_for_f2fs() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.f2fs_compression = Some("zstd".to_string());
+        assert_eq!(f2fs_mount_options(&disk), None);
+
+        disk.filesystem = Filesystem::F2fs;
+        assert_eq!(
+            f2fs_mount_options(&disk).as_deref(),
+            Some("defaults,noatime,compress_algorithm=zstd,compress_extension=*")
+        );
+
+        disk.f2fs_compression = Some("none".to_string());
+        assert_eq!(f2fs_mount_options(&disk), None);
+    }
+
+    #[test]
+    fn filesystem_override_skips_btrfs_subvolume() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        let home = disk
+            .partitions
+            .iter_mut()
+            .find(|p| p.mount_point == "/home");
+        home.unwrap().filesystem = Some(Filesystem::Xfs);
+        // Overriding to the global filesystem is not an override
+        disk.partitions[1].filesystem = Some(Filesystem::Btrfs);
+        let layout = compute_layout_from_config(&disk, 200_000).unwrap();
+
+        assert_eq!(layout.filesystem_overrides.len(), 1);
+        assert_eq!(
+            layout.partition_filesystem("HOME", &disk.filesystem),
+            &Filesystem::Xfs
+        );
+        assert_eq!(
+            layout.partition_filesystem("VAR", &disk.filesystem),
+            &Filesystem::Btrfs
+        );
+        let subvol = |name: &str| {
+            layout
+                .partitions
+                .iter()
+                .find(|p| p.name == name)
+                .and_then(|p| p.subvolume_name.clone())
+        };
+        assert_eq!(subvol("HOME"), None);
+        assert_eq!(subvol("VAR").as_deref(), Some("@var"));
+    }
+
+    #[test]
+    fn f2fs_options_are_not_applied_to_overridden_partitions() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.filesystem = Filesystem::F2fs;
+        disk.f2fs_compression = Some("zstd".to_string());
+        disk.partitions[3].filesystem = Some(Filesystem::Ext4);
+        let layout = compute_layout_from_config(&disk, 200_000).unwrap();
+
+        assert!(layout.data_mount_options("ROOT").is_some());
+        assert_eq!(
+            layout.data_mount_options(&disk.partitions[3].effective_label()),
+            None
+        );
+    }
+
+    #[test]
+    fn btrfs_mount_options_defaults_and_overrides() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        assert_eq!(btrfs_mount_options(&disk), "defaults,noatime,compress=zstd");
+
+        disk.btrfs_compression = Some("zstd:3".to_string());
+        disk.btrfs_mount_options = Some("defaults,relatime".to_string());
+        disk.btrfs_ssd = Some(true);
+        assert_eq!(
+            btrfs_mount_options(&disk),
+            "defaults,relatime,compress=zstd:3,ssd,discard=async"
+        );
+
+        disk.btrfs_compression = Some("none".to_string());
+        disk.btrfs_ssd = Some(false);
+        assert_eq!(btrfs_mount_options(&disk), "defaults,relatime");
+
+        // A mirror is never mounted degraded behind the user's back
+        disk.mirror_device = Some("/dev/sdb".to_string());
+        assert_eq!(btrfs_mount_options(&disk), "defaults,relatime");
+    }
+
+    #[test]
+    fn snapshots_subvolume_sits_next_to_root() {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.filesystem = Filesystem::Btrfs;
+        disk.btrfs_snapshots = true;
+        let layout = compute_layout_from_config(&disk, 512 * 1024).unwrap();
+        let snapshots = layout
+            .subvolumes
+            .iter()
+            .flatten()
+            .find(|s| s.name == SNAPSHOTS_SUBVOLUME)
+            .expect("@snapshots subvolume");
+        assert_eq!(snapshots.mount_point, SNAPSHOTS_MOUNT_POINT);
+
+        let root = layout.container_subvolumes("Root");
+        assert!(root.iter().any(|s| s.name == SNAPSHOTS_SUBVOLUME));
+        assert!(!layout
+            .container_subvolumes("Home")
+            .iter()
+            .any(|s| s.name == SNAPSHOTS_SUBVOLUME));
+
+        disk.filesystem = Filesystem::Ext4;
+        let layout = compute_layout_from_config(&disk, 512 * 1024).unwrap();
+        assert!(!layout.uses_subvolumes());
+    }
+
+    #[test]
+    fn logs_and_package_cache_stay_out_of_snapshots() {
+        let nocow = |svols: &[SubvolumeDef]| -> Vec<String> {
+            svols
+                .iter()
+                .filter(|s| s.nocow)
+                .map(|s| s.name.clone())
+                .collect()
+        };
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.filesystem = Filesystem::Btrfs;
+        disk.btrfs_snapshots = true;
+
+        // /var on its own volume carries them
+        let layout = compute_layout_from_config(&disk, 512 * 1024).unwrap();
+        assert!(nocow(layout.subvolumes.as_ref().unwrap()).is_empty());
+        assert_eq!(
+            nocow(&layout.container_subvolumes("Var")),
+            vec!["@log", PKG_CACHE_SUBVOLUME]
+        );
+
+        // Otherwise they sit next to @, @log switched to nocow
+        disk.partitions.retain(|p| p.mount_point == "/");
+        disk.partitions[0].size_mib = 0;
+        let layout = compute_layout_from_config(&disk, 512 * 1024).unwrap();
+        let subvolumes = layout.subvolumes.unwrap();
+        assert_eq!(nocow(&subvolumes), vec!["@log", PKG_CACHE_SUBVOLUME]);
+        assert_eq!(subvolumes.iter().filter(|s| s.name == "@log").count(), 1);
+
+        disk.btrfs_snapshots = false;
+        let layout = compute_layout_from_config(&disk, 512 * 1024).unwrap();
+        assert!(nocow(layout.subvolumes.as_ref().unwrap()).is_empty());
+    }
 }
 
 /// Print layout summary
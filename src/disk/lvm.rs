@@ -3,10 +3,23 @@
 //! Provides functions for creating and managing LVM thin pools and volumes
 //! on top of LUKS-encrypted devices.
 
+use crate::config::{DeploymentConfig, ThinVolumeSpec};
+use crate::disk::layouts::ComputedLayout;
 use crate::utils::command::CommandRunner;
 use crate::utils::error::{DeploytixError, Result};
+use std::fs;
 use tracing::info;
 
+/// Default `thin_pool_autoextend_threshold`: grow the pool once it is this
+/// percent full.
+pub const DEFAULT_THIN_AUTOEXTEND_THRESHOLD: u8 = 80;
+
+/// Percent of its current size the pool grows by on each autoextend.
+const THIN_AUTOEXTEND_PERCENT: u8 = 20;
+
+/// LVM configuration file on the installed system.
+const LVM_CONF: &str = "/etc/lvm/lvm.conf";
+
 /// LVM thin volume definition
 #[derive(Debug, Clone)]
 pub struct ThinVolumeDef {
@@ -44,12 +57,87 @@ pub fn default_thin_volumes() -> Vec<ThinVolumeDef> {
     ]
 }
 
+/// Thin volumes for a layout: its planned volumes (the partitions collapsed
+/// into the LVM PV), or [`default_thin_volumes`] for layouts that plan none.
+pub fn layout_thin_volumes(layout: &ComputedLayout) -> Vec<ThinVolumeDef> {
+    match layout.planned_thin_volumes {
+        Some(ref planned) if !planned.is_empty() => planned
+            .iter()
+            .map(|pv| ThinVolumeDef {
+                name: pv.name.clone(),
+                virtual_size: pv.virtual_size.clone(),
+                mount_point: pv.mount_point.clone(),
+            })
+            .collect(),
+        _ => default_thin_volumes(),
+    }
+}
+
+/// Whether `size` is an lvcreate size: a number (decimals allowed) with an
+/// optional unit suffix, e.g. "50G", "1.5T", "512m".
+pub fn is_valid_lvm_size(size: &str) -> bool {
+    let digits = size.trim_end_matches(|c: char| "bBsSkKmMgGtTpPeE".contains(c));
+    if size.len() - digits.len() > 1 {
+        return false;
+    }
+    let mut parts = digits.splitn(2, '.');
+    let whole = parts.next().unwrap_or_default();
+    let frac = parts.next();
+    !whole.is_empty()
+        && whole.chars().all(|c| c.is_ascii_digit())
+        && frac.is_none_or(|f| !f.is_empty() && f.chars().all(|c| c.is_ascii_digit()))
+        && digits.chars().any(|c| c != '0' && c != '.')
+}
+
+/// Check configured thin volumes: valid LV names and sizes, absolute and
+/// distinct mount points, and exactly one root volume.
+pub fn validate_thin_volumes(volumes: &[ThinVolumeSpec]) -> std::result::Result<(), String> {
+    let mut names = std::collections::HashSet::new();
+    let mut mounts = std::collections::HashSet::new();
+    for vol in volumes {
+        let valid_name = !vol.name.is_empty()
+            && !vol.name.starts_with('-')
+            && vol
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+_.-".contains(c));
+        if !valid_name {
+            return Err(format!("Invalid LVM thin volume name '{}'", vol.name));
+        }
+        if !is_valid_lvm_size(&vol.virtual_size) {
+            return Err(format!(
+                "Invalid virtual_size '{}' for thin volume '{}' (expected e.g. 50G)",
+                vol.virtual_size, vol.name
+            ));
+        }
+        if !vol.mount_point.starts_with('/') {
+            return Err(format!(
+                "Thin volume '{}' mount point must be absolute, got '{}'",
+                vol.name, vol.mount_point
+            ));
+        }
+        if !names.insert(vol.name.as_str()) {
+            return Err(format!("Duplicate thin volume name '{}'", vol.name));
+        }
+        if !mounts.insert(vol.mount_point.as_str()) {
+            return Err(format!(
+                "Duplicate thin volume mount point '{}'",
+                vol.mount_point
+            ));
+        }
+    }
+    if !mounts.contains("/") {
+        return Err("lvm_thin_volumes must include a volume mounted at /".to_string());
+    }
+    Ok(())
+}
+
 /// Create a physical volume on a device
 pub fn create_pv(cmd: &CommandRunner, device: &str) -> Result<()> {
     info!("Creating LVM physical volume on {}", device);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] pvcreate {}", device);
+        cmd.log_dry_run(&format!("pvcreate {}", device));
         return Ok(());
     }
 
@@ -64,16 +152,19 @@ pub fn create_pv(cmd: &CommandRunner, device: &str) -> Result<()> {
     Ok(())
 }
 
-/// Create a volume group
-pub fn create_vg(cmd: &CommandRunner, vg_name: &str, pv_device: &str) -> Result<()> {
-    info!("Creating volume group '{}' on {}", vg_name, pv_device);
+/// Create a volume group on one or more physical volumes
+pub fn create_vg(cmd: &CommandRunner, vg_name: &str, pv_devices: &[&str]) -> Result<()> {
+    let pvs = pv_devices.join(" ");
+    info!("Creating volume group '{}' on {}", vg_name, pvs);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] vgcreate {} {}", vg_name, pv_device);
+        cmd.log_dry_run(&format!("vgcreate {} {}", vg_name, pvs));
         return Ok(());
     }
 
-    cmd.run("vgcreate", &[vg_name, pv_device])
+    let mut args = vec![vg_name];
+    args.extend(pv_devices);
+    cmd.run("vgcreate", &args)
         .map(|_| ())
         .map_err(|e| DeploytixError::CommandFailed {
             command: "vgcreate".to_string(),
@@ -86,51 +177,92 @@ pub fn create_vg(cmd: &CommandRunner, vg_name: &str, pv_device: &str) -> Result<
 
 /// Create a thin pool in a volume group
 ///
-/// The thin pool is created with the specified percentage of the VG size.
+/// The thin pool is created with the specified percentage of the VG size,
+/// or with `on_pv` of that physical volume only (leaving the other PVs
+/// free for a mirror leg).
 /// Metadata is automatically sized (typically 1% of pool size, min 2MiB).
 pub fn create_thin_pool(
     cmd: &CommandRunner,
     vg_name: &str,
     pool_name: &str,
     size_percent: u8,
+    on_pv: Option<&str>,
 ) -> Result<()> {
+    let extents = match on_pv {
+        Some(_) => format!("{}%PVS", size_percent),
+        None => format!("{}%VG", size_percent),
+    };
     info!(
-        "Creating thin pool '{}/{}' using {}% of VG",
-        vg_name, pool_name, size_percent
+        "Creating thin pool '{}/{}' using {} of {}",
+        vg_name,
+        pool_name,
+        extents,
+        on_pv.unwrap_or(vg_name)
     );
 
+    // Using --type thin-pool creates both data and metadata LVs
+    let mut args = vec![
+        "--type",
+        "thin-pool",
+        "-l",
+        &extents,
+        "-n",
+        pool_name,
+        vg_name,
+    ];
+    args.extend(on_pv);
+
     if cmd.is_dry_run() {
-        println!(
-            "  [dry-run] lvcreate --type thin-pool -l {}%VG -n {} {}",
-            size_percent, pool_name, vg_name
-        );
+        cmd.log_dry_run(&format!("lvcreate {}", args.join(" ")));
         return Ok(());
     }
 
-    // Create thin pool with percentage of VG
-    // Using --type thin-pool creates both data and metadata LVs
-    cmd.run(
-        "lvcreate",
-        &[
-            "--type",
-            "thin-pool",
-            "-l",
-            &format!("{}%VG", size_percent),
-            "-n",
-            pool_name,
-            vg_name,
-        ],
-    )
-    .map(|_| ())
-    .map_err(|e| DeploytixError::CommandFailed {
-        command: "lvcreate thin-pool".to_string(),
-        stderr: e.to_string(),
-    })?;
+    cmd.run("lvcreate", &args)
+        .map(|_| ())
+        .map_err(|e| DeploytixError::CommandFailed {
+            command: "lvcreate thin-pool".to_string(),
+            stderr: e.to_string(),
+        })?;
 
     info!("Thin pool '{}/{}' created", vg_name, pool_name);
     Ok(())
 }
 
+/// Convert the data and metadata LVs of a thin pool to RAID1, placing the
+/// second leg on `mirror_pv`.  Every thin volume in the pool is then
+/// stored on both physical volumes.
+pub fn mirror_thin_pool(
+    cmd: &CommandRunner,
+    vg_name: &str,
+    pool_name: &str,
+    mirror_pv: &str,
+) -> Result<()> {
+    info!(
+        "Mirroring thin pool '{}/{}' onto {}",
+        vg_name, pool_name, mirror_pv
+    );
+
+    for sub_lv in ["tdata", "tmeta"] {
+        let lv = format!("{}/{}_{}", vg_name, pool_name, sub_lv);
+        let args = ["-y", "--type", "raid1", "-m", "1", &lv, mirror_pv];
+
+        if cmd.is_dry_run() {
+            cmd.log_dry_run(&format!("lvconvert {}", args.join(" ")));
+            continue;
+        }
+
+        cmd.run("lvconvert", &args)
+            .map(|_| ())
+            .map_err(|e| DeploytixError::CommandFailed {
+                command: format!("lvconvert --type raid1 {}", lv),
+                stderr: e.to_string(),
+            })?;
+    }
+
+    info!("Thin pool '{}/{}' mirrored", vg_name, pool_name);
+    Ok(())
+}
+
 /// Create a thin logical volume from a thin pool
 ///
 /// Thin LVs can have virtual sizes larger than the physical pool size
@@ -148,10 +280,10 @@ pub fn create_thin_lv(
     );
 
     if cmd.is_dry_run() {
-        println!(
-            "  [dry-run] lvcreate -V {} --thin -n {} {}/{}",
+        cmd.log_dry_run(&format!(
+            "lvcreate -V {} --thin -n {} {}/{}",
             virtual_size, lv_name, vg_name, pool_name
-        );
+        ));
         return Ok(());
     }
 
@@ -203,7 +335,7 @@ pub fn activate_vg(cmd: &CommandRunner, vg_name: &str) -> Result<()> {
     info!("Activating volume group '{}'", vg_name);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] vgchange -ay {}", vg_name);
+        cmd.log_dry_run(&format!("vgchange -ay {}", vg_name));
         return Ok(());
     }
 
@@ -226,7 +358,7 @@ pub fn deactivate_vg(cmd: &CommandRunner, vg_name: &str) -> Result<()> {
     info!("Deactivating volume group '{}'", vg_name);
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] vgchange -an {}", vg_name);
+        cmd.log_dry_run(&format!("vgchange -an {}", vg_name));
         return Ok(());
     }
 
@@ -271,8 +403,8 @@ pub fn scan_and_activate(cmd: &CommandRunner) -> Result<()> {
     info!("Scanning for LVM volume groups");
 
     if cmd.is_dry_run() {
-        println!("  [dry-run] vgscan");
-        println!("  [dry-run] vgchange -ay");
+        cmd.log_dry_run("vgscan");
+        cmd.log_dry_run("vgchange -ay");
         return Ok(());
     }
 
@@ -332,3 +464,156 @@ pub fn get_thin_pool_usage(vg_name: &str, pool_name: &str) -> Result<(f64, f64)>
         Ok((0.0, 0.0))
     }
 }
+
+/// Set `activation { ... }` options in lvm.conf content.
+///
+/// An existing (or commented-out) `key = ...` line is replaced in place;
+/// otherwise the option is added at the top of the `activation` section,
+/// which is created if missing.
+pub fn set_lvm_activation_settings(content: &str, settings: &[(&str, String)]) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    for (key, value) in settings {
+        let new_line = format!("\t{} = {}", key, value);
+        let matches_key = |line: &str| {
+            line.trim_start()
+                .trim_start_matches('#')
+                .trim_start()
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        };
+
+        if let Some(idx) = lines
+            .iter()
+            .position(|l| !l.trim_start().starts_with('#') && matches_key(l))
+            .or_else(|| lines.iter().position(|l| matches_key(l)))
+        {
+            lines[idx] = new_line;
+        } else if let Some(idx) = lines.iter().position(|l| l.trim() == "activation {") {
+            lines.insert(idx + 1, new_line);
+        } else {
+            lines.push("activation {".to_string());
+            lines.push(new_line);
+            lines.push("}".to_string());
+        }
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Configure thin pool autoextend so dmeventd grows the pool before it
+/// fills up (a full thin pool corrupts data).
+///
+/// Extension needs free extents in the VG, which `lvm_thin_pool_percent`
+/// leaves unallocated.  The `dmeventd` service that performs the
+/// monitoring is enabled alongside the other services.
+pub fn configure_thin_autoextend(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let threshold = config
+        .disk
+        .thin_autoextend_threshold
+        .unwrap_or(DEFAULT_THIN_AUTOEXTEND_THRESHOLD);
+    let settings = [
+        ("monitoring", "1".to_string()),
+        ("thin_pool_autoextend_threshold", threshold.to_string()),
+        (
+            "thin_pool_autoextend_percent",
+            THIN_AUTOEXTEND_PERCENT.to_string(),
+        ),
+    ];
+
+    info!(
+        "Configuring thin pool autoextend: at {}% full, grow by {}%",
+        threshold, THIN_AUTOEXTEND_PERCENT
+    );
+
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would set in {}:", LVM_CONF);
+        for (key, value) in &settings {
+            println!("    activation/{} = {}", key, value);
+        }
+        return Ok(());
+    }
+
+    let path = format!("{}{}", install_root, LVM_CONF);
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    fs::write(&path, set_lvm_activation_settings(&content, &settings))?;
+
+    info!("Thin pool autoextend configured in {}", LVM_CONF);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activation_settings_replace_commented_defaults() {
+        let conf = "activation {\n\t# thin_pool_autoextend_threshold = 100\n\tmonitoring = 0\n}\n";
+        let out = set_lvm_activation_settings(
+            conf,
+            &[
+                ("thin_pool_autoextend_threshold", "80".to_string()),
+                ("monitoring", "1".to_string()),
+                ("thin_pool_autoextend_percent", "20".to_string()),
+            ],
+        );
+        assert_eq!(
+            out,
+            "activation {\n\tthin_pool_autoextend_percent = 20\n\tthin_pool_autoextend_threshold = 80\n\tmonitoring = 1\n}\n"
+        );
+    }
+
+    #[test]
+    fn lvm_size_strings() {
+        for ok in ["50G", "1.5T", "512m", "100", "2e"] {
+            assert!(is_valid_lvm_size(ok), "{}", ok);
+        }
+        for bad in ["", "G", "0G", "50GB", "5.G", "-1G", "50 G"] {
+            assert!(!is_valid_lvm_size(bad), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn thin_volumes_need_one_root() {
+        let vol = |name: &str, size: &str, mount: &str| ThinVolumeSpec {
+            name: name.to_string(),
+            virtual_size: size.to_string(),
+            mount_point: mount.to_string(),
+        };
+        assert!(
+            validate_thin_volumes(&[vol("root", "50G", "/"), vol("home", "1T", "/home")]).is_ok()
+        );
+        assert!(validate_thin_volumes(&[vol("home", "1T", "/home")]).is_err());
+        assert!(validate_thin_volumes(&[vol("root", "50G", "/"), vol("r2", "5G", "/")]).is_err());
+        assert!(validate_thin_volumes(&[vol("root", "fifty", "/")]).is_err());
+        assert!(validate_thin_volumes(&[vol("-root", "50G", "/")]).is_err());
+    }
+
+    #[test]
+    fn layout_thin_volumes_follow_config() {
+        let mut config = DeploymentConfig::sample();
+        config.disk.use_lvm_thin = true;
+        config.disk.lvm_thin_volumes = Some(vec![ThinVolumeSpec {
+            name: "root".to_string(),
+            virtual_size: "80G".to_string(),
+            mount_point: "/".to_string(),
+        }]);
+        let layout =
+            crate::disk::layouts::compute_layout_from_config(&config.disk, 256 * 1024).unwrap();
+        let volumes = layout_thin_volumes(&layout);
+        assert_eq!(volumes.len(), 1);
+        assert_eq!(volumes[0].virtual_size, "80G");
+    }
+
+    #[test]
+    fn activation_section_is_created_when_missing() {
+        let out = set_lvm_activation_settings("", &[("monitoring", "1".to_string())]);
+        assert_eq!(out, "activation {\n\tmonitoring = 1\n}\n");
+    }
+}
@@ -0,0 +1,155 @@
+//! RAID1 mirror across two disks
+//!
+//! With `disk.mirror_device` set, both disks get the same partition table.
+//! btrfs filesystems (data and /boot) are created with the `raid1` profile
+//! across the matching partitions, LVM thin pools get a mirror leg on the
+//! second disk, and the bootloader is installed on both disks.
+
+use crate::config::{DiskConfig, Filesystem};
+
+/// Largest size difference between the two disks, in percent of the
+/// larger one.  The layout is planned for the smaller disk, so anything
+/// beyond it on the larger disk goes unused.
+pub const MIRROR_SIZE_TOLERANCE_PERCENT: u64 = 10;
+
+/// Check that a mirrored config is something the pipeline can build:
+/// two distinct disks, plain btrfs or LVM thin for the data, and a btrfs
+/// /boot so the kernel is mirrored as well.
+pub fn validate_mirror(disk: &DiskConfig) -> std::result::Result<(), String> {
+    let Some(ref mirror) = disk.mirror_device else {
+        return Ok(());
+    };
+    if mirror.is_empty() {
+        return Err("mirror_device cannot be empty".to_string());
+    }
+    if *mirror == disk.device {
+        return Err(format!(
+            "mirror_device must be a different disk than device ({})",
+            mirror
+        ));
+    }
+    if disk.encryption {
+        return Err("mirror_device is not supported with encryption".to_string());
+    }
+    if disk.boot_filesystem != Filesystem::Btrfs {
+        return Err(
+            "mirror_device needs boot_filesystem = \"btrfs\" so /boot is mirrored too".to_string(),
+        );
+    }
+    if disk.use_lvm_thin {
+        return Ok(());
+    }
+    if disk.filesystem != Filesystem::Btrfs {
+        return Err(
+            "mirror_device needs filesystem = \"btrfs\" (RAID1 profile) or use_lvm_thin (mirrored pool)"
+                .to_string(),
+        );
+    }
+    if let Some(part) = disk.partitions.iter().find(|p| {
+        p.filesystem
+            .as_ref()
+            .is_some_and(|fs| *fs != Filesystem::Btrfs)
+    }) {
+        return Err(format!(
+            "partition {} cannot be mirrored: only btrfs partitions get the RAID1 profile",
+            part.mount_point
+        ));
+    }
+    Ok(())
+}
+
+/// Whether root is a btrfs RAID1 across both disks (rather than an LVM
+/// mirror), so booting from one disk needs `degraded` in its `rootflags`.
+pub fn mirrors_btrfs_root(disk: &DiskConfig) -> bool {
+    disk.mirror_device.is_some() && !disk.use_lvm_thin && disk.filesystem == Filesystem::Btrfs
+}
+
+/// Size (MiB) to plan the layout of a mirrored pair for: the smaller disk.
+/// Fails when the disks are not in the same size class.
+pub fn mirror_planning_mib(primary_mib: u64, mirror_mib: u64) -> std::result::Result<u64, String> {
+    let (smaller, larger) = (primary_mib.min(mirror_mib), primary_mib.max(mirror_mib));
+    if smaller == 0 {
+        return Err("cannot read the size of the mirror disks".to_string());
+    }
+    let difference = larger - smaller;
+    if difference * 100 > larger * MIRROR_SIZE_TOLERANCE_PERCENT {
+        return Err(format!(
+            "mirror disks differ in size by more than {}% ({} MiB vs {} MiB)",
+            MIRROR_SIZE_TOLERANCE_PERCENT, primary_mib, mirror_mib
+        ));
+    }
+    Ok(smaller)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CustomPartitionEntry;
+
+    fn mirrored_btrfs() -> DiskConfig {
+        let mut disk = crate::config::DeploymentConfig::sample().disk;
+        disk.device = "/dev/sda".to_string();
+        disk.mirror_device = Some("/dev/sdb".to_string());
+        disk.filesystem = Filesystem::Btrfs;
+        disk.boot_filesystem = Filesystem::Btrfs;
+        disk.encryption = false;
+        disk.use_lvm_thin = false;
+        disk
+    }
+
+    #[test]
+    fn btrfs_and_lvm_thin_mirrors_are_accepted() {
+        assert!(validate_mirror(&mirrored_btrfs()).is_ok());
+
+        let mut lvm = mirrored_btrfs();
+        lvm.use_lvm_thin = true;
+        lvm.filesystem = Filesystem::Ext4;
+        assert!(validate_mirror(&lvm).is_ok());
+    }
+
+    #[test]
+    fn mirror_must_be_a_distinct_disk() {
+        let mut disk = mirrored_btrfs();
+        disk.mirror_device = Some("/dev/sda".to_string());
+        assert!(validate_mirror(&disk)
+            .unwrap_err()
+            .contains("different disk"));
+    }
+
+    #[test]
+    fn unsupported_mirror_combinations_are_rejected() {
+        let mut disk = mirrored_btrfs();
+        disk.encryption = true;
+        assert!(validate_mirror(&disk).is_err());
+
+        let mut disk = mirrored_btrfs();
+        disk.boot_filesystem = Filesystem::Ext4;
+        assert!(validate_mirror(&disk)
+            .unwrap_err()
+            .contains("boot_filesystem"));
+
+        let mut disk = mirrored_btrfs();
+        disk.filesystem = Filesystem::Ext4;
+        assert!(validate_mirror(&disk).is_err());
+
+        let mut disk = mirrored_btrfs();
+        disk.partitions.push(CustomPartitionEntry {
+            mount_point: "/data".to_string(),
+            label: None,
+            size_mib: 1024,
+            encryption: None,
+            filesystem: Some(Filesystem::Xfs),
+            mount_options: None,
+        });
+        assert!(validate_mirror(&disk).unwrap_err().contains("/data"));
+    }
+
+    #[test]
+    fn mirror_is_planned_for_the_smaller_disk() {
+        assert_eq!(mirror_planning_mib(476_940, 476_940), Ok(476_940));
+        assert_eq!(mirror_planning_mib(500_000, 476_940), Ok(476_940));
+        assert_eq!(mirror_planning_mib(476_940, 500_000), Ok(476_940));
+        assert!(mirror_planning_mib(1_000_000, 476_940).is_err());
+        assert!(mirror_planning_mib(0, 476_940).is_err());
+    }
+}
@@ -1,8 +1,13 @@
 //! Disk management modules
 
+pub mod benchmark;
 pub mod detection;
+pub mod dualboot;
 pub mod formatting;
+pub mod image;
 pub mod layouts;
 pub mod lvm;
+pub mod mirror;
+pub mod mount;
 pub mod partitioning;
 pub mod volumes;
@@ -0,0 +1,234 @@
+//! Idempotent mounting
+//!
+//! Every mount of the target goes through [`ensure_mounted`], which checks
+//! `/proc/mounts` first so a resumed or repeated run does not stack a
+//! second mount on top of the first.
+
+use crate::utils::command::CommandRunner;
+use crate::utils::error::{DeploytixError, Result};
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+/// Source and options of the filesystem mounted at `target`, according to
+/// the `/proc/mounts` contents `mounts`.  With stacked mounts the topmost
+/// one (listed last) wins.
+fn mounted_at(mounts: &str, target: &str) -> Option<(String, String)> {
+    mounts.lines().rev().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let source = fields.next()?;
+        let mount_point = fields.next()?;
+        let _fstype = fields.next()?;
+        let options = fields.next()?;
+        (unescape(mount_point) == target).then(|| (unescape(source), options.to_string()))
+    })
+}
+
+/// Check an installation root given on the command line and return it
+/// without trailing slashes.  It must be absolute and must not be `/`:
+/// the target would be written over the host, and cleanup would unmount
+/// every host mount.
+pub fn check_install_root(root: &str) -> std::result::Result<String, String> {
+    if !root.starts_with('/') {
+        return Err(format!(
+            "installation root '{}' must be an absolute path",
+            root
+        ));
+    }
+    let trimmed = root.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Err("installation root must not be /".to_string());
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Sources and mount points of the mounts at or below `root` in the
+/// `/proc/mounts` contents `mounts`, deepest first so they can be
+/// unmounted in order.  `/install2` is not below `/install`.  An empty
+/// root (`/`) matches nothing rather than every host mount.
+pub fn mounts_under(mounts: &str, root: &str) -> Vec<(String, String)> {
+    let root = root.trim_end_matches('/');
+    if root.is_empty() {
+        return Vec::new();
+    }
+    let mut found: Vec<(String, String)> = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((unescape(fields.next()?), unescape(fields.next()?)))
+        })
+        .filter(|(_, mp)| {
+            mp.strip_prefix(root)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .collect();
+    found.sort_by_key(|(_, mp)| std::cmp::Reverse(mp.matches('/').count()));
+    found
+}
+
+/// Mount points at or below `root`, deepest first (see [`mounts_under`]).
+pub fn mount_points_under(mounts: &str, root: &str) -> Vec<String> {
+    mounts_under(mounts, root)
+        .into_iter()
+        .map(|(_, mp)| mp)
+        .collect()
+}
+
+/// Undo the octal escapes (`\040` for a space) `/proc/mounts` uses.
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let code = rest.get(pos + 1..pos + 4);
+        match code.and_then(|c| u8::from_str_radix(c, 8).ok()) {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether a mount of `mounted` with `mounted_options` is the mount that
+/// `source` with `options` would create.  Device paths are compared after
+/// resolving symlinks (`/dev/vg/root` is `/dev/dm-0`), and a btrfs
+/// `subvol=` must match too.
+fn is_same_mount(
+    source: &str,
+    options: Option<&str>,
+    mounted: &str,
+    mounted_options: &str,
+) -> bool {
+    let resolve = |path: &str| fs::canonicalize(path).unwrap_or_else(|_| Path::new(path).into());
+    if mounted != source && resolve(mounted) != resolve(source) {
+        return false;
+    }
+    let subvol = |opts: &str| {
+        opts.split(',')
+            .find_map(|o| o.strip_prefix("subvol="))
+            .map(|s| s.trim_start_matches('/').to_string())
+    };
+    match options.and_then(subvol) {
+        Some(wanted) => subvol(mounted_options).as_deref() == Some(wanted.as_str()),
+        None => true,
+    }
+}
+
+/// Mount `source` at `target` (created if missing) unless it is already
+/// mounted there.  A different filesystem already mounted at `target` is
+/// an error rather than something to mount over.
+pub fn ensure_mounted(
+    cmd: &CommandRunner,
+    source: &str,
+    target: &str,
+    fstype: Option<&str>,
+    options: Option<&str>,
+) -> Result<()> {
+    let mut args = Vec::new();
+    if let Some(fstype) = fstype {
+        args.extend(["-t", fstype]);
+    }
+    if let Some(options) = options {
+        args.extend(["-o", options]);
+    }
+    args.extend([source, target]);
+
+    if cmd.is_dry_run() {
+        cmd.run("mount", &args)?;
+        return Ok(());
+    }
+
+    fs::create_dir_all(target)?;
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+    if let Some((mounted, mounted_options)) = mounted_at(&mounts, target) {
+        if is_same_mount(source, options, &mounted, &mounted_options) {
+            info!("{} is already mounted at {}", source, target);
+            return Ok(());
+        }
+        return Err(DeploytixError::MountError(format!(
+            "{} is already mounted at {} (wanted {})",
+            mounted, target, source
+        )));
+    }
+
+    cmd.run("mount", &args)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+/dev/vda4 /install ext4 rw,relatime 0 0
+/dev/mapper/Crypt-Root /install/mnt\\040data btrfs rw,relatime,subvol=/@data 0 0
+/dev/vda2 /install/boot ext4 rw,relatime 0 0
+/dev/vda3 /install/boot ext4 rw,relatime 0 0
+";
+
+    #[test]
+    fn topmost_mount_at_target_is_found() {
+        assert_eq!(
+            mounted_at(MOUNTS, "/install/boot").map(|(source, _)| source),
+            Some("/dev/vda3".to_string())
+        );
+        assert_eq!(
+            mounted_at(MOUNTS, "/install/mnt data").map(|(source, _)| source),
+            Some("/dev/mapper/Crypt-Root".to_string())
+        );
+        assert!(mounted_at(MOUNTS, "/install/home").is_none());
+    }
+
+    #[test]
+    fn mount_points_under_root_are_listed_deepest_first() {
+        let mounts = format!("{}/dev/vdb1 /install2 ext4 rw 0 0\n", MOUNTS);
+        assert_eq!(
+            mount_points_under(&mounts, "/install/"),
+            vec![
+                "/install/mnt data",
+                "/install/boot",
+                "/install/boot",
+                "/install"
+            ]
+        );
+        assert_eq!(mount_points_under(&mounts, "/install2"), vec!["/install2"]);
+        assert!(mount_points_under(&mounts, "/").is_empty());
+    }
+
+    #[test]
+    fn install_root_must_be_absolute_and_not_slash() {
+        assert_eq!(
+            check_install_root("/mnt/target/"),
+            Ok("/mnt/target".to_string())
+        );
+        assert!(check_install_root("/").is_err());
+        assert!(check_install_root("//").is_err());
+        assert!(check_install_root("install").is_err());
+        assert!(check_install_root("").is_err());
+    }
+
+    #[test]
+    fn btrfs_subvolumes_must_match() {
+        let (mounted, options) = mounted_at(MOUNTS, "/install/mnt data").unwrap();
+        assert!(is_same_mount(
+            "/dev/mapper/Crypt-Root",
+            Some("subvol=@data,compress=zstd"),
+            &mounted,
+            &options
+        ));
+        assert!(!is_same_mount(
+            "/dev/mapper/Crypt-Root",
+            Some("subvol=@home"),
+            &mounted,
+            &options
+        ));
+        assert!(!is_same_mount("/dev/vda9", None, &mounted, &options));
+    }
+}
@@ -9,43 +9,62 @@ use std::io::Write;
 use tracing::info;
 use uuid::Uuid;
 
-/// Read the logical block size of a device from sysfs.
-///
-/// Returns the value from `/sys/block/<name>/queue/logical_block_size`,
-/// which sfdisk uses to interpret the sector counts in a partition script.
-/// Falls back to 512 when the attribute is unavailable (virtual devices,
-/// older kernels).
-///
-/// Note: `/sys/block/<dev>/size` always reports capacity in 512-byte units
-/// regardless of the logical block size, so `size_bytes = size_sectors * 512`
-/// remains correct. Only the sector counts and alignment in the sfdisk
-/// script need to use the actual logical block size.
-fn logical_sector_size(device: &str) -> u64 {
-    let name = std::path::Path::new(device)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("");
-    let path = format!("/sys/block/{}/queue/logical_block_size", name);
-    std::fs::read_to_string(&path)
-        .ok()
-        .and_then(|s| s.trim().parse().ok())
-        .unwrap_or(512)
-}
-
 /// Generate sfdisk script for a partition layout
 pub fn generate_sfdisk_script(device: &str, layout: &ComputedLayout) -> Result<String> {
+    sfdisk_script_sized(device, layout, None)
+}
+
+/// [`generate_sfdisk_script`] for a disk of `size_mib` MiB instead of the
+/// device's own size, so both disks of a mirror get identical partitions
+/// and a simulated disk gets a real script.
+fn sfdisk_script_sized(
+    device: &str,
+    layout: &ComputedLayout,
+    size_mib: Option<u64>,
+) -> Result<String> {
     let device_info = get_device_info(device).map_err(|e| {
         DeploytixError::PartitionError(format!("Cannot read device info for {}: {}", device, e))
     })?;
-    // Read the actual logical sector size from sysfs so that 4096-byte-sector
-    // NVMe drives get correct sector counts and alignment in the script.
+    // Use the device's logical sector size so that 4Kn drives get correct
+    // sector counts and alignment in the script.
     // /sys/block/<dev>/size always reports capacity in 512-byte units, so
     // size_bytes is still computed as size_sectors * 512.
-    let sector_size = logical_sector_size(device);
-    let total_sectors = device_info.size_bytes / sector_size;
+    let size_bytes = size_mib.map_or(device_info.size_bytes, |mib| mib * 1024 * 1024);
+    Ok(layout_to_sfdisk(
+        device,
+        layout,
+        size_bytes,
+        device_info.logical_sector_size,
+        device_info.physical_sector_size,
+    ))
+}
 
-    let first_lba = 2048u64;
-    let last_lba = total_sectors.saturating_sub(34);
+/// Sectors reserved at the end of the disk for the backup GPT: the
+/// 16 KiB partition entry array plus the header sector.
+fn backup_gpt_sectors(sector_size: u64) -> u64 {
+    (128 * 128u64).div_ceil(sector_size) + 1
+}
+
+/// Render a computed layout as an sfdisk script for a disk of `size_bytes`
+/// with the given logical and physical sector sizes.
+///
+/// Partitions start on 1 MiB boundaries (or the physical sector size, if
+/// larger), expressed in logical sectors.
+///
+/// This is the exact script `apply_partitions` feeds to sfdisk; partition
+/// and label UUIDs are freshly generated on every call.
+pub fn layout_to_sfdisk(
+    device: &str,
+    layout: &ComputedLayout,
+    size_bytes: u64,
+    sector_size: u64,
+    physical_sector_size: u64,
+) -> String {
+    let total_sectors = size_bytes / sector_size;
+
+    let align_sectors = (1024 * 1024).max(physical_sector_size) / sector_size;
+    let first_lba = align_sectors;
+    let last_lba = total_sectors.saturating_sub(backup_gpt_sectors(sector_size) + 1);
 
     let label_id = Uuid::new_v4();
 
@@ -59,13 +78,9 @@ pub fn generate_sfdisk_script(device: &str, layout: &ComputedLayout) -> Result<S
     script.push_str(&format!("sector-size: {}\n", sector_size));
     script.push('\n');
 
-    let align_sectors = (1024 * 1024) / sector_size; // 1 MiB alignment
     let mut current_sector = first_lba;
 
     for (i, part) in layout.partitions.iter().enumerate() {
-        let part_uuid = Uuid::new_v4();
-        let part_path = partition_path(device, part.number);
-
         // Calculate size in sectors
         let size_sectors = if part.size_mib == 0 {
             // Remainder - use all remaining space
@@ -74,28 +89,7 @@ pub fn generate_sfdisk_script(device: &str, layout: &ComputedLayout) -> Result<S
             (part.size_mib * 1024 * 1024) / sector_size
         };
 
-        // Build partition line
-        let mut line = format!(
-            "{} : start={}, size={}, type={}, uuid={}, name=\"{}\"",
-            part_path, current_sector, size_sectors, part.type_guid, part_uuid, part.name
-        );
-
-        // Add GPT attributes.
-        // is_bios_boot maps to the LegacyBIOSBootable GPT attribute bit — the
-        // same flag toggled by fdisk's expert-mode "Bootable" option, which
-        // tells GRUB where the /boot filesystem lives on legacy BIOS systems.
-        let mut attrs: Vec<String> = Vec::new();
-        if part.is_bios_boot {
-            attrs.push("LegacyBIOSBootable".to_string());
-        }
-        if let Some(ref extra) = part.attributes {
-            attrs.push(extra.clone());
-        }
-        if !attrs.is_empty() {
-            line.push_str(&format!(", attrs=\"{}\"", attrs.join(",")));
-        }
-
-        script.push_str(&line);
+        script.push_str(&partition_line(device, part, current_sector, size_sectors));
         script.push('\n');
 
         // Update position for next partition (aligned)
@@ -105,11 +99,48 @@ pub fn generate_sfdisk_script(device: &str, layout: &ComputedLayout) -> Result<S
         }
     }
 
-    Ok(script)
+    script
+}
+
+/// sfdisk script line creating `part` at `start` with `size` sectors,
+/// under a freshly generated partition UUID.
+pub(crate) fn partition_line(device: &str, part: &PartitionDef, start: u64, size: u64) -> String {
+    let mut line = format!(
+        "{} : start={}, size={}, type={}, uuid={}, name=\"{}\"",
+        partition_path(device, part.number),
+        start,
+        size,
+        part.type_guid,
+        Uuid::new_v4(),
+        part.name
+    );
+
+    // Add GPT attributes.
+    // is_bios_boot maps to the LegacyBIOSBootable GPT attribute bit — the
+    // same flag toggled by fdisk's expert-mode "Bootable" option, which
+    // tells GRUB where the /boot filesystem lives on legacy BIOS systems.
+    let mut attrs: Vec<String> = Vec::new();
+    if part.is_bios_boot {
+        attrs.push("LegacyBIOSBootable".to_string());
+    }
+    if let Some(ref extra) = part.attributes {
+        attrs.push(extra.clone());
+    }
+    if !attrs.is_empty() {
+        line.push_str(&format!(", attrs=\"{}\"", attrs.join(",")));
+    }
+    line
 }
 
-/// Apply partition layout to a disk using sfdisk
-pub fn apply_partitions(cmd: &CommandRunner, device: &str, layout: &ComputedLayout) -> Result<()> {
+/// Apply partition layout to a disk using sfdisk.  `size_mib` partitions
+/// the disk as if it had that size (e.g. the smaller disk of a mirror);
+/// it must not exceed the real size.
+pub fn apply_partitions(
+    cmd: &CommandRunner,
+    device: &str,
+    layout: &ComputedLayout,
+    size_mib: Option<u64>,
+) -> Result<()> {
     info!(
         "Applying {} partition layout to {}",
         layout.partitions.len(),
@@ -117,37 +148,64 @@ pub fn apply_partitions(cmd: &CommandRunner, device: &str, layout: &ComputedLayo
     );
 
     // Generate sfdisk script
-    let script = generate_sfdisk_script(device, layout)?;
+    let script = sfdisk_script_sized(device, layout, size_mib)?;
+
+    if !cmd.is_dry_run() {
+        // Wipe existing partition table
+        info!("Wiping existing partition table on {}...", device);
+        let _ = cmd.run("wipefs", &["-a", device]);
+    }
+
+    info!("Writing new GPT partition table to {}...", device);
+    run_sfdisk(cmd, device, &[], &script)?;
+
+    info!(
+        "Partitioning of {} complete ({} partitions created)",
+        device,
+        layout.partitions.len()
+    );
+    Ok(())
+}
+
+/// Feed `script` to `sfdisk [extra_args] device` and let the kernel and
+/// udev pick up the new partitions.
+pub(crate) fn run_sfdisk(
+    cmd: &CommandRunner,
+    device: &str,
+    extra_args: &[&str],
+    script: &str,
+) -> Result<()> {
+    let mut args = extra_args.to_vec();
+    args.push(device);
 
     if cmd.is_dry_run() {
         println!("  [dry-run] Would apply sfdisk script:");
         for line in script.lines() {
             println!("    {}", line);
         }
+        cmd.log_dry_run(&format!("sfdisk {}", args.join(" ")));
         return Ok(());
     }
 
     // Write script to temp file
-    let script_path = "/tmp/deploytix/partition_script";
-    fs::create_dir_all("/tmp/deploytix")?;
-    let mut file = fs::File::create(script_path)?;
+    let script_path = crate::resources::runtime_path("partition_script");
+    if let Some(dir) = script_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::File::create(&script_path)?;
     file.write_all(script.as_bytes())?;
     drop(file);
 
-    // Wipe existing partition table
-    info!("Wiping existing partition table on {}...", device);
-    let _ = cmd.run("wipefs", &["-a", device]);
-
     // Apply with sfdisk - pipe script via stdin from file
-    info!("Writing new GPT partition table to {}...", device);
     let result = std::process::Command::new("sfdisk")
-        .arg(device)
-        .stdin(fs::File::open(script_path)?)
+        .args(&args)
+        .stdin(fs::File::open(&script_path)?)
         .output()
         .map_err(|e| DeploytixError::CommandFailed {
             command: "sfdisk".to_string(),
             stderr: e.to_string(),
         })?;
+    cmd.transcribe_with_input("sfdisk", &args, script, result.status.code().unwrap_or(-1));
 
     if !result.status.success() {
         let stderr = String::from_utf8_lossy(&result.stderr);
@@ -167,12 +225,6 @@ pub fn apply_partitions(cmd: &CommandRunner, device: &str, layout: &ComputedLayo
 
     // Clean up
     let _ = fs::remove_file(script_path);
-
-    info!(
-        "Partitioning of {} complete ({} partitions created)",
-        device,
-        layout.partitions.len()
-    );
     Ok(())
 }
 
@@ -227,3 +279,68 @@ pub fn wipe_partition_table(cmd: &CommandRunner, device: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DeploymentConfig;
+    use crate::disk::layouts::compute_layout_from_config;
+
+    fn field(line: &str, key: &str) -> u64 {
+        line.split(" : ")
+            .nth(1)
+            .and_then(|fields| fields.split(", ").find_map(|f| f.strip_prefix(key)))
+            .and_then(|v| v.parse().ok())
+            .unwrap()
+    }
+
+    #[test]
+    fn sfdisk_script_covers_encrypted_layout_without_overlap() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.disk.encryption = true;
+        let disk_bytes = 256 * 1024 * 1024 * 1024u64;
+        let layout = compute_layout_from_config(&cfg.disk, disk_bytes / (1024 * 1024)).unwrap();
+
+        let script = layout_to_sfdisk("/dev/sda", &layout, disk_bytes, 512, 512);
+        assert!(script.starts_with("label: gpt\n"));
+        assert!(script.contains("device: /dev/sda\n"));
+
+        let lines: Vec<&str> = script.lines().filter(|l| l.contains(" : ")).collect();
+        assert_eq!(lines.len(), layout.partitions.len());
+
+        let last_lba = disk_bytes / 512 - 34;
+        let mut next_free = 2048;
+        for (line, part) in lines.iter().zip(&layout.partitions) {
+            assert!(line.starts_with(&partition_path("/dev/sda", part.number)));
+            assert!(line.contains(&format!("type={}", part.type_guid)));
+            let start = field(line, "start=");
+            let size = field(line, "size=");
+            assert!(start >= next_free, "{}", line);
+            assert_eq!(start % 2048, 0, "1 MiB aligned: {}", line);
+            next_free = start + size;
+        }
+        assert_eq!(next_free - 1, last_lba, "last partition fills the disk");
+    }
+
+    #[test]
+    fn sfdisk_script_uses_4k_logical_sectors() {
+        let cfg = DeploymentConfig::sample();
+        let disk_bytes = 512 * 1024 * 1024 * 1024u64;
+        let layout = compute_layout_from_config(&cfg.disk, disk_bytes / (1024 * 1024)).unwrap();
+
+        let script = layout_to_sfdisk("/dev/nvme0n1", &layout, disk_bytes, 4096, 4096);
+        assert!(script.contains("sector-size: 4096\n"));
+        assert!(script.contains("first-lba: 256\n"));
+
+        let total = disk_bytes / 4096;
+        assert!(script.contains(&format!("last-lba: {}\n", total - 6)));
+
+        let lines: Vec<&str> = script.lines().filter(|l| l.contains(" : ")).collect();
+        for (line, part) in lines.iter().zip(&layout.partitions) {
+            assert_eq!(field(line, "start=") % 256, 0, "1 MiB aligned: {}", line);
+            if part.size_mib > 0 {
+                assert_eq!(field(line, "size="), part.size_mib * 256);
+            }
+        }
+    }
+}
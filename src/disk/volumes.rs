@@ -53,8 +53,8 @@ pub struct VolumeSet {
     pub entries: Vec<VolumeEntry>,
     /// Boot partition (always present in all layouts).
     pub boot: BootVolume,
-    /// EFI partition device path.
-    pub efi: String,
+    /// EFI partition device path (None on BIOS layouts).
+    pub efi: Option<String>,
     /// Swap device path (None if swap is file-based or ZRAM-only).
     pub swap: Option<String>,
     /// Btrfs subvolumes to create/mount on the root volume (if applicable).
@@ -70,14 +70,14 @@ impl VolumeSet {
     pub fn from_layout(layout: &ComputedLayout, device: &str) -> Self {
         let mut entries = Vec::new();
         let mut boot = None;
-        let mut efi = String::new();
+        let mut efi = None;
         let mut swap = None;
 
         for part in &layout.partitions {
             let dev = partition_path(device, part.number);
 
             if part.is_efi {
-                efi = dev;
+                efi = Some(dev);
             } else if part.is_boot_fs {
                 boot = Some(BootVolume {
                     raw_device: dev.clone(),
@@ -106,6 +106,15 @@ impl VolumeSet {
                     encrypted: part.is_luks,
                     luks_container: None,
                 });
+            } else if !part.is_bios_boot {
+                // Btrfs root: its mount point moved to the "@" subvolume
+                entries.push(VolumeEntry {
+                    name: part.name.clone(),
+                    mount_point: "/".to_string(),
+                    device_path: dev,
+                    encrypted: part.is_luks,
+                    luks_container: None,
+                });
             }
         }
 
@@ -228,7 +237,7 @@ impl VolumeSet {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::disk::layouts::{partition_types, PartitionDef};
+    use crate::disk::layouts::{default_btrfs_mount_options, partition_types, PartitionDef};
 
     fn sample_standard_layout() -> ComputedLayout {
         ComputedLayout {
@@ -306,6 +315,11 @@ mod tests {
             ],
             total_mib: 500000,
             subvolumes: None,
+            btrfs_mount_options: default_btrfs_mount_options(),
+            f2fs_mount_options: None,
+            discard: false,
+            filesystem_overrides: Default::default(),
+            mount_option_overrides: Default::default(),
             planned_thin_volumes: None,
         }
     }
@@ -315,7 +329,7 @@ mod tests {
         let layout = sample_standard_layout();
         let vs = VolumeSet::from_layout(&layout, "/dev/sda");
 
-        assert_eq!(vs.efi, "/dev/sda1");
+        assert_eq!(vs.efi.as_deref(), Some("/dev/sda1"));
         assert_eq!(vs.boot.raw_device, "/dev/sda2");
         assert!(vs.swap.is_some());
         assert_eq!(vs.swap.as_ref().unwrap(), "/dev/sda3");
@@ -326,6 +340,28 @@ mod tests {
         assert_eq!(vs.entries[1].name, "HOME");
     }
 
+    #[test]
+    fn bios_layout_has_no_esp() {
+        let mut layout = sample_standard_layout();
+        layout.partitions.retain(|p| !p.is_efi);
+        let vs = VolumeSet::from_layout(&layout, "/dev/sda");
+
+        assert!(vs.efi.is_none());
+        assert_eq!(vs.boot.raw_device, "/dev/sda2");
+    }
+
+    #[test]
+    fn subvolume_root_is_still_an_entry() {
+        let mut layout = sample_standard_layout();
+        layout.partitions[3].mount_point = None;
+        let vs = VolumeSet::from_layout(&layout, "/dev/sda");
+
+        assert_eq!(vs.entries.len(), 2);
+        assert_eq!(vs.entries[0].name, "ROOT");
+        assert_eq!(vs.entries[0].mount_point, "/");
+        assert_eq!(vs.entries[0].device_path, "/dev/sda4");
+    }
+
     #[test]
     fn apply_encryption_updates_paths() {
         let layout = sample_standard_layout();
@@ -415,6 +451,11 @@ mod tests {
             ],
             total_mib: 500000,
             subvolumes: None,
+            btrfs_mount_options: default_btrfs_mount_options(),
+            f2fs_mount_options: None,
+            discard: false,
+            filesystem_overrides: Default::default(),
+            mount_option_overrides: Default::default(),
             planned_thin_volumes: None,
         };
 
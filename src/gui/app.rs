@@ -1,17 +1,22 @@
 //! Main GUI application
 
 use crate::config::{
-    DeploymentConfig, DesktopConfig, DiskConfig, GpuDriverVendor, NetworkConfig, PackagesConfig,
-    SystemConfig, UserConfig,
+    DeploymentConfig, DesktopConfig, DiskConfig, EspMount, GpuDriverVendor, MicrocodeLoading,
+    NetworkConfig, PackagesConfig, SystemConfig, UserConfig,
 };
 use crate::disk::detection::list_block_devices;
+use crate::disk::layouts::compute_layout_from_config;
 use crate::install::Installer;
 use eframe::egui;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::thread;
 
 use super::{panels, state::*, theme, widgets};
 
+/// eframe storage keys for the appearance settings
+const THEME_KEY: &str = "theme";
+const ZOOM_KEY: &str = "zoom";
+
 /// Main GUI application state.
 pub struct DeploytixGui {
     step: WizardStep,
@@ -20,6 +25,7 @@ pub struct DeploytixGui {
     user: UserState,
     packages: PackagesState,
     install: InstallState,
+    appearance: AppearanceState,
     /// Tracks whether the configure panel passes validation (one-frame lag
     /// is fine in immediate-mode UI).
     config_valid: bool,
@@ -34,6 +40,7 @@ impl Default for DeploytixGui {
             user: UserState::default(),
             packages: PackagesState::default(),
             install: InstallState::default(),
+            appearance: AppearanceState::default(),
             config_valid: false,
         }
     }
@@ -42,7 +49,52 @@ impl Default for DeploytixGui {
 impl DeploytixGui {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         theme::apply(&cc.egui_ctx);
-        Self::default()
+        let mut app = Self::default();
+        if let Some(storage) = cc.storage {
+            if let Some(choice) = storage
+                .get_string(THEME_KEY)
+                .and_then(|s| theme::ThemeChoice::from_key(&s))
+            {
+                app.appearance.theme = choice;
+            }
+            if let Some(zoom) = storage
+                .get_string(ZOOM_KEY)
+                .and_then(|s| s.parse::<f32>().ok())
+            {
+                app.appearance.zoom =
+                    zoom.clamp(*theme::ZOOM_RANGE.start(), *theme::ZOOM_RANGE.end());
+            }
+        }
+        theme::set_theme(&cc.egui_ctx, app.appearance.theme);
+        cc.egui_ctx.set_zoom_factor(app.appearance.zoom);
+        app
+    }
+
+    /// Theme and text size menu in the header.
+    fn settings_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("\u{2699}", |ui| {
+            ui.label("Theme");
+            for choice in theme::ThemeChoice::ALL {
+                if ui
+                    .radio_value(&mut self.appearance.theme, choice, choice.label())
+                    .changed()
+                {
+                    theme::set_theme(ui.ctx(), choice);
+                }
+            }
+            ui.separator();
+            ui.label("Text size");
+            if ui
+                .add(
+                    egui::Slider::new(&mut self.appearance.zoom, theme::ZOOM_RANGE)
+                        .step_by(0.05)
+                        .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                )
+                .changed()
+            {
+                ui.ctx().set_zoom_factor(self.appearance.zoom);
+            }
+        });
     }
 
     fn refresh_disks(&mut self) {
@@ -50,6 +102,9 @@ impl DeploytixGui {
             Ok(devices) => {
                 self.disk.devices = devices;
                 self.disk.selected_device_index = None;
+                self.disk.smart = None;
+                self.disk.smart_receiver = None;
+                self.disk.smart_failure_acknowledged = false;
             }
             Err(e) => {
                 self.disk.devices = Vec::new();
@@ -62,52 +117,59 @@ impl DeploytixGui {
     }
 
     fn build_config(&self) -> DeploymentConfig {
-        let device_path = self.disk.selected_device_path().to_string();
-
         DeploymentConfig {
-            disk: DiskConfig {
-                device: device_path,
-                filesystem: self.disk.filesystem.clone(),
-                boot_filesystem: crate::config::boot_filesystem_for(&self.disk.filesystem),
-                encryption: self.disk.encryption,
-                encryption_password: if self.disk.encryption {
-                    Some(self.disk.encryption_password.clone())
-                } else {
-                    None
-                },
-                luks_mapper_name: crate::config::default_luks_mapper_name(),
-                boot_encryption: self.disk.boot_encryption,
-                luks_boot_mapper_name: crate::config::default_luks_boot_mapper_name(),
-                keyfile_path: None,
-                integrity: self.disk.integrity,
-                keyfile_enabled: self.disk.encryption,
-                use_subvolumes: self.disk.use_subvolumes,
-                use_lvm_thin: self.disk.use_lvm_thin,
-                lvm_vg_name: self.disk.lvm_vg_name.clone(),
-                lvm_thin_pool_name: self.disk.lvm_thin_pool_name.clone(),
-                lvm_thin_pool_percent: self.disk.lvm_thin_pool_percent,
-                swap_type: self.disk.swap_type.clone(),
-                swap_file_size_mib: 0,
-                zram_algorithm: "zstd".to_string(),
-                partitions: self.disk.partitions.clone(),
-            },
+            disk: disk_config(&self.disk),
             system: SystemConfig {
                 init: self.system.init_system.clone(),
                 bootloader: self.system.bootloader.clone(),
+                kernel: Default::default(),
+                grub_timeout: Some(self.system.grub_timeout),
+                grub_default: None,
+                grub_distributor: None,
+                grub_os_prober: false,
                 timezone: self.system.timezone.clone(),
-                locale: self.system.locale.clone(),
+                locales: self
+                    .system
+                    .locale
+                    .split(',')
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect(),
+                locale_overrides: Default::default(),
                 keymap: self.system.keymap.clone(),
+                console_font: Some(self.system.console_font.trim().to_string())
+                    .filter(|s| !s.is_empty()),
                 hostname: self.system.hostname.clone(),
                 hibernation: false,
+                tmp_tmpfs: self.system.tmp_tmpfs,
+                tmp_tmpfs_size: Some(self.system.tmp_tmpfs_size.trim().to_string())
+                    .filter(|s| !s.is_empty()),
+                hardened: self.system.hardened,
                 secureboot: self.system.secureboot,
                 secureboot_method: self.system.secureboot_method.clone(),
                 secureboot_keys_path: None,
+                blacklist_modules: Vec::new(),
+                load_modules: Vec::new(),
+                kernel_params: Vec::new(),
+                root_authorized_keys: Vec::new(),
+                extra_services: Vec::new(),
+                ssh: self.system.ssh,
+                ssh_password_authentication: None,
+                microcode: MicrocodeLoading::default(),
+                time_sync: self.system.time_sync,
+                firewall: self.system.firewall,
+                firewall_open_ssh: None,
+                firmware_packages: Vec::new(),
+                initramfs_compression: None,
+                initramfs_modules: Vec::new(),
+                initramfs_binaries: Vec::new(),
             },
             user: UserConfig {
                 name: self.user.username.clone(),
                 password: self.user.password.clone(),
                 groups: crate::config::default_groups(),
                 sudoer: self.user.sudoer,
+                shell: Some(self.user.shell.clone()).filter(|s| s != "/bin/bash"),
             },
             network: NetworkConfig {
                 backend: self.packages.network_backend.clone(),
@@ -124,10 +186,12 @@ impl DeploytixGui {
                 } else {
                     Some(self.packages.wifi_password.clone())
                 },
+                download_retries: crate::utils::command::DEFAULT_NETWORK_RETRIES,
             },
             desktop: DesktopConfig {
                 environment: self.packages.desktop_env.clone(),
                 display_manager: self.packages.display_manager,
+                profile: self.packages.desktop_profile,
             },
             packages: PackagesConfig {
                 install_yay: self.packages.install_yay,
@@ -154,7 +218,12 @@ impl DeploytixGui {
                     drivers
                 },
                 extra_packages: crate::config::ExtraPackagesConfig::default(),
+                parallel_downloads: None,
+                pacman_cache_dir: None,
             },
+            filesystem_options: Default::default(),
+            zfs: Default::default(),
+            sudo: Default::default(),
         }
     }
 
@@ -207,6 +276,7 @@ impl DeploytixGui {
         self.install.receiver = Some(rx);
         self.install.status = "Starting installation...".to_string();
         self.install.progress = 0.0;
+        self.install.eta = None;
         self.install.logs.clear();
         self.install.active_prompt = None;
 
@@ -235,6 +305,24 @@ impl DeploytixGui {
                 return;
             }
 
+            for warning in crate::install::preflight::preflight_warnings(&config) {
+                let _ = tx.send(InstallMessage::Log(format!(
+                    "Pre-flight warning: {}",
+                    warning
+                )));
+            }
+            let issues = crate::install::preflight::preflight_issues(&config);
+            if !issues.is_empty() {
+                for issue in &issues {
+                    let _ = tx.send(InstallMessage::Log(format!("Pre-flight: {}", issue)));
+                }
+                let _ = tx.send(InstallMessage::Error(format!(
+                    "Pre-flight checks failed: {}",
+                    issues.join("; ")
+                )));
+                return;
+            }
+
             let _ = tx.send(InstallMessage::Log("Configuration validated".to_string()));
             let _ = tx.send(InstallMessage::Progress(0.1));
 
@@ -250,9 +338,10 @@ impl DeploytixGui {
 
             let progress_tx = tx.clone();
             let progress_cb: crate::install::ProgressCallback =
-                Box::new(move |progress, status| {
+                Box::new(move |progress, status, eta| {
                     let gui_progress = 0.15 + progress * 0.80;
                     let _ = progress_tx.send(InstallMessage::Progress(gui_progress));
+                    let _ = progress_tx.send(InstallMessage::Eta(eta));
                     let _ = progress_tx.send(InstallMessage::Status(status.to_string()));
                     let _ = progress_tx.send(InstallMessage::Log(format!(
                         "[{:.0}%] {}",
@@ -261,9 +350,16 @@ impl DeploytixGui {
                     )));
                 });
 
+            let output_tx = tx.clone();
+            let output_cb: crate::utils::command::OutputCallback = Box::new(move |line| {
+                let _ = output_tx.send(InstallMessage::Log(format!("  {}", line)));
+            });
+
             let mut installer = Installer::new(config, false)
                 .with_skip_confirm(true)
-                .with_progress_callback(progress_cb);
+                .with_assume_yes(true)
+                .with_progress_callback(progress_cb)
+                .with_output_callback(output_cb);
             if let Some(policy) = policy_handle {
                 installer = installer.with_policy(policy);
             }
@@ -282,6 +378,24 @@ impl DeploytixGui {
         });
     }
 
+    /// Take the SMART report from the worker thread once it is done.
+    /// Reports for a disk that is no longer selected are dropped.
+    fn poll_smart_report(&mut self) {
+        let Some(ref rx) = self.disk.smart_receiver else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok((path, report)) => {
+                if self.disk.selected_device_path() == path {
+                    self.disk.smart = report;
+                }
+                self.disk.smart_receiver = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.disk.smart_receiver = None,
+        }
+    }
+
     fn poll_install_messages(&mut self) {
         let mut should_clear = false;
 
@@ -290,6 +404,9 @@ impl DeploytixGui {
                 match msg {
                     InstallMessage::Status(s) => self.install.status = s,
                     InstallMessage::Progress(p) => self.install.progress = p,
+                    InstallMessage::Eta(eta) => {
+                        self.install.eta = eta.map(|secs| (secs, std::time::Instant::now()))
+                    }
                     InstallMessage::Log(s) => self.install.logs.push(s),
                     InstallMessage::Finished => {
                         self.install.finished = true;
@@ -532,7 +649,91 @@ impl DeploytixGui {
     }
 }
 
+/// The `[disk]` section the disk settings produce.
+fn disk_config(disk: &DiskState) -> DiskConfig {
+    DiskConfig {
+        device: disk.selected_device_path().to_string(),
+        mirror_device: None,
+        preserve_partitions: false,
+        existing_esp: None,
+        filesystem: disk.filesystem.clone(),
+        boot_filesystem: crate::config::boot_filesystem_for(&disk.filesystem),
+        encryption: disk.encryption,
+        encryption_password: if disk.encryption {
+            Some(disk.encryption_password.clone())
+        } else {
+            None
+        },
+        luks_mapper_name: crate::config::default_luks_mapper_name(),
+        boot_encryption: disk.boot_encryption,
+        luks_boot_mapper_name: crate::config::default_luks_boot_mapper_name(),
+        keyfile_path: None,
+        keyfile_dir: crate::config::default_keyfile_dir(),
+        keyfile_size_bytes: None,
+        integrity: disk.integrity,
+        integrity_trim_acknowledged: disk.integrity && disk.integrity_trim_acknowledged,
+        integrity_no_wipe: false,
+        luks_format_timeout_mins: None,
+        parallel_format: false,
+        format_jobs: None,
+        keyfile_enabled: disk.encryption,
+        fido2_unlock: false,
+        btrfs_snapshots: false,
+        recovery_passphrase: crate::config::RecoveryPassphrase::Disabled,
+        use_subvolumes: disk.use_subvolumes,
+        use_lvm_thin: disk.use_lvm_thin,
+        lvm_vg_name: disk.lvm_vg_name.clone(),
+        lvm_thin_pool_name: disk.lvm_thin_pool_name.clone(),
+        lvm_thin_pool_percent: disk.lvm_thin_pool_percent,
+        thin_autoextend_threshold: None,
+        lvm_thin_volumes: None,
+        esp_mountpoint: EspMount::default(),
+        firmware: None,
+        swap_type: disk.swap_type.clone(),
+        swap_file_size_mib: 0,
+        zram_algorithm: "zstd".to_string(),
+        zram_tmp_mib: 0,
+        zram_log_mib: 0,
+        zram_log_sync_minutes: 30,
+        zram_devices: 1,
+        vm_swappiness: None,
+        btrfs_compression: None,
+        btrfs_mount_options: None,
+        btrfs_ssd: None,
+        discard: None,
+        btrfs_mixed: None,
+        btrfs_subvolumes: None,
+        f2fs_compression: None,
+        labels: Default::default(),
+        partitions: disk.partitions.clone(),
+    }
+}
+
+/// Recompute the layout preview when the disk settings or the selected
+/// disk have changed since the last frame.  Computing a layout reads
+/// /proc/meminfo and /sys and logs the detected RAM, too much to repeat
+/// every frame.
+fn refresh_layout_preview(disk: &mut DiskState) {
+    let disk_mib = disk.selected_disk_size_mib();
+    if disk_mib == 0 {
+        disk.layout_cache = None;
+        return;
+    }
+    let config = disk_config(disk);
+    let key = format!("{} {:?}", disk_mib, config);
+    if disk.layout_cache.as_ref().is_some_and(|(k, _)| *k == key) {
+        return;
+    }
+    let layout = compute_layout_from_config(&config, disk_mib);
+    disk.layout_cache = Some((key, layout));
+}
+
 impl eframe::App for DeploytixGui {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        storage.set_string(THEME_KEY, self.appearance.theme.as_str().to_string());
+        storage.set_string(ZOOM_KEY, self.appearance.zoom.to_string());
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if self.disk.refreshing {
             self.refresh_disks();
@@ -543,6 +744,11 @@ impl eframe::App for DeploytixGui {
             ctx.request_repaint();
         }
 
+        if self.disk.smart_receiver.is_some() {
+            self.poll_smart_report();
+            ctx.request_repaint();
+        }
+
         // Drain interactive prompts queued by the worker thread.
         self.poll_prompt_queue();
         // Render the modal if a prompt is in flight.
@@ -559,6 +765,10 @@ impl eframe::App for DeploytixGui {
                         .size(18.0)
                         .color(theme::ACCENT),
                 );
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.add_space(theme::SPACING_MD);
+                    self.settings_menu(ui);
+                });
             });
             ui.add_space(theme::SPACING_XS);
             widgets::step_indicator(ui, self.step);
@@ -635,9 +845,11 @@ impl eframe::App for DeploytixGui {
                         &mut self.system,
                         &mut self.user,
                         &mut self.packages,
+                        refresh_layout_preview,
                     );
                 }
                 WizardStep::Summary => {
+                    refresh_layout_preview(&mut self.disk);
                     panels::summary::show(
                         ui,
                         &self.disk,
@@ -645,6 +857,7 @@ impl eframe::App for DeploytixGui {
                         &self.user,
                         &self.packages,
                         &mut self.install,
+                        self.disk.layout_preview(),
                     );
 
                     if self.install.save_requested {
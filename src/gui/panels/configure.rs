@@ -10,7 +10,8 @@ use crate::gui::{
 use egui::{RichText, Ui};
 
 use super::{
-    disk_config, disk_selection, handheld_gaming, network_desktop, system_config, user_config,
+    disk_config, disk_selection, handheld_gaming, layout_preview, network_desktop, system_config,
+    user_config,
 };
 
 /// Column heading rendered at the top of each grid column.
@@ -40,6 +41,10 @@ fn sub_heading(ui: &mut Ui, title: &str) {
 
 /// Render the unified configuration panel as a 3-column grid.
 ///
+/// `refresh_preview` updates the partition layout preview from the disk
+/// settings; it runs after the disk sections so the preview follows this
+/// frame's edits.
+///
 /// Returns `true` when all sections pass validation and the user may
 /// proceed to the review step.
 pub fn show(
@@ -48,6 +53,7 @@ pub fn show(
     system: &mut SystemState,
     user: &mut UserState,
     packages: &mut PackagesState,
+    refresh_preview: fn(&mut DiskState),
 ) -> bool {
     let mut disk_selected = false;
     let mut disk_valid = false;
@@ -60,6 +66,8 @@ pub fn show(
         disk_selected = disk_selection::show_sections(&mut cols[0], disk);
         cols[0].add_space(theme::SPACING_SM);
         disk_valid = disk_config::show_sections(&mut cols[0], disk);
+        refresh_preview(disk);
+        layout_preview::show_section(&mut cols[0], disk.layout_preview());
 
         // ═══ Column 2: System & User ══════════════════════════════════
         column_heading(&mut cols[1], "System");
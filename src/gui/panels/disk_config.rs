@@ -3,6 +3,7 @@
 use crate::config::{CustomPartitionEntry, Filesystem, SwapType};
 use crate::disk::layouts::{BOOT_MIB, EFI_MIB};
 use crate::gui::{state::DiskState, theme, widgets};
+use crate::utils::password::{PasswordStrength, MIN_ENCRYPTION_STRENGTH};
 use egui::{RichText, Ui};
 
 /// Estimated swap size for GUI space calculations (8 GiB).
@@ -14,6 +15,7 @@ const MIN_PART_GIB: u64 = 1;
 /// Render disk configuration sections. Returns `true` when configuration is valid.
 pub(crate) fn show_sections(ui: &mut Ui, disk: &mut DiskState) -> bool {
     let disk_size_mib = disk.selected_disk_size_mib();
+    widgets::advanced_toggle(ui, &mut disk.show_advanced);
 
     // ── Filesystem & Swap ──────────────────────────────────────
     widgets::section(ui, "Filesystem & Swap", |ui| {
@@ -21,13 +23,18 @@ pub(crate) fn show_sections(ui: &mut Ui, disk: &mut DiskState) -> bool {
     });
 
     // ── Encryption ─────────────────────────────────────────────
+    let is_ssd = disk.selected_disk_is_ssd();
     widgets::section(ui, "Encryption", |ui| {
         encryption_section(
             ui,
+            is_ssd,
+            disk.show_advanced,
             &mut disk.encryption,
             &mut disk.encryption_password,
+            &mut disk.encryption_password_confirm,
             &mut disk.boot_encryption,
             &mut disk.integrity,
+            &mut disk.integrity_trim_acknowledged,
         );
     });
 
@@ -35,6 +42,7 @@ pub(crate) fn show_sections(ui: &mut Ui, disk: &mut DiskState) -> bool {
     widgets::section(ui, "LVM Thin Provisioning", |ui| {
         lvm_section(
             ui,
+            disk.show_advanced,
             &mut disk.use_lvm_thin,
             &mut disk.lvm_vg_name,
             &mut disk.lvm_thin_pool_name,
@@ -46,11 +54,16 @@ pub(crate) fn show_sections(ui: &mut Ui, disk: &mut DiskState) -> bool {
     disk.use_subvolumes = disk.filesystem == Filesystem::Btrfs;
 
     // ── Partitions ─────────────────────────────────────────────
+    // Per-partition filesystems only apply to plain data partitions
+    let fs_overridable =
+        !disk.encryption && !disk.use_lvm_thin && disk.filesystem != Filesystem::Zfs;
     widgets::section(ui, "Partitions", |ui| {
         partition_section(
             ui,
             disk_size_mib,
             &disk.swap_type,
+            &disk.filesystem,
+            fs_overridable,
             &mut disk.partitions,
             &mut disk.new_partition_mount,
             &mut disk.new_partition_size,
@@ -75,6 +88,18 @@ fn filesystem_section(ui: &mut Ui, filesystem: &mut Filesystem, swap_type: &mut
                 ui.selectable_value(filesystem, Filesystem::F2fs, "f2fs");
             });
     });
+    // Mirrors DeploymentConfig::validate_settings: subvolumes need btrfs
+    match filesystem {
+        Filesystem::Btrfs => {}
+        Filesystem::Zfs => widgets::info_text(ui, "ZFS uses datasets instead of btrfs subvolumes"),
+        _ => widgets::info_text(
+            ui,
+            &format!(
+                "No subvolumes or snapshots on {}: they need btrfs",
+                filesystem
+            ),
+        ),
+    }
     ui.add_space(theme::SPACING_XS);
 
     let supports_swap_file = *filesystem == Filesystem::Btrfs || *filesystem == Filesystem::Ext4;
@@ -95,18 +120,30 @@ fn filesystem_section(ui: &mut Ui, filesystem: &mut Filesystem, swap_type: &mut
             });
     });
 
+    if !supports_swap_file {
+        widgets::info_text(
+            ui,
+            &format!("Swap file needs btrfs or ext4, not {}", filesystem),
+        );
+    }
+
     if *swap_type == SwapType::FileZram || *swap_type == SwapType::ZramOnly {
         ui.add_space(theme::SPACING_XS);
         widgets::info_text(ui, "ZRAM: 4 GiB fixed (zstd compression)");
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn encryption_section(
     ui: &mut Ui,
+    is_ssd: Option<bool>,
+    show_advanced: bool,
     encryption: &mut bool,
     password: &mut String,
+    password_confirm: &mut String,
     boot_encryption: &mut bool,
     integrity: &mut bool,
+    integrity_trim_acknowledged: &mut bool,
 ) {
     ui.checkbox(encryption, "Enable LUKS encryption on data partitions");
 
@@ -117,13 +154,52 @@ fn encryption_section(
             ui.add(egui::TextEdit::singleline(password).password(true));
         });
         ui.add_space(theme::SPACING_XS);
+        ui.horizontal(|ui| {
+            ui.label("Confirm:");
+            ui.add(egui::TextEdit::singleline(password_confirm).password(true));
+        });
+        if !password.is_empty() {
+            let strength = PasswordStrength::of(password);
+            let color = match strength {
+                PasswordStrength::Weak => theme::ERROR,
+                PasswordStrength::Fair => theme::WARNING,
+                _ => theme::SUCCESS,
+            };
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::ProgressBar::new(strength.fraction())
+                        .desired_width(160.0)
+                        .fill(color),
+                );
+                ui.label(RichText::new(format!("Strength: {}", strength)).color(color));
+            });
+        }
+        ui.add_space(theme::SPACING_XS);
 
-        ui.checkbox(integrity, "Enable dm-integrity (per-sector HMAC-SHA256)");
+        if !show_advanced {
+            if *integrity {
+                widgets::info_text(ui, "dm-integrity enabled (see Advanced)");
+            }
+        } else {
+            ui.checkbox(integrity, "Enable dm-integrity (per-sector HMAC-SHA256)");
+        }
         if *integrity {
             widgets::info_text(
                 ui,
                 "Detects silent data corruption. Disables TRIM/discard support.",
             );
+            if is_ssd == Some(true) {
+                ui.add_space(theme::SPACING_XS);
+                widgets::validation_warning(
+                    ui,
+                    "Target disk is an SSD: without TRIM, writes slow down and the flash \
+                     wears faster over time. Disable integrity to enable discard instead.",
+                );
+                ui.checkbox(
+                    integrity_trim_acknowledged,
+                    "Keep dm-integrity without TRIM on this SSD",
+                );
+            }
         }
         ui.add_space(theme::SPACING_XS);
 
@@ -142,6 +218,7 @@ fn encryption_section(
 
 fn lvm_section(
     ui: &mut Ui,
+    show_advanced: bool,
     use_lvm_thin: &mut bool,
     vg_name: &mut String,
     pool_name: &mut String,
@@ -154,6 +231,16 @@ fn lvm_section(
             ui,
             "Data partitions are collapsed into a single LVM PV with thin volumes.",
         );
+        if !show_advanced {
+            widgets::info_text(
+                ui,
+                &format!(
+                    "Pool {}/{} at {}% of the VG (tune under Advanced)",
+                    vg_name, pool_name, pool_percent
+                ),
+            );
+            return;
+        }
         ui.add_space(theme::SPACING_SM);
 
         ui.horizontal(|ui| {
@@ -176,15 +263,18 @@ fn lvm_section(
 
         widgets::info_text(
             ui,
-            "Thin volumes: root (50G), usr (50G), var (30G), home (200G)",
+            "Thin volumes mirror the data partitions; set disk.lvm_thin_volumes in a config file to size them explicitly.",
         );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn partition_section(
     ui: &mut Ui,
     disk_size_mib: u64,
     swap_type: &SwapType,
+    filesystem: &Filesystem,
+    fs_overridable: bool,
     partitions: &mut Vec<CustomPartitionEntry>,
     new_mount: &mut String,
     new_size: &mut String,
@@ -255,6 +345,25 @@ fn partition_section(
                 partitions[i].size_mib = gib * 1024;
             }
 
+            // Root carries the btrfs subvolumes, so it keeps the global filesystem
+            if fs_overridable && !(mount == "/" && *filesystem == Filesystem::Btrfs) {
+                let part_fs = &mut partitions[i].filesystem;
+                egui::ComboBox::from_id_salt(("part_fs", i))
+                    .selected_text(match part_fs {
+                        Some(fs) => fs.to_string(),
+                        None => format!("{} (default)", filesystem),
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(part_fs, None, "default");
+                        ui.selectable_value(part_fs, Some(Filesystem::Btrfs), "btrfs");
+                        ui.selectable_value(part_fs, Some(Filesystem::Ext4), "ext4");
+                        ui.selectable_value(part_fs, Some(Filesystem::Xfs), "xfs");
+                        ui.selectable_value(part_fs, Some(Filesystem::F2fs), "f2fs");
+                    });
+            } else {
+                partitions[i].filesystem = None;
+            }
+
             if mount != "/" && ui.small_button("\u{2715}").clicked() {
                 remove_idx = Some(i);
             }
@@ -326,6 +435,8 @@ fn try_add_partition(
             label: lbl,
             size_mib: size_gib * 1024,
             encryption: None,
+            filesystem: None,
+            mount_options: None,
         });
         mount.clear();
         size.clear();
@@ -338,6 +449,29 @@ fn validate(ui: &mut Ui, disk: &DiskState) -> bool {
         widgets::validation_error(ui, "Please enter an encryption password");
         return false;
     }
+    if disk.encryption && disk.encryption_password != disk.encryption_password_confirm {
+        widgets::validation_error(ui, "Encryption passwords do not match");
+        return false;
+    }
+    if disk.encryption && PasswordStrength::of(&disk.encryption_password) < MIN_ENCRYPTION_STRENGTH
+    {
+        widgets::validation_error(
+            ui,
+            "Encryption password is too weak: use at least 8 characters mixing letters, digits and symbols",
+        );
+        return false;
+    }
+    if disk.encryption
+        && disk.integrity
+        && disk.selected_disk_is_ssd() == Some(true)
+        && !disk.integrity_trim_acknowledged
+    {
+        widgets::validation_error(
+            ui,
+            "Choose integrity (no TRIM) or disable it to keep discard on this SSD",
+        );
+        return false;
+    }
     if disk.use_lvm_thin && disk.lvm_vg_name.is_empty() {
         widgets::validation_error(ui, "Volume group name cannot be empty");
         return false;
@@ -1,7 +1,22 @@
 //! Disk selection panel
 
+use crate::disk::detection::smart_health;
 use crate::gui::{state::DiskState, theme, widgets};
 use egui::{RichText, Ui};
+use std::sync::mpsc::channel;
+use std::thread;
+
+/// Run `smartctl` for `path` on a worker thread; it takes seconds per disk
+/// and would otherwise freeze the window.
+fn start_smart_check(disk: &mut DiskState, path: String) {
+    let (tx, rx) = channel();
+    disk.smart = None;
+    disk.smart_receiver = Some(rx);
+    thread::spawn(move || {
+        let report = smart_health(&path);
+        let _ = tx.send((path, report));
+    });
+}
 
 /// Render disk selection sections. Returns `true` when a disk is selected.
 pub(crate) fn show_sections(ui: &mut Ui, disk: &mut DiskState) -> bool {
@@ -23,21 +38,56 @@ pub(crate) fn show_sections(ui: &mut Ui, disk: &mut DiskState) -> bool {
                 .color(theme::TEXT_MUTED),
             );
         } else {
+            let mut clicked = None;
             for (i, dev) in disk.devices.iter().enumerate() {
                 let is_selected = disk.selected_device_index == Some(i);
                 let text = format!(
-                    "{} \u{2014} {} {} ({})",
+                    "{} \u{2014} {} {} ({}, {})",
                     dev.path,
                     dev.size_human(),
                     dev.model.as_deref().unwrap_or("Unknown"),
+                    dev.media_kind(),
                     dev.device_type
                 );
                 if ui.selectable_label(is_selected, &text).clicked() {
+                    clicked = Some(i);
+                }
+            }
+            if let Some(i) = clicked {
+                if disk.selected_device_index != Some(i) {
                     disk.selected_device_index = Some(i);
+                    disk.smart_failure_acknowledged = false;
+                    start_smart_check(disk, disk.devices[i].path.clone());
                 }
             }
         }
     });
 
-    disk.selected_device_index.is_some()
+    // ── SMART health of the selected disk ──────────────────────
+    let mut healthy = true;
+    if disk.selected_device_index.is_some() {
+        if disk.smart_receiver.is_some() {
+            healthy = false;
+            widgets::info_text(ui, "Checking SMART health\u{2026}");
+        } else if let Some(report) = &disk.smart {
+            if report.passed {
+                widgets::info_text(ui, &format!("SMART health: {}", report.summary()));
+            } else {
+                healthy = false;
+                widgets::validation_error(
+                    ui,
+                    &format!(
+                        "SMART health check FAILED ({}). This drive may be dying.",
+                        report.summary()
+                    ),
+                );
+                ui.checkbox(
+                    &mut disk.smart_failure_acknowledged,
+                    "Install onto this disk anyway",
+                );
+            }
+        }
+    }
+
+    disk.selected_device_index.is_some() && (healthy || disk.smart_failure_acknowledged)
 }
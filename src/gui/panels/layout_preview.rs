@@ -0,0 +1,161 @@
+//! Partition layout preview
+//!
+//! Renders the layout `compute_layout_from_config` produces for the
+//! selected disk as a proportional bar with a legend, plus the planned
+//! LVM thin volumes and btrfs subvolumes.
+
+use crate::disk::layouts::{ComputedLayout, PartitionDef};
+use crate::gui::{theme, widgets};
+use crate::utils::error::Result;
+use egui::{Color32, CornerRadius, FontId, RichText, Stroke, Ui};
+
+/// Height of the layout bar in points.
+const BAR_HEIGHT: f32 = 28.0;
+
+/// Narrowest a segment is drawn, so tiny partitions stay visible.
+const MIN_SEGMENT_WIDTH: f32 = 6.0;
+
+/// Colors cycled through for data partitions.
+const DATA_COLORS: [Color32; 4] = [
+    Color32::from_rgb(96, 165, 250),
+    Color32::from_rgb(167, 139, 250),
+    Color32::from_rgb(45, 212, 191),
+    Color32::from_rgb(244, 114, 182),
+];
+
+/// Sizes of `layout.partitions` in MiB, with the remainder partition
+/// resolved against the disk size.
+fn resolved_sizes(layout: &ComputedLayout) -> Vec<u64> {
+    let fixed: u64 = layout.partitions.iter().map(|p| p.size_mib).sum();
+    let remainder = layout.total_mib.saturating_sub(fixed);
+    layout
+        .partitions
+        .iter()
+        .map(|p| {
+            if p.size_mib == 0 {
+                remainder
+            } else {
+                p.size_mib
+            }
+        })
+        .collect()
+}
+
+fn segment_color(part: &PartitionDef, data_index: &mut usize) -> Color32 {
+    if part.is_efi || part.is_bios_boot {
+        theme::ACCENT_DIM
+    } else if part.is_boot_fs || part.mount_point.as_deref() == Some("/boot") {
+        theme::ACCENT
+    } else if part.is_swap {
+        theme::WARNING
+    } else {
+        let color = DATA_COLORS[*data_index % DATA_COLORS.len()];
+        *data_index += 1;
+        color
+    }
+}
+
+fn format_size(mib: u64) -> String {
+    if mib >= 1024 {
+        format!("{:.1} GiB", mib as f64 / 1024.0)
+    } else {
+        format!("{} MiB", mib)
+    }
+}
+
+/// Render the layout section.  `layout` is None until a disk is selected.
+pub fn show_section(ui: &mut Ui, layout: Option<&Result<ComputedLayout>>) {
+    widgets::section(ui, "Partition Layout", |ui| match layout {
+        None => widgets::info_text(ui, "Select a target disk to preview its layout"),
+        Some(Err(e)) => widgets::validation_error(ui, &format!("Layout does not fit: {}", e)),
+        Some(Ok(layout)) => show_layout(ui, layout),
+    });
+}
+
+fn show_layout(ui: &mut Ui, layout: &ComputedLayout) {
+    let sizes = resolved_sizes(layout);
+    let mut data_index = 0;
+    let colors: Vec<Color32> = layout
+        .partitions
+        .iter()
+        .map(|p| segment_color(p, &mut data_index))
+        .collect();
+
+    // ── Proportional bar ──
+    let desired = egui::vec2(ui.available_width(), BAR_HEIGHT);
+    let (response, painter) = ui.allocate_painter(desired, egui::Sense::hover());
+    let rect = response.rect;
+    let total = layout.total_mib.max(1) as f32;
+    let mut x = rect.left();
+    for ((part, size), color) in layout.partitions.iter().zip(&sizes).zip(&colors) {
+        let width = (*size as f32 / total * rect.width()).max(MIN_SEGMENT_WIDTH);
+        let segment = egui::Rect::from_min_size(
+            egui::pos2(x, rect.top()),
+            egui::vec2(width.min(rect.right() - x), BAR_HEIGHT),
+        );
+        painter.rect_filled(segment, CornerRadius::same(3), *color);
+        painter.rect_stroke(
+            segment,
+            CornerRadius::same(3),
+            Stroke::new(1.0_f32, ui.visuals().faint_bg_color),
+            egui::StrokeKind::Inside,
+        );
+        if segment.width() > 48.0 {
+            painter.text(
+                segment.center(),
+                egui::Align2::CENTER_CENTER,
+                &part.name,
+                FontId::proportional(11.0),
+                theme::BG_BASE,
+            );
+        }
+        x += width;
+        if x >= rect.right() {
+            break;
+        }
+    }
+    ui.add_space(theme::SPACING_SM);
+
+    // ── Legend ──
+    egui::Grid::new("layout_preview_grid")
+        .num_columns(4)
+        .spacing([12.0, 2.0])
+        .show(ui, |ui| {
+            for ((part, size), color) in layout.partitions.iter().zip(&sizes).zip(&colors) {
+                ui.label(RichText::new("\u{25a0}").color(*color));
+                ui.label(RichText::new(&part.name).strong());
+                ui.label(RichText::new(format_size(*size)).color(theme::TEXT_SECONDARY));
+                let mut role = part.mount_point.clone().unwrap_or_else(|| {
+                    if part.is_swap {
+                        "swap".to_string()
+                    } else {
+                        String::new()
+                    }
+                });
+                if part.is_luks {
+                    role.push_str(" (LUKS)");
+                }
+                ui.label(RichText::new(role).color(theme::TEXT_MUTED));
+                ui.end_row();
+            }
+        });
+
+    if let Some(ref volumes) = layout.planned_thin_volumes {
+        ui.add_space(theme::SPACING_XS);
+        let list: Vec<String> = volumes
+            .iter()
+            .map(|v| format!("{} {} ({})", v.name, v.mount_point, v.virtual_size))
+            .collect();
+        widgets::info_text(ui, &format!("Thin volumes: {}", list.join(", ")));
+    }
+    if let Some(ref subvolumes) = layout.subvolumes {
+        if !subvolumes.is_empty() {
+            ui.add_space(theme::SPACING_XS);
+            let list: Vec<String> = subvolumes
+                .iter()
+                .map(|s| format!("{} \u{2192} {}", s.name, s.mount_point))
+                .collect();
+            widgets::info_text(ui, &format!("Subvolumes: {}", list.join(", ")));
+        }
+    }
+}
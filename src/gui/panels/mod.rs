@@ -4,6 +4,7 @@ pub mod configure;
 pub mod disk_config;
 pub mod disk_selection;
 pub mod handheld_gaming;
+pub mod layout_preview;
 pub mod network_desktop;
 pub mod progress;
 pub mod summary;
@@ -1,6 +1,8 @@
 //! Network and desktop configuration panel
 
-use crate::config::{DesktopEnvironment, DisplayManager, Filesystem, IwdFrontend, NetworkBackend};
+use crate::config::{
+    DesktopEnvironment, DesktopProfile, DisplayManager, Filesystem, IwdFrontend, NetworkBackend,
+};
 use crate::gui::{state::PackagesState, theme, widgets};
 use egui::Ui;
 
@@ -138,6 +140,34 @@ pub(crate) fn show_sections(ui: &mut Ui, packages: &mut PackagesState, filesyste
             });
 
         if packages.desktop_env != DesktopEnvironment::None {
+            ui.add_space(theme::SPACING_XS);
+            ui.horizontal(|ui| {
+                ui.label("Package set:");
+                egui::ComboBox::from_id_salt("desktop_profile")
+                    .selected_text(format!("{}", packages.desktop_profile))
+                    .show_ui(ui, |ui| {
+                        for profile in DesktopProfile::ALL {
+                            ui.selectable_value(
+                                &mut packages.desktop_profile,
+                                profile,
+                                profile.to_string(),
+                            );
+                        }
+                    });
+            });
+            match packages.desktop_profile {
+                DesktopProfile::Full => widgets::info_text(
+                    ui,
+                    "The complete desktop with its bundled applications, \
+                     Bluetooth and power management.",
+                ),
+                DesktopProfile::Minimal => widgets::info_text(
+                    ui,
+                    "Just the desktop shell, a terminal and a file manager. \
+                     Recommended for small or constrained disks.",
+                ),
+            }
+
             // The gamescope ↔ desktop loop is built on greetd; coerce the
             // display manager while session switching is on (mirrors the
             // network backend coercion above and the config validation).
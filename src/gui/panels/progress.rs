@@ -1,6 +1,7 @@
 //! Installation progress panel
 
 use crate::gui::{state::InstallState, theme, widgets};
+use crate::install::eta::format_eta;
 use egui::{RichText, Ui};
 
 /// Render the installation progress panel.
@@ -34,6 +35,12 @@ pub fn show(ui: &mut Ui, install: &InstallState) {
             ui.label(&install.status);
             ui.add_space(theme::SPACING_SM);
             ui.add(egui::ProgressBar::new(install.progress).show_percentage());
+            if let Some((secs, received)) = install.eta {
+                // Count down between phase updates
+                let left = secs.saturating_sub(received.elapsed().as_secs());
+                ui.add_space(theme::SPACING_XS);
+                ui.label(RichText::new(format_eta(left)).color(theme::TEXT_SECONDARY));
+            }
         });
     }
 
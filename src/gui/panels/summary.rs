@@ -1,9 +1,11 @@
 //! Summary and review panel
 
+use crate::disk::layouts::ComputedLayout;
 use crate::gui::{
     state::{DiskState, InstallState, PackagesState, SystemState, UserState},
     theme, widgets,
 };
+use crate::utils::error::Result;
 use egui::{RichText, Ui};
 
 /// Render the summary panel. Returns `true` when the user may proceed.
@@ -14,6 +16,7 @@ pub fn show(
     user: &UserState,
     packages: &PackagesState,
     install: &mut InstallState,
+    layout: Option<&Result<ComputedLayout>>,
 ) -> bool {
     widgets::page_heading(ui, "Review Configuration");
 
@@ -102,6 +105,11 @@ pub fn show(
                     row(ui, "Network", &format!("{}", packages.network_backend));
                     row(ui, "Desktop", &format!("{}", packages.desktop_env));
                     if packages.desktop_env != crate::config::DesktopEnvironment::None {
+                        row(
+                            ui,
+                            "Desktop Packages",
+                            &format!("{}", packages.desktop_profile),
+                        );
                         row(
                             ui,
                             "Display Manager",
@@ -157,6 +165,9 @@ pub fn show(
                 });
         });
 
+        // ── Partition layout on the selected disk ──────────────────
+        super::layout_preview::show_section(ui, layout);
+
         // ── Save configuration ─────────────────────────────────────
         widgets::section(ui, "Save Configuration", |ui| {
             ui.horizontal(|ui| {
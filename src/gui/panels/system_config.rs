@@ -1,11 +1,13 @@
 //! System configuration panel
 
-use crate::config::{InitSystem, SecureBootMethod};
+use crate::config::{Bootloader, Firewall, InitSystem, SecureBootMethod, TimeSync};
 use crate::gui::{state::SystemState, theme, widgets};
-use egui::{RichText, Ui};
+use egui::Ui;
 
 /// Render system configuration sections. Returns `true` when valid.
 pub(crate) fn show_sections(ui: &mut Ui, system: &mut SystemState) -> bool {
+    widgets::advanced_toggle(ui, &mut system.show_advanced);
+
     widgets::section(ui, "Init & Bootloader", |ui| {
         ui.horizontal(|ui| {
             ui.label("Init System:");
@@ -22,13 +24,44 @@ pub(crate) fn show_sections(ui: &mut Ui, system: &mut SystemState) -> bool {
 
         ui.horizontal(|ui| {
             ui.label("Bootloader:");
-            ui.label(RichText::new(format!("{}", system.bootloader)).color(theme::TEXT_SECONDARY));
+            egui::ComboBox::from_id_salt("bootloader")
+                .selected_text(format!("{}", system.bootloader))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut system.bootloader, Bootloader::Grub, "GRUB");
+                    ui.selectable_value(&mut system.bootloader, Bootloader::Refind, "rEFInd");
+                });
         });
+
+        if system.bootloader == Bootloader::Grub {
+            ui.add_space(theme::SPACING_XS);
+            ui.horizontal(|ui| {
+                ui.label("Menu timeout:");
+                ui.add(
+                    egui::DragValue::new(&mut system.grub_timeout)
+                        .range(0..=60)
+                        .suffix(" s"),
+                );
+                if system.grub_timeout == 0 {
+                    ui.label(
+                        egui::RichText::new("menu hidden, boots immediately")
+                            .color(theme::TEXT_SECONDARY),
+                    );
+                }
+            });
+        }
     });
 
     widgets::section(ui, "SecureBoot", |ui| {
         ui.checkbox(&mut system.secureboot, "Enable SecureBoot signing");
-        if system.secureboot {
+        if system.secureboot && !system.show_advanced {
+            widgets::info_text(
+                ui,
+                &format!(
+                    "Method: {} (change under Advanced)",
+                    system.secureboot_method
+                ),
+            );
+        } else if system.secureboot {
             ui.add_space(theme::SPACING_XS);
             ui.horizontal(|ui| {
                 ui.label("Method:");
@@ -58,13 +91,19 @@ pub(crate) fn show_sections(ui: &mut Ui, system: &mut SystemState) -> bool {
     widgets::section(ui, "Locale & Identity", |ui| {
         ui.horizontal(|ui| {
             ui.label("Timezone:");
-            ui.text_edit_singleline(&mut system.timezone);
+            ui.add(
+                egui::TextEdit::singleline(&mut system.timezone)
+                    .hint_text("e.g. Europe/Berlin, or auto"),
+            );
         });
         ui.add_space(theme::SPACING_XS);
 
         ui.horizontal(|ui| {
-            ui.label("Locale:");
-            ui.text_edit_singleline(&mut system.locale);
+            ui.label("Locales:");
+            ui.add(
+                egui::TextEdit::singleline(&mut system.locale)
+                    .hint_text("en_US.UTF-8, de_DE.UTF-8 (first is the default)"),
+            );
         });
         ui.add_space(theme::SPACING_XS);
 
@@ -74,13 +113,97 @@ pub(crate) fn show_sections(ui: &mut Ui, system: &mut SystemState) -> bool {
         });
         ui.add_space(theme::SPACING_XS);
 
+        ui.horizontal(|ui| {
+            ui.label("Console font:");
+            ui.add(
+                egui::TextEdit::singleline(&mut system.console_font)
+                    .hint_text("default (e.g. ter-132n for HiDPI)"),
+            );
+        });
+        ui.add_space(theme::SPACING_XS);
+
         ui.horizontal(|ui| {
             ui.label("Hostname:");
             ui.text_edit_singleline(&mut system.hostname);
         });
     });
 
+    widgets::section(ui, "Temporary Files", |ui| {
+        ui.checkbox(&mut system.tmp_tmpfs, "Mount /tmp as tmpfs (in RAM)");
+        if system.tmp_tmpfs {
+            ui.add_space(theme::SPACING_XS);
+            ui.horizontal(|ui| {
+                ui.label("Size:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut system.tmp_tmpfs_size)
+                        .hint_text("half of RAM")
+                        .desired_width(80.0),
+                );
+            });
+        }
+        ui.checkbox(
+            &mut system.hardened,
+            "Hardened mount options (nodev/nosuid/noexec, hidepid /proc)",
+        );
+    });
+
+    widgets::section(ui, "Time Synchronization", |ui| {
+        ui.horizontal(|ui| {
+            ui.label("NTP client:");
+            egui::ComboBox::from_id_salt("time_sync")
+                .selected_text(format!("{}", system.time_sync))
+                .show_ui(ui, |ui| {
+                    for choice in [
+                        TimeSync::Chrony,
+                        TimeSync::Ntpd,
+                        TimeSync::OpenNtpd,
+                        TimeSync::None,
+                    ] {
+                        ui.selectable_value(&mut system.time_sync, choice, choice.to_string());
+                    }
+                });
+        });
+    });
+
+    widgets::section(ui, "Remote Access", |ui| {
+        ui.checkbox(&mut system.ssh, "Enable SSH server (no root login)");
+        ui.horizontal(|ui| {
+            ui.label("Firewall:");
+            egui::ComboBox::from_id_salt("firewall")
+                .selected_text(format!("{}", system.firewall))
+                .show_ui(ui, |ui| {
+                    for choice in [Firewall::None, Firewall::Nftables, Firewall::Ufw] {
+                        ui.selectable_value(&mut system.firewall, choice, choice.to_string());
+                    }
+                });
+        });
+        if system.firewall != Firewall::None {
+            let note = if system.ssh {
+                "Denies incoming traffic except SSH"
+            } else {
+                "Denies all incoming traffic"
+            };
+            ui.label(egui::RichText::new(note).color(theme::TEXT_SECONDARY));
+        }
+    });
+
     // Validation
+    let font = system.console_font.trim();
+    if !font.is_empty() && !crate::configure::locale::is_known_console_font(font) {
+        widgets::validation_error(ui, "Unknown console font");
+        return false;
+    }
+    if system.tmp_tmpfs
+        && !system.tmp_tmpfs_size.trim().is_empty()
+        && !crate::install::is_valid_tmpfs_size(system.tmp_tmpfs_size.trim())
+    {
+        widgets::validation_error(ui, "tmpfs size must look like 4G, 512M or 25%");
+        return false;
+    }
+    if system.secureboot && system.bootloader != Bootloader::Grub {
+        widgets::validation_error(ui, "SecureBoot signing requires GRUB");
+        return false;
+    }
     if system.hostname.is_empty() {
         widgets::validation_error(ui, "Hostname cannot be empty");
         return false;
@@ -1,5 +1,6 @@
 //! User configuration panel
 
+use crate::config::LOGIN_SHELLS;
 use crate::gui::{state::UserState, theme, widgets};
 use egui::Ui;
 
@@ -25,6 +26,18 @@ pub(crate) fn show_sections(ui: &mut Ui, user: &mut UserState) -> bool {
         ui.add_space(theme::SPACING_SM);
 
         ui.checkbox(&mut user.sudoer, "Add user to wheel group (sudo access)");
+        ui.add_space(theme::SPACING_XS);
+
+        ui.horizontal(|ui| {
+            ui.label("Shell:");
+            egui::ComboBox::from_id_salt("login_shell")
+                .selected_text(user.shell.as_str())
+                .show_ui(ui, |ui| {
+                    for (path, _) in LOGIN_SHELLS {
+                        ui.selectable_value(&mut user.shell, path.to_string(), *path);
+                    }
+                });
+        });
     });
 
     // Validation
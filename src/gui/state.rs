@@ -1,10 +1,14 @@
 //! Application state types for the Deploytix GUI
 
 use crate::config::{
-    Bootloader, CustomPartitionEntry, DesktopEnvironment, DisplayManager, Filesystem, InitSystem,
-    IwdFrontend, NetworkBackend, SecureBootMethod, SwapType,
+    Bootloader, CustomPartitionEntry, DesktopEnvironment, DesktopProfile, DisplayManager,
+    Filesystem, Firewall, InitSystem, IwdFrontend, NetworkBackend, SecureBootMethod, SwapType,
+    TimeSync,
 };
-use crate::disk::detection::BlockDevice;
+use crate::disk::detection::{BlockDevice, SmartReport};
+use crate::disk::layouts::ComputedLayout;
+use crate::gui::theme::{self, ThemeChoice};
+use crate::utils::error::Result;
 use std::sync::mpsc::Receiver;
 
 // ── Wizard navigation ──────────────────────────────────────────────────
@@ -55,6 +59,8 @@ impl WizardStep {
 pub enum InstallMessage {
     Status(String),
     Progress(f32),
+    /// Estimated seconds remaining, `None` while no estimate is available.
+    Eta(Option<u64>),
     Log(String),
     Finished,
     Error(String),
@@ -73,6 +79,13 @@ pub struct DiskState {
     pub devices: Vec<BlockDevice>,
     pub selected_device_index: Option<usize>,
     pub refreshing: bool,
+    /// SMART report for the selected disk (None when unavailable)
+    pub smart: Option<SmartReport>,
+    /// Pending SMART check of the selected disk, answered by a worker
+    /// thread with the device path and its report
+    pub smart_receiver: Option<Receiver<(String, Option<SmartReport>)>>,
+    /// User confirmed installing onto a disk that failed SMART
+    pub smart_failure_acknowledged: bool,
 
     // Filesystem
     pub filesystem: Filesystem,
@@ -80,8 +93,12 @@ pub struct DiskState {
     // Encryption
     pub encryption: bool,
     pub encryption_password: String,
+    pub encryption_password_confirm: String,
     pub boot_encryption: bool,
     pub integrity: bool,
+    pub integrity_trim_acknowledged: bool,
+    /// Show dm-integrity and LVM thin tuning
+    pub show_advanced: bool,
 
     // Swap
     pub swap_type: SwapType,
@@ -97,6 +114,9 @@ pub struct DiskState {
 
     // Partition table
     pub partitions: Vec<CustomPartitionEntry>,
+    /// Layout preview, keyed by the disk settings and disk size it was
+    /// computed from
+    pub layout_cache: Option<(String, Result<ComputedLayout>)>,
 
     // New-partition form fields
     pub new_partition_mount: String,
@@ -110,11 +130,17 @@ impl Default for DiskState {
             devices: Vec::new(),
             selected_device_index: None,
             refreshing: true,
+            smart: None,
+            smart_receiver: None,
+            smart_failure_acknowledged: false,
             filesystem: Filesystem::Btrfs,
             encryption: false,
             encryption_password: String::new(),
+            encryption_password_confirm: String::new(),
             boot_encryption: false,
             integrity: false,
+            integrity_trim_acknowledged: false,
+            show_advanced: false,
             swap_type: SwapType::Partition,
             use_subvolumes: false,
             use_lvm_thin: false,
@@ -122,6 +148,7 @@ impl Default for DiskState {
             lvm_thin_pool_name: "thinpool".to_string(),
             lvm_thin_pool_percent: 95,
             partitions: crate::config::default_partitions(),
+            layout_cache: None,
             new_partition_mount: String::new(),
             new_partition_size: String::new(),
             new_partition_label: String::new(),
@@ -138,6 +165,18 @@ impl DiskState {
             .unwrap_or(0)
     }
 
+    /// Layout from the last preview refresh, or `None` without a disk.
+    pub fn layout_preview(&self) -> Option<&Result<ComputedLayout>> {
+        self.layout_cache.as_ref().map(|(_, layout)| layout)
+    }
+
+    /// Whether the selected device is an SSD, or `None` if none selected.
+    pub fn selected_disk_is_ssd(&self) -> Option<bool> {
+        self.selected_device_index
+            .and_then(|i| self.devices.get(i))
+            .map(|d| !d.is_rotational)
+    }
+
     /// Device path of the selected device, or `"(none)"`.
     pub fn selected_device_path(&self) -> &str {
         self.selected_device_index
@@ -151,12 +190,27 @@ impl DiskState {
 pub struct SystemState {
     pub init_system: InitSystem,
     pub bootloader: Bootloader,
+    pub grub_timeout: u32,
     pub timezone: String,
+    /// Comma-separated locales; the first is `LANG`
     pub locale: String,
     pub keymap: String,
+    /// Console font; empty keeps the kernel default
+    pub console_font: String,
     pub hostname: String,
     pub secureboot: bool,
     pub secureboot_method: SecureBootMethod,
+    pub tmp_tmpfs: bool,
+    /// tmpfs size for `/tmp`; empty uses the kernel default
+    pub tmp_tmpfs_size: String,
+    /// Enable the SSH server
+    pub ssh: bool,
+    pub time_sync: TimeSync,
+    pub firewall: Firewall,
+    /// Hardened fstab mount options
+    pub hardened: bool,
+    /// Show the SecureBoot method
+    pub show_advanced: bool,
 }
 
 impl Default for SystemState {
@@ -164,12 +218,21 @@ impl Default for SystemState {
         Self {
             init_system: InitSystem::Runit,
             bootloader: Bootloader::Grub,
+            grub_timeout: 5,
             timezone: "UTC".to_string(),
             locale: "en_US.UTF-8".to_string(),
             keymap: "us".to_string(),
+            console_font: String::new(),
             hostname: "artix".to_string(),
             secureboot: false,
             secureboot_method: SecureBootMethod::Sbctl,
+            tmp_tmpfs: false,
+            tmp_tmpfs_size: String::new(),
+            ssh: false,
+            time_sync: TimeSync::default(),
+            firewall: Firewall::default(),
+            hardened: false,
+            show_advanced: false,
         }
     }
 }
@@ -180,6 +243,8 @@ pub struct UserState {
     pub password: String,
     pub password_confirm: String,
     pub sudoer: bool,
+    /// Login shell path
+    pub shell: String,
 }
 
 impl Default for UserState {
@@ -189,6 +254,7 @@ impl Default for UserState {
             password: String::new(),
             password_confirm: String::new(),
             sudoer: true,
+            shell: "/bin/bash".to_string(),
         }
     }
 }
@@ -203,6 +269,7 @@ pub struct PackagesState {
     pub wifi_password: String,
     pub desktop_env: DesktopEnvironment,
     pub display_manager: DisplayManager,
+    pub desktop_profile: DesktopProfile,
     pub install_yay: bool,
     pub install_wine: bool,
     pub install_gaming: bool,
@@ -227,6 +294,7 @@ impl Default for PackagesState {
             wifi_password: String::new(),
             desktop_env: DesktopEnvironment::None,
             display_manager: DisplayManager::default(),
+            desktop_profile: DesktopProfile::default(),
             install_yay: false,
             install_wine: false,
             install_gaming: false,
@@ -244,6 +312,21 @@ impl Default for PackagesState {
     }
 }
 
+/// Appearance settings, persisted across restarts
+pub struct AppearanceState {
+    pub theme: ThemeChoice,
+    pub zoom: f32,
+}
+
+impl Default for AppearanceState {
+    fn default() -> Self {
+        Self {
+            theme: ThemeChoice::default(),
+            zoom: theme::DEFAULT_ZOOM,
+        }
+    }
+}
+
 /// Installation progress and summary state.
 pub struct InstallState {
     // Summary / pre-install
@@ -261,6 +344,8 @@ pub struct InstallState {
     // Progress
     pub status: String,
     pub progress: f32,
+    /// Estimated seconds remaining and when the estimate arrived.
+    pub eta: Option<(u64, std::time::Instant)>,
     pub logs: Vec<String>,
     pub finished: bool,
     pub error: Option<String>,
@@ -308,6 +393,7 @@ impl Default for InstallState {
             rehearsal_requested: false,
             status: String::new(),
             progress: 0.0,
+            eta: None,
             logs: Vec::new(),
             finished: false,
             error: None,
@@ -33,9 +33,78 @@ pub const SPACING_XS: f32 = 4.0;
 pub const SPACING_SM: f32 = 8.0;
 pub const SPACING_MD: f32 = 16.0;
 
+/// Default zoom factor, so the full configuration grid fits on one screen.
+pub const DEFAULT_ZOOM: f32 = 0.75;
+
+/// Zoom range offered by the settings menu.
+pub const ZOOM_RANGE: std::ops::RangeInclusive<f32> = 0.5..=2.0;
+
+// ── Theme selection ────────────────────────────────────────────────────
+
+/// Color scheme chosen in the settings menu
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ThemeChoice {
+    /// Follow the desktop's light/dark preference
+    System,
+    #[default]
+    Dark,
+    Light,
+}
+
+impl ThemeChoice {
+    pub const ALL: [ThemeChoice; 3] = [Self::System, Self::Dark, Self::Light];
+
+    /// Key stored in eframe's persistent storage
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::Dark => "dark",
+            Self::Light => "light",
+        }
+    }
+
+    pub fn from_key(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|choice| choice.as_str() == s)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::System => "System",
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+        }
+    }
+
+    fn preference(&self) -> egui::ThemePreference {
+        match self {
+            Self::System => egui::ThemePreference::System,
+            Self::Dark => egui::ThemePreference::Dark,
+            Self::Light => egui::ThemePreference::Light,
+        }
+    }
+}
+
+/// Switch between the dark and light visuals.
+pub fn set_theme(ctx: &egui::Context, choice: ThemeChoice) {
+    ctx.set_theme(choice.preference());
+}
+
 // ── Theme application ──────────────────────────────────────────────────
 
-/// Apply the Deploytix dark theme to the egui context.
+/// Light counterpart of the Deploytix theme: egui's light visuals with
+/// the same accent and status colors.
+fn light_visuals() -> Visuals {
+    let mut visuals = Visuals::light();
+    visuals.selection.bg_fill = Color32::from_rgb(186, 230, 253);
+    visuals.selection.stroke = Stroke::new(1.0_f32, ACCENT_DIM);
+    visuals.hyperlink_color = ACCENT_DIM;
+    visuals.warn_fg_color = Color32::from_rgb(180, 83, 9);
+    visuals.error_fg_color = Color32::from_rgb(185, 28, 28);
+    visuals.window_corner_radius = CornerRadius::same(8);
+    visuals
+}
+
+/// Apply the Deploytix dark and light themes to the egui context.
 pub fn apply(ctx: &egui::Context) {
     let mut visuals = Visuals::dark();
 
@@ -83,14 +152,15 @@ pub fn apply(ctx: &egui::Context) {
     visuals.warn_fg_color = WARNING;
     visuals.error_fg_color = ERROR;
 
-    ctx.set_visuals(visuals);
+    ctx.set_visuals_of(egui::Theme::Dark, visuals);
+    ctx.set_visuals_of(egui::Theme::Light, light_visuals());
 
     // Scale down so the full configuration grid fits on one screen.
-    ctx.set_zoom_factor(0.75);
+    ctx.set_zoom_factor(DEFAULT_ZOOM);
 
     // Adjust spacing for a more spacious feel
-    let mut style = (*ctx.style()).clone();
-    style.spacing.item_spacing = egui::vec2(8.0, 6.0);
-    style.spacing.button_padding = egui::vec2(12.0, 6.0);
-    ctx.set_style(style);
+    ctx.all_styles_mut(|style| {
+        style.spacing.item_spacing = egui::vec2(8.0, 6.0);
+        style.spacing.button_padding = egui::vec2(12.0, 6.0);
+    });
 }
@@ -79,7 +79,7 @@ pub fn step_indicator(ui: &mut Ui, current: WizardStep) {
 
         // Label
         let label_color = if is_current {
-            Color32::WHITE
+            ui.visuals().strong_text_color()
         } else if is_past {
             theme::TEXT_SECONDARY
         } else {
@@ -100,10 +100,10 @@ pub fn step_indicator(ui: &mut Ui, current: WizardStep) {
 /// Render a framed section card with a title and body content.
 pub fn section(ui: &mut Ui, title: &str, add_body: impl FnOnce(&mut Ui)) {
     egui::Frame::new()
-        .fill(theme::BG_SECTION)
+        .fill(ui.visuals().faint_bg_color)
         .corner_radius(CornerRadius::same(8))
         .inner_margin(Margin::same(14))
-        .stroke(Stroke::new(1.0_f32, theme::BG_HOVER))
+        .stroke(ui.visuals().widgets.noninteractive.bg_stroke)
         .show(ui, |ui| {
             ui.label(
                 RichText::new(title)
@@ -129,6 +129,15 @@ pub fn validation_warning(ui: &mut Ui, msg: &str) {
     ui.label(RichText::new(format!("\u{26a0} {}", msg)).color(theme::WARNING));
 }
 
+/// Toggle revealing a panel's advanced options.
+pub fn advanced_toggle(ui: &mut Ui, show_advanced: &mut bool) {
+    ui.horizontal(|ui| {
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.toggle_value(show_advanced, "\u{2699} Advanced");
+        });
+    });
+}
+
 /// Render muted informational text.
 pub fn info_text(ui: &mut Ui, msg: &str) {
     ui.label(RichText::new(msg).color(theme::TEXT_MUTED).size(12.0));
@@ -168,7 +177,7 @@ pub fn page_heading(ui: &mut Ui, title: &str) {
         RichText::new(title)
             .strong()
             .size(20.0)
-            .color(Color32::WHITE),
+            .color(ui.visuals().strong_text_color()),
     );
     ui.add_space(theme::SPACING_SM);
 }
@@ -1,13 +1,16 @@
 //! Basestrap wrapper for base system installation
 
-use crate::config::{DeploymentConfig, DesktopEnvironment, Filesystem, NetworkBackend};
-use crate::utils::command::CommandRunner;
+use crate::config::{
+    Bootloader, DeploymentConfig, DesktopEnvironment, Filesystem, Firmware, NetworkBackend,
+};
+use crate::resources::runtime_path;
+use crate::utils::command::{CommandRunner, NETWORK_RETRY_BACKOFF};
 use crate::utils::error::{DeploytixError, Result};
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::thread;
-use std::time::Duration;
+use std::time::Instant;
 use tracing::{info, warn};
 
 /// Build the package list for basestrap
@@ -21,59 +24,69 @@ pub fn build_package_list(config: &DeploymentConfig) -> Vec<String> {
         config.system.init.base_package().to_string(),
     ]);
 
+    // Seat manager whose init service package is installed: elogind for
+    // the login-screen display managers, otherwise seatd (also on headless
+    // installs, which keeps the provider choice deterministic)
+    let seat = crate::configure::services::seat_manager(config).unwrap_or("seatd");
+
     // For s6, pre-select providers to avoid interactive prompts
     if config.system.init == crate::config::InitSystem::S6 {
-        // D-Bus provider for s6; no elogind, use seatd for seats
+        // D-Bus provider for s6
         packages.push("dbus-s6".to_string());
-        // no elogind-s6
         // Core s6 service packages
         packages.push("networkmanager-s6".to_string());
-        packages.push("seatd-s6".to_string());
+        packages.push(format!("{}-s6", seat));
         packages.push("iwd-s6".to_string());
     }
 
-    // Kernel and firmware
-    packages.extend([
-        "linux-firmware".to_string(),
-        "linux-zen".to_string(),
-        "linux-zen-headers".to_string(),
-    ]);
+    // Kernel, firmware (full linux-firmware or the selected subpackages)
+    // and CPU microcode
+    packages.extend(crate::configure::microcode::firmware_packages(config));
+    let kernel = config.system.kernel;
+    packages.extend([kernel.package().to_string(), kernel.headers_package()]);
+    packages.extend(crate::configure::microcode::microcode_packages(config));
+
+    // Login shell (bash is part of base)
+    if let Some(shell) = config.user.shell_package().filter(|p| *p != "bash") {
+        packages.push(shell.to_string());
+    }
+
+    if config.system.console_font.is_some() {
+        packages.push(crate::configure::locale::CONSOLE_FONT_PACKAGE.to_string());
+    }
 
     // Filesystem tools — always include btrfs-progs as it is commonly needed
     packages.push("btrfs-progs".to_string());
-    // Data filesystem tools
-    match config.disk.filesystem {
-        Filesystem::Ext4 => packages.push("e2fsprogs".to_string()),
-        Filesystem::Xfs => packages.push("xfsprogs".to_string()),
-        Filesystem::F2fs => packages.push("f2fs-tools".to_string()),
-        Filesystem::Zfs => {
-            packages.push("zfs-utils".to_string());
-            // Kernel module is separate from userspace tools
-            packages.push("zfs-linux-zen".to_string());
+    // Tools for the data, boot and per-partition filesystems
+    for filesystem in config.disk.filesystems_in_use() {
+        match filesystem {
+            Filesystem::Ext4 => packages.push("e2fsprogs".to_string()),
+            Filesystem::Xfs => packages.push("xfsprogs".to_string()),
+            Filesystem::F2fs => packages.push("f2fs-tools".to_string()),
+            Filesystem::Zfs => {
+                packages.push("zfs-utils".to_string());
+                // Kernel module is separate from userspace tools
+                packages.push(format!("zfs-{}", kernel.package()));
+            }
+            Filesystem::Btrfs => {} // Already added above
         }
-        Filesystem::Btrfs => {} // Already added above
     }
-    // Boot filesystem tools (if different from data filesystem)
-    match config.disk.boot_filesystem {
-        Filesystem::Ext4 if config.disk.filesystem != Filesystem::Ext4 => {
-            packages.push("e2fsprogs".to_string());
-        }
-        Filesystem::Xfs if config.disk.filesystem != Filesystem::Xfs => {
-            packages.push("xfsprogs".to_string());
-        }
-        Filesystem::F2fs if config.disk.filesystem != Filesystem::F2fs => {
-            packages.push("f2fs-tools".to_string());
-        }
-        Filesystem::Zfs if config.disk.filesystem != Filesystem::Zfs => {
-            packages.push("zfs-utils".to_string());
-            packages.push("zfs-linux-zen".to_string());
+
+    // Bootloader (efibootmgr only matters on UEFI)
+    if config.disk.firmware() == Firmware::Uefi {
+        packages.push("efibootmgr".to_string());
+    }
+    packages.push(
+        match config.system.bootloader {
+            Bootloader::Grub => "grub",
+            Bootloader::Refind => "refind",
         }
-        _ => {} // same as data filesystem or btrfs (already added)
+        .to_string(),
+    );
+    if config.system.grub_os_prober {
+        packages.push("os-prober".to_string());
     }
 
-    // Bootloader
-    packages.extend(["efibootmgr".to_string(), "grub".to_string()]);
-
     // Deploytix — install itself (CLI + GUI) and tkg-gui on the target
     // system so they remain available after first boot for re-deployment
     // and kernel builds.
@@ -98,20 +111,18 @@ pub fn build_package_list(config: &DeploymentConfig) -> Vec<String> {
     // Build tools
     packages.extend(["gcc".to_string(), "rustup".to_string()]);
 
-    // Seat management — always include seatd and its init service package
-    // to resolve provider conflicts (e.g. elogind vs seatd) deterministically.
+    // Seat management — seatd is always installed, plus the init service
+    // package of the seat manager.  elogind-<init> and seatd-<init> conflict
+    // (both ship a seat manager service), so only one of them is installed.
     packages.push("seatd".to_string());
     if config.system.init != crate::config::InitSystem::S6 {
-        let seatd_service = format!("seatd-{}", config.system.init);
-        packages.push(seatd_service);
+        packages.push(format!("{}-{}", seat, config.system.init));
     }
 
-    // elogind — installed alongside any desktop because greetd's PAM stack
+    // elogind — installed alongside any desktop: greetd's PAM stack
     // (pam_elogind) needs the elogind D-Bus service to create the seat
-    // session that grants DRM/input ACLs.  Only the base package is
-    // installed; the init-specific elogind-<init> service package conflicts
-    // with seatd-<init> (both ship a login1 seat manager unit) and is
-    // blacklisted in configure::services::build_service_packages().
+    // session that grants DRM/input ACLs, and the login-screen display
+    // managers run on it as their seat manager.
     if config.desktop.environment != crate::config::DesktopEnvironment::None {
         packages.push("elogind".to_string());
     }
@@ -200,12 +211,22 @@ pub fn build_package_list(config: &DeploymentConfig) -> Vec<String> {
     }
 
     // Encryption tools (if enabled)
-    if config.disk.encryption {
+    if config.disk.uses_luks() {
         packages.push("cryptsetup".to_string());
     }
+    if config.disk.fido2_unlock {
+        packages.push(crate::configure::fido2::FIDO2_PACKAGE.to_string());
+    }
+    packages.extend(crate::configure::snapper::packages(config));
+
+    // Compressor for a non-default initramfs compression
+    let compression = crate::configure::mkinitcpio::compression(config);
+    if let Some(pkg) = crate::configure::mkinitcpio::compression_package(compression) {
+        packages.push(pkg.to_string());
+    }
 
     // lvm2 provides device-mapper, required by mkinitcpio encrypt/lvm2 hooks
-    if config.disk.encryption || config.disk.use_lvm_thin {
+    if config.disk.uses_luks() || config.disk.use_lvm_thin {
         packages.push("lvm2".to_string());
     }
 
@@ -278,10 +299,14 @@ const CUSTOM_PACKAGE_NAMES: &[&str] = &[
 const ISO_REPO_PATH: &str = "/var/lib/deploytix-repo";
 
 /// Temporary repo the installer creates when no repo is configured.
-const TEMP_REPO_DIR: &str = "/tmp/deploytix-local-repo";
+fn temp_repo_dir() -> String {
+    runtime_path("local-repo").to_string_lossy().into_owned()
+}
 
 /// Temporary pacman.conf that adds the [deploytix] repo.
-const TEMP_PACMAN_CONF: &str = "/tmp/deploytix-pacman.conf";
+fn temp_pacman_conf() -> String {
+    runtime_path("pacman.conf").to_string_lossy().into_owned()
+}
 
 // === Arch Linux [extra] repository support ===
 //
@@ -678,7 +703,8 @@ fn build_missing_packages(missing: &[&str]) -> Vec<PathBuf> {
 /// Create a temporary local pacman repository from the given package
 /// files and generate a repo database with `repo-add`.
 fn create_temp_repo(cmd: &CommandRunner, packages: &[PathBuf]) -> Result<()> {
-    let repo = Path::new(TEMP_REPO_DIR);
+    let repo_dir = temp_repo_dir();
+    let repo = Path::new(&repo_dir);
 
     // Clean previous run.
     if repo.is_dir() {
@@ -693,7 +719,7 @@ fn create_temp_repo(cmd: &CommandRunner, packages: &[PathBuf]) -> Result<()> {
     }
 
     // Build the pacman database.
-    let db_path = format!("{}/deploytix.db.tar.zst", TEMP_REPO_DIR);
+    let db_path = format!("{}/deploytix.db.tar.zst", repo_dir);
     let pkg_paths: Vec<String> = std::fs::read_dir(repo)
         .map_err(DeploytixError::Io)?
         .filter_map(|e| e.ok())
@@ -711,7 +737,7 @@ fn create_temp_repo(cmd: &CommandRunner, packages: &[PathBuf]) -> Result<()> {
 
     cmd.run("repo-add", &args)?;
 
-    info!("Created temporary deploytix repo at {}", TEMP_REPO_DIR);
+    info!("Created temporary deploytix repo at {}", repo_dir);
     Ok(())
 }
 
@@ -730,13 +756,14 @@ fn write_custom_pacman_conf(repo_dir: &str) -> Result<Option<String>> {
         repo_dir,
     );
 
-    std::fs::write(TEMP_PACMAN_CONF, &custom).map_err(DeploytixError::Io)?;
+    let conf_path = temp_pacman_conf();
+    std::fs::write(&conf_path, &custom).map_err(DeploytixError::Io)?;
 
     info!(
         "Custom pacman.conf written to {} (repo: file://{})",
-        TEMP_PACMAN_CONF, repo_dir
+        conf_path, repo_dir
     );
-    Ok(Some(TEMP_PACMAN_CONF.to_string()))
+    Ok(Some(conf_path))
 }
 
 /// Ensure the deploytix custom packages are resolvable by pacman for
@@ -838,7 +865,7 @@ pub fn prepare_deploytix_repo(
         packages.len()
     );
     create_temp_repo(cmd, &packages)?;
-    write_custom_pacman_conf(TEMP_REPO_DIR)
+    write_custom_pacman_conf(&temp_repo_dir())
 }
 
 // === Arch Linux [extra] repository detection / injection ===
@@ -886,56 +913,272 @@ fn ensure_arch_repos(existing_conf: Option<String>, cmd: &CommandRunner) -> Resu
         mirror_entry,
     );
 
-    std::fs::write(TEMP_PACMAN_CONF, &updated).map_err(DeploytixError::Io)?;
+    let conf_path = temp_pacman_conf();
+    std::fs::write(&conf_path, &updated).map_err(DeploytixError::Io)?;
 
     info!(
         "Updated pacman.conf at {} with Arch [extra] repository",
-        TEMP_PACMAN_CONF,
+        conf_path,
     );
 
-    Ok(Some(TEMP_PACMAN_CONF.to_string()))
+    Ok(Some(conf_path))
 }
 
-/// Maximum number of retry attempts for basestrap on network failures
-const BASESTRAP_MAX_RETRIES: u32 = 3;
+// === Parallel downloads ===
 
-/// Delay between retry attempts (in seconds)
-const BASESTRAP_RETRY_DELAY_SECS: u64 = 5;
+/// Upper bound for the detected `ParallelDownloads` default.
+const MAX_PARALLEL_DOWNLOADS: u32 = 5;
 
-/// Check if an error message indicates a transient network failure
-fn is_network_error(stderr: &str) -> bool {
-    let network_error_patterns = [
-        "Operation too slow",
-        "failed retrieving file",
-        "failed to retrieve some files",
-        "Connection timed out",
-        "Could not resolve host",
-        "Network is unreachable",
-        "Connection refused",
-        "SSL connection timeout",
-        "error: failed to synchronize",
-    ];
+/// Artix mirrorlist consulted for the number of usable mirrors.
+const MIRRORLIST_PATH: &str = "/etc/pacman.d/mirrorlist";
 
-    network_error_patterns
-        .iter()
-        .any(|pattern| stderr.contains(pattern))
+/// Default `ParallelDownloads` for `cores` CPUs and `mirrors` enabled
+/// mirrors.  Downloads are I/O bound, so two per core is fine, but a
+/// single mirror is not hammered with more than three connections.
+fn default_parallel_downloads(cores: u32, mirrors: u32) -> u32 {
+    let cap = if mirrors <= 1 {
+        3
+    } else {
+        MAX_PARALLEL_DOWNLOADS
+    };
+    (cores * 2).clamp(2, cap)
+}
+
+/// Number of uncommented `Server =` lines in the mirrorlist.
+fn count_mirrors(mirrorlist: &str) -> u32 {
+    mirrorlist
+        .lines()
+        .filter(|line| line.trim_start().starts_with("Server"))
+        .count() as u32
+}
+
+/// `ParallelDownloads` for basestrap: the configured value, else one
+/// detected from the host.
+fn parallel_downloads(config: &DeploymentConfig) -> u32 {
+    if let Some(n) = config.packages.parallel_downloads {
+        return n.max(1);
+    }
+    let cores = std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1);
+    let mirrors = std::fs::read_to_string(MIRRORLIST_PATH)
+        .map(|list| count_mirrors(&list))
+        .unwrap_or(0);
+    default_parallel_downloads(cores, mirrors)
+}
+
+/// Set `ParallelDownloads = n` in the `[options]` section of `conf`,
+/// replacing an existing (possibly commented-out) setting.
+fn set_parallel_downloads(conf: &str, n: u32) -> String {
+    let setting = format!("ParallelDownloads = {}", n);
+    let mut out = Vec::new();
+    let mut in_options = false;
+    let mut done = false;
+    for line in conf.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            // Leaving [options] without having seen the key: add it here
+            if in_options && !done {
+                out.push(setting.clone());
+                done = true;
+            }
+            in_options = trimmed == "[options]";
+        } else if in_options
+            && !done
+            && trimmed
+                .trim_start_matches('#')
+                .trim_start()
+                .starts_with("ParallelDownloads")
+        {
+            out.push(setting.clone());
+            done = true;
+            continue;
+        }
+        out.push(line.to_string());
+    }
+    if !done {
+        if !in_options {
+            out.push("[options]".to_string());
+        }
+        out.push(setting);
+    }
+    out.join("\n") + "\n"
+}
+
+/// Write the pacman.conf basestrap uses with `ParallelDownloads` set,
+/// starting from the custom config if one was generated.
+fn write_parallel_downloads_conf(
+    existing_conf: Option<String>,
+    n: u32,
+    cmd: &CommandRunner,
+) -> Result<Option<String>> {
+    if cmd.is_dry_run() {
+        println!(
+            "  [dry-run] Would set ParallelDownloads = {} for basestrap",
+            n
+        );
+        return Ok(existing_conf);
+    }
+
+    let conf_path = existing_conf.as_deref().unwrap_or("/etc/pacman.conf");
+    let conf_content = std::fs::read_to_string(conf_path).map_err(DeploytixError::Io)?;
+
+    let conf_path = temp_pacman_conf();
+    std::fs::write(&conf_path, set_parallel_downloads(&conf_content, n))
+        .map_err(DeploytixError::Io)?;
+    info!("basestrap downloads up to {} packages in parallel", n);
+    Ok(Some(conf_path))
+}
+
+// === Host package cache ===
+
+/// pacman's package cache inside the target.
+const TARGET_CACHE_DIR: &str = "var/cache/pacman/pkg";
+
+/// Check that `dir` is an existing directory we can write packages to.
+pub fn check_package_cache_dir(dir: &str) -> std::result::Result<(), String> {
+    if !Path::new(dir).is_absolute() {
+        return Err("must be an absolute path".to_string());
+    }
+    if !Path::new(dir).is_dir() {
+        return Err("is not an existing directory".to_string());
+    }
+    let probe = Path::new(dir).join(".deploytix-write-test");
+    std::fs::write(&probe, b"")
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| format!("is not writable ({})", e))
+}
+
+/// Bind-mount `packages.pacman_cache_dir` over the target's pacman cache
+/// so basestrap and later in-chroot pacman runs share the host cache.
+///
+/// The mount lives under `install_root`, so `unmount_all` and the
+/// installer's emergency cleanup release it with the other mounts.
+pub fn mount_package_cache(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    install_root: &str,
+) -> Result<()> {
+    let Some(ref cache_dir) = config.packages.pacman_cache_dir else {
+        return Ok(());
+    };
+    let target = format!("{}/{}", install_root, TARGET_CACHE_DIR);
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would bind-mount {} at {}", cache_dir, target);
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&target)?;
+    cmd.run("mount", &["--bind", cache_dir, &target])?;
+    info!("Using host package cache {}", cache_dir);
+    Ok(())
+}
+
+/// Share of a pacman transaction's progress given to downloading; the rest
+/// is installing.
+const DOWNLOAD_SHARE: f32 = 0.4;
+
+/// Progress of a pacman transaction, followed through its output.
+///
+/// basestrap's output is piped, so pacman prints no progress bars: the
+/// package count comes from the `Packages (N)` line, then each
+/// `downloading …...` and `installing …...` line moves the count on.
+#[derive(Debug, Default)]
+pub struct PacmanProgress {
+    total: Option<u32>,
+    downloaded: u32,
+    installed: u32,
+    output_seen: bool,
+}
+
+impl PacmanProgress {
+    /// Read one line of output.  Returns true when [`fraction`] changed,
+    /// or on the first line seen before the package count, when the
+    /// progress becomes indeterminate.
+    ///
+    /// [`fraction`]: PacmanProgress::fraction
+    pub fn update(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        if let Some(total) = package_count(line) {
+            self.total = Some(total);
+            return true;
+        }
+        let Some(total) = self.total else {
+            return !std::mem::replace(&mut self.output_seen, true);
+        };
+        let Some(rest) = line.strip_suffix("...") else {
+            return false;
+        };
+        let counter = if is_package_download(rest) {
+            &mut self.downloaded
+        } else if ["installing ", "upgrading ", "reinstalling "]
+            .iter()
+            .any(|op| rest.starts_with(op))
+        {
+            &mut self.installed
+        } else {
+            return false;
+        };
+        if *counter >= total {
+            return false;
+        }
+        *counter += 1;
+        true
+    }
+
+    /// Fraction of the transaction done, `None` while the package count
+    /// is unknown.
+    pub fn fraction(&self) -> Option<f32> {
+        let total = self.total? as f32;
+        if self.installed > 0 {
+            Some(DOWNLOAD_SHARE + (1.0 - DOWNLOAD_SHARE) * self.installed as f32 / total)
+        } else {
+            Some(DOWNLOAD_SHARE * self.downloaded as f32 / total)
+        }
+    }
 }
 
-/// Run basestrap to install the base system
+/// Package count from pacman's `Packages (N) …` line
+fn package_count(line: &str) -> Option<u32> {
+    let (count, _) = line.strip_prefix("Packages (")?.split_once(')')?;
+    count.trim().parse().ok().filter(|n| *n > 0)
+}
+
+/// Whether a line (without its trailing `...`) starts a package download:
+/// `downloading foo.pkg.tar.zst` or ` foo.pkg.tar.zst downloading`.
+/// Signature files are not packages.
+fn is_package_download(rest: &str) -> bool {
+    let file = rest
+        .strip_prefix("downloading ")
+        .or_else(|| rest.strip_suffix(" downloading"));
+    file.is_some_and(|f| !f.ends_with(".sig") && !f.ends_with(".db"))
+}
+
+/// Run basestrap to install the base system, passing the fraction of it
+/// done to `on_progress` as pacman's output moves it on, or `None` when
+/// the output carries no package count to measure it against
 pub fn run_basestrap(
     cmd: &CommandRunner,
     config: &DeploymentConfig,
     install_root: &str,
+    on_progress: &dyn Fn(Option<f32>),
 ) -> Result<()> {
-    run_basestrap_with_retries(cmd, config, install_root, BASESTRAP_MAX_RETRIES)
+    run_basestrap_with_retries(
+        cmd,
+        config,
+        install_root,
+        cmd.network_retries(),
+        on_progress,
+    )
 }
 
-/// Run basestrap with configurable retry count
+/// Run basestrap, retrying failed downloads up to `max_retries` times
 pub fn run_basestrap_with_retries(
     cmd: &CommandRunner,
     config: &DeploymentConfig,
     install_root: &str,
     max_retries: u32,
+    on_progress: &dyn Fn(Option<f32>),
 ) -> Result<()> {
     // Build the package list first so we know exactly which custom
     // packages need to be resolved.
@@ -958,6 +1201,8 @@ pub fn run_basestrap_with_retries(
     // are not mirrored in the Artix repositories.
     let custom_conf = ensure_arch_repos(custom_conf, cmd)?;
 
+    let custom_conf = write_parallel_downloads_conf(custom_conf, parallel_downloads(config), cmd)?;
+
     info!(
         "Installing {} packages with basestrap to {}",
         packages.len(),
@@ -980,41 +1225,125 @@ pub fn run_basestrap_with_retries(
     let pkg_refs: Vec<&str> = packages.iter().map(|s| s.as_str()).collect();
     args.extend(pkg_refs);
 
-    let mut last_error = None;
-
-    for attempt in 1..=max_retries {
-        match cmd.run("basestrap", &args) {
-            Ok(_) => {
-                if attempt > 1 {
-                    info!("basestrap succeeded on attempt {}", attempt);
-                }
-                return Ok(());
+    let started = Instant::now();
+    let tracker = RefCell::new(PacmanProgress::default());
+    let result = cmd.run_with_retry_observed(
+        "basestrap",
+        &args,
+        max_retries,
+        NETWORK_RETRY_BACKOFF,
+        &|line| {
+            let mut tracker = tracker.borrow_mut();
+            if tracker.update(line) {
+                on_progress(tracker.fraction());
             }
-            Err(e) => {
-                let error_str = e.to_string();
-
-                if is_network_error(&error_str) && attempt < max_retries {
-                    warn!(
-                        "basestrap failed due to network error (attempt {}/{}): {}",
-                        attempt, max_retries, error_str
-                    );
-                    warn!("Retrying in {} seconds...", BASESTRAP_RETRY_DELAY_SECS);
-                    thread::sleep(Duration::from_secs(BASESTRAP_RETRY_DELAY_SECS));
-                    last_error = Some(error_str);
-                } else {
-                    // Non-network error or final attempt - fail immediately
-                    return Err(DeploytixError::CommandFailed {
-                        command: "basestrap".to_string(),
-                        stderr: error_str,
-                    });
-                }
-            }
-        }
+        },
+    );
+    if let Some(ref conf_path) = custom_conf {
+        let _ = std::fs::remove_file(conf_path);
+    }
+    result?;
+    info!(
+        "basestrap finished in {:.1}s",
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filesystem_overrides_add_their_tools() {
+        let mut cfg = DeploymentConfig::sample();
+        cfg.disk.filesystem = Filesystem::Ext4;
+        cfg.disk.boot_filesystem = Filesystem::Ext4;
+        assert!(!build_package_list(&cfg).contains(&"xfsprogs".to_string()));
+
+        let home = cfg
+            .disk
+            .partitions
+            .iter_mut()
+            .find(|p| p.mount_point == "/home");
+        home.unwrap().filesystem = Some(Filesystem::Xfs);
+        let packages = build_package_list(&cfg);
+        assert!(packages.contains(&"xfsprogs".to_string()));
+        assert_eq!(packages.iter().filter(|p| *p == "e2fsprogs").count(), 1);
     }
 
-    // Should not reach here, but handle it just in case
-    Err(DeploytixError::CommandFailed {
-        command: "basestrap".to_string(),
-        stderr: last_error.unwrap_or_else(|| "Unknown error after retries".to_string()),
-    })
+    #[test]
+    fn package_cache_dir_must_be_a_writable_directory() {
+        let dir = std::env::temp_dir();
+        assert!(check_package_cache_dir(dir.to_str().unwrap()).is_ok());
+        assert!(check_package_cache_dir("relative/cache").is_err());
+        assert!(check_package_cache_dir("/nonexistent/deploytix-cache").is_err());
+    }
+
+    #[test]
+    fn parallel_downloads_default_is_bounded() {
+        assert_eq!(default_parallel_downloads(1, 10), 2);
+        assert_eq!(default_parallel_downloads(16, 10), MAX_PARALLEL_DOWNLOADS);
+        assert_eq!(default_parallel_downloads(16, 1), 3);
+        assert_eq!(default_parallel_downloads(16, 0), 3);
+    }
+
+    #[test]
+    fn mirrors_are_counted_when_enabled() {
+        let list = "## Default\nServer = https://a/$repo\n# Server = https://b/$repo\nServer = https://c/$repo\n";
+        assert_eq!(count_mirrors(list), 2);
+    }
+
+    #[test]
+    fn parallel_downloads_replaces_commented_setting() {
+        let conf = "[options]\nArchitecture = auto\n#ParallelDownloads = 5\n\n[system]\nInclude = /etc/pacman.d/mirrorlist\n";
+        let out = set_parallel_downloads(conf, 4);
+        assert!(out.contains("\nParallelDownloads = 4\n"));
+        assert!(!out.contains("#ParallelDownloads"));
+        assert_eq!(out.matches("ParallelDownloads").count(), 1);
+    }
+
+    #[test]
+    fn parallel_downloads_is_added_to_options() {
+        let conf = "[options]\nArchitecture = auto\n[system]\nInclude = x\n";
+        let out = set_parallel_downloads(conf, 3);
+        let options_end = out.find("[system]").unwrap();
+        assert!(out[..options_end].contains("ParallelDownloads = 3"));
+    }
+
+    #[test]
+    fn pacman_output_maps_to_transaction_progress() {
+        let mut progress = PacmanProgress::default();
+        assert!(progress.update(":: Synchronizing package databases..."));
+        assert_eq!(progress.fraction(), None);
+        assert!(!progress.update("downloading system.db..."));
+
+        assert!(progress.update("Packages (4) base-3-2  linux-6.9.arch1-1"));
+        assert_eq!(progress.fraction(), Some(0.0));
+        assert!(progress.update("downloading base-3-2-any.pkg.tar.zst..."));
+        assert!(!progress.update("downloading base-3-2-any.pkg.tar.zst.sig..."));
+        assert!(progress.update(" linux-6.9.arch1-1-x86_64.pkg.tar.zst downloading..."));
+        assert_eq!(progress.fraction(), Some(DOWNLOAD_SHARE * 0.5));
+
+        assert!(!progress.update("checking keyring..."));
+        assert!(progress.update("installing base..."));
+        assert!(progress.update("upgrading linux..."));
+        assert_eq!(
+            progress.fraction(),
+            Some(DOWNLOAD_SHARE + (1.0 - DOWNLOAD_SHARE) * 0.5)
+        );
+        assert!(progress.update("reinstalling glibc..."));
+        assert!(progress.update("installing bash..."));
+        assert!(!progress.update("installing extra..."));
+        assert_eq!(progress.fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn unparsed_pacman_output_is_indeterminate_once() {
+        let mut progress = PacmanProgress::default();
+        assert!(progress.update("installing base..."));
+        assert!(!progress.update("installing linux..."));
+        assert_eq!(progress.fraction(), None);
+        assert!(!progress.update("Packages (0)"));
+    }
 }
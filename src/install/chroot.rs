@@ -1,31 +1,155 @@
 //! Chroot and mount operations
 
-use crate::config::Filesystem;
-use crate::disk::detection::partition_path;
+use crate::config::{Filesystem, ZfsConfig};
 use crate::disk::formatting::{
     create_btrfs_subvolumes, create_zfs_datasets, create_zfs_pool, mount_btrfs_subvolumes,
     mount_zfs_boot, mount_zfs_datasets,
 };
-use crate::disk::layouts::{ComputedLayout, SubvolumeDef};
+use crate::disk::layouts::{exclude_from_snapshots, ComputedLayout, SubvolumeDef};
+use crate::disk::mount::{ensure_mounted, mount_points_under};
+use crate::disk::volumes::VolumeSet;
+use crate::resources::runtime_path;
 use crate::utils::command::CommandRunner;
-use crate::utils::error::Result;
+use crate::utils::error::{DeploytixError, Result};
 use tracing::{info, warn};
 
-/// Mount all partitions according to the layout
-/// Handles regular partitions, btrfs subvolume layouts, and ZFS dataset layouts
-pub fn mount_partitions(
+/// Mount the data volume of partition `name` (not the ESP or /boot),
+/// adding the layout's f2fs compression options when compression is
+/// enabled so files written during installation are compressed too.
+pub fn mount_data_volume(
     cmd: &CommandRunner,
     device: &str,
+    target: &str,
+    name: &str,
+    layout: &ComputedLayout,
+) -> Result<()> {
+    ensure_mounted(cmd, device, target, None, layout.data_mount_options(name))
+}
+
+/// Mount a resolved [`VolumeSet`] under `install_root`: data volumes
+/// shallowest first, then /boot, the ESP, and swap.
+///
+/// The plain, multi-LUKS, and LVM thin paths differ only in the device
+/// paths recorded in the set, so they all mount through here.
+pub fn mount_volume_set(
+    cmd: &CommandRunner,
+    volumes: &VolumeSet,
     layout: &ComputedLayout,
     install_root: &str,
     boot_filesystem: &Filesystem,
 ) -> Result<()> {
-    mount_partitions_inner(cmd, device, layout, install_root, boot_filesystem)
+    mount_data_volumes(cmd, volumes, layout, install_root)?;
+    mount_system_volumes(cmd, volumes, layout, install_root, boot_filesystem)
 }
 
-fn mount_partitions_inner(
+/// Mount the data volumes of a [`VolumeSet`] under `install_root`,
+/// shallowest first.
+pub fn mount_data_volumes(
     cmd: &CommandRunner,
-    device: &str,
+    volumes: &VolumeSet,
+    layout: &ComputedLayout,
+    install_root: &str,
+) -> Result<()> {
+    for entry in volumes.entries_mount_order() {
+        let full_mount = if entry.mount_point == "/" {
+            install_root.to_string()
+        } else {
+            format!("{}{}", install_root, entry.mount_point)
+        };
+
+        info!("Mounting {} to {}", entry.device_path, full_mount);
+        mount_data_volume(cmd, &entry.device_path, &full_mount, &entry.name, layout)?;
+    }
+    Ok(())
+}
+
+/// Mount /boot and the ESP and enable swap, once the root filesystem is
+/// mounted.  /boot goes first because the ESP usually lives inside it.
+pub fn mount_system_volumes(
+    cmd: &CommandRunner,
+    volumes: &VolumeSet,
+    layout: &ComputedLayout,
+    install_root: &str,
+    boot_filesystem: &Filesystem,
+) -> Result<()> {
+    mount_boot_and_esp(cmd, volumes, layout, install_root, boot_filesystem, true)?;
+
+    if let Some(ref swap_device) = volumes.swap {
+        info!("Enabling swap on {}", swap_device);
+        cmd.run("swapon", &[swap_device])?;
+    }
+
+    Ok(())
+}
+
+/// Mount /boot and the ESP of an already installed system.  Unlike
+/// [`mount_system_volumes`] nothing is created and swap stays off.
+pub fn remount_system_volumes(
+    cmd: &CommandRunner,
+    volumes: &VolumeSet,
+    layout: &ComputedLayout,
+    install_root: &str,
+    boot_filesystem: &Filesystem,
+) -> Result<()> {
+    mount_boot_and_esp(cmd, volumes, layout, install_root, boot_filesystem, false)
+}
+
+fn mount_boot_and_esp(
+    cmd: &CommandRunner,
+    volumes: &VolumeSet,
+    layout: &ComputedLayout,
+    install_root: &str,
+    boot_filesystem: &Filesystem,
+    create_subvolume: bool,
+) -> Result<()> {
+    if *boot_filesystem == Filesystem::Zfs {
+        // Boot pool was already created by format_boot_partition()
+        mount_zfs_boot(cmd, install_root)?;
+    } else {
+        let boot_device = &volumes.boot.device_path;
+        if boot_device.is_empty() {
+            return Err(DeploytixError::ConfigError(
+                "No Boot partition found in layout".to_string(),
+            ));
+        }
+
+        if *boot_filesystem == Filesystem::Btrfs && create_subvolume {
+            // Btrfs boot: create @boot subvolume and mount with subvol=@boot
+            mount_boot_btrfs_subvolume(
+                cmd,
+                boot_device,
+                install_root,
+                &layout.btrfs_mount_options,
+            )?;
+        } else if *boot_filesystem == Filesystem::Btrfs {
+            mount_btrfs_subvolumes(
+                cmd,
+                boot_device,
+                &[boot_subvolume(&layout.btrfs_mount_options)],
+                install_root,
+            )?;
+        } else {
+            let boot_mount = format!("{}/boot", install_root);
+            info!("Mounting {} to {}", boot_device, boot_mount);
+            ensure_mounted(cmd, boot_device, &boot_mount, None, None)?;
+        }
+    }
+
+    // BIOS layouts have no ESP
+    if let Some(ref efi_device) = volumes.efi {
+        let efi_mount = format!("{}{}", install_root, layout.esp_mount_point());
+        info!("Mounting {} to {}", efi_device, efi_mount);
+        ensure_mounted(cmd, efi_device, &efi_mount, None, None)?;
+    }
+
+    Ok(())
+}
+
+/// Mount all partitions according to the layout
+/// Handles regular partitions, btrfs subvolume layouts, and ZFS dataset layouts
+pub fn mount_partitions(
+    cmd: &CommandRunner,
+    volumes: &VolumeSet,
     layout: &ComputedLayout,
     install_root: &str,
     boot_filesystem: &Filesystem,
@@ -34,7 +158,7 @@ fn mount_partitions_inner(
     if layout.uses_subvolumes() {
         return mount_partitions_with_subvolumes(
             cmd,
-            device,
+            volumes,
             layout,
             install_root,
             boot_filesystem,
@@ -42,144 +166,54 @@ fn mount_partitions_inner(
     }
 
     info!(
-        "Mounting {} partitions from {} to {}",
-        layout.partitions.len(),
-        device,
+        "Mounting {} volumes to {}",
+        volumes.entries.len(),
         install_root
     );
 
-    // Sort partitions by mount point depth (root first, then deeper paths)
-    let mut mount_order: Vec<_> = layout
-        .partitions
-        .iter()
-        .filter(|p| p.mount_point.is_some() && !p.is_swap)
-        .collect();
-
-    mount_order.sort_by_key(|p| {
-        let mp = p.mount_point.as_ref().unwrap();
-        mp.matches('/').count()
-    });
-
-    // Mount each partition
-    for part in mount_order {
-        let part_path = partition_path(device, part.number);
-        let mount_point = part.mount_point.as_ref().unwrap();
-
-        // Btrfs boot: create @boot subvolume and mount with subvol=@boot
-        if part.is_boot_fs && *boot_filesystem == Filesystem::Btrfs {
-            mount_boot_btrfs_subvolume(cmd, &part_path, install_root)?;
-            continue;
-        }
-
-        // Construct full mount path
-        let full_mount = if mount_point == "/" {
-            install_root.to_string()
-        } else {
-            format!("{}{}", install_root, mount_point)
-        };
-
-        // Create mount point directory
-        if !cmd.is_dry_run() {
-            std::fs::create_dir_all(&full_mount)?;
-        }
-
-        info!("Mounting {} to {}", part_path, full_mount);
-        cmd.run("mount", &[&part_path, &full_mount])?;
-    }
-
-    // Enable swap partitions
-    for part in &layout.partitions {
-        if part.is_swap {
-            let part_path = partition_path(device, part.number);
-            info!("Enabling swap on {}", part_path);
-            cmd.run("swapon", &[&part_path])?;
-        }
-    }
-
-    Ok(())
+    mount_volume_set(cmd, volumes, layout, install_root, boot_filesystem)
 }
 
 /// Format and mount a ZFS layout.
 ///
 /// Creates the data pool and datasets, mounts them via legacy mountpoints,
-/// then mounts EFI, boot, and swap normally.
+/// then mounts EFI, boot, and swap normally.  A `passphrase` creates the
+/// pool with native encryption.
 pub fn mount_partitions_zfs(
     cmd: &CommandRunner,
-    device: &str,
+    volumes: &VolumeSet,
     layout: &ComputedLayout,
-    _filesystem: &Filesystem,
+    zfs: &ZfsConfig,
+    passphrase: Option<&str>,
     boot_filesystem: &Filesystem,
     install_root: &str,
 ) -> Result<()> {
-    info!("Setting up ZFS pools and datasets on {}", device);
-
-    // Find the ROOT partition to create the data pool on
-    let root_part = layout
-        .partitions
-        .iter()
-        .find(|p| p.mount_point.as_deref() == Some("/") || p.name == "ROOT")
-        .ok_or_else(|| {
-            crate::utils::error::DeploytixError::ConfigError(
-                "No ROOT partition found for ZFS layout".to_string(),
-            )
-        })?;
-    let root_path = partition_path(device, root_part.number);
+    // The root volume holds the data pool
+    let root_path = root_volume(volumes).ok_or_else(|| {
+        DeploytixError::ConfigError("No ROOT partition found for ZFS layout".to_string())
+    })?;
+    info!("Setting up ZFS pools and datasets on {}", root_path);
 
     // Create the data pool and datasets
-    create_zfs_pool(cmd, &root_path)?;
-    create_zfs_datasets(cmd)?;
+    create_zfs_pool(cmd, root_path, zfs, passphrase)?;
+    create_zfs_datasets(cmd, zfs)?;
 
     // Mount datasets to install root
     if !cmd.is_dry_run() {
         std::fs::create_dir_all(install_root)?;
     }
-    mount_zfs_datasets(cmd, install_root)?;
-
-    // Mount boot partition
-    if *boot_filesystem == Filesystem::Zfs {
-        // Boot pool was already created by format_boot_partition()
-        mount_zfs_boot(cmd, install_root)?;
-    } else if *boot_filesystem == Filesystem::Btrfs {
-        // Btrfs boot: create @boot subvolume and mount with subvol=@boot
-        let boot_part = layout.partitions.iter().find(|p| p.is_boot_fs);
-        if let Some(boot) = boot_part {
-            let boot_dev = partition_path(device, boot.number);
-            mount_boot_btrfs_subvolume(cmd, &boot_dev, install_root)?;
-        }
-    } else {
-        // Non-ZFS, non-btrfs boot: mount the partition normally
-        let boot_part = layout.partitions.iter().find(|p| p.is_boot_fs);
-        if let Some(boot) = boot_part {
-            let boot_dev = partition_path(device, boot.number);
-            let boot_mount = format!("{}/boot", install_root);
-            if !cmd.is_dry_run() {
-                std::fs::create_dir_all(&boot_mount)?;
-            }
-            cmd.run("mount", &[&boot_dev, &boot_mount])?;
-        }
-    }
-
-    // Mount EFI partition
-    let efi_part = layout.partitions.iter().find(|p| p.is_efi);
-    if let Some(efi) = efi_part {
-        let efi_dev = partition_path(device, efi.number);
-        let efi_mount = format!("{}/boot/efi", install_root);
-        if !cmd.is_dry_run() {
-            std::fs::create_dir_all(&efi_mount)?;
-        }
-        cmd.run("mount", &[&efi_dev, &efi_mount])?;
-    }
+    mount_zfs_datasets(cmd, zfs, install_root)?;
 
-    // Enable swap
-    for part in &layout.partitions {
-        if part.is_swap {
-            let part_path = partition_path(device, part.number);
-            info!("Enabling swap on {}", part_path);
-            cmd.run("swapon", &[&part_path])?;
-        }
-    }
+    mount_system_volumes(cmd, volumes, layout, install_root, boot_filesystem)
+}
 
-    Ok(())
+/// Device of the volume mounted at `/`
+fn root_volume(volumes: &VolumeSet) -> Option<&str> {
+    volumes
+        .entries
+        .iter()
+        .find(|e| e.mount_point == "/")
+        .map(|e| e.device_path.as_str())
 }
 
 /// Mount partitions for layouts using btrfs subvolumes
@@ -189,107 +223,109 @@ pub fn mount_partitions_zfs(
 /// with subvol=@boot).
 fn mount_partitions_with_subvolumes(
     cmd: &CommandRunner,
-    device: &str,
+    volumes: &VolumeSet,
     layout: &ComputedLayout,
     install_root: &str,
     boot_filesystem: &Filesystem,
 ) -> Result<()> {
     let subvolumes = layout.subvolumes.as_ref().unwrap();
-    info!("Setting up btrfs subvolumes on {} (root partition)", device);
 
-    // Find the ROOT partition
-    let root_part = layout
-        .partitions
-        .iter()
-        .find(|p| p.name == "ROOT")
-        .ok_or_else(|| {
-            crate::utils::error::DeploytixError::ConfigError(
-                "No ROOT partition found for subvolume layout".to_string(),
-            )
-        })?;
-
-    let root_path = partition_path(device, root_part.number);
+    let root_path = root_volume(volumes).ok_or_else(|| {
+        DeploytixError::ConfigError("No ROOT partition found for subvolume layout".to_string())
+    })?;
+    info!(
+        "Setting up btrfs subvolumes on {} (root partition)",
+        root_path
+    );
 
     // Create subvolumes on the ROOT partition
     // This temporarily mounts the raw btrfs, creates subvolumes, then unmounts
-    let temp_mount = "/tmp/deploytix_btrfs_setup";
-    create_btrfs_subvolumes(cmd, &root_path, subvolumes, temp_mount)?;
+    let temp_mount = runtime_path("btrfs_setup");
+    create_btrfs_subvolumes(cmd, root_path, subvolumes, &temp_mount.to_string_lossy())?;
 
     // Now mount the subvolumes to their final locations
-    mount_btrfs_subvolumes(cmd, &root_path, subvolumes, install_root)?;
-
-    // Handle BOOT partition — must be mounted before EFI (/boot/efi is inside /boot)
-    if let Some(boot_part) = layout.partitions.iter().find(|p| p.is_boot_fs) {
-        let boot_path = partition_path(device, boot_part.number);
+    mount_btrfs_subvolumes(cmd, root_path, subvolumes, install_root)?;
+
+    // Mount the remaining data volumes.  Those whose partition carries a
+    // btrfs subvolume (subvolume_name.is_some()) are mounted via
+    // `subvol=@name` instead of a raw filesystem mount.
+    for entry in volumes.entries_mount_order() {
+        if entry.mount_point == "/" {
+            continue; // Root handled above
+        }
 
-        if *boot_filesystem == Filesystem::Btrfs {
-            // BOOT partition is btrfs: create @boot subvolume, then mount it
-            mount_boot_btrfs_subvolume(cmd, &boot_path, install_root)?;
+        let subvol_name = layout
+            .partitions
+            .iter()
+            .find(|p| p.name == entry.name)
+            .and_then(|p| p.subvolume_name.as_ref());
+        if let Some(subvol_name) = subvol_name {
+            // Btrfs partition with a dedicated subvolume: create the subvolume
+            // then mount it with `subvol=@name`.
+            let part_subvols = partition_subvolumes(layout, subvol_name, &entry.mount_point);
+
+            let temp_mount =
+                runtime_path(&format!("btrfs_{}", subvol_name.trim_start_matches('@')));
+            create_btrfs_subvolumes(
+                cmd,
+                &entry.device_path,
+                &part_subvols,
+                &temp_mount.to_string_lossy(),
+            )?;
+            mount_btrfs_subvolumes(cmd, &entry.device_path, &part_subvols, install_root)?;
         } else {
-            // Non-btrfs boot: plain mount
-            let full_mount = format!("{}/boot", install_root);
+            // Non-btrfs (or plain-mount) partition.
+            let full_mount = format!("{}{}", install_root, entry.mount_point);
             if !cmd.is_dry_run() {
                 std::fs::create_dir_all(&full_mount)?;
             }
-            info!("Mounting {} to {}", boot_path, full_mount);
-            cmd.run("mount", &[&boot_path, &full_mount])?;
+            info!("Mounting {} to {}", entry.device_path, full_mount);
+            mount_data_volume(cmd, &entry.device_path, &full_mount, &entry.name, layout)?;
         }
     }
 
-    // Mount remaining partitions (EFI, swap, and other btrfs data partitions).
-    // Data partitions that carry a per-partition btrfs subvolume (subvolume_name.is_some())
-    // are mounted via `subvol=@name` instead of a raw filesystem mount.
-    for part in &layout.partitions {
-        if part.name == "ROOT" || part.is_boot_fs {
-            continue; // Already handled above
-        }
+    mount_system_volumes(cmd, volumes, layout, install_root, boot_filesystem)
+}
 
-        if part.is_swap {
-            let part_path = partition_path(device, part.number);
-            info!("Enabling swap on {}", part_path);
-            cmd.run("swapon", &[&part_path])?;
-        } else if let Some(ref mount_point) = part.mount_point {
-            let part_path = partition_path(device, part.number);
-
-            if let Some(ref subvol_name) = part.subvolume_name {
-                // Btrfs partition with a dedicated subvolume: create the subvolume
-                // then mount it with `subvol=@name`.
-                //
-                // /var is special: in addition to its own "@var" subvolume it
-                // also hosts "@log" (→ /var/log) so that logs can be excluded
-                // from snapshots independently.
-                let mut part_subvols = vec![SubvolumeDef {
-                    name: subvol_name.clone(),
-                    mount_point: mount_point.clone(),
-                    mount_options: "defaults,noatime,compress=zstd".to_string(),
-                }];
-                if mount_point == "/var" {
-                    part_subvols.push(SubvolumeDef {
-                        name: "@log".to_string(),
-                        mount_point: "/var/log".to_string(),
-                        mount_options: "defaults,noatime,compress=zstd".to_string(),
-                    });
-                }
-
-                let temp_mount = format!(
-                    "/tmp/deploytix_btrfs_{}",
-                    subvol_name.trim_start_matches('@')
-                );
-                create_btrfs_subvolumes(cmd, &part_path, &part_subvols, &temp_mount)?;
-                mount_btrfs_subvolumes(cmd, &part_path, &part_subvols, install_root)?;
-            } else {
-                // Non-btrfs (or plain-mount) partition.
-                let full_mount = format!("{}{}", install_root, mount_point);
-                if !cmd.is_dry_run() {
-                    std::fs::create_dir_all(&full_mount)?;
-                }
-                info!("Mounting {} to {}", part_path, full_mount);
-                cmd.run("mount", &[&part_path, &full_mount])?;
-            }
+/// Subvolumes on a data partition with a dedicated btrfs subvolume.
+///
+/// /var is special: in addition to its own "@var" subvolume it also hosts
+/// "@log" (→ /var/log) so that logs can be excluded from snapshots
+/// independently, and with snapshots "@pkg" (→ /var/cache/pacman/pkg).
+pub fn partition_subvolumes(
+    layout: &ComputedLayout,
+    subvol_name: &str,
+    mount_point: &str,
+) -> Vec<SubvolumeDef> {
+    let mount_options = &layout.btrfs_mount_options;
+    let mut subvols = vec![SubvolumeDef {
+        name: subvol_name.to_string(),
+        mount_point: mount_point.to_string(),
+        mount_options: mount_options.to_string(),
+        nocow: false,
+    }];
+    if mount_point == "/var" {
+        if layout.has_snapshots() {
+            exclude_from_snapshots(&mut subvols, mount_options);
+        } else {
+            subvols.push(SubvolumeDef {
+                name: "@log".to_string(),
+                mount_point: "/var/log".to_string(),
+                mount_options: mount_options.to_string(),
+                nocow: false,
+            });
         }
     }
+    subvols
+}
 
-    Ok(())
+fn boot_subvolume(mount_options: &str) -> SubvolumeDef {
+    SubvolumeDef {
+        name: "@boot".to_string(),
+        mount_point: "/boot".to_string(),
+        mount_options: mount_options.to_string(),
+        nocow: false,
+    }
 }
 
 /// Setup and mount a btrfs boot partition with an @boot subvolume.
@@ -303,18 +339,15 @@ pub fn mount_boot_btrfs_subvolume(
     cmd: &CommandRunner,
     boot_device: &str,
     install_root: &str,
+    mount_options: &str,
 ) -> Result<()> {
     info!(
         "Setting up btrfs @boot subvolume on {} for {}",
         boot_device, install_root
     );
-    let boot_subvol = vec![SubvolumeDef {
-        name: "@boot".to_string(),
-        mount_point: "/boot".to_string(),
-        mount_options: "defaults,noatime,compress=zstd".to_string(),
-    }];
-    let boot_temp = "/tmp/deploytix_btrfs_boot";
-    create_btrfs_subvolumes(cmd, boot_device, &boot_subvol, boot_temp)?;
+    let boot_subvol = vec![boot_subvolume(mount_options)];
+    let boot_temp = runtime_path("btrfs_boot");
+    create_btrfs_subvolumes(cmd, boot_device, &boot_subvol, &boot_temp.to_string_lossy())?;
     mount_btrfs_subvolumes(cmd, boot_device, &boot_subvol, install_root)?;
     Ok(())
 }
@@ -328,23 +361,11 @@ pub fn unmount_all(cmd: &CommandRunner, install_root: &str) -> Result<()> {
 
     // Get list of mounted filesystems under install_root
     let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
-    let mut mount_points: Vec<&str> = mounts
-        .lines()
-        .filter_map(|line| {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 && parts[1].starts_with(install_root) {
-                Some(parts[1])
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    // Sort by depth (deepest first for proper unmounting)
-    mount_points.sort_by_key(|b| std::cmp::Reverse(b.matches('/').count()));
+    // Deepest first for proper unmounting
+    let mount_points = mount_points_under(&mounts, install_root);
 
     // Unmount each
-    for mp in mount_points {
+    for mp in &mount_points {
         info!("Unmounting {}", mp);
         if let Err(e) = cmd.run("umount", &[mp]) {
             warn!("Failed to unmount {}: {} (trying lazy unmount)", mp, e);
@@ -2,7 +2,8 @@
 
 use crate::config::DeploymentConfig;
 use crate::configure::encryption::{get_luks_uuid, LuksContainer};
-use crate::configure::keyfiles::{keyfile_path, VolumeKeyfile};
+use crate::configure::fido2;
+use crate::configure::keyfiles::VolumeKeyfile;
 use crate::disk::detection::partition_path;
 use crate::utils::command::CommandRunner;
 use crate::utils::error::Result;
@@ -111,33 +112,60 @@ pub fn generate_crypttab(
     Ok(())
 }
 
+/// Options for a multi-volume data container; `fido2` adds the token
+/// option the crypttab-unlock hook looks for.
+fn data_volume_options(integrity: bool, fido2: bool) -> String {
+    if fido2 {
+        format!("{},{}", crypttab_options(integrity), fido2::CRYPTTAB_OPTION)
+    } else {
+        crypttab_options(integrity).to_string()
+    }
+}
+
 /// Generate /etc/crypttab for multi-volume encrypted system
 ///
 /// Creates entries for ROOT, USR, VAR, HOME and optionally BOOT with keyfile
-/// paths for automatic unlocking during initramfs.
+/// paths for automatic unlocking during initramfs.  With `fido2` the data
+/// volumes are unlocked by the security key instead of keyfiles.
 pub fn generate_crypttab_multi_volume(
     cmd: &CommandRunner,
     containers: &[LuksContainer],
     boot_container: Option<&LuksContainer>,
     keyfiles: &[VolumeKeyfile],
     integrity: bool,
+    fido2: bool,
     install_root: &str,
 ) -> Result<()> {
     let total = containers.len() + if boot_container.is_some() { 1 } else { 0 };
-    let options = crypttab_options(integrity);
+    let options = data_volume_options(integrity, fido2);
+    // The keyfile would unlock before the token is ever asked for
+    let keyfile = |volume_name: &str| -> String {
+        keyfiles
+            .iter()
+            .find(|k| k.volume_name == volume_name)
+            .map(|k| k.keyfile_path.clone())
+            .unwrap_or_else(|| "none".to_string())
+    };
+    let data_keyfile = |volume_name: &str| -> String {
+        if fido2 {
+            return "none".to_string();
+        }
+        keyfile(volume_name)
+    };
     info!("Generating /etc/crypttab for {} encrypted volumes", total);
 
     if cmd.is_dry_run() {
         println!("  [dry-run] Would generate /etc/crypttab:");
         for container in containers {
-            let kf_path = keyfile_path(&container.volume_name);
             println!(
                 "    {} UUID=<LUKS_UUID> {} {}",
-                container.volume_name, kf_path, options
+                container.volume_name,
+                data_keyfile(&container.volume_name),
+                options
             );
         }
         if let Some(boot) = boot_container {
-            let kf_path = keyfile_path(&boot.volume_name);
+            let kf_path = keyfile(&boot.volume_name);
             // Boot uses LUKS1, always discard (no integrity)
             println!(
                 "    {} UUID=<BOOT_LUKS_UUID> {} luks,discard",
@@ -158,18 +186,11 @@ pub fn generate_crypttab_multi_volume(
     for container in containers {
         let uuid = get_luks_uuid(&container.device)?;
 
-        // Find matching keyfile
-        let kf_path = keyfiles
-            .iter()
-            .find(|k| k.volume_name == container.volume_name)
-            .map(|k| k.keyfile_path.clone())
-            .unwrap_or_else(|| keyfile_path(&container.volume_name));
-
         content.push_str(&format!(
             "{name}    UUID={uuid}    {keyfile}    {options}\n",
             name = container.volume_name,
             uuid = uuid,
-            keyfile = kf_path,
+            keyfile = data_keyfile(&container.volume_name),
             options = options,
         ));
     }
@@ -179,11 +200,7 @@ pub fn generate_crypttab_multi_volume(
     if let Some(boot) = boot_container {
         let uuid = get_luks_uuid(&boot.device)?;
 
-        let kf_path = keyfiles
-            .iter()
-            .find(|k| k.volume_name == boot.volume_name)
-            .map(|k| k.keyfile_path.clone())
-            .unwrap_or_else(|| keyfile_path(&boot.volume_name));
+        let kf_path = keyfile(&boot.volume_name);
 
         content.push_str(&format!(
             "{name}    UUID={uuid}    {keyfile}    luks,discard\n",
@@ -220,4 +237,13 @@ mod tests {
         // dm-integrity is incompatible with TRIM/discard
         assert_eq!(crypttab_options(true), "luks");
     }
+
+    #[test]
+    fn fido2_adds_token_option() {
+        assert_eq!(
+            data_volume_options(false, true),
+            "luks,discard,fido2-device=auto"
+        );
+        assert_eq!(data_volume_options(true, false), "luks");
+    }
 }
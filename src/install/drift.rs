@@ -0,0 +1,324 @@
+//! Configuration drift check against an installed system
+//!
+//! Compares the fields of a [`DeploymentConfig`] that can be read back from
+//! an installed system (hostname, locale, keymap, timezone, init, kernel,
+//! desktop, fstab mount points) and reports every mismatch.  Nothing on the
+//! system is changed.
+
+use crate::config::{DeploymentConfig, DesktopEnvironment, Filesystem, InitSystem, KernelChoice};
+use crate::configure::locale::AUTO_TIMEZONE;
+use crate::disk::layouts::compute_layout_from_config;
+use std::fs;
+
+/// Disk size the expected layout is computed for.  Only the mount points
+/// are compared, and those do not depend on the size.
+const NOMINAL_DISK_MIB: u64 = 1024 * 1024;
+
+/// Package that marks each desktop environment as installed.
+pub(super) const DESKTOP_MARKERS: &[(DesktopEnvironment, &str)] = &[
+    (DesktopEnvironment::Kde, "plasma-desktop"),
+    (DesktopEnvironment::Gnome, "gnome-shell"),
+    (DesktopEnvironment::Xfce, "xfce4-session"),
+];
+
+/// One compared field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+    pub matches: bool,
+}
+
+impl FieldDiff {
+    fn exact(field: &'static str, expected: String, actual: String) -> Self {
+        let matches = expected == actual;
+        Self {
+            field,
+            expected,
+            actual,
+            matches,
+        }
+    }
+}
+
+/// Result of comparing a configuration with an installed system.
+#[derive(Debug, Clone, Default)]
+pub struct DriftReport {
+    pub fields: Vec<FieldDiff>,
+}
+
+impl DriftReport {
+    /// Whether any field differs from the configuration.
+    pub fn has_drift(&self) -> bool {
+        self.fields.iter().any(|f| !f.matches)
+    }
+
+    /// Print a field-by-field table followed by a summary line.
+    pub fn print(&self) {
+        println!("  {:<10} {:<30} ACTUAL", "FIELD", "EXPECTED");
+        println!("{}", "-".repeat(72));
+        for f in &self.fields {
+            let mark = if f.matches { ' ' } else { '✗' };
+            println!("{} {:<10} {:<30} {}", mark, f.field, f.expected, f.actual);
+        }
+        println!();
+
+        let drifted = self.fields.iter().filter(|f| !f.matches).count();
+        if drifted == 0 {
+            println!("✓ No drift found");
+        } else {
+            println!("✗ {} field(s) drifted from the configuration", drifted);
+        }
+    }
+}
+
+/// Compare `config` with the system installed at `root` ("/" for the
+/// running system).
+pub fn check_drift(config: &DeploymentConfig, root: &str) -> DriftReport {
+    let read = |path: &str| fs::read_to_string(format!("{}{}", root.trim_end_matches('/'), path));
+    let packages = installed_packages(root);
+    let unset = || "(unset)".to_string();
+
+    let mut fields = vec![
+        FieldDiff::exact(
+            "hostname",
+            config.system.hostname.clone(),
+            read("/etc/hostname")
+                .map(|s| s.trim().to_string())
+                .unwrap_or_else(|_| unset()),
+        ),
+        FieldDiff::exact(
+            "locale",
+            config.system.lang().to_string(),
+            read("/etc/locale.conf")
+                .ok()
+                .and_then(|c| conf_value(&c, "LANG"))
+                .unwrap_or_else(unset),
+        ),
+        FieldDiff::exact(
+            "keymap",
+            config.system.keymap.clone(),
+            read("/etc/vconsole.conf")
+                .ok()
+                .and_then(|c| conf_value(&c, "KEYMAP"))
+                .unwrap_or_else(unset),
+        ),
+    ];
+
+    // "auto" was resolved at install time; any zone satisfies it
+    let installed_zone = installed_timezone(root);
+    fields.push(FieldDiff {
+        field: "timezone",
+        expected: config.system.timezone.clone(),
+        matches: match installed_zone {
+            Some(ref zone) => {
+                config.system.timezone == AUTO_TIMEZONE || *zone == config.system.timezone
+            }
+            None => false,
+        },
+        actual: installed_zone.unwrap_or_else(unset),
+    });
+
+    let inits = [
+        InitSystem::Runit,
+        InitSystem::OpenRC,
+        InitSystem::S6,
+        InitSystem::Dinit,
+    ];
+    let installed_inits: Vec<String> = inits
+        .iter()
+        .filter(|i| packages.iter().any(|p| p == i.base_package()))
+        .map(|i| i.to_string())
+        .collect();
+    fields.push(FieldDiff {
+        field: "init",
+        expected: config.system.init.to_string(),
+        matches: installed_inits.contains(&config.system.init.to_string()),
+        actual: list_or_none(&installed_inits),
+    });
+
+    let kernels = [
+        KernelChoice::Zen,
+        KernelChoice::Lts,
+        KernelChoice::Stable,
+        KernelChoice::Hardened,
+    ];
+    let installed_kernels: Vec<String> = kernels
+        .iter()
+        .map(|k| k.package().to_string())
+        .filter(|k| packages.contains(k))
+        .collect();
+    fields.push(FieldDiff {
+        field: "kernel",
+        expected: config.system.kernel.package().to_string(),
+        matches: installed_kernels
+            .iter()
+            .any(|k| k == config.system.kernel.package()),
+        actual: list_or_none(&installed_kernels),
+    });
+
+    let desktop = DESKTOP_MARKERS
+        .iter()
+        .find(|(_, marker)| packages.iter().any(|p| p == marker))
+        .map(|(de, _)| de.clone())
+        .unwrap_or(DesktopEnvironment::None);
+    fields.push(FieldDiff::exact(
+        "desktop",
+        config.desktop.environment.to_string(),
+        desktop.to_string(),
+    ));
+
+    // ZFS datasets mount themselves and never appear in fstab
+    if config.disk.filesystem != Filesystem::Zfs {
+        if let Some(expected) = expected_mount_points(config) {
+            let actual = read("/etc/fstab")
+                .map(|c| fstab_mount_points(&c))
+                .unwrap_or_default();
+            fields.push(FieldDiff::exact(
+                "fstab",
+                list_or_none(&expected),
+                list_or_none(&actual),
+            ));
+        }
+    }
+
+    DriftReport { fields }
+}
+
+/// Value of `KEY=value` in a shell-style config file, unquoted.
+pub(super) fn conf_value(content: &str, key: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let (k, v) = line.trim().split_once('=')?;
+        (k.trim() == key).then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Zone name the `/etc/localtime` symlink points at.
+pub(super) fn installed_timezone(root: &str) -> Option<String> {
+    let target = fs::read_link(format!("{}/etc/localtime", root.trim_end_matches('/'))).ok()?;
+    let target = target.to_string_lossy();
+    let (_, zone) = target.split_once("zoneinfo/")?;
+    Some(zone.to_string())
+}
+
+/// Names of the packages in the pacman local database under `root`.
+pub(super) fn installed_packages(root: &str) -> Vec<String> {
+    let dir = format!("{}/var/lib/pacman/local", root.trim_end_matches('/'));
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| package_name(&e.file_name().to_string_lossy()))
+        .collect()
+}
+
+/// Package name from a local database entry (`name-pkgver-pkgrel`).
+fn package_name(entry: &str) -> Option<String> {
+    let mut parts = entry.rsplitn(3, '-');
+    let (_rel, _ver, name) = (parts.next()?, parts.next()?, parts.next()?);
+    Some(name.to_string())
+}
+
+/// Mount points the configuration puts in fstab, sorted.
+fn expected_mount_points(config: &DeploymentConfig) -> Option<Vec<String>> {
+    let layout = compute_layout_from_config(&config.disk, NOMINAL_DISK_MIB).ok()?;
+    let mut mounts: Vec<String> = layout
+        .partitions
+        .iter()
+        .filter(|p| !p.is_swap)
+        .filter_map(|p| p.mount_point.clone())
+        .chain(
+            layout
+                .subvolumes
+                .iter()
+                .flatten()
+                .map(|s| s.mount_point.clone()),
+        )
+        .chain(
+            layout
+                .planned_thin_volumes
+                .iter()
+                .flatten()
+                .map(|v| v.mount_point.clone()),
+        )
+        .collect();
+    if config.system.tmp_tmpfs {
+        mounts.push("/tmp".to_string());
+    }
+    mounts.sort();
+    mounts.dedup();
+    Some(mounts)
+}
+
+/// Mount points of the non-swap entries in fstab, sorted.
+fn fstab_mount_points(content: &str) -> Vec<String> {
+    let mut mounts: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let fields: Vec<&str> = l.split_whitespace().collect();
+            (fields.len() >= 3 && fields[2] != "swap").then(|| fields[1].to_string())
+        })
+        .collect();
+    mounts.sort();
+    mounts.dedup();
+    mounts
+}
+
+fn list_or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        "(none)".to_string()
+    } else {
+        items.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_shell_style_values() {
+        let conf = "# comment\nLANG=\"en_US.UTF-8\"\nLC_TIME=C\n";
+        assert_eq!(conf_value(conf, "LANG").as_deref(), Some("en_US.UTF-8"));
+        assert_eq!(conf_value(conf, "KEYMAP"), None);
+    }
+
+    #[test]
+    fn package_names_from_local_db() {
+        assert_eq!(
+            package_name("linux-zen-6.9.1.zen1-1").as_deref(),
+            Some("linux-zen")
+        );
+        assert_eq!(package_name("runit-2.1.2-5").as_deref(), Some("runit"));
+        assert_eq!(package_name("ALPM_DB_VERSION"), None);
+    }
+
+    #[test]
+    fn fstab_mounts_skip_swap_and_comments() {
+        let fstab = "# /\nUUID=a  /  btrfs  defaults  0  0\nUUID=b  none  swap  defaults  0  0\ntmpfs  /tmp  tmpfs  defaults  0  0\n";
+        assert_eq!(fstab_mount_points(fstab), vec!["/", "/tmp"]);
+    }
+
+    #[test]
+    fn drift_is_reported_per_field() {
+        let root = std::env::temp_dir().join(format!("deploytix-drift-{}", std::process::id()));
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::write(root.join("etc/hostname"), "other\n").unwrap();
+        fs::write(root.join("etc/locale.conf"), "LANG=en_US.UTF-8\n").unwrap();
+
+        let mut config = DeploymentConfig::sample();
+        config.system.locales = vec!["en_US.UTF-8".to_string()];
+        let report = check_drift(&config, &root.to_string_lossy());
+        let _ = fs::remove_dir_all(&root);
+
+        let field = |name: &str| report.fields.iter().find(|f| f.field == name).unwrap();
+        assert!(field("locale").matches);
+        assert!(!field("hostname").matches);
+        assert_eq!(field("hostname").actual, "other");
+        assert!(report.has_drift());
+    }
+}
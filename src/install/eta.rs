@@ -0,0 +1,407 @@
+//! Remaining-time estimates for the installation progress
+//!
+//! Each install phase is keyed by its progress status message, less any
+//! trailing "..." and parenthesised detail (see [`phase_key`]).  When an
+//! installation succeeds, the wall-clock duration of every phase and the
+//! time from its start to the end of the install are folded into moving
+//! averages kept in `~/.local/state/deploytix/phase-timings.toml`.  Later
+//! runs estimate the time remaining from those averages; phases never seen
+//! before fall back to extrapolating from the progress fraction.
+//!
+//! The same timings make up the per-phase report printed after a
+//! successful install.
+
+use crate::utils::error::{DeploytixError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// The phase a progress `status` belongs to: the message without its
+/// trailing "..." or parenthesised detail, so "Installing AUR packages
+/// (zen-browser)..." and "Installing AUR packages..." share timings.
+pub fn phase_key(status: &str) -> &str {
+    let key = status.trim_end_matches('.').trim_end();
+    match key
+        .strip_suffix(')')
+        .and_then(|k| k.rfind(" (").map(|i| &k[..i]))
+    {
+        Some(base) if !base.is_empty() => base,
+        _ => key,
+    }
+}
+
+/// Weight of the latest run in the moving averages.
+const SMOOTHING: f64 = 0.3;
+
+/// Below this progress fraction an extrapolated estimate is mostly noise.
+const MIN_EXTRAPOLATION_PROGRESS: f32 = 0.1;
+
+/// Averaged timings of one phase.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhaseStats {
+    /// How long the phase itself took.
+    pub duration_secs: f64,
+    /// Time from the start of the phase to the end of the install.
+    pub remaining_secs: f64,
+}
+
+/// Phase timings from prior successful runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PhaseHistory {
+    #[serde(default)]
+    pub phases: BTreeMap<String, PhaseStats>,
+}
+
+/// `~/.local/state/deploytix/phase-timings.toml`, honouring `XDG_STATE_HOME`.
+fn history_path() -> Result<PathBuf> {
+    let base = if let Ok(xdg) = std::env::var("XDG_STATE_HOME") {
+        PathBuf::from(xdg)
+    } else if let Ok(home) = std::env::var("HOME") {
+        PathBuf::from(home).join(".local").join("state")
+    } else {
+        return Err(DeploytixError::ConfigError(
+            "neither HOME nor XDG_STATE_HOME is set".to_string(),
+        ));
+    };
+    Ok(base.join("deploytix").join("phase-timings.toml"))
+}
+
+impl PhaseHistory {
+    /// Load the history, or an empty one if there is none or it is unreadable.
+    pub fn load() -> Self {
+        history_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the history back to the state file.
+    pub fn save(&self) -> Result<()> {
+        let path = history_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content =
+            toml::to_string_pretty(self).map_err(|e| DeploytixError::ConfigError(e.to_string()))?;
+        fs::write(&path, content)?;
+        debug!("Phase timings saved to {}", path.display());
+        Ok(())
+    }
+
+    /// Fold one run's timings into the averages.
+    fn record(&mut self, phase: &str, sample: PhaseStats) {
+        let average = |old: f64, new: f64| old + SMOOTHING * (new - old);
+        self.phases
+            .entry(phase.to_string())
+            .and_modify(|s| {
+                s.duration_secs = average(s.duration_secs, sample.duration_secs);
+                s.remaining_secs = average(s.remaining_secs, sample.remaining_secs);
+            })
+            .or_insert(sample);
+    }
+}
+
+/// A phase of the current run.
+struct PhaseRun {
+    status: String,
+    /// Offset from the start of the run.
+    started: Duration,
+    duration: Option<Duration>,
+}
+
+/// Times the phases of one installation and estimates what is left.
+pub struct PhaseTimer {
+    history: PhaseHistory,
+    start: Instant,
+    phases: Vec<PhaseRun>,
+}
+
+impl PhaseTimer {
+    pub fn new(history: PhaseHistory) -> Self {
+        Self {
+            history,
+            start: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Close the running phase and start `status`.  Returns the estimated
+    /// seconds remaining, if any estimate can be made.
+    pub fn begin_phase(&mut self, progress: f32, status: &str) -> Option<u64> {
+        let now = self.start.elapsed();
+        self.finish_current(now);
+        self.phases.push(PhaseRun {
+            status: status.to_string(),
+            started: now,
+            duration: None,
+        });
+        estimate(&self.history, status, progress, now)
+    }
+
+    /// Estimated seconds remaining partway through the running phase, now
+    /// at `progress`: the estimate from its history less the time spent in
+    /// it so far, or an extrapolation without one.
+    pub fn estimate_within_phase(&self, progress: f32) -> Option<u64> {
+        let now = self.start.elapsed();
+        let phase = self.phases.last()?;
+        match self.history.phases.get(phase_key(&phase.status)) {
+            Some(stats) => {
+                let spent = (now - phase.started).as_secs_f64();
+                Some((stats.remaining_secs - spent).max(0.0).round() as u64)
+            }
+            None => estimate(&self.history, &phase.status, progress, now),
+        }
+    }
+
+    fn finish_current(&mut self, now: Duration) {
+        if let Some(phase) = self.phases.last_mut().filter(|p| p.duration.is_none()) {
+            let duration = now - phase.started;
+            debug!("Phase '{}' took {:.1?}", phase.status, duration);
+            phase.duration = Some(duration);
+        }
+    }
+
+    /// Close the running phase and return the timings of the run.
+    pub fn finish(mut self) -> RunTimings {
+        let total = self.start.elapsed();
+        self.finish_current(total);
+        RunTimings {
+            phases: self
+                .phases
+                .into_iter()
+                .map(|p| PhaseTiming {
+                    status: p.status,
+                    started: p.started,
+                    duration: p.duration.unwrap_or_default(),
+                })
+                .collect(),
+            total,
+            history: self.history,
+        }
+    }
+}
+
+/// Wall-clock time of one phase of a finished run.
+#[derive(Debug, Clone)]
+pub struct PhaseTiming {
+    pub status: String,
+    /// Offset from the start of the run.
+    pub started: Duration,
+    pub duration: Duration,
+}
+
+/// Timings of a finished run, in phase order.
+pub struct RunTimings {
+    pub phases: Vec<PhaseTiming>,
+    pub total: Duration,
+    history: PhaseHistory,
+}
+
+impl RunTimings {
+    /// The phases merged by [`phase_key`], in order of first appearance:
+    /// durations add up, and each starts where its first run started.
+    fn grouped(&self) -> Vec<PhaseTiming> {
+        let mut groups: Vec<PhaseTiming> = Vec::new();
+        for phase in &self.phases {
+            let key = phase_key(&phase.status);
+            match groups.iter_mut().find(|g| g.status == key) {
+                Some(group) => group.duration += phase.duration,
+                None => groups.push(PhaseTiming {
+                    status: key.to_string(),
+                    ..phase.clone()
+                }),
+            }
+        }
+        groups
+    }
+
+    /// The per-phase table printed after the install, one line per phase.
+    pub fn report_lines(&self) -> Vec<String> {
+        let total = self.total.as_secs_f64().max(f64::EPSILON);
+        let mut lines = vec![
+            "Phase timings:".to_string(),
+            format!("  {:<60} {:>8} {:>6}", "PHASE", "TIME", "SHARE"),
+        ];
+        for phase in self.grouped() {
+            let share = phase.duration.as_secs_f64() / total * 100.0;
+            lines.push(format!(
+                "  {:<60} {:>8} {:>5.0}%",
+                phase.status,
+                format_duration(phase.duration),
+                share
+            ));
+        }
+        lines.push(format!(
+            "  {:<60} {:>8}",
+            "Total",
+            format_duration(self.total)
+        ));
+        lines
+    }
+
+    /// Fold this run into the history and save it.  Only called for
+    /// successful installations, so aborted runs do not skew the averages.
+    pub fn save(mut self) -> Result<()> {
+        for phase in self.grouped() {
+            let sample = PhaseStats {
+                duration_secs: phase.duration.as_secs_f64(),
+                remaining_secs: (self.total - phase.started).as_secs_f64(),
+            };
+            self.history.record(&phase.status, sample);
+        }
+        info!("Installation phases took {:.0?} in total", self.total);
+        self.history.save()
+    }
+}
+
+/// Compact duration for the timing report, e.g. "4m12s" or "35s".
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Seconds remaining at the start of `status`: the averaged time to the end
+/// from prior runs, or else the elapsed time extrapolated over the rest of
+/// the progress bar.
+fn estimate(history: &PhaseHistory, status: &str, progress: f32, elapsed: Duration) -> Option<u64> {
+    if let Some(stats) = history.phases.get(phase_key(status)) {
+        return Some(stats.remaining_secs.round() as u64);
+    }
+    if progress < MIN_EXTRAPOLATION_PROGRESS {
+        return None;
+    }
+    let progress = f64::from(progress.min(1.0));
+    Some((elapsed.as_secs_f64() * (1.0 - progress) / progress).round() as u64)
+}
+
+/// Human-readable remaining time, e.g. "~6 min remaining".
+pub fn format_eta(secs: u64) -> String {
+    match secs {
+        0 => "almost done".to_string(),
+        1..=59 => "<1 min remaining".to_string(),
+        _ => format!("~{} min remaining", secs.div_ceil(60)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_is_a_moving_average() {
+        let mut history = PhaseHistory::default();
+        let sample = |secs| PhaseStats {
+            duration_secs: secs,
+            remaining_secs: secs * 2.0,
+        };
+        history.record("Installing base system", sample(100.0));
+        history.record("Installing base system", sample(200.0));
+
+        let stats = history.phases["Installing base system"];
+        assert_eq!(stats.duration_secs, 130.0);
+        assert_eq!(stats.remaining_secs, 260.0);
+    }
+
+    #[test]
+    fn estimate_prefers_history_over_extrapolation() {
+        let mut history = PhaseHistory::default();
+        history.record(
+            "Installing base system",
+            PhaseStats {
+                duration_secs: 240.0,
+                remaining_secs: 360.0,
+            },
+        );
+        let elapsed = Duration::from_secs(60);
+
+        assert_eq!(
+            estimate(&history, "Installing base system", 0.3, elapsed),
+            Some(360)
+        );
+        assert_eq!(
+            estimate(&history, "Configuring system", 0.5, elapsed),
+            Some(60)
+        );
+        assert_eq!(estimate(&history, "Partitioning disk", 0.05, elapsed), None);
+    }
+
+    #[test]
+    fn estimate_within_phase_counts_down_from_history() {
+        let mut history = PhaseHistory::default();
+        history.record(
+            "Installing base system",
+            PhaseStats {
+                duration_secs: 240.0,
+                remaining_secs: 360.0,
+            },
+        );
+        let mut timer = PhaseTimer::new(history);
+        assert_eq!(timer.estimate_within_phase(0.3), None);
+
+        timer.begin_phase(0.3, "Installing base system");
+        timer.start -= Duration::from_secs(60);
+        assert_eq!(timer.estimate_within_phase(0.4), Some(300));
+    }
+
+    #[test]
+    fn phase_keys_drop_ellipsis_and_detail() {
+        assert_eq!(phase_key("Partitioning disk..."), "Partitioning disk");
+        assert_eq!(
+            phase_key("Installing base system (this may take a while)..."),
+            "Installing base system"
+        );
+        assert_eq!(
+            phase_key("Installing AUR packages (zen-browser)..."),
+            phase_key("Installing AUR packages...")
+        );
+        assert_eq!(phase_key("(odd)"), "(odd)");
+    }
+
+    #[test]
+    fn eta_strings() {
+        assert_eq!(format_eta(0), "almost done");
+        assert_eq!(format_eta(45), "<1 min remaining");
+        assert_eq!(format_eta(330), "~6 min remaining");
+    }
+
+    #[test]
+    fn report_lists_each_phase_with_its_share() {
+        let timings = RunTimings {
+            phases: vec![
+                PhaseTiming {
+                    status: "Partitioning disk...".to_string(),
+                    started: Duration::ZERO,
+                    duration: Duration::from_secs(30),
+                },
+                PhaseTiming {
+                    status: "Installing base system...".to_string(),
+                    started: Duration::from_secs(30),
+                    duration: Duration::from_secs(200),
+                },
+                PhaseTiming {
+                    status: "Installing base system (retry)...".to_string(),
+                    started: Duration::from_secs(230),
+                    duration: Duration::from_secs(70),
+                },
+            ],
+            total: Duration::from_secs(300),
+            history: PhaseHistory::default(),
+        };
+        let lines = timings.report_lines();
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[2].starts_with("  Partitioning disk "));
+        assert!(lines[2].ends_with("30s    10%"));
+        assert!(lines[3].starts_with("  Installing base system "));
+        assert!(lines[3].ends_with("4m30s    90%"));
+        assert!(lines[4].ends_with("5m00s"));
+    }
+}
@@ -1,12 +1,12 @@
 //! Fstab generation
 
-use crate::config::{Filesystem, SwapType};
-use crate::configure::encryption::LuksContainer;
+use crate::config::{Filesystem, SwapType, ZfsConfig};
 use crate::configure::swap::{swap_file_fstab_entry, SWAP_FILE_PATH};
 use crate::disk::detection::partition_path;
-use crate::disk::formatting::{get_partition_uuid, ZFS_BOOT_DATASET, ZFS_DATASETS};
-use crate::disk::layouts::{mount_point_to_subvol_name, multi_volume_subvolumes, ComputedLayout};
-use crate::disk::lvm::{lv_path, ThinVolumeDef};
+use crate::disk::formatting::{get_partition_uuid, zfs_datasets, ZFS_BOOT_DATASET};
+use crate::disk::layouts::ComputedLayout;
+use crate::disk::volumes::VolumeSet;
+use crate::install::chroot::partition_subvolumes;
 use crate::utils::command::CommandRunner;
 use crate::utils::error::Result;
 use std::fs;
@@ -18,13 +18,17 @@ use tracing::info;
 ///
 /// Only ext4 supports reliable automatic boot-time fsck (pass 2).
 /// btrfs, xfs, f2fs, and zfs must not be fsck'd at boot (pass 0).
-fn boot_fs_fstab_entry(boot_filesystem: &Filesystem) -> (&'static str, &'static str, u8) {
+/// `btrfs_options` is the layout's btrfs mount option string.
+fn boot_fs_fstab_entry(
+    boot_filesystem: &Filesystem,
+    btrfs_options: &str,
+) -> (&'static str, String, u8) {
     match boot_filesystem {
-        Filesystem::Ext4 => ("ext4", "defaults,noatime", 0),
-        Filesystem::Btrfs => ("btrfs", "subvol=@boot,defaults,noatime,compress=zstd", 0),
-        Filesystem::Xfs => ("xfs", "defaults,noatime", 0),
-        Filesystem::F2fs => ("f2fs", "defaults,noatime", 0),
-        Filesystem::Zfs => ("zfs", "zfsutil,defaults,noatime", 0),
+        Filesystem::Ext4 => ("ext4", "defaults,noatime".to_string(), 0),
+        Filesystem::Btrfs => ("btrfs", format!("subvol=@boot,{}", btrfs_options), 0),
+        Filesystem::Xfs => ("xfs", "defaults,noatime".to_string(), 0),
+        Filesystem::F2fs => ("f2fs", "defaults,noatime".to_string(), 0),
+        Filesystem::Zfs => ("zfs", "zfsutil,defaults".to_string(), 0),
     }
 }
 
@@ -46,27 +50,51 @@ fn fsck_pass(filesystem: &Filesystem, mount_point: &str) -> u8 {
     }
 }
 
-/// Return the fstab filesystem type string and default mount options for a
-/// data partition.
-fn fs_fstab_entry(filesystem: &Filesystem) -> (&'static str, &'static str) {
-    match filesystem {
-        Filesystem::Btrfs => ("btrfs", "defaults,noatime,compress=zstd"),
+/// Return the fstab filesystem type string and mount options for the data
+/// volume at `mount_point`: the options set on its partition entry, else
+/// the defaults, using the layout's btrfs options and, for f2fs, its
+/// compression options.  On SSDs ext4, xfs and f2fs also get `discard`.
+fn fs_fstab_entry(
+    filesystem: &Filesystem,
+    mount_point: &str,
+    layout: &ComputedLayout,
+) -> (&'static str, String) {
+    let (fstype, defaults) = match filesystem {
+        Filesystem::Btrfs => ("btrfs", layout.btrfs_mount_options.as_str()),
         Filesystem::Ext4 => ("ext4", "defaults,noatime"),
         Filesystem::Xfs => ("xfs", "defaults,noatime"),
-        Filesystem::F2fs => ("f2fs", "defaults,noatime"),
-        Filesystem::Zfs => ("zfs", "zfsutil,defaults,noatime"),
-    }
+        Filesystem::F2fs => (
+            "f2fs",
+            layout
+                .f2fs_mount_options
+                .as_deref()
+                .unwrap_or("defaults,noatime"),
+        ),
+        Filesystem::Zfs => ("zfs", "zfsutil,defaults"),
+    };
+    let discard = layout.discard
+        && matches!(
+            filesystem,
+            Filesystem::Ext4 | Filesystem::Xfs | Filesystem::F2fs
+        );
+    let defaults = if discard {
+        format!("{},discard", defaults)
+    } else {
+        defaults.to_string()
+    };
+    (fstype, layout.fstab_mount_options(mount_point, &defaults))
 }
 
-/// Append standard ZFS dataset fstab entries.
+/// Append fstab entries for the configured ZFS datasets.
 ///
 /// ZFS datasets with `mountpoint=legacy` are referenced by dataset name
-/// (e.g., `rpool/ROOT`) rather than by UUID.
-fn append_zfs_dataset_entries(content: &mut String) {
-    for (ds, mp) in ZFS_DATASETS {
+/// (e.g., `rpool/ROOT`) rather than by UUID.  No atime option is given, so the
+/// pool's `zfs.atime` properties apply.
+fn append_zfs_dataset_entries(content: &mut String, zfs: &ZfsConfig) {
+    for (ds, mp) in zfs_datasets(zfs) {
         let pass = 0; // ZFS handles its own fsck; pass is always 0
         content.push_str(&format!(
-            "{}  {}  zfs  zfsutil,defaults,noatime  0  {}\n",
+            "{}  {}  zfs  zfsutil,defaults  0  {}\n",
             ds, mp, pass
         ));
     }
@@ -76,7 +104,7 @@ fn append_zfs_dataset_entries(content: &mut String) {
 fn append_zfs_boot_entry(content: &mut String) {
     content.push_str(&format!(
         "\n# Boot dataset (ZFS)\n\
-         {}  /boot  zfs  zfsutil,defaults,noatime  0  0\n",
+         {}  /boot  zfs  zfsutil,defaults  0  0\n",
         ZFS_BOOT_DATASET
     ));
 }
@@ -90,6 +118,7 @@ pub fn generate_fstab(
     install_root: &str,
     filesystem: &Filesystem,
     boot_filesystem: &Filesystem,
+    zfs: &ZfsConfig,
 ) -> Result<()> {
     // Check if this layout uses subvolumes
     if layout.uses_subvolumes() {
@@ -129,7 +158,7 @@ pub fn generate_fstab(
 
     // ZFS data filesystem: use dataset names instead of partition UUIDs
     if *filesystem == Filesystem::Zfs {
-        append_zfs_dataset_entries(&mut fstab_content);
+        append_zfs_dataset_entries(&mut fstab_content, zfs);
 
         // EFI and swap still use UUIDs
         for part in &layout.partitions {
@@ -137,8 +166,9 @@ pub fn generate_fstab(
             if part.is_efi {
                 let uuid = get_partition_uuid(&part_path)?;
                 fstab_content.push_str(&format!(
-                    "UUID={}\t/boot/efi\tvfat\tumask=0077,defaults\t0\t0\n",
-                    uuid
+                    "UUID={}\t{}\tvfat\tumask=0077,defaults\t0\t0\n",
+                    uuid,
+                    layout.esp_mount_point()
                 ));
             } else if part.is_swap {
                 let uuid = get_partition_uuid(&part_path)?;
@@ -165,16 +195,21 @@ pub fn generate_fstab(
                     options = "umask=0077,defaults".to_string();
                     pass = 0;
                 } else if part.is_boot_fs {
-                    let (bfs, bopts, bpass) = boot_fs_fstab_entry(boot_filesystem);
+                    let (bfs, bopts, bpass) =
+                        boot_fs_fstab_entry(boot_filesystem, &layout.btrfs_mount_options);
                     fstype = bfs.to_string();
-                    options = bopts.to_string();
+                    options = bopts;
                     pass = bpass;
                 } else {
+                    let filesystem = layout.partition_filesystem(&part.name, filesystem);
                     fstype = filesystem.to_string();
-                    options = match filesystem {
-                        Filesystem::Btrfs => "defaults,noatime,compress=zstd".to_string(),
-                        _ => "defaults,noatime".to_string(),
-                    };
+                    options = layout.fstab_mount_options(
+                        mount_point,
+                        match filesystem {
+                            Filesystem::Btrfs => &layout.btrfs_mount_options,
+                            _ => "defaults,noatime",
+                        },
+                    );
                     pass = fsck_pass(filesystem, mount_point);
                 }
 
@@ -233,7 +268,7 @@ fn generate_fstab_with_subvolumes(
                 "    UUID=<ROOT_UUID> {} btrfs subvol={},{} 0 {}",
                 sv.mount_point,
                 sv.name,
-                sv.mount_options,
+                layout.fstab_mount_options(&sv.mount_point, &sv.mount_options),
                 0 // btrfs: no boot-time fsck
             );
         }
@@ -255,7 +290,11 @@ fn generate_fstab_with_subvolumes(
         let pass = 0; // btrfs: no boot-time fsck
         content.push_str(&format!(
             "UUID={}  {}  btrfs  subvol={},{}  0  {}\n",
-            root_uuid, sv.mount_point, sv.name, sv.mount_options, pass,
+            root_uuid,
+            sv.mount_point,
+            sv.name,
+            layout.fstab_mount_options(&sv.mount_point, &sv.mount_options),
+            pass,
         ));
     }
 
@@ -275,18 +314,20 @@ fn generate_fstab_with_subvolumes(
             ));
         } else if part.is_efi {
             content.push_str(&format!(
-                "\n# EFI System Partition\nUUID={}  /boot/efi  vfat  umask=0077,defaults  0  0\n",
-                uuid
+                "\n# EFI System Partition\nUUID={}  {}  vfat  umask=0077,defaults  0  0\n",
+                uuid,
+                layout.esp_mount_point()
             ));
         } else if part.is_boot_fs {
             // BOOT partition: btrfs gets @boot subvolume, others get a plain entry
             if *boot_filesystem == Filesystem::Btrfs {
                 content.push_str(&format!(
-                    "\n# Boot partition (btrfs @boot subvolume)\nUUID={}  /boot  btrfs  subvol=@boot,defaults,noatime,compress=zstd  0  0\n",
-                    uuid
+                    "\n# Boot partition (btrfs @boot subvolume)\nUUID={}  /boot  btrfs  subvol=@boot,{}  0  0\n",
+                    uuid, layout.btrfs_mount_options
                 ));
             } else {
-                let (fstype, opts, pass) = boot_fs_fstab_entry(boot_filesystem);
+                let (fstype, opts, pass) =
+                    boot_fs_fstab_entry(boot_filesystem, &layout.btrfs_mount_options);
                 content.push_str(&format!(
                     "\n# Boot partition\nUUID={}  /boot  {}  {}  0  {}\n",
                     uuid, fstype, opts, pass
@@ -296,20 +337,28 @@ fn generate_fstab_with_subvolumes(
             if let Some(ref subvol_name) = part.subvolume_name {
                 // Btrfs data partition with its own dedicated subvolume.
                 content.push_str(&format!(
-                    "\n# {} partition (btrfs {} subvolume)\nUUID={}  {}  btrfs  subvol={},defaults,noatime,compress=zstd  0  0\n",
-                    part.name, subvol_name, uuid, mount_point, subvol_name
+                    "\n# {} partition (btrfs {} subvolume)\nUUID={}  {}  btrfs  subvol={},{}  0  0\n",
+                    part.name,
+                    subvol_name,
+                    uuid,
+                    mount_point,
+                    subvol_name,
+                    layout.fstab_mount_options(mount_point, &layout.btrfs_mount_options)
                 ));
-                // /var also hosts @log (→ /var/log) on the same btrfs filesystem.
-                if mount_point == "/var" {
-                    let log_subvol = mount_point_to_subvol_name("/var/log");
+                // /var also hosts @log (and @pkg) on the same btrfs filesystem.
+                for sv in partition_subvolumes(layout, subvol_name, mount_point)
+                    .iter()
+                    .skip(1)
+                {
                     content.push_str(&format!(
-                        "UUID={}  /var/log  btrfs  subvol={},defaults,noatime,compress=zstd  0  0\n",
-                        uuid, log_subvol
+                        "UUID={}  {}  btrfs  subvol={},{}  0  0\n",
+                        uuid, sv.mount_point, sv.name, sv.mount_options
                     ));
                 }
             } else {
                 // Non-btrfs data partition or btrfs partition without a named subvolume.
-                let (fstype, options) = fs_fstab_entry(filesystem);
+                let filesystem = layout.partition_filesystem(&part.name, filesystem);
+                let (fstype, options) = fs_fstab_entry(filesystem, mount_point, layout);
                 let pass = fsck_pass(filesystem, mount_point);
                 content.push_str(&format!(
                     "\nUUID={}  {}  {}  {}  0  {}\n",
@@ -331,15 +380,10 @@ fn generate_fstab_with_subvolumes(
     Ok(())
 }
 
-/// Generate fstab for multi-volume encrypted system
-///
-/// Creates entries for separate encrypted partitions (ROOT, USR, VAR, HOME)
-/// mounted from /dev/mapper/Crypt-* devices.
-/// Parameters for multi-volume encrypted fstab generation
-pub struct MultiVolumeFstabParams<'a> {
+/// Parameters for fstab generation from a resolved [`VolumeSet`]
+pub struct VolumeFstabParams<'a> {
     pub cmd: &'a CommandRunner,
-    pub containers: &'a [LuksContainer],
-    pub device: &'a str,
+    pub volumes: &'a VolumeSet,
     pub layout: &'a ComputedLayout,
     pub filesystem: &'a Filesystem,
     pub boot_filesystem: &'a Filesystem,
@@ -347,34 +391,32 @@ pub struct MultiVolumeFstabParams<'a> {
     pub install_root: &'a str,
 }
 
-pub fn generate_fstab_multi_volume(params: &MultiVolumeFstabParams) -> Result<()> {
+/// Generate fstab for a multi-volume encrypted or LVM thin system
+///
+/// Each data volume is listed by the UUID of the filesystem on its resolved
+/// device (`/dev/mapper/Crypt-*` or `/dev/vg/lv`).  LUKS volumes of a
+/// subvolume layout get one entry per btrfs subvolume.
+pub fn generate_fstab_volumes(params: &VolumeFstabParams) -> Result<()> {
     let cmd = params.cmd;
-    let containers = params.containers;
-    let device = params.device;
+    let volumes = params.volumes;
     let layout = params.layout;
     let filesystem = params.filesystem;
     let boot_filesystem = params.boot_filesystem;
     let swap_type = params.swap_type;
     let install_root = params.install_root;
     info!(
-        "Generating /etc/fstab for {} encrypted volumes",
-        containers.len()
+        "Generating /etc/fstab for {} volumes",
+        volumes.entries.len()
     );
 
     if cmd.is_dry_run() {
-        let (fstype, fsopts) = fs_fstab_entry(filesystem);
-        println!("  [dry-run] Would generate fstab with encrypted volumes:");
-        for container in containers {
-            let mp = container.volume_name.to_lowercase();
-            let mount_point = if mp == "root" {
-                "/".to_string()
-            } else {
-                format!("/{}", mp)
-            };
-            let pass = fsck_pass(filesystem, &mount_point);
+        println!("  [dry-run] Would generate fstab with volumes:");
+        for entry in volumes.entries_mount_order() {
+            let pass = fsck_pass(filesystem, &entry.mount_point);
+            let (fstype, fsopts) = fs_fstab_entry(filesystem, &entry.mount_point, layout);
             println!(
                 "    {} {} {} {} 0 {}",
-                container.mapped_path, mount_point, fstype, fsopts, pass
+                entry.device_path, entry.mount_point, fstype, fsopts, pass
             );
         }
         return Ok(());
@@ -382,55 +424,54 @@ pub fn generate_fstab_multi_volume(params: &MultiVolumeFstabParams) -> Result<()
 
     let mut content = String::from(
         "# /etc/fstab: static file system information.\n\
-         # Generated by Deploytix - Multi-volume encrypted system\n\
+         # Generated by Deploytix\n\
          #\n\
          # <file system> <mount point> <type> <options> <dump> <pass>\n\n",
     );
 
-    // Add encrypted volume entries
-    if layout.uses_subvolumes() {
-        // With subvolumes: each container has named subvolumes (e.g. @, @usr, @var, @home)
-        for container in containers {
-            let fs_uuid = get_partition_uuid(&container.mapped_path)?;
-            let svols = multi_volume_subvolumes(&container.volume_name);
-            for sv in &svols {
+    // Add data volume entries
+    for entry in volumes.entries_mount_order() {
+        // Get UUID of the filesystem on the mapped device or LV
+        let fs_uuid = get_partition_uuid(&entry.device_path)?;
+        match entry.luks_container {
+            // With subvolumes: each container has named subvolumes (e.g. @, @usr, @var, @home)
+            Some(ref container) if layout.uses_subvolumes() => {
+                for sv in &layout.container_subvolumes(&container.volume_name) {
+                    content.push_str(&format!(
+                        "# {} (LUKS encrypted)\n\
+                         UUID={}  {}  btrfs  subvol={},{}  0  0\n\n",
+                        container.volume_name,
+                        fs_uuid,
+                        sv.mount_point,
+                        sv.name,
+                        layout.fstab_mount_options(&sv.mount_point, &sv.mount_options),
+                    ));
+                }
+            }
+            _ => {
+                // Note: ZFS is blocked with LUKS and LVM thin at validation
+                // time, so this path always uses a traditional filesystem.
+                let pass = fsck_pass(filesystem, &entry.mount_point);
+                let (fstype, options) = fs_fstab_entry(filesystem, &entry.mount_point, layout);
+                let kind = if entry.encrypted {
+                    "partition (LUKS encrypted)"
+                } else {
+                    "thin volume"
+                };
                 content.push_str(&format!(
-                    "# {} (LUKS encrypted)\n\
-                     UUID={}  {}  btrfs  subvol={},{}  0  0\n\n",
-                    container.volume_name, fs_uuid, sv.mount_point, sv.name, sv.mount_options,
+                    "# {} {}\n\
+                     UUID={}  {}  {}  {}  0  {}\n\n",
+                    entry.name, kind, fs_uuid, entry.mount_point, fstype, options, pass
                 ));
             }
         }
-    } else {
-        for container in containers {
-            let mount_point = match container.volume_name.as_str() {
-                "Root" => "/".to_string(),
-                name => format!("/{}", name.to_lowercase()),
-            };
-
-            let pass = fsck_pass(filesystem, &mount_point);
-
-            // Get UUID of the filesystem on the mapped device
-            let fs_uuid = get_partition_uuid(&container.mapped_path)?;
-
-            // Note: ZFS is blocked with multi-volume encryption at validation
-            // time, so this path always uses a traditional filesystem.
-            let (fstype, options) = fs_fstab_entry(filesystem);
-            content.push_str(&format!(
-                "# {} partition (LUKS encrypted)\n\
-                 UUID={}  {}  {}  {}  0  {}\n\n",
-                container.volume_name, fs_uuid, mount_point, fstype, options, pass
-            ));
-        }
     }
 
     // Add swap based on swap_type
     match swap_type {
         SwapType::Partition => {
-            let swap_part = layout.partitions.iter().find(|p| p.is_swap);
-            if let Some(swap) = swap_part {
-                let swap_device = partition_path(device, swap.number);
-                let swap_uuid = get_partition_uuid(&swap_device)?;
+            if let Some(ref swap_device) = volumes.swap {
+                let swap_uuid = get_partition_uuid(swap_device)?;
                 content.push_str(&format!(
                     "# Swap partition\n\
                      UUID={}  none  swap  defaults  0  0\n\n",
@@ -452,27 +493,38 @@ pub fn generate_fstab_multi_volume(params: &MultiVolumeFstabParams) -> Result<()
     }
 
     // Add BOOT partition
-    let boot_part = layout.partitions.iter().find(|p| p.is_boot_fs);
-    if let Some(boot) = boot_part {
-        let boot_device = partition_path(device, boot.number);
-        let boot_uuid = get_partition_uuid(&boot_device)?;
-        let (boot_fstype, boot_opts, boot_pass) = boot_fs_fstab_entry(boot_filesystem);
+    // When boot is encrypted, its device is the mapped LUKS1 device
+    // When boot is ZFS, use the boot dataset name instead of UUID
+    if *boot_filesystem == Filesystem::Zfs {
+        append_zfs_boot_entry(&mut content);
+    } else {
+        let boot = &volumes.boot;
+        let boot_uuid = get_partition_uuid(&boot.device_path)?;
+        let (boot_fstype, boot_opts, boot_pass) =
+            boot_fs_fstab_entry(boot_filesystem, &layout.btrfs_mount_options);
         content.push_str(&format!(
-            "# Boot partition\n\
+            "# Boot partition{}\n\
              UUID={}  /boot  {}  {}  0  {}\n\n",
-            boot_uuid, boot_fstype, boot_opts, boot_pass
+            if boot.encrypted {
+                " (LUKS1 encrypted)"
+            } else {
+                ""
+            },
+            boot_uuid,
+            boot_fstype,
+            boot_opts,
+            boot_pass
         ));
     }
 
     // Add EFI partition
-    let efi_part = layout.partitions.iter().find(|p| p.is_efi);
-    if let Some(efi) = efi_part {
-        let efi_device = partition_path(device, efi.number);
-        let efi_uuid = get_partition_uuid(&efi_device)?;
+    if let Some(ref efi_device) = volumes.efi {
+        let efi_uuid = get_partition_uuid(efi_device)?;
         content.push_str(&format!(
             "# EFI System Partition\n\
-             UUID={}  /boot/efi  vfat  umask=0077,defaults  0  0\n",
-            efi_uuid
+             UUID={}  {}  vfat  umask=0077,defaults  0  0\n",
+            efi_uuid,
+            layout.esp_mount_point()
         ));
     }
 
@@ -481,170 +533,177 @@ pub fn generate_fstab_multi_volume(params: &MultiVolumeFstabParams) -> Result<()
     fs::write(&fstab_path, &content)?;
 
     info!(
-        "Fstab written to {} with {} encrypted volumes",
+        "Fstab written to {} with {} volumes",
         fstab_path,
-        containers.len()
+        volumes.entries.len()
     );
     Ok(())
 }
 
-/// Parameters for LVM thin fstab generation
-pub struct LvmThinFstabParams<'a> {
-    pub cmd: &'a CommandRunner,
-    pub vg_name: &'a str,
-    pub thin_volumes: &'a [ThinVolumeDef],
-    pub device: &'a str,
-    pub layout: &'a ComputedLayout,
-    pub filesystem: &'a Filesystem,
-    pub swap_type: &'a SwapType,
-    pub boot_mapped_device: Option<&'a str>,
-    pub boot_filesystem: &'a Filesystem,
-    pub install_root: &'a str,
+/// Add swap file entry to an existing fstab
+pub fn append_swap_file_entry(install_root: &str) -> Result<()> {
+    let fstab_path = format!("{}/etc/fstab", install_root);
+
+    let mut content = fs::read_to_string(&fstab_path).unwrap_or_default();
+    content.push_str("\n# Swap file\n");
+    content.push_str(&swap_file_fstab_entry());
+
+    fs::write(&fstab_path, content)?;
+    info!("Added swap file entry to fstab");
+    Ok(())
 }
 
-/// Generate fstab for LVM thin provisioning layout
-///
-/// Creates entries for thin LVs mounted from /dev/vg/lv paths.
-/// When `boot_mapped_device` is Some, the boot partition is encrypted and
-/// the filesystem UUID should be read from the mapped device path.
-pub fn generate_fstab_lvm_thin(params: &LvmThinFstabParams) -> Result<()> {
-    let cmd = params.cmd;
-    let vg_name = params.vg_name;
-    let thin_volumes = params.thin_volumes;
-    let device = params.device;
-    let layout = params.layout;
-    let swap_type = params.swap_type;
-    let boot_mapped_device = params.boot_mapped_device;
-    let install_root = params.install_root;
-    info!("Generating /etc/fstab for LVM thin volumes");
+/// Whether `size` is a tmpfs `size=` value: a non-zero amount with an
+/// optional k/m/g suffix, or a percentage of RAM (1-100%).
+pub fn is_valid_tmpfs_size(size: &str) -> bool {
+    if let Some(pct) = size.strip_suffix('%') {
+        return pct.parse::<u8>().is_ok_and(|p| (1..=100).contains(&p));
+    }
+    let digits = size.trim_end_matches(|c: char| "kKmMgG".contains(c));
+    size.len() - digits.len() <= 1
+        && !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && digits.parse::<u64>().is_ok_and(|n| n > 0)
+}
 
+/// Whether `options` is a comma-separated list of mount options: no empty
+/// entries and nothing that would break the fstab line (whitespace, `#`).
+pub fn is_valid_mount_options(options: &str) -> bool {
+    options
+        .split(',')
+        .all(|o| !o.is_empty() && !o.chars().any(|c| c.is_whitespace() || c == '#'))
+}
+
+/// fstab line mounting `/tmp` as tmpfs
+fn tmpfs_fstab_entry(size: Option<&str>) -> String {
+    let mut options = "defaults,nosuid,nodev,mode=1777".to_string();
+    if let Some(size) = size {
+        options.push_str(&format!(",size={}", size));
+    }
+    format!("tmpfs  /tmp  tmpfs  {}  0  0\n", options)
+}
+
+/// Add a tmpfs `/tmp` entry to an existing fstab
+pub fn append_tmpfs_entry(
+    cmd: &CommandRunner,
+    install_root: &str,
+    size: Option<&str>,
+) -> Result<()> {
+    let entry = tmpfs_fstab_entry(size);
     if cmd.is_dry_run() {
-        let filesystem = params.filesystem;
-        let (fstype, fsopts) = fs_fstab_entry(filesystem);
-        println!("  [dry-run] Would generate fstab with LVM thin volumes:");
-        for vol in thin_volumes {
-            let pass = fsck_pass(filesystem, &vol.mount_point);
-            println!(
-                "    /dev/{}/{} {} {} {} 0 {}",
-                vg_name, vol.name, vol.mount_point, fstype, fsopts, pass
-            );
-        }
+        println!("  [dry-run] Would append to fstab: {}", entry.trim_end());
         return Ok(());
     }
 
-    let mut content = String::from(
-        "# /etc/fstab: static file system information.\n\
-         # Generated by Deploytix - LVM Thin Provisioning layout\n\
-         #\n\
-         # <file system> <mount point> <type> <options> <dump> <pass>\n\n",
-    );
-
-    // Add LVM thin volume entries
-    let filesystem = params.filesystem;
-    for vol in thin_volumes {
-        let lv_device = lv_path(vg_name, &vol.name);
-        let fs_uuid = get_partition_uuid(&lv_device)?;
-        let pass = fsck_pass(filesystem, &vol.mount_point);
-        let (fstype, options) = fs_fstab_entry(filesystem);
+    let fstab_path = format!("{}/etc/fstab", install_root);
+    let mut content = fs::read_to_string(&fstab_path).unwrap_or_default();
+    content.push_str("\n# tmpfs /tmp\n");
+    content.push_str(&entry);
 
-        content.push_str(&format!(
-            "# {} thin volume\n\
-             UUID={}  {}  {}  {}  0  {}\n\n",
-            vol.name, fs_uuid, vol.mount_point, fstype, options, pass
-        ));
-    }
+    fs::write(&fstab_path, content)?;
+    info!("Added tmpfs /tmp entry to fstab");
+    Ok(())
+}
 
-    // Add swap based on swap_type
-    match swap_type {
-        SwapType::Partition => {
-            // Add SWAP partition if present
-            let swap_part = layout.partitions.iter().find(|p| p.is_swap);
-            if let Some(swap) = swap_part {
-                let swap_device = partition_path(device, swap.number);
-                let swap_uuid = get_partition_uuid(&swap_device)?;
-                content.push_str(&format!(
-                    "# Swap partition\n\
-                     UUID={}  none  swap  defaults  0  0\n\n",
-                    swap_uuid
-                ));
-            }
-        }
-        SwapType::FileZram => {
-            // Add swap file entry
-            content.push_str(&format!(
-                "# Swap file (ZRAM provides additional compressed swap)\n\
-                 {}  none  swap  defaults  0  0\n\n",
-                SWAP_FILE_PATH
-            ));
+/// Options `system.hardened` adds per mount point.  /home and /var keep
+/// exec: user scripts, Steam/Proton, Flatpak and DKMS run binaries there.
+pub const HARDENED_MOUNT_OPTIONS: &[(&str, &str)] = &[
+    ("/home", "nodev,nosuid"),
+    ("/var", "nodev,nosuid"),
+    ("/var/log", "nodev,nosuid,noexec"),
+    ("/tmp", "nodev,nosuid,noexec"),
+    ("/boot", "nodev,nosuid,noexec"),
+    ("/boot/efi", "nodev,nosuid,noexec"),
+    ("/efi", "nodev,nosuid,noexec"),
+];
+
+/// /proc with other users' processes hidden; members of group `proc`
+/// still see them all.
+const HARDENED_PROC_ENTRY: &str =
+    "proc  /proc  proc  nosuid,nodev,noexec,hidepid=2,gid=proc  0  0\n";
+
+/// Add the [`HARDENED_MOUNT_OPTIONS`] to the matching entries of `content`
+/// and, with `hide_pids`, a hidepid /proc entry.  Mount points in
+/// `explicit` carry options set on their partition entry and are left as
+/// written.
+fn harden_fstab_content(content: &str, explicit: &[&str], hide_pids: bool) -> String {
+    let mut hardened = String::with_capacity(content.len());
+    let mut has_proc = false;
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 || fields[0].starts_with('#') {
+            hardened.push_str(line);
+            hardened.push('\n');
+            continue;
         }
-        SwapType::ZramOnly => {
-            // No fstab entry needed - ZRAM is configured via service
-            content.push_str("# Swap: ZRAM only (configured via service)\n\n");
+        has_proc |= fields[1] == "/proc";
+        let extra = HARDENED_MOUNT_OPTIONS
+            .iter()
+            .find(|(mp, _)| *mp == fields[1] && !explicit.contains(mp))
+            .map(|(_, opts)| *opts);
+        match extra {
+            Some(extra) => {
+                let mut options: Vec<&str> = fields[3].split(',').collect();
+                for opt in extra.split(',') {
+                    if !options.contains(&opt) {
+                        options.push(opt);
+                    }
+                }
+                let options = options.join(",");
+                let mut fields = fields;
+                fields[3] = &options;
+                hardened.push_str(&fields.join("  "));
+            }
+            None => hardened.push_str(line),
         }
+        hardened.push('\n');
+    }
+    if hide_pids && !has_proc {
+        hardened.push_str("\n# /proc with hidepid (system.hardened)\n");
+        hardened.push_str(HARDENED_PROC_ENTRY);
     }
+    hardened
+}
 
-    // Add BOOT partition
-    // When boot is encrypted, use the mapped device path for the filesystem UUID
-    // When boot is ZFS, use the boot dataset name instead of UUID
-    if *params.boot_filesystem == Filesystem::Zfs {
-        append_zfs_boot_entry(&mut content);
-    } else {
-        let (boot_fstype, boot_opts, boot_pass) = boot_fs_fstab_entry(params.boot_filesystem);
-        if let Some(mapped_dev) = boot_mapped_device {
-            let boot_uuid = get_partition_uuid(mapped_dev)?;
-            content.push_str(&format!(
-                "# Boot partition (LUKS1 encrypted)\n\
-                 UUID={}  /boot  {}  {}  0  {}\n\n",
-                boot_uuid, boot_fstype, boot_opts, boot_pass
-            ));
-        } else {
-            let boot_part = layout.partitions.iter().find(|p| p.is_boot_fs);
-            if let Some(boot) = boot_part {
-                let boot_device = partition_path(device, boot.number);
-                let boot_uuid = get_partition_uuid(&boot_device)?;
-                content.push_str(&format!(
-                    "# Boot partition\n\
-                     UUID={}  /boot  {}  {}  0  {}\n\n",
-                    boot_uuid, boot_fstype, boot_opts, boot_pass
-                ));
+/// Apply the `system.hardened` mount options to the generated fstab.
+/// `explicit` lists mount points whose partition entry sets mount_options.
+///
+/// Desktops get no hidepid /proc (`hide_pids` false): polkitd, which is
+/// not in group `proc`, could then no longer see the processes it
+/// authorizes, and seat handling breaks along with it.
+pub fn harden_fstab(
+    cmd: &CommandRunner,
+    install_root: &str,
+    explicit: &[&str],
+    hide_pids: bool,
+) -> Result<()> {
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would add hardened mount options to fstab:");
+        for (mount_point, options) in HARDENED_MOUNT_OPTIONS {
+            if !explicit.contains(mount_point) {
+                println!("    {}: {}", mount_point, options);
             }
         }
+        if hide_pids {
+            println!("    {}", HARDENED_PROC_ENTRY.trim_end());
+        }
     }
 
-    // Add EFI partition
-    let efi_part = layout.partitions.iter().find(|p| p.is_efi);
-    if let Some(efi) = efi_part {
-        let efi_device = partition_path(device, efi.number);
-        let efi_uuid = get_partition_uuid(&efi_device)?;
-        content.push_str(&format!(
-            "# EFI System Partition\n\
-             UUID={}  /boot/efi  vfat  umask=0077,defaults  0  0\n",
-            efi_uuid
-        ));
+    // gid=proc needs the group; the filesystem package usually ships it
+    if hide_pids {
+        cmd.run_in_chroot(install_root, "groupadd -f -r proc")?;
+    }
+    if cmd.is_dry_run() {
+        return Ok(());
     }
 
     let fstab_path = format!("{}/etc/fstab", install_root);
-    fs::create_dir_all(format!("{}/etc", install_root))?;
-    fs::write(&fstab_path, &content)?;
-
-    info!(
-        "Fstab written to {} with {} LVM thin volumes",
-        fstab_path,
-        thin_volumes.len()
-    );
-    Ok(())
-}
-
-/// Add swap file entry to an existing fstab
-pub fn append_swap_file_entry(install_root: &str) -> Result<()> {
-    let fstab_path = format!("{}/etc/fstab", install_root);
-
-    let mut content = fs::read_to_string(&fstab_path).unwrap_or_default();
-    content.push_str("\n# Swap file\n");
-    content.push_str(&swap_file_fstab_entry());
-
-    fs::write(&fstab_path, content)?;
-    info!("Added swap file entry to fstab");
+    let content = fs::read_to_string(&fstab_path)?;
+    fs::write(
+        &fstab_path,
+        harden_fstab_content(&content, explicit, hide_pids),
+    )?;
+    info!("Applied hardened mount options to fstab");
     Ok(())
 }
 
@@ -652,6 +711,27 @@ pub fn append_swap_file_entry(install_root: &str) -> Result<()> {
 mod tests {
     use super::*;
 
+    // ── tmpfs /tmp ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn tmpfs_size_strings() {
+        for ok in ["4G", "512m", "1048576", "25%", "100%"] {
+            assert!(is_valid_tmpfs_size(ok), "{}", ok);
+        }
+        for bad in ["", "0", "0G", "4GB", "G", "0%", "150%", "half"] {
+            assert!(!is_valid_tmpfs_size(bad), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn tmpfs_entry_carries_size() {
+        assert_eq!(
+            tmpfs_fstab_entry(Some("4G")),
+            "tmpfs  /tmp  tmpfs  defaults,nosuid,nodev,mode=1777,size=4G  0  0\n"
+        );
+        assert!(!tmpfs_fstab_entry(None).contains("size="));
+    }
+
     // ── fsck_pass ────────────────────────────────────────────────────────────
 
     #[test]
@@ -691,4 +771,123 @@ mod tests {
             }
         }
     }
+
+    // ── fs_fstab_entry ───────────────────────────────────────────────────────
+
+    #[test]
+    fn f2fs_entry_carries_compression_options() {
+        let mut config = crate::config::DeploymentConfig::sample();
+        config.disk.filesystem = Filesystem::F2fs;
+        let mut layout =
+            crate::disk::layouts::compute_layout_from_config(&config.disk, 100_000).unwrap();
+        assert_eq!(
+            fs_fstab_entry(&Filesystem::F2fs, "/", &layout).1,
+            "defaults,noatime"
+        );
+
+        config.disk.f2fs_compression = Some("lz4".to_string());
+        layout = crate::disk::layouts::compute_layout_from_config(&config.disk, 100_000).unwrap();
+        assert_eq!(
+            fs_fstab_entry(&Filesystem::F2fs, "/", &layout).1,
+            "defaults,noatime,compress_algorithm=lz4,compress_extension=*"
+        );
+    }
+
+    fn ssd_layout(filesystem: Filesystem) -> ComputedLayout {
+        let mut config = crate::config::DeploymentConfig::sample();
+        config.disk.filesystem = filesystem;
+        config.disk.discard = Some(true);
+        crate::disk::layouts::compute_layout_from_config(&config.disk, 100_000).unwrap()
+    }
+
+    #[test]
+    fn ssd_entries_add_discard() {
+        for (filesystem, fstype) in [
+            (Filesystem::Ext4, "ext4"),
+            (Filesystem::Xfs, "xfs"),
+            (Filesystem::F2fs, "f2fs"),
+        ] {
+            let layout = ssd_layout(filesystem.clone());
+            assert_eq!(
+                fs_fstab_entry(&filesystem, "/", &layout),
+                (fstype, "defaults,noatime,discard".to_string())
+            );
+        }
+        // btrfs keeps its own ssd options
+        let layout = ssd_layout(Filesystem::Btrfs);
+        assert!(!fs_fstab_entry(&Filesystem::Btrfs, "/", &layout)
+            .1
+            .ends_with(",discard"));
+    }
+
+    #[test]
+    fn partition_mount_options_replace_the_defaults() {
+        let mut config = crate::config::DeploymentConfig::sample();
+        config.disk.filesystem = Filesystem::Ext4;
+        let home = config
+            .disk
+            .partitions
+            .iter_mut()
+            .find(|p| p.mount_point == "/home");
+        home.unwrap().mount_options = Some("nodev,nosuid,noexec".to_string());
+        let layout =
+            crate::disk::layouts::compute_layout_from_config(&config.disk, 200_000).unwrap();
+
+        assert_eq!(
+            fs_fstab_entry(&Filesystem::Ext4, "/home", &layout),
+            ("ext4", "nodev,nosuid,noexec".to_string())
+        );
+        assert_eq!(
+            fs_fstab_entry(&Filesystem::Ext4, "/var", &layout).1,
+            "defaults,noatime"
+        );
+    }
+
+    #[test]
+    fn hardening_adds_options_and_hidepid_proc() {
+        let content = "# header\n\
+                       UUID=a  /  ext4  defaults,noatime  0  1\n\
+                       UUID=b  /home  ext4  defaults,noatime  0  2\n\
+                       UUID=c  /var  ext4  nodev,nosuid  0  2\n\
+                       tmpfs  /tmp  tmpfs  defaults,nosuid,nodev,mode=1777  0  0\n";
+        let hardened = harden_fstab_content(content, &["/var"], true);
+        let lines: Vec<&str> = hardened.lines().collect();
+
+        assert_eq!(lines[0], "# header");
+        assert_eq!(lines[1], "UUID=a  /  ext4  defaults,noatime  0  1");
+        assert_eq!(
+            lines[2],
+            "UUID=b  /home  ext4  defaults,noatime,nodev,nosuid  0  2"
+        );
+        assert_eq!(lines[3], "UUID=c  /var  ext4  nodev,nosuid  0  2");
+        assert_eq!(
+            lines[4],
+            "tmpfs  /tmp  tmpfs  defaults,nosuid,nodev,mode=1777,noexec  0  0"
+        );
+        assert!(hardened.ends_with(HARDENED_PROC_ENTRY));
+
+        // Already present /proc entries are kept, not duplicated
+        let again = harden_fstab_content(&hardened, &[], true);
+        assert_eq!(again.matches(HARDENED_PROC_ENTRY).count(), 1);
+    }
+
+    #[test]
+    fn desktop_hardening_keeps_proc_visible() {
+        let content = "UUID=b  /home  ext4  defaults,noatime  0  2\n";
+        let hardened = harden_fstab_content(content, &[], false);
+        assert_eq!(
+            hardened,
+            "UUID=b  /home  ext4  defaults,noatime,nodev,nosuid  0  2\n"
+        );
+        assert!(!hardened.contains("hidepid"));
+    }
+
+    #[test]
+    fn mount_option_lists() {
+        assert!(is_valid_mount_options("nodev,nosuid,noexec"));
+        assert!(is_valid_mount_options("defaults,uid=1000"));
+        for bad in ["", "nodev,,noexec", "nodev, noexec", "nodev,", "a#b"] {
+            assert!(!is_valid_mount_options(bad), "{:?} should be rejected", bad);
+        }
+    }
 }
@@ -0,0 +1,519 @@
+//! Best-effort configuration export from an existing install
+//!
+//! Reads what an installed Artix system can tell about itself (fstab,
+//! crypttab, `/etc/default/grub`, installed packages, hostname, locale,
+//! timezone, the first regular user) and fills a [`DeploymentConfig`] with
+//! it.  Everything else keeps the sample defaults.  Values that were
+//! inferred rather than read, and values that could not be found, are
+//! annotated in the generated TOML.
+
+use super::drift::{conf_value, installed_packages, installed_timezone, DESKTOP_MARKERS};
+use crate::config::{
+    Bootloader, CustomPartitionEntry, DeploymentConfig, DesktopEnvironment, EspMount, Filesystem,
+    InitSystem, KernelChoice, NetworkBackend, SwapType,
+};
+use crate::configure::bootloader::DEFAULT_GRUB_DISTRIBUTOR;
+use crate::configure::locale::LOCALE_CATEGORIES;
+use crate::disk::detection::backing_disks;
+use crate::utils::error::Result;
+use std::fs;
+
+/// How a value in the generated config should be read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Note {
+    /// Inferred indirectly; review before installing
+    Guess(&'static str),
+    /// Not found on the system; the sample default is kept
+    Placeholder(&'static str),
+}
+
+/// A configuration read back from an installed system.
+#[derive(Debug, Clone)]
+pub struct ImportedConfig {
+    pub config: DeploymentConfig,
+    /// `(section, key, note)` for annotated values
+    notes: Vec<(&'static str, &'static str, Note)>,
+}
+
+impl ImportedConfig {
+    fn note(&mut self, section: &'static str, key: &'static str, note: Note) {
+        self.notes.push((section, key, note));
+    }
+
+    /// Serialize as TOML with a comment on every guessed or placeholder
+    /// value.
+    pub fn to_toml(&self, root: &str) -> Result<String> {
+        let body = toml::to_string_pretty(&self.config)?;
+        let mut out = format!(
+            "# Generated by `deploytix import-config` from {}\n\
+             # \"guess\" values were inferred and should be reviewed; \"placeholder\"\n\
+             # values could not be read from the system and keep the sample default.\n\n",
+            root
+        );
+
+        let mut section = String::new();
+        for line in body.lines() {
+            out.push_str(line);
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                section = trimmed.trim_matches(|c| c == '[' || c == ']').to_string();
+            } else if let Some((key, _)) = trimmed.split_once(" = ") {
+                let note = self
+                    .notes
+                    .iter()
+                    .find(|(s, k, _)| *s == section && *k == key)
+                    .map(|(_, _, n)| n);
+                match note {
+                    Some(Note::Guess(why)) => out.push_str(&format!("  # guess: {}", why)),
+                    Some(Note::Placeholder(why)) => {
+                        out.push_str(&format!("  # placeholder: {}", why))
+                    }
+                    None => {}
+                }
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+/// One fstab entry.
+struct FstabEntry {
+    source: String,
+    mount_point: String,
+    fstype: String,
+    options: String,
+}
+
+fn parse_fstab(content: &str) -> Vec<FstabEntry> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let fields: Vec<&str> = l.split_whitespace().collect();
+            (fields.len() >= 4).then(|| FstabEntry {
+                source: fields[0].to_string(),
+                mount_point: fields[1].to_string(),
+                fstype: fields[2].to_string(),
+                options: fields[3].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Device path for an fstab source (`UUID=`, `LABEL=`, `PARTUUID=` or a
+/// path under `/dev`).
+fn source_device(source: &str) -> Option<String> {
+    for (prefix, dir) in [
+        ("UUID=", "by-uuid"),
+        ("LABEL=", "by-label"),
+        ("PARTUUID=", "by-partuuid"),
+    ] {
+        if let Some(id) = source.strip_prefix(prefix) {
+            return Some(format!("/dev/disk/{}/{}", dir, id));
+        }
+    }
+    source.starts_with("/dev/").then(|| source.to_string())
+}
+
+/// Size of a block device in MiB, from sysfs.
+fn device_size_mib(device: &str) -> Option<u64> {
+    let path = fs::canonicalize(device).ok()?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let sectors: u64 = fs::read_to_string(format!("/sys/class/block/{}/size", name))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(sectors * 512 / (1024 * 1024))
+}
+
+fn parse_filesystem(fstype: &str) -> Option<Filesystem> {
+    match fstype {
+        "btrfs" => Some(Filesystem::Btrfs),
+        "ext4" => Some(Filesystem::Ext4),
+        "xfs" => Some(Filesystem::Xfs),
+        "f2fs" => Some(Filesystem::F2fs),
+        "zfs" => Some(Filesystem::Zfs),
+        _ => None,
+    }
+}
+
+/// Inspect the system installed at `root` ("/" for the running system).
+pub fn import_config(root: &str) -> ImportedConfig {
+    let base = root.trim_end_matches('/');
+    let read = |path: &str| fs::read_to_string(format!("{}{}", base, path)).unwrap_or_default();
+    let packages = installed_packages(root);
+    let has = |name: &str| packages.iter().any(|p| p == name);
+
+    let mut imported = ImportedConfig {
+        config: DeploymentConfig::sample(),
+        notes: Vec::new(),
+    };
+
+    import_system(&mut imported, root, &read, &has);
+    import_disk(&mut imported, &read, &has);
+    import_user(&mut imported, &read);
+
+    // Desktop and network come from the installed packages
+    imported.config.desktop.environment = DESKTOP_MARKERS
+        .iter()
+        .find(|(_, marker)| has(marker))
+        .map(|(de, _)| de.clone())
+        .unwrap_or(DesktopEnvironment::None);
+
+    let network = &mut imported.config.network;
+    network.wifi_ssid = None;
+    network.wifi_password = None;
+    if has("networkmanager") {
+        network.backend = if has("wpa_supplicant") && !has("iwd") {
+            NetworkBackend::NetworkManagerWpa
+        } else {
+            NetworkBackend::NetworkManager
+        };
+        imported.note(
+            "network",
+            "backend",
+            Note::Guess("from the installed network packages"),
+        );
+    } else if has("iwd") {
+        network.backend = NetworkBackend::Iwd;
+    } else {
+        imported.note(
+            "network",
+            "backend",
+            Note::Placeholder("no supported network stack installed"),
+        );
+    }
+
+    imported
+}
+
+fn import_system(
+    imported: &mut ImportedConfig,
+    root: &str,
+    read: &dyn Fn(&str) -> String,
+    has: &dyn Fn(&str) -> bool,
+) {
+    let system = &mut imported.config.system;
+    let mut notes = Vec::new();
+
+    match read("/etc/hostname").trim() {
+        "" => notes.push(("hostname", Note::Placeholder("/etc/hostname not found"))),
+        name => system.hostname = name.to_string(),
+    }
+    let locale_conf = read("/etc/locale.conf");
+    match conf_value(&locale_conf, "LANG") {
+        Some(lang) => {
+            // LANG first, then the other locales enabled in locale.gen
+            let generated = read("/etc/locale.gen");
+            let others = generated
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .filter_map(|l| l.split_whitespace().next())
+                .filter(|l| *l != lang)
+                .map(str::to_string);
+            system.locales = std::iter::once(lang.clone()).chain(others).collect();
+        }
+        None => notes.push(("locales", Note::Placeholder("no LANG in /etc/locale.conf"))),
+    }
+    system.locale_overrides = LOCALE_CATEGORIES
+        .iter()
+        .filter_map(|c| conf_value(&locale_conf, c).map(|l| (c.to_string(), l)))
+        .collect();
+    system.console_font = conf_value(&read("/etc/vconsole.conf"), "FONT");
+    match conf_value(&read("/etc/vconsole.conf"), "KEYMAP") {
+        Some(keymap) => system.keymap = keymap,
+        None => notes.push((
+            "keymap",
+            Note::Placeholder("no KEYMAP in /etc/vconsole.conf"),
+        )),
+    }
+    match installed_timezone(root) {
+        Some(zone) => system.timezone = zone,
+        None => notes.push((
+            "timezone",
+            Note::Placeholder("/etc/localtime is not a zoneinfo link"),
+        )),
+    }
+
+    let inits: Vec<InitSystem> = [
+        InitSystem::Runit,
+        InitSystem::OpenRC,
+        InitSystem::S6,
+        InitSystem::Dinit,
+    ]
+    .into_iter()
+    .filter(|i| has(i.base_package()))
+    .collect();
+    match inits.as_slice() {
+        [] => notes.push((
+            "init",
+            Note::Placeholder("no init system package installed"),
+        )),
+        [init] => system.init = init.clone(),
+        [init, ..] => {
+            system.init = init.clone();
+            notes.push(("init", Note::Guess("several init systems installed")));
+        }
+    }
+
+    let kernels: Vec<KernelChoice> = [
+        KernelChoice::Zen,
+        KernelChoice::Lts,
+        KernelChoice::Stable,
+        KernelChoice::Hardened,
+    ]
+    .into_iter()
+    .filter(|k| has(k.package()))
+    .collect();
+    match kernels.as_slice() {
+        [] => notes.push(("kernel", Note::Placeholder("no supported kernel installed"))),
+        [kernel] => system.kernel = *kernel,
+        [kernel, ..] => {
+            system.kernel = *kernel;
+            notes.push(("kernel", Note::Guess("several kernels installed")));
+        }
+    }
+
+    let grub = read("/etc/default/grub");
+    if has("refind") && !has("grub") {
+        system.bootloader = Bootloader::Refind;
+        system.grub_timeout = None;
+    } else if has("grub") || !grub.is_empty() {
+        system.bootloader = Bootloader::Grub;
+        system.grub_timeout = conf_value(&grub, "GRUB_TIMEOUT").and_then(|t| t.parse().ok());
+        system.grub_default = conf_value(&grub, "GRUB_DEFAULT");
+        system.grub_distributor =
+            conf_value(&grub, "GRUB_DISTRIBUTOR").filter(|name| name != DEFAULT_GRUB_DISTRIBUTOR);
+        system.grub_os_prober =
+            conf_value(&grub, "GRUB_DISABLE_OS_PROBER").as_deref() == Some("false");
+    } else {
+        notes.push((
+            "bootloader",
+            Note::Placeholder("no GRUB or rEFInd installed"),
+        ));
+    }
+
+    for (key, note) in notes {
+        imported.note("system", key, note);
+    }
+}
+
+fn import_disk(
+    imported: &mut ImportedConfig,
+    read: &dyn Fn(&str) -> String,
+    has: &dyn Fn(&str) -> bool,
+) {
+    let fstab = parse_fstab(&read("/etc/fstab"));
+    let disk = &mut imported.config.disk;
+    let mut notes = Vec::new();
+
+    let root_entry = fstab.iter().find(|e| e.mount_point == "/");
+    match root_entry.and_then(|e| parse_filesystem(&e.fstype)) {
+        Some(fs) => disk.filesystem = fs,
+        // A ZFS root is mounted by the pool, not fstab
+        None if has("zfs-utils") => {
+            disk.filesystem = Filesystem::Zfs;
+            notes.push((
+                "filesystem",
+                Note::Guess("no / in fstab but zfs-utils is installed"),
+            ));
+        }
+        None => notes.push((
+            "filesystem",
+            Note::Placeholder("no supported / entry in fstab"),
+        )),
+    }
+
+    match root_entry
+        .and_then(|e| source_device(&e.source))
+        .and_then(|dev| backing_disks(&dev).into_iter().next())
+    {
+        Some(device) => {
+            disk.device = device;
+            notes.push((
+                "device",
+                Note::Guess("disk currently backing /; check before installing"),
+            ));
+        }
+        None => notes.push(("device", Note::Placeholder("disk backing / not found"))),
+    }
+
+    disk.encryption = read("/etc/crypttab")
+        .lines()
+        .map(str::trim)
+        .any(|l| !l.is_empty() && !l.starts_with('#'));
+
+    disk.esp_mountpoint = if fstab.iter().any(|e| e.mount_point == "/efi") {
+        EspMount::Efi
+    } else {
+        EspMount::BootEfi
+    };
+
+    let swap = fstab.iter().find(|e| e.fstype == "swap");
+    disk.swap_type = match swap {
+        Some(e) if source_device(&e.source).is_some() => SwapType::Partition,
+        Some(_) => SwapType::FileZram,
+        None => {
+            notes.push(("swap_type", Note::Guess("no swap in fstab")));
+            SwapType::ZramOnly
+        }
+    };
+
+    if let Some(tmp) = fstab
+        .iter()
+        .find(|e| e.mount_point == "/tmp" && e.fstype == "tmpfs")
+    {
+        imported.config.system.tmp_tmpfs = true;
+        imported.config.system.tmp_tmpfs_size = tmp
+            .options
+            .split(',')
+            .find_map(|o| o.strip_prefix("size="))
+            .map(str::to_string);
+    }
+
+    // Data partitions: every block-device mount except boot, ESP and swap,
+    // one per device (btrfs subvolume mounts share theirs)
+    let mut partitions: Vec<(String, CustomPartitionEntry)> = Vec::new();
+    for entry in &fstab {
+        let Some(device) = source_device(&entry.source) else {
+            continue;
+        };
+        if entry.fstype == "swap"
+            || ["/boot", "/boot/efi", "/efi"].contains(&entry.mount_point.as_str())
+            || partitions.iter().any(|(d, _)| *d == device)
+        {
+            continue;
+        }
+        partitions.push((
+            device.clone(),
+            CustomPartitionEntry {
+                mount_point: entry.mount_point.clone(),
+                label: None,
+                size_mib: device_size_mib(&device).unwrap_or(0),
+                encryption: None,
+                filesystem: None,
+                mount_options: None,
+            },
+        ));
+    }
+    if !partitions.is_empty() {
+        // The largest partition takes whatever the new disk has left
+        if let Some((_, largest)) = partitions.iter_mut().max_by_key(|(_, p)| p.size_mib) {
+            largest.size_mib = 0;
+        }
+        disk.partitions = partitions.into_iter().map(|(_, p)| p).collect();
+        notes.push(("size_mib", Note::Guess("current partition size")));
+    }
+
+    for (key, note) in notes {
+        let section = if key == "size_mib" {
+            "disk.partitions"
+        } else {
+            "disk"
+        };
+        imported.note(section, key, note);
+    }
+}
+
+fn import_user(imported: &mut ImportedConfig, read: &dyn Fn(&str) -> String) {
+    let user = &mut imported.config.user;
+
+    // First regular account (uid 1000-59999) and its login shell
+    let account = read("/etc/passwd").lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        let uid: u32 = fields.get(2)?.parse().ok()?;
+        (1000..60000)
+            .contains(&uid)
+            .then(|| (fields[0].to_string(), fields.get(6).map(|s| s.to_string())))
+    });
+    let Some((name, shell)) = account else {
+        imported.note(
+            "user",
+            "name",
+            Note::Placeholder("no regular user account found"),
+        );
+        imported.note("user", "password", Note::Placeholder("set a password"));
+        return;
+    };
+
+    user.groups = read("/etc/group")
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let members = fields.get(3)?;
+            members
+                .split(',')
+                .any(|m| m == name)
+                .then(|| fields[0].to_string())
+        })
+        .collect();
+    user.sudoer = user.groups.iter().any(|g| g == "wheel");
+    user.shell = shell.filter(|s| !s.is_empty() && s != "/bin/bash" && s != "/usr/bin/bash");
+    user.name = name;
+    imported.note(
+        "user",
+        "password",
+        Note::Placeholder("passwords cannot be exported; set one"),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fstab_sources_resolve_to_devices() {
+        assert_eq!(
+            source_device("UUID=abcd").as_deref(),
+            Some("/dev/disk/by-uuid/abcd")
+        );
+        assert_eq!(
+            source_device("/dev/mapper/Crypt-Root").as_deref(),
+            Some("/dev/mapper/Crypt-Root")
+        );
+        assert_eq!(source_device("tmpfs"), None);
+        assert_eq!(source_device("/swap/swapfile"), None);
+    }
+
+    #[test]
+    fn imports_identity_from_root() {
+        let root = std::env::temp_dir().join(format!("deploytix-import-{}", std::process::id()));
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::write(root.join("etc/hostname"), "box\n").unwrap();
+        fs::write(root.join("etc/locale.conf"), "LANG=de_DE.UTF-8\n").unwrap();
+        fs::write(
+            root.join("etc/passwd"),
+            "root:x:0:0::/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/zsh\n",
+        )
+        .unwrap();
+        fs::write(root.join("etc/group"), "wheel:x:998:alice\naudio:x:995:\n").unwrap();
+        fs::write(
+            root.join("etc/fstab"),
+            "tmpfs  /tmp  tmpfs  defaults,size=2G  0  0\n",
+        )
+        .unwrap();
+
+        let root_str = root.to_string_lossy().to_string();
+        let imported = import_config(&root_str);
+        let toml = imported.to_toml(&root_str).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        let config = &imported.config;
+        assert_eq!(config.system.hostname, "box");
+        assert_eq!(config.system.locales, vec!["de_DE.UTF-8"]);
+        assert_eq!(config.user.name, "alice");
+        assert_eq!(config.user.groups, vec!["wheel"]);
+        assert!(config.user.sudoer);
+        assert_eq!(config.user.shell.as_deref(), Some("/bin/zsh"));
+        assert_eq!(config.system.tmp_tmpfs_size.as_deref(), Some("2G"));
+
+        assert!(toml.contains("hostname = \"box\"\n"));
+        assert!(toml.contains("# placeholder: no KEYMAP in /etc/vconsole.conf"));
+        assert!(toml.contains("# placeholder: passwords cannot be exported"));
+        assert!(toml::from_str::<DeploymentConfig>(&toml).is_ok());
+    }
+}
@@ -1,43 +1,58 @@
 //! Main installation orchestrator
 
-use crate::config::{DeploymentConfig, Filesystem, SwapType};
+use crate::config::{Bootloader, DeploymentConfig, Firmware, RecoveryPassphrase, SwapType};
 use crate::configure;
 use crate::configure::encryption::{
     close_multi_luks, setup_multi_volume_encryption, LuksContainer,
 };
 use crate::configure::keyfiles::{setup_keyfiles_for_volumes, VolumeKeyfile};
 use crate::desktop;
-use crate::disk::detection::{get_device_info, partition_path};
+use crate::disk::detection::{
+    get_device_info, is_running_system_disk, partition_path, probe_existing_data, smart_health,
+    BlockDevice,
+};
+use crate::disk::dualboot::{self, AlongsidePlan};
 use crate::disk::formatting::{
-    create_btrfs_subvolumes, format_all_partitions, format_boot_partition, format_efi,
-    format_partition, format_swap, mount_btrfs_subvolumes,
+    ashift_for_sector_size, create_btrfs_subvolumes, format_all_partitions, format_boot_partition,
+    format_btrfs_raid1, format_efi, format_partition, format_swap, mount_btrfs_subvolumes,
 };
+use crate::disk::image::{check_loop_partscan, detach_loop, loop_backing_file, DiskImage};
 use crate::disk::layouts::{
-    compute_layout_from_config, get_luks_partitions, multi_volume_subvolumes, print_layout_summary,
-    ComputedLayout,
+    compute_layout_from_config, f2fs_compression_enabled, get_luks_partitions,
+    print_layout_summary, ComputedLayout,
 };
-use crate::disk::lvm::{self, lv_path, ThinVolumeDef};
+use crate::disk::lvm::{self, ThinVolumeDef};
+use crate::disk::mirror::mirror_planning_mib;
+use crate::disk::mount::mount_points_under;
 use crate::disk::partitioning::apply_partitions;
+use crate::disk::volumes::VolumeSet;
 use crate::install::crypttab::generate_crypttab_multi_volume;
+use crate::install::eta::{self, PhaseHistory, PhaseTimer};
 use crate::install::fstab::{
-    append_swap_file_entry, generate_fstab_lvm_thin, generate_fstab_multi_volume,
-    LvmThinFstabParams, MultiVolumeFstabParams,
+    append_swap_file_entry, append_tmpfs_entry, generate_fstab_volumes, harden_fstab,
+    VolumeFstabParams,
 };
 use crate::install::{
-    generate_fstab, mount_boot_btrfs_subvolume, mount_partitions, mount_partitions_zfs,
-    run_basestrap, unmount_all,
+    generate_fstab, mount_package_cache, mount_partitions, mount_partitions_zfs,
+    mount_system_volumes, mount_volume_set, run_basestrap, unmount_all,
+};
+use crate::utils::command::{CommandRunner, OperationRecord, OutputCallback};
+use crate::utils::deps::{
+    can_install_dependencies, check_dependencies, check_feature_versions, ensure_dependencies,
+    install_dependencies,
 };
-use crate::utils::command::{CommandRunner, OperationRecord};
-use crate::utils::deps::ensure_dependencies;
 use crate::utils::error::{DeploytixError, Result};
-use crate::utils::prompt::warn_confirm;
+use crate::utils::prompt::{prompt_confirm, prompt_new_password, warn_confirm};
 use crate::utils::signal;
+use crate::utils::transcript::{Transcript, TRANSCRIPT_NAME};
+use std::cell::{Cell, RefCell};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tracing::{info, warn};
 
-/// Installation target path
-pub const INSTALL_ROOT: &str = "/install";
+/// Default installation target path (`install --root`)
+pub const DEFAULT_INSTALL_ROOT: &str = "/install";
 
 /// Where the post-install extras step persists the merged config.
 /// `~/.config/deploytix/last-install.toml`.
@@ -67,8 +82,9 @@ fn save_config_with_extras(config: &DeploymentConfig) -> Result<()> {
 }
 
 /// Progress callback type for reporting installation progress.
-/// Takes a value between 0.0 and 1.0, and a status message describing the current phase.
-pub type ProgressCallback = Box<dyn Fn(f32, &str) + Send>;
+/// Takes a value between 0.0 and 1.0, a status message describing the current
+/// phase, and the estimated seconds remaining when an estimate is available.
+pub type ProgressCallback = Box<dyn Fn(f32, &str, Option<u64>) + Send>;
 
 /// Main installer struct
 pub struct Installer {
@@ -85,44 +101,140 @@ pub struct Installer {
     lvm_thin_volumes: Vec<ThinVolumeDef>,
     /// LUKS container for LVM PV (LvmThin layout)
     luks_lvm_container: Option<LuksContainer>,
+    /// Volumes to format, mount and list in fstab; resolved once the
+    /// partition, encryption and LVM thin layers are set up
+    volumes: Option<VolumeSet>,
+    /// Package providing the FIDO2 token plugin (`disk.fido2_unlock`);
+    /// set during preparation
+    fido2_plugin_package: Option<String>,
+    /// Whether the target disk is rotational (HDD); set during preparation
+    rotational: bool,
     /// Skip interactive confirmation prompt (e.g. when GUI already confirmed)
     skip_confirm: bool,
+    /// Answer yes to package and dependency prompts and proceed on a disk
+    /// in use (`--yes`)
+    assume_yes: bool,
+    /// Allow installing onto the disk backing `/` or the live medium
+    force_target: bool,
+    /// Image attached for `--image`; its loop device is detached at the end
+    disk_image: Option<DiskImage>,
+    /// Disk size a dry run plans for instead of probing the host and device
+    simulated_disk_mib: Option<u64>,
+    /// Where the target is mounted while installing
+    install_root: String,
+    /// Free region and numbering for installing next to an existing
+    /// system (`disk.preserve_partitions`); set during preparation
+    alongside: Option<AlongsidePlan>,
     /// Optional progress callback for GUI integration
     progress_cb: Option<ProgressCallback>,
+    /// Phase timings for the remaining-time estimate; started with the
+    /// phases after preparation, so confirmation prompts are not counted
+    phase_timer: RefCell<Option<PhaseTimer>>,
 }
 
 impl Installer {
     pub fn new(config: DeploymentConfig, dry_run: bool) -> Self {
+        let cmd = CommandRunner::new(dry_run).with_network_retries(config.network.download_retries);
         Self {
             config,
-            cmd: CommandRunner::new(dry_run),
+            cmd,
             layout: None,
             luks_containers: Vec::new(),
             luks_boot_container: None,
             keyfiles: Vec::new(),
             lvm_thin_volumes: Vec::new(),
             luks_lvm_container: None,
+            volumes: None,
+            fido2_plugin_package: None,
+            rotational: true,
             skip_confirm: false,
+            assume_yes: false,
+            force_target: false,
+            disk_image: None,
+            simulated_disk_mib: None,
+            install_root: DEFAULT_INSTALL_ROOT.to_string(),
+            alongside: None,
             progress_cb: None,
+            phase_timer: RefCell::new(None),
         }
     }
 
-    /// Skip the interactive confirmation prompt.
+    /// Skip the confirmation before the disk is erased.
     /// Use this when confirmation has already been obtained (e.g. via GUI).
-    #[allow(dead_code)]
     pub fn with_skip_confirm(mut self, skip: bool) -> Self {
         self.skip_confirm = skip;
         self
     }
 
+    /// Install missing host packages without asking and proceed on a
+    /// target whose filesystems are in use.  Does not skip the
+    /// confirmation before the disk is erased.
+    pub fn with_assume_yes(mut self, yes: bool) -> Self {
+        self.assume_yes = yes;
+        self
+    }
+
+    /// Allow the target to be the disk the running system or live ISO is
+    /// booted from.  Only exposed through `--force-target`.
+    pub fn with_force_target(mut self, force: bool) -> Self {
+        self.force_target = force;
+        self
+    }
+
+    /// Write every command the install runs, with secrets redacted, to
+    /// `deploytix-install.sh` in the runtime dir; it is copied to `/root`
+    /// of the installed system before unmounting.  The runtime dir must
+    /// already be resolved.
+    pub fn with_transcript(mut self) -> Result<Self> {
+        let path = crate::resources::runtime_path(TRANSCRIPT_NAME);
+        let transcript =
+            Transcript::create(&path, &self.config.disk.device, self.config.secrets())?;
+        info!("Recording commands to {}", path.display());
+        self.cmd = self.cmd.with_transcript(Arc::new(transcript));
+        Ok(self)
+    }
+
+    /// Install into a disk image attached with [`open_image`]; the
+    /// config's device must be its loop device.  The loop device is
+    /// detached when the install finishes or fails.
+    ///
+    /// [`open_image`]: crate::disk::image::open_image
+    pub fn with_disk_image(mut self, image: DiskImage) -> Self {
+        self.disk_image = Some(image);
+        self
+    }
+
+    /// Plan a dry run for a disk of `size_mib` MiB without probing the host
+    /// (dependencies, running-system disk, SMART, existing data), so the
+    /// recorded command sequence is the same on every machine.  Ignored
+    /// outside dry-run mode.
+    pub fn with_simulated_disk(mut self, size_mib: u64) -> Self {
+        self.simulated_disk_mib = Some(size_mib);
+        self
+    }
+
+    /// Mount the target under `root` instead of [`DEFAULT_INSTALL_ROOT`].
+    pub fn with_install_root(mut self, root: &str) -> Self {
+        self.install_root = root.trim_end_matches('/').to_string();
+        self
+    }
+
     /// Set a progress callback for reporting installation progress.
-    /// The callback receives a progress value (0.0–1.0) and a status message.
+    /// The callback receives a progress value (0.0–1.0), a status message
+    /// and the estimated seconds remaining.
     #[allow(dead_code)]
     pub fn with_progress_callback(mut self, cb: ProgressCallback) -> Self {
         self.progress_cb = Some(cb);
         self
     }
 
+    /// Receive the output of long-running commands (basestrap, pacman,
+    /// yay) line by line as they print it.
+    pub fn with_output_callback(mut self, cb: OutputCallback) -> Self {
+        self.cmd = self.cmd.with_output_callback(cb);
+        self
+    }
+
     /// Attach a recording channel to the internal `CommandRunner`.
     /// Every command executed during installation will send an
     /// `OperationRecord` through the channel.  Used by the rehearsal system.
@@ -140,10 +252,57 @@ impl Installer {
         self
     }
 
-    /// Report progress via the callback, if one is set.
+    /// Report progress via the callback, if one is set, with the estimated
+    /// time remaining once the phase timer is running.
     fn report_progress(&self, progress: f32, status: &str) {
+        let eta = self
+            .phase_timer
+            .borrow_mut()
+            .as_mut()
+            .and_then(|timer| timer.begin_phase(progress, status));
+        if let Some(secs) = eta {
+            info!(
+                "[{:.0}%] {} ({})",
+                progress * 100.0,
+                status,
+                eta::format_eta(secs)
+            );
+        }
+        if let Some(ref cb) = self.progress_cb {
+            cb(progress, status, eta);
+        }
+    }
+
+    /// Progress observer for a long phase started at `start`: reports the
+    /// fraction of the phase it is given as progress towards `end`, without
+    /// starting a new phase.  Only changes of a whole percent are reported,
+    /// so a line per package does not flood the log.
+    fn phase_progress<'a>(&'a self, start: f32, end: f32, status: &'a str) -> impl Fn(f32) + 'a {
+        let last_percent = Cell::new((start * 100.0) as u32);
+        move |fraction| {
+            let progress = start + (end - start) * fraction.clamp(0.0, 1.0);
+            let percent = (progress * 100.0) as u32;
+            if last_percent.replace(percent) == percent {
+                return;
+            }
+            let eta = self
+                .phase_timer
+                .borrow()
+                .as_ref()
+                .and_then(|timer| timer.estimate_within_phase(progress));
+            if let Some(ref cb) = self.progress_cb {
+                cb(progress, status, eta);
+            }
+        }
+    }
+
+    /// Report a long phase started at `start` whose progress cannot be
+    /// measured, e.g. when the command's output carries no counters.
+    fn phase_indeterminate(&self, start: f32, status: &str) {
+        let status = format!("{} (progress unknown)", status);
+        info!("[{:.0}%] {}", start * 100.0, status);
         if let Some(ref cb) = self.progress_cb {
-            cb(progress, status);
+            cb(start, &status, None);
         }
     }
 
@@ -162,7 +321,12 @@ impl Installer {
 
         // Phase 1: Preparation (no resources to clean up if this fails)
         self.report_progress(0.0, "Preparing installation...");
-        self.prepare()?;
+        if let Err(e) = self.prepare() {
+            if let Some(ref image) = self.disk_image {
+                detach_loop(&self.cmd, &image.loop_device);
+            }
+            return Err(e);
+        }
 
         // Run all remaining phases with cleanup guard
         let result = self.run_phases();
@@ -176,6 +340,10 @@ impl Installer {
             self.emergency_cleanup();
         }
 
+        if let Some(transcript) = self.cmd.transcript() {
+            println!("  Command transcript: {}", transcript.path().display());
+        }
+
         // Re-raise the caught signal so the parent shell sees the
         // correct exit status (e.g. 128 + signal number).
         if signal::is_interrupted() {
@@ -192,8 +360,10 @@ impl Installer {
     /// each step checks its own feature flag and is a no-op if disabled.
     fn run_phases(&mut self) -> Result<()> {
         let uses_lvm_thin = self.config.disk.use_lvm_thin;
-        let uses_encryption = self.config.disk.encryption;
+        // ZFS encrypts natively inside the pool: no LUKS layer or hooks
+        let uses_encryption = self.config.disk.uses_luks();
         let uses_multi_luks = uses_encryption && !uses_lvm_thin;
+        *self.phase_timer.borrow_mut() = Some(PhaseTimer::new(PhaseHistory::load()));
 
         // Phase 2: Partition disk
         self.report_progress(0.10, "Partitioning disk...");
@@ -203,47 +373,86 @@ impl Installer {
         if uses_lvm_thin {
             self.report_progress(0.15, "Setting up LVM thin provisioning...");
             self.setup_lvm_thin()?;
+            self.resolve_volumes();
+            self.enroll_recovery_passphrase()?;
             self.report_progress(0.22, "Formatting LVM volumes...");
-            self.format_lvm_volumes()?;
+            self.format_volumes()?;
             self.report_progress(0.28, "Mounting LVM volumes...");
             self.mount_lvm_volumes()?;
         } else if uses_multi_luks {
             self.report_progress(0.15, "Setting up encryption...");
             self.setup_multi_volume_encryption()?;
+            self.resolve_volumes();
+            // Before FIDO2 and keyfiles, which take the next free keyslots
+            self.enroll_recovery_passphrase()?;
+            if self.config.disk.fido2_unlock {
+                self.report_progress(0.18, "Enrolling FIDO2 security key...");
+                self.enroll_fido2()?;
+            }
             self.report_progress(0.22, "Formatting encrypted partitions...");
-            self.format_multi_volume_partitions()?;
+            self.format_volumes()?;
             self.report_progress(0.28, "Mounting encrypted partitions...");
             self.mount_multi_volume_partitions()?;
         } else if self.config.disk.filesystem == crate::config::Filesystem::Zfs {
             // ZFS: format non-ZFS partitions (EFI, swap, boot if non-ZFS),
             // then create pools/datasets and mount everything.
             self.report_progress(0.20, "Formatting partitions and creating ZFS pools...");
+            self.resolve_volumes();
             self.format_partitions()?;
             self.report_progress(0.25, "Creating ZFS datasets and mounting...");
             self.mount_partitions_zfs()?;
         } else {
             self.report_progress(0.20, "Formatting partitions...");
+            self.resolve_volumes();
             self.format_partitions()?;
             self.report_progress(0.28, "Mounting partitions...");
             self.mount_partitions()?;
         }
 
         // Phase 3: Base system
-        self.report_progress(0.30, "Installing base system (this may take a while)...");
-        self.install_base_system()?;
+        let status = "Installing base system (this may take a while)...";
+        self.report_progress(0.30, status);
+        {
+            let progress = self.phase_progress(0.30, 0.55, status);
+            self.install_base_system(&|fraction| match fraction {
+                Some(fraction) => progress(fraction),
+                None => self.phase_indeterminate(0.30, status),
+            })?;
+        }
+        if self.config.disk.fido2_unlock {
+            self.install_token_plugin()?;
+        }
 
         // Phase 3.5: Generate fstab
         self.report_progress(0.55, "Generating fstab...");
-        if uses_lvm_thin {
-            self.generate_fstab_lvm_thin()?;
-        } else if uses_multi_luks {
-            self.generate_fstab_multi_volume()?;
+        if uses_lvm_thin || uses_multi_luks {
+            self.generate_fstab_volumes()?;
         } else {
             self.generate_fstab()?;
             if self.config.disk.swap_type == SwapType::FileZram {
-                append_swap_file_entry(INSTALL_ROOT)?;
+                append_swap_file_entry(&self.install_root)?;
             }
         }
+        if self.config.system.tmp_tmpfs {
+            append_tmpfs_entry(
+                &self.cmd,
+                &self.install_root,
+                self.config.system.tmp_tmpfs_size.as_deref(),
+            )?;
+        }
+        if self.config.system.hardened {
+            let explicit: Vec<&str> = self
+                .config
+                .disk
+                .partitions
+                .iter()
+                .filter(|p| p.mount_options.is_some())
+                .map(|p| p.mount_point.as_str())
+                .collect();
+            let hide_pids =
+                self.config.desktop.environment == crate::config::DesktopEnvironment::None;
+            harden_fstab(&self.cmd, &self.install_root, &explicit, hide_pids)?;
+        }
 
         // Phase 3.6: Crypttab and keyfiles (for encrypted systems)
         if uses_multi_luks {
@@ -257,13 +466,24 @@ impl Installer {
             }
             self.generate_crypttab_lvm_thin()?;
         }
-
-        // Phase 3.7: Swap configuration (ZRAM / swap file)
-        if self.config.disk.swap_type != SwapType::Partition {
+        // Phase 3.7: Swap configuration (ZRAM / swap file, swappiness)
+        if self.config.disk.swap_type != SwapType::Partition
+            || self.config.disk.vm_swappiness.is_some()
+        {
             self.report_progress(0.62, "Configuring swap...");
             self.configure_swap()?;
         }
 
+        // Phase 3.8: zram-backed /tmp and /var/log
+        if configure::zram_mounts::is_enabled(&self.config) {
+            self.report_progress(0.63, "Configuring zram mounts...");
+            configure::zram_mounts::configure_zram_mounts(
+                &self.cmd,
+                &self.config,
+                &self.install_root,
+            )?;
+        }
+
         // Phase 4: System configuration
         self.report_progress(0.65, "Configuring system...");
         self.configure_system()?;
@@ -367,7 +587,7 @@ impl Installer {
                 &self.cmd,
                 &self.config.system.init,
                 "hhd",
-                INSTALL_ROOT,
+                &self.install_root,
             )?;
         }
 
@@ -379,7 +599,7 @@ impl Installer {
                 &self.cmd,
                 &self.config.system.init,
                 "plugin_loader",
-                INSTALL_ROOT,
+                &self.install_root,
             )?;
         }
 
@@ -391,7 +611,7 @@ impl Installer {
                 &self.cmd,
                 &self.config.system.init,
                 "evdevhook2",
-                INSTALL_ROOT,
+                &self.install_root,
             )?;
         }
 
@@ -400,11 +620,13 @@ impl Installer {
         // config).  Additionally, when an interactive policy is attached,
         // prompts the user for more.
         self.run_extras_phase()?;
+        configure::services::enable_extra_services(&self.cmd, &self.config, &self.install_root)?;
 
         // Phase 6: Finalization
         self.report_progress(0.96, "Finalizing installation...");
         self.finalize()?;
 
+        let timings = self.phase_timer.take().map(PhaseTimer::finish);
         self.report_progress(1.0, "Installation complete");
         info!(
             "Installation to {} finished successfully",
@@ -413,6 +635,20 @@ impl Installer {
         println!("\n✓ Installation completed successfully!");
         println!("  You can now reboot into your new Artix Linux system.");
 
+        if let Some(timings) = timings {
+            println!();
+            for line in timings.report_lines() {
+                println!("{}", line);
+                self.cmd.log_output(&line);
+            }
+            // Dry-run timings would drag the averages towards zero
+            if !self.cmd.is_dry_run() {
+                if let Err(e) = timings.save() {
+                    warn!("Failed to save phase timings: {}", e);
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -428,30 +664,16 @@ impl Installer {
 
         info!(
             "Emergency cleanup: releasing all resources on {}",
-            INSTALL_ROOT
+            &self.install_root
         );
 
-        // 1. Unmount all filesystems under INSTALL_ROOT (deepest first)
+        // 1. Unmount all filesystems under the install root (deepest first)
         if let Ok(mounts) = fs::read_to_string("/proc/mounts") {
-            let mut mount_points: Vec<&str> = mounts
-                .lines()
-                .filter_map(|line| {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 2 && parts[1].starts_with(INSTALL_ROOT) {
-                        Some(parts[1])
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            mount_points.sort_by_key(|b| std::cmp::Reverse(b.matches('/').count()));
-
-            for mp in mount_points {
+            for mp in mount_points_under(&mounts, &self.install_root) {
                 info!("Emergency cleanup: unmounting {}", mp);
-                if self.cmd.force_run("umount", &[mp]).is_err() {
+                if self.cmd.force_run("umount", &[&mp]).is_err() {
                     // Try lazy unmount as fallback
-                    if let Err(e) = self.cmd.force_run("umount", &["-l", mp]) {
+                    if let Err(e) = self.cmd.force_run("umount", &["-l", &mp]) {
                         warn!("Emergency cleanup: failed to unmount {}: {}", mp, e);
                     }
                 }
@@ -504,6 +726,12 @@ impl Installer {
             }
         }
 
+        // 5. Detach the loop device of an image we attached
+        if let Some(ref image) = self.disk_image {
+            info!("Emergency cleanup: detaching {}", image.loop_device);
+            detach_loop(&self.cmd, &image.loop_device);
+        }
+
         info!("Emergency cleanup complete");
     }
 
@@ -511,8 +739,6 @@ impl Installer {
     /// that is still writing wipe-tags after the parent was interrupted).
     /// These processes prevent dm mappings from being closed.
     fn kill_orphaned_cryptsetup() {
-        use tracing::warn;
-
         // Read /proc to find cryptsetup processes whose parent is init (PPID=1),
         // indicating they were orphaned when deploytix was interrupted.
         let Ok(proc_entries) = fs::read_dir("/proc") else {
@@ -551,22 +777,9 @@ impl Installer {
                         "Emergency cleanup: killing orphaned cryptsetup process (PID {})",
                         pid
                     );
-                    unsafe {
-                        libc::kill(pid as i32, libc::SIGTERM);
-                    }
-                    // Give it a moment to exit
-                    std::thread::sleep(std::time::Duration::from_millis(500));
-                    // Force-kill if still alive
-                    if std::path::Path::new(&format!("/proc/{}", pid)).exists() {
-                        warn!(
-                            "Emergency cleanup: SIGTERM failed, sending SIGKILL to PID {}",
-                            pid
-                        );
-                        unsafe {
-                            libc::kill(pid as i32, libc::SIGKILL);
-                        }
-                        std::thread::sleep(std::time::Duration::from_millis(200));
-                    }
+                    signal::terminate(pid as i32, || {
+                        std::path::Path::new(&format!("/proc/{}", pid)).exists()
+                    });
                 }
             }
         }
@@ -579,38 +792,205 @@ impl Installer {
             self.config.disk.device
         );
 
-        // Check host system dependencies
-        self.report_progress(0.02, "Checking host dependencies...");
-        ensure_dependencies(
-            &self.cmd,
-            &self.config.disk.filesystem,
-            &self.config.disk.boot_filesystem,
-            self.config.disk.encryption,
-            self.config.disk.use_lvm_thin,
-            &self.config.system.bootloader,
-        )?;
+        // Scratch files (partition script, temporary repo) need a writable
+        // runtime dir; a no-op when the CLI already resolved it.
+        if !self.cmd.is_dry_run() {
+            crate::resources::ensure_runtime_dir(None)?;
+        }
 
         // Get device info and compute layout
-        let device_info = get_device_info(&self.config.disk.device)?;
-        let disk_mib = device_info.size_mib();
+        let device_info = match self.simulated_disk_mib {
+            Some(size_mib) if self.cmd.is_dry_run() => {
+                BlockDevice::simulated(&self.config.disk.device, size_mib)
+            }
+            _ => self.probe_host_and_target()?,
+        };
+        let mut disk_mib = device_info.size_mib();
+        let mut rotational = device_info.is_rotational;
+
+        // A mirror gets the same partitions, planned for the smaller disk
+        if let Some(mirror) = self.config.disk.mirror_device.clone() {
+            let mirror_info = match self.simulated_disk_mib {
+                Some(size_mib) if self.cmd.is_dry_run() => {
+                    BlockDevice::simulated(&mirror, size_mib)
+                }
+                _ => self.probe_target(&mirror)?,
+            };
+            disk_mib = mirror_planning_mib(disk_mib, mirror_info.size_mib())
+                .map_err(DeploytixError::ValidationError)?;
+            rotational |= mirror_info.is_rotational;
+            info!(
+                "Mirror disk: {} ({}, {}, {} MiB); planning for {} MiB",
+                mirror,
+                mirror_info.model.as_deref().unwrap_or("Unknown"),
+                mirror_info.media_kind(),
+                mirror_info.size_mib(),
+                disk_mib
+            );
+        }
 
         info!(
-            "Target disk: {} ({}, {} MiB)",
+            "Target disk: {} ({}, {}, {} MiB)",
             self.config.disk.device,
             device_info.model.as_deref().unwrap_or("Unknown"),
+            device_info.media_kind(),
             disk_mib
         );
 
+        // SSD/HDD tuning: mkfs skips discard on HDDs, and btrfs gets
+        // ssd,discard=async on flash unless the config says otherwise.
+        // Neither applies under dm-integrity.  A mirror counts as
+        // rotational when either disk is.
+        self.rotational = rotational;
+        let integrity = self.config.disk.uses_luks() && self.config.disk.integrity;
+        let trim = !rotational && !integrity;
+        if self.config.disk.btrfs_ssd.is_none() {
+            self.config.disk.btrfs_ssd = Some(trim);
+        }
+        if self.config.disk.discard.is_none() {
+            self.config.disk.discard = Some(trim);
+        }
+        if self.config.zfs.ashift.is_none() {
+            self.config.zfs.ashift = Some(ashift_for_sector_size(device_info.physical_sector_size));
+        }
+        if let Some(lint) = self.config.integrity_trim_lint(rotational) {
+            warn!("{}", lint);
+        }
+        if let Some(lint) = self.config.shell_package_lint() {
+            warn!("{}", lint);
+        }
+        if let Some(lint) = self.config.firewall_ssh_lint() {
+            warn!("{}", lint);
+        }
+        if let Some(lint) = self.config.display_manager_lint() {
+            warn!("{}", lint);
+        }
+
+        // Resolve the firmware once so layout and bootloader agree
+        let firmware = self.config.disk.firmware();
+        self.config.disk.firmware = Some(firmware);
+        info!("Boot firmware: {}", firmware);
+        if firmware == Firmware::Uefi
+            && !self.cmd.is_dry_run()
+            && self.config.disk.existing_esp.is_none()
+            && !configure::bootloader::efivars_usable()
+        {
+            warn!(
+                "This live system has no writable EFI variables (booted in BIOS/CSM mode?); \
+                 the target will boot from the removable path but gets no UEFI boot entry"
+            );
+        }
+
+        // Resolve `timezone = "auto"` once; the saved config keeps the result
+        if self.config.system.timezone == configure::locale::AUTO_TIMEZONE {
+            self.config.system.timezone = configure::locale::detect_timezone(self.cmd.is_dry_run());
+        }
+
+        // Next to an existing system, the layout is planned for the largest
+        // free region (plus the ESP it reuses)
+        if self.config.disk.preserves_partitions() {
+            let device = &self.config.disk.device;
+            let esp = self.config.disk.existing_esp.as_deref();
+            let table = if self.simulated_disk_mib.is_some() && self.cmd.is_dry_run() {
+                dualboot::simulated_table(esp, disk_mib)
+            } else {
+                if esp.is_some()
+                    && !self.cmd.is_dry_run()
+                    && !configure::bootloader::efivars_usable()
+                {
+                    return Err(DeploytixError::ValidationError(
+                        "existing_esp needs EFI variables to register the boot entry; \
+                         boot the live system in UEFI mode"
+                            .to_string(),
+                    ));
+                }
+                dualboot::read_partition_table(device)?
+            };
+            let plan = dualboot::plan_alongside(device, esp, &table)?;
+            if let Some(esp) = esp {
+                info!("Reusing EFI System Partition {}", esp);
+            }
+            info!(
+                "{} MiB of free space on {} for the new partitions",
+                plan.region_mib(),
+                device
+            );
+            disk_mib = plan.layout_mib();
+            self.alongside = Some(plan);
+        }
+
         // Compute partition layout (features are applied as layers)
-        let layout = compute_layout_from_config(&self.config.disk, disk_mib)?;
+        let mut layout = compute_layout_from_config(&self.config.disk, disk_mib)?;
+        if let Some(ref plan) = self.alongside {
+            dualboot::adopt_layout(&mut layout, plan)?;
+        }
         print_layout_summary(&layout);
+        if let Some(ref plan) = self.alongside {
+            // Also catches a layout that does not fit before anything is
+            // written
+            print!(
+                "{}",
+                dualboot::alongside_preview(&self.config.disk.device, &layout, plan)?
+            );
+            println!();
+        }
         self.layout = Some(layout);
 
+        // Show what is about to be destroyed and refuse to touch a disk that
+        // is in use (mounted, active swap, or the running system's root).
+        if self.simulated_disk_mib.is_none() || !self.cmd.is_dry_run() {
+            self.check_existing_data(&self.config.disk.device)?;
+            if let Some(ref mirror) = self.config.disk.mirror_device {
+                self.check_existing_data(mirror)?;
+            }
+        }
+
+        if self.config.disk.btrfs_snapshots && !self.config.disk.uses_btrfs_snapshots() {
+            warn!(
+                "btrfs_snapshots needs btrfs subvolumes outside LVM thin; skipping snapper setup"
+            );
+        }
+
+        // A missing security key or token plugin must stop us before the
+        // disk is wiped
+        if self.config.disk.fido2_unlock && !self.cmd.is_dry_run() {
+            configure::fido2::check_enrollment_possible()?;
+            self.fido2_plugin_package = Some(configure::fido2::token_plugin_package()?);
+        }
+
+        if self.config.disk.recovery_passphrase == RecoveryPassphrase::Prompt
+            && !self.cmd.is_dry_run()
+        {
+            let recovery = prompt_new_password("Recovery passphrase")?;
+            if self.config.disk.encryption_password.as_deref() == Some(recovery.as_str()) {
+                return Err(DeploytixError::ValidationError(
+                    "The recovery passphrase must differ from the encryption password".to_string(),
+                ));
+            }
+            self.config.disk.recovery_passphrase = RecoveryPassphrase::Value(recovery);
+        }
+
         // Confirm with user
-        let warning = format!(
-            "This will ERASE ALL DATA on {}. This operation cannot be undone!",
-            self.config.disk.device
-        );
+        let targets = match self.config.disk.mirror_device {
+            Some(ref mirror) => format!("{} and {}", self.config.disk.device, mirror),
+            None => self.config.disk.device.clone(),
+        };
+        let warning = match (self.alongside.is_some(), &self.config.disk.existing_esp) {
+            (true, Some(esp)) => format!(
+                "This will add partitions to the free space on {} and install GRUB into {}. \
+                 Existing partitions are kept, but back up the disk first!",
+                targets, esp
+            ),
+            (true, None) => format!(
+                "This will add partitions to the free space on {}. \
+                 Existing partitions are kept, but back up the disk first!",
+                targets
+            ),
+            (false, _) => format!(
+                "This will ERASE ALL DATA on {}. This operation cannot be undone!",
+                targets
+            ),
+        };
 
         if !self.cmd.is_dry_run() && !self.skip_confirm && !warn_confirm(&warning)? {
             return Err(crate::utils::error::DeploytixError::UserCancelled);
@@ -618,12 +998,148 @@ impl Installer {
 
         // Create installation directory
         if !self.cmd.is_dry_run() {
-            std::fs::create_dir_all(INSTALL_ROOT)?;
+            std::fs::create_dir_all(&self.install_root)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check the host tools and the target disk before anything is
+    /// written, and read the disk's size and type.
+    fn probe_host_and_target(&self) -> Result<BlockDevice> {
+        // Check host system dependencies
+        self.report_progress(0.02, "Checking host dependencies...");
+        let missing = ensure_dependencies(
+            &self.config.disk.filesystems_in_use(),
+            self.config.disk.uses_luks(),
+            self.config.disk.use_lvm_thin,
+            &self.config.system.bootloader,
+        );
+        if !missing.is_empty() {
+            self.install_missing_dependencies(&missing)?;
+        }
+        check_feature_versions(&self.config)?;
+
+        self.probe_target(&self.config.disk.device)
+    }
+
+    /// Refuse a disk backing the running system, then read its size and
+    /// type and report its SMART health.
+    fn probe_target(&self, device: &str) -> Result<BlockDevice> {
+        // Never wipe the disk we are running from
+        if let Some(disk) = is_running_system_disk(device) {
+            if !self.force_target {
+                return Err(DeploytixError::RunningSystemDisk(format!(
+                    "{} backs / or the live medium ({})",
+                    device, disk
+                )));
+            }
+            warn!(
+                "--force-target: installing onto {}, which backs the running system",
+                disk
+            );
+        }
+
+        // Partitions on a loop device only appear with partition scanning
+        check_loop_partscan(device)?;
+        if let Some(backing) = loop_backing_file(device) {
+            info!("Target {} is a loop device backed by {}", device, backing);
+        }
+
+        let device_info = get_device_info(device)?;
+        if let Some(report) = smart_health(device) {
+            if report.passed {
+                info!("SMART health of {}: {}", device, report.summary());
+            } else {
+                warn!("SMART health of {}: {}", device, report.summary());
+            }
+        }
+        Ok(device_info)
+    }
+
+    /// Offer to install missing host packages (without asking under
+    /// `--yes`) and fail if that is declined or not possible.
+    fn install_missing_dependencies(&self, missing: &[String]) -> Result<()> {
+        let missing_err = |pkgs: &[String]| DeploytixError::MissingDependencies(pkgs.join(" "));
+
+        if !can_install_dependencies(missing) {
+            return Err(missing_err(missing));
+        }
+        let approved = self.assume_yes
+            || self.cmd.is_dry_run()
+            || prompt_confirm("Install the missing packages with pacman now?", true)
+                .unwrap_or(false);
+        if !approved {
+            return Err(missing_err(missing));
+        }
+
+        install_dependencies(&self.cmd, missing)?;
+        if self.cmd.is_dry_run() {
+            return Ok(());
         }
 
+        let still_missing = check_dependencies(
+            &self.config.disk.filesystems_in_use(),
+            self.config.disk.uses_luks(),
+            self.config.disk.use_lvm_thin,
+            &self.config.system.bootloader,
+        );
+        if !still_missing.is_empty() {
+            return Err(missing_err(&still_missing));
+        }
         Ok(())
     }
 
+    /// List existing filesystems and LUKS containers on a target disk and
+    /// refuse to proceed if any of them is in use, unless `--yes` was
+    /// given.  A disk that cannot be inspected is refused as well.
+    fn check_existing_data(&self, device: &str) -> Result<()> {
+        let existing = probe_existing_data(device)
+            .inspect_err(|e| warn!("Could not check {} for filesystems in use: {}", device, e))?;
+
+        if existing.is_empty() {
+            println!("\nNo existing filesystems detected on {}", device);
+            return Ok(());
+        }
+
+        println!("\nExisting data on {}:", device);
+        for entry in &existing {
+            println!(
+                "  {:<28} {:<12} {:<16} {}",
+                entry.path,
+                entry.fstype.as_deref().unwrap_or("-"),
+                entry.label.as_deref().unwrap_or("-"),
+                entry
+                    .mountpoint
+                    .as_deref()
+                    .map(|m| format!("mounted at {}", m))
+                    .unwrap_or_default()
+            );
+        }
+
+        let in_use: Vec<String> = existing
+            .iter()
+            .filter(|e| e.is_mounted())
+            .map(|e| format!("{} on {}", e.path, e.mountpoint.as_deref().unwrap_or("")))
+            .collect();
+        if in_use.is_empty() {
+            return Ok(());
+        }
+
+        let reason = if existing.iter().any(|e| e.is_root()) {
+            format!("{} hosts the running system's root filesystem", device)
+        } else {
+            format!("{} is in use ({})", device, in_use.join(", "))
+        };
+
+        if self.cmd.is_dry_run() || self.assume_yes {
+            warn!("{}", reason);
+            return Ok(());
+        }
+
+        Err(DeploytixError::DeviceMounted(reason))
+    }
+
     /// Partition the disk
     fn partition_disk(&self) -> Result<()> {
         let layout = self.layout.as_ref().unwrap();
@@ -632,7 +1148,23 @@ impl Installer {
             self.config.disk.device,
             self.config.disk.partitions.len()
         );
-        apply_partitions(&self.cmd, &self.config.disk.device, layout)?;
+        if let Some(ref plan) = self.alongside {
+            return dualboot::apply_partitions_alongside(
+                &self.cmd,
+                &self.config.disk.device,
+                layout,
+                plan,
+            );
+        }
+        // A mirror gets identical partitions on both disks, sized for the
+        // smaller one; a simulated disk has no size to read
+        let simulated = self.simulated_disk_mib.is_some() && self.cmd.is_dry_run();
+        let size_mib =
+            (simulated || self.config.disk.mirror_device.is_some()).then_some(layout.total_mib);
+        apply_partitions(&self.cmd, &self.config.disk.device, layout, size_mib)?;
+        if let Some(ref mirror) = self.config.disk.mirror_device {
+            apply_partitions(&self.cmd, mirror, layout, size_mib)?;
+        }
         Ok(())
     }
 
@@ -648,8 +1180,10 @@ impl Installer {
             &self.cmd,
             &self.config.disk.device,
             layout,
-            &self.config.disk.filesystem,
-            &self.config.disk.boot_filesystem,
+            &self.config.disk,
+            self.rotational,
+            &self.config.filesystem_options,
+            self.config.zfs.atime,
         )?;
 
         Ok(())
@@ -657,14 +1191,14 @@ impl Installer {
 
     /// Mount partitions
     fn mount_partitions(&self) -> Result<()> {
-        info!("[Phase 2/6] Mounting partitions to {}", INSTALL_ROOT);
+        info!("[Phase 2/6] Mounting partitions to {}", &self.install_root);
 
         let layout = self.layout.as_ref().unwrap();
         mount_partitions(
             &self.cmd,
-            &self.config.disk.device,
+            self.volumes(),
             layout,
-            INSTALL_ROOT,
+            &self.install_root,
             &self.config.disk.boot_filesystem,
         )?;
 
@@ -675,31 +1209,53 @@ impl Installer {
     fn mount_partitions_zfs(&self) -> Result<()> {
         info!(
             "[Phase 2/6] Creating ZFS pools/datasets and mounting to {}",
-            INSTALL_ROOT
+            &self.install_root
         );
 
         let layout = self.layout.as_ref().unwrap();
         mount_partitions_zfs(
             &self.cmd,
-            &self.config.disk.device,
+            self.volumes(),
             layout,
-            &self.config.disk.filesystem,
+            &self.config.zfs,
+            self.config
+                .disk
+                .encryption_password
+                .as_deref()
+                .filter(|_| self.config.disk.uses_zfs_encryption()),
             &self.config.disk.boot_filesystem,
-            INSTALL_ROOT,
+            &self.install_root,
         )?;
 
         Ok(())
     }
 
-    /// Install base system using basestrap
-    fn install_base_system(&self) -> Result<()> {
+    /// Install base system using basestrap, passing the fraction done to
+    /// `on_progress`
+    fn install_base_system(&self, on_progress: &dyn Fn(Option<f32>)) -> Result<()> {
         info!("[Phase 3/6] Installing base system via basestrap");
 
-        run_basestrap(&self.cmd, &self.config, INSTALL_ROOT)?;
+        // Share the host's package cache across installs, if configured
+        mount_package_cache(&self.cmd, &self.config, &self.install_root)?;
+        run_basestrap(&self.cmd, &self.config, &self.install_root, on_progress)?;
 
         Ok(())
     }
 
+    /// Install the package found during preparation that provides the
+    /// FIDO2 token plugin, then check the plugin is in place
+    fn install_token_plugin(&self) -> Result<()> {
+        if let Some(ref package) = self.fido2_plugin_package {
+            configure::packages::pacman_install_chroot_reviewed(
+                &self.cmd,
+                &self.install_root,
+                "FIDO2 token plugin",
+                vec![package.clone()],
+            )?;
+        }
+        configure::fido2::check_token_plugin(&self.cmd, &self.install_root)
+    }
+
     /// Generate fstab
     fn generate_fstab(&self) -> Result<()> {
         info!("[Phase 3/6] Generating /etc/fstab with partition UUIDs");
@@ -709,9 +1265,10 @@ impl Installer {
             &self.cmd,
             &self.config.disk.device,
             layout,
-            INSTALL_ROOT,
+            &self.install_root,
             &self.config.disk.filesystem,
             &self.config.disk.boot_filesystem,
+            &self.config.zfs,
         )?;
 
         Ok(())
@@ -735,7 +1292,8 @@ impl Installer {
         //      (now-updated) keyring package
         if !self.cmd.is_dry_run() {
             info!("Initialising pacman keyring in chroot");
-            self.cmd.run_in_chroot(INSTALL_ROOT, "pacman-key --init")?;
+            self.cmd
+                .run_in_chroot(&self.install_root, "pacman-key --init")?;
             // Refresh the keyring package before populating. The old
             // keyring can still verify the *keyring package* itself
             // (Artix signs keyring updates with the master key that
@@ -743,50 +1301,66 @@ impl Installer {
             // fails we still populate with whatever is installed.
             let _ = self
                 .cmd
-                .run_in_chroot(INSTALL_ROOT, "pacman -Sy --noconfirm artix-keyring");
+                .run_in_chroot(&self.install_root, "pacman -Sy --noconfirm artix-keyring");
             self.cmd
-                .run_in_chroot(INSTALL_ROOT, "pacman-key --populate artix")?;
+                .run_in_chroot(&self.install_root, "pacman-key --populate artix")?;
         }
 
         // Locale and timezone
-        configure::locale::configure_locale(&self.cmd, &self.config, INSTALL_ROOT)?;
+        configure::locale::configure_locale(&self.cmd, &self.config, &self.install_root)?;
 
         // Dinit needs an explicit keymap-loading service (other inits
         // handle this through their own boot infrastructure).
         if self.config.system.init == crate::config::InitSystem::Dinit {
             configure::locale::create_dinit_keymap_service(
-                INSTALL_ROOT,
+                &self.install_root,
                 &self.config.system.keymap,
+                self.config.system.console_font.as_deref(),
             )?;
         }
 
+        // Kernel module blacklist (modprobe.d)
+        configure::modules::configure_blacklist(&self.cmd, &self.config, &self.install_root)?;
+
+        // Boot-time module loading (modules-load.d; VFIO also goes into
+        // the initramfs via mkinitcpio MODULES below)
+        configure::modules::configure_modules_load(&self.cmd, &self.config, &self.install_root)?;
+
         // User creation
-        configure::users::create_user(&self.cmd, &self.config, INSTALL_ROOT)?;
+        configure::users::create_user(&self.cmd, &self.config, &self.install_root)?;
+
+        // Thin pool autoextend (LVM thin only)
+        if self.config.disk.use_lvm_thin {
+            lvm::configure_thin_autoextend(&self.cmd, &self.config, &self.install_root)?;
+        }
 
         // mkinitcpio
-        configure::mkinitcpio::configure_mkinitcpio(&self.cmd, &self.config, INSTALL_ROOT)?;
+        configure::mkinitcpio::configure_mkinitcpio(&self.cmd, &self.config, &self.install_root)?;
 
         // Bootloader (use layout-aware version when encryption or LVM thin is active)
-        if self.config.disk.encryption || self.config.disk.use_lvm_thin {
+        if self.config.disk.uses_luks() || self.config.disk.use_lvm_thin {
             let layout = self.layout.as_ref().unwrap();
             configure::bootloader::install_bootloader_with_layout(
                 &self.cmd,
                 &self.config,
                 &self.config.disk.device,
                 layout,
-                INSTALL_ROOT,
+                &self.install_root,
             )?;
 
             // Pacman hook to reinstall GRUB on kernel/grub updates.
             // Without this, a kernel update breaks boot on encrypted systems
             // because the standalone EFI binary (or GRUB core image) still
-            // references the old kernel and must be rebuilt.
-            configure::bootloader::create_grub_reinstall_hook(
-                &self.cmd,
-                &self.config,
-                &self.config.disk.device,
-                INSTALL_ROOT,
-            )?;
+            // references the old kernel and must be rebuilt.  rEFInd reads
+            // kernels straight from /boot and needs no rebuild.
+            if self.config.system.bootloader == Bootloader::Grub {
+                configure::bootloader::create_grub_reinstall_hook(
+                    &self.cmd,
+                    &self.config,
+                    &self.config.disk.device,
+                    &self.install_root,
+                )?;
+            }
         } else {
             let layout = self.layout.as_ref().unwrap();
             configure::bootloader::install_bootloader(
@@ -794,23 +1368,37 @@ impl Installer {
                 &self.config,
                 &self.config.disk.device,
                 layout,
-                INSTALL_ROOT,
+                &self.install_root,
             )?;
         }
 
         // Network
-        configure::network::configure_network(&self.cmd, &self.config, INSTALL_ROOT)?;
+        configure::network::configure_network(&self.cmd, &self.config, &self.install_root)?;
 
         // Display manager configuration (if desktop environment selected);
         // dispatches on desktop.display_manager (greetd auto-login default)
         configure::display_manager::configure_display_manager(
             &self.cmd,
             &self.config,
-            INSTALL_ROOT,
+            &self.install_root,
         )?;
 
         // Services
-        configure::services::enable_services(&self.cmd, &self.config, INSTALL_ROOT)?;
+        configure::services::enable_services(&self.cmd, &self.config, &self.install_root)?;
+
+        // Root SSH keys and sshd hardening (after services so openssh's
+        // sshd_config exists)
+        configure::ssh::configure_root_ssh(&self.cmd, &self.config, &self.install_root)?;
+        configure::ssh::configure_sshd(&self.cmd, &self.config, &self.install_root)?;
+
+        // NTP client configuration (its package comes with the services)
+        configure::time_sync::configure_time_sync(&self.cmd, &self.config, &self.install_root)?;
+
+        // Default-deny firewall (package and service come with the services)
+        configure::firewall::configure_firewall(&self.cmd, &self.config, &self.install_root)?;
+
+        // Snapper config for the @snapshots subvolume
+        configure::snapper::configure_snapper(&self.cmd, &self.config, &self.install_root)?;
 
         Ok(())
     }
@@ -825,15 +1413,15 @@ impl Installer {
             }
             DesktopEnvironment::Kde => {
                 info!("[Phase 5/6] Installing KDE Plasma desktop environment");
-                desktop::kde::install(&self.cmd, &self.config, INSTALL_ROOT)?;
+                desktop::kde::install(&self.cmd, &self.config, &self.install_root)?;
             }
             DesktopEnvironment::Gnome => {
                 info!("[Phase 5/6] Installing GNOME desktop environment");
-                desktop::gnome::install(&self.cmd, &self.config, INSTALL_ROOT)?;
+                desktop::gnome::install(&self.cmd, &self.config, &self.install_root)?;
             }
             DesktopEnvironment::Xfce => {
                 info!("[Phase 5/6] Installing XFCE desktop environment");
-                desktop::xfce::install(&self.cmd, &self.config, INSTALL_ROOT)?;
+                desktop::xfce::install(&self.cmd, &self.config, &self.install_root)?;
             }
         }
 
@@ -845,67 +1433,75 @@ impl Installer {
     /// Install GPU driver packages
     fn install_gpu_drivers(&self) -> Result<()> {
         info!("Installing selected GPU driver packages");
-        configure::packages::install_gpu_drivers(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_gpu_drivers(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install Wine compatibility packages
     fn install_wine_packages(&self) -> Result<()> {
         info!("Installing Wine compatibility packages");
-        configure::packages::install_wine_packages(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_wine_packages(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install gaming packages
     fn install_gaming_packages(&self) -> Result<()> {
         info!("Installing gaming packages");
-        configure::packages::install_gaming_packages(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_gaming_packages(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install the gamescope update utility (canonical rebuild + AUR guard)
     fn install_gamescope_update(&self) -> Result<()> {
         info!("Installing gamescope update utility");
-        configure::gamescope_update::setup_gamescope_update(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::gamescope_update::setup_gamescope_update(
+            &self.cmd,
+            &self.config,
+            &self.install_root,
+        )
     }
 
     /// Install session switching scripts (gamescope ↔ desktop)
     fn install_session_switching(&self) -> Result<()> {
         info!("Installing session switching (gamescope ↔ desktop)");
-        configure::session_switching::setup_session_switching(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::session_switching::setup_session_switching(
+            &self.cmd,
+            &self.config,
+            &self.install_root,
+        )
     }
 
     /// Install yay AUR helper from source
     fn install_yay(&self) -> Result<()> {
         info!("Building and installing yay AUR helper from source");
-        configure::packages::install_yay(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_yay(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install AUR packages via yay
     fn install_aur_packages(&self) -> Result<()> {
         info!("Installing AUR packages via yay");
-        configure::packages::install_aur_packages(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_aur_packages(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install the chosen iwd GUI frontend (iwgtk / iwdgui / iwqt) via yay
     fn install_iwd_frontend(&self) -> Result<()> {
         info!("Installing iwd GUI frontend via yay");
-        configure::packages::install_iwd_frontend(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_iwd_frontend(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install btrfs snapshot tools (snapper, btrfs-assistant) via yay
     fn install_btrfs_tools(&self) -> Result<()> {
         info!("Installing btrfs snapshot tools via yay");
-        configure::packages::install_btrfs_tools(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_btrfs_tools(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install user autostart entries (audio-startup, nm-applet)
     fn install_autostart_entries(&self) -> Result<()> {
         info!("Installing user autostart entries");
-        configure::packages::install_autostart_entries(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_autostart_entries(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Write /etc/sysctl.d/99-gaming.conf with gaming performance tweaks
     fn install_sysctl_gaming(&self) -> Result<()> {
         info!("Installing gaming sysctl tweaks");
-        configure::packages::install_sysctl_gaming(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_sysctl_gaming(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Write /etc/sysctl.d/99-network-performance.conf with network tuning
@@ -914,27 +1510,27 @@ impl Installer {
         configure::packages::install_sysctl_network_performance(
             &self.cmd,
             &self.config,
-            INSTALL_ROOT,
+            &self.install_root,
         )
     }
 
     /// Install Handheld Daemon (HHD) via yay + init-specific service file
     fn install_hhd(&self) -> Result<()> {
         info!("Installing Handheld Daemon (HHD)");
-        configure::packages::install_hhd(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_hhd(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install Decky Loader + init-specific service file
     fn install_decky_loader(&self) -> Result<()> {
         info!("Installing Decky Loader");
-        configure::packages::install_decky_loader(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_decky_loader(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Install evdevhook2 (Cemuhook UDP motion server) via yay + udev rule
     /// + init-specific service file
     fn install_evdevhook2(&self) -> Result<()> {
         info!("Installing evdevhook2 (Cemuhook UDP motion server)");
-        configure::packages::install_evdevhook2(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::packages::install_evdevhook2(&self.cmd, &self.config, &self.install_root)
     }
 
     /// Phase 5.95 — post-install extras.
@@ -953,9 +1549,11 @@ impl Installer {
         let pre_aur = self.config.packages.extra_packages.aur.clone();
         if !pre_pacman.is_empty() {
             self.report_progress(0.94, "Installing extra pacman packages from config...");
-            if let Err(e) =
-                configure::packages::install_extras_pacman(&self.cmd, INSTALL_ROOT, &pre_pacman)
-            {
+            if let Err(e) = configure::packages::install_extras_pacman(
+                &self.cmd,
+                &self.install_root,
+                &pre_pacman,
+            ) {
                 warn!("config-supplied pacman extras failed: {}", e);
             }
         }
@@ -964,7 +1562,7 @@ impl Installer {
             if let Err(e) = configure::packages::install_extras_aur(
                 &self.cmd,
                 &self.config,
-                INSTALL_ROOT,
+                &self.install_root,
                 &pre_aur,
             ) {
                 warn!("config-supplied AUR extras failed: {}", e);
@@ -979,7 +1577,7 @@ impl Installer {
             if !extras.pacman.is_empty() {
                 if let Err(e) = configure::packages::install_extras_pacman(
                     &self.cmd,
-                    INSTALL_ROOT,
+                    &self.install_root,
                     &extras.pacman,
                 ) {
                     warn!("interactive pacman extras failed: {}", e);
@@ -994,7 +1592,7 @@ impl Installer {
                 if let Err(e) = configure::packages::install_extras_aur(
                     &self.cmd,
                     &self.config,
-                    INSTALL_ROOT,
+                    &self.install_root,
                     &extras.aur,
                 ) {
                     warn!("interactive AUR extras failed: {}", e);
@@ -1019,17 +1617,65 @@ impl Installer {
     fn finalize(&self) -> Result<()> {
         info!("[Phase 6/6] Finalizing installation (regenerating initramfs, unmounting)");
 
-        // Regenerate initramfs
-        self.cmd.run_in_chroot(INSTALL_ROOT, "mkinitcpio -P")?;
+        // Regenerate initramfs, after making sure the chosen kernel's
+        // preset will produce the image the bootloader expects
+        configure::mkinitcpio::verify_kernel_preset(&self.cmd, &self.config, &self.install_root)?;
+        self.cmd
+            .run_in_chroot(&self.install_root, "mkinitcpio -P")?;
+
+        // Baseline snapshot of the finished system
+        configure::snapper::create_baseline_snapshot(&self.cmd, &self.config, &self.install_root)?;
+
+        // Refuse to finish if the target would not boot
+        self.report_progress(0.98, "Verifying installation...");
+        let open_containers: Vec<&configure::encryption::LuksContainer> = self
+            .luks_containers
+            .iter()
+            .chain(self.luks_boot_container.iter())
+            .chain(self.luks_lvm_container.iter())
+            .collect();
+        super::selfcheck::check_bootability(
+            &self.cmd,
+            &self.config,
+            &open_containers,
+            &self.install_root,
+        )?;
+
+        // Second copy of the bootloader, once the ESP is complete
+        if let Some(ref mirror) = self.config.disk.mirror_device {
+            self.report_progress(0.985, "Installing bootloader on mirror disk...");
+            configure::bootloader::install_mirror_bootloader(
+                &self.cmd,
+                &self.config,
+                self.layout.as_ref().unwrap(),
+                mirror,
+                &self.install_root,
+            )?;
+        }
+
+        // Keep the command transcript with the system it built
+        if let Some(transcript) = self.cmd.transcript().filter(|_| !self.cmd.is_dry_run()) {
+            let dest = Path::new(&self.install_root)
+                .join("root")
+                .join(TRANSCRIPT_NAME);
+            match transcript.copy_to(&dest) {
+                Ok(()) => info!("Command transcript copied to /root/{}", TRANSCRIPT_NAME),
+                Err(e) => warn!("Failed to copy the command transcript: {}", e),
+            }
+        }
 
         // Unmount all partitions
-        unmount_all(&self.cmd, INSTALL_ROOT)?;
+        unmount_all(&self.cmd, &self.install_root)?;
 
         // Export ZFS pools if ZFS was used
         if self.config.disk.filesystem == crate::config::Filesystem::Zfs
             || self.config.disk.boot_filesystem == crate::config::Filesystem::Zfs
         {
-            crate::disk::formatting::export_zfs_pools(&self.cmd)?;
+            crate::disk::formatting::export_zfs_pools(
+                &self.cmd,
+                &self.config.disk,
+                &self.config.zfs.pool_name,
+            )?;
         }
 
         // Close LUKS boot container if opened (close before root volumes)
@@ -1050,9 +1696,38 @@ impl Installer {
             configure::encryption::close_luks(&self.cmd, &lvm_container.mapper_name)?;
         }
 
+        // Detach the image so it can be copied or booted in a VM
+        if let Some(ref image) = self.disk_image {
+            detach_loop(&self.cmd, &image.loop_device);
+            info!("Image written to {}", image.path);
+        }
+
         Ok(())
     }
 
+    /// Resolve the layout into the volumes to format and mount, with
+    /// opened LUKS mappings and LVM thin volumes applied.
+    fn resolve_volumes(&mut self) {
+        let layout = self.layout.as_ref().unwrap();
+        let mut volumes = VolumeSet::from_layout(layout, &self.config.disk.device);
+        volumes.apply_encryption(&self.luks_containers);
+        if let Some(ref boot_container) = self.luks_boot_container {
+            volumes.apply_boot_encryption(boot_container);
+        }
+        if self.config.disk.use_lvm_thin {
+            volumes.apply_lvm_thin(&self.config.disk.lvm_vg_name, &self.lvm_thin_volumes);
+        } else if self.config.disk.uses_luks() {
+            // Only the LUKS containers are formatted on this path
+            volumes.entries.retain(|e| e.encrypted);
+        }
+        self.volumes = Some(volumes);
+    }
+
+    /// The volumes set by [`Self::resolve_volumes`]
+    fn volumes(&self) -> &VolumeSet {
+        self.volumes.as_ref().unwrap()
+    }
+
     // ==================== MULTI-VOLUME ENCRYPTION METHODS ====================
 
     /// Setup multi-volume LUKS encryption (root, usr, var, home)
@@ -1081,6 +1756,7 @@ impl Installer {
             &self.config,
             &self.config.disk.device,
             &all_luks_parts,
+            &self.phase_progress(0.15, 0.18, "Setting up encryption..."),
         )?;
 
         self.luks_containers = containers;
@@ -1112,56 +1788,86 @@ impl Installer {
         Ok(())
     }
 
-    /// Format all partitions for multi-volume encrypted layout
-    fn format_multi_volume_partitions(&self) -> Result<()> {
-        info!("[Phase 2/6] Formatting multi-volume encrypted partitions");
+    /// Format the volumes of an encrypted or LVM thin install: each data
+    /// volume with the configured filesystem, then swap, /boot and the ESP
+    fn format_volumes(&self) -> Result<()> {
+        info!("[Phase 2/6] Formatting volumes");
 
         let layout = self.layout.as_ref().unwrap();
+        let volumes = self.volumes();
 
-        // Format each LUKS-mapped device with the configured filesystem
-        for container in &self.luks_containers {
+        for entry in &volumes.entries {
+            // LUKS volumes are labelled after their container ("Root"),
+            // thin volumes after their LV ("root")
+            let name = entry
+                .luks_container
+                .as_ref()
+                .map_or(entry.name.as_str(), |c| c.volume_name.as_str());
             format_partition(
                 &self.cmd,
-                &container.mapped_path,
+                &entry.device_path,
                 &self.config.disk.filesystem,
-                Some(&container.volume_name),
+                Some(self.config.disk.fs_label(name)),
+                self.rotational,
+                self.config.disk.btrfs_mixed,
+                f2fs_compression_enabled(&self.config.disk),
+                &self.config.filesystem_options,
             )?;
         }
 
-        // Format SWAP partition
-        let swap_part = layout.partitions.iter().find(|p| p.is_swap);
-        if let Some(swap) = swap_part {
-            let swap_device = partition_path(&self.config.disk.device, swap.number);
-            format_swap(&self.cmd, &swap_device, Some("SWAP"))?;
+        if let Some(ref swap_device) = volumes.swap {
+            format_swap(
+                &self.cmd,
+                swap_device,
+                Some(self.config.disk.fs_label("SWAP")),
+            )?;
         }
 
-        // Format BOOT partition with the configured boot filesystem
-        if let Some(ref boot_container) = self.luks_boot_container {
+        // Format BOOT with the configured boot filesystem; a mirror (never
+        // combined with encryption) gets a RAID1 btrfs across both disks
+        let boot_part = layout
+            .partitions
+            .iter()
+            .find(|p| p.is_boot_fs)
+            .ok_or_else(|| {
+                DeploytixError::ConfigError("No Boot partition found in layout".to_string())
+            })?;
+        if let Some(ref mirror) = self.config.disk.mirror_device {
+            let mirror_boot = partition_path(mirror, boot_part.number);
+            format_btrfs_raid1(
+                &self.cmd,
+                &[&volumes.boot.device_path, &mirror_boot],
+                self.config.disk.fs_label("BOOT"),
+                self.rotational,
+                Some(false),
+            )?;
+        } else {
             format_boot_partition(
                 &self.cmd,
-                &boot_container.mapped_path,
+                &volumes.boot.device_path,
                 &self.config.disk.boot_filesystem,
+                self.config.disk.fs_label("BOOT"),
+                self.rotational,
+                self.config.zfs.atime,
             )?;
-        } else {
-            let boot_part = layout
-                .partitions
-                .iter()
-                .find(|p| p.is_boot_fs)
-                .ok_or_else(|| {
-                    DeploytixError::ConfigError("No Boot partition found in layout".to_string())
-                })?;
-            let boot_device = partition_path(&self.config.disk.device, boot_part.number);
-            format_boot_partition(&self.cmd, &boot_device, &self.config.disk.boot_filesystem)?;
         }
 
-        // Format EFI partition as FAT32
-        let efi_part = layout.partitions.iter().find(|p| p.is_efi).ok_or_else(|| {
-            DeploytixError::ConfigError("No EFI partition found in layout".to_string())
-        })?;
-        let efi_device = partition_path(&self.config.disk.device, efi_part.number);
-        format_efi(&self.cmd, &efi_device)?;
+        // Format EFI partition as FAT32 (BIOS layouts have none, and an
+        // existing ESP is kept as it is)
+        let reuses_esp = self.config.disk.existing_esp.is_some();
+        if let Some(efi_part) = layout.partitions.iter().find(|p| p.is_efi && !reuses_esp) {
+            let mirror_efi = self
+                .config
+                .disk
+                .mirror_device
+                .as_ref()
+                .map(|mirror| partition_path(mirror, efi_part.number));
+            for efi_device in volumes.efi.iter().chain(mirror_efi.as_ref()) {
+                format_efi(&self.cmd, efi_device, self.config.disk.fs_label("EFI"))?;
+            }
+        }
 
-        info!("Multi-volume partitions formatted successfully");
+        info!("Volumes formatted successfully");
         Ok(())
     }
 
@@ -1169,65 +1875,30 @@ impl Installer {
     fn mount_multi_volume_partitions(&self) -> Result<()> {
         info!(
             "[Phase 2/6] Mounting multi-volume encrypted partitions to {}",
-            INSTALL_ROOT
+            &self.install_root
         );
 
         let layout = self.layout.as_ref().unwrap();
+        let volumes = self.volumes();
 
         if layout.uses_subvolumes() {
             self.mount_multi_volume_with_subvolumes()?;
+            mount_system_volumes(
+                &self.cmd,
+                volumes,
+                layout,
+                &self.install_root,
+                &self.config.disk.boot_filesystem,
+            )
         } else {
-            self.mount_multi_volume_plain()?;
-        }
-
-        // Mount BOOT partition
-        let boot_source = if let Some(ref boot_container) = self.luks_boot_container {
-            boot_container.mapped_path.clone()
-        } else {
-            let boot_part = layout
-                .partitions
-                .iter()
-                .find(|p| p.is_boot_fs)
-                .ok_or_else(|| {
-                    DeploytixError::ConfigError("No Boot partition found in layout".to_string())
-                })?;
-            partition_path(&self.config.disk.device, boot_part.number)
-        };
-
-        if self.config.disk.boot_filesystem == Filesystem::Btrfs {
-            mount_boot_btrfs_subvolume(&self.cmd, &boot_source, INSTALL_ROOT)?;
-        } else {
-            let boot_mount = format!("{}/boot", INSTALL_ROOT);
-            if !self.cmd.is_dry_run() {
-                fs::create_dir_all(&boot_mount)?;
-            }
-            self.cmd.run("mount", &[&boot_source, &boot_mount])?;
-            info!("Mounted {} to {}", boot_source, boot_mount);
-        }
-
-        // Mount EFI partition
-        let efi_part = layout.partitions.iter().find(|p| p.is_efi).ok_or_else(|| {
-            DeploytixError::ConfigError("No EFI partition found in layout".to_string())
-        })?;
-        let efi_device = partition_path(&self.config.disk.device, efi_part.number);
-        let efi_mount = format!("{}/boot/efi", INSTALL_ROOT);
-
-        if !self.cmd.is_dry_run() {
-            fs::create_dir_all(&efi_mount)?;
-        }
-        self.cmd.run("mount", &[&efi_device, &efi_mount])?;
-        info!("Mounted {} to {}", efi_device, efi_mount);
-
-        // Enable swap partitions
-        for part in &layout.partitions {
-            if part.is_swap {
-                let swap_device = partition_path(&self.config.disk.device, part.number);
-                info!("Enabling swap on {}", swap_device);
-                self.cmd.run("swapon", &[&swap_device])?;
-            }
+            mount_volume_set(
+                &self.cmd,
+                volumes,
+                layout,
+                &self.install_root,
+                &self.config.disk.boot_filesystem,
+            )
         }
-
-        Ok(())
     }
 
     /// Mount multi-volume encrypted partitions with btrfs subvolumes.
@@ -1235,10 +1906,11 @@ impl Installer {
     /// Creates subvolumes on each LUKS-mapped btrfs volume and mounts them:
     /// - Root: @ (→ /)
     /// - Usr:  @usr (→ /usr)
-    /// - Var:  @var (→ /var), @log (→ /var/log)
+    /// - Var:  @var (→ /var), @log (→ /var/log), with snapshots @pkg
     /// - Home: @home (→ /home)
     fn mount_multi_volume_with_subvolumes(&self) -> Result<()> {
-        let temp_mount = "/tmp/deploytix_btrfs_crypto";
+        let temp_path = crate::resources::runtime_path("btrfs_crypto");
+        let temp_mount = temp_path.to_string_lossy();
 
         // Root container must be mounted first
         let root_container = self
@@ -1247,18 +1919,19 @@ impl Installer {
             .find(|c| c.volume_name == "Root")
             .ok_or_else(|| DeploytixError::ConfigError("No Root container found".to_string()))?;
 
-        let root_svols = multi_volume_subvolumes("Root");
+        let layout = self.layout.as_ref().unwrap();
+        let root_svols = layout.container_subvolumes("Root");
         create_btrfs_subvolumes(
             &self.cmd,
             &root_container.mapped_path,
             &root_svols,
-            temp_mount,
+            &temp_mount,
         )?;
         mount_btrfs_subvolumes(
             &self.cmd,
             &root_container.mapped_path,
             &root_svols,
-            INSTALL_ROOT,
+            &self.install_root,
         )?;
 
         // Mount other encrypted volume subvolumes
@@ -1267,45 +1940,73 @@ impl Installer {
                 continue;
             }
 
-            let svols = multi_volume_subvolumes(&container.volume_name);
-            create_btrfs_subvolumes(&self.cmd, &container.mapped_path, &svols, temp_mount)?;
-            mount_btrfs_subvolumes(&self.cmd, &container.mapped_path, &svols, INSTALL_ROOT)?;
+            let svols = layout.container_subvolumes(&container.volume_name);
+            create_btrfs_subvolumes(&self.cmd, &container.mapped_path, &svols, &temp_mount)?;
+            mount_btrfs_subvolumes(
+                &self.cmd,
+                &container.mapped_path,
+                &svols,
+                &self.install_root,
+            )?;
         }
 
         Ok(())
     }
 
-    /// Mount multi-volume encrypted partitions without subvolumes (plain mount).
-    fn mount_multi_volume_plain(&self) -> Result<()> {
-        let root_container = self
-            .luks_containers
-            .iter()
-            .find(|c| c.volume_name == "Root")
-            .ok_or_else(|| DeploytixError::ConfigError("No Root container found".to_string()))?;
+    /// Enroll the FIDO2 security key into the data containers
+    fn enroll_fido2(&self) -> Result<()> {
+        info!("[Phase 2/6] Enrolling FIDO2 security key");
 
-        if !self.cmd.is_dry_run() {
-            fs::create_dir_all(INSTALL_ROOT)?;
-        }
-        self.cmd
-            .run("mount", &[&root_container.mapped_path, INSTALL_ROOT])?;
-        info!("Mounted {} to {}", root_container.mapped_path, INSTALL_ROOT);
+        let password = self
+            .config
+            .disk
+            .encryption_password
+            .as_ref()
+            .ok_or_else(|| {
+                DeploytixError::ValidationError(
+                    "Encryption password required for FIDO2 enrollment".to_string(),
+                )
+            })?;
+        configure::fido2::enroll_containers(&self.cmd, &self.luks_containers, password)
+    }
 
-        for container in &self.luks_containers {
-            if container.volume_name == "Root" {
-                continue;
-            }
+    /// Add the recovery passphrase to keyslot 1 of every container, if one
+    /// is configured.
+    fn enroll_recovery_passphrase(&self) -> Result<()> {
+        if self.config.disk.recovery_passphrase.is_disabled() {
+            return Ok(());
+        }
+        info!("[Phase 2/6] Adding recovery passphrase");
 
-            let mount_name = container.volume_name.to_lowercase();
-            let mount_point = format!("{}/{}", INSTALL_ROOT, mount_name);
+        let password = self
+            .config
+            .disk
+            .encryption_password
+            .as_ref()
+            .ok_or_else(|| {
+                DeploytixError::ValidationError(
+                    "Encryption password required for recovery passphrase".to_string(),
+                )
+            })?;
+        // Dry runs never prompt, so there may be nothing to enroll
+        let recovery = match &self.config.disk.recovery_passphrase {
+            RecoveryPassphrase::Value(value) => value.as_str(),
+            _ => "",
+        };
 
-            if !self.cmd.is_dry_run() {
-                fs::create_dir_all(&mount_point)?;
-            }
-            self.cmd
-                .run("mount", &[&container.mapped_path, &mount_point])?;
-            info!("Mounted {} to {}", container.mapped_path, mount_point);
+        for container in self
+            .luks_containers
+            .iter()
+            .chain(self.luks_boot_container.iter())
+            .chain(self.luks_lvm_container.iter())
+        {
+            configure::keyfiles::add_passphrase_to_luks(
+                &self.cmd,
+                &container.device,
+                password,
+                recovery,
+            )?;
         }
-
         Ok(())
     }
 
@@ -1330,32 +2031,19 @@ impl Installer {
             all_containers.push(boot_container.clone());
         }
 
-        let keyfiles =
-            setup_keyfiles_for_volumes(&self.cmd, &all_containers, password, INSTALL_ROOT)?;
+        let keyfiles = setup_keyfiles_for_volumes(
+            &self.cmd,
+            &all_containers,
+            password,
+            &self.config.disk,
+            &self.install_root,
+        )?;
 
         self.keyfiles = keyfiles;
         info!("Keyfiles created for {} volumes", all_containers.len());
         Ok(())
     }
 
-    /// Generate fstab for multi-volume encrypted system
-    fn generate_fstab_multi_volume(&self) -> Result<()> {
-        info!("[Phase 3/6] Generating /etc/fstab for multi-volume encrypted system");
-
-        let layout = self.layout.as_ref().unwrap();
-
-        generate_fstab_multi_volume(&MultiVolumeFstabParams {
-            cmd: &self.cmd,
-            containers: &self.luks_containers,
-            device: &self.config.disk.device,
-            layout,
-            filesystem: &self.config.disk.filesystem,
-            boot_filesystem: &self.config.disk.boot_filesystem,
-            swap_type: &self.config.disk.swap_type,
-            install_root: INSTALL_ROOT,
-        })
-    }
-
     /// Generate crypttab for multi-volume encrypted system
     fn generate_crypttab_multi_volume(&self) -> Result<()> {
         info!("[Phase 3/6] Generating /etc/crypttab for multi-volume encrypted system");
@@ -1366,7 +2054,8 @@ impl Installer {
             self.luks_boot_container.as_ref(),
             &self.keyfiles,
             self.config.disk.integrity,
-            INSTALL_ROOT,
+            self.config.disk.fido2_unlock,
+            &self.install_root,
         )
     }
 
@@ -1374,7 +2063,7 @@ impl Installer {
     fn install_custom_hooks(&self) -> Result<()> {
         let layout = self.layout.as_ref().unwrap();
 
-        configure::hooks::install_custom_hooks(&self.cmd, &self.config, layout, INSTALL_ROOT)
+        configure::hooks::install_custom_hooks(&self.cmd, &self.config, layout, &self.install_root)
     }
 
     // ==================== LVM THIN PROVISIONING METHODS ====================
@@ -1404,6 +2093,12 @@ impl Installer {
             })?;
 
         let lvm_device = partition_path(&self.config.disk.device, lvm_part.number);
+        let mirror_pv = self
+            .config
+            .disk
+            .mirror_device
+            .as_ref()
+            .map(|mirror| partition_path(mirror, lvm_part.number));
 
         // Setup LUKS encryption on LVM PV partition
         if self.config.disk.encryption {
@@ -1419,16 +2114,17 @@ impl Installer {
                 })?;
 
             let container = if self.config.disk.integrity {
-                self.report_progress(
-                    0.16,
-                    "Setting up encrypted LVM partition with dm-integrity...",
-                );
+                let status = "Setting up encrypted LVM partition with dm-integrity...";
+                self.report_progress(0.16, status);
                 configure::encryption::setup_single_luks_with_integrity(
                     &self.cmd,
                     &lvm_device,
                     password,
                     "Crypt-LVM",
                     "Lvm",
+                    self.config.disk.integrity_no_wipe,
+                    self.config.disk.luks_format_timeout_mins,
+                    &self.phase_progress(0.16, 0.21, status),
                 )?
             } else {
                 self.report_progress(0.16, "Setting up encrypted LVM partition...");
@@ -1438,45 +2134,39 @@ impl Installer {
                     password,
                     "Crypt-LVM",
                     "Lvm",
+                    self.config.disk.luks_format_timeout_mins,
                 )?
             };
 
             // Create PV on the LUKS container
             self.report_progress(0.18, "Creating LVM physical volume and volume group...");
             lvm::create_pv(&self.cmd, &container.mapped_path)?;
-            lvm::create_vg(&self.cmd, vg_name, &container.mapped_path)?;
+            lvm::create_vg(&self.cmd, vg_name, &[&container.mapped_path])?;
 
             self.luks_lvm_container = Some(container);
         } else {
             // Create PV directly on partition
             lvm::create_pv(&self.cmd, &lvm_device)?;
-            lvm::create_vg(&self.cmd, vg_name, &lvm_device)?;
+            let mut pvs = vec![lvm_device.as_str()];
+            if let Some(ref mirror_pv) = mirror_pv {
+                lvm::create_pv(&self.cmd, mirror_pv)?;
+                pvs.push(mirror_pv);
+            }
+            lvm::create_vg(&self.cmd, vg_name, &pvs)?;
         }
 
-        // Create thin pool
+        // Create thin pool; a mirror keeps it on the first disk until the
+        // second leg is added
         self.report_progress(0.19, "Creating LVM thin pool and volumes...");
-        lvm::create_thin_pool(&self.cmd, vg_name, pool_name, pool_percent)?;
+        let pool_pv = mirror_pv.as_ref().map(|_| lvm_device.as_str());
+        lvm::create_thin_pool(&self.cmd, vg_name, pool_name, pool_percent, pool_pv)?;
+        if let Some(ref mirror_pv) = mirror_pv {
+            lvm::mirror_thin_pool(&self.cmd, vg_name, pool_name, mirror_pv)?;
+        }
 
         // Create thin volumes from the layout's planned_thin_volumes (which
         // reflect the actual partitions that were collapsed into the LVM PV).
-        // Falls back to default_thin_volumes() for legacy layouts that don't
-        // populate planned_thin_volumes.
-        let thin_volumes = if let Some(ref planned) = layout.planned_thin_volumes {
-            if planned.is_empty() {
-                lvm::default_thin_volumes()
-            } else {
-                planned
-                    .iter()
-                    .map(|pv| lvm::ThinVolumeDef {
-                        name: pv.name.clone(),
-                        virtual_size: pv.virtual_size.clone(),
-                        mount_point: pv.mount_point.clone(),
-                    })
-                    .collect()
-            }
-        } else {
-            lvm::default_thin_volumes()
-        };
+        let thin_volumes = lvm::layout_thin_volumes(layout);
         lvm::create_all_thin_volumes(&self.cmd, vg_name, pool_name, &thin_volumes)?;
 
         // Activate VG to make LVs available
@@ -1512,67 +2202,12 @@ impl Installer {
         Ok(())
     }
 
-    /// Format LVM thin volumes as btrfs
-    fn format_lvm_volumes(&self) -> Result<()> {
-        info!("[Phase 2/6] Formatting LVM thin volumes");
-
-        let layout = self.layout.as_ref().unwrap();
-        let vg_name = &self.config.disk.lvm_vg_name;
-
-        // Format each thin volume with the configured filesystem
-        for vol in &self.lvm_thin_volumes {
-            let lv_device = lv_path(vg_name, &vol.name);
-            format_partition(
-                &self.cmd,
-                &lv_device,
-                &self.config.disk.filesystem,
-                Some(&vol.name),
-            )?;
-        }
-
-        // Format SWAP partition if present and using partition swap
-        if self.config.disk.swap_type == SwapType::Partition {
-            let swap_part = layout.partitions.iter().find(|p| p.is_swap);
-            if let Some(swap) = swap_part {
-                let swap_device = partition_path(&self.config.disk.device, swap.number);
-                format_swap(&self.cmd, &swap_device, Some("SWAP"))?;
-            }
-        }
-
-        // Format BOOT partition with the configured boot filesystem
-        if let Some(ref boot_container) = self.luks_boot_container {
-            // Encrypted boot: format the mapped device
-            format_boot_partition(
-                &self.cmd,
-                &boot_container.mapped_path,
-                &self.config.disk.boot_filesystem,
-            )?;
-        } else {
-            let boot_part = layout
-                .partitions
-                .iter()
-                .find(|p| p.is_boot_fs)
-                .ok_or_else(|| {
-                    DeploytixError::ConfigError("No Boot partition found in layout".to_string())
-                })?;
-            let boot_device = partition_path(&self.config.disk.device, boot_part.number);
-            format_boot_partition(&self.cmd, &boot_device, &self.config.disk.boot_filesystem)?;
-        }
-
-        // Format EFI partition as FAT32
-        let efi_part = layout.partitions.iter().find(|p| p.is_efi).ok_or_else(|| {
-            DeploytixError::ConfigError("No EFI partition found in layout".to_string())
-        })?;
-        let efi_device = partition_path(&self.config.disk.device, efi_part.number);
-        format_efi(&self.cmd, &efi_device)?;
-
-        info!("LVM thin volumes formatted successfully");
-        Ok(())
-    }
-
     /// Mount LVM thin volumes for installation
     fn mount_lvm_volumes(&self) -> Result<()> {
-        info!("[Phase 2/6] Mounting LVM thin volumes to {}", INSTALL_ROOT);
+        info!(
+            "[Phase 2/6] Mounting LVM thin volumes to {}",
+            &self.install_root
+        );
 
         let layout = self.layout.as_ref().unwrap();
         let vg_name = &self.config.disk.lvm_vg_name;
@@ -1581,105 +2216,30 @@ impl Installer {
         // This is a safety measure in case the VG wasn't properly activated
         lvm::scan_and_activate(&self.cmd)?;
 
-        // Mount root first (use lv_paths for logging both formats)
         let (root_device, root_mapper) = lvm::lv_paths(vg_name, "root");
         info!("Root LV paths: {} (or {})", root_device, root_mapper);
-        if !self.cmd.is_dry_run() {
-            fs::create_dir_all(INSTALL_ROOT)?;
-        }
-        self.cmd.run("mount", &[&root_device, INSTALL_ROOT])?;
-        info!("Mounted {} to {}", root_device, INSTALL_ROOT);
-
-        // Mount other volumes in order
-        for vol in &self.lvm_thin_volumes {
-            if vol.mount_point == "/" {
-                continue; // Already mounted
-            }
-
-            let lv_device = lv_path(vg_name, &vol.name);
-            let mount_point = format!("{}{}", INSTALL_ROOT, vol.mount_point);
-
-            if !self.cmd.is_dry_run() {
-                fs::create_dir_all(&mount_point)?;
-            }
-            self.cmd.run("mount", &[&lv_device, &mount_point])?;
-            info!("Mounted {} to {}", lv_device, mount_point);
-        }
-
-        // Mount BOOT partition
-        let boot_source = if let Some(ref boot_container) = self.luks_boot_container {
-            boot_container.mapped_path.clone()
-        } else {
-            let boot_part = layout
-                .partitions
-                .iter()
-                .find(|p| p.is_boot_fs)
-                .ok_or_else(|| {
-                    DeploytixError::ConfigError("No Boot partition found in layout".to_string())
-                })?;
-            partition_path(&self.config.disk.device, boot_part.number)
-        };
 
-        if self.config.disk.boot_filesystem == Filesystem::Btrfs {
-            mount_boot_btrfs_subvolume(&self.cmd, &boot_source, INSTALL_ROOT)?;
-        } else {
-            let boot_mount = format!("{}/boot", INSTALL_ROOT);
-            if !self.cmd.is_dry_run() {
-                fs::create_dir_all(&boot_mount)?;
-            }
-            self.cmd.run("mount", &[&boot_source, &boot_mount])?;
-            info!("Mounted {} to {}", boot_source, boot_mount);
-        }
-
-        // Mount EFI partition
-        let efi_part = layout.partitions.iter().find(|p| p.is_efi).ok_or_else(|| {
-            DeploytixError::ConfigError("No EFI partition found in layout".to_string())
-        })?;
-        let efi_device = partition_path(&self.config.disk.device, efi_part.number);
-        let efi_mount = format!("{}/boot/efi", INSTALL_ROOT);
-
-        if !self.cmd.is_dry_run() {
-            fs::create_dir_all(&efi_mount)?;
-        }
-        self.cmd.run("mount", &[&efi_device, &efi_mount])?;
-        info!("Mounted {} to {}", efi_device, efi_mount);
-
-        // Enable swap partitions
-        for part in &layout.partitions {
-            if part.is_swap {
-                let swap_device = partition_path(&self.config.disk.device, part.number);
-                info!("Enabling swap on {}", swap_device);
-                self.cmd.run("swapon", &[&swap_device])?;
-            }
-        }
-
-        Ok(())
+        mount_volume_set(
+            &self.cmd,
+            self.volumes(),
+            layout,
+            &self.install_root,
+            &self.config.disk.boot_filesystem,
+        )
     }
 
-    /// Generate fstab for LVM thin layout
-    fn generate_fstab_lvm_thin(&self) -> Result<()> {
-        info!("[Phase 3/6] Generating /etc/fstab for LVM thin layout");
-
-        let layout = self.layout.as_ref().unwrap();
-        let vg_name = &self.config.disk.lvm_vg_name;
-
-        // Pass encrypted boot mapped device path if boot encryption is enabled
-        let boot_mapped = self
-            .luks_boot_container
-            .as_ref()
-            .map(|c| c.mapped_path.as_str());
+    /// Generate fstab for a multi-volume encrypted or LVM thin system
+    fn generate_fstab_volumes(&self) -> Result<()> {
+        info!("[Phase 3/6] Generating /etc/fstab");
 
-        generate_fstab_lvm_thin(&LvmThinFstabParams {
+        generate_fstab_volumes(&VolumeFstabParams {
             cmd: &self.cmd,
-            vg_name,
-            thin_volumes: &self.lvm_thin_volumes,
-            device: &self.config.disk.device,
-            layout,
+            volumes: self.volumes(),
+            layout: self.layout.as_ref().unwrap(),
             filesystem: &self.config.disk.filesystem,
-            swap_type: &self.config.disk.swap_type,
-            boot_mapped_device: boot_mapped,
             boot_filesystem: &self.config.disk.boot_filesystem,
-            install_root: INSTALL_ROOT,
+            swap_type: &self.config.disk.swap_type,
+            install_root: &self.install_root,
         })
     }
 
@@ -1688,13 +2248,20 @@ impl Installer {
         if let Some(ref container) = self.luks_lvm_container {
             info!("[Phase 3/6] Generating /etc/crypttab for LVM LUKS container");
 
-            let crypttab_path = format!("{}/etc/crypttab", INSTALL_ROOT);
+            let crypttab_path = format!("{}/etc/crypttab", &self.install_root);
             if !self.cmd.is_dry_run() {
-                fs::create_dir_all(format!("{}/etc", INSTALL_ROOT))?;
+                fs::create_dir_all(format!("{}/etc", &self.install_root))?;
             }
 
-            // Get LUKS UUID
-            let luks_uuid = configure::encryption::get_luks_uuid(&container.device)?;
+            // Get LUKS UUID (the containers do not exist in a dry run)
+            let luks_uuid_of = |device: &str| {
+                if self.cmd.is_dry_run() {
+                    Ok("<LUKS_UUID>".to_string())
+                } else {
+                    configure::encryption::get_luks_uuid(device)
+                }
+            };
+            let luks_uuid = luks_uuid_of(&container.device)?;
 
             // Determine keyfile path for LVM container
             let lvm_keyfile = if !self.keyfiles.is_empty() {
@@ -1719,7 +2286,7 @@ impl Installer {
             // Add boot LUKS1 entry if boot encryption is enabled
             // Boot always uses discard (LUKS1 doesn't support integrity)
             if let Some(ref boot_container) = self.luks_boot_container {
-                let boot_uuid = configure::encryption::get_luks_uuid(&boot_container.device)?;
+                let boot_uuid = luks_uuid_of(&boot_container.device)?;
 
                 let boot_keyfile = self
                     .keyfiles
@@ -1734,9 +2301,14 @@ impl Installer {
                 ));
             }
 
-            if !self.cmd.is_dry_run() {
-                fs::write(&crypttab_path, content)?;
+            if self.cmd.is_dry_run() {
+                println!("  [dry-run] Would write {}:", crypttab_path);
+                for line in content.lines() {
+                    println!("    {}", line);
+                }
+                return Ok(());
             }
+            fs::write(&crypttab_path, content)?;
             info!("Crypttab written to {}", crypttab_path);
         }
         Ok(())
@@ -1781,7 +2353,8 @@ impl Installer {
             &self.cmd,
             &all_containers,
             password,
-            INSTALL_ROOT,
+            &self.config.disk,
+            &self.install_root,
         )?;
 
         self.keyfiles = keyfiles;
@@ -1802,7 +2375,7 @@ impl Installer {
         );
 
         // Use the unified configure_swap function
-        configure::swap::configure_swap(&self.cmd, &self.config, INSTALL_ROOT)
+        configure::swap::configure_swap(&self.cmd, &self.config, &self.install_root)
     }
 
     // ==================== SECUREBOOT METHODS ====================
@@ -1811,10 +2384,10 @@ impl Installer {
     fn setup_secureboot(&self) -> Result<()> {
         info!("[Phase 4/6] Setting up SecureBoot");
 
-        configure::secureboot::setup_secureboot(&self.cmd, &self.config, INSTALL_ROOT)?;
+        configure::secureboot::setup_secureboot(&self.cmd, &self.config, &self.install_root)?;
 
         // Sign boot files
-        configure::secureboot::sign_boot_files(&self.cmd, &self.config, INSTALL_ROOT)?;
+        configure::secureboot::sign_boot_files(&self.cmd, &self.config, &self.install_root)?;
 
         // Print enrollment instructions for user
         configure::secureboot::print_enrollment_instructions(&self.config);
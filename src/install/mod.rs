@@ -3,8 +3,14 @@
 mod basestrap;
 mod chroot;
 pub mod crypttab;
+pub mod drift;
+pub mod eta;
 mod fstab;
+pub mod import;
 mod installer;
+pub mod preflight;
+pub mod rescue;
+pub mod selfcheck;
 
 pub use basestrap::*;
 pub use chroot::*;
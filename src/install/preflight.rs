@@ -0,0 +1,118 @@
+//! Pre-flight checks run after validation, before anything is written
+//!
+//! [`DeploymentConfig::validate`] checks that a configuration is coherent;
+//! these checks look at the machine it is about to run on: the target disk
+//! must hold the configured layout and scratch files need a writable
+//! runtime directory.  Every problem is collected so they can all be fixed
+//! in one go.  Low memory for LUKS2 key derivation is only a warning:
+//! cryptsetup lowers the argon2id memory cost to what the machine has.
+
+use crate::config::DeploymentConfig;
+use crate::disk::detection::{available_ram_mib, get_device_info};
+use crate::disk::layouts::compute_layout_from_config;
+use crate::utils::error::{DeploytixError, Result};
+use tracing::warn;
+
+/// Memory cryptsetup's argon2id uses for LUKS2 key derivation at most
+/// (its default cost, 1 GiB).
+pub const ARGON2_MEMORY_MIB: u64 = 1024;
+
+/// Disk size check: the layout must fit on `disk_mib`.
+fn check_disk_size(config: &DeploymentConfig, disk_mib: u64) -> Option<String> {
+    match compute_layout_from_config(&config.disk, disk_mib) {
+        Ok(_) => None,
+        Err(DeploytixError::DiskTooSmall {
+            size_mib,
+            required_mib,
+        }) => Some(format!(
+            "{} holds {} MiB but the configured layout needs at least {} MiB",
+            config.disk.device, size_mib, required_mib
+        )),
+        Err(e) => Some(format!("partition layout: {}", e)),
+    }
+}
+
+/// RAM check: with less than argon2id's default memory cost available,
+/// cryptsetup derives LUKS2 keys with a weaker, smaller cost.
+fn check_ram(config: &DeploymentConfig, available_mib: Option<u64>) -> Option<String> {
+    if !config.disk.uses_luks() {
+        return None;
+    }
+    match available_mib {
+        Some(mib) if mib < ARGON2_MEMORY_MIB => Some(format!(
+            "only {} MiB RAM available; LUKS2 key derivation (argon2id) will use less than \
+             its default {} MiB, which makes the passphrase cheaper to brute-force",
+            mib, ARGON2_MEMORY_MIB
+        )),
+        _ => None,
+    }
+}
+
+/// Pre-flight findings for `config` on this machine that do not stop the
+/// install.
+pub fn preflight_warnings(config: &DeploymentConfig) -> Vec<String> {
+    check_ram(config, available_ram_mib()).into_iter().collect()
+}
+
+/// Every pre-flight problem found for `config` on this machine.
+pub fn preflight_issues(config: &DeploymentConfig) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    match get_device_info(&config.disk.device) {
+        Ok(info) => issues.extend(check_disk_size(config, info.size_mib())),
+        Err(e) => issues.push(format!("{}: {}", config.disk.device, e)),
+    }
+
+    if let Err(e) = crate::resources::ensure_runtime_dir(None) {
+        issues.push(e.to_string());
+    }
+
+    issues
+}
+
+impl DeploymentConfig {
+    /// Run the pre-flight checks, failing with all issues at once.
+    /// Warnings are logged.
+    pub fn preflight(&self) -> Result<()> {
+        for warning in preflight_warnings(self) {
+            warn!("Pre-flight: {}", warning);
+        }
+        let issues = preflight_issues(self);
+        if issues.is_empty() {
+            return Ok(());
+        }
+        Err(DeploytixError::PreflightFailed(
+            issues
+                .iter()
+                .map(|i| format!("\n  - {}", i))
+                .collect::<String>(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Firmware;
+
+    #[test]
+    fn small_disk_is_reported() {
+        let mut config = DeploymentConfig::sample();
+        config.disk.firmware = Some(Firmware::Uefi);
+        let issue = check_disk_size(&config, 2048).unwrap();
+        assert!(issue.contains("holds 2048 MiB"), "{}", issue);
+        assert!(check_disk_size(&config, 512 * 1024).is_none());
+    }
+
+    #[test]
+    fn ram_is_only_checked_for_luks() {
+        let mut config = DeploymentConfig::sample();
+        config.disk.encryption = true;
+        assert!(check_ram(&config, Some(512)).is_some());
+        assert!(check_ram(&config, Some(4096)).is_none());
+        assert!(check_ram(&config, None).is_none());
+
+        config.disk.encryption = false;
+        assert!(check_ram(&config, Some(512)).is_none());
+    }
+}
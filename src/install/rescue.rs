@@ -0,0 +1,365 @@
+//! Interactive chroot into an installed system
+//!
+//! Re-opens the storage stack the installer built from the same
+//! configuration (LUKS containers, the LVM volume group, ZFS pools),
+//! mounts the installed system at [`DEFAULT_INSTALL_ROOT`] without creating
+//! anything, and runs a shell in it (or the post-install verification).
+//! Everything is unmounted and closed again afterwards, whether or not it
+//! succeeded.
+
+use super::chroot::{
+    mount_data_volume, mount_data_volumes, partition_subvolumes, remount_system_volumes,
+    unmount_all,
+};
+use super::installer::DEFAULT_INSTALL_ROOT;
+use super::selfcheck::check_bootability;
+use crate::config::{DeploymentConfig, Filesystem};
+use crate::configure::encryption::{
+    force_close_luks, reopen_luks, reopen_multi_volume, LuksContainer,
+};
+use crate::disk::detection::{get_device_info, partition_path};
+use crate::disk::formatting::{
+    export_zfs_pools, load_zfs_key, mount_btrfs_subvolumes, mount_zfs_datasets, ZFS_BPOOL_NAME,
+};
+use crate::disk::layouts::{compute_layout_from_config, get_luks_partitions, ComputedLayout};
+use crate::disk::lvm;
+use crate::disk::volumes::VolumeSet;
+use crate::utils::command::{command_exists, CommandRunner};
+use crate::utils::error::{DeploytixError, Result};
+use crate::utils::signal;
+use std::process::Command;
+use tracing::{info, warn};
+
+/// An installed system opened and mounted for a chroot session.
+struct RescueSession {
+    cmd: CommandRunner,
+    config: DeploymentConfig,
+    layout: ComputedLayout,
+    luks_containers: Vec<LuksContainer>,
+    luks_lvm_container: Option<LuksContainer>,
+    luks_boot_container: Option<LuksContainer>,
+    zfs_imported: bool,
+}
+
+/// Open and mount the system installed on `config.disk.device`, run an
+/// interactive shell in it, then unmount and close everything.
+///
+/// `config` must describe the layout the system was installed with.
+pub fn enter_installed_system(config: DeploymentConfig, dry_run: bool) -> Result<()> {
+    let mut session = RescueSession::new(config, dry_run)?;
+
+    // Ctrl-C in the shell must not kill us before the teardown
+    signal::install_signal_handlers();
+
+    let result = session
+        .open()
+        .and_then(|volumes| session.mount(&volumes))
+        .and_then(|()| session.run_shell());
+    session.close();
+    result
+}
+
+/// Open and mount the system installed on `config.disk.device` and run the
+/// post-install verification on it (see [`check_bootability`]).
+pub fn verify_installed_system(config: DeploymentConfig, dry_run: bool) -> Result<()> {
+    let mut session = RescueSession::new(config, dry_run)?;
+    signal::install_signal_handlers();
+
+    let result = session
+        .open()
+        .and_then(|volumes| session.mount(&volumes))
+        .and_then(|()| {
+            let containers: Vec<&LuksContainer> = session
+                .luks_containers
+                .iter()
+                .chain(session.luks_boot_container.iter())
+                .chain(session.luks_lvm_container.iter())
+                .collect();
+            check_bootability(
+                &session.cmd,
+                &session.config,
+                &containers,
+                DEFAULT_INSTALL_ROOT,
+            )
+        });
+    session.close();
+    result
+}
+
+impl RescueSession {
+    fn new(mut config: DeploymentConfig, dry_run: bool) -> Result<Self> {
+        let device_info = get_device_info(&config.disk.device)?;
+        config.disk.firmware = Some(config.disk.firmware());
+        let layout = compute_layout_from_config(&config.disk, device_info.size_mib())?;
+        Ok(Self {
+            cmd: CommandRunner::new(dry_run),
+            config,
+            layout,
+            luks_containers: Vec::new(),
+            luks_lvm_container: None,
+            luks_boot_container: None,
+            zfs_imported: false,
+        })
+    }
+
+    /// Open LUKS containers, activate LVM and import ZFS pools, returning
+    /// the volumes to mount.  What was opened before a failure is recorded
+    /// on the session, so [`Self::close`] releases it.
+    fn open(&mut self) -> Result<VolumeSet> {
+        let disk = &self.config.disk;
+        let device = &disk.device;
+        info!("Opening the system installed on {}", device);
+
+        if disk.uses_luks() {
+            let password = disk.encryption_password.clone().ok_or_else(|| {
+                DeploytixError::ValidationError("Encryption password required".to_string())
+            })?;
+
+            if disk.use_lvm_thin {
+                let lvm_part = self
+                    .layout
+                    .partitions
+                    .iter()
+                    .find(|p| {
+                        p.mount_point.as_deref() == Some("/")
+                            || p.name.to_lowercase().contains("lvm")
+                    })
+                    .ok_or_else(|| {
+                        DeploytixError::ConfigError(
+                            "No LVM PV partition found in layout".to_string(),
+                        )
+                    })?;
+                self.luks_lvm_container = Some(reopen_luks(
+                    &self.cmd,
+                    &partition_path(device, lvm_part.number),
+                    "Crypt-LVM",
+                    "Lvm",
+                    &password,
+                )?);
+            } else {
+                let luks_parts: Vec<(u32, &str)> = get_luks_partitions(&self.layout)
+                    .iter()
+                    .map(|p| (p.number, p.name.as_str()))
+                    .collect();
+                self.luks_containers =
+                    reopen_multi_volume(&self.cmd, device, &luks_parts, &password)?;
+            }
+
+            if disk.boot_encryption {
+                let boot_part = self
+                    .layout
+                    .partitions
+                    .iter()
+                    .find(|p| p.is_boot_fs)
+                    .ok_or_else(|| {
+                        DeploytixError::ConfigError("No Boot partition found in layout".to_string())
+                    })?;
+                let mapper = &disk.luks_boot_mapper_name;
+                self.luks_boot_container = Some(reopen_luks(
+                    &self.cmd,
+                    &partition_path(device, boot_part.number),
+                    mapper,
+                    mapper.trim_start_matches("Crypt-"),
+                    &password,
+                )?);
+            }
+        }
+
+        if disk.use_lvm_thin {
+            lvm::activate_vg(&self.cmd, &disk.lvm_vg_name)?;
+        }
+
+        if disk.filesystem == Filesystem::Zfs || disk.boot_filesystem == Filesystem::Zfs {
+            self.zfs_imported = true;
+            if disk.filesystem == Filesystem::Zfs {
+                let pool = &self.config.zfs.pool_name;
+                self.import_pool(pool)?;
+                if disk.uses_zfs_encryption() {
+                    let password = disk.encryption_password.as_deref().ok_or_else(|| {
+                        DeploytixError::ValidationError("Encryption password required".to_string())
+                    })?;
+                    load_zfs_key(&self.cmd, pool, password)?;
+                }
+            }
+            if disk.boot_filesystem == Filesystem::Zfs {
+                self.import_pool(ZFS_BPOOL_NAME)?;
+            }
+        }
+
+        let mut volumes = VolumeSet::from_layout(&self.layout, device);
+        volumes.apply_encryption(&self.luks_containers);
+        if let Some(ref boot_container) = self.luks_boot_container {
+            volumes.apply_boot_encryption(boot_container);
+        }
+        if disk.use_lvm_thin {
+            volumes.apply_lvm_thin(&disk.lvm_vg_name, &lvm::layout_thin_volumes(&self.layout));
+        }
+        Ok(volumes)
+    }
+
+    /// Import a pool without mounting; its datasets use legacy mountpoints.
+    /// `-f` because the pool was last imported by the installed system.
+    fn import_pool(&self, pool: &str) -> Result<()> {
+        self.cmd
+            .run("zpool", &["import", "-f", "-N", pool])
+            .map(|_| ())
+            .map_err(|e| {
+                DeploytixError::FilesystemError(format!(
+                    "Failed to import ZFS pool {}: {}",
+                    pool, e
+                ))
+            })
+    }
+
+    /// Mount the installed system the way the installer laid it out.
+    fn mount(&self, volumes: &VolumeSet) -> Result<()> {
+        let disk = &self.config.disk;
+        let layout = &self.layout;
+        info!("Mounting the installed system to {}", DEFAULT_INSTALL_ROOT);
+
+        if !self.cmd.is_dry_run() {
+            std::fs::create_dir_all(DEFAULT_INSTALL_ROOT)?;
+        }
+
+        if disk.use_lvm_thin {
+            mount_data_volumes(&self.cmd, volumes, layout, DEFAULT_INSTALL_ROOT)?;
+        } else if !self.luks_containers.is_empty() {
+            if layout.uses_subvolumes() {
+                // Root first: the other volumes mount inside it
+                let mut containers: Vec<&LuksContainer> = self.luks_containers.iter().collect();
+                containers.sort_by_key(|c| c.volume_name != "Root");
+                for container in containers {
+                    let svols = layout.container_subvolumes(&container.volume_name);
+                    mount_btrfs_subvolumes(
+                        &self.cmd,
+                        &container.mapped_path,
+                        &svols,
+                        DEFAULT_INSTALL_ROOT,
+                    )?;
+                }
+            } else {
+                let mut volumes = volumes.clone();
+                volumes.entries.retain(|e| e.encrypted);
+                mount_data_volumes(&self.cmd, &volumes, layout, DEFAULT_INSTALL_ROOT)?;
+            }
+        } else if disk.filesystem == Filesystem::Zfs {
+            mount_zfs_datasets(&self.cmd, &self.config.zfs, DEFAULT_INSTALL_ROOT)?;
+        } else if layout.uses_subvolumes() {
+            self.mount_subvolume_partitions(volumes)?;
+        } else {
+            mount_data_volumes(&self.cmd, volumes, layout, DEFAULT_INSTALL_ROOT)?;
+        }
+
+        remount_system_volumes(
+            &self.cmd,
+            volumes,
+            layout,
+            DEFAULT_INSTALL_ROOT,
+            &disk.boot_filesystem,
+        )
+    }
+
+    /// Mount a plain btrfs subvolume layout: the ROOT partition's
+    /// subvolumes, then the remaining data partitions.
+    fn mount_subvolume_partitions(&self, volumes: &VolumeSet) -> Result<()> {
+        let layout = &self.layout;
+        for entry in volumes.entries_mount_order() {
+            if entry.name == "ROOT" {
+                if let Some(ref subvolumes) = layout.subvolumes {
+                    mount_btrfs_subvolumes(
+                        &self.cmd,
+                        &entry.device_path,
+                        subvolumes,
+                        DEFAULT_INSTALL_ROOT,
+                    )?;
+                }
+                continue;
+            }
+
+            let subvol_name = layout
+                .partitions
+                .iter()
+                .find(|p| p.name == entry.name)
+                .and_then(|p| p.subvolume_name.as_deref());
+            match subvol_name {
+                Some(name) => {
+                    let svols = partition_subvolumes(layout, name, &entry.mount_point);
+                    mount_btrfs_subvolumes(
+                        &self.cmd,
+                        &entry.device_path,
+                        &svols,
+                        DEFAULT_INSTALL_ROOT,
+                    )?;
+                }
+                None => {
+                    let target = format!("{}{}", DEFAULT_INSTALL_ROOT, entry.mount_point);
+                    if !self.cmd.is_dry_run() {
+                        std::fs::create_dir_all(&target)?;
+                    }
+                    mount_data_volume(&self.cmd, &entry.device_path, &target, &entry.name, layout)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run an interactive shell in the mounted system.
+    fn run_shell(&self) -> Result<()> {
+        let program = if command_exists("artix-chroot") {
+            "artix-chroot"
+        } else {
+            "chroot"
+        };
+
+        if self.cmd.is_dry_run() {
+            println!("  [dry-run] {} {}", program, DEFAULT_INSTALL_ROOT);
+            return Ok(());
+        }
+
+        println!(
+            "Entering {} (installed on {}). Exit the shell to unmount.",
+            DEFAULT_INSTALL_ROOT, self.config.disk.device
+        );
+        let status = Command::new(program)
+            .arg(DEFAULT_INSTALL_ROOT)
+            .status()
+            .map_err(|e| DeploytixError::CommandFailed {
+                command: program.to_string(),
+                stderr: e.to_string(),
+            })?;
+        if !status.success() {
+            // The last command in the shell failed; not an error of ours
+            warn!("Chroot shell exited with {}", status);
+        }
+        Ok(())
+    }
+
+    /// Unmount everything and close what [`Self::open`] opened.  Best
+    /// effort: every step runs even if an earlier one fails.
+    fn close(&self) {
+        info!("Closing the installed system");
+
+        if let Err(e) = unmount_all(&self.cmd, DEFAULT_INSTALL_ROOT) {
+            warn!("Failed to unmount {}: {}", DEFAULT_INSTALL_ROOT, e);
+        }
+        if self.zfs_imported {
+            let _ = export_zfs_pools(&self.cmd, &self.config.disk, &self.config.zfs.pool_name);
+        }
+        // Like the installer's emergency cleanup, these run even after
+        // Ctrl-C, and one failure does not stop the rest.
+        if self.config.disk.use_lvm_thin {
+            let vg_name = &self.config.disk.lvm_vg_name;
+            if let Err(e) = self.cmd.force_run("vgchange", &["-an", vg_name]) {
+                warn!("Failed to deactivate VG {}: {}", vg_name, e);
+            }
+        }
+        let containers: Vec<LuksContainer> = self
+            .luks_containers
+            .iter()
+            .chain(self.luks_lvm_container.iter())
+            .chain(self.luks_boot_container.iter())
+            .cloned()
+            .collect();
+        force_close_luks(&self.cmd, &containers);
+    }
+}
@@ -0,0 +1,483 @@
+//! Bootability self-check run at the end of the installation
+//!
+//! Catches silent failures (missing initramfs, bootloader not installed,
+//! malformed fstab, fstab or crypttab pointing at devices that do not
+//! exist, a missing user account) before the target is unmounted, so the
+//! install fails instead of leaving an unbootable system behind.  Also run
+//! on demand by `deploytix verify`.
+
+use crate::config::{Bootloader, DeploymentConfig, Firmware};
+use crate::configure::encryption::{get_luks_uuid, LuksContainer};
+use crate::utils::command::CommandRunner;
+use crate::utils::error::{DeploytixError, Result};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tracing::info;
+
+/// GRUB core image written by `grub-install --target=i386-pc` on BIOS.
+const BIOS_CORE_IMG: &str = "/boot/grub/i386-pc/core.img";
+
+/// GRUB configuration generated by `grub-mkconfig`.
+const GRUB_CFG: &str = "/boot/grub/grub.cfg";
+
+/// rEFInd kernel parameters written next to the kernels.
+const REFIND_LINUX_CONF: &str = "/boot/refind_linux.conf";
+
+/// Files that must exist (and be non-empty) on the target for it to boot.
+fn required_boot_files(config: &DeploymentConfig) -> Vec<String> {
+    let disk = &config.disk;
+    let loader = match disk.firmware() {
+        Firmware::Uefi => format!(
+            "{}/{}",
+            disk.esp_mountpoint.path(),
+            crate::configure::bootloader::efi_loader(config)
+        ),
+        Firmware::Bios => BIOS_CORE_IMG.to_string(),
+    };
+    let loader_config = match config.system.bootloader {
+        Bootloader::Grub => GRUB_CFG,
+        Bootloader::Refind => REFIND_LINUX_CONF,
+    };
+    vec![
+        loader,
+        loader_config.to_string(),
+        config.system.kernel.vmlinuz(),
+        config.system.kernel.initramfs(),
+    ]
+    .into_iter()
+    .chain(
+        crate::configure::microcode::early_initrd_images(config)
+            .into_iter()
+            .map(|img| format!("/boot/{}", img)),
+    )
+    .collect()
+}
+
+/// Check fstab content for structural problems.
+///
+/// Every entry must have six fields with numeric dump/pass columns, mount
+/// points must be absolute (or `none` for swap), and exactly one entry must
+/// mount `/`.
+pub fn check_fstab(content: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut root_entries = 0;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 6 {
+            problems.push(format!(
+                "fstab line {}: expected 6 fields, found {}",
+                idx + 1,
+                fields.len()
+            ));
+            continue;
+        }
+
+        let mount_point = fields[1];
+        if mount_point != "none" && !mount_point.starts_with('/') {
+            problems.push(format!(
+                "fstab line {}: invalid mount point '{}'",
+                idx + 1,
+                mount_point
+            ));
+        }
+        if fields[4].parse::<u8>().is_err() || fields[5].parse::<u8>().is_err() {
+            problems.push(format!("fstab line {}: dump/pass must be numeric", idx + 1));
+        }
+        if mount_point == "/" {
+            root_entries += 1;
+        }
+    }
+
+    match root_entries {
+        1 => {}
+        0 => problems.push("fstab has no entry for /".to_string()),
+        n => problems.push(format!("fstab has {} entries for /", n)),
+    }
+
+    problems
+}
+
+/// Device path an fstab/crypttab source refers to: the `/dev/disk/by-*`
+/// link for `UUID=`, `PARTUUID=`, `LABEL=` and `PARTLABEL=`, the source
+/// itself for `/dev/` paths, and `None` for anything else (tmpfs, swap
+/// files, ...).
+fn source_path(source: &str) -> Option<String> {
+    let (key, value) = match source.split_once('=') {
+        Some(pair) => pair,
+        None => return source.starts_with("/dev/").then(|| source.to_string()),
+    };
+    let dir = match key {
+        "UUID" => "by-uuid",
+        "PARTUUID" => "by-partuuid",
+        "LABEL" => "by-label",
+        "PARTLABEL" => "by-partlabel",
+        _ => return None,
+    };
+    Some(format!("/dev/disk/{}/{}", dir, value.trim_matches('"')))
+}
+
+/// Check that every device-backed fstab source resolves, using `exists`
+/// to test the device path.
+pub fn check_fstab_sources(content: &str, exists: impl Fn(&str) -> bool) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            let source = fields.next()?;
+            let mount_point = fields.next().unwrap_or("none");
+            let path = source_path(source)?;
+            (!exists(&path)).then(|| {
+                format!(
+                    "fstab source {} for {} does not resolve to a device",
+                    source, mount_point
+                )
+            })
+        })
+        .collect()
+}
+
+/// Check that every crypttab entry names an existing LUKS device.
+/// Entries with the `plain` option are only checked for existence.
+pub fn check_crypttab_devices(
+    content: &str,
+    exists: impl Fn(&str) -> bool,
+    is_luks: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(name), Some(source)) = (fields.first(), fields.get(1)) else {
+            continue;
+        };
+        let Some(path) = source_path(source) else {
+            problems.push(format!(
+                "crypttab entry {}: unsupported device {}",
+                name, source
+            ));
+            continue;
+        };
+        let plain = fields
+            .get(3)
+            .is_some_and(|opts| opts.split(',').any(|o| o == "plain"));
+        if !exists(&path) {
+            problems.push(format!("crypttab entry {}: {} not found", name, source));
+        } else if !plain && !is_luks(&path) {
+            problems.push(format!(
+                "crypttab entry {}: {} is not a LUKS device",
+                name, source
+            ));
+        }
+    }
+    problems
+}
+
+/// Check that `user` has an entry in passwd `content`.
+pub fn check_user(content: &str, user: &str) -> Option<String> {
+    let found = content.lines().any(|l| l.split(':').next() == Some(user));
+    (!found).then(|| format!("user {} missing from /etc/passwd", user))
+}
+
+/// Check that `user` is a member of each of `groups` in group `content`.
+pub fn check_groups(content: &str, user: &str, groups: &[&str]) -> Vec<String> {
+    groups
+        .iter()
+        .filter(|group| {
+            !content.lines().any(|l| {
+                let mut fields = l.split(':');
+                fields.next() == Some(**group)
+                    && fields
+                        .nth(2)
+                        .is_some_and(|members| members.split(',').any(|m| m == user))
+            })
+        })
+        .map(|group| format!("user {} is not in group {}", user, group))
+        .collect()
+}
+
+/// Whether `cryptsetup isLuks` accepts `path`.
+fn is_luks_device(path: &str) -> bool {
+    Command::new("cryptsetup")
+        .args(["isLuks", path])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Check that every opened LUKS container is referenced in crypttab by its
+/// LUKS UUID.  `containers` pairs each mapper name with its UUID.
+pub fn check_crypttab(content: &str, containers: &[(String, String)]) -> Vec<String> {
+    let referenced: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_whitespace().nth(1))
+        .collect();
+
+    containers
+        .iter()
+        .filter(|(_, uuid)| !referenced.contains(&format!("UUID={}", uuid).as_str()))
+        .map(|(name, uuid)| {
+            format!(
+                "crypttab does not reference open container {} (UUID={})",
+                name, uuid
+            )
+        })
+        .collect()
+}
+
+/// Verify the installed system is bootable before it is unmounted.
+///
+/// Checks the EFI loader (or BIOS core image), bootloader config, kernel and
+/// initramfs, fstab syntax and devices, crypttab devices and (when
+/// encryption is used) that crypttab covers every opened LUKS container,
+/// the user account and, with a desktop, the seat manager and the user's
+/// seat groups.  Returns an error listing every failed check.
+pub fn check_bootability(
+    cmd: &CommandRunner,
+    config: &DeploymentConfig,
+    containers: &[&LuksContainer],
+    install_root: &str,
+) -> Result<()> {
+    info!("Running bootability self-check on {}", install_root);
+
+    if cmd.is_dry_run() {
+        println!("  [dry-run] Would verify bootability:");
+        for file in required_boot_files(config) {
+            println!("    {} exists", file);
+        }
+        println!("    /etc/fstab parses, mounts / and its devices exist");
+        println!("    /etc/crypttab devices are LUKS devices");
+        for c in containers {
+            println!("    /etc/crypttab references {}", c.mapper_name);
+        }
+        println!("    user {} is in /etc/passwd", config.user.name);
+        if let Some(seat) = crate::configure::services::seat_manager(config) {
+            println!("    seat manager {} is installed", seat);
+            println!(
+                "    user {} is in groups {}",
+                config.user.name,
+                crate::configure::users::SEAT_GROUPS.join(", ")
+            );
+        }
+        return Ok(());
+    }
+
+    let mut problems = Vec::new();
+
+    for file in required_boot_files(config) {
+        let path = format!("{}{}", install_root, file);
+        match fs::metadata(&path) {
+            Ok(m) if m.len() > 0 => {}
+            Ok(_) => problems.push(format!("{} is empty", file)),
+            Err(_) => problems.push(format!(
+                "{} missing",
+                Path::new(&file)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| file.clone())
+            )),
+        }
+    }
+
+    match fs::read_to_string(format!("{}/etc/fstab", install_root)) {
+        Ok(content) => {
+            problems.extend(check_fstab(&content));
+            problems.extend(check_fstab_sources(&content, |p| Path::new(p).exists()));
+        }
+        Err(_) => problems.push("/etc/fstab missing".to_string()),
+    }
+
+    if let Ok(content) = fs::read_to_string(format!("{}/etc/crypttab", install_root)) {
+        problems.extend(check_crypttab_devices(
+            &content,
+            |p| Path::new(p).exists(),
+            is_luks_device,
+        ));
+    }
+
+    if !containers.is_empty() {
+        let mut expected = Vec::new();
+        for c in containers {
+            expected.push((c.mapper_name.clone(), get_luks_uuid(&c.device)?));
+        }
+        match fs::read_to_string(format!("{}/etc/crypttab", install_root)) {
+            Ok(content) => problems.extend(check_crypttab(&content, &expected)),
+            Err(_) => problems.push("/etc/crypttab missing".to_string()),
+        }
+    }
+
+    match fs::read_to_string(format!("{}/etc/passwd", install_root)) {
+        Ok(content) => problems.extend(check_user(&content, &config.user.name)),
+        Err(_) => problems.push("/etc/passwd missing".to_string()),
+    }
+
+    // Without a seat manager and seat access the desktop never starts
+    if let Some(seat) = crate::configure::services::seat_manager(config) {
+        let init = &config.system.init;
+        if !crate::configure::services::service_installed(init, seat, install_root) {
+            problems.push(format!(
+                "seat manager {} has no {} service; the desktop will not start",
+                seat, init
+            ));
+        }
+        match fs::read_to_string(format!("{}/etc/group", install_root)) {
+            Ok(content) => problems.extend(check_groups(
+                &content,
+                &config.user.name,
+                crate::configure::users::SEAT_GROUPS,
+            )),
+            Err(_) => problems.push("/etc/group missing".to_string()),
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(DeploytixError::BootCheckFailed(problems.join("; ")));
+    }
+
+    info!("Bootability self-check passed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD_FSTAB: &str = "# /etc/fstab\n\
+        UUID=aaaa  /  btrfs  subvol=@,defaults,noatime  0  0\n\
+        UUID=bbbb  /boot/efi  vfat  umask=0077,defaults  0  0\n\
+        UUID=cccc  none  swap  defaults  0  0\n";
+
+    #[test]
+    fn valid_fstab_has_no_problems() {
+        assert!(check_fstab(GOOD_FSTAB).is_empty());
+    }
+
+    #[test]
+    fn fstab_without_root_is_rejected() {
+        let problems = check_fstab("UUID=bbbb  /boot/efi  vfat  defaults  0  0\n");
+        assert_eq!(problems, vec!["fstab has no entry for /".to_string()]);
+    }
+
+    #[test]
+    fn malformed_fstab_lines_are_reported() {
+        let fstab = format!(
+            "{}UUID=dddd  /home  ext4\nUUID=eeee  home  ext4  defaults  0  x\n",
+            GOOD_FSTAB
+        );
+        let problems = check_fstab(&fstab);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+    }
+
+    #[test]
+    fn crypttab_must_cover_open_containers() {
+        let crypttab = "# <name> <device> <keyfile> <options>\n\
+                        Root    UUID=1111    none    luks\n";
+        let open = vec![
+            ("Crypt-Root".to_string(), "1111".to_string()),
+            ("Crypt-Home".to_string(), "2222".to_string()),
+        ];
+        let problems = check_crypttab(crypttab, &open);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("Crypt-Home"));
+    }
+
+    #[test]
+    fn fstab_sources_must_resolve() {
+        let exists = |p: &str| p == "/dev/disk/by-uuid/aaaa" || p == "/dev/disk/by-uuid/bbbb";
+        let problems = check_fstab_sources(GOOD_FSTAB, exists);
+        assert_eq!(
+            problems,
+            vec!["fstab source UUID=cccc for none does not resolve to a device".to_string()]
+        );
+        assert!(check_fstab_sources(
+            "tmpfs  /tmp  tmpfs  defaults  0  0
+",
+            |_| false
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn crypttab_devices_must_be_luks() {
+        let crypttab = "Root  UUID=1111  none  luks
+                        Home  UUID=2222  none  luks
+                        Usr   PARTUUID=3333  none  luks
+";
+        let exists = |p: &str| !p.ends_with("3333");
+        let is_luks = |p: &str| p.ends_with("1111");
+        let problems = check_crypttab_devices(crypttab, exists, is_luks);
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("Home") && problems[0].contains("not a LUKS device"));
+        assert!(problems[1].contains("Usr") && problems[1].contains("not found"));
+    }
+
+    #[test]
+    fn user_must_be_in_passwd() {
+        let passwd = "root:x:0:0::/root:/bin/bash
+alice:x:1000:1000::/home/alice:/bin/bash
+";
+        assert_eq!(check_user(passwd, "alice"), None);
+        assert!(check_user(passwd, "ali").is_some());
+    }
+
+    #[test]
+    fn user_must_be_in_seat_groups() {
+        let group = "video:x:985:alice,bob\nseat:x:970:bob\n";
+        assert!(check_groups(group, "bob", &["seat", "video"]).is_empty());
+        assert_eq!(
+            check_groups(group, "alice", &["seat", "video"]),
+            vec!["user alice is not in group seat"]
+        );
+    }
+
+    #[test]
+    fn initramfs_is_required() {
+        assert!(required_boot_files(&DeploymentConfig::sample())
+            .iter()
+            .any(|f| f.ends_with("initramfs-linux-zen.img")));
+    }
+
+    #[test]
+    fn boot_files_follow_kernel_choice() {
+        let mut config = DeploymentConfig::sample();
+        config.system.kernel = crate::config::KernelChoice::Lts;
+        let files = required_boot_files(&config);
+        assert!(files.contains(&"/boot/vmlinuz-linux-lts".to_string()));
+        assert!(files.contains(&"/boot/initramfs-linux-lts.img".to_string()));
+    }
+
+    #[test]
+    fn loader_follows_firmware_and_esp_mount() {
+        let mut config = DeploymentConfig::sample();
+        config.disk.firmware = Some(Firmware::Uefi);
+        config.disk.esp_mountpoint = crate::config::EspMount::Efi;
+        assert!(required_boot_files(&config).contains(&"/efi/EFI/BOOT/BOOTX64.EFI".to_string()));
+
+        config.disk.existing_esp = Some("/dev/sda1".to_string());
+        assert!(required_boot_files(&config).contains(&"/efi/EFI/Artix/grubx64.efi".to_string()));
+
+        config.disk.firmware = Some(Firmware::Bios);
+        assert!(required_boot_files(&config).contains(&BIOS_CORE_IMG.to_string()));
+    }
+
+    #[test]
+    fn refind_requires_its_kernel_parameters() {
+        let mut config = DeploymentConfig::sample();
+        config.system.bootloader = Bootloader::Refind;
+        let files = required_boot_files(&config);
+        assert!(files.contains(&REFIND_LINUX_CONF.to_string()));
+        assert!(!files.contains(&GRUB_CFG.to_string()));
+    }
+}
@@ -3,7 +3,7 @@
 //! A portable CLI tool for deploying Artix Linux to removable media and disks.
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use tracing::info;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
@@ -127,10 +127,27 @@ struct Cli {
     #[arg(short, long, global = true)]
     verbose: bool,
 
+    /// Base directory for scratch files (default: /tmp, falling back to
+    /// /run and /var/tmp when /tmp is full or read-only)
+    #[arg(long, global = true, value_name = "DIR")]
+    runtime_dir: Option<std::path::PathBuf>,
+
+    /// Pretend the host has this much RAM (MiB) for swap and layout
+    /// sizing, to reproduce another machine's plan
+    #[arg(long, global = true, value_name = "MIB")]
+    simulate_ram: Option<u64>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+/// Output format for `preview-layout`
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LayoutFormat {
+    Text,
+    Json,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start interactive installation wizard
@@ -155,6 +172,47 @@ enum Commands {
         /// Mutually exclusive with `--interactive`.
         #[arg(long, conflicts_with = "interactive")]
         no_interactive: bool,
+
+        /// Allow installing onto the disk backing the running system's `/`
+        /// or the live ISO medium.  Refused by default.
+        #[arg(long)]
+        force_target: bool,
+
+        /// Install missing host packages without asking and proceed on a
+        /// target disk whose filesystems are in use.  The confirmation
+        /// before the disk is erased is still shown
+        #[arg(short = 'y', long)]
+        yes: bool,
+
+        /// Append the output of long-running commands (basestrap, pacman,
+        /// yay) and the phase timing report to this file
+        #[arg(long, value_name = "FILE")]
+        log_file: Option<String>,
+
+        /// Format up to N partitions concurrently (turns on
+        /// `parallel_format`; 1 keeps formatting serial)
+        #[arg(short = 'j', long, value_name = "N")]
+        jobs: Option<usize>,
+
+        /// Install into this disk image file instead of a disk; it is
+        /// attached to a loop device and detached when the install ends
+        #[arg(long, value_name = "PATH", conflicts_with = "device")]
+        image: Option<String>,
+
+        /// Create the image with this size in GiB when it does not exist
+        /// (sparse), or grow an existing smaller one
+        #[arg(long, value_name = "GIB", requires = "image")]
+        image_size: Option<u64>,
+
+        /// Record every command run (secrets redacted) as a shell script in
+        /// the runtime dir, copied to /root/deploytix-install.sh of the
+        /// installed system
+        #[arg(long)]
+        transcript: bool,
+
+        /// Mount the target system here while installing
+        #[arg(long, value_name = "PATH", default_value = install::DEFAULT_INSTALL_ROOT, value_parser = disk::mount::check_install_root)]
+        root: String,
     },
 
     /// List available disks for installation
@@ -168,6 +226,54 @@ enum Commands {
     Validate {
         /// Path to configuration file
         config: String,
+
+        /// Write the computed partition plan for the configured disk as an
+        /// sfdisk script (apply manually with `sfdisk <disk> < file`)
+        #[arg(long, value_name = "FILE")]
+        export_sfdisk: Option<String>,
+    },
+
+    /// Preview the partitions, thin volumes and subvolumes a configuration
+    /// produces, without touching any disk
+    #[command(alias = "dump-layout")]
+    PreviewLayout {
+        /// Path to configuration file (default: the sample configuration)
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// Read the disk size from this device instead of the configured one
+        #[arg(short, long, conflicts_with = "size")]
+        device: Option<String>,
+
+        /// Disk size to plan for in GiB (default: the configured device)
+        #[arg(long, value_name = "GIB", alias = "size-gib")]
+        size: Option<u64>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = LayoutFormat::Text)]
+        format: LayoutFormat,
+    },
+
+    /// Compare a configuration with an installed system and report every
+    /// field that drifted (exits non-zero on drift)
+    Diff {
+        /// Path to configuration file
+        config: String,
+
+        /// Root of the installed system to inspect
+        #[arg(long, default_value = "/")]
+        root: String,
+    },
+
+    /// Write a best-effort configuration reproducing an existing install
+    ImportConfig {
+        /// Root of the installed system to inspect
+        #[arg(long, default_value = "/")]
+        root: String,
+
+        /// Output path for the configuration file
+        #[arg(short, long, default_value = "deploytix.toml")]
+        output: String,
     },
 
     /// Generate a sample configuration file
@@ -186,6 +292,96 @@ enum Commands {
         /// Wipe partition table after unmounting
         #[arg(short, long)]
         wipe: bool,
+
+        /// Make the data unrecoverable before wiping: discard on SSDs, one
+        /// random pass on HDDs, or only the LUKS headers of encrypted disks
+        /// (implies --wipe)
+        #[arg(long)]
+        secure: bool,
+
+        /// Print what would be done without touching the disk
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Unmount everything under this installation root
+        #[arg(long, value_name = "PATH", default_value = install::DEFAULT_INSTALL_ROOT, value_parser = disk::mount::check_install_root)]
+        root: String,
+    },
+
+    /// Remove a Deploytix install from a disk: delete its UEFI boot
+    /// entries, release its LVM/ZFS/LUKS volumes and optionally wipe it
+    Uninstall {
+        /// Disk holding the install
+        #[arg(short, long)]
+        device: String,
+
+        /// Wipe partition table afterwards
+        #[arg(short, long)]
+        wipe: bool,
+
+        /// Make the data unrecoverable before wiping (implies --wipe; see
+        /// `cleanup --secure`)
+        #[arg(long)]
+        secure: bool,
+
+        /// Print what would be done without touching the disk or NVRAM
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Installation root the disk may still be mounted under
+        #[arg(long, value_name = "PATH", default_value = install::DEFAULT_INSTALL_ROOT, value_parser = disk::mount::check_install_root)]
+        root: String,
+    },
+
+    /// Measure cipher and filesystem throughput (plain, LUKS2, LUKS2 +
+    /// integrity) on a scratch device or loopback file
+    Benchmark {
+        /// Scratch block device, or a file to benchmark through a loop device
+        /// (created when missing)
+        target: String,
+
+        /// Size of the loopback file created for a missing target, in MiB
+        #[arg(long, default_value_t = 2048)]
+        file_size_mib: u64,
+
+        /// Data written per filesystem run, in MiB
+        #[arg(long, default_value_t = 256)]
+        write_mib: u64,
+
+        /// Overwrite a target that holds existing data
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Open and mount an installed system and check that it can boot
+    /// (loader, kernel and initramfs, fstab, crypttab, user account)
+    Verify {
+        /// Configuration the system was installed with
+        #[arg(short, long, default_value = "deploytix.toml")]
+        config: String,
+
+        /// Disk the system is installed on (overrides the config)
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// Show the commands that would run without running them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Open and mount an installed system and start a shell inside it
+    Chroot {
+        /// Configuration the system was installed with
+        #[arg(short, long, default_value = "deploytix.toml")]
+        config: String,
+
+        /// Disk the system is installed on (overrides the config)
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// Show the commands that would run without running them
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Run a rehearsal installation: execute the full install on disk,
@@ -230,24 +426,60 @@ fn init_logging(verbose: bool) {
     };
 
     tracing_subscriber::registry()
-        .with(fmt::layer().with_target(false))
+        .with(fmt::layer().with_target(false).with_writer(std::io::stderr))
         .with(filter)
         .init();
 }
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {:?}", err);
+        let code = err
+            .downcast_ref::<DeploytixError>()
+            .map_or(1, DeploytixError::exit_code);
+        std::process::exit(code);
+    }
+}
+
+fn run() -> Result<()> {
     let cli = Cli::parse();
     init_logging(cli.verbose);
 
     // Start looping theme music (runs in background; stops when handle drops)
     let _audio = resources::audio::play_theme_loop();
 
+    if let Some(ram_mib) = cli.simulate_ram {
+        disk::detection::simulate_ram_mib(ram_mib)?;
+    }
+
+    // Commands that write scratch files need a usable runtime dir up front
+    if matches!(
+        cli.command,
+        None | Some(
+            Commands::Install { .. }
+                | Commands::Cleanup { .. }
+                | Commands::Uninstall { .. }
+                | Commands::Rehearse { .. }
+                | Commands::Benchmark { .. }
+        )
+    ) {
+        resources::ensure_runtime_dir(cli.runtime_dir.as_deref())?;
+    }
+
     match cli.command {
         Some(Commands::Install {
             config,
             device,
             interactive,
             no_interactive,
+            force_target,
+            yes,
+            log_file,
+            jobs,
+            image,
+            image_size,
+            transcript,
+            root,
         }) => {
             // Activation: explicit flag wins; otherwise interactive ON
             // when no config file is supplied, OFF when -c is given.
@@ -258,19 +490,100 @@ fn main() -> Result<()> {
             } else {
                 config.is_none()
             };
-            cmd_install(config, device, interactive_resolved)?;
+            cmd_install(
+                config,
+                device,
+                interactive_resolved,
+                force_target,
+                yes,
+                log_file.as_deref(),
+                jobs,
+                image.as_deref(),
+                image_size,
+                transcript,
+                &root,
+            )?;
         }
         Some(Commands::ListDisks { all }) => {
             cmd_list_disks(all)?;
         }
-        Some(Commands::Validate { config }) => {
-            cmd_validate(&config)?;
+        Some(Commands::Validate {
+            config,
+            export_sfdisk,
+        }) => {
+            cmd_validate(&config, export_sfdisk.as_deref())?;
+        }
+        Some(Commands::PreviewLayout {
+            config,
+            device,
+            size,
+            format,
+        }) => {
+            cmd_preview_layout(config.as_deref(), device.as_deref(), size, format)?;
+        }
+        Some(Commands::Diff { config, root }) => {
+            cmd_diff(&config, &root)?;
+        }
+        Some(Commands::ImportConfig { root, output }) => {
+            cmd_import_config(&root, &output)?;
         }
         Some(Commands::GenerateConfig { output }) => {
             cmd_generate_config(&output)?;
         }
-        Some(Commands::Cleanup { device, wipe }) => {
-            cmd_cleanup(device, wipe)?;
+        Some(Commands::Cleanup {
+            device,
+            wipe,
+            secure,
+            dry_run,
+            root,
+        }) => {
+            cmd_cleanup(device, wipe || secure, secure, dry_run, &root)?;
+        }
+        Some(Commands::Uninstall {
+            device,
+            wipe,
+            secure,
+            dry_run,
+            root,
+        }) => {
+            cmd_uninstall(&device, wipe || secure, secure, dry_run, &root)?;
+        }
+        Some(Commands::Benchmark {
+            target,
+            file_size_mib,
+            write_mib,
+            force,
+        }) => {
+            if !nix::unistd::geteuid().is_root() {
+                return Err(DeploytixError::NotRoot.into());
+            }
+            let opts = disk::benchmark::BenchmarkOptions {
+                target,
+                file_size_mib,
+                write_mib,
+                force,
+            };
+            disk::benchmark::run_benchmark(
+                &deploytix::utils::command::CommandRunner::new(false),
+                &opts,
+            )?;
+        }
+        Some(Commands::Verify {
+            config,
+            device,
+            dry_run,
+        }) => {
+            let config = load_installed_config(&config, device)?;
+            install::rescue::verify_installed_system(config, dry_run)?;
+            println!("✓ Installation verified");
+        }
+        Some(Commands::Chroot {
+            config,
+            device,
+            dry_run,
+        }) => {
+            let config = load_installed_config(&config, device)?;
+            install::rescue::enter_installed_system(config, dry_run)?;
         }
         Some(Commands::Rehearse { config, log_file }) => {
             cmd_rehearse(&config, &log_file)?;
@@ -283,17 +596,38 @@ fn main() -> Result<()> {
         }
         None => {
             // Default: run interactive wizard with full interactive review
-            cmd_install(None, None, true)?;
+            cmd_install(
+                None,
+                None,
+                true,
+                false,
+                false,
+                None,
+                None,
+                None,
+                None,
+                false,
+                install::DEFAULT_INSTALL_ROOT,
+            )?;
         }
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn cmd_install(
     config_path: Option<String>,
     device: Option<String>,
     interactive: bool,
+    force_target: bool,
+    yes: bool,
+    log_file: Option<&str>,
+    jobs: Option<usize>,
+    image: Option<&str>,
+    image_size: Option<u64>,
+    transcript: bool,
+    install_root: &str,
 ) -> Result<()> {
     use install::Installer;
 
@@ -302,31 +636,86 @@ fn cmd_install(
         return Err(DeploytixError::NotRoot.into());
     }
 
-    // Load or create configuration
-    let config = if let Some(path) = config_path {
-        info!("Loading configuration from {}", path);
-        DeploymentConfig::from_file(&path)?
-    } else {
-        info!("Starting interactive configuration wizard");
-        DeploymentConfig::from_wizard(device)?
+    // Attach the image first so the wizard and config target its loop device
+    let runner = deploytix::utils::command::CommandRunner::new(false);
+    let image = image
+        .map(|path| disk::image::open_image(&runner, path, image_size))
+        .transpose()?;
+
+    let config = match load_install_config(config_path, device, image.as_ref(), jobs) {
+        Ok(config) => config,
+        Err(e) => {
+            if let Some(ref image) = image {
+                disk::image::detach_loop(&runner, &image.loop_device);
+            }
+            return Err(e);
+        }
     };
 
-    // Validate configuration
-    config.validate()?;
-
     // Run installation
-    let mut installer = Installer::new(config, false);
+    let mut installer = Installer::new(config, false)
+        .with_force_target(force_target)
+        .with_assume_yes(yes)
+        .with_install_root(install_root);
+    if let Some(image) = image {
+        installer = installer.with_disk_image(image);
+    }
+    if transcript {
+        installer = installer.with_transcript()?;
+    }
     if interactive {
         use std::sync::Arc;
         let policy = Arc::new(deploytix::utils::cli_policy::CliInteractivePolicy::new());
         installer = installer.with_policy(policy);
         info!("Interactive mode ON — pacman commands will be reviewed before running");
     }
+    if let Some(path) = log_file {
+        use std::io::Write;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow::anyhow!("cannot open log file {}: {}", path, e))?;
+        installer = installer.with_output_callback(Box::new(move |line| {
+            let _ = writeln!(&file, "{}", line);
+        }));
+        info!("Command output is logged to {}", path);
+    }
     installer.run()?;
 
     Ok(())
 }
 
+/// Load the install configuration (or run the wizard), pointed at the
+/// attached image when there is one, then validate and preflight it.
+fn load_install_config(
+    config_path: Option<String>,
+    device: Option<String>,
+    image: Option<&disk::image::DiskImage>,
+    jobs: Option<usize>,
+) -> Result<DeploymentConfig> {
+    let device = image.map(|i| i.loop_device.clone()).or(device);
+    let mut config = if let Some(path) = config_path {
+        info!("Loading configuration from {}", path);
+        DeploymentConfig::from_file(&path)?
+    } else {
+        info!("Starting interactive configuration wizard");
+        DeploymentConfig::from_wizard(device.clone())?
+    };
+    if let Some(image) = image {
+        config.disk.device = image.loop_device.clone();
+    }
+    if let Some(jobs) = jobs {
+        config.disk.parallel_format = true;
+        config.disk.format_jobs = Some(jobs);
+    }
+
+    // Validate configuration, then check it fits this machine
+    config.validate()?;
+    config.preflight()?;
+    Ok(config)
+}
+
 fn cmd_list_disks(all: bool) -> Result<()> {
     use disk::detection::list_block_devices;
 
@@ -337,15 +726,19 @@ fn cmd_list_disks(all: bool) -> Result<()> {
         return Ok(());
     }
 
-    println!("{:<15} {:>10} {:<20} TYPE", "DEVICE", "SIZE", "MODEL");
-    println!("{}", "-".repeat(60));
+    println!(
+        "{:<15} {:>10} {:<20} {:<6} TYPE",
+        "DEVICE", "SIZE", "MODEL", "MEDIA"
+    );
+    println!("{}", "-".repeat(67));
 
     for dev in devices {
         println!(
-            "{:<15} {:>10} {:<20} {}",
+            "{:<15} {:>10} {:<20} {:<6} {}",
             dev.path,
             dev.size_human(),
             dev.model.as_deref().unwrap_or("-"),
+            dev.media_kind(),
             dev.device_type
         );
     }
@@ -353,10 +746,116 @@ fn cmd_list_disks(all: bool) -> Result<()> {
     Ok(())
 }
 
-fn cmd_validate(config_path: &str) -> Result<()> {
+fn cmd_validate(config_path: &str, export_sfdisk: Option<&str>) -> Result<()> {
     let config = DeploymentConfig::from_file(config_path)?;
     config.validate()?;
     println!("✓ Configuration is valid");
+
+    if let Some(output) = export_sfdisk {
+        let device = &config.disk.device;
+        if config.disk.preserves_partitions() {
+            // Only the new partitions, for `sfdisk --append`
+            let table = disk::dualboot::read_partition_table(device)?;
+            let plan = disk::dualboot::plan_alongside(
+                device,
+                config.disk.existing_esp.as_deref(),
+                &table,
+            )?;
+            let mut layout =
+                disk::layouts::compute_layout_from_config(&config.disk, plan.layout_mib())?;
+            disk::dualboot::adopt_layout(&mut layout, &plan)?;
+            print!(
+                "{}",
+                disk::dualboot::alongside_preview(device, &layout, &plan)?
+            );
+            let script = disk::dualboot::alongside_sfdisk_script(device, &layout, &plan)?;
+            std::fs::write(output, script)?;
+            println!(
+                "✓ sfdisk --append script for {} written to {}",
+                device, output
+            );
+            return Ok(());
+        }
+        let disk_mib = disk::detection::get_device_info(device)?.size_mib();
+        let layout = disk::layouts::compute_layout_from_config(&config.disk, disk_mib)?;
+        let script = disk::partitioning::generate_sfdisk_script(device, &layout)?;
+        std::fs::write(output, script)?;
+        println!("✓ sfdisk script for {} written to {}", device, output);
+    }
+    Ok(())
+}
+
+fn cmd_preview_layout(
+    config_path: Option<&str>,
+    device: Option<&str>,
+    size_gib: Option<u64>,
+    format: LayoutFormat,
+) -> Result<()> {
+    let config = match config_path {
+        Some(path) => DeploymentConfig::from_file(path)?,
+        None => DeploymentConfig::sample(),
+    };
+
+    let disk_mib = match size_gib {
+        Some(gib) => gib.checked_mul(1024).ok_or_else(|| {
+            DeploytixError::ConfigError(format!("--size {} GiB is out of range", gib))
+        })?,
+        None => {
+            let device = device.unwrap_or(&config.disk.device);
+            disk::detection::get_device_info(device)?.size_mib()
+        }
+    };
+    let layout = disk::layouts::compute_layout_from_config(&config.disk, disk_mib)?;
+
+    match format {
+        LayoutFormat::Json => println!("{}", serde_json::to_string_pretty(&layout)?),
+        LayoutFormat::Text => {
+            disk::layouts::print_layout_summary(&layout);
+
+            if let Some(volumes) = &layout.planned_thin_volumes {
+                println!("LVM thin volumes:");
+                for vol in volumes {
+                    println!(
+                        "  {:<10} {:>8}  {}",
+                        vol.name, vol.virtual_size, vol.mount_point
+                    );
+                }
+                println!();
+            }
+            if let Some(subvolumes) = layout.subvolumes.as_ref().filter(|s| !s.is_empty()) {
+                println!("Btrfs subvolumes:");
+                for sv in subvolumes {
+                    println!(
+                        "  {:<12} {:<12} {}",
+                        sv.name, sv.mount_point, sv.mount_options
+                    );
+                }
+                println!();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_diff(config_path: &str, root: &str) -> Result<()> {
+    let config = DeploymentConfig::from_file(config_path)?;
+    let report = install::drift::check_drift(&config, root);
+    report.print();
+
+    if report.has_drift() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn cmd_import_config(root: &str, output: &str) -> Result<()> {
+    let imported = install::import::import_config(root);
+    std::fs::write(output, imported.to_toml(root)?)?;
+    println!(
+        "✓ Configuration imported from {} written to {}",
+        root, output
+    );
+    println!("  Review the values marked \"guess\" and \"placeholder\" before installing.");
     Ok(())
 }
 
@@ -401,19 +900,70 @@ fn cmd_rehearse(config_path: &str, log_file: &str) -> Result<()> {
     Ok(())
 }
 
-fn cmd_cleanup(device: Option<String>, wipe: bool) -> Result<()> {
+fn cmd_cleanup(
+    device: Option<String>,
+    wipe: bool,
+    secure: bool,
+    dry_run: bool,
+    install_root: &str,
+) -> Result<()> {
     use cleanup::Cleaner;
 
     if !nix::unistd::geteuid().is_root() {
         return Err(DeploytixError::NotRoot.into());
     }
 
-    let cleaner = Cleaner::new(false);
+    let cleaner = Cleaner::new(dry_run)
+        .with_secure_erase(secure)
+        .with_install_root(install_root);
     cleaner.cleanup(device.as_deref(), wipe)?;
 
     Ok(())
 }
 
+fn cmd_uninstall(
+    device: &str,
+    wipe: bool,
+    secure: bool,
+    dry_run: bool,
+    install_root: &str,
+) -> Result<()> {
+    use cleanup::Cleaner;
+
+    if !nix::unistd::geteuid().is_root() {
+        return Err(DeploytixError::NotRoot.into());
+    }
+
+    let cleaner = Cleaner::new(dry_run)
+        .with_secure_erase(secure)
+        .with_install_root(install_root);
+    cleaner.uninstall(device, wipe)?;
+
+    Ok(())
+}
+
+/// Load the configuration an existing install was made with, for `chroot`
+/// and `verify`.
+fn load_installed_config(config_path: &str, device: Option<String>) -> Result<DeploymentConfig> {
+    if !nix::unistd::geteuid().is_root() {
+        return Err(DeploytixError::NotRoot.into());
+    }
+
+    let mut config = DeploymentConfig::from_file(config_path)?;
+    if let Some(device) = device {
+        config.disk.device = device;
+    }
+    // Saved configs usually leave the passphrase out
+    if config.disk.encryption && config.disk.encryption_password.is_none() {
+        config.disk.encryption_password = Some(deploytix::utils::prompt::prompt_password(
+            "LUKS passphrase",
+            false,
+        )?);
+    }
+    config.validate()?;
+    Ok(config)
+}
+
 fn cmd_generate_desktop_file(
     de: Option<String>,
     bindir: Option<String>,
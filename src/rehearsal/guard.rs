@@ -5,12 +5,14 @@
 //! drop.  This guarantees the disk is restored to a pristine state even if
 //! the rehearsal panics or encounters an early error.
 
+use crate::disk::mount::mount_points_under;
 use std::fs;
+use std::path::Path;
 use std::process::{Command, Stdio};
 use tracing::{info, warn};
 
-/// Install root path (mirrors `install::installer::INSTALL_ROOT`).
-const INSTALL_ROOT: &str = "/install";
+/// Where the rehearsal installer mounts the target.
+const INSTALL_ROOT: &str = crate::install::DEFAULT_INSTALL_ROOT;
 
 /// RAII guard that wipes the target disk when dropped.
 ///
@@ -74,12 +76,12 @@ impl DiskWipeGuard {
 
         // 4. Write blank GPT
         let script = "label: gpt\n";
-        let script_path = "/tmp/deploytix_rehearsal_wipe";
-        if fs::write(script_path, script).is_ok() {
+        let script_path = crate::resources::runtime_path("rehearsal_wipe");
+        if fs::write(&script_path, script).is_ok() {
             let ok = Command::new("sfdisk")
                 .arg(device)
                 .stdin(
-                    fs::File::open(script_path)
+                    fs::File::open(&script_path)
                         .unwrap_or_else(|_| fs::File::open("/dev/null").unwrap()),
                 )
                 .stdout(Stdio::null())
@@ -112,24 +114,10 @@ impl DiskWipeGuard {
 
     fn unmount_all() {
         let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
-        let mut mount_points: Vec<&str> = mounts
-            .lines()
-            .filter_map(|line| {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 && parts[1].starts_with(INSTALL_ROOT) {
-                    Some(parts[1])
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        mount_points.sort_by_key(|b| std::cmp::Reverse(b.matches('/').count()));
-
-        for mp in mount_points {
+        for mp in mount_points_under(&mounts, INSTALL_ROOT) {
             info!("DiskWipeGuard: unmounting {}", mp);
-            if Self::run_quiet("umount", &[mp]).is_err() {
-                let _ = Self::run_quiet("umount", &["-l", mp]);
+            if Self::run_quiet("umount", &[&mp]).is_err() {
+                let _ = Self::run_quiet("umount", &["-l", &mp]);
             }
         }
 
@@ -137,7 +125,7 @@ impl DiskWipeGuard {
         let swaps = fs::read_to_string("/proc/swaps").unwrap_or_default();
         for line in swaps.lines().skip(1) {
             if let Some(dev) = line.split_whitespace().next() {
-                if dev.starts_with(INSTALL_ROOT) || dev.contains("/dev/mapper/Crypt-") {
+                if Path::new(dev).starts_with(INSTALL_ROOT) || dev.contains("/dev/mapper/Crypt-") {
                     let _ = Self::run_quiet("swapoff", &[dev]);
                 }
             }
@@ -4,3 +4,6 @@
 //! that are compiled into the binary for portability.
 
 pub mod audio;
+pub mod runtime;
+
+pub use runtime::{ensure_runtime_dir, runtime_path};
@@ -0,0 +1,131 @@
+//! Runtime directory for installer scratch files
+//!
+//! Partition and wipe scripts, the temporary package repo and btrfs setup
+//! mountpoints live under a single runtime directory, `/tmp/deploytix` by
+//! default.  Some live environments mount `/tmp` as a small or read-only
+//! tmpfs, so [`ensure_runtime_dir`] checks the directory is writable with
+//! some free space before anything is written there, falling back to other
+//! bases or an explicit `--runtime-dir`.
+
+use crate::utils::error::{DeploytixError, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// Directory name created under the runtime base.
+const RUNTIME_DIR_NAME: &str = "deploytix";
+
+/// Preferred base directory.
+const DEFAULT_BASE: &str = "/tmp";
+
+/// Bases tried when `/tmp` is unusable and no `--runtime-dir` was given.
+const FALLBACK_BASES: &[&str] = &["/run", "/var/tmp"];
+
+/// Free space required in the runtime directory (the temporary package
+/// repo is the largest thing written there).
+pub const MIN_RUNTIME_FREE_MIB: u64 = 64;
+
+static RUNTIME_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// The runtime directory chosen by [`ensure_runtime_dir`], or the default
+/// `/tmp/deploytix` if it has not been resolved yet.
+pub fn runtime_dir() -> PathBuf {
+    RUNTIME_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| Path::new(DEFAULT_BASE).join(RUNTIME_DIR_NAME))
+}
+
+/// Path of a scratch file or directory inside the runtime directory.
+pub fn runtime_path(name: &str) -> PathBuf {
+    runtime_dir().join(name)
+}
+
+/// Resolve and verify the runtime directory.
+///
+/// With `base` set (from `--runtime-dir`) only `<base>/deploytix` is tried;
+/// otherwise `/tmp/deploytix` is preferred and `/run` and `/var/tmp` are
+/// used as fallbacks.  The first resolution wins for the rest of the
+/// process.
+pub fn ensure_runtime_dir(base: Option<&Path>) -> Result<PathBuf> {
+    if let Some(dir) = RUNTIME_DIR.get() {
+        return Ok(dir.clone());
+    }
+
+    let candidates: Vec<PathBuf> = match base {
+        Some(b) => vec![b.join(RUNTIME_DIR_NAME)],
+        None => std::iter::once(DEFAULT_BASE)
+            .chain(FALLBACK_BASES.iter().copied())
+            .map(|b| Path::new(b).join(RUNTIME_DIR_NAME))
+            .collect(),
+    };
+
+    let mut first_failure: Option<(PathBuf, String)> = None;
+    for dir in candidates {
+        match check_usable(&dir) {
+            Ok(()) => {
+                if let Some((failed, _)) = &first_failure {
+                    warn!(
+                        "Runtime dir {} is unusable, falling back to {}",
+                        failed.display(),
+                        dir.display()
+                    );
+                }
+                info!("Using runtime dir {}", dir.display());
+                return Ok(RUNTIME_DIR.get_or_init(|| dir).clone());
+            }
+            Err(reason) => {
+                warn!("Runtime dir {}: {}", dir.display(), reason);
+                first_failure.get_or_insert((dir, reason));
+            }
+        }
+    }
+
+    let (path, reason) = first_failure.expect("at least one runtime dir candidate");
+    Err(DeploytixError::RuntimeDirUnusable {
+        path: path.display().to_string(),
+        reason,
+    })
+}
+
+/// Create `dir`, prove it is writable, and check its free space.
+fn check_usable(dir: &Path) -> std::result::Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let probe = dir.join(".write-test");
+    fs::write(&probe, b"ok").map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(&probe);
+
+    let free = free_mib(dir).map_err(|e| e.to_string())?;
+    if free < MIN_RUNTIME_FREE_MIB {
+        return Err(format!(
+            "only {} MiB free, need {} MiB",
+            free, MIN_RUNTIME_FREE_MIB
+        ));
+    }
+    Ok(())
+}
+
+/// Space available to unprivileged writers on the filesystem holding `dir`.
+fn free_mib(dir: &Path) -> nix::Result<u64> {
+    let stat = nix::sys::statvfs::statvfs(dir)?;
+    Ok(stat.blocks_available() * stat.fragment_size() / (1024 * 1024))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writable_dir_is_usable() {
+        let dir = std::env::temp_dir().join(format!("deploytix-runtime-{}", std::process::id()));
+        assert!(check_usable(&dir).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unwritable_dir_is_rejected() {
+        assert!(check_usable(Path::new("/proc/deploytix")).is_err());
+    }
+}
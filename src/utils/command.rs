@@ -2,11 +2,43 @@
 
 use crate::utils::error::{DeploytixError, Result};
 use crate::utils::interactive::{PacmanDecision, PacmanInvocation, PolicyHandle};
-use std::process::{Command, Output, Stdio};
-use std::sync::mpsc::Sender;
+use crate::utils::transcript::Transcript;
+use std::io::{BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
+/// Default retry count for network-bound commands (package downloads).
+pub const DEFAULT_NETWORK_RETRIES: u32 = 3;
+
+/// Delay before the first retry of a network-bound command; doubled on
+/// each further attempt.
+pub const NETWORK_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Whether a command's error output shows a transient network failure
+/// (download, timeout, mirror or DNS), the only kind worth retrying.
+pub fn is_transient_error(stderr: &str) -> bool {
+    const PATTERNS: &[&str] = &[
+        "Operation too slow",
+        "failed retrieving file",
+        "failed to retrieve some files",
+        "failed to synchronize",
+        "download library error",
+        "Connection timed out",
+        "Connection reset",
+        "Connection refused",
+        "SSL connection timeout",
+        "Could not resolve host",
+        "Temporary failure in name resolution",
+        "Network is unreachable",
+    ];
+    PATTERNS.iter().any(|pattern| stderr.contains(pattern))
+}
+
 /// Record of a single command invocation captured during rehearsal mode.
 #[derive(Debug, Clone)]
 pub struct OperationRecord {
@@ -18,6 +50,22 @@ pub struct OperationRecord {
     pub success: bool,
 }
 
+/// Receives each line a streamed command prints (see
+/// [`CommandRunner::run_captured`]).
+pub type OutputCallback = Box<dyn Fn(&str) + Send + Sync>;
+
+/// How often a command running under a timeout is polled for exit.
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Map a spawn failure to `CommandNotFound` or `Io`.
+fn spawn_error(program: &str, e: std::io::Error) -> DeploytixError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        DeploytixError::CommandNotFound(program.to_string())
+    } else {
+        DeploytixError::Io(e)
+    }
+}
+
 /// Execute a command and return the output
 pub fn run_command(program: &str, args: &[&str]) -> Result<Output> {
     debug!("Running: {} {}", program, args.join(" "));
@@ -26,14 +74,162 @@ pub fn run_command(program: &str, args: &[&str]) -> Result<Output> {
         .args(args)
         .stdin(Stdio::null())
         .output()
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                DeploytixError::CommandNotFound(program.to_string())
+        .map_err(|e| spawn_error(program, e))?;
+
+    check_output(program, args, output)
+}
+
+/// Execute a command, killing it if it runs longer than `timeout`.
+pub fn run_command_with_timeout(program: &str, args: &[&str], timeout: Duration) -> Result<Output> {
+    debug!(
+        "Running (timeout {}s): {} {}",
+        timeout.as_secs(),
+        program,
+        args.join(" ")
+    );
+
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()
+        .map_err(|e| spawn_error(program, e))?;
+
+    let command = format!("{} {}", program, args.join(" "));
+    let output = wait_with_timeout(child, &command, Some(timeout))?;
+    check_output(program, args, output)
+}
+
+/// Wait for `child` and collect its output.
+///
+/// If it is still running after `timeout` (or the installer is
+/// interrupted), its process group is stopped with SIGTERM-then-SIGKILL and
+/// `Timeout` (or `Interrupted`) is returned.  The child must have been
+/// spawned in its own process group (`process_group(0)`) so that helpers it
+/// forked are stopped with it.  `None` waits indefinitely.
+pub fn wait_with_timeout(
+    mut child: Child,
+    command: &str,
+    timeout: Option<Duration>,
+) -> Result<Output> {
+    let Some(timeout) = timeout else {
+        return Ok(child.wait_with_output()?);
+    };
+
+    // Drain the pipes concurrently so a chatty child cannot block on a
+    // full pipe while we poll for its exit.
+    fn drain<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = pipe.read_to_end(&mut buf);
+            buf
+        })
+    }
+    let stdout = child.stdout.take().map(drain);
+    let stderr = child.stderr.take().map(drain);
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        let interrupted = crate::utils::signal::is_interrupted();
+        if interrupted || start.elapsed() >= timeout {
+            if interrupted {
+                warn!("Interrupted; stopping '{}'", command);
             } else {
-                DeploytixError::Io(e)
+                warn!(
+                    "'{}' still running after {}s; stopping it",
+                    command,
+                    timeout.as_secs()
+                );
+            }
+            crate::utils::signal::terminate(-(child.id() as i32), || {
+                matches!(child.try_wait(), Ok(None))
+            });
+            let _ = child.wait();
+            return Err(if interrupted {
+                DeploytixError::Interrupted
+            } else {
+                DeploytixError::Timeout {
+                    command: command.to_string(),
+                    secs: timeout.as_secs(),
+                }
+            });
+        }
+
+        thread::sleep(TIMEOUT_POLL_INTERVAL);
+    };
+
+    let collect =
+        |h: Option<JoinHandle<Vec<u8>>>| h.and_then(|h| h.join().ok()).unwrap_or_default();
+    Ok(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+/// [`wait_with_timeout`], passing each line the child prints on stdout to
+/// `on_line` as it arrives.  The returned `Output` has no stdout.
+pub fn wait_with_timeout_observed(
+    mut child: Child,
+    command: &str,
+    timeout: Option<Duration>,
+    on_line: &dyn Fn(&str),
+) -> Result<Output> {
+    let Some(stdout) = child.stdout.take() else {
+        return wait_with_timeout(child, command, timeout);
+    };
+    let (tx, rx) = mpsc::channel();
+    let reader = thread::spawn(move || forward_lines(stdout, tx));
+
+    // Wait on a helper thread so the lines are handled on this one; the
+    // channel closes once the child exits or is stopped.
+    let output = thread::scope(|scope| {
+        let waiter = scope.spawn(move || wait_with_timeout(child, command, timeout));
+        for line in rx {
+            on_line(&line);
+        }
+        waiter
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    });
+    let _ = reader.join();
+    output
+}
+
+/// Forward the lines read from `pipe` to `tx`.
+///
+/// A carriage return discards the partial line, so progress bars that
+/// redraw in place (pacman downloads) arrive once, in their final state.
+fn forward_lines(pipe: impl Read, tx: Sender<String>) {
+    let mut line = Vec::new();
+    for byte in BufReader::new(pipe).bytes().map_while(|b| b.ok()) {
+        match byte {
+            b'\n' => {
+                if tx
+                    .send(String::from_utf8_lossy(&line).into_owned())
+                    .is_err()
+                {
+                    return;
+                }
+                line.clear();
             }
-        })?;
+            b'\r' => line.clear(),
+            _ => line.push(byte),
+        }
+    }
+    if !line.is_empty() {
+        let _ = tx.send(String::from_utf8_lossy(&line).into_owned());
+    }
+}
 
+/// Turn a non-zero exit into `CommandFailed`, logging its stderr.
+fn check_output(program: &str, args: &[&str], output: Output) -> Result<Output> {
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
         warn!(
@@ -81,12 +277,16 @@ pub fn log_dry_run(program: &str, args: &[&str]) {
 ///
 /// When a recorder channel is set, every executed command is captured as an
 /// `OperationRecord` and sent through the channel.  This is used by the
-/// rehearsal system to produce a detailed execution log.  The recorder is
+/// rehearsal system to produce a detailed execution log, and in dry-run
+/// mode to capture the commands that would have run.  The recorder is
 /// opt-in and has zero overhead when not configured.
 pub struct CommandRunner {
     dry_run: bool,
     recorder: Option<Sender<OperationRecord>>,
     policy: Option<PolicyHandle>,
+    network_retries: u32,
+    output_cb: Option<OutputCallback>,
+    transcript: Option<Arc<Transcript>>,
 }
 
 impl CommandRunner {
@@ -95,9 +295,38 @@ impl CommandRunner {
             dry_run,
             recorder: None,
             policy: None,
+            network_retries: DEFAULT_NETWORK_RETRIES,
+            output_cb: None,
+            transcript: None,
         }
     }
 
+    /// Stream the output of [`Self::run_captured`] commands to `cb`, line
+    /// by line, as they run.
+    pub fn with_output_callback(mut self, cb: OutputCallback) -> Self {
+        self.output_cb = Some(cb);
+        self
+    }
+
+    /// Send a line to the output callback (e.g. the `--log-file`), if one
+    /// is set.
+    pub fn log_output(&self, line: &str) {
+        if let Some(ref cb) = self.output_cb {
+            cb(line);
+        }
+    }
+
+    /// Set how many times network-bound commands are retried.
+    pub fn with_network_retries(mut self, retries: u32) -> Self {
+        self.network_retries = retries;
+        self
+    }
+
+    /// Retry count for network-bound commands (package downloads).
+    pub fn network_retries(&self) -> u32 {
+        self.network_retries
+    }
+
     /// Attach a recording channel.  Every command execution will send an
     /// `OperationRecord` through the channel before returning.
     pub fn with_recorder(mut self, tx: Sender<OperationRecord>) -> Self {
@@ -105,6 +334,47 @@ impl CommandRunner {
         self
     }
 
+    /// Append every command that runs to `transcript` (see
+    /// [`crate::utils::transcript`]).  Dry runs write nothing.
+    pub fn with_transcript(mut self, transcript: Arc<Transcript>) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// The attached command transcript, if any.
+    pub fn transcript(&self) -> Option<&Arc<Transcript>> {
+        self.transcript.as_ref()
+    }
+
+    /// Note in the transcript a command run outside the runner because a
+    /// passphrase is written to its stdin.
+    pub fn transcribe_stdin(&self, program: &str, args: &[&str]) {
+        if let Some(ref transcript) = self.transcript {
+            if !self.dry_run {
+                transcript.command_with_stdin(program, args);
+            }
+        }
+    }
+
+    /// Note in the transcript a command run outside the runner with a
+    /// (non-secret) script on its stdin.
+    pub fn transcribe_with_input(&self, program: &str, args: &[&str], input: &str, exit_code: i32) {
+        if let Some(ref transcript) = self.transcript {
+            if !self.dry_run {
+                transcript.command_with_input(program, args, input, exit_code);
+            }
+        }
+    }
+
+    /// Append an executed command to the transcript; `None` means it could
+    /// not be started.
+    fn transcribe(&self, program: &str, args: &[&str], output: Option<&Output>) {
+        if let Some(ref transcript) = self.transcript {
+            let exit_code = output.map(|o| o.status.code().unwrap_or(-1));
+            transcript.command(program, args, exit_code);
+        }
+    }
+
     /// Attach an interactive policy that reviews user-facing pacman /
     /// basestrap / yay invocations before they run.  See
     /// `crate::utils::interactive` for the contract.
@@ -133,6 +403,28 @@ impl CommandRunner {
         }
     }
 
+    /// Print (and record) a command a dry run skips without going through
+    /// [`Self::run`], e.g. one fed a passphrase on stdin.
+    pub fn log_dry_run(&self, command: &str) {
+        println!("  [dry-run] {}", command);
+        self.record_dry_run(command);
+    }
+
+    /// Record a command that dry-run mode skipped, as a successful run
+    /// with no output, so dry runs can be compared command by command.
+    fn record_dry_run(&self, command_str: &str) {
+        if let Some(ref tx) = self.recorder {
+            let _ = tx.send(OperationRecord {
+                command: command_str.to_string(),
+                stdout: String::new(),
+                stderr: String::new(),
+                exit_code: 0,
+                duration: Duration::ZERO,
+                success: true,
+            });
+        }
+    }
+
     /// Record a failed command (one that could not be spawned at all).
     fn record_err(&self, command_str: &str, err: &DeploytixError, elapsed: Duration) {
         if let Some(ref tx) = self.recorder {
@@ -153,6 +445,7 @@ impl CommandRunner {
         }
         if self.dry_run {
             log_dry_run(program, args);
+            self.record_dry_run(&format!("{} {}", program, args.join(" ")));
             Ok(None)
         } else {
             let cmd_str = format!("{} {}", program, args.join(" "));
@@ -160,10 +453,12 @@ impl CommandRunner {
             match run_command(program, args) {
                 Ok(output) => {
                     self.record(&cmd_str, &output, start.elapsed());
+                    self.transcribe(program, args, Some(&output));
                     Ok(Some(output))
                 }
                 Err(e) => {
                     self.record_err(&cmd_str, &e, start.elapsed());
+                    self.transcribe(program, args, None);
                     Err(e)
                 }
             }
@@ -176,17 +471,21 @@ impl CommandRunner {
         }
         if self.dry_run {
             println!("  [dry-run] chroot {} bash -c '{}'", chroot_path, command);
+            self.record_dry_run(&format!("chroot {} bash -c '{}'", chroot_path, command));
             Ok(None)
         } else {
             let cmd_str = format!("chroot {} bash -c '{}'", chroot_path, command);
             let start = Instant::now();
+            let chroot_args = [chroot_path, "bash", "-c", command];
             match run_in_artix_chroot(chroot_path, command) {
                 Ok(output) => {
                     self.record(&cmd_str, &output, start.elapsed());
+                    self.transcribe("chroot", &chroot_args, Some(&output));
                     Ok(Some(output))
                 }
                 Err(e) => {
                     self.record_err(&cmd_str, &e, start.elapsed());
+                    self.transcribe("chroot", &chroot_args, None);
                     Err(e)
                 }
             }
@@ -198,6 +497,7 @@ impl CommandRunner {
     pub fn force_run(&self, program: &str, args: &[&str]) -> Result<Option<Output>> {
         if self.dry_run {
             log_dry_run(program, args);
+            self.record_dry_run(&format!("{} {}", program, args.join(" ")));
             Ok(None)
         } else {
             let cmd_str = format!("{} {}", program, args.join(" "));
@@ -205,16 +505,228 @@ impl CommandRunner {
             match run_command(program, args) {
                 Ok(output) => {
                     self.record(&cmd_str, &output, start.elapsed());
+                    self.transcribe(program, args, Some(&output));
                     Ok(Some(output))
                 }
                 Err(e) => {
                     self.record_err(&cmd_str, &e, start.elapsed());
+                    self.transcribe(program, args, None);
                     Err(e)
                 }
             }
         }
     }
 
+    /// Run a command, streaming each line of its stdout and stderr to the
+    /// output callback as it is printed (or to the debug log when none is
+    /// attached), and return the combined output.
+    ///
+    /// A non-zero exit fails with the combined output as the error text.
+    /// Used for long-running commands whose progress the user should see,
+    /// such as basestrap and pacman downloads.
+    pub fn run_captured(&self, program: &str, args: &[&str]) -> Result<String> {
+        self.run_captured_observed(program, args, &|_| {})
+    }
+
+    /// [`Self::run_captured`], also passing each line to `on_line` so the
+    /// caller can follow the command's progress.
+    pub fn run_captured_observed(
+        &self,
+        program: &str,
+        args: &[&str],
+        on_line: &dyn Fn(&str),
+    ) -> Result<String> {
+        if crate::utils::signal::is_interrupted() {
+            return Err(DeploytixError::Interrupted);
+        }
+        if self.dry_run {
+            log_dry_run(program, args);
+            self.record_dry_run(&format!("{} {}", program, args.join(" ")));
+            return Ok(String::new());
+        }
+
+        let cmd_str = format!("{} {}", program, args.join(" "));
+        let start = Instant::now();
+        match self.stream_command(program, args, on_line) {
+            Ok(output) => {
+                self.record(&cmd_str, &output, start.elapsed());
+                self.transcribe(program, args, Some(&output));
+                let combined = String::from_utf8_lossy(&output.stdout).into_owned();
+                if !output.status.success() {
+                    warn!("Command failed: {}", cmd_str);
+                    return Err(DeploytixError::CommandFailed {
+                        command: cmd_str,
+                        stderr: combined,
+                    });
+                }
+                Ok(combined)
+            }
+            Err(e) => {
+                self.record_err(&cmd_str, &e, start.elapsed());
+                self.transcribe(program, args, None);
+                Err(e)
+            }
+        }
+    }
+
+    /// [`Self::run_in_chroot`] with the output streaming of
+    /// [`Self::run_captured`].
+    pub fn run_in_chroot_captured(&self, chroot_path: &str, command: &str) -> Result<String> {
+        if self.dry_run {
+            println!("  [dry-run] chroot {} bash -c '{}'", chroot_path, command);
+            self.record_dry_run(&format!("chroot {} bash -c '{}'", chroot_path, command));
+            return Ok(String::new());
+        }
+        let program = if command_exists("artix-chroot") {
+            "artix-chroot"
+        } else {
+            "chroot"
+        };
+        self.run_captured(program, &[chroot_path, "bash", "-c", command])
+    }
+
+    /// Spawn a command and pass its output lines on as they arrive.  The
+    /// returned `Output` holds stdout and stderr interleaved in `stdout`.
+    fn stream_command(
+        &self,
+        program: &str,
+        args: &[&str],
+        on_line: &dyn Fn(&str),
+    ) -> Result<Output> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(program, e))?;
+
+        let (tx, rx) = mpsc::channel();
+        let mut readers = Vec::new();
+        if let Some(pipe) = child.stdout.take() {
+            let tx = tx.clone();
+            readers.push(thread::spawn(move || forward_lines(pipe, tx)));
+        }
+        if let Some(pipe) = child.stderr.take() {
+            let tx = tx.clone();
+            readers.push(thread::spawn(move || forward_lines(pipe, tx)));
+        }
+        drop(tx);
+
+        let mut combined = String::new();
+        for line in rx {
+            match self.output_cb {
+                Some(ref cb) => cb(&line),
+                None => debug!("{}", line),
+            }
+            on_line(&line);
+            combined.push_str(&line);
+            combined.push('\n');
+        }
+        for reader in readers {
+            let _ = reader.join();
+        }
+
+        Ok(Output {
+            status: child.wait()?,
+            stdout: combined.into_bytes(),
+            stderr: Vec::new(),
+        })
+    }
+
+    /// Run a command, re-running it up to `retries` more times if it fails
+    /// with a transient network error (see [`is_transient_error`]); any
+    /// other failure is returned at once.  The delay starts at `backoff`
+    /// and doubles per attempt.
+    /// Output is streamed as with [`Self::run_captured`].
+    ///
+    /// Only for idempotent commands such as package downloads — never for
+    /// destructive ones like `cryptsetup luksFormat` or `mkfs`.
+    pub fn run_with_retry(
+        &self,
+        program: &str,
+        args: &[&str],
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<String> {
+        self.run_with_retry_observed(program, args, retries, backoff, &|_| {})
+    }
+
+    /// [`Self::run_with_retry`] with the line observer of
+    /// [`Self::run_captured_observed`].  Lines of every attempt are passed
+    /// on.
+    pub fn run_with_retry_observed(
+        &self,
+        program: &str,
+        args: &[&str],
+        retries: u32,
+        backoff: Duration,
+        on_line: &dyn Fn(&str),
+    ) -> Result<String> {
+        let label = format!("{} {}", program, args.join(" "));
+        self.retry(&label, retries, backoff, || {
+            self.run_captured_observed(program, args, on_line)
+        })
+    }
+
+    /// [`Self::run_in_chroot_captured`] with the retry behaviour of
+    /// [`Self::run_with_retry`].
+    pub fn run_in_chroot_with_retry(
+        &self,
+        chroot_path: &str,
+        command: &str,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<String> {
+        self.retry(command, retries, backoff, || {
+            self.run_in_chroot_captured(chroot_path, command)
+        })
+    }
+
+    fn retry<T>(
+        &self,
+        label: &str,
+        retries: u32,
+        backoff: Duration,
+        mut attempt: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        let attempts = retries.saturating_add(1);
+        let mut delay = backoff;
+        for n in 1..=attempts {
+            match attempt() {
+                Ok(output) => {
+                    if n > 1 {
+                        info!("'{}' succeeded on attempt {}/{}", label, n, attempts);
+                    }
+                    return Ok(output);
+                }
+                // Only a network failure is worth retrying; a missing
+                // package, a conflict or an interrupt will not go away.
+                Err(DeploytixError::CommandFailed { ref stderr, .. })
+                    if n < attempts && is_transient_error(stderr) =>
+                {
+                    warn!(
+                        "'{}' failed (attempt {}/{}): {}",
+                        label,
+                        n,
+                        attempts,
+                        // The cause is usually the last thing printed
+                        stderr
+                            .lines()
+                            .rev()
+                            .find(|l| !l.trim().is_empty())
+                            .unwrap_or("(no details)")
+                    );
+                    warn!("Retrying in {} seconds...", delay.as_secs());
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!("the final attempt always returns")
+    }
+
     pub fn is_dry_run(&self) -> bool {
         self.dry_run
     }
@@ -268,3 +780,104 @@ impl CommandRunner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn dry_run_records_skipped_commands_in_order() {
+        let (tx, rx) = mpsc::channel();
+        let cmd = CommandRunner::new(true).with_recorder(tx);
+        cmd.run("wipefs", &["-a", "/dev/vda1"]).unwrap();
+        cmd.log_dry_run("cryptsetup open /dev/vda2 Crypt-Root");
+        cmd.run_in_chroot("/install", "mkinitcpio -P").unwrap();
+        let commands: Vec<String> = rx.try_iter().map(|r| r.command).collect();
+        assert_eq!(
+            commands,
+            [
+                "wipefs -a /dev/vda1",
+                "cryptsetup open /dev/vda2 Crypt-Root",
+                "chroot /install bash -c 'mkinitcpio -P'",
+            ]
+        );
+    }
+
+    #[test]
+    fn retry_reruns_only_transient_failures() {
+        let (tx, rx) = mpsc::channel();
+        let cmd = CommandRunner::new(false).with_recorder(tx);
+        let script = "echo 'error: failed retrieving file core.db' >&2; exit 1";
+        assert!(cmd
+            .run_with_retry("sh", &["-c", script], 2, Duration::ZERO)
+            .is_err());
+        assert_eq!(rx.try_iter().count(), 3);
+
+        assert!(cmd.run_with_retry("false", &[], 2, Duration::ZERO).is_err());
+        assert_eq!(rx.try_iter().count(), 1);
+
+        assert!(is_transient_error("curl: Could not resolve host: mirror"));
+        assert!(!is_transient_error("error: target not found: foo"));
+    }
+
+    #[test]
+    fn timeout_kills_hung_command() {
+        let start = Instant::now();
+        let result = run_command_with_timeout("sleep", &["30"], Duration::from_millis(200));
+        assert!(matches!(result, Err(DeploytixError::Timeout { .. })));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let output = run_command_with_timeout("echo", &["done"], Duration::from_secs(5)).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "done");
+    }
+
+    #[test]
+    fn captured_output_is_streamed_and_returned() {
+        let lines = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        let cmd = CommandRunner::new(false).with_output_callback(Box::new(move |l| {
+            sink.lock().unwrap().push(l.to_string());
+        }));
+
+        let out = cmd
+            .run_captured(
+                "sh",
+                &["-c", "echo one; printf '10%%\\r100%%\\n'; echo two >&2"],
+            )
+            .unwrap();
+        let mut streamed = lines.lock().unwrap().clone();
+        streamed.sort();
+        assert_eq!(streamed, vec!["100%", "one", "two"]);
+        assert!(out.contains("one\n") && out.contains("two\n"));
+
+        match cmd.run_captured("sh", &["-c", "echo broken >&2; exit 1"]) {
+            Err(DeploytixError::CommandFailed { stderr, .. }) => assert_eq!(stderr, "broken\n"),
+            other => panic!("expected CommandFailed, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn observed_wait_passes_stdout_lines_on() {
+        let lines = std::cell::RefCell::new(Vec::new());
+        let child = Command::new("sh")
+            .args(["-c", "echo 10%; echo 100%; exit 3"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let output = wait_with_timeout_observed(child, "sh", Some(Duration::from_secs(10)), &|l| {
+            lines.borrow_mut().push(l.to_string())
+        })
+        .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(lines.into_inner(), vec!["10%", "100%"]);
+    }
+
+    #[test]
+    fn retry_stops_after_success() {
+        let (tx, rx) = mpsc::channel();
+        let cmd = CommandRunner::new(false).with_recorder(tx);
+        assert!(cmd.run_with_retry("true", &[], 2, Duration::ZERO).is_ok());
+        assert_eq!(rx.try_iter().count(), 1);
+    }
+}
@@ -1,11 +1,11 @@
 //! Host system dependency checking and installation
 
-use crate::config::{Bootloader, Filesystem};
+use crate::config::{Bootloader, DeploymentConfig, Filesystem, SecureBootMethod};
 use crate::utils::command::CommandRunner;
-use crate::utils::error::Result;
+use crate::utils::error::{DeploytixError, Result};
 use std::collections::HashMap;
 use std::process::Command;
-use tracing::info;
+use tracing::{info, warn};
 
 /// Binary to package mapping for Artix/Arch
 fn binary_to_package() -> HashMap<&'static str, &'static str> {
@@ -34,10 +34,14 @@ fn binary_to_package() -> HashMap<&'static str, &'static str> {
     // Bootloaders
     map.insert("grub-install", "grub");
     map.insert("grub-mkconfig", "grub");
+    map.insert("refind-install", "refind");
 
     // Artix tools
     map.insert("basestrap", "artools");
 
+    // Optional diagnostics
+    map.insert("smartctl", "smartmontools");
+
     map
 }
 
@@ -50,10 +54,21 @@ fn binary_exists(name: &str) -> bool {
         .unwrap_or(false)
 }
 
+/// Check for an optional tool that enhances but never gates the install.
+///
+/// Logs which package would provide it when missing.
+pub fn optional_binary_available(name: &str) -> bool {
+    if binary_exists(name) {
+        return true;
+    }
+    let pkg = binary_to_package().get(name).copied().unwrap_or("unknown");
+    info!("{} not found (package: {}), skipping", name, pkg);
+    false
+}
+
 /// Determine required binaries based on configuration
 pub fn required_binaries(
-    filesystem: &Filesystem,
-    boot_filesystem: &Filesystem,
+    filesystems: &[Filesystem],
     encryption: bool,
     use_lvm_thin: bool,
     bootloader: &Bootloader,
@@ -66,23 +81,15 @@ pub fn required_binaries(
         "basestrap",
     ];
 
-    // Data filesystem tool
-    match filesystem {
-        Filesystem::Ext4 => bins.push("mkfs.ext4"),
-        Filesystem::Btrfs => bins.push("mkfs.btrfs"),
-        Filesystem::Xfs => bins.push("mkfs.xfs"),
-        Filesystem::Zfs => bins.push("zpool"),
-        Filesystem::F2fs => bins.push("mkfs.f2fs"),
-    }
-
-    // Boot filesystem tool (only add if different from data filesystem)
-    match boot_filesystem {
-        Filesystem::Ext4 if filesystem != &Filesystem::Ext4 => bins.push("mkfs.ext4"),
-        Filesystem::Btrfs if filesystem != &Filesystem::Btrfs => bins.push("mkfs.btrfs"),
-        Filesystem::Xfs if filesystem != &Filesystem::Xfs => bins.push("mkfs.xfs"),
-        Filesystem::Zfs if filesystem != &Filesystem::Zfs => bins.push("zpool"),
-        Filesystem::F2fs if filesystem != &Filesystem::F2fs => bins.push("mkfs.f2fs"),
-        _ => {} // already covered by data filesystem match
+    // One mkfs tool per filesystem in use (data, boot and overrides)
+    for filesystem in filesystems {
+        bins.push(match filesystem {
+            Filesystem::Ext4 => "mkfs.ext4",
+            Filesystem::Btrfs => "mkfs.btrfs",
+            Filesystem::Xfs => "mkfs.xfs",
+            Filesystem::Zfs => "zpool",
+            Filesystem::F2fs => "mkfs.f2fs",
+        });
     }
 
     // Encryption
@@ -103,6 +110,9 @@ pub fn required_binaries(
             bins.push("grub-install");
             bins.push("grub-mkconfig");
         }
+        Bootloader::Refind => {
+            bins.push("refind-install");
+        }
     }
 
     bins
@@ -110,19 +120,12 @@ pub fn required_binaries(
 
 /// Check for missing dependencies and return list of missing packages
 pub fn check_dependencies(
-    filesystem: &Filesystem,
-    boot_filesystem: &Filesystem,
+    filesystems: &[Filesystem],
     encryption: bool,
     use_lvm_thin: bool,
     bootloader: &Bootloader,
 ) -> Vec<String> {
-    let required = required_binaries(
-        filesystem,
-        boot_filesystem,
-        encryption,
-        use_lvm_thin,
-        bootloader,
-    );
+    let required = required_binaries(filesystems, encryption, use_lvm_thin, bootloader);
     let bin_to_pkg = binary_to_package();
 
     let mut missing_packages: Vec<String> = Vec::new();
@@ -143,24 +146,18 @@ pub fn check_dependencies(
     missing_packages
 }
 
-/// Check dependencies and optionally install missing packages
-/// Returns Ok(()) if all dependencies are satisfied (or were installed)
-/// Returns Err if dependencies are missing and user declined to install
+/// Check dependencies and report what is missing.
+///
+/// Prints each missing binary with its providing package and returns the
+/// packages to install (empty when everything is present).  Installing
+/// them is left to the caller, see [`install_dependencies`].
 pub fn ensure_dependencies(
-    cmd: &CommandRunner,
-    filesystem: &Filesystem,
-    boot_filesystem: &Filesystem,
+    filesystems: &[Filesystem],
     encryption: bool,
     use_lvm_thin: bool,
     bootloader: &Bootloader,
-) -> Result<()> {
-    let required = required_binaries(
-        filesystem,
-        boot_filesystem,
-        encryption,
-        use_lvm_thin,
-        bootloader,
-    );
+) -> Vec<String> {
+    let required = required_binaries(filesystems, encryption, use_lvm_thin, bootloader);
     let bin_to_pkg = binary_to_package();
 
     // Collect missing binaries with their providing packages
@@ -179,7 +176,7 @@ pub fn ensure_dependencies(
 
     if missing_details.is_empty() {
         info!("All required host dependencies are installed");
-        return Ok(());
+        return missing_packages;
     }
 
     println!("\n⚠ Missing host system dependencies:");
@@ -189,43 +186,225 @@ pub fn ensure_dependencies(
     println!("\nPackages to install: {}", missing_packages.join(" "));
     println!();
 
+    missing_packages
+}
+
+/// Whether missing packages can be installed on this host at all.
+pub fn can_install_dependencies(packages: &[String]) -> bool {
+    binary_exists("pacman") && !packages.iter().any(|p| p == "unknown")
+}
+
+/// Install missing host packages with `pacman -S`.
+pub fn install_dependencies(cmd: &CommandRunner, packages: &[String]) -> Result<()> {
     if cmd.is_dry_run() {
         println!(
             "[dry-run] Would install: pacman -S --noconfirm {}",
-            missing_packages.join(" ")
+            packages.join(" ")
         );
         return Ok(());
     }
 
-    // Install missing packages automatically
     println!("Installing missing packages...");
     let status = Command::new("pacman")
         .args(["-S", "--noconfirm"])
-        .args(&missing_packages)
+        .args(packages)
         .status()?;
 
     if !status.success() {
         return Err(crate::utils::error::DeploytixError::CommandFailed {
-            command: format!("pacman -S {}", missing_packages.join(" ")),
+            command: format!("pacman -S {}", packages.join(" ")),
             stderr: format!("Exit code: {:?}", status.code()),
         });
     }
 
-    // Verify installation
-    let still_missing = check_dependencies(
-        filesystem,
-        boot_filesystem,
-        encryption,
-        use_lvm_thin,
-        bootloader,
-    );
-    if !still_missing.is_empty() {
-        return Err(crate::utils::error::DeploytixError::ConfigError(format!(
-            "Failed to install some dependencies: {}",
-            still_missing.join(", ")
-        )));
+    info!("Installed missing dependencies: {}", packages.join(" "));
+    Ok(())
+}
+
+/// A minimum tool version needed by an optional feature.
+struct VersionGate {
+    /// Feature that needs the version, for the error message.
+    feature: &'static str,
+    /// Tool (or package) whose version is checked.
+    tool: &'static str,
+    minimum: &'static str,
+}
+
+/// `cryptsetup luksFormat --integrity` arrived with LUKS2 in 2.0.0.
+const CRYPTSETUP_INTEGRITY: VersionGate = VersionGate {
+    feature: "dm-integrity",
+    tool: "cryptsetup",
+    minimum: "2.0.0",
+};
+
+/// Thin provisioning landed in LVM 2.02.89.
+const LVM_THIN: VersionGate = VersionGate {
+    feature: "LVM thin provisioning",
+    tool: "lvm2",
+    minimum: "2.02.89",
+};
+
+/// `sbctl enroll-keys --microsoft` needs sbctl 0.9.
+const SBCTL_MICROSOFT_KEYS: VersionGate = VersionGate {
+    feature: "SecureBoot (sbctl)",
+    tool: "sbctl",
+    minimum: "0.9",
+};
+
+/// Leading dotted number of `text`, e.g. "2.03.22(2)" -> [2, 3, 22].
+fn parse_version(text: &str) -> Option<Vec<u32>> {
+    let token = text
+        .split_whitespace()
+        .find(|t| t.starts_with(|c: char| c.is_ascii_digit()) && t.contains('.'))?;
+    let numeric: String = token
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    numeric
+        .split('.')
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse().ok())
+        .collect()
+}
+
+/// Compare dotted versions, treating missing components as 0.
+fn version_at_least(found: &[u32], minimum: &[u32]) -> bool {
+    let len = found.len().max(minimum.len());
+    let pad = |v: &[u32]| {
+        (0..len)
+            .map(|i| v.get(i).copied().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+    pad(found) >= pad(minimum)
+}
+
+/// Combined stdout and stderr of a command, if it ran at all.
+fn command_text(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    Some(format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+/// Version reported by a tool (or, for tools that only run inside the
+/// target, by the sync database basestrap will install from).
+fn tool_version(tool: &str) -> Option<String> {
+    match tool {
+        "cryptsetup" => command_text("cryptsetup", &["--version"]),
+        // "  LVM version:     2.03.22(2) (2023-08-02)"
+        "lvm2" => command_text("lvm", &["version"])?
+            .lines()
+            .find(|l| l.trim_start().starts_with("LVM version"))
+            .and_then(|l| l.split_once(':'))
+            .map(|(_, v)| v.to_string()),
+        // "Version         : 0.14-1"
+        _ => command_text("pacman", &["-Si", tool])?
+            .lines()
+            .find(|l| l.starts_with("Version"))
+            .and_then(|l| l.split_once(':'))
+            .map(|(_, v)| v.to_string()),
+    }
+}
+
+/// Check `version_text` against `gate`, returning the failure message.
+fn gate_failure(gate: &VersionGate, version_text: &str) -> Option<String> {
+    let minimum = parse_version(gate.minimum)?;
+    let found = parse_version(version_text)?;
+    if version_at_least(&found, &minimum) {
+        return None;
     }
+    let found = found
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(".");
+    Some(format!(
+        "{} {} does not support {}; version {} or newer is required",
+        gate.tool, found, gate.feature, gate.minimum
+    ))
+}
 
-    info!("Successfully installed missing dependencies");
-    Ok(())
+/// Check the versions of tools needed by the enabled optional features
+/// (dm-integrity, LVM thin, sbctl SecureBoot).
+///
+/// Presence is checked by [`ensure_dependencies`]; a version that cannot
+/// be determined is only warned about.
+pub fn check_feature_versions(config: &DeploymentConfig) -> Result<()> {
+    let mut gates = Vec::new();
+    if config.disk.encryption && config.disk.integrity {
+        gates.push(&CRYPTSETUP_INTEGRITY);
+    }
+    if config.disk.use_lvm_thin {
+        gates.push(&LVM_THIN);
+    }
+    if config.system.secureboot && config.system.secureboot_method == SecureBootMethod::Sbctl {
+        gates.push(&SBCTL_MICROSOFT_KEYS);
+    }
+
+    let mut failures = Vec::new();
+    for gate in gates {
+        let Some(text) = tool_version(gate.tool) else {
+            warn!(
+                "Could not determine the {} version; {} needs {} or newer",
+                gate.tool, gate.feature, gate.minimum
+            );
+            continue;
+        };
+        match gate_failure(gate, &text) {
+            Some(failure) => failures.push(failure),
+            None => info!("{} version is sufficient for {}", gate.tool, gate.feature),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(DeploytixError::DependencyTooOld(failures.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_binaries_cover_every_filesystem() {
+        let filesystems = [Filesystem::Ext4, Filesystem::Xfs];
+        let bins = required_binaries(&filesystems, false, false, &Bootloader::Grub);
+        assert!(bins.contains(&"mkfs.ext4"));
+        assert!(bins.contains(&"mkfs.xfs"));
+        assert!(!bins.contains(&"mkfs.btrfs"));
+    }
+
+    #[test]
+    fn parses_tool_version_output() {
+        assert_eq!(
+            parse_version("cryptsetup 2.7.0 flags: UDEV BLKID"),
+            Some(vec![2, 7, 0])
+        );
+        assert_eq!(
+            parse_version(" 2.03.22(2) (2023-08-02)"),
+            Some(vec![2, 3, 22])
+        );
+        assert_eq!(parse_version(" 0.14-1"), Some(vec![0, 14]));
+        assert_eq!(parse_version("no version here"), None);
+    }
+
+    #[test]
+    fn compares_versions_numerically() {
+        assert!(version_at_least(&[2, 3, 22], &[2, 2, 89]));
+        assert!(version_at_least(&[0, 10], &[0, 9]));
+        assert!(version_at_least(&[2, 0], &[2, 0, 0]));
+        assert!(!version_at_least(&[1, 7, 5], &[2, 0, 0]));
+    }
+
+    #[test]
+    fn old_cryptsetup_names_required_version() {
+        let failure = gate_failure(&CRYPTSETUP_INTEGRITY, "cryptsetup 1.7.5").unwrap();
+        assert!(failure.contains("cryptsetup 1.7.5"));
+        assert!(failure.contains("2.0.0 or newer"));
+        assert!(gate_failure(&CRYPTSETUP_INTEGRITY, "cryptsetup 2.6.1").is_none());
+    }
 }
@@ -17,6 +17,11 @@ pub enum DeploytixError {
     #[error("Device is currently mounted: {0}")]
     DeviceMounted(String),
 
+    #[error(
+        "Refusing to install onto the running system's disk: {0} (use --force-target to override)"
+    )]
+    RunningSystemDisk(String),
+
     #[error("Disk too small: {size_mib}MiB < required minimum {required_mib}MiB")]
     DiskTooSmall { size_mib: u64, required_mib: u64 },
 
@@ -32,9 +37,30 @@ pub enum DeploytixError {
     #[error("Chroot error: {0}")]
     ChrootError(String),
 
+    #[error("runtime dir {path} is not writable ({reason}), use --runtime-dir")]
+    RuntimeDirUnusable { path: String, reason: String },
+
+    #[error("Could not determine installed RAM: {0}")]
+    RamUndetectable(String),
+
+    #[error("Pre-flight checks failed:{0}")]
+    PreflightFailed(String),
+
+    #[error("Bootability check failed: {0}")]
+    BootCheckFailed(String),
+
     #[error("Command failed: {command}\n{stderr}")]
     CommandFailed { command: String, stderr: String },
 
+    #[error("Command timed out after {secs}s: {command}")]
+    Timeout { command: String, secs: u64 },
+
+    #[error("Missing host dependencies: {0} (install with: pacman -S {0})")]
+    MissingDependencies(String),
+
+    #[error("Host tool too old: {0}")]
+    DependencyTooOld(String),
+
     #[error("Command not found: {0}")]
     CommandNotFound(String),
 
@@ -63,4 +89,64 @@ pub enum DeploytixError {
     Nix(#[from] nix::Error),
 }
 
+impl DeploytixError {
+    /// Process exit status for this error.  The codes are stable so
+    /// wrapper scripts can tell failures apart; 1 is left for errors that
+    /// are not a `DeploytixError` and 2 for command-line usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::UserCancelled => 3,
+            Self::NotRoot => 4,
+            Self::DeviceNotFound(_) => 10,
+            Self::NotBlockDevice(_) => 11,
+            Self::DeviceMounted(_) => 12,
+            Self::RunningSystemDisk(_) => 13,
+            Self::DiskTooSmall { .. } => 14,
+            Self::PreflightFailed(_) => 15,
+            Self::ConfigError(_) => 20,
+            Self::ValidationError(_) => 21,
+            Self::TomlParse(_) => 22,
+            Self::TomlSerialize(_) => 23,
+            Self::MissingDependencies(_) => 30,
+            Self::DependencyTooOld(_) => 31,
+            Self::CommandNotFound(_) => 32,
+            Self::RuntimeDirUnusable { .. } => 33,
+            Self::RamUndetectable(_) => 34,
+            Self::PartitionError(_) => 40,
+            Self::FilesystemError(_) => 41,
+            Self::MountError(_) => 42,
+            Self::ChrootError(_) => 43,
+            Self::CommandFailed { .. } => 44,
+            Self::Timeout { .. } => 45,
+            Self::BootCheckFailed(_) => 46,
+            Self::Io(_) => 50,
+            Self::Nix(_) => 51,
+            // 128 + SIGINT, as a shell reports a Ctrl-C
+            Self::Interrupted => 130,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, DeploytixError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancellation_is_distinct_from_failures() {
+        let cancelled = DeploytixError::UserCancelled.exit_code();
+        for err in [
+            DeploytixError::NotRoot,
+            DeploytixError::DiskTooSmall {
+                size_mib: 1,
+                required_mib: 2,
+            },
+            DeploytixError::ConfigError(String::new()),
+            DeploytixError::Interrupted,
+        ] {
+            assert_ne!(err.exit_code(), cancelled, "{}", err);
+            assert!(err.exit_code() > 2, "{}", err);
+        }
+    }
+}
@@ -5,5 +5,7 @@ pub mod command;
 pub mod deps;
 pub mod error;
 pub mod interactive;
+pub mod password;
 pub mod prompt;
 pub mod signal;
+pub mod transcript;
@@ -0,0 +1,103 @@
+//! Password strength estimation
+//!
+//! A deliberately simple score from length and character classes, in the
+//! spirit of zxcvbn's buckets: enough to flag a short or trivially
+//! repetitive passphrase before it locks away a whole disk.
+
+/// Coarse password strength
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PasswordStrength {
+    Weak,
+    Fair,
+    Good,
+    Strong,
+}
+
+/// Weakest strength accepted for the disk encryption passphrase.
+pub const MIN_ENCRYPTION_STRENGTH: PasswordStrength = PasswordStrength::Fair;
+
+impl PasswordStrength {
+    /// Score `password`: one point each for 8, 12 and 16 characters and
+    /// one for mixing at least three character classes.  Anything shorter
+    /// than 8 characters or built from fewer than 4 distinct characters
+    /// is weak.
+    pub fn of(password: &str) -> Self {
+        let len = password.chars().count();
+        let mut distinct: Vec<char> = password.chars().collect();
+        distinct.sort_unstable();
+        distinct.dedup();
+        if len < 8 || distinct.len() < 4 {
+            return Self::Weak;
+        }
+
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ]
+        .iter()
+        .filter(|&&present| present)
+        .count();
+
+        let points =
+            1 + usize::from(len >= 12) + usize::from(len >= 16) + usize::from(classes >= 3);
+        match points {
+            1 => Self::Weak,
+            2 => Self::Fair,
+            3 => Self::Good,
+            _ => Self::Strong,
+        }
+    }
+
+    /// Fraction of the meter to fill
+    pub fn fraction(&self) -> f32 {
+        match self {
+            Self::Weak => 0.25,
+            Self::Fair => 0.5,
+            Self::Good => 0.75,
+            Self::Strong => 1.0,
+        }
+    }
+}
+
+impl std::fmt::Display for PasswordStrength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Weak => write!(f, "weak"),
+            Self::Fair => write!(f, "fair"),
+            Self::Good => write!(f, "good"),
+            Self::Strong => write!(f, "strong"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_or_repetitive_passwords_are_weak() {
+        assert_eq!(PasswordStrength::of("Ab1!"), PasswordStrength::Weak);
+        assert_eq!(
+            PasswordStrength::of("aaaaaaaaaaaaaaaaaaaa"),
+            PasswordStrength::Weak
+        );
+        assert_eq!(PasswordStrength::of("password"), PasswordStrength::Weak);
+    }
+
+    #[test]
+    fn length_and_classes_raise_the_score() {
+        assert_eq!(PasswordStrength::of("Passw0rd"), PasswordStrength::Fair);
+        assert_eq!(PasswordStrength::of("correcthorse"), PasswordStrength::Fair);
+        assert_eq!(
+            PasswordStrength::of("correcthorsebattery"),
+            PasswordStrength::Good
+        );
+        assert_eq!(
+            PasswordStrength::of("Correct-horse-battery-9"),
+            PasswordStrength::Strong
+        );
+        assert!(PasswordStrength::of("Passw0rd") >= MIN_ENCRYPTION_STRENGTH);
+    }
+}
@@ -1,6 +1,7 @@
 //! User prompt utilities using dialoguer
 
 use crate::utils::error::{DeploytixError, Result};
+use crate::utils::password::PasswordStrength;
 use dialoguer::{theme::ColorfulTheme, Confirm, FuzzySelect, Input, MultiSelect, Password, Select};
 
 /// Prompt for text input
@@ -31,6 +32,25 @@ pub fn prompt_password(prompt: &str, confirm: bool) -> Result<String> {
         .map_err(|_| DeploytixError::UserCancelled)
 }
 
+/// Prompt for a new password with confirmation, warning when it scores
+/// weak and asking again unless the user keeps it.
+pub fn prompt_new_password(prompt: &str) -> Result<String> {
+    loop {
+        let password = prompt_password(prompt, true)?;
+        let strength = PasswordStrength::of(&password);
+        if strength > PasswordStrength::Weak {
+            return Ok(password);
+        }
+        if warn_confirm(&format!(
+            "This password is {}: use at least 8 characters, ideally 12+ with mixed \
+             letters, digits and symbols.  Keep it anyway?",
+            strength
+        ))? {
+            return Ok(password);
+        }
+    }
+}
+
 /// Prompt for yes/no confirmation
 pub fn prompt_confirm(prompt: &str, default: bool) -> Result<bool> {
     let theme = ColorfulTheme::default();
@@ -9,6 +9,8 @@
 //!   immediate termination.
 
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tracing::warn;
 
 /// Set to `true` by the signal handler on the first SIGINT/SIGTERM.
 static INTERRUPTED: AtomicBool = AtomicBool::new(false);
@@ -70,6 +72,23 @@ pub fn is_interrupted() -> bool {
     INTERRUPTED.load(Ordering::SeqCst)
 }
 
+/// Stop `pid` (a whole process group when negative): SIGTERM first, then
+/// SIGKILL if `running` still reports it alive after a short grace period.
+pub fn terminate(pid: i32, mut running: impl FnMut() -> bool) {
+    unsafe {
+        libc::kill(pid, libc::SIGTERM);
+    }
+    // Give it a moment to exit
+    std::thread::sleep(Duration::from_millis(500));
+    if running() {
+        warn!("SIGTERM did not stop PID {}, sending SIGKILL", pid);
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
 /// Re-raise the caught signal with the default handler so the process exits
 /// with the correct signal status (visible to the parent shell).
 ///
@@ -0,0 +1,239 @@
+//! Shell transcript of the commands an installation ran
+//!
+//! Every command the [`CommandRunner`](crate::utils::command::CommandRunner)
+//! executes is appended as a quoted shell line, so the deployment can be
+//! reviewed, attached to a bug report or replayed step by step.  Secrets
+//! never reach the file: registered passwords are masked wherever they
+//! appear, key-file arguments are replaced, and input fed on stdin is only
+//! noted.
+
+use crate::utils::error::Result;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// Replacement for redacted secrets.
+pub const REDACTED: &str = "<redacted>";
+
+/// Options whose value names a key file or carries a secret.
+const SECRET_OPTIONS: &[&str] = &[
+    "--key-file",
+    "--keyfile",
+    "--new-keyfile",
+    "--password",
+    "--passphrase",
+];
+
+/// File name of the transcript in the runtime dir and in `/root` of the
+/// installed system.
+pub const TRANSCRIPT_NAME: &str = "deploytix-install.sh";
+
+/// Append-only shell transcript.
+pub struct Transcript {
+    path: PathBuf,
+    file: Mutex<File>,
+    secrets: Vec<String>,
+}
+
+impl Transcript {
+    /// Create (truncate) the transcript at `path`, readable by root only,
+    /// and write the header.  `secrets` are masked in every line.
+    pub fn create(path: &Path, target: &str, secrets: Vec<String>) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        writeln!(file, "#!/bin/sh")?;
+        writeln!(
+            file,
+            "# Commands run by deploytix {} installing onto {}",
+            env!("CARGO_PKG_VERSION"),
+            target
+        )?;
+        writeln!(
+            file,
+            "# Secrets are shown as {}; passphrases fed on stdin are not recorded.",
+            REDACTED
+        )?;
+        writeln!(
+            file,
+            "# Review before re-running: it repartitions and formats the target."
+        )?;
+        writeln!(file)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            secrets: secrets.into_iter().filter(|s| !s.is_empty()).collect(),
+        })
+    }
+
+    /// Path of the transcript file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a command and, when it did not succeed, its exit status.
+    pub fn command(&self, program: &str, args: &[&str], exit_code: Option<i32>) {
+        let mut line = self.render(program, args);
+        match exit_code {
+            Some(0) => {}
+            Some(code) => line.push_str(&format!("  # exit {}", code)),
+            None => line.push_str("  # failed to start"),
+        }
+        self.write_line(&line);
+    }
+
+    /// Append a command whose secret input was written to its stdin.
+    pub fn command_with_stdin(&self, program: &str, args: &[&str]) {
+        let line = format!("{}  # passphrase on stdin", self.render(program, args));
+        self.write_line(&line);
+    }
+
+    /// Append a command that read a non-secret script on stdin (e.g. the
+    /// sfdisk partition table) as a here-document.
+    pub fn command_with_input(&self, program: &str, args: &[&str], input: &str, exit_code: i32) {
+        let mut line = format!("{} <<'EOF'", self.render(program, args));
+        if exit_code != 0 {
+            line.push_str(&format!("  # exit {}", exit_code));
+        }
+        line.push('\n');
+        line.push_str(input.trim_end());
+        line.push_str("\nEOF");
+        self.write_line(&line);
+    }
+
+    fn render(&self, program: &str, args: &[&str]) -> String {
+        std::iter::once(program.to_string())
+            .chain(redact_args(args, &self.secrets))
+            .map(|word| shell_quote(&word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn write_line(&self, line: &str) {
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        if let Err(e) = writeln!(file, "{}", line) {
+            warn!("Failed to write command transcript: {}", e);
+        }
+    }
+
+    /// Copy the transcript to `dest` (root-only), e.g. into the installed
+    /// system.
+    pub fn copy_to(&self, dest: &Path) -> Result<()> {
+        if let Ok(mut file) = self.file.lock() {
+            file.flush()?;
+        }
+        if let Some(dir) = dest.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::copy(&self.path, dest)?;
+        fs::set_permissions(dest, std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        Ok(())
+    }
+}
+
+/// Mask `secrets` wherever they appear in `args` and replace the value of
+/// key-file and password options.
+pub fn redact_args(args: &[&str], secrets: &[String]) -> Vec<String> {
+    let mut out = Vec::with_capacity(args.len());
+    let mut redact_next = false;
+    for arg in args {
+        if redact_next {
+            out.push(REDACTED.to_string());
+            redact_next = false;
+            continue;
+        }
+        if let Some((option, _)) = arg.split_once('=') {
+            if SECRET_OPTIONS.contains(&option) {
+                out.push(format!("{}={}", option, REDACTED));
+                continue;
+            }
+        }
+        redact_next = SECRET_OPTIONS.contains(arg);
+        let mut masked = arg.to_string();
+        for secret in secrets {
+            masked = masked.replace(secret.as_str(), REDACTED);
+        }
+        out.push(masked);
+    }
+    out
+}
+
+/// Quote `word` for a POSIX shell when it is not a plain word.
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_words_are_not_quoted() {
+        assert_eq!(shell_quote("/dev/vda1"), "/dev/vda1");
+        assert_eq!(shell_quote("subvol=@,noatime"), "subvol=@,noatime");
+        assert_eq!(shell_quote("mkinitcpio -P"), "'mkinitcpio -P'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn key_files_and_passwords_are_redacted() {
+        let secrets = vec!["hunter2".to_string()];
+        assert_eq!(
+            redact_args(
+                &["open", "--key-file", "/etc/root.key", "/dev/vda2", "root"],
+                &secrets
+            ),
+            ["open", "--key-file", REDACTED, "/dev/vda2", "root"]
+        );
+        assert_eq!(
+            redact_args(&["--password=hunter2", "create", "-d", "rpool"], &secrets),
+            ["--password=<redacted>", "create", "-d", "rpool"]
+        );
+        assert_eq!(
+            redact_args(&["-c", "echo 'user:hunter2' | chpasswd"], &secrets),
+            ["-c", "echo 'user:<redacted>' | chpasswd"]
+        );
+    }
+
+    #[test]
+    fn transcript_is_a_quoted_script_with_failures_marked() {
+        let path = std::env::temp_dir().join(format!(
+            "deploytix-transcript-test-{}.sh",
+            std::process::id()
+        ));
+        let transcript = Transcript::create(&path, "/dev/vda", vec!["s3cret".into()]).unwrap();
+        transcript.command("mkfs.ext4", &["-F", "-L", "ROOT", "/dev/vda2"], Some(0));
+        transcript.command("chroot", &["/install", "bash", "-c", "x s3cret"], Some(1));
+        transcript.command_with_stdin("cryptsetup", &["open", "/dev/vda3", "Crypt-Root"]);
+        transcript.command_with_input("sfdisk", &["/dev/vda"], "label: gpt\n", 0);
+        let content = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert!(content.starts_with("#!/bin/sh\n"));
+        assert!(content.contains("\nmkfs.ext4 -F -L ROOT /dev/vda2\n"));
+        assert!(content.contains("\nchroot /install bash -c 'x <redacted>'  # exit 1\n"));
+        assert!(content.contains("\ncryptsetup open /dev/vda3 Crypt-Root  # passphrase on stdin\n"));
+        assert!(content.ends_with("\nsfdisk /dev/vda <<'EOF'\nlabel: gpt\nEOF\n"));
+        assert!(!content.contains("s3cret"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Golden tests for the installer pipeline: run a dry-run install of each
+//! config in `tests/golden/` against a simulated disk and compare the
+//! ordered commands it would execute with `<name>.commands`.
+//!
+//! After an intended pipeline change, regenerate the snapshots with
+//! `UPDATE_GOLDEN=1 cargo test --test dry_run_golden` and review the diff.
+
+use deploytix::config::DeploymentConfig;
+use deploytix::disk::detection::simulate_ram_mib;
+use deploytix::install::Installer;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+/// Disk size every golden config is planned for (256 GiB).
+const DISK_MIB: u64 = 256 * 1024;
+
+/// RAM the swap sizing sees, so snapshots do not depend on the host.
+const RAM_MIB: u64 = 8192;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Commands a dry-run install of `tests/golden/<name>.toml` would run.
+fn dry_run_commands(name: &str) -> Vec<String> {
+    simulate_ram_mib(RAM_MIB).unwrap();
+    let path = golden_dir().join(format!("{}.toml", name));
+    let config = DeploymentConfig::from_file(path.to_str().unwrap()).unwrap();
+    config.validate_settings().unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    Installer::new(config, true)
+        .with_skip_confirm(true)
+        .with_simulated_disk(DISK_MIB)
+        .with_recorder(tx)
+        .run()
+        .unwrap_or_else(|e| panic!("dry run of {} failed: {}", name, e));
+    rx.try_iter().map(|rec| rec.command).collect()
+}
+
+fn assert_golden(name: &str) {
+    let actual = dry_run_commands(name).join("\n") + "\n";
+    let golden = golden_dir().join(format!("{}.commands", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&golden).unwrap_or_else(|e| {
+        panic!(
+            "cannot read {} ({}); run with UPDATE_GOLDEN=1 to create it",
+            golden.display(),
+            e
+        )
+    });
+    if actual != expected {
+        let first_diff = actual
+            .lines()
+            .zip(expected.lines())
+            .position(|(a, e)| a != e)
+            .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+        panic!(
+            "{} diverges from {} at command {}:\n  expected: {}\n  actual:   {}\n\
+             Rerun with UPDATE_GOLDEN=1 if the change is intended.",
+            name,
+            golden.display(),
+            first_diff + 1,
+            expected.lines().nth(first_diff).unwrap_or("<end>"),
+            actual.lines().nth(first_diff).unwrap_or("<end>")
+        );
+    }
+}
+
+#[test]
+fn standard_encrypted_matches_golden() {
+    assert_golden("standard_encrypted");
+}
+
+#[test]
+fn lvm_thin_matches_golden() {
+    assert_golden("lvm_thin");
+}
+
+#[test]
+fn lvm_mirror_matches_golden() {
+    assert_golden("lvm_mirror");
+}
+
+#[test]
+fn custom_partitions_match_golden() {
+    assert_golden("custom");
+}
+
+#[test]
+fn btrfs_mirror_matches_golden() {
+    assert_golden("btrfs_mirror");
+}
+
+#[test]
+fn zfs_matches_golden() {
+    assert_golden("zfs");
+}
+
+#[test]
+fn dual_boot_matches_golden() {
+    assert_golden("dual_boot");
+}